@@ -48,6 +48,7 @@ pub struct AnsibleTask {
     pub loop_expr: Option<serde_yaml::Value>,
     pub with_items: Option<serde_yaml::Value>,
     pub with_dict: Option<serde_yaml::Value>,
+    pub with_nested: Option<serde_yaml::Value>,
     pub tags: Option<serde_yaml::Value>,
     #[serde(rename = "become")]
     pub r#become: Option<bool>,
@@ -72,7 +72,6 @@ impl ExpressionConverter {
         variable_map.insert("ansible_distribution", "host.distribution");
         variable_map.insert("ansible_architecture", "host.arch");
         variable_map.insert("inventory_hostname", "host.name");
-        variable_map.insert("inventory_hostname_short", "host.short_name");
         variable_map.insert("ansible_user", "host.user");
         variable_map.insert("ansible_host", "host.address");
         variable_map.insert("ansible_port", "host.port");
@@ -178,10 +177,10 @@ impl ExpressionConverter {
             return format!("groups.{}", &caps[1]);
         }
 
-        // Handle hostvars[host]['var'] → hosts[host].var
+        // Handle hostvars[host]['var'] → hostvars[host].var
         let hostvars_re = Regex::new(r"hostvars\[(.+?)\]\['(\w+)'\]").unwrap();
         if let Some(caps) = hostvars_re.captures(var) {
-            return format!("hosts[{}].{}", &caps[1], &caps[2]);
+            return format!("hostvars[{}].{}", &caps[1], &caps[2]);
         }
 
         var.to_string()
@@ -337,6 +337,8 @@ impl Converter {
             "template",
             "file",
             "stat",
+            "hostname",
+            "timezone",
             "lineinfile",
             "blockinfile",
             "user",
@@ -456,6 +458,49 @@ impl Converter {
                     .to_string(),
             };
             output.push_str(&format!("    loop: {}\n", items_str));
+        } else if let Some(with_dict) = &task.with_dict {
+            let items_str = match with_dict {
+                serde_yaml::Value::String(s) => {
+                    let inner = s.trim();
+                    let inner = inner
+                        .strip_prefix("{{")
+                        .and_then(|e| e.strip_suffix("}}"))
+                        .unwrap_or(inner);
+                    self.expression_converter
+                        .convert_string(&format!("{{{{ {} | dict2items }}}}", inner.trim()))
+                        .output
+                }
+                // Expand a literal dict here; JSON keeps it on one line
+                serde_yaml::Value::Mapping(map) => {
+                    let items: Vec<serde_json::Value> = map
+                        .iter()
+                        .map(|(k, v)| {
+                            serde_json::json!({
+                                "key": serde_json::to_value(k).unwrap_or_default(),
+                                "value": serde_json::to_value(v).unwrap_or_default(),
+                            })
+                        })
+                        .collect();
+                    serde_json::to_string(&items).unwrap_or_default()
+                }
+                other => serde_yaml::to_string(other)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+            output.push_str(&format!("    loop: {}\n", items_str));
+        } else if let Some(serde_yaml::Value::Sequence(lists)) = &task.with_nested {
+            output.push_str("    loop_nested:\n");
+            for list in lists {
+                let list_str = match list {
+                    serde_yaml::Value::String(s) => {
+                        format!("\"{}\"", self.expression_converter.convert_string(s).output)
+                    }
+                    // JSON is valid YAML flow syntax, keeping each list on one line
+                    other => serde_json::to_string(other).unwrap_or_default(),
+                };
+                output.push_str(&format!("      - {}\n", list_str));
+            }
         }
 
         // Tags
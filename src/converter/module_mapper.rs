@@ -1,6 +1,8 @@
 use serde_yaml::Value;
 use std::collections::HashMap;
 
+use super::expression::ExpressionConverter;
+
 /// Maps Ansible modules to Nexus smart actions
 pub struct ModuleMapper {
     mappings: HashMap<&'static str, ModuleMapping>,
@@ -104,11 +106,27 @@ impl ModuleMapper {
         mappings.insert(
             "stat",
             ModuleMapping {
-                nexus_module: "file",
-                nexus_action: Some("stat"),
+                nexus_module: "stat",
+                nexus_action: None,
                 arg_converter: convert_stat_module,
             },
         );
+        mappings.insert(
+            "hostname",
+            ModuleMapping {
+                nexus_module: "hostname",
+                nexus_action: None,
+                arg_converter: convert_hostname_module,
+            },
+        );
+        mappings.insert(
+            "timezone",
+            ModuleMapping {
+                nexus_module: "timezone",
+                nexus_action: None,
+                arg_converter: convert_timezone_module,
+            },
+        );
         mappings.insert(
             "lineinfile",
             ModuleMapping {
@@ -202,7 +220,7 @@ impl ModuleMapper {
         mappings.insert(
             "debug",
             ModuleMapping {
-                nexus_module: "log",
+                nexus_module: "debug",
                 nexus_action: None,
                 arg_converter: convert_debug_module,
             },
@@ -228,7 +246,7 @@ impl ModuleMapper {
         mappings.insert(
             "set_fact",
             ModuleMapping {
-                nexus_module: "set",
+                nexus_module: "set_fact",
                 nexus_action: None,
                 arg_converter: convert_set_fact_module,
             },
@@ -528,13 +546,50 @@ fn convert_file_module(args: &Value) -> Result<ModuleConversionResult, String> {
 fn convert_stat_module(args: &Value) -> Result<ModuleConversionResult, String> {
     let path = get_str(args, "path").ok_or("Missing 'path' in stat module")?;
 
+    // Ansible checksums by default; nexus only when asked, so keep an explicit request
+    let mut additional_lines = vec![];
+    if get_bool(args, "get_checksum") == Some(true) {
+        additional_lines.push("get_checksum: true".to_string());
+    }
+
     Ok(ModuleConversionResult {
-        action_line: format!("file: stat {}", path),
-        additional_lines: vec![],
+        action_line: format!("stat: {}", path),
+        additional_lines,
         warnings: vec![],
     })
 }
 
+fn convert_hostname_module(args: &Value) -> Result<ModuleConversionResult, String> {
+    let name = get_str(args, "name").ok_or("Missing 'name' in hostname module")?;
+
+    // nexus picks hostnamectl or /etc/hostname itself
+    let mut warnings = vec![];
+    if args.get("use").is_some() {
+        warnings.push("hostname 'use' is not supported; the strategy is detected".to_string());
+    }
+
+    Ok(ModuleConversionResult {
+        action_line: format!("hostname: {}", name),
+        additional_lines: vec![],
+        warnings,
+    })
+}
+
+fn convert_timezone_module(args: &Value) -> Result<ModuleConversionResult, String> {
+    let name = get_str(args, "name").ok_or("Missing 'name' in timezone module")?;
+
+    let mut warnings = vec![];
+    if args.get("hwclock").is_some() {
+        warnings.push("timezone 'hwclock' is not supported".to_string());
+    }
+
+    Ok(ModuleConversionResult {
+        action_line: format!("timezone: {}", name),
+        additional_lines: vec![],
+        warnings,
+    })
+}
+
 fn convert_lineinfile_module(args: &Value) -> Result<ModuleConversionResult, String> {
     let path = get_str(args, "path")
         .or_else(|| get_str(args, "dest"))
@@ -792,25 +847,26 @@ fn convert_uri_module(args: &Value) -> Result<ModuleConversionResult, String> {
 }
 
 fn convert_debug_module(args: &Value) -> Result<ModuleConversionResult, String> {
+    let mut params = Vec::new();
     if let Some(msg) = get_str(args, "msg") {
-        Ok(ModuleConversionResult {
-            action_line: format!("log: \"{}\"", msg),
-            additional_lines: vec![],
-            warnings: vec![],
-        })
+        params.push(format!("msg: \"{}\"", msg));
     } else if let Some(var) = get_str(args, "var") {
-        Ok(ModuleConversionResult {
-            action_line: format!("log: ${{{}}}", var),
-            additional_lines: vec![],
-            warnings: vec![],
-        })
-    } else {
-        Ok(ModuleConversionResult {
-            action_line: "log: debug".to_string(),
-            additional_lines: vec![],
-            warnings: vec![],
-        })
+        params.push(format!("var: {}", var));
     }
+    if let Some(verbosity) = args.get("verbosity").and_then(|v| v.as_u64()) {
+        params.push(format!("verbosity: {}", verbosity));
+    }
+
+    let action_line = match params.as_slice() {
+        [msg] if msg.starts_with("msg: ") => format!("debug: {}", &msg["msg: ".len()..]),
+        _ => format!("debug: {{{}}}", params.join(", ")),
+    };
+
+    Ok(ModuleConversionResult {
+        action_line,
+        additional_lines: vec![],
+        warnings: vec![],
+    })
 }
 
 fn convert_fail_module(args: &Value) -> Result<ModuleConversionResult, String> {
@@ -824,56 +880,101 @@ fn convert_fail_module(args: &Value) -> Result<ModuleConversionResult, String> {
 }
 
 fn convert_assert_module(args: &Value) -> Result<ModuleConversionResult, String> {
-    // TODO: Handle 'that' array of conditions
-    let msg = get_str(args, "fail_msg").or_else(|| get_str(args, "msg"));
+    let conditions: Vec<String> = match args.get("that") {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(s)) => vec![s.clone()],
+        _ => vec![],
+    };
+    if conditions.is_empty() {
+        return Err("Missing 'that' in assert module".to_string());
+    }
 
-    let warnings = vec!["Assert conditions need manual review".to_string()];
+    // Bare Jinja2 conditions, like `when:`; wrap whole expressions in ${...}
+    let converter = ExpressionConverter::new();
+    let mut warnings = Vec::new();
+    let mut additional_lines = vec!["  that:".to_string()];
+    for condition in &conditions {
+        let converted = converter.convert_condition(condition);
+        warnings.extend(converted.warnings);
+        let output = converted.output;
+        let expr = if !output.contains("${") {
+            format!("${{{}}}", output)
+        } else {
+            let whole = output.starts_with("${")
+                && output.ends_with('}')
+                && output.matches("${").count() == 1;
+            if !whole {
+                warnings.push(format!("Assert condition needs manual review: {}", output));
+            }
+            output
+        };
+        additional_lines.push(format!("    - {:?}", expr));
+    }
 
-    let action_line = if let Some(msg) = msg {
-        format!("assert: true --msg \"{}\"  # TODO: convert conditions", msg)
-    } else {
-        "assert: true  # TODO: convert conditions".to_string()
-    };
+    let fail_msg = get_str(args, "fail_msg").or_else(|| get_str(args, "msg"));
+    for (key, msg) in [
+        ("fail_msg", fail_msg),
+        ("success_msg", get_str(args, "success_msg")),
+    ] {
+        if let Some(msg) = msg {
+            additional_lines.push(format!(
+                "  {}: {:?}",
+                key,
+                converter.convert_string(&msg).output
+            ));
+        }
+    }
+    if get_bool(args, "quiet") == Some(true) {
+        additional_lines.push("  quiet: true".to_string());
+    }
 
     Ok(ModuleConversionResult {
-        action_line,
-        additional_lines: vec![],
+        action_line: "assert:".to_string(),
+        additional_lines,
         warnings,
     })
 }
 
 fn convert_set_fact_module(args: &Value) -> Result<ModuleConversionResult, String> {
-    let mut lines = Vec::new();
-
-    if let Some(map) = args.as_mapping() {
-        for (key, value) in map {
-            if let Some(key_str) = key.as_str() {
-                let value_str = match value {
-                    Value::String(s) => s.clone(),
-                    _ => serde_yaml::to_string(value)
-                        .unwrap_or_default()
-                        .trim()
-                        .to_string(),
-                };
-                lines.push(format!("set: {} = {}", key_str, value_str));
-            }
+    let map = args
+        .as_mapping()
+        .ok_or_else(|| "set_fact needs a mapping of names to values".to_string())?;
+
+    let converter = ExpressionConverter::new();
+    let mut warnings = Vec::new();
+    let mut additional_lines = Vec::new();
+    for (key, value) in map {
+        let Some(name) = key.as_str() else {
+            continue;
+        };
+        if name == "cacheable" {
+            continue;
         }
+        let value_str = match value {
+            Value::String(s) => {
+                let converted = converter.convert_string(s);
+                warnings.extend(converted.warnings);
+                format!("{:?}", converted.output)
+            }
+            // Lists and mappings as flow YAML, which JSON is
+            other => serde_json::to_string(other).map_err(|e| e.to_string())?,
+        };
+        additional_lines.push(format!("  {}: {}", name, value_str));
+    }
+    if additional_lines.is_empty() {
+        return Err("set_fact has no facts to set".to_string());
+    }
+    if get_bool(args, "cacheable") == Some(true) {
+        additional_lines.push("  cacheable: true".to_string());
     }
-
-    let action_line = lines
-        .first()
-        .cloned()
-        .unwrap_or_else(|| "set: # TODO: convert".to_string());
-    let additional_lines = if lines.len() > 1 {
-        lines[1..].to_vec()
-    } else {
-        vec![]
-    };
 
     Ok(ModuleConversionResult {
-        action_line,
+        action_line: "set_fact:".to_string(),
         additional_lines,
-        warnings: vec![],
+        warnings,
     })
 }
 
@@ -971,7 +1072,25 @@ mod tests {
         let mapper = ModuleMapper::new();
         let args: Value = from_str("path: /etc/config.conf").unwrap();
         let result = mapper.convert("stat", &args).unwrap();
-        assert_eq!(result.action_line, "file: stat /etc/config.conf");
+        assert_eq!(result.action_line, "stat: /etc/config.conf");
+
+        let args: Value = from_str("path: /etc/config.conf\nget_checksum: true").unwrap();
+        let result = mapper.convert("stat", &args).unwrap();
+        assert_eq!(result.additional_lines, vec!["get_checksum: true"]);
+    }
+
+    #[test]
+    fn test_hostname_and_timezone_modules() {
+        let mapper = ModuleMapper::new();
+        let args: Value = from_str("name: web01.example.com").unwrap();
+        let result = mapper.convert("hostname", &args).unwrap();
+        assert_eq!(result.action_line, "hostname: web01.example.com");
+        assert!(result.warnings.is_empty());
+
+        let args: Value = from_str("name: Europe/Berlin\nhwclock: UTC").unwrap();
+        let result = mapper.convert("timezone", &args).unwrap();
+        assert_eq!(result.action_line, "timezone: Europe/Berlin");
+        assert_eq!(result.warnings.len(), 1);
     }
 
     #[test]
@@ -982,4 +1101,58 @@ mod tests {
         assert!(result.action_line.contains("file: mkdir /opt/app"));
         assert!(result.action_line.contains("--owner app"));
     }
+
+    #[test]
+    fn test_debug_module() {
+        let mapper = ModuleMapper::new();
+        let args: Value = from_str("msg: Deployed").unwrap();
+        let result = mapper.convert("debug", &args).unwrap();
+        assert_eq!(result.action_line, "debug: \"Deployed\"");
+
+        let args: Value = from_str("var: result.stdout\nverbosity: 2").unwrap();
+        let result = mapper.convert("debug", &args).unwrap();
+        assert_eq!(
+            result.action_line,
+            "debug: {var: result.stdout, verbosity: 2}"
+        );
+    }
+
+    #[test]
+    fn test_assert_module() {
+        let mapper = ModuleMapper::new();
+        let args: Value = from_str(
+            "that:\n  - port > 0\n  - env is defined\nfail_msg: \"bad port {{ port }}\"\nquiet: true",
+        )
+        .unwrap();
+        let result = mapper.convert("assert", &args).unwrap();
+        assert_eq!(result.action_line, "assert:");
+        assert_eq!(
+            result.additional_lines,
+            vec![
+                "  that:",
+                "    - \"${port > 0}\"",
+                "    - \"${env != null}\"",
+                "  fail_msg: \"bad port ${port}\"",
+                "  quiet: true",
+            ]
+        );
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_set_fact_module() {
+        let mapper = ModuleMapper::new();
+        let args: Value =
+            from_str("release: \"{{ version }}\"\nports: [80, 443]\ncacheable: yes").unwrap();
+        let result = mapper.convert("set_fact", &args).unwrap();
+        assert_eq!(result.action_line, "set_fact:");
+        assert_eq!(
+            result.additional_lines,
+            vec![
+                "  release: \"${version}\"",
+                "  ports: [80,443]",
+                "  cacheable: true",
+            ]
+        );
+    }
 }
@@ -8,7 +8,8 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use super::context::TaskOutput;
-use super::ssh::SshConnection;
+use super::Connection;
+use crate::modules::shell_quote;
 use crate::output::errors::NexusError;
 
 /// Unique identifier for async jobs
@@ -49,6 +50,41 @@ struct AsyncJobInfo {
     timeout: Duration,
 }
 
+/// Files on the host that record a job's PID, output and exit code
+struct JobFiles {
+    pid: String,
+    out: String,
+    err: String,
+    exit: String,
+}
+
+impl JobFiles {
+    fn new(job_id: &str) -> Self {
+        let base = format!("/tmp/.nexus_async_{}", job_id);
+        JobFiles {
+            out: format!("{}.out", base),
+            err: format!("{}.err", base),
+            exit: format!("{}.exit", base),
+            pid: base,
+        }
+    }
+
+    /// Shell command that starts `command` detached from the connection and
+    /// prints its PID. The exit code is written once the command finishes.
+    fn background_command(&self, command: &str) -> String {
+        let script = format!(
+            "({}) > {} 2> {}; echo $? > {}",
+            command, self.out, self.err, self.exit
+        );
+        format!(
+            "nohup sh -c {} > /dev/null 2>&1 < /dev/null & echo $! > {} && cat {}",
+            shell_quote(&script),
+            self.pid,
+            self.pid
+        )
+    }
+}
+
 impl AsyncJobTracker {
     pub fn new() -> Self {
         AsyncJobTracker {
@@ -56,45 +92,29 @@ impl AsyncJobTracker {
         }
     }
 
-    /// Start an async job on a remote host
+    /// Start `command` in the background on the host and return its job id
     pub async fn start_job(
         &self,
-        conn: &SshConnection,
+        conn: &dyn Connection,
         command: &str,
         timeout: u64,
     ) -> Result<JobId, NexusError> {
         let job_id = generate_job_id();
         let host = conn.host_name().to_string();
+        let files = JobFiles::new(&job_id);
 
-        // Create the background job command using nohup
-        // The job file will contain the PID and be used to track status
-        let job_file = format!("/tmp/.nexus_async_{}", job_id);
-        let out_file = format!("/tmp/.nexus_async_{}.out", job_id);
-        let err_file = format!("/tmp/.nexus_async_{}.err", job_id);
-
-        // Start the command in background:
-        // 1. Redirect stdout/stderr to files
-        // 2. Run in background with nohup
-        // 3. Save PID to job file
-        // 4. Return immediately
-        let bg_command = format!(
-            r#"nohup sh -c '({}) > {} 2> {} & echo $! > {} && echo "started:$!" > {}.status'"#,
-            command, out_file, err_file, job_file, job_file
-        );
-
-        let result = conn.exec(&bg_command)?;
+        let result = conn.exec(&files.background_command(command)).await?;
 
         if !result.success() {
             return Err(NexusError::Runtime {
                 function: None,
                 message: format!("Failed to start async job: {}", result.stderr),
                 suggestion: Some("Check command syntax and permissions".to_string()),
+                location: None,
             });
         }
 
-        // Read the PID from the job file
-        let pid_result = conn.exec(&format!("cat {}", job_file))?;
-        let pid: i32 = pid_result
+        let pid: i32 = result
             .stdout
             .trim()
             .parse()
@@ -102,6 +122,7 @@ impl AsyncJobTracker {
                 function: None,
                 message: format!("Failed to parse PID: {}", e),
                 suggestion: None,
+                location: None,
             })?;
 
         // Track the job
@@ -125,85 +146,101 @@ impl AsyncJobTracker {
     /// Check the status of an async job
     pub async fn check_status(
         &self,
-        conn: &SshConnection,
+        conn: &dyn Connection,
         job_id: &str,
     ) -> Result<JobStatus, NexusError> {
-        let _host = conn.host_name();
-        let job_file = format!("/tmp/.nexus_async_{}", job_id);
-        let out_file = format!("/tmp/.nexus_async_{}.out", job_id);
-        let err_file = format!("/tmp/.nexus_async_{}.err", job_id);
+        let files = JobFiles::new(job_id);
 
         // Check if job file exists
-        let exists = conn.exec(&format!("test -f {}", job_file))?.success();
+        let exists = conn
+            .exec(&format!("test -f {}", files.pid))
+            .await?
+            .success();
         if !exists {
             return Ok(JobStatus::NotFound);
         }
 
+        // The exit code file only appears once the command has finished
+        let exit_result = conn
+            .exec(&format!("cat {} 2>/dev/null", files.exit))
+            .await?;
+        if let Ok(exit_code) = exit_result.stdout.trim().parse::<i32>() {
+            let stdout = conn
+                .exec(&format!("cat {} 2>/dev/null", files.out))
+                .await?
+                .stdout;
+            let stderr = conn
+                .exec(&format!("cat {} 2>/dev/null", files.err))
+                .await?
+                .stdout;
+
+            return Ok(JobStatus::Finished {
+                exit_code,
+                stdout,
+                stderr,
+            });
+        }
+
         // Read the PID
-        let pid_result = conn.exec(&format!("cat {}", job_file))?;
+        let pid_result = conn.exec(&format!("cat {}", files.pid)).await?;
         let pid: i32 = pid_result.stdout.trim().parse().unwrap_or(0);
 
         // Check if process is still running
-        let is_running = conn
-            .exec(&format!("kill -0 {} 2>/dev/null", pid))?
-            .success();
+        let is_running = pid > 0
+            && conn
+                .exec(&format!("kill -0 {} 2>/dev/null", pid))
+                .await?
+                .success();
 
         if is_running {
-            // Process still running
-            return Ok(JobStatus::Running {
-                pid,
-                started_at: chrono::Utc::now().to_rfc3339(),
-            });
+            let started_at = conn
+                .exec(&format!(
+                    "date -u -r {} +%Y-%m-%dT%H:%M:%SZ 2>/dev/null",
+                    files.pid
+                ))
+                .await?
+                .stdout
+                .trim()
+                .to_string();
+            return Ok(JobStatus::Running { pid, started_at });
         }
 
-        // Process finished - collect output
-        let stdout = conn
-            .exec(&format!("cat {} 2>/dev/null || echo ''", out_file))?
-            .stdout;
-        let stderr = conn
-            .exec(&format!("cat {} 2>/dev/null || echo ''", err_file))?
-            .stderr;
-
-        // Get exit code from status file if it exists
-        let exit_code_result =
-            conn.exec(&format!("cat {}.exit 2>/dev/null || echo 0", job_file))?;
-        let exit_code: i32 = exit_code_result.stdout.trim().parse().unwrap_or(0);
-
-        Ok(JobStatus::Finished {
-            exit_code,
-            stdout,
-            stderr,
+        // Gone without recording an exit code, e.g. killed or the host rebooted
+        Ok(JobStatus::Failed {
+            error: format!("Async job {} exited without reporting a status", job_id),
         })
     }
 
-    /// Poll for job completion with retries
+    /// Check the job every `poll_interval` seconds until it finishes, killing
+    /// it once it has run for `timeout` seconds
     pub async fn poll_until_complete(
         &self,
-        conn: &SshConnection,
+        conn: &dyn Connection,
         job_id: &str,
         poll_interval: u64,
-        max_retries: u32,
+        timeout: u64,
     ) -> Result<TaskOutput, NexusError> {
         let poll_duration = Duration::from_secs(poll_interval);
-        let mut attempts = 0;
+        let deadline = Instant::now() + Duration::from_secs(timeout);
 
         loop {
             let status = self.check_status(conn, job_id).await?;
 
             match status {
                 JobStatus::Running { .. } => {
-                    attempts += 1;
-                    if attempts >= max_retries {
+                    if Instant::now() >= deadline {
                         // Timeout - kill the job
                         self.kill_job(conn, job_id).await?;
+                        self.cleanup_job(conn, job_id).await.ok();
                         return Ok(TaskOutput::failed(format!(
-                            "Async job timed out after {} polls",
-                            max_retries
+                            "Async job timed out after {}s",
+                            timeout
                         )));
                     }
 
-                    // Wait before next poll
-                    tokio::time::sleep(poll_duration).await;
+                    // Wait before next poll, but not past the deadline
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    tokio::time::sleep(poll_duration.min(remaining)).await;
                 }
                 JobStatus::Finished {
                     exit_code,
@@ -213,22 +250,18 @@ impl AsyncJobTracker {
                     // Cleanup job files
                     self.cleanup_job(conn, job_id).await.ok();
 
-                    if exit_code == 0 {
-                        return Ok(TaskOutput::changed()
-                            .with_stdout(stdout)
-                            .with_stderr(stderr));
+                    let mut output = if exit_code == 0 {
+                        TaskOutput::changed()
                     } else {
-                        let mut output = TaskOutput::failed(format!(
-                            "Async job failed with exit code {}",
-                            exit_code
-                        ));
-                        output.stdout = stdout;
-                        output.stderr = stderr;
-                        output.exit_code = exit_code;
-                        return Ok(output);
-                    }
+                        TaskOutput::failed(format!("Async job failed with exit code {}", exit_code))
+                    };
+                    output.stdout = stdout;
+                    output.stderr = stderr;
+                    output.exit_code = exit_code;
+                    return Ok(output);
                 }
                 JobStatus::Failed { error } => {
+                    self.cleanup_job(conn, job_id).await.ok();
                     return Ok(TaskOutput::failed(error));
                 }
                 JobStatus::TimedOut => {
@@ -242,28 +275,31 @@ impl AsyncJobTracker {
     }
 
     /// Kill a running async job
-    pub async fn kill_job(&self, conn: &SshConnection, job_id: &str) -> Result<(), NexusError> {
-        let job_file = format!("/tmp/.nexus_async_{}", job_id);
+    pub async fn kill_job(&self, conn: &dyn Connection, job_id: &str) -> Result<(), NexusError> {
+        let files = JobFiles::new(job_id);
 
         // Read PID
-        let pid_result = conn.exec(&format!("cat {} 2>/dev/null || echo 0", job_file))?;
+        let pid_result = conn
+            .exec(&format!("cat {} 2>/dev/null || echo 0", files.pid))
+            .await?;
         let pid: i32 = pid_result.stdout.trim().parse().unwrap_or(0);
 
         if pid > 0 {
-            // Kill the process and all children
+            // Kill the job's children, then the shell that runs them
             conn.exec(&format!(
-                "kill -TERM -{} 2>/dev/null || kill -TERM {} 2>/dev/null",
+                "pkill -TERM -P {} 2>/dev/null; kill -TERM {} 2>/dev/null",
                 pid, pid
-            ))?;
+            ))
+            .await?;
         }
 
         Ok(())
     }
 
     /// Cleanup job files from remote host
-    pub async fn cleanup_job(&self, conn: &SshConnection, job_id: &str) -> Result<(), NexusError> {
+    pub async fn cleanup_job(&self, conn: &dyn Connection, job_id: &str) -> Result<(), NexusError> {
         let pattern = format!("/tmp/.nexus_async_{}*", job_id);
-        conn.exec(&format!("rm -f {}", pattern))?;
+        conn.exec(&format!("rm -f {}", pattern)).await?;
 
         // Remove from tracker
         let host = conn.host_name().to_string();
@@ -294,6 +330,3 @@ fn generate_job_id() -> String {
     let random: u32 = rand::random();
     format!("{:x}_{:x}", now, random)
 }
-
-// Add rand dependency if not already present
-extern crate rand;
@@ -129,6 +129,7 @@ impl Checkpoint {
                     CHECKPOINT_VERSION, self.version
                 ),
                 suggestion: Some("Delete the checkpoint and run from the beginning".to_string()),
+                location: None,
             });
         }
 
@@ -139,6 +140,7 @@ impl Checkpoint {
                 function: None,
                 message: "Playbook has been modified since checkpoint was created".to_string(),
                 suggestion: Some("Delete the checkpoint and run from the beginning, or use --force-resume to ignore this warning".to_string()),
+                location: None,
             });
         }
 
@@ -152,6 +154,7 @@ impl Checkpoint {
                     inventory_path.display()
                 ),
                 suggestion: Some("Use the same inventory file as the checkpoint".to_string()),
+                location: None,
             });
         }
 
@@ -219,6 +222,7 @@ impl CheckpointManager {
             function: None,
             message: format!("Failed to serialize checkpoint: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         fs::write(&path, json).map_err(|e| NexusError::Io {
@@ -241,6 +245,7 @@ impl CheckpointManager {
                 function: None,
                 message: format!("Failed to parse checkpoint: {}", e),
                 suggestion: Some("The checkpoint file may be corrupted".to_string()),
+                location: None,
             })?;
 
         Ok(checkpoint)
@@ -312,7 +317,7 @@ impl CheckpointManager {
         }
 
         // Sort by timestamp (newest first)
-        checkpoints.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        checkpoints.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
 
         Ok(checkpoints)
     }
@@ -3,10 +3,14 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use dashmap::DashMap;
 use parking_lot::RwLock;
 
 use crate::inventory::Host;
-use crate::parser::ast::Value;
+use crate::output::diff::Diff;
+use crate::output::events::EventEmitter;
+use crate::parser::ast::{Expression, Value};
+use crate::runtime::evaluate_expression;
 
 /// Context for task execution on a specific host
 #[derive(Debug, Clone)]
@@ -21,6 +25,8 @@ pub struct ExecutionContext {
     pub check_mode: bool,
     /// Whether to show diffs for file changes
     pub diff_mode: bool,
+    /// Number of `-v` flags given to the run
+    pub verbosity: u8,
     /// Current loop item (if in a loop)
     pub loop_item: Option<Value>,
     /// Current loop index (if in a loop)
@@ -29,6 +35,15 @@ pub struct ExecutionContext {
     pub sudo: bool,
     /// User to run commands as (via sudo -u)
     pub sudo_user: Option<String>,
+    /// Play-level environment for command and shell tasks
+    pub environment: HashMap<String, Expression>,
+    /// Where command and shell tasks stream their output lines (TUI mode)
+    pub output_stream: Option<EventEmitter>,
+    /// Every play host's variables, read through `hostvars`
+    hostvars: Option<HostVars>,
+    /// `vars:` of the task and of its block, over play, role and host vars.
+    /// Evaluated when read, so they can refer to `item`.
+    pub scoped_vars: HashMap<String, Expression>,
 }
 
 impl ExecutionContext {
@@ -46,6 +61,23 @@ impl ExecutionContext {
             "inventory_hostname".to_string(),
             Value::String(host.name.clone()),
         );
+        let short_name = host.name.split('.').next().unwrap_or_default();
+        vars.insert(
+            "inventory_hostname_short".to_string(),
+            Value::String(short_name.to_string()),
+        );
+        let mut group_names: Vec<&String> = host.groups.iter().collect();
+        group_names.sort();
+        group_names.dedup();
+        vars.insert(
+            "group_names".to_string(),
+            Value::List(
+                group_names
+                    .into_iter()
+                    .map(|g| Value::String(g.clone()))
+                    .collect(),
+            ),
+        );
 
         ExecutionContext {
             host,
@@ -53,10 +85,15 @@ impl ExecutionContext {
             registered: Arc::new(RwLock::new(HashMap::new())),
             check_mode: false,
             diff_mode: false,
+            verbosity: 0,
             loop_item: None,
             loop_index: None,
             sudo: false,
             sudo_user: None,
+            environment: HashMap::new(),
+            output_stream: None,
+            hostvars: None,
+            scoped_vars: HashMap::new(),
         }
     }
 
@@ -66,6 +103,12 @@ impl ExecutionContext {
         self
     }
 
+    /// Play-level environment for command and shell tasks
+    pub fn with_environment(mut self, environment: HashMap<String, Expression>) -> Self {
+        self.environment = environment;
+        self
+    }
+
     pub fn with_check_mode(mut self, check: bool) -> Self {
         self.check_mode = check;
         self
@@ -76,6 +119,34 @@ impl ExecutionContext {
         self
     }
 
+    pub fn with_verbosity(mut self, verbosity: u8) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Stream command output lines to `emitter` as they arrive
+    pub fn with_output_stream(mut self, emitter: Option<EventEmitter>) -> Self {
+        self.output_stream = emitter;
+        self
+    }
+
+    /// Add this context to `hostvars`, replacing the host's entry, and read the
+    /// other hosts' variables from it
+    pub fn with_hostvars(mut self, hostvars: HostVars) -> Self {
+        hostvars.hosts.insert(
+            self.host.name.clone(),
+            (self.vars.clone(), self.registered.clone()),
+        );
+        self.hostvars = Some(hostvars);
+        self
+    }
+
+    /// Add `vars:` of a block or task, over those already scoped
+    pub fn with_scoped_vars(mut self, vars: HashMap<String, Expression>) -> Self {
+        self.scoped_vars.extend(vars);
+        self
+    }
+
     pub fn with_loop_item(mut self, item: Value, index: usize) -> Self {
         self.loop_item = Some(item.clone());
         self.loop_index = Some(index);
@@ -92,12 +163,24 @@ impl ExecutionContext {
         if name == "item" {
             return self.loop_item.clone();
         }
+        if name == "hostvars" {
+            if let Some(ref hostvars) = self.hostvars {
+                return Some(hostvars.to_value());
+            }
+        }
 
         // Check registered results
         if let Some(output) = self.registered.read().get(name) {
             return Some(output.to_value());
         }
 
+        if let Some(expr) = self.scoped_vars.get(name) {
+            // Evaluated without itself, so `pkg: "${pkg}-dev"` reads the outer `pkg`
+            let mut outer = self.clone();
+            outer.scoped_vars.remove(name);
+            return evaluate_expression(expr, &outer).ok();
+        }
+
         // Check regular vars
         self.vars.read().get(name).cloned()
     }
@@ -150,6 +233,11 @@ impl ExecutionContext {
         self.vars.write().insert(name.into(), value);
     }
 
+    /// Remove a variable, returning its previous value
+    pub fn remove_var(&self, name: &str) -> Option<Value> {
+        self.vars.write().remove(name)
+    }
+
     /// Register task output
     pub fn register(&self, name: impl Into<String>, output: TaskOutput) {
         self.registered.write().insert(name.into(), output);
@@ -173,10 +261,15 @@ impl ExecutionContext {
             registered: self.registered.clone(),
             check_mode: self.check_mode,
             diff_mode: self.diff_mode,
+            verbosity: self.verbosity,
             loop_item: self.loop_item.clone(),
             loop_index: self.loop_index,
             sudo: self.sudo,
             sudo_user: self.sudo_user.clone(),
+            environment: self.environment.clone(),
+            output_stream: self.output_stream.clone(),
+            hostvars: self.hostvars.clone(),
+            scoped_vars: self.scoped_vars.clone(),
         }
     }
 
@@ -194,12 +287,70 @@ impl ExecutionContext {
     }
 }
 
+/// The variables of every host in the play, shared by their contexts so that
+/// `hostvars['db1'].ansible_hostname` sees facts and `set_fact` values as they change
+#[derive(Debug, Clone, Default)]
+pub struct HostVars {
+    #[allow(clippy::type_complexity)]
+    hosts: Arc<
+        DashMap<
+            String,
+            (
+                Arc<RwLock<HashMap<String, Value>>>,
+                Arc<RwLock<HashMap<String, TaskOutput>>>,
+            ),
+        >,
+    >,
+}
+
+impl HostVars {
+    pub fn new() -> Self {
+        HostVars::default()
+    }
+
+    /// Set `host`'s variables until a context for it is added with `with_hostvars`
+    pub fn insert(&self, host: impl Into<String>, vars: HashMap<String, Value>) {
+        self.hosts.insert(
+            host.into(),
+            (
+                Arc::new(RwLock::new(vars)),
+                Arc::new(RwLock::new(HashMap::new())),
+            ),
+        );
+    }
+
+    /// Forget every host, e.g. at the start of a play
+    pub fn clear(&self) {
+        self.hosts.clear();
+    }
+
+    /// A dict of host name to that host's variables and registered results
+    pub fn to_value(&self) -> Value {
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|entry| {
+                let (vars, registered) = entry.value();
+                let mut vars = vars.read().clone();
+                for (name, output) in registered.read().iter() {
+                    vars.insert(name.clone(), output.to_value());
+                }
+                (entry.key().clone(), Value::Dict(vars))
+            })
+            .collect();
+        Value::Dict(hosts)
+    }
+}
+
 /// Escape a command for use in sh -c
 fn shell_escape(cmd: &str) -> String {
     // Wrap in single quotes and escape any existing single quotes
     format!("'{}'", cmd.replace('\'', "'\"'\"'"))
 }
 
+/// Reported in place of the output of `no_log` tasks
+pub const NO_LOG_MESSAGE: &str = "the output has been hidden due to no_log";
+
 /// Output from a task execution
 #[derive(Debug, Clone, Default)]
 pub struct TaskOutput {
@@ -212,7 +363,7 @@ pub struct TaskOutput {
     pub message: Option<String>,
     pub data: HashMap<String, Value>,
     /// Diff output for file changes (when diff_mode is enabled)
-    pub diff: Option<String>,
+    pub diff: Option<Diff>,
 }
 
 impl TaskOutput {
@@ -266,11 +417,31 @@ impl TaskOutput {
         self
     }
 
-    pub fn with_diff(mut self, diff: impl Into<String>) -> Self {
-        self.diff = Some(diff.into());
+    pub fn with_diff(mut self, diff: Diff) -> Self {
+        self.diff = Some(diff);
         self
     }
 
+    /// Copy for reporting a `no_log` task, with everything that could echo a secret
+    /// replaced. Status and exit code are kept.
+    pub fn censored(&self) -> TaskOutput {
+        let hide = |text: &str| {
+            if text.is_empty() {
+                String::new()
+            } else {
+                NO_LOG_MESSAGE.to_string()
+            }
+        };
+        TaskOutput {
+            stdout: hide(&self.stdout),
+            stderr: hide(&self.stderr),
+            message: self.message.as_ref().map(|_| NO_LOG_MESSAGE.to_string()),
+            data: HashMap::new(),
+            diff: self.diff.as_ref().map(|_| Diff::hidden(NO_LOG_MESSAGE)),
+            ..self.clone()
+        }
+    }
+
     /// Convert to a Value for use in expressions
     pub fn to_value(&self) -> Value {
         let mut map = HashMap::new();
@@ -278,6 +449,7 @@ impl TaskOutput {
         map.insert("stdout".to_string(), Value::String(self.stdout.clone()));
         map.insert("stderr".to_string(), Value::String(self.stderr.clone()));
         map.insert("rc".to_string(), Value::Int(self.exit_code as i64));
+        map.insert("exit_code".to_string(), Value::Int(self.exit_code as i64));
         map.insert("changed".to_string(), Value::Bool(self.changed));
         map.insert("failed".to_string(), Value::Bool(self.failed));
         map.insert("skipped".to_string(), Value::Bool(self.skipped));
@@ -286,13 +458,11 @@ impl TaskOutput {
             map.insert("msg".to_string(), Value::String(msg.clone()));
         }
 
-        // Add stdout lines
-        let lines: Vec<Value> = self
-            .stdout
-            .lines()
-            .map(|l| Value::String(l.to_string()))
-            .collect();
-        map.insert("stdout_lines".to_string(), Value::List(lines));
+        // Add stdout and stderr split into lines
+        let lines =
+            |text: &str| Value::List(text.lines().map(|l| Value::String(l.to_string())).collect());
+        map.insert("stdout_lines".to_string(), lines(&self.stdout));
+        map.insert("stderr_lines".to_string(), lines(&self.stderr));
 
         // Add custom data
         for (k, v) in &self.data {
@@ -379,4 +549,76 @@ mod tests {
             panic!("Expected Dict");
         }
     }
+
+    #[test]
+    fn test_registered_result_fields() {
+        let ctx = create_test_context();
+
+        let mut output = TaskOutput::failed("exit 2")
+            .with_stdout("one\ntwo\n")
+            .with_stderr("warning: disk\n");
+        output.exit_code = 2;
+        ctx.register("result", output);
+
+        let Some(Value::Dict(map)) = ctx.get_var("result") else {
+            panic!("Expected Dict");
+        };
+        let strings = |items: &[&str]| {
+            Value::List(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+        assert_eq!(map.get("rc"), Some(&Value::Int(2)));
+        assert_eq!(map.get("exit_code"), Some(&Value::Int(2)));
+        assert_eq!(map.get("stdout_lines"), Some(&strings(&["one", "two"])));
+        assert_eq!(map.get("stderr_lines"), Some(&strings(&["warning: disk"])));
+        assert_eq!(map.get("failed"), Some(&Value::Bool(true)));
+        assert_eq!(map.get("changed"), Some(&Value::Bool(false)));
+        assert_eq!(map.get("skipped"), Some(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_magic_vars_and_hostvars() {
+        let mut web = Host::new("web1.example.com");
+        web.groups = vec!["web".to_string(), "app".to_string()];
+        let ctx = ExecutionContext::new(Arc::new(web), HashMap::new());
+        assert_eq!(
+            ctx.get_var("inventory_hostname_short"),
+            Some(Value::String("web1".to_string()))
+        );
+        assert_eq!(
+            ctx.get_var("group_names"),
+            Some(Value::List(vec![
+                Value::String("app".to_string()),
+                Value::String("web".to_string()),
+            ]))
+        );
+        assert!(ctx.get_var("hostvars").is_none());
+
+        let hostvars = HostVars::new();
+        let mut db_vars = HashMap::new();
+        db_vars.insert("port".to_string(), Value::Int(5432));
+        hostvars.insert("db1", db_vars);
+        let ctx = ctx.with_hostvars(hostvars.clone());
+        let db = ExecutionContext::new(Arc::new(Host::new("db1")), HashMap::new())
+            .with_hostvars(hostvars);
+
+        // Later changes on db1 are visible from web1
+        db.set_var("ansible_hostname", Value::String("db1".to_string()));
+        db.register("ping", TaskOutput::success().with_stdout("pong"));
+        let path = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            ctx.get_nested_var(&path(&["hostvars", "db1", "ansible_hostname"])),
+            Some(Value::String("db1".to_string()))
+        );
+        assert_eq!(
+            ctx.get_nested_var(&path(&["hostvars", "db1", "ping", "stdout"])),
+            Some(Value::String("pong".to_string()))
+        );
+        // db1's context replaced the entry it started with
+        assert!(ctx
+            .get_nested_var(&path(&["hostvars", "db1", "port"]))
+            .is_none());
+        assert!(ctx
+            .get_nested_var(&path(&["hostvars", "web1.example.com"]))
+            .is_some());
+    }
 }
@@ -182,6 +182,9 @@ mod tests {
                 cmd: crate::parser::ast::Expression::String("echo test".to_string()),
                 creates: None,
                 removes: None,
+                environment: HashMap::new(),
+                stdin: None,
+                stdin_add_newline: true,
             },
             ..Default::default()
         }
@@ -0,0 +1,168 @@
+// Task debugger - an interactive prompt when a task fails (--debug-on-failure)
+
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::executor::ExecutionContext;
+use crate::output::OutputWriter;
+use crate::parser::expressions::parse_expression;
+use crate::runtime::evaluate_expression;
+
+const HELP: &str = concat!(
+    "  p <expr>  print a variable or expression, e.g. p result.stdout\n",
+    "  r         retry the task on this host\n",
+    "  c         continue, keeping the failure\n",
+    "  q         abort the run",
+);
+
+/// What to do with a failed task after the debugger prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Run the task on the host again
+    Retry,
+    /// Keep the failure and go on as usual
+    Continue,
+    /// Stop the run
+    Abort,
+}
+
+/// Prompts on the terminal when a task fails. Hosts run in parallel, so prompts
+/// are taken one at a time, with the progress bar hidden.
+pub struct TaskDebugger {
+    output: Arc<Mutex<OutputWriter>>,
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl TaskDebugger {
+    pub fn new(output: Arc<Mutex<OutputWriter>>) -> Self {
+        TaskDebugger {
+            output,
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Show why `task_name` failed on `host` and ask what to do until the user retries,
+    /// continues or aborts. `p` evaluates against the host's context.
+    pub async fn prompt(
+        &self,
+        task_name: &str,
+        host: &str,
+        error: &str,
+        ctx: &ExecutionContext,
+    ) -> DebugAction {
+        let _guard = self.lock.lock().await;
+        let (task_name, host, error, ctx) = (
+            task_name.to_string(),
+            host.to_string(),
+            error.to_string(),
+            ctx.clone(),
+        );
+        let output = self.output.clone();
+        tokio::task::spawn_blocking(move || {
+            output.lock().suspend_progress(|| {
+                let stdin = std::io::stdin();
+                let mut stdout = std::io::stdout();
+                run_session(
+                    &task_name,
+                    &host,
+                    &error,
+                    &ctx,
+                    &mut stdin.lock(),
+                    &mut stdout,
+                )
+            })
+        })
+        .await
+        .unwrap_or(DebugAction::Abort)
+    }
+}
+
+/// The prompt loop, reading commands from `input`. End of input aborts.
+fn run_session(
+    task_name: &str,
+    host: &str,
+    error: &str,
+    ctx: &ExecutionContext,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> DebugAction {
+    writeln!(out, "\n[{}] TASK: {} (debug)", host, task_name).ok();
+    writeln!(out, "  failed: {}", error).ok();
+    writeln!(out, "{}", HELP).ok();
+
+    loop {
+        write!(out, "[{}] debug> ", host).ok();
+        out.flush().ok();
+
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return DebugAction::Abort,
+            Ok(_) => {}
+        }
+
+        let line = line.trim();
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "r" | "retry" => return DebugAction::Retry,
+            "c" | "continue" => return DebugAction::Continue,
+            "q" | "quit" => return DebugAction::Abort,
+            "p" | "print" if !arg.trim().is_empty() => {
+                writeln!(out, "{}", print_expression(arg.trim(), ctx)).ok();
+            }
+            "" => {}
+            _ => {
+                writeln!(out, "{}", HELP).ok();
+            }
+        }
+    }
+}
+
+/// A variable or expression's value as pretty JSON, or why it couldn't be evaluated
+fn print_expression(expr: &str, ctx: &ExecutionContext) -> String {
+    let value = parse_expression(expr).and_then(|e| evaluate_expression(&e, ctx));
+    match value {
+        Ok(value) => serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string()),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Host;
+    use crate::parser::ast::Value;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_debug_session() {
+        let mut vars = HashMap::new();
+        vars.insert("port".to_string(), Value::Int(8080));
+        let ctx = ExecutionContext::new(Arc::new(Host::new("web1")), vars);
+
+        let session = |input: &str| {
+            let mut out = Vec::new();
+            let action = run_session(
+                "Start app",
+                "web1",
+                "exit code 1",
+                &ctx,
+                &mut input.as_bytes(),
+                &mut out,
+            );
+            (action, String::from_utf8(out).unwrap())
+        };
+
+        let (action, out) = session("p port\np missing\nr\n");
+        assert_eq!(action, DebugAction::Retry);
+        assert!(out.contains("failed: exit code 1"));
+        assert!(out.contains("debug> 8080\n"));
+        assert!(out.contains("error:"));
+
+        assert_eq!(session("\nhelp\nc\n").0, DebugAction::Continue);
+        assert_eq!(session("q\n").0, DebugAction::Abort);
+        assert_eq!(session("").0, DebugAction::Abort);
+    }
+}
@@ -0,0 +1,271 @@
+// Docker container execution via the docker CLI
+
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+use super::{CommandResult, Connection};
+use crate::inventory::Host;
+use crate::output::errors::NexusError;
+
+/// Connection that runs commands in a container with `docker exec` and moves
+/// files with `docker cp`
+pub struct DockerConnection {
+    host_name: String,
+    container: String,
+}
+
+impl DockerConnection {
+    /// Create a connection to `container` for the inventory host `host_name`
+    pub fn new(host_name: impl Into<String>, container: impl Into<String>) -> Self {
+        DockerConnection {
+            host_name: host_name.into(),
+            container: container.into(),
+        }
+    }
+
+    /// Connect to the container named by the host's `ansible_host` (its address)
+    pub fn for_host(host: &Host) -> Self {
+        Self::new(&host.name, &host.address)
+    }
+
+    /// Name or ID of the container commands run in
+    pub fn container(&self) -> &str {
+        &self.container
+    }
+
+    /// Arguments after `docker` that run `cmd` in the container through `sh -c`
+    fn exec_args<'a>(&'a self, cmd: &'a str) -> [&'a str; 6] {
+        ["exec", "-i", &self.container, "sh", "-c", cmd]
+    }
+
+    fn docker_error(&self, action: &str, e: impl std::fmt::Display) -> NexusError {
+        NexusError::Runtime {
+            function: None,
+            message: format!(
+                "Failed to {} in container {}: {}",
+                action, self.container, e
+            ),
+            suggestion: Some(
+                "Check that the docker CLI is installed and the container is running".to_string(),
+            ),
+            location: None,
+        }
+    }
+
+    /// A local scratch path for staging a `docker cp` transfer
+    fn staging_path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "nexus-docker-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ))
+    }
+
+    /// Run `docker cp from to`, failing with its stderr if the copy didn't succeed
+    async fn copy(&self, from: &str, to: &str, path: &str) -> Result<(), NexusError> {
+        let output = Command::new("docker")
+            .args(["cp", from, to])
+            .output()
+            .await
+            .map_err(|e| self.docker_error("copy a file", e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(NexusError::Io {
+                message: format!(
+                    "docker cp failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                path: Some(PathBuf::from(path)),
+            })
+        }
+    }
+}
+
+#[async_trait]
+impl Connection for DockerConnection {
+    async fn exec(&self, cmd: &str) -> Result<CommandResult, NexusError> {
+        let output = Command::new("docker")
+            .args(self.exec_args(cmd))
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| self.docker_error("execute command", e))?;
+
+        Ok(CommandResult {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code().unwrap_or(-1),
+        })
+    }
+
+    async fn exec_with_stdin(&self, cmd: &str, stdin: &str) -> Result<CommandResult, NexusError> {
+        let mut child = Command::new("docker")
+            .args(self.exec_args(cmd))
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.docker_error("execute command", e))?;
+
+        // Same as the local connection: write while collecting output so a full
+        // stdout pipe can't deadlock us
+        let input = child.stdin.take();
+        let write_input = async move {
+            if let Some(mut input) = input {
+                let _ = input.write_all(stdin.as_bytes()).await;
+            }
+        };
+        let ((), output) = tokio::join!(write_input, child.wait_with_output());
+        let output = output.map_err(|e| self.docker_error("execute command", e))?;
+
+        Ok(CommandResult {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code().unwrap_or(-1),
+        })
+    }
+
+    async fn exec_streaming(
+        &self,
+        cmd: &str,
+        on_stdout: Box<dyn Fn(String) + Send + Sync>,
+        on_stderr: Box<dyn Fn(String) + Send + Sync>,
+    ) -> Result<CommandResult, NexusError> {
+        let mut child = Command::new("docker")
+            .args(self.exec_args(cmd))
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.docker_error("execute command", e))?;
+
+        let stdout_handle = child
+            .stdout
+            .take()
+            .ok_or_else(|| self.docker_error("capture stdout", "no pipe"))?;
+        let stderr_handle = child
+            .stderr
+            .take()
+            .ok_or_else(|| self.docker_error("capture stderr", "no pipe"))?;
+
+        let stdout_task = tokio::spawn(async move {
+            let mut full = String::new();
+            let mut reader = BufReader::new(stdout_handle);
+            let mut line = String::new();
+            while let Ok(n) = reader.read_line(&mut line).await {
+                if n == 0 {
+                    break;
+                }
+                on_stdout(line.clone());
+                full.push_str(&line);
+                line.clear();
+            }
+            full
+        });
+        let stderr_task = tokio::spawn(async move {
+            let mut full = String::new();
+            let mut reader = BufReader::new(stderr_handle);
+            let mut line = String::new();
+            while let Ok(n) = reader.read_line(&mut line).await {
+                if n == 0 {
+                    break;
+                }
+                on_stderr(line.clone());
+                full.push_str(&line);
+                line.clear();
+            }
+            full
+        });
+
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| self.docker_error("wait for command", e))?;
+        let stdout = stdout_task
+            .await
+            .map_err(|e| self.docker_error("read stdout", e))?;
+        let stderr = stderr_task
+            .await
+            .map_err(|e| self.docker_error("read stderr", e))?;
+
+        Ok(CommandResult {
+            stdout,
+            stderr,
+            exit_code: status.code().unwrap_or(-1),
+        })
+    }
+
+    async fn read_file(&self, path: &str) -> Result<String, NexusError> {
+        let staged = self.staging_path();
+        let copied = self
+            .copy(
+                &format!("{}:{}", self.container, path),
+                &staged.to_string_lossy(),
+                path,
+            )
+            .await;
+        let content = match copied {
+            Ok(()) => tokio::fs::read_to_string(&staged)
+                .await
+                .map_err(|e| NexusError::Io {
+                    message: format!("Failed to read file: {}", e),
+                    path: Some(PathBuf::from(path)),
+                }),
+            Err(e) => Err(e),
+        };
+        let _ = tokio::fs::remove_file(&staged).await;
+        content
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<(), NexusError> {
+        let staged = self.staging_path();
+        tokio::fs::write(&staged, content)
+            .await
+            .map_err(|e| NexusError::Io {
+                message: format!("Failed to stage file for docker cp: {}", e),
+                path: Some(staged.clone()),
+            })?;
+        let copied = self
+            .copy(
+                &staged.to_string_lossy(),
+                &format!("{}:{}", self.container, path),
+                path,
+            )
+            .await;
+        let _ = tokio::fs::remove_file(&staged).await;
+        copied
+    }
+
+    fn host_name(&self) -> &str {
+        &self.host_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::Value;
+
+    #[test]
+    fn test_container_from_ansible_host() {
+        let host = Host::new("web")
+            .with_address("web-container")
+            .with_var("ansible_connection", Value::String("docker".to_string()));
+        let conn = DockerConnection::for_host(&host);
+
+        assert_eq!(conn.host_name(), "web");
+        assert_eq!(conn.container(), "web-container");
+        assert_eq!(
+            conn.exec_args("echo hi"),
+            ["exec", "-i", "web-container", "sh", "-c", "echo hi"]
+        );
+    }
+}
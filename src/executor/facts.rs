@@ -9,11 +9,14 @@
 // - Fact filtering: only gather the facts you need
 
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 
-use crate::executor::SshConnection;
+use crate::executor::{ExecutionContext, SshConnection};
+use crate::modules::detect_package_manager;
 use crate::output::errors::NexusError;
 use crate::parser::ast::Value;
 
@@ -62,6 +65,24 @@ impl Default for HostFacts {
     }
 }
 
+/// Default lifetime of facts cached on disk (`--fact-cache-ttl`)
+pub const DEFAULT_FACT_CACHE_TTL: Duration = Duration::from_secs(7200);
+
+/// On-disk format of `<cache_dir>/<host>.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedFacts {
+    /// Unix time the facts were gathered
+    gathered_at: u64,
+    facts: HashMap<String, Value>,
+    /// Unix time `set_facts` were last updated
+    #[serde(default)]
+    set_at: u64,
+    /// Values from `set_fact` with `cacheable: true`, kept apart from gathered facts
+    /// so they never stand in for gathering
+    #[serde(default)]
+    set_facts: HashMap<String, Value>,
+}
+
 /// Registry for caching facts across hosts
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -72,6 +93,10 @@ pub struct FactCache {
     ttl: Duration,
     /// Whether to auto-gather on first access
     auto_gather: bool,
+    /// Directory facts are persisted to between runs, if any
+    cache_dir: Option<PathBuf>,
+    /// Cacheable `set_fact` values per host
+    set_fact_values: RwLock<HashMap<String, HashMap<String, Value>>>,
 }
 
 impl FactCache {
@@ -80,6 +105,8 @@ impl FactCache {
             hosts: RwLock::new(HashMap::new()),
             ttl: Duration::from_secs(3600), // 1 hour default
             auto_gather: true,
+            cache_dir: None,
+            set_fact_values: RwLock::new(HashMap::new()),
         }
     }
 
@@ -89,7 +116,116 @@ impl FactCache {
             hosts: RwLock::new(HashMap::new()),
             ttl,
             auto_gather: true,
+            cache_dir: None,
+            set_fact_values: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Persist facts to `dir` so later runs can reuse them until the TTL expires
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Default persistent cache directory: `~/.nexus/facts`
+    pub fn default_cache_dir() -> PathBuf {
+        std::env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(".nexus")
+            .join("facts")
+    }
+
+    /// Load a host's facts from the cache directory.
+    /// Missing, stale or unreadable files return `None` so the caller re-gathers.
+    /// Loaded facts are also kept in memory, aged by how long ago they were gathered.
+    pub fn load_persisted(&self, host: &str) -> Option<HostFacts> {
+        let cached = self.read_cache_file(host)?;
+        let age = age_of(cached.gathered_at);
+        if age > self.ttl {
+            return None;
+        }
+
+        let facts = HostFacts {
+            facts: cached.facts,
+            gathered_at: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+            categories: Vec::new(),
+        };
+        self.set_facts(host, facts.clone());
+        Some(facts)
+    }
+
+    /// Store freshly gathered facts in memory and, when a cache directory is set, on disk
+    pub fn persist(&self, host: &str, facts: HashMap<String, Value>) -> std::io::Result<()> {
+        let mut host_facts = HostFacts::new();
+        host_facts.merge(facts);
+
+        if let Some(path) = self.cache_file(host) {
+            let cached = CachedFacts {
+                gathered_at: unix_now(),
+                facts: host_facts.facts.clone(),
+                ..self.read_cache_file(host).unwrap_or_default()
+            };
+            write_atomic(&path, &serde_json::to_string_pretty(&cached)?)?;
+        }
+
+        self.set_facts(host, host_facts);
+        Ok(())
+    }
+
+    /// Remember `set_fact` values for later plays and, when a cache directory is set,
+    /// later runs. They expire with the same TTL as gathered facts.
+    pub fn cache_set_facts(
+        &self,
+        host: &str,
+        facts: HashMap<String, Value>,
+    ) -> std::io::Result<()> {
+        self.set_fact_values
+            .write()
+            .entry(host.to_string())
+            .or_default()
+            .extend(facts.clone());
+
+        if let Some(path) = self.cache_file(host) {
+            let mut cached = self.read_cache_file(host).unwrap_or_default();
+            if age_of(cached.set_at) > self.ttl {
+                cached.set_facts.clear();
+            }
+            cached.set_facts.extend(facts);
+            cached.set_at = unix_now();
+            write_atomic(&path, &serde_json::to_string_pretty(&cached)?)?;
+        }
+        Ok(())
+    }
+
+    /// Cacheable `set_fact` values for a host, from this run or a fresh cache file
+    pub fn cached_set_facts(&self, host: &str) -> HashMap<String, Value> {
+        if let Some(facts) = self.set_fact_values.read().get(host) {
+            return facts.clone();
         }
+
+        let facts = self
+            .read_cache_file(host)
+            .filter(|cached| age_of(cached.set_at) <= self.ttl)
+            .map(|cached| cached.set_facts)
+            .unwrap_or_default();
+        self.set_fact_values
+            .write()
+            .insert(host.to_string(), facts.clone());
+        facts
+    }
+
+    fn cache_file(&self, host: &str) -> Option<PathBuf> {
+        let name: String = host
+            .chars()
+            .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+            .collect();
+        Some(self.cache_dir.as_ref()?.join(format!("{}.json", name)))
+    }
+
+    fn read_cache_file(&self, host: &str) -> Option<CachedFacts> {
+        let content = std::fs::read_to_string(self.cache_file(host)?).ok()?;
+        serde_json::from_str(&content).ok()
     }
 
     /// Get facts for a host
@@ -145,6 +281,28 @@ impl Default for FactCache {
     }
 }
 
+/// Write via a temporary file so an interrupted run never leaves a truncated cache file
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// How long ago a Unix time was
+fn age_of(unix_time: u64) -> Duration {
+    Duration::from_secs(unix_now().saturating_sub(unix_time))
+}
+
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)
+}
+
 /// Categories of facts to gather
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FactCategory {
@@ -167,18 +325,83 @@ pub enum FactCategory {
 }
 
 impl FactCategory {
-    /// Get all categories except All
+    /// The categories `All` stands for: everything but the installed package
+    /// list, which is slow on large hosts and only gathered when asked for
     pub fn all_categories() -> Vec<FactCategory> {
         vec![
             FactCategory::System,
             FactCategory::Hardware,
             FactCategory::Network,
             FactCategory::Mounts,
-            FactCategory::Packages,
             FactCategory::Services,
             FactCategory::Environment,
         ]
     }
+
+    /// Look up a category by name; `min` is an alias for `system`
+    pub fn from_name(name: &str) -> Option<FactCategory> {
+        match name.trim().to_lowercase().as_str() {
+            "system" | "min" => Some(FactCategory::System),
+            "hardware" => Some(FactCategory::Hardware),
+            "network" => Some(FactCategory::Network),
+            "mounts" => Some(FactCategory::Mounts),
+            "packages" => Some(FactCategory::Packages),
+            "services" => Some(FactCategory::Services),
+            "environment" => Some(FactCategory::Environment),
+            "all" => Some(FactCategory::All),
+            _ => None,
+        }
+    }
+
+    /// The categories a `gather_subset` selects, as in Ansible: named categories
+    /// are gathered, `!name` excludes one (starting from all of them when nothing
+    /// is named), and the `system` facts are always gathered unless `!min` (or
+    /// `!system`) is given. An empty subset gathers everything.
+    pub fn resolve_subset(subset: &[String]) -> Result<Vec<FactCategory>, String> {
+        let mut included = Vec::new();
+        let mut excluded = Vec::new();
+        let mut exclude_min = false;
+        for entry in subset {
+            let (negated, name) = match entry.trim().strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, entry.trim()),
+            };
+            let category = FactCategory::from_name(name).ok_or_else(|| {
+                format!(
+                    "unknown fact subset '{}' (expected all, min, system, hardware, network, \
+                     mounts, packages, services or environment, optionally prefixed with !)",
+                    name
+                )
+            })?;
+            match (negated, category) {
+                (false, FactCategory::All) => included.extend(FactCategory::all_categories()),
+                (false, category) => included.push(category),
+                // `!all` leaves only the minimal facts
+                (true, FactCategory::All) => excluded.extend(FactCategory::all_categories()),
+                (true, FactCategory::System) => exclude_min = true,
+                (true, category) => excluded.push(category),
+            }
+        }
+
+        if included.is_empty() {
+            included = FactCategory::all_categories();
+        }
+        let mut categories = Vec::new();
+        if !exclude_min {
+            categories.push(FactCategory::System);
+        }
+        for category in included {
+            let wanted = if category == FactCategory::System {
+                !exclude_min
+            } else {
+                !excluded.contains(&category)
+            };
+            if wanted && !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        Ok(categories)
+    }
 }
 
 /// Fact gatherer - collects system facts via SSH
@@ -186,18 +409,25 @@ pub struct FactGatherer;
 
 impl FactGatherer {
     /// Gather facts for specified categories
-    pub fn gather(
+    pub async fn gather(
         conn: &SshConnection,
         categories: &[FactCategory],
     ) -> Result<HashMap<String, Value>, NexusError> {
         let mut facts = HashMap::new();
 
-        // Expand All to all categories
-        let cats: Vec<FactCategory> = if categories.contains(&FactCategory::All) {
-            FactCategory::all_categories()
-        } else {
-            categories.to_vec()
-        };
+        // Expand All, keeping categories it doesn't cover (packages)
+        let mut cats = Vec::new();
+        for category in categories {
+            let expanded = match category {
+                FactCategory::All => FactCategory::all_categories(),
+                category => vec![*category],
+            };
+            for category in expanded {
+                if !cats.contains(&category) {
+                    cats.push(category);
+                }
+            }
+        }
 
         for category in cats {
             let category_facts = match category {
@@ -205,7 +435,7 @@ impl FactGatherer {
                 FactCategory::Hardware => Self::gather_hardware(conn)?,
                 FactCategory::Network => Self::gather_network(conn)?,
                 FactCategory::Mounts => Self::gather_mounts(conn)?,
-                FactCategory::Packages => Self::gather_packages(conn)?,
+                FactCategory::Packages => Self::gather_packages(conn).await?,
                 FactCategory::Services => Self::gather_services(conn)?,
                 FactCategory::Environment => Self::gather_environment(conn)?,
                 FactCategory::All => continue, // Already expanded
@@ -220,8 +450,8 @@ impl FactGatherer {
     }
 
     /// Gather all facts
-    pub fn gather_all(conn: &SshConnection) -> Result<HashMap<String, Value>, NexusError> {
-        Self::gather(conn, &[FactCategory::All])
+    pub async fn gather_all(conn: &SshConnection) -> Result<HashMap<String, Value>, NexusError> {
+        Self::gather(conn, &[FactCategory::All]).await
     }
 
     /// Gather basic system facts
@@ -493,55 +723,36 @@ impl FactGatherer {
         Ok(facts)
     }
 
-    /// Gather package manager facts
-    fn gather_packages(conn: &SshConnection) -> Result<HashMap<String, Value>, NexusError> {
+    /// Gather package facts: the package manager and every installed package,
+    /// as `packages` mapping each name to its version
+    async fn gather_packages(conn: &SshConnection) -> Result<HashMap<String, Value>, NexusError> {
         let mut facts = HashMap::new();
 
-        // Detect package manager
-        let managers = [
-            ("apt", "which apt 2>/dev/null"),
-            ("dnf", "which dnf 2>/dev/null"),
-            ("yum", "which yum 2>/dev/null"),
-            ("pacman", "which pacman 2>/dev/null"),
-            ("zypper", "which zypper 2>/dev/null"),
-            ("apk", "which apk 2>/dev/null"),
-        ];
-
-        for (name, cmd) in managers {
-            let result = conn.exec(cmd)?;
-            if result.success() && !result.stdout.trim().is_empty() {
-                facts.insert(
-                    "package_manager".to_string(),
-                    Value::String(name.to_string()),
-                );
-                break;
-            }
-        }
+        // Hosts without a supported package manager just have no package facts
+        let Ok(manager) = detect_package_manager(conn).await else {
+            return Ok(facts);
+        };
+        facts.insert(
+            "package_manager".to_string(),
+            Value::String(manager.as_str().to_string()),
+        );
 
-        // Get installed packages count (not the full list - that could be huge)
-        let count_cmds = [
-            ("apt", "dpkg -l 2>/dev/null | grep '^ii' | wc -l"),
-            ("dnf", "rpm -qa 2>/dev/null | wc -l"),
-            ("yum", "rpm -qa 2>/dev/null | wc -l"),
-            ("pacman", "pacman -Q 2>/dev/null | wc -l"),
-        ];
-
-        for (_, cmd) in count_cmds {
-            let result = conn.exec(cmd)?;
-            if result.success() {
-                if let Ok(n) = result.stdout.trim().parse::<i64>() {
-                    if n > 0 {
-                        facts.insert("installed_packages_count".to_string(), Value::Int(n));
-                        break;
-                    }
-                }
-            }
+        let result = conn.exec(manager.list_installed_cmd())?;
+        if result.success() {
+            let packages = parse_installed_packages(&result.stdout);
+            facts.insert(
+                "installed_packages_count".to_string(),
+                Value::Int(packages.len() as i64),
+            );
+            facts.insert("packages".to_string(), Value::Dict(packages));
         }
 
         Ok(facts)
     }
 
-    /// Gather service facts
+    /// Gather service facts: whether systemd is in use, the running units, and
+    /// `services` mapping every service to its state and boot status. Hosts
+    /// without a running systemd fall back to `service --status-all`.
     fn gather_services(conn: &SshConnection) -> Result<HashMap<String, Value>, NexusError> {
         let mut facts = HashMap::new();
 
@@ -550,18 +761,42 @@ impl FactGatherer {
         let has_systemd = result.success() && !result.stdout.trim().is_empty();
         facts.insert("has_systemd".to_string(), Value::Bool(has_systemd));
 
-        if has_systemd {
-            // Get running services
-            let result = conn.exec("systemctl list-units --type=service --state=running --no-pager --no-legend | awk '{print $1}' | head -50")?;
-            if result.success() {
-                let services: Vec<Value> = result
-                    .stdout
-                    .lines()
-                    .map(|s| Value::String(s.trim().to_string()))
-                    .collect();
-                facts.insert("running_services".to_string(), Value::List(services));
+        // systemctl can be installed without systemd running (e.g. in containers)
+        let units = match has_systemd {
+            true => Some(conn.exec(SYSTEMD_UNITS_CMD)?).filter(|result| result.success()),
+            false => None,
+        };
+
+        let services = match units {
+            Some(units) => {
+                let unit_files = conn.exec(
+                    "systemctl list-unit-files --type=service --no-pager --no-legend 2>/dev/null",
+                )?;
+                parse_systemd_services(&units.stdout, &unit_files.stdout)
             }
-        }
+            None => {
+                let result = conn.exec("service --status-all 2>&1")?;
+                if !result.success() {
+                    return Ok(facts);
+                }
+                parse_sysv_services(&result.stdout)
+            }
+        };
+
+        let mut running: Vec<String> = services
+            .iter()
+            .filter(|(_, service)| {
+                matches!(service, Value::Dict(fields)
+                    if fields.get("state") == Some(&Value::String("running".to_string())))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        running.sort();
+        facts.insert(
+            "running_services".to_string(),
+            Value::List(running.into_iter().map(Value::String).collect()),
+        );
+        facts.insert("services".to_string(), Value::Dict(services));
 
         Ok(facts)
     }
@@ -646,6 +881,135 @@ impl FactGatherer {
     }
 }
 
+/// Every service unit, loaded or not, one per line without decorations
+const SYSTEMD_UNITS_CMD: &str =
+    "systemctl list-units --type=service --all --no-pager --no-legend --plain";
+
+/// Facts whose values are large maps, only exposed under `ansible_facts` so
+/// they can't shadow a playbook variable of the same name
+const NESTED_ONLY_FACTS: &[&str] = &["packages", "services"];
+
+/// The variables gathered facts are exposed as: each fact under its Ansible
+/// name (`ansible_os_family`, ...), and all of them under `ansible_facts`
+pub fn ansible_fact_vars(facts: &HashMap<String, Value>) -> HashMap<String, Value> {
+    let mut vars = HashMap::new();
+    for (key, value) in facts {
+        if NESTED_ONLY_FACTS.contains(&key.as_str()) {
+            continue;
+        }
+        let ansible_key = match key.as_str() {
+            "hostname" => "ansible_hostname",
+            "hostname_short" => "ansible_hostname_short",
+            "os_family" => "ansible_os_family",
+            "os_name" => "ansible_distribution",
+            "os_version" => "ansible_distribution_version",
+            "kernel_version" => "ansible_kernel",
+            "architecture" => "ansible_architecture",
+            "cpu_count" => "ansible_processor_count",
+            "memory_total_mb" => "ansible_memtotal_mb",
+            "default_ipv4" => "ansible_default_ipv4_address",
+            "interfaces" => "ansible_interfaces",
+            _ => key.as_str(),
+        };
+        vars.insert(ansible_key.to_string(), value.clone());
+    }
+    vars.insert("ansible_facts".to_string(), Value::Dict(facts.clone()));
+    vars
+}
+
+/// Record a fact that a module has just changed, under its Ansible name and in
+/// `ansible_facts`, so later tasks see the new value without gathering again
+pub fn update_fact(ctx: &ExecutionContext, key: &str, value: Value) {
+    let mut facts = match ctx.get_var("ansible_facts") {
+        Some(Value::Dict(facts)) => facts,
+        _ => HashMap::new(),
+    };
+    facts.insert(key.to_string(), value.clone());
+
+    let changed = HashMap::from([(key.to_string(), value)]);
+    for (name, value) in ansible_fact_vars(&changed) {
+        if name != "ansible_facts" {
+            ctx.set_var(name, value);
+        }
+    }
+    ctx.set_var("ansible_facts", Value::Dict(facts));
+}
+
+/// Parse `name version` lines into a name -> version map. A package installed
+/// in several versions (such as rpm kernels) keeps the last one listed.
+fn parse_installed_packages(stdout: &str) -> HashMap<String, Value> {
+    stdout
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .map(|(name, version)| (name.to_string(), Value::String(version.trim().to_string())))
+        .collect()
+}
+
+/// One `services` entry, shaped like Ansible's service_facts
+fn service_fact(name: &str, state: &str, status: &str, source: &str) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert("name".to_string(), Value::String(name.to_string()));
+    fields.insert("state".to_string(), Value::String(state.to_string()));
+    fields.insert("status".to_string(), Value::String(status.to_string()));
+    fields.insert("source".to_string(), Value::String(source.to_string()));
+    Value::Dict(fields)
+}
+
+/// Services from `systemctl list-units` (`UNIT LOAD ACTIVE SUB ...` lines), with
+/// their boot status (enabled, disabled, static, masked) from `list-unit-files`
+fn parse_systemd_services(units: &str, unit_files: &str) -> HashMap<String, Value> {
+    let statuses: HashMap<&str, &str> = unit_files
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect();
+
+    units
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (unit, active, sub) = match fields[..] {
+                [unit, _load, active, sub, ..] if unit.ends_with(".service") => (unit, active, sub),
+                _ => return None,
+            };
+            let state = match (active, sub) {
+                (_, "running") => "running",
+                ("failed", _) => "failed",
+                _ => "stopped",
+            };
+            let status = statuses.get(unit).copied().unwrap_or("unknown");
+            Some((
+                unit.to_string(),
+                service_fact(unit, state, status, "systemd"),
+            ))
+        })
+        .collect()
+}
+
+/// Services from SysV `service --status-all` lines such as ` [ + ]  cron`
+fn parse_sysv_services(stdout: &str) -> HashMap<String, Value> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (marker, name) = line.trim().strip_prefix('[')?.split_once(']')?;
+            let name = name.trim();
+            let state = match marker.trim() {
+                "+" => "running",
+                "-" => "stopped",
+                _ => "unknown",
+            };
+            (!name.is_empty()).then(|| {
+                (
+                    name.to_string(),
+                    service_fact(name, state, "unknown", "sysv"),
+                )
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -661,6 +1025,210 @@ mod tests {
         assert!(facts.is_stale(Duration::ZERO));
     }
 
+    #[test]
+    fn test_resolve_gather_subset() {
+        let subset = |entries: &[&str]| {
+            let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+            FactCategory::resolve_subset(&entries)
+        };
+
+        assert_eq!(subset(&[]).unwrap(), FactCategory::all_categories());
+        assert_eq!(
+            subset(&["!hardware", "network"]).unwrap(),
+            vec![FactCategory::System, FactCategory::Network]
+        );
+        assert_eq!(
+            subset(&["!hardware", "!packages"]).unwrap(),
+            vec![
+                FactCategory::System,
+                FactCategory::Network,
+                FactCategory::Mounts,
+                FactCategory::Services,
+                FactCategory::Environment,
+            ]
+        );
+        assert_eq!(subset(&["!all"]).unwrap(), vec![FactCategory::System]);
+        assert_eq!(subset(&["!all", "!min"]).unwrap(), vec![]);
+        assert_eq!(
+            subset(&["min", "Network"]).unwrap(),
+            vec![FactCategory::System, FactCategory::Network]
+        );
+        assert!(subset(&["!cpu"]).unwrap_err().contains("'cpu'"));
+
+        // The package list is only gathered when asked for
+        assert!(!subset(&[]).unwrap().contains(&FactCategory::Packages));
+        assert_eq!(
+            subset(&["packages"]).unwrap(),
+            vec![FactCategory::System, FactCategory::Packages]
+        );
+        assert!(subset(&["all", "packages"])
+            .unwrap()
+            .contains(&FactCategory::Packages));
+    }
+
+    #[test]
+    fn test_parse_installed_packages() {
+        let stdout = "openssl 3.0.2-0ubuntu1.15\nnginx 1.18.0-6ubuntu14.4\n\nkernel 5.14.0-362\nkernel 5.14.0-427\n";
+        let packages = parse_installed_packages(stdout);
+
+        assert_eq!(packages.len(), 3);
+        assert_eq!(
+            packages.get("nginx"),
+            Some(&Value::String("1.18.0-6ubuntu14.4".to_string()))
+        );
+        assert_eq!(
+            packages.get("kernel"),
+            Some(&Value::String("5.14.0-427".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_ansible_fact_vars() {
+        let mut packages = HashMap::new();
+        packages.insert("nginx".to_string(), Value::String("1.24.0".to_string()));
+        let mut facts = HashMap::new();
+        facts.insert("os_family".to_string(), Value::String("Debian".to_string()));
+        facts.insert("packages".to_string(), Value::Dict(packages));
+
+        let vars = ansible_fact_vars(&facts);
+        assert_eq!(
+            vars.get("ansible_os_family"),
+            Some(&Value::String("Debian".to_string()))
+        );
+        // The package map is only reachable as ansible_facts.packages
+        assert!(!vars.contains_key("packages"));
+        assert_eq!(vars.get("ansible_facts"), Some(&Value::Dict(facts)));
+    }
+
+    #[test]
+    fn test_update_fact() {
+        use crate::inventory::Host;
+        use std::sync::Arc;
+
+        let mut facts = HashMap::new();
+        facts.insert("os_family".to_string(), Value::String("Debian".to_string()));
+        facts.insert("hostname".to_string(), Value::String("old".to_string()));
+        let ctx = ExecutionContext::new(Arc::new(Host::new("web1")), ansible_fact_vars(&facts));
+
+        update_fact(&ctx, "hostname", Value::String("web01".to_string()));
+        assert_eq!(
+            ctx.get_var("ansible_hostname"),
+            Some(Value::String("web01".to_string()))
+        );
+        let Some(Value::Dict(facts)) = ctx.get_var("ansible_facts") else {
+            panic!("ansible_facts should be a dict");
+        };
+        assert_eq!(facts["hostname"], Value::String("web01".to_string()));
+        assert_eq!(facts["os_family"], Value::String("Debian".to_string()));
+    }
+
+    #[test]
+    fn test_parse_service_facts() {
+        let units = "\
+cron.service      loaded    active   running Regular background program processing daemon
+nginx.service     loaded    failed   failed  A high performance web server
+rsync.service     loaded    inactive dead    fast remote file copy program daemon
+ssh.socket        loaded    active   listening OpenBSD Secure Shell socket
+";
+        let unit_files = "\
+cron.service      enabled enabled
+nginx.service     enabled enabled
+rsync.service     disabled enabled
+";
+        let services = parse_systemd_services(units, unit_files);
+        assert_eq!(services.len(), 3);
+        assert_eq!(
+            services.get("cron.service"),
+            Some(&service_fact(
+                "cron.service",
+                "running",
+                "enabled",
+                "systemd"
+            ))
+        );
+        assert_eq!(
+            services.get("nginx.service"),
+            Some(&service_fact(
+                "nginx.service",
+                "failed",
+                "enabled",
+                "systemd"
+            ))
+        );
+        assert_eq!(
+            services.get("rsync.service"),
+            Some(&service_fact(
+                "rsync.service",
+                "stopped",
+                "disabled",
+                "systemd"
+            ))
+        );
+
+        let sysv = " [ + ]  cron\n [ - ]  rsync\n [ ? ]  hwclock.sh\n";
+        let services = parse_sysv_services(sysv);
+        assert_eq!(
+            services.get("cron"),
+            Some(&service_fact("cron", "running", "unknown", "sysv"))
+        );
+        assert_eq!(
+            services.get("hwclock.sh"),
+            Some(&service_fact("hwclock.sh", "unknown", "unknown", "sysv"))
+        );
+    }
+
+    #[test]
+    fn test_fact_cache_persists_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut facts = HashMap::new();
+        facts.insert("os_family".to_string(), Value::String("Debian".to_string()));
+        facts.insert("cpu_count".to_string(), Value::Int(4));
+
+        FactCache::with_ttl(DEFAULT_FACT_CACHE_TTL)
+            .with_cache_dir(dir.path())
+            .persist("web1", facts.clone())
+            .unwrap();
+
+        // A later run reads the facts back
+        let cache = FactCache::with_ttl(DEFAULT_FACT_CACHE_TTL).with_cache_dir(dir.path());
+        assert_eq!(cache.load_persisted("web1").unwrap().facts, facts);
+        assert_eq!(cache.get_fact("web1", "cpu_count"), Some(Value::Int(4)));
+        assert!(cache.load_persisted("db1").is_none());
+
+        // Stale entries are ignored
+        std::fs::write(
+            dir.path().join("web1.json"),
+            r#"{"gathered_at": 0, "facts": {"os_family": "Debian"}}"#,
+        )
+        .unwrap();
+        assert!(cache.load_persisted("web1").is_none());
+
+        // So are corrupt ones
+        std::fs::write(dir.path().join("web1.json"), "{not json").unwrap();
+        assert!(cache.load_persisted("web1").is_none());
+    }
+
+    #[test]
+    fn test_cached_set_facts_survive_runs_without_counting_as_gathered() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut facts = HashMap::new();
+        facts.insert("release".to_string(), Value::String("v2".to_string()));
+
+        let cache = FactCache::with_ttl(DEFAULT_FACT_CACHE_TTL).with_cache_dir(dir.path());
+        cache.cache_set_facts("web1", facts.clone()).unwrap();
+        assert_eq!(cache.cached_set_facts("web1"), facts);
+
+        let later = FactCache::with_ttl(DEFAULT_FACT_CACHE_TTL).with_cache_dir(dir.path());
+        assert_eq!(later.cached_set_facts("web1"), facts);
+        assert!(later.load_persisted("web1").is_none());
+
+        // Gathering afterwards keeps them
+        later.persist("web1", HashMap::new()).unwrap();
+        let third = FactCache::with_ttl(DEFAULT_FACT_CACHE_TTL).with_cache_dir(dir.path());
+        assert_eq!(third.cached_set_facts("web1"), facts);
+        assert!(third.cached_set_facts("db1").is_empty());
+    }
+
     #[test]
     fn test_fact_cache_basic() {
         let cache = FactCache::new();
@@ -26,6 +26,8 @@ pub struct HandlerRegistry {
     dependencies: HashMap<String, Vec<String>>,
     /// Handler groups (group name -> handler names)
     groups: HashMap<String, Vec<String>>,
+    /// Listen topics (topic -> handler names subscribed to it)
+    listeners: HashMap<String, Vec<String>>,
     /// Handlers that have been flushed (already run)
     flushed: RwLock<HashSet<String>>,
 }
@@ -37,6 +39,7 @@ impl HandlerRegistry {
             execution_order: Vec::new(),
             dependencies: HashMap::new(),
             groups: HashMap::new(),
+            listeners: HashMap::new(),
             flushed: RwLock::new(HashSet::new()),
         }
     }
@@ -48,6 +51,9 @@ impl HandlerRegistry {
         // Build execution order (for now, just preserve definition order)
         for handler in handlers {
             registry.execution_order.push(handler.name.clone());
+            for topic in &handler.listen {
+                registry.subscribe(topic, &handler.name);
+            }
         }
 
         registry
@@ -61,8 +67,10 @@ impl HandlerRegistry {
         // This is acceptable because role handlers typically don't depend on each other.
     }
 
-    /// Notify a handler for a specific host
+    /// Notify a handler for a specific host.
+    /// A handler that already ran becomes pending again when re-notified.
     pub fn notify(&self, handler_name: &str, host: &str) {
+        self.flushed.write().remove(handler_name);
         let mut notifications = self.notifications.write();
         notifications
             .entry(handler_name.to_string())
@@ -70,10 +78,41 @@ impl HandlerRegistry {
             .insert(host.to_string());
     }
 
-    /// Notify multiple handlers at once
+    /// Subscribe a handler to a listen topic
+    pub fn subscribe(&mut self, topic: &str, handler_name: &str) {
+        let subscribers = self.listeners.entry(topic.to_string()).or_default();
+        if !subscribers.iter().any(|h| h == handler_name) {
+            subscribers.push(handler_name.to_string());
+        }
+    }
+
+    /// Resolve a notification to handler names: the handler with that name
+    /// and every handler listening on it as a topic
+    pub fn resolve(&self, notification: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        if self.execution_order.iter().any(|h| h == notification) {
+            names.push(notification.to_string());
+        }
+        if let Some(subscribers) = self.listeners.get(notification) {
+            for name in subscribers {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        if names.is_empty() {
+            // Unknown name: record it so the notification isn't silently lost
+            names.push(notification.to_string());
+        }
+        names
+    }
+
+    /// Notify multiple handlers at once, resolving names and listen topics
     pub fn notify_all(&self, handler_names: &[String], host: &str) {
-        for name in handler_names {
-            self.notify(name, host);
+        for notification in handler_names {
+            for name in self.resolve(notification) {
+                self.notify(&name, host);
+            }
         }
     }
 
@@ -232,7 +271,7 @@ mod tests {
         assert!(!registry.has_pending());
 
         // Add a handler to execution order
-        let mut registry = HandlerRegistry {
+        let registry = HandlerRegistry {
             execution_order: vec!["restart_nginx".to_string()],
             ..Default::default()
         };
@@ -276,6 +315,20 @@ mod tests {
         assert!(!registry.has_pending());
     }
 
+    #[test]
+    fn test_renotify_after_flush() {
+        let mut registry = HandlerRegistry::new();
+        registry.execution_order.push("restart_nginx".to_string());
+
+        registry.notify("restart_nginx", "host1");
+        registry.mark_flushed("restart_nginx");
+
+        // A later notification (e.g. after meta: flush_handlers) runs it again
+        registry.notify("restart_nginx", "host2");
+        assert_eq!(registry.pending_handlers(), vec!["restart_nginx"]);
+        assert_eq!(registry.notified_hosts("restart_nginx"), vec!["host2"]);
+    }
+
     #[test]
     fn test_execution_order() {
         let mut registry = HandlerRegistry::new();
@@ -310,6 +363,39 @@ mod tests {
         assert_eq!(pending.len(), 2);
     }
 
+    #[test]
+    fn test_listen_topic_notifies_all_subscribers() {
+        use crate::parser::ast::{Expression, ModuleCall, ServiceState};
+
+        let handler = |name: &str, listen: &[&str]| Handler {
+            name: name.to_string(),
+            module: ModuleCall::Service {
+                name: Expression::String(name.to_string()),
+                state: ServiceState::Restarted,
+                enabled: None,
+            },
+            listen: listen.iter().map(|t| t.to_string()).collect(),
+            location: None,
+        };
+        let registry = HandlerRegistry::from_handlers(&[
+            handler("restart nginx", &["restart web stack"]),
+            handler("restart php-fpm", &["restart web stack"]),
+            handler("reload haproxy", &[]),
+        ]);
+
+        registry.notify_all(&["restart web stack".to_string()], "host1");
+
+        assert_eq!(
+            registry.pending_handlers(),
+            vec!["restart nginx", "restart php-fpm"]
+        );
+
+        // Handler names still work alongside topics
+        registry.notify_all(&["reload haproxy".to_string()], "host2");
+        assert_eq!(registry.pending_handlers().len(), 3);
+        assert_eq!(registry.notified_hosts("reload haproxy"), vec!["host2"]);
+    }
+
     #[test]
     fn test_parallel_batch_no_deps() {
         let mut registry = HandlerRegistry::new();
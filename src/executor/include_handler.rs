@@ -63,6 +63,7 @@ impl Scheduler {
                         file_path_value
                     ),
                     suggestion: None,
+                    location: None,
                 });
             }
         };
@@ -95,14 +96,14 @@ impl Scheduler {
                 .print_task_header(&format!("INCLUDE: {}", file_path));
         }
 
-        // Execute the included tasks
+        // Execute the included tasks, which carry the include's tags
         Box::pin(self.execute_task_list(
             &included_tasks,
             hosts,
             &include_vars,
             use_sudo,
             sudo_user,
-            tag_filter,
+            &tag_filter.with_inherited_tags(&include.tags),
             handler_registry,
             recap,
         ))
@@ -150,14 +151,14 @@ impl Scheduler {
                 .print_task_header(&format!("IMPORT: {}", import.file));
         }
 
-        // Execute the imported tasks
+        // Execute the imported tasks, which carry the import's tags
         Box::pin(self.execute_task_list(
             &included_tasks,
             hosts,
             &import_vars,
             use_sudo,
             sudo_user,
-            tag_filter,
+            &tag_filter.with_inherited_tags(&import.tags),
             handler_registry,
             recap,
         ))
@@ -0,0 +1,234 @@
+// Listing a play's tasks and tags without running anything (--list-tasks / --list-tags)
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::executor::context::ExecutionContext;
+use crate::inventory::Host;
+use crate::output::errors::NexusError;
+use crate::parser::ast::{Playbook, Task, TaskOrBlock, Value};
+use crate::parser::{load_vars_file, parse_task_file};
+use crate::runtime::evaluate_expression;
+
+use super::scheduler::Scheduler;
+
+/// A task in play order, with the tags it carries including those of its block, role or import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedTask {
+    /// Play section: `pre_tasks`, `role: <name>`, `tasks` or `post_tasks`
+    pub section: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    /// An `include_tasks` whose file is only known at runtime, so its tasks are not listed
+    pub unresolved: bool,
+}
+
+impl Scheduler {
+    /// Expand the play's roles, imports and includes into the ordered task list.
+    /// Nothing is executed and no connections are opened. Includes whose file depends
+    /// on runtime values (registered vars, facts, loop items) are listed unexpanded.
+    pub fn list_tasks(&self, playbook: &Playbook) -> Result<Vec<ListedTask>, NexusError> {
+        let playbook_dir = Path::new(&playbook.source_file)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        *self.playbook_dir.lock() = Some(playbook_dir.clone());
+
+        // Play vars without vars_prompt: listing must not stop to ask for input
+        let mut vars = playbook.vars.clone();
+        for file in &playbook.vars_files {
+            vars.extend(load_vars_file(
+                &playbook_dir.join(file),
+                Some(&self.config.vault_secrets),
+            )?);
+        }
+        vars.extend(self.config.extra_vars.clone());
+
+        let mut listed = Vec::new();
+        self.list_task_items(&playbook.pre_tasks, "pre_tasks", &[], &vars, &mut listed)?;
+
+        for role_ref in &playbook.roles {
+            let role_execution_order = {
+                let mut resolver = self.role_resolver.lock();
+                resolver.resolve_dependencies(&role_ref.role)?
+            };
+            for role_name in role_execution_order {
+                let role = {
+                    let mut resolver = self.role_resolver.lock();
+                    resolver.resolve(&role_name)?.clone()
+                };
+                let mut role_vars = role.defaults.clone();
+                role_vars.extend(role.vars.clone());
+                role_vars.extend(role_ref.vars.clone());
+                role_vars.extend(vars.clone());
+
+                self.list_task_items(
+                    &role.tasks,
+                    &format!("role: {}", role.name),
+                    &role_ref.tags,
+                    &role_vars,
+                    &mut listed,
+                )?;
+            }
+        }
+
+        self.list_task_items(&playbook.tasks, "tasks", &[], &vars, &mut listed)?;
+        self.list_task_items(&playbook.post_tasks, "post_tasks", &[], &vars, &mut listed)?;
+
+        Ok(listed)
+    }
+
+    fn list_task_items(
+        &self,
+        items: &[TaskOrBlock],
+        section: &str,
+        inherited_tags: &[String],
+        vars: &HashMap<String, Value>,
+        listed: &mut Vec<ListedTask>,
+    ) -> Result<(), NexusError> {
+        let list_task = |task: &Task, tags: &[String], listed: &mut Vec<ListedTask>| {
+            listed.push(ListedTask {
+                section: section.to_string(),
+                name: task.name.clone(),
+                tags: merge_tags(tags, &task.tags),
+                unresolved: false,
+            });
+        };
+
+        for item in items {
+            match item {
+                TaskOrBlock::Task(task) => list_task(task, inherited_tags, listed),
+                TaskOrBlock::Block(block) => {
+                    let tags = merge_tags(inherited_tags, &block.tags);
+                    for task in block.block.iter().chain(&block.rescue).chain(&block.always) {
+                        list_task(task, &tags, listed);
+                    }
+                }
+                TaskOrBlock::Import(import) => {
+                    let tasks = parse_task_file(&self.resolve_task_file(&import.file))?;
+                    let mut import_vars = vars.clone();
+                    import_vars.extend(import.vars.clone());
+
+                    self.list_task_items(
+                        &tasks,
+                        section,
+                        &merge_tags(inherited_tags, &import.tags),
+                        &import_vars,
+                        listed,
+                    )?;
+                }
+                TaskOrBlock::Include(include) => {
+                    let tags = merge_tags(inherited_tags, &include.tags);
+                    let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), vars.clone());
+                    let file = match evaluate_expression(&include.file, &ctx) {
+                        Ok(Value::String(file)) => file,
+                        _ => {
+                            listed.push(ListedTask {
+                                section: section.to_string(),
+                                name: "include_tasks (resolved at runtime)".to_string(),
+                                tags,
+                                unresolved: true,
+                            });
+                            continue;
+                        }
+                    };
+
+                    let tasks = parse_task_file(&self.resolve_task_file(&file))?;
+                    self.list_task_items(&tasks, section, &tags, vars, listed)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Task files are relative to the playbook directory, like in `execute_import`
+    fn resolve_task_file(&self, file: &str) -> std::path::PathBuf {
+        if Path::new(file).is_absolute() {
+            return Path::new(file).to_path_buf();
+        }
+        match *self.playbook_dir.lock() {
+            Some(ref dir) => dir.join(file),
+            None => Path::new(file).to_path_buf(),
+        }
+    }
+}
+
+fn merge_tags(inherited: &[String], own: &[String]) -> Vec<String> {
+    let mut tags = inherited.to_vec();
+    for tag in own {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::SchedulerConfig;
+    use crate::output::OutputWriter;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_list_tasks_expands_imports_and_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("web.nx.yml"),
+            r#"
+- name: Install nginx
+  command: "true"
+  tags: [install]
+"#,
+        )
+        .unwrap();
+        let playbook_path = dir.path().join("site.nx.yml");
+        let yaml = r#"
+hosts: webservers
+pre_tasks:
+  - name: Update cache
+    command: "true"
+tasks:
+  - import_tasks: web.nx.yml
+    tags: [web]
+  - name: Configure
+    tags: [config]
+    block:
+      - name: Write config
+        command: "true"
+        tags: [config, files]
+  - include_tasks: "${registered.file}"
+"#;
+        let playbook =
+            crate::parser::parse_playbook(yaml, playbook_path.to_string_lossy().to_string())
+                .unwrap();
+
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let listed = scheduler.list_tasks(&playbook).unwrap();
+
+        let summary: Vec<(&str, &str, Vec<&str>)> = listed
+            .iter()
+            .map(|t| {
+                (
+                    t.section.as_str(),
+                    t.name.as_str(),
+                    t.tags.iter().map(|s| s.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("pre_tasks", "Update cache", vec![]),
+                ("tasks", "Install nginx", vec!["web", "install"]),
+                ("tasks", "Write config", vec!["config", "files"]),
+                ("tasks", "include_tasks (resolved at runtime)", vec![]),
+            ]
+        );
+    }
+}
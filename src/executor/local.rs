@@ -1,9 +1,11 @@
 // Local command execution without SSH
 
 use async_trait::async_trait;
+use std::future::Future;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
 
 use super::{CommandResult, Connection};
 use crate::output::errors::NexusError;
@@ -11,6 +13,8 @@ use crate::output::errors::NexusError;
 /// Local connection for executing commands on localhost
 pub struct LocalConnection {
     host_name: String,
+    /// Commands still running after this long are killed
+    command_timeout: Option<Duration>,
 }
 
 impl LocalConnection {
@@ -18,6 +22,28 @@ impl LocalConnection {
     pub fn new(host_name: impl Into<String>) -> Self {
         LocalConnection {
             host_name: host_name.into(),
+            command_timeout: None,
+        }
+    }
+
+    /// Kill commands that run longer than `timeout`, failing with a timeout error
+    pub fn with_command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = Some(timeout);
+        self
+    }
+
+    /// Run `command`'s future, failing if it outlasts the command timeout. Commands are
+    /// spawned with `kill_on_drop`, so dropping the future kills the process.
+    async fn within_timeout<T>(
+        &self,
+        command: &str,
+        run: impl Future<Output = Result<T, NexusError>>,
+    ) -> Result<T, NexusError> {
+        match self.command_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, run)
+                .await
+                .map_err(|_| NexusError::command_timeout(&self.host_name, command, timeout))?,
+            None => run.await,
         }
     }
 
@@ -27,20 +53,64 @@ impl LocalConnection {
     }
 }
 
+/// A spawned command's process group, killed when dropped before `finish`: a command
+/// that times out or whose task is cancelled takes down everything its `sh -c`
+/// started, not just the shell
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    /// The command ran to completion; leave anything it started in the background
+    fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            // SAFETY: killpg only sends a signal; the group is the one spawn created
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Spawn `command` as the leader of a new process group
+fn spawn(command: &mut Command) -> std::io::Result<(Child, ProcessGroup)> {
+    #[cfg(unix)]
+    command.process_group(0);
+    let child = command.kill_on_drop(true).spawn()?;
+    let group = ProcessGroup(child.id());
+    Ok((child, group))
+}
+
 #[async_trait]
 impl Connection for LocalConnection {
     async fn exec(&self, cmd: &str) -> Result<CommandResult, NexusError> {
         // Execute command using sh -c
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .output()
-            .await
-            .map_err(|e| NexusError::Runtime {
-                function: None,
-                message: format!("Failed to execute local command: {}", e),
-                suggestion: Some("Check that 'sh' is available on the system".to_string()),
-            })?;
+        let spawn_error = |e: std::io::Error| NexusError::Runtime {
+            function: None,
+            message: format!("Failed to execute local command: {}", e),
+            suggestion: Some("Check that 'sh' is available on the system".to_string()),
+            location: None,
+        };
+        let run = async {
+            let (child, group) = spawn(
+                Command::new("sh")
+                    .arg("-c")
+                    .arg(cmd)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped()),
+            )
+            .map_err(spawn_error)?;
+            let output = child.wait_with_output().await.map_err(spawn_error)?;
+            group.finish();
+            Ok(output)
+        };
+        let output = self.within_timeout(cmd, run).await?;
 
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -53,6 +123,48 @@ impl Connection for LocalConnection {
         })
     }
 
+    async fn exec_with_stdin(&self, cmd: &str, stdin: &str) -> Result<CommandResult, NexusError> {
+        let spawn_error = |e: std::io::Error| NexusError::Runtime {
+            function: None,
+            message: format!("Failed to execute local command: {}", e),
+            suggestion: Some("Check that 'sh' is available on the system".to_string()),
+            location: None,
+        };
+
+        let (mut child, group) = spawn(
+            Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .map_err(spawn_error)?;
+
+        // Write while collecting output, so a command that fills its stdout pipe
+        // before reading all of its input can't deadlock us
+        let input = child.stdin.take();
+        let write_input = async move {
+            if let Some(mut input) = input {
+                // A command that exits without reading its input closes the pipe; that's fine
+                let _ = input.write_all(stdin.as_bytes()).await;
+            }
+        };
+        let run = async {
+            let ((), output) = tokio::join!(write_input, child.wait_with_output());
+            let output = output.map_err(spawn_error)?;
+            group.finish();
+            Ok(output)
+        };
+        let output = self.within_timeout(cmd, run).await?;
+
+        Ok(CommandResult {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code().unwrap_or(-1),
+        })
+    }
+
     async fn exec_streaming(
         &self,
         cmd: &str,
@@ -60,28 +172,32 @@ impl Connection for LocalConnection {
         on_stderr: Box<dyn Fn(String) + Send + Sync>,
     ) -> Result<CommandResult, NexusError> {
         // Execute command with streaming output
-        let mut child = Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| NexusError::Runtime {
-                function: None,
-                message: format!("Failed to spawn local command: {}", e),
-                suggestion: Some("Check that 'sh' is available on the system".to_string()),
-            })?;
+        let (mut child, group) = spawn(
+            Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .map_err(|e| NexusError::Runtime {
+            function: None,
+            message: format!("Failed to spawn local command: {}", e),
+            suggestion: Some("Check that 'sh' is available on the system".to_string()),
+            location: None,
+        })?;
 
         let stdout_handle = child.stdout.take().ok_or_else(|| NexusError::Runtime {
             function: None,
             message: "Failed to capture stdout".to_string(),
             suggestion: None,
+            location: None,
         })?;
 
         let stderr_handle = child.stderr.take().ok_or_else(|| NexusError::Runtime {
             function: None,
             message: "Failed to capture stderr".to_string(),
             suggestion: None,
+            location: None,
         })?;
 
         // Spawn tasks to read stdout and stderr
@@ -124,23 +240,38 @@ impl Connection for LocalConnection {
         });
 
         // Wait for command to complete
-        let status = child.wait().await.map_err(|e| NexusError::Runtime {
-            function: None,
-            message: format!("Failed to wait for command: {}", e),
-            suggestion: None,
-        })?;
+        let wait = async {
+            child.wait().await.map_err(|e| NexusError::Runtime {
+                function: None,
+                message: format!("Failed to wait for command: {}", e),
+                suggestion: None,
+                location: None,
+            })
+        };
+        let status = match self.within_timeout(cmd, wait).await {
+            Ok(status) => status,
+            Err(e) => {
+                // The readers finish once the killed command's pipes close
+                drop(group);
+                child.kill().await.ok();
+                return Err(e);
+            }
+        };
+        group.finish();
 
         // Collect output
         let stdout = stdout_task.await.map_err(|e| NexusError::Runtime {
             function: None,
             message: format!("Failed to read stdout: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         let stderr = stderr_task.await.map_err(|e| NexusError::Runtime {
             function: None,
             message: format!("Failed to read stderr: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         let exit_code = status.code().unwrap_or(-1);
@@ -199,6 +330,35 @@ mod tests {
         assert_eq!(result.exit_code, 1);
     }
 
+    #[tokio::test]
+    async fn test_local_exec_with_stdin() {
+        let conn = LocalConnection::new("localhost");
+        let result = conn
+            .exec_with_stdin("tr a-z A-Z", "piped input\n")
+            .await
+            .unwrap();
+
+        assert!(result.success());
+        assert_eq!(result.stdout, "PIPED INPUT\n");
+    }
+
+    #[tokio::test]
+    async fn test_local_exec_timeout() {
+        let conn = LocalConnection::new("localhost").with_command_timeout(Duration::from_secs(1));
+        let start = std::time::Instant::now();
+        let err = conn.exec("sleep 60").await.unwrap_err();
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(matches!(err, NexusError::Timeout { .. }));
+        assert!(err
+            .to_string()
+            .contains("command timed out after 1s on host localhost: sleep 60"));
+
+        let err = conn.exec_with_stdin("sleep 60", "").await.unwrap_err();
+        assert!(matches!(err, NexusError::Timeout { .. }));
+        assert!(conn.exec("echo fast").await.unwrap().success());
+    }
+
     #[test]
     fn test_should_use_local() {
         assert!(LocalConnection::should_use_local("localhost"));
@@ -7,30 +7,64 @@ pub mod async_jobs;
 pub mod checkpoint;
 pub mod context;
 pub mod dag;
+pub mod debugger;
+pub mod docker;
 pub mod facts;
 pub mod handlers;
 pub mod include_handler;
+pub mod listing;
 pub mod local;
+#[cfg(feature = "winrm")]
+mod ntlm;
 pub mod plan;
 pub mod retry;
 pub mod scheduler;
 pub mod ssh;
 pub mod tags;
+#[cfg(feature = "winrm")]
+pub mod winrm;
 
 pub use async_jobs::{AsyncJobTracker, JobId, JobStatus};
 pub use checkpoint::{Checkpoint, CheckpointInfo, CheckpointManager, TaskKey};
-pub use context::{ExecutionContext, TaskOutput};
+pub use context::{ExecutionContext, HostVars, TaskOutput};
 pub use dag::TaskDag;
-pub use facts::{FactCache, FactCategory, FactGatherer, HostFacts};
+pub use debugger::{DebugAction, TaskDebugger};
+pub use docker::DockerConnection;
+pub use facts::{FactCache, FactCategory, FactGatherer, HostFacts, DEFAULT_FACT_CACHE_TTL};
 pub use handlers::{FlushMode, HandlerConfig, HandlerRegistry};
+pub use listing::ListedTask;
 pub use local::LocalConnection;
 pub use plan::{ChangeType, ExecutionPlan, HostPlan, PlanGenerator, PlannedChange, SshConfig};
 pub use retry::{
     calculate_delay, CircuitBreaker, CircuitBreakerRegistry, CircuitState, RetryResult,
 };
-pub use scheduler::{Scheduler, SchedulerConfig};
-pub use ssh::{CommandResult, ConnectionPool, ConnectionType, SshConnection};
+pub use scheduler::{Forks, Scheduler, SchedulerConfig, AUTO_FORKS_CEILING};
+pub use ssh::{
+    CommandResult, ConnectionPool, ConnectionType, HostKeyChecking, SshConnection,
+    DEFAULT_CONNECTION_RETRIES, DEFAULT_MULTIPLEX_IDLE_TIMEOUT,
+};
 pub use tags::TagFilter;
+#[cfg(feature = "winrm")]
+pub use winrm::{WinRmAuth, WinRmConnection};
+
+/// Quote a string as a PowerShell single-quoted literal
+pub fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// The error for a WinRM host when nexus was built without the `winrm` feature
+#[cfg(not(feature = "winrm"))]
+pub(crate) fn winrm_unavailable(host: &crate::inventory::Host) -> NexusError {
+    NexusError::Runtime {
+        function: None,
+        message: format!(
+            "Host {} uses ansible_connection: winrm, but this build has no WinRM support",
+            host.name
+        ),
+        suggestion: Some("Rebuild nexus with the `winrm` feature enabled".to_string()),
+        location: None,
+    }
+}
 
 /// Common trait for all connection types (SSH, local, etc.)
 #[async_trait]
@@ -38,6 +72,9 @@ pub trait Connection: Send + Sync {
     /// Execute a command and return the result
     async fn exec(&self, cmd: &str) -> Result<CommandResult, NexusError>;
 
+    /// Execute a command with `stdin` written to its standard input
+    async fn exec_with_stdin(&self, cmd: &str, stdin: &str) -> Result<CommandResult, NexusError>;
+
     /// Execute a command with streaming output callbacks
     /// Note: For simplicity, callbacks receive owned Strings
     async fn exec_streaming(
@@ -55,4 +92,9 @@ pub trait Connection: Send + Sync {
 
     /// Get the host name for this connection
     fn host_name(&self) -> &str;
+
+    /// Whether commands run in PowerShell on Windows rather than in a POSIX shell
+    fn is_windows(&self) -> bool {
+        false
+    }
 }
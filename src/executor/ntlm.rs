@@ -0,0 +1,249 @@
+// NTLMv2 authentication messages (MS-NLMP), as used by WinRM over HTTP
+
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+const FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+/// AV pair id of the server's timestamp in the challenge's target info
+const MSV_AV_TIMESTAMP: u16 = 7;
+
+/// Seconds between 1601-01-01 (the FILETIME epoch) and the Unix epoch
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+/// What the server sent back in its CHALLENGE_MESSAGE
+#[derive(Debug, Clone)]
+pub(crate) struct Challenge {
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+/// First leg: the NEGOTIATE_MESSAGE, with empty domain and workstation
+pub(crate) fn negotiate_message() -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32);
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend_from_slice(&1u32.to_le_bytes());
+    msg.extend_from_slice(&FLAGS.to_le_bytes());
+    // Domain and workstation fields: length, max length, offset
+    msg.extend_from_slice(&[0; 16]);
+    msg
+}
+
+/// Read the server challenge and target info out of a CHALLENGE_MESSAGE
+pub(crate) fn parse_challenge(msg: &[u8]) -> Result<Challenge, String> {
+    if msg.len() < 48 || &msg[..8] != SIGNATURE || msg[8..12] != 2u32.to_le_bytes() {
+        return Err("not an NTLM challenge message".to_string());
+    }
+
+    let mut server_challenge = [0; 8];
+    server_challenge.copy_from_slice(&msg[24..32]);
+
+    let len = u16::from_le_bytes([msg[40], msg[41]]) as usize;
+    let offset = u32::from_le_bytes([msg[44], msg[45], msg[46], msg[47]]) as usize;
+    let target_info = msg
+        .get(offset..offset + len)
+        .ok_or("NTLM challenge target info is out of bounds")?
+        .to_vec();
+
+    Ok(Challenge {
+        server_challenge,
+        target_info,
+    })
+}
+
+/// Third leg: the AUTHENTICATE_MESSAGE answering `challenge` with NTLMv2 responses.
+/// `user` may be `DOMAIN\user`; a UPN (`user@domain`) is sent as the user name.
+pub(crate) fn authenticate_message(
+    challenge: &Challenge,
+    user: &str,
+    password: &str,
+    client_challenge: [u8; 8],
+) -> Vec<u8> {
+    let (domain, user) = match user.split_once('\\') {
+        Some((domain, user)) => (domain, user),
+        None => ("", user),
+    };
+    let timestamp = server_timestamp(&challenge.target_info).unwrap_or_else(now_filetime);
+
+    let key = ntowf_v2(user, password, domain);
+    let (lm_response, nt_response) = responses(&key, challenge, client_challenge, timestamp);
+
+    let fields = [
+        lm_response,
+        nt_response,
+        utf16le(domain),
+        utf16le(user),
+        Vec::new(), // workstation
+        Vec::new(), // encrypted random session key
+    ];
+
+    let header_len = 64;
+    let mut msg = Vec::new();
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = header_len;
+    for field in &fields {
+        let len = field.len() as u16;
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    msg.extend_from_slice(&FLAGS.to_le_bytes());
+    for field in &fields {
+        msg.extend_from_slice(field);
+    }
+    msg
+}
+
+/// The LMv2 and NTLMv2 challenge responses
+fn responses(
+    key: &[u8; 16],
+    challenge: &Challenge,
+    client_challenge: [u8; 8],
+    timestamp: u64,
+) -> (Vec<u8>, Vec<u8>) {
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.to_le_bytes());
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(&challenge.target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let mut nt_response = hmac_md5(key, &[&challenge.server_challenge, &blob]).to_vec();
+    nt_response.extend_from_slice(&blob);
+
+    let mut lm_response = hmac_md5(key, &[&challenge.server_challenge, &client_challenge]).to_vec();
+    lm_response.extend_from_slice(&client_challenge);
+
+    (lm_response, nt_response)
+}
+
+/// NTOWFv2: the NTLMv2 key derived from the password, user and domain
+fn ntowf_v2(user: &str, password: &str, domain: &str) -> [u8; 16] {
+    let nt_hash = Md4::digest(utf16le(password));
+    let identity = utf16le(&format!("{}{}", user.to_uppercase(), domain));
+    hmac_md5(&nt_hash, &[&identity])
+}
+
+fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = <Hmac<Md5> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// The MsvAvTimestamp AV pair from the challenge's target info, if the server sent one
+fn server_timestamp(target_info: &[u8]) -> Option<u64> {
+    let mut rest = target_info;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let value = rest.get(4..4 + len)?;
+        if id == MSV_AV_TIMESTAMP && len == 8 {
+            return Some(u64::from_le_bytes(value.try_into().ok()?));
+        }
+        if id == 0 {
+            break;
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
+
+/// Current time as a Windows FILETIME (100ns ticks since 1601)
+fn now_filetime() -> u64 {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    (since_epoch.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000
+        + u64::from(since_epoch.subsec_nanos()) / 100
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The NTLMv2 example from MS-NLMP section 4.2.4
+    #[test]
+    fn test_ntlmv2_responses_match_spec() {
+        let key = ntowf_v2("User", "Password", "Domain");
+        assert_eq!(hex(&key), "0c868a403bfd7a93a3001ef22ef02e3f");
+
+        let mut target_info = vec![0x02, 0x00, 0x0c, 0x00];
+        target_info.extend(utf16le("Domain"));
+        target_info.extend([0x01, 0x00, 0x0c, 0x00]);
+        target_info.extend(utf16le("Server"));
+        target_info.extend([0; 4]);
+        let challenge = Challenge {
+            server_challenge: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+            target_info,
+        };
+
+        let (lm, nt) = responses(&key, &challenge, [0xaa; 8], 0);
+        assert_eq!(hex(&lm), "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa");
+        assert_eq!(hex(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+    }
+
+    #[test]
+    fn test_challenge_round_trip() {
+        let mut target_info = vec![0x07, 0x00, 0x08, 0x00];
+        target_info.extend(42u64.to_le_bytes());
+        target_info.extend([0; 4]);
+
+        let mut msg = SIGNATURE.to_vec();
+        msg.extend(2u32.to_le_bytes());
+        msg.extend([0; 8]); // target name
+        msg.extend(FLAGS.to_le_bytes());
+        msg.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+        msg.extend([0; 8]); // reserved
+        msg.extend((target_info.len() as u16).to_le_bytes());
+        msg.extend((target_info.len() as u16).to_le_bytes());
+        msg.extend(48u32.to_le_bytes());
+        msg.extend(&target_info);
+
+        let challenge = parse_challenge(&msg).unwrap();
+        assert_eq!(challenge.server_challenge, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(server_timestamp(&challenge.target_info), Some(42));
+
+        let auth = authenticate_message(&challenge, "CORP\\admin", "secret", [0xaa; 8]);
+        assert_eq!(&auth[..8], SIGNATURE);
+        assert_eq!(auth[8..12], 3u32.to_le_bytes());
+        // The user name field points at "admin" in UTF-16
+        let len = u16::from_le_bytes([auth[36], auth[37]]) as usize;
+        let offset = u32::from_le_bytes([auth[40], auth[41], auth[42], auth[43]]) as usize;
+        assert_eq!(&auth[offset..offset + len], utf16le("admin").as_slice());
+
+        assert!(parse_challenge(&negotiate_message()).is_err());
+    }
+}
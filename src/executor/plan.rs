@@ -4,8 +4,8 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use crate::executor::ExecutionContext;
-use crate::inventory::Inventory;
-use crate::modules::{AnyConnection, ModuleExecutor};
+use crate::inventory::{resolve_limit, HostFilter, Inventory};
+use crate::modules::{skip_for_guards, AnyConnection, ModuleExecutor, TemplateEngine};
 use crate::output::errors::NexusError;
 use crate::parser::ast::{
     FileState, ModuleCall, PackageState, Playbook, ServiceState, Task, TaskOrBlock, UserState,
@@ -133,6 +133,11 @@ impl ExecutionPlan {
             estimated_duration,
         }
     }
+
+    /// Whether applying the plan would create, modify or remove anything
+    pub fn has_changes(&self) -> bool {
+        self.creates + self.modifies + self.removes > 0
+    }
 }
 
 /// Plan generator
@@ -160,27 +165,9 @@ impl PlanGenerator {
 
         // Apply limit filter if specified
         if let Some(limit_pattern) = limit {
-            hosts.retain(|host| {
-                // Match against hostname or pattern
-                if limit_pattern.contains('*') || limit_pattern.contains('?') {
-                    // Wildcard pattern matching
-                    let pattern = limit_pattern.replace('*', ".*").replace('?', ".");
-                    if let Ok(re) = regex::Regex::new(&format!("^{}$", pattern)) {
-                        re.is_match(&host.name)
-                    } else {
-                        host.name == limit_pattern
-                    }
-                } else if limit_pattern.contains(',') {
-                    // Comma-separated list of hosts
-                    limit_pattern
-                        .split(',')
-                        .map(str::trim)
-                        .any(|h| h == host.name)
-                } else {
-                    // Exact match
-                    host.name == limit_pattern
-                }
-            });
+            hosts = HostFilter::new()
+                .with_limit(resolve_limit(inventory, limit_pattern)?)
+                .apply(hosts);
         }
 
         let mut host_plans = Vec::new();
@@ -200,9 +187,24 @@ impl PlanGenerator {
         }
 
         for host in hosts {
-            // Get connection from pool
-            let ssh_conn = pool.get(host)?;
-            let conn = AnyConnection::Ssh(ssh_conn);
+            // Same connection choice as the scheduler, so localhost is checked locally
+            let conn = match pool.get_connection_type(host) {
+                crate::executor::ssh::ConnectionType::Local => {
+                    AnyConnection::Local(crate::executor::LocalConnection::new(&host.name))
+                }
+                crate::executor::ssh::ConnectionType::Docker => {
+                    AnyConnection::Docker(crate::executor::DockerConnection::for_host(host))
+                }
+                #[cfg(feature = "winrm")]
+                crate::executor::ssh::ConnectionType::WinRm => {
+                    AnyConnection::WinRm(crate::executor::WinRmConnection::for_host(host, &pool)?)
+                }
+                #[cfg(not(feature = "winrm"))]
+                crate::executor::ssh::ConnectionType::WinRm => {
+                    return Err(crate::executor::winrm_unavailable(host))
+                }
+                crate::executor::ssh::ConnectionType::Ssh => AnyConnection::Ssh(pool.get(host)?),
+            };
 
             // Generate changes for each task
             let mut changes = Vec::new();
@@ -304,10 +306,34 @@ impl PlanGenerator {
         conn: &AnyConnection,
         host: &str,
     ) -> Result<PlannedChange, NexusError> {
+        // A file or template task held back by its creates/removes guard changes nothing
+        if let ModuleCall::File {
+            creates, removes, ..
+        }
+        | ModuleCall::Template {
+            creates, removes, ..
+        } = &task.module
+        {
+            if let Some(skipped) =
+                skip_for_guards(ctx, conn.as_connection(), creates, removes).await?
+            {
+                return Ok(PlannedChange {
+                    task_name: task.name.clone(),
+                    module: task.module.module_name().to_string(),
+                    change_type: ChangeType::NoChange,
+                    current_state: Some(skipped.stdout),
+                    desired_state: None,
+                    diff: None,
+                    is_dangerous: false,
+                    danger_reason: None,
+                });
+            }
+        }
+
         match &task.module {
-            ModuleCall::Package { name, state } => {
-                let name_val = evaluate_expression(name, ctx)?;
-                self.check_package_state(conn, host, &name_val.to_string(), *state, &task.name)
+            ModuleCall::Package { name, state, .. } => {
+                let names = crate::modules::evaluate_names(name, ctx)?;
+                self.check_package_state(conn, host, &names, *state, &task.name)
                     .await
             }
 
@@ -350,10 +376,38 @@ impl PlanGenerator {
                 .await
             }
 
+            ModuleCall::Template { src, dest, .. } => {
+                let src_val = evaluate_expression(src, ctx)?.to_string();
+                let dest_val = evaluate_expression(dest, ctx)?.to_string();
+
+                // Render locally the same way the template module does, then compare
+                // against the remote file like any other managed content
+                let src_path = std::path::Path::new(&src_val);
+                let mut engine = TemplateEngine::new();
+                if let Some(parent) = src_path.parent() {
+                    engine.add_search_path(parent.to_string_lossy().to_string());
+                }
+                let rendered = engine.render_file(src_path, ctx)?;
+
+                let mut change = self
+                    .check_file_state(
+                        conn,
+                        host,
+                        &dest_val,
+                        FileState::File,
+                        Some(rendered),
+                        &task.name,
+                    )
+                    .await?;
+                change.module = "template".to_string();
+                Ok(change)
+            }
+
             ModuleCall::Command {
                 cmd,
                 creates,
                 removes,
+                ..
             } => {
                 let cmd_val = evaluate_expression(cmd, ctx)?;
                 let creates_val = creates
@@ -402,7 +456,7 @@ impl PlanGenerator {
         &self,
         conn: &AnyConnection,
         _host: &str,
-        name: &str,
+        names: &[String],
         state: PackageState,
         task_name: &str,
     ) -> Result<PlannedChange, NexusError> {
@@ -410,12 +464,21 @@ impl PlanGenerator {
 
         // Detect package manager
         let manager = crate::modules::detect_package_manager(connection).await?;
-        let check_cmd = manager.check_installed_cmd(name);
-        let is_installed = connection.exec(&check_cmd).await?.success();
+        let mut installed_count = 0;
+        for name in names {
+            // Version pins don't affect presence
+            let package = name.split('=').next().unwrap_or(name).trim();
+            let check_cmd = manager.check_installed_cmd(package);
+            if connection.exec(&check_cmd).await?.success() {
+                installed_count += 1;
+            }
+        }
+        let all_installed = installed_count == names.len();
+        let any_installed = installed_count > 0;
 
         let (change_type, current, desired) = match state {
             PackageState::Installed => {
-                if is_installed {
+                if all_installed {
                     (
                         ChangeType::NoChange,
                         Some("installed".to_string()),
@@ -430,7 +493,7 @@ impl PlanGenerator {
                 }
             }
             PackageState::Latest => {
-                if is_installed {
+                if all_installed {
                     (
                         ChangeType::Modify,
                         Some("current version".to_string()),
@@ -445,7 +508,7 @@ impl PlanGenerator {
                 }
             }
             PackageState::Absent => {
-                if is_installed {
+                if any_installed {
                     (
                         ChangeType::Remove,
                         Some("installed".to_string()),
@@ -621,7 +684,12 @@ impl PlanGenerator {
                             ChangeType::Create,
                             Some("does not exist".to_string()),
                             Some("will be created".to_string()),
-                            None,
+                            Some(crate::output::diff::generate_unified_diff(
+                                "",
+                                new_content,
+                                "/dev/null",
+                                &format!("{} (desired)", path),
+                            )),
                         )
                     }
                 } else if exists {
@@ -844,6 +912,7 @@ impl Default for PlanGenerator {
 }
 
 /// SSH configuration for planning
+#[derive(Clone)]
 pub struct SshConfig {
     pub user: Option<String>,
     pub password: Option<String>,
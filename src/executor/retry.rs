@@ -39,6 +39,11 @@ impl CircuitBreakerRegistry {
         circuit
     }
 
+    /// Forget a circuit, so its next use starts closed again
+    pub fn remove(&self, name: &str) {
+        self.circuits.write().remove(name);
+    }
+
     /// Get circuit status for reporting
     pub fn get_status(&self, name: &str) -> Option<CircuitState> {
         let circuits = self.circuits.read();
@@ -1,6 +1,9 @@
 // Parallel task scheduler
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -11,27 +14,37 @@ use tokio::sync::Semaphore;
 
 use super::async_jobs::AsyncJobTracker;
 use super::checkpoint::{Checkpoint, CheckpointManager};
-use super::context::{ExecutionContext, TaskOutput};
+use super::context::{ExecutionContext, HostVars, TaskOutput, NO_LOG_MESSAGE};
 use super::dag::TaskDag;
+use super::debugger::{DebugAction, TaskDebugger};
+use super::facts::{FactCache, DEFAULT_FACT_CACHE_TTL};
 use super::handlers::HandlerRegistry;
 use super::retry::{calculate_delay, CircuitBreakerRegistry};
-use super::ssh::ConnectionPool;
+use super::ssh::{
+    ConnectionPool, ConnectionType, HostKeyChecking, DEFAULT_CONNECTION_RETRIES,
+    DEFAULT_MULTIPLEX_IDLE_TIMEOUT,
+};
 use super::tags::TagFilter;
-use crate::inventory::{Host, Inventory};
-use crate::modules::ModuleExecutor;
+use crate::inventory::{resolve_limit, Host, HostFilter, Inventory};
+use crate::modules::{evaluate_environment, prefix_environment, AnyConnection, ModuleExecutor};
 use crate::output::errors::NexusError;
 use crate::output::events::{EventEmitter, TaskStatus};
 use crate::output::terminal::{PlayRecap, TaskResult};
 use crate::output::OutputWriter;
-use crate::parser::ast::{Block, Handler, Playbook, Serial, Task, TaskOrBlock, Value};
+use crate::parser::ast::{
+    Block, Expression, Handler, MetaAction, ModuleCall, Playbook, Serial, SerialBatch, Task,
+    TaskOrBlock, Value, VarsPrompt,
+};
+use crate::parser::load_vars_file;
 use crate::parser::roles::RoleResolver;
 use crate::plugins::CallbackManager;
 use crate::runtime::evaluate_expression;
+use crate::vault::VaultSecrets;
 
 /// Configuration for the scheduler
 #[derive(Debug, Clone)]
 pub struct SchedulerConfig {
-    /// Maximum concurrent hosts
+    /// Maximum concurrent hosts (0 = all of a task's hosts at once)
     pub max_parallel_hosts: usize,
     /// Maximum concurrent tasks per host
     pub max_parallel_tasks: usize,
@@ -45,6 +58,8 @@ pub struct SchedulerConfig {
     pub diff_mode: bool,
     /// Verbose output
     pub verbose: bool,
+    /// Number of `-v` flags, compared against `debug` tasks' `verbosity`
+    pub verbosity: u8,
     /// SSH password for authentication
     pub ssh_password: Option<String>,
     /// SSH private key path
@@ -53,6 +68,9 @@ pub struct SchedulerConfig {
     pub ssh_user: Option<String>,
     /// Enable sudo for all tasks (CLI override)
     pub sudo: bool,
+    /// User sudo runs tasks as (`--become-user`), over the playbook's `sudo_user`;
+    /// a task's `run_as` still wins
+    pub sudo_user: Option<String>,
     /// Sudo password for privilege escalation
     pub sudo_password: Option<String>,
     /// Tag filter for selecting tasks
@@ -63,6 +81,34 @@ pub struct SchedulerConfig {
     pub resume: bool,
     /// Resume from specific checkpoint file
     pub resume_from: Option<std::path::PathBuf>,
+    /// Reuse one persistent SSH session per host, opening a channel per command
+    pub ssh_multiplex: bool,
+    /// Close multiplexed SSH sessions after this much idle time
+    pub ssh_idle_timeout: Duration,
+    /// Retries (with exponential backoff) after a transient SSH connection failure
+    pub connection_retries: u32,
+    /// How SSH host keys are verified against known_hosts
+    pub host_key_checking: HostKeyChecking,
+    /// Vault passwords (by vault ID) for decrypting `vars_files` and inline `!vault` values
+    pub vault_secrets: VaultSecrets,
+    /// Variables from the command line; these take precedence over everything else
+    pub extra_vars: HashMap<String, Value>,
+    /// Skip tasks until the first one with this name (`--start-at-task`)
+    pub start_at_task: Option<String>,
+    /// Cache gathered facts in this directory and reuse them on later runs
+    pub fact_cache_dir: Option<std::path::PathBuf>,
+    /// How long cached facts stay valid
+    pub fact_cache_ttl: Duration,
+    /// Only run against the play's hosts that also match this pattern (`--limit`)
+    pub limit: Option<String>,
+    /// Fact categories auto-gathering collects unless the play sets `gather_subset`
+    /// (`--gather-subset`, empty = all)
+    pub gather_subset: Vec<String>,
+    /// Directories searched, in order, for modules that aren't built in (`--module-path`)
+    pub module_paths: Vec<std::path::PathBuf>,
+    /// Prompt on the terminal when a task fails, to inspect, retry, continue or abort
+    /// (`--debug-on-failure`; only for interactive text output)
+    pub debug_on_failure: bool,
 }
 
 impl Default for SchedulerConfig {
@@ -75,19 +121,103 @@ impl Default for SchedulerConfig {
             check_mode: false,
             diff_mode: false,
             verbose: false,
+            verbosity: 0,
             ssh_password: None,
             ssh_private_key: None,
             ssh_user: None,
             sudo: false,
+            sudo_user: None,
             sudo_password: None,
             tag_filter: None,
             enable_checkpoints: false,
             resume: false,
             resume_from: None,
+            ssh_multiplex: false,
+            ssh_idle_timeout: DEFAULT_MULTIPLEX_IDLE_TIMEOUT,
+            connection_retries: DEFAULT_CONNECTION_RETRIES,
+            host_key_checking: HostKeyChecking::default(),
+            vault_secrets: VaultSecrets::default(),
+            extra_vars: HashMap::new(),
+            start_at_task: None,
+            fact_cache_dir: None,
+            fact_cache_ttl: DEFAULT_FACT_CACHE_TTL,
+            limit: None,
+            gather_subset: Vec::new(),
+            module_paths: Vec::new(),
+            debug_on_failure: false,
         }
     }
 }
 
+/// Most forks `--forks auto` will pick, however many CPUs and file descriptors there are
+pub const AUTO_FORKS_CEILING: usize = 256;
+
+/// File descriptors set aside for the process itself (logs, callbacks, stdio)
+const RESERVED_FDS: u64 = 64;
+
+/// File descriptors a host in flight can hold: its socket, plus pipes for
+/// local commands and the multiplexed session's channels
+const FDS_PER_FORK: u64 = 4;
+
+/// How many hosts run a task at once (`--forks`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forks {
+    /// At most this many hosts; 0 runs all of them at once
+    Count(usize),
+    /// Pick a count from the CPUs and the open file limit
+    Auto,
+}
+
+impl Forks {
+    /// The `max_parallel_hosts` to schedule with
+    pub fn resolve(self) -> usize {
+        match self {
+            Forks::Count(n) => n,
+            Forks::Auto => auto_forks(
+                std::thread::available_parallelism().map_or(1, |n| n.get()),
+                open_file_limit(),
+            ),
+        }
+    }
+}
+
+impl FromStr for Forks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Forks::Auto);
+        }
+        s.parse().map(Forks::Count).map_err(|_| {
+            format!(
+                "invalid forks '{}' (expected a number, 0 for unlimited, or auto)",
+                s
+            )
+        })
+    }
+}
+
+/// Forks are mostly waiting on the network, so allow many per CPU, but keep
+/// enough file descriptors free that connections can't run the process out
+fn auto_forks(cpus: usize, fd_limit: Option<u64>) -> usize {
+    let by_cpu = cpus.saturating_mul(16);
+    let by_fds = fd_limit.map_or(usize::MAX, |limit| {
+        (limit.saturating_sub(RESERVED_FDS) / FDS_PER_FORK) as usize
+    });
+    by_cpu.min(by_fds).clamp(1, AUTO_FORKS_CEILING)
+}
+
+/// The soft limit on open files, from /proc on Linux
+fn open_file_limit() -> Option<u64> {
+    let limits = std::fs::read_to_string("/proc/self/limits").ok()?;
+    let line = limits.lines().find(|l| l.starts_with("Max open files"))?;
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
 /// The task scheduler
 #[allow(dead_code)]
 pub struct Scheduler {
@@ -98,7 +228,7 @@ pub struct Scheduler {
     /// Circuit breaker registry for retry logic
     circuit_breakers: Arc<CircuitBreakerRegistry>,
     /// Role resolver for loading roles
-    role_resolver: Mutex<RoleResolver>,
+    pub(super) role_resolver: Mutex<RoleResolver>,
     /// Async job tracker for background tasks
     async_tracker: Arc<AsyncJobTracker>,
     /// Callback manager for plugins
@@ -113,6 +243,31 @@ pub struct Scheduler {
     pub(super) playbook_dir: Arc<Mutex<Option<std::path::PathBuf>>>,
     /// Per-host execution contexts that persist registered variables across tasks
     host_contexts: Arc<DashMap<String, ExecutionContext>>,
+    /// Every play host's variables, for `hostvars`
+    hostvars: HostVars,
+    /// Facts gathered for each host in the current play
+    host_facts: Arc<DashMap<String, HashMap<String, Value>>>,
+    /// `vars:` of the block being run, for its tasks
+    block_vars: Arc<Mutex<HashMap<String, Expression>>>,
+    /// Handler definitions for the current play (playbook and role handlers)
+    play_handlers: Arc<Mutex<Vec<Handler>>>,
+    /// The current play's `environment:`, inherited by its command and shell tasks
+    play_environment: Arc<Mutex<HashMap<String, Expression>>>,
+    /// The current play's `throttle:`, the default for tasks without their own
+    play_throttle: Arc<Mutex<Option<usize>>>,
+    /// Set by `meta: end_play` to skip the rest of the current play
+    play_ended: Arc<AtomicBool>,
+    /// Whether the `start_at_task` task has been reached; tasks before it are skipped
+    start_at_reached: Arc<AtomicBool>,
+    /// Gathered facts and cacheable `set_fact` values, on disk with `fact_cache_dir`
+    fact_cache: Arc<FactCache>,
+    /// `(group, host)` memberships added by `group_by`, applied to the inventory of
+    /// every later play
+    dynamic_groups: Arc<Mutex<Vec<(String, String)>>>,
+    /// Prompt for failed tasks, with `debug_on_failure`
+    debugger: Option<Arc<TaskDebugger>>,
+    /// Set when the user quits from the debugger prompt
+    debug_aborted: Arc<AtomicBool>,
 }
 
 impl Scheduler {
@@ -127,7 +282,11 @@ impl Scheduler {
     ) -> Self {
         let mut pool = ConnectionPool::new()
             .with_connect_timeout(config.connect_timeout)
-            .with_command_timeout(config.command_timeout);
+            .with_command_timeout(config.command_timeout)
+            .with_multiplex(config.ssh_multiplex)
+            .with_idle_timeout(config.ssh_idle_timeout)
+            .with_connect_retries(config.connection_retries)
+            .with_host_key_checking(config.host_key_checking);
 
         if let Some(ref password) = config.ssh_password {
             pool = pool.with_password(password.clone());
@@ -139,10 +298,20 @@ impl Scheduler {
             pool = pool.with_default_user(user.clone());
         }
 
+        let mut fact_cache = FactCache::with_ttl(config.fact_cache_ttl);
+        if let Some(ref dir) = config.fact_cache_dir {
+            fact_cache = fact_cache.with_cache_dir(dir);
+        }
+
+        let modules = ModuleExecutor::new().with_module_paths(config.module_paths.clone());
+        let debugger = config
+            .debug_on_failure
+            .then(|| Arc::new(TaskDebugger::new(output.clone())));
+
         Scheduler {
             config,
             pool: Arc::new(pool),
-            modules: Arc::new(ModuleExecutor::new()),
+            modules: Arc::new(modules),
             output,
             circuit_breakers: Arc::new(CircuitBreakerRegistry::new()),
             role_resolver: Mutex::new(RoleResolver::new()),
@@ -153,6 +322,18 @@ impl Scheduler {
             event_emitter: None,
             playbook_dir: Arc::new(Mutex::new(None)),
             host_contexts: Arc::new(DashMap::new()),
+            hostvars: HostVars::new(),
+            host_facts: Arc::new(DashMap::new()),
+            block_vars: Arc::new(Mutex::new(HashMap::new())),
+            play_handlers: Arc::new(Mutex::new(Vec::new())),
+            play_environment: Arc::new(Mutex::new(HashMap::new())),
+            play_throttle: Arc::new(Mutex::new(None)),
+            play_ended: Arc::new(AtomicBool::new(false)),
+            start_at_reached: Arc::new(AtomicBool::new(true)),
+            fact_cache: Arc::new(fact_cache),
+            dynamic_groups: Arc::new(Mutex::new(Vec::new())),
+            debugger,
+            debug_aborted: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -182,23 +363,274 @@ impl Scheduler {
     ) -> ExecutionContext {
         self.host_contexts
             .entry(host.name.clone())
-            .or_insert_with(|| ExecutionContext::new(Arc::new(host.clone()), playbook_vars.clone()))
+            .or_insert_with(|| {
+                self.new_context(host, playbook_vars)
+                    .with_hostvars(self.hostvars.clone())
+            })
             .clone()
     }
 
+    /// `vars:` of a block or task, less those given as extra vars, which win
+    fn without_extra_vars(
+        &self,
+        vars: &HashMap<String, Expression>,
+    ) -> HashMap<String, Expression> {
+        vars.iter()
+            .filter(|(key, _)| !self.config.extra_vars.contains_key(*key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// The host's context for a task, with the vars of the block it is in
+    fn task_context(
+        &self,
+        host: &Host,
+        playbook_vars: &HashMap<String, Value>,
+    ) -> ExecutionContext {
+        self.get_or_create_context(host, playbook_vars)
+            .with_scoped_vars(self.block_vars.lock().clone())
+    }
+
+    /// A fresh context for `host`: play vars, the host's facts, cached `set_fact`
+    /// values and extra vars, in increasing precedence
+    fn new_context(&self, host: &Host, playbook_vars: &HashMap<String, Value>) -> ExecutionContext {
+        let ctx = ExecutionContext::new(Arc::new(host.clone()), playbook_vars.clone())
+            .with_environment(self.play_environment.lock().clone());
+        // This host's own facts, where the play vars hold the last host gathered
+        if let Some(facts) = self.host_facts.get(&host.name) {
+            for (key, value) in facts.iter() {
+                ctx.set_var(key.clone(), value.clone());
+            }
+        }
+        // Cacheable facts from earlier plays and runs
+        for (key, value) in self.fact_cache.cached_set_facts(&host.name) {
+            ctx.set_var(key, value);
+        }
+        // Extra vars win over host vars too
+        for (key, value) in &self.config.extra_vars {
+            ctx.set_var(key, value.clone());
+        }
+        ctx
+    }
+
+    /// Give `hostvars` an entry for each of `hosts` that has no context yet, so
+    /// hosts can read each other's variables before they have run a task
+    fn init_hostvars(&self, hosts: &[&Host], playbook_vars: &HashMap<String, Value>) {
+        for host in hosts {
+            if !self.host_contexts.contains_key(&host.name) {
+                let vars = self.new_context(host, playbook_vars).all_vars();
+                self.hostvars.insert(host.name.clone(), vars);
+            }
+        }
+    }
+
+    /// Build the play-level variables.
+    /// Precedence (lowest to highest): playbook vars, vars_files, vars_prompt, extra vars.
+    fn resolve_play_vars(&self, playbook: &Playbook) -> Result<HashMap<String, Value>, NexusError> {
+        let mut vars = playbook.vars.clone();
+
+        let playbook_dir = std::path::Path::new(&playbook.source_file)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        for file in &playbook.vars_files {
+            let path = playbook_dir.join(file);
+            vars.extend(load_vars_file(&path, Some(&self.config.vault_secrets))?);
+        }
+
+        for prompt in &playbook.vars_prompt {
+            // A value supplied with -e skips the prompt
+            if self.config.extra_vars.contains_key(&prompt.name) {
+                continue;
+            }
+            vars.insert(prompt.name.clone(), prompt_for_var(prompt)?);
+        }
+
+        vars.extend(self.config.extra_vars.clone());
+        Ok(vars)
+    }
+
     /// Clear host contexts (should be called at start of playbook execution)
     fn clear_host_contexts(&self) {
         self.host_contexts.clear();
+        self.hostvars.clear();
+        self.host_facts.clear();
+    }
+
+    /// Whether `meta: end_play` has stopped the current play
+    fn play_ended(&self) -> bool {
+        self.play_ended.load(Ordering::SeqCst)
+    }
+
+    /// Start skipping tasks again until `start_at_task` is reached
+    fn reset_start_at_task(&self) {
+        self.start_at_reached
+            .store(self.config.start_at_task.is_none(), Ordering::SeqCst);
+    }
+
+    /// Whether `task` comes before `start_at_task` and should be skipped.
+    /// The first task with a matching name (in any role, block or include) starts the run.
+    fn before_start_at_task(&self, task: &Task) -> bool {
+        if self.start_at_reached.load(Ordering::SeqCst) {
+            return false;
+        }
+        if self.config.start_at_task.as_deref() == Some(task.name.as_str()) {
+            self.start_at_reached.store(true, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+
+    /// Fail early when `start_at_task` names no task in any of the plays.
+    /// Includes only resolvable at runtime might contain it, so those let the run go ahead.
+    fn check_start_at_task(&self, plays: &[Playbook]) -> Result<(), NexusError> {
+        let Some(ref name) = self.config.start_at_task else {
+            return Ok(());
+        };
+        for play in plays {
+            let listed = self.list_tasks(play)?;
+            if listed.iter().any(|t| &t.name == name || t.unresolved) {
+                return Ok(());
+            }
+        }
+        Err(NexusError::Runtime {
+            function: None,
+            message: format!("--start-at-task: no task named '{}' in the playbook", name),
+            suggestion: Some("Run with --list-tasks to see the task names".to_string()),
+            location: None,
+        })
+    }
+
+    /// Count the play's tasks per host, including role tasks
+    fn count_play_tasks(&self, playbook: &Playbook) -> usize {
+        let mut total_tasks =
+            playbook.tasks.len() + playbook.pre_tasks.len() + playbook.post_tasks.len();
+
+        // Add tasks from roles
+        for role_ref in &playbook.roles {
+            // Resolve role dependencies to get all roles that will be executed
+            let role_execution_order = {
+                let mut resolver = self.role_resolver.lock();
+                match resolver.resolve_dependencies(&role_ref.role) {
+                    Ok(order) => order,
+                    Err(_) => continue, // Skip counting if resolution fails
+                }
+            };
+
+            // Count tasks in each role
+            for role_name in role_execution_order {
+                let role = {
+                    let mut resolver = self.role_resolver.lock();
+                    match resolver.resolve(&role_name) {
+                        Ok(r) => r.clone(),
+                        Err(_) => continue,
+                    }
+                };
+                total_tasks += role.tasks.len();
+            }
+        }
+
+        total_tasks
+    }
+
+    /// Execute the plays of a playbook in order, each against the hosts its own
+    /// pattern matches in the shared inventory. A play that leaves failed or
+    /// unreachable hosts stops the run; the returned recap covers every play that ran.
+    pub async fn execute_plays(
+        &self,
+        plays: &[Playbook],
+        inventory: &Inventory,
+    ) -> Result<PlayRecap, NexusError> {
+        // `start_at_task` can name a task of any play; the plays before it are skipped
+        self.check_start_at_task(plays)?;
+        self.reset_start_at_task();
+        self.dynamic_groups.lock().clear();
+
+        let start_time = Instant::now();
+        let mut recap = PlayRecap::new();
+        recap.check_mode = self.config.check_mode;
+
+        for play in plays {
+            recap.merge(self.execute_play(play, inventory).await?);
+            if recap.has_failures() {
+                break;
+            }
+        }
+
+        recap.total_duration = start_time.elapsed();
+        Ok(recap)
     }
 
-    /// Execute a playbook
+    /// Execute a playbook that is a single play
     pub async fn execute_playbook(
         &self,
         playbook: &Playbook,
         inventory: &Inventory,
+    ) -> Result<PlayRecap, NexusError> {
+        self.execute_plays(std::slice::from_ref(playbook), inventory)
+            .await
+    }
+
+    /// The hosts a play runs on: those its `hosts:` pattern matches in `inventory`,
+    /// narrowed by `--limit`. Fails if the pattern or the limit matches nothing.
+    pub fn play_hosts<'a>(
+        &self,
+        playbook: &Playbook,
+        inventory: &'a Inventory,
+    ) -> Result<Vec<&'a Host>, NexusError> {
+        let mut hosts = inventory.get_hosts(&playbook.hosts);
+
+        if hosts.is_empty() {
+            return Err(NexusError::Inventory {
+                message: format!("No hosts matched pattern: {:?}", playbook.hosts),
+                suggestion: Some("Check your inventory and host pattern".to_string()),
+            });
+        }
+
+        if let Some(limit) = &self.config.limit {
+            hosts = HostFilter::new()
+                .with_limit(resolve_limit(inventory, limit)?)
+                .apply(hosts);
+
+            if hosts.is_empty() {
+                return Err(NexusError::Inventory {
+                    message: format!("limit did not match any hosts: {}", limit),
+                    suggestion: Some(format!(
+                        "The limit is applied to the hosts matched by '{}'",
+                        playbook.hosts
+                    )),
+                });
+            }
+        }
+
+        Ok(hosts)
+    }
+
+    /// The inventory with the groups `group_by` tasks of earlier plays added hosts to
+    fn inventory_with_dynamic_groups<'a>(&self, inventory: &'a Inventory) -> Cow<'a, Inventory> {
+        let memberships = self.dynamic_groups.lock();
+        if memberships.is_empty() {
+            return Cow::Borrowed(inventory);
+        }
+        let mut inventory = inventory.clone();
+        for (group, host) in memberships.iter() {
+            inventory.add_host_to_group(host, group);
+        }
+        Cow::Owned(inventory)
+    }
+
+    /// Execute one play
+    async fn execute_play(
+        &self,
+        playbook: &Playbook,
+        inventory: &Inventory,
     ) -> Result<PlayRecap, NexusError> {
         // Clear any previous host contexts to start fresh
         self.clear_host_contexts();
+        self.play_ended.store(false, Ordering::SeqCst);
+        *self.play_handlers.lock() = playbook.handlers.clone();
+        *self.play_environment.lock() = playbook.environment.clone();
+        *self.play_throttle.lock() = playbook.throttle;
 
         // Set playbook directory for resolving relative includes/imports
         {
@@ -206,21 +638,18 @@ impl Scheduler {
             if let Some(dir) = path.parent() {
                 *self.playbook_dir.lock() = Some(dir.to_path_buf());
             }
+            // Roles of an imported playbook can sit next to it
+            self.add_playbook_role_path(path);
         }
 
-        let hosts = inventory.get_hosts(&playbook.hosts);
-
-        if hosts.is_empty() {
-            return Err(NexusError::Inventory {
-                message: format!("No hosts matched pattern: {:?}", playbook.hosts),
-                suggestion: Some("Check your inventory and host pattern".to_string()),
-            });
-        }
+        let inventory = &self.inventory_with_dynamic_groups(inventory);
+        let hosts = self.play_hosts(playbook, inventory)?;
 
         // Print header
         {
             let out = self.output.lock();
-            out.print_playbook_header(&playbook.source_file, hosts.len());
+            let title = playbook.name.as_deref().unwrap_or(&playbook.source_file);
+            out.print_playbook_header(title, hosts.len());
         }
 
         // Callback: playbook start
@@ -229,36 +658,12 @@ impl Scheduler {
             .on_playbook_start(&playbook.source_file, &host_names)
             .await;
 
+        // Progress for the terminal and the TUI
+        let total_tasks = self.count_play_tasks(playbook);
+        self.output.lock().start_progress(hosts.len(), total_tasks);
+
         // Emit playbook start event for TUI
         if let Some(ref emitter) = self.event_emitter {
-            // Count total tasks including role tasks
-            let mut total_tasks =
-                playbook.tasks.len() + playbook.pre_tasks.len() + playbook.post_tasks.len();
-
-            // Add tasks from roles
-            for role_ref in &playbook.roles {
-                // Resolve role dependencies to get all roles that will be executed
-                let role_execution_order = {
-                    let mut resolver = self.role_resolver.lock();
-                    match resolver.resolve_dependencies(&role_ref.role) {
-                        Ok(order) => order,
-                        Err(_) => continue, // Skip counting if resolution fails
-                    }
-                };
-
-                // Count tasks in each role
-                for role_name in role_execution_order {
-                    let role = {
-                        let mut resolver = self.role_resolver.lock();
-                        match resolver.resolve(&role_name) {
-                            Ok(r) => r.clone(),
-                            Err(_) => continue,
-                        }
-                    };
-                    total_tasks += role.tasks.len();
-                }
-            }
-
             emitter.playbook_start(
                 playbook.source_file.clone(),
                 host_names.clone(),
@@ -274,11 +679,9 @@ impl Scheduler {
         }
 
         let mut recap = PlayRecap::new();
+        recap.check_mode = self.config.check_mode;
         let start_time = Instant::now();
 
-        // Collect all handlers (from playbook + roles)
-        let mut all_handlers = playbook.handlers.clone();
-
         // Create handler registry - will be populated with role handlers too
         let handler_registry = Arc::new(HandlerRegistry::from_handlers(&playbook.handlers));
 
@@ -289,12 +692,36 @@ impl Scheduler {
         let tag_filter = self.config.tag_filter.clone().unwrap_or_default();
 
         // Merge playbook vars with role defaults/vars
-        let mut effective_vars = playbook.vars.clone();
+        let mut effective_vars = magic_vars(inventory, &hosts);
+        effective_vars.extend(self.resolve_play_vars(playbook)?);
 
         // 0. Auto-gather facts if enabled
         if playbook.gather_facts {
-            use crate::executor::facts::{FactCategory, FactGatherer};
-            use std::collections::HashMap;
+            use crate::executor::facts::{ansible_fact_vars, FactCategory, FactGatherer};
+
+            // The play's gather_subset overrides --gather-subset
+            let subset = if playbook.gather_subset.is_empty() {
+                &self.config.gather_subset
+            } else {
+                &playbook.gather_subset
+            };
+            let categories =
+                FactCategory::resolve_subset(subset).map_err(|e| NexusError::Runtime {
+                    function: None,
+                    message: format!("Invalid gather_subset: {}", e),
+                    suggestion: Some("Use category names such as network or !hardware".to_string()),
+                    location: None,
+                })?;
+            let gather_all = categories.len() == FactCategory::all_categories().len();
+
+            // Gathered facts are only reused from disk, and only when the play
+            // wants every category (a partial set would hide the rest)
+            let fact_cache = self
+                .config
+                .fact_cache_dir
+                .as_ref()
+                .filter(|_| gather_all)
+                .map(|_| self.fact_cache.as_ref());
 
             if self.config.verbose {
                 self.output.lock().print_task_header("GATHERING FACTS");
@@ -302,8 +729,9 @@ impl Scheduler {
 
             // Gather facts on all hosts
             for host in &hosts {
-                // Skip fact gathering for local connections (not yet implemented)
-                if host.is_local() {
+                // Facts are only gathered over SSH so far
+                let connection_type = self.pool.get_connection_type(host);
+                if connection_type != ConnectionType::Ssh {
                     if self.config.verbose {
                         let out = self.output.lock();
                         out.print_task_result(&TaskResult {
@@ -311,8 +739,12 @@ impl Scheduler {
                             task_name: "Gathering Facts".to_string(),
                             changed: false,
                             failed: false,
+                            unreachable: false,
                             skipped: true,
-                            stdout: Some("Skipped for local connection".to_string()),
+                            stdout: Some(format!(
+                                "Skipped for {} connection",
+                                connection_type.as_str()
+                            )),
                             stderr: None,
                             message: None,
                             duration: Duration::from_millis(0),
@@ -322,35 +754,38 @@ impl Scheduler {
                     continue;
                 }
 
-                let conn = self.pool.get(host)?;
+                // Reuse facts cached by an earlier run; stale or corrupt entries are re-gathered
+                let cached = fact_cache
+                    .as_ref()
+                    .and_then(|cache| cache.load_persisted(&host.name));
+                let from_cache = cached.is_some();
+                let gathered = match cached {
+                    Some(cached) => Ok(cached.facts),
+                    // An unreachable host is reported by its first task instead
+                    None => match self.pool.get(host) {
+                        Ok(conn) => FactGatherer::gather(&conn, &categories).await,
+                        Err(e) => Err(e),
+                    },
+                };
 
-                // Gather all fact categories
-                match FactGatherer::gather(&conn, &[FactCategory::All]) {
+                match gathered {
                     Ok(facts) => {
-                        // Convert facts to Ansible-compatible names
-                        let mut ansible_facts = HashMap::new();
-                        for (key, value) in &facts {
-                            let ansible_key = match key.as_str() {
-                                "hostname" => "ansible_hostname",
-                                "hostname_short" => "ansible_hostname_short",
-                                "os_family" => "ansible_os_family",
-                                "os_name" => "ansible_distribution",
-                                "os_version" => "ansible_distribution_version",
-                                "kernel_version" => "ansible_kernel",
-                                "architecture" => "ansible_architecture",
-                                "cpu_count" => "ansible_processor_count",
-                                "memory_total_mb" => "ansible_memtotal_mb",
-                                "default_ipv4" => "ansible_default_ipv4_address",
-                                "interfaces" => "ansible_interfaces",
-                                _ => key.as_str(),
-                            };
-                            ansible_facts.insert(ansible_key.to_string(), value.clone());
+                        if let Some(cache) = fact_cache.as_ref().filter(|_| !from_cache) {
+                            if let Err(e) = cache.persist(&host.name, facts.clone()) {
+                                eprintln!(
+                                    "Warning: could not cache facts for {}: {}",
+                                    host.name, e
+                                );
+                            }
                         }
 
                         // Store facts in effective_vars for this playbook run
-                        for (key, value) in ansible_facts {
-                            effective_vars.insert(key, value);
-                        }
+                        let fact_vars: HashMap<String, Value> = ansible_fact_vars(&facts)
+                            .into_iter()
+                            .filter(|(key, _)| !self.config.extra_vars.contains_key(key))
+                            .collect();
+                        effective_vars.extend(fact_vars.clone());
+                        self.host_facts.insert(host.name.clone(), fact_vars);
 
                         if self.config.verbose {
                             let out = self.output.lock();
@@ -359,8 +794,16 @@ impl Scheduler {
                                 task_name: "Gathering Facts".to_string(),
                                 changed: false,
                                 failed: false,
+                                unreachable: false,
                                 skipped: false,
-                                stdout: Some(format!("Gathered {} facts", facts.len())),
+                                stdout: Some(if from_cache {
+                                    format!(
+                                        "Using {} cached facts (gathering skipped)",
+                                        facts.len()
+                                    )
+                                } else {
+                                    format!("Gathered {} facts", facts.len())
+                                }),
                                 stderr: None,
                                 message: None,
                                 duration: Duration::from_millis(0),
@@ -376,6 +819,7 @@ impl Scheduler {
                                 task_name: "Gathering Facts".to_string(),
                                 changed: false,
                                 failed: true,
+                                unreachable: false,
                                 skipped: false,
                                 stdout: None,
                                 stderr: Some(e.to_string()),
@@ -390,6 +834,8 @@ impl Scheduler {
             }
         }
 
+        self.init_hostvars(&hosts, &effective_vars);
+
         // 1. Execute pre_tasks
         if !playbook.pre_tasks.is_empty() {
             self.output.lock().print_task_header("PRE-TASKS");
@@ -481,15 +927,11 @@ impl Scheduler {
                     // Add role handlers to registry
                     for handler in &role.handlers {
                         handler_registry.add_handler(&handler.name);
-                        all_handlers.push(handler.clone());
+                        self.play_handlers.lock().push(handler.clone());
                     }
 
-                    // Apply role-level tags
-                    let role_tag_filter = if !role_ref.tags.is_empty() {
-                        TagFilter::include_tags(role_ref.tags.clone())
-                    } else {
-                        tag_filter.clone()
-                    };
+                    // Every task of the role carries the role's tags
+                    let role_tag_filter = tag_filter.with_inherited_tags(&role_ref.tags);
 
                     // Execute role tasks
                     let failed = self
@@ -560,12 +1002,13 @@ impl Scheduler {
             }
         }
 
-        // 5. Execute pending handlers at end of playbook
-        if handler_registry.has_pending() {
+        // 5. Execute pending handlers at end of playbook (end_play skips them)
+        if handler_registry.has_pending() && !self.play_ended() {
             if self.config.verbose {
                 self.output.lock().print_task_header("RUNNING HANDLERS");
             }
 
+            let all_handlers = self.play_handlers.lock().clone();
             let handler_results = self
                 .execute_handlers(
                     &all_handlers,
@@ -613,6 +1056,10 @@ impl Scheduler {
         recap: &mut PlayRecap,
     ) -> Result<bool, NexusError> {
         for item in tasks {
+            if self.play_ended() {
+                return Ok(false);
+            }
+
             match item {
                 TaskOrBlock::Import(import) => {
                     // Static import - load tasks from file and execute inline
@@ -635,24 +1082,36 @@ impl Scheduler {
                 }
                 TaskOrBlock::Include(include) => {
                     // Dynamic include - resolve file path and vars at runtime
-                    // Check when condition
-                    if let Some(ref when) = include.when {
-                        let ctx = ExecutionContext::new(Arc::new(hosts[0].clone()), vars.clone());
-                        let result = evaluate_expression(when, &ctx)?;
-                        if !result.is_truthy() {
-                            if self.config.verbose {
-                                self.output
-                                    .lock()
-                                    .print_task_header("INCLUDE (skipped by condition)");
+                    // Check when condition on each host; the include is skipped on the others
+                    let include_hosts = match include.when {
+                        Some(ref when) => {
+                            let (passed, skipped) = self
+                                .split_hosts(when, hosts, vars)
+                                .map_err(|e| e.with_location(include.location.as_ref()))?;
+                            for host in skipped {
+                                let result = TaskResult::skipped(&host.name, "include_tasks");
+                                recap.record(&result);
+                                self.output.lock().print_task_result(&result);
                             }
-                            continue;
+                            passed
+                        }
+                        None => hosts.to_vec(),
+                    };
+                    if include_hosts.is_empty() {
+                        if self.config.verbose {
+                            self.output
+                                .lock()
+                                .print_task_header("INCLUDE (skipped by condition)");
                         }
+                        continue;
                     }
+                    let hosts = include_hosts.as_slice();
 
                     // Handle loop
                     if let Some(ref loop_expr) = include.loop_expr {
                         let ctx = ExecutionContext::new(Arc::new(hosts[0].clone()), vars.clone());
-                        let loop_value = evaluate_expression(loop_expr, &ctx)?;
+                        let loop_value = evaluate_expression(loop_expr, &ctx)
+                            .map_err(|e| e.with_location(include.location.as_ref()))?;
 
                         let items = match loop_value {
                             Value::List(items) => items,
@@ -662,6 +1121,7 @@ impl Scheduler {
                                     message: "include_tasks loop must evaluate to a list"
                                         .to_string(),
                                     suggestion: None,
+                                    location: include.location.clone(),
                                 })
                             }
                         };
@@ -708,6 +1168,15 @@ impl Scheduler {
                     }
                 }
                 TaskOrBlock::Task(task) => {
+                    if !tag_filter.should_run(&task.tags) {
+                        if self.config.verbose {
+                            self.output
+                                .lock()
+                                .print_task_header(&format!("{} (skipped by tags)", task.name));
+                        }
+                        continue;
+                    }
+
                     let results = self
                         .execute_task_on_hosts_with_handlers(
                             task,
@@ -719,19 +1188,33 @@ impl Scheduler {
                         )
                         .await?;
 
+                    // Record every host's result before stopping on a failure
+                    let mut failed = false;
                     for result in results {
                         recap.record(&result);
-                        self.output.lock().print_task_result(&result);
-
-                        // Stop on failure
-                        if result.failed {
-                            return Ok(true);
+                        {
+                            let out = self.output.lock();
+                            out.print_task_result(&result);
+                            out.advance_progress(&result);
                         }
+                        failed |= result.failed;
+                    }
+
+                    // Stop on failure
+                    if failed {
+                        return Ok(true);
                     }
                 }
                 TaskOrBlock::Block(block) => {
-                    // Check if block should run based on tags
-                    if !tag_filter.should_run(&block.tags) {
+                    // The block's tasks carry its tags; skip it when none of them would run
+                    let block_tag_filter = tag_filter.with_inherited_tags(&block.tags);
+                    let any_task_runs = block
+                        .block
+                        .iter()
+                        .chain(&block.rescue)
+                        .chain(&block.always)
+                        .any(|task| block_tag_filter.should_run(&task.tags));
+                    if !any_task_runs {
                         if self.config.verbose {
                             let block_name = block.name.as_deref().unwrap_or("Block");
                             self.output
@@ -741,38 +1224,24 @@ impl Scheduler {
                         continue;
                     }
 
-                    // Check when condition for block
-                    if let Some(ref when) = block.when {
-                        // Create a temporary context for the when evaluation
-                        let ctx = ExecutionContext::new(Arc::new(hosts[0].clone()), vars.clone());
-                        let result = evaluate_expression(when, &ctx)?;
-                        if !result.is_truthy() {
-                            if self.config.verbose {
-                                let block_name = block.name.as_deref().unwrap_or("Block");
-                                self.output.lock().print_task_header(&format!(
-                                    "{} (skipped by condition)",
-                                    block_name
-                                ));
-                            }
-                            continue;
-                        }
-                    }
-
-                    // Execute block
+                    // The block's vars apply to its `when` and all of its tasks, but
+                    // extra vars still win
+                    *self.block_vars.lock() = self.without_extra_vars(&block.vars);
                     let block_failed = self
-                        .execute_block(
+                        .execute_block_when(
                             block,
                             hosts,
                             vars,
                             use_sudo,
                             sudo_user,
-                            tag_filter,
+                            &block_tag_filter,
                             handler_registry,
                             recap,
                         )
-                        .await?;
+                        .await;
+                    self.block_vars.lock().clear();
 
-                    if block_failed {
+                    if block_failed? {
                         return Ok(true);
                     }
                 }
@@ -782,9 +1251,10 @@ impl Scheduler {
         Ok(false)
     }
 
-    /// Execute a block with rescue/always logic
+    /// Run `block` on the hosts where its `when` holds, each checked against its
+    /// own variables, and report its tasks as skipped on the others
     #[allow(clippy::too_many_arguments)]
-    async fn execute_block(
+    async fn execute_block_when(
         &self,
         block: &Block,
         hosts: &[&Host],
@@ -795,20 +1265,114 @@ impl Scheduler {
         handler_registry: &HandlerRegistry,
         recap: &mut PlayRecap,
     ) -> Result<bool, NexusError> {
-        let block_name = block.name.as_deref().unwrap_or("Block");
+        let (block_hosts, skipped) = match block.when {
+            Some(ref when) => self
+                .split_hosts(when, hosts, vars)
+                .map_err(|e| e.with_location(block.location.as_ref()))?,
+            None => (hosts.to_vec(), Vec::new()),
+        };
 
-        // Print block header
+        // The header goes first so the skipped results print under their block
         if self.config.verbose {
-            self.output
-                .lock()
-                .print_task_header(&format!("BLOCK: {}", block_name));
+            let block_name = block.name.as_deref().unwrap_or("Block");
+            let header = if block_hosts.is_empty() {
+                format!("{} (skipped by condition)", block_name)
+            } else {
+                format!("BLOCK: {}", block_name)
+            };
+            self.output.lock().print_task_header(&header);
+        }
+        for host in skipped {
+            let ctx = self.task_context(host, vars);
+            self.skip_block(block, &ctx, tag_filter, recap);
+        }
+        if block_hosts.is_empty() {
+            return Ok(false);
+        }
+
+        self.execute_block(
+            block,
+            &block_hosts,
+            vars,
+            use_sudo,
+            sudo_user,
+            tag_filter,
+            handler_registry,
+            recap,
+        )
+        .await
+    }
+
+    /// Split `hosts` into those where `when` holds and those where it doesn't,
+    /// evaluating it against each host's own context
+    fn split_hosts<'a>(
+        &self,
+        when: &Expression,
+        hosts: &[&'a Host],
+        vars: &HashMap<String, Value>,
+    ) -> Result<(Vec<&'a Host>, Vec<&'a Host>), NexusError> {
+        let mut passed = Vec::new();
+        let mut skipped = Vec::new();
+        for host in hosts {
+            let ctx = self.task_context(host, vars);
+            if evaluate_expression(when, &ctx)?.is_truthy() {
+                passed.push(*host);
+            } else {
+                skipped.push(*host);
+            }
+        }
+        Ok((passed, skipped))
+    }
+
+    /// Report the tasks of `block` as skipped on the context's host, whose `when`
+    /// was false, registering their results as skipped
+    fn skip_block(
+        &self,
+        block: &Block,
+        ctx: &ExecutionContext,
+        tag_filter: &TagFilter,
+        recap: &mut PlayRecap,
+    ) {
+        for task in block
+            .block
+            .iter()
+            .filter(|t| tag_filter.should_run(&t.tags))
+        {
+            if let Some(ref var_name) = task.register {
+                ctx.register(var_name, skipped_by_condition());
+            }
+            let result = TaskResult::skipped(&ctx.host.name, &task.name);
+            recap.record(&result);
+            let out = self.output.lock();
+            out.print_task_result(&result);
+            out.advance_progress(&result);
         }
+    }
+
+    /// Execute a block with rescue/always logic. The caller prints the block's header.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_block(
+        &self,
+        block: &Block,
+        hosts: &[&Host],
+        vars: &HashMap<String, Value>,
+        use_sudo: bool,
+        sudo_user: &Option<String>,
+        tag_filter: &TagFilter,
+        handler_registry: &HandlerRegistry,
+        recap: &mut PlayRecap,
+    ) -> Result<bool, NexusError> {
+        let block_name = block.name.as_deref().unwrap_or("Block");
 
         let mut block_failed = false;
         let mut failed_task_info: Option<(String, String)> = None;
 
         // Execute main block tasks
         for task in &block.block {
+            if self.play_ended() {
+                return Ok(false);
+            }
+
             // Check if task should run based on tags
             if !tag_filter.should_run(&task.tags) {
                 if self.config.verbose {
@@ -832,7 +1396,11 @@ impl Scheduler {
 
             for result in results {
                 recap.record(&result);
-                self.output.lock().print_task_result(&result);
+                {
+                    let out = self.output.lock();
+                    out.print_task_result(&result);
+                    out.advance_progress(&result);
+                }
 
                 // If task failed, run rescue
                 if result.failed {
@@ -894,7 +1462,11 @@ impl Scheduler {
 
                 for result in results {
                     recap.record(&result);
-                    self.output.lock().print_task_result(&result);
+                    {
+                        let out = self.output.lock();
+                        out.print_task_result(&result);
+                        out.advance_progress(&result);
+                    }
 
                     // If rescue task fails, the whole block fails
                     if result.failed {
@@ -981,7 +1553,11 @@ impl Scheduler {
 
             for result in results {
                 recap.record(&result);
-                self.output.lock().print_task_result(&result);
+                {
+                    let out = self.output.lock();
+                    out.print_task_result(&result);
+                    out.advance_progress(&result);
+                }
                 // Note: We don't stop on failure in always section
             }
         }
@@ -1017,6 +1593,7 @@ impl Scheduler {
                             handler_name
                         ),
                         suggestion: Some("Define the handler in the handlers section".to_string()),
+                        location: None,
                     });
                 }
             };
@@ -1047,7 +1624,9 @@ impl Scheduler {
                 fail_when: None,
                 changed_when: None,
                 notify: Vec::new(),
+                always_notify: false,
                 loop_expr: None,
+                loop_nested: false,
                 loop_var: "item".to_string(),
                 location: handler.location.clone(),
                 sudo: None,
@@ -1059,6 +1638,9 @@ impl Scheduler {
                 throttle: None,
                 delegate_to: None,
                 delegate_facts: false,
+                no_log: false,
+                check_mode: None,
+                vars: HashMap::new(),
             };
 
             // Callback: handler start for each host
@@ -1119,6 +1701,32 @@ impl Scheduler {
         playbook_sudo_user: &Option<String>,
         handler_registry: &HandlerRegistry,
     ) -> Result<Vec<TaskResult>, NexusError> {
+        if self.before_start_at_task(task) {
+            if self.config.verbose {
+                self.output
+                    .lock()
+                    .print_task_header(&format!("{} (skipped by --start-at-task)", task.name));
+            }
+            return Ok(hosts
+                .iter()
+                .map(|host| TaskResult::skipped(&host.name, &task.name))
+                .collect());
+        }
+
+        if let ModuleCall::Meta { action } = task.module {
+            return self
+                .execute_meta(
+                    action,
+                    task,
+                    hosts,
+                    playbook_vars,
+                    playbook_sudo,
+                    playbook_sudo_user,
+                    handler_registry,
+                )
+                .await;
+        }
+
         let results = self
             .execute_task_on_hosts(
                 task,
@@ -1130,9 +1738,11 @@ impl Scheduler {
             .await?;
 
         // Track handler notifications for hosts where task changed
+        // (or succeeded at all, with always_notify)
         if !task.notify.is_empty() {
             for result in &results {
-                if result.changed && !result.failed {
+                let notifies = result.changed || (task.always_notify && !result.skipped);
+                if notifies && !result.failed {
                     // Notify all handlers for this host
                     handler_registry.notify_all(&task.notify, &result.host);
                 }
@@ -1142,8 +1752,114 @@ impl Scheduler {
         Ok(results)
     }
 
-    /// Execute a task on multiple hosts in parallel
-    async fn execute_task_on_hosts(
+    /// Handle a `meta:` task. These act on scheduler state rather than running on hosts.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_meta(
+        &self,
+        action: MetaAction,
+        task: &Task,
+        hosts: &[&Host],
+        playbook_vars: &HashMap<String, Value>,
+        playbook_sudo: bool,
+        playbook_sudo_user: &Option<String>,
+        handler_registry: &HandlerRegistry,
+    ) -> Result<Vec<TaskResult>, NexusError> {
+        self.output.lock().print_task_header(&task.name);
+
+        let meta_result = |host: &Host, skipped: bool, message: &str| TaskResult {
+            host: host.name.clone(),
+            task_name: task.name.clone(),
+            changed: false,
+            failed: false,
+            unreachable: false,
+            skipped,
+            stdout: Some(message.to_string()),
+            stderr: None,
+            message: None,
+            duration: Duration::from_millis(0),
+            diff: None,
+        };
+
+        // `when` is checked on each host; actions that apply to the whole play follow
+        // the first host's answer
+        let mut selected = Vec::with_capacity(hosts.len());
+        if let Some(when) = &task.when {
+            for host in hosts {
+                let ctx = self.get_or_create_context(host, playbook_vars);
+                let result = evaluate_expression(when, &ctx)
+                    .map_err(|e| e.with_location(task.location.as_ref()))?;
+                selected.push(result.is_truthy());
+            }
+        } else {
+            selected.resize(hosts.len(), true);
+        }
+        let per_host = matches!(action, MetaAction::ClearFacts);
+        if !per_host && !selected.first().copied().unwrap_or(false) {
+            return Ok(hosts
+                .iter()
+                .map(|h| meta_result(h, true, "condition not met"))
+                .collect());
+        }
+
+        let message = match action {
+            MetaAction::FlushHandlers => {
+                if handler_registry.has_pending() {
+                    let handlers = self.play_handlers.lock().clone();
+                    return self
+                        .execute_handlers(
+                            &handlers,
+                            hosts,
+                            playbook_vars,
+                            playbook_sudo,
+                            playbook_sudo_user,
+                            handler_registry,
+                        )
+                        .await;
+                }
+                "No handlers pending"
+            }
+            MetaAction::ClearFacts => {
+                let mut results = Vec::with_capacity(hosts.len());
+                for (host, &selected) in hosts.iter().zip(&selected) {
+                    if !selected {
+                        results.push(meta_result(host, true, "condition not met"));
+                        continue;
+                    }
+                    let ctx = self.get_or_create_context(host, playbook_vars);
+                    for name in ctx.all_vars().keys() {
+                        if name.starts_with("ansible_") {
+                            ctx.remove_var(name);
+                        }
+                    }
+                    results.push(meta_result(host, false, "Facts cleared"));
+                }
+                return Ok(results);
+            }
+            MetaAction::EndPlay => {
+                self.play_ended.store(true, Ordering::SeqCst);
+                "Ending play"
+            }
+            // A failed task already stops the play, so there are no failed hosts to reset
+            MetaAction::ClearHostErrors => "No host errors to clear",
+        };
+
+        Ok(hosts
+            .iter()
+            .map(|h| meta_result(h, false, message))
+            .collect())
+    }
+
+    /// The user a task's sudo runs as: its `run_as`, else `--become-user`, else the
+    /// playbook's `sudo_user`
+    fn task_sudo_user(&self, task: &Task, playbook_sudo_user: &Option<String>) -> Option<String> {
+        task.run_as
+            .clone()
+            .or_else(|| self.config.sudo_user.clone())
+            .or_else(|| playbook_sudo_user.clone())
+    }
+
+    /// Execute a task on multiple hosts in parallel
+    async fn execute_task_on_hosts(
         &self,
         task: &Task,
         hosts: &[&Host],
@@ -1157,15 +1873,23 @@ impl Scheduler {
             out.print_task_header(&task.name);
         }
 
-        // Semaphore to limit concurrent hosts
-        // Task-level throttle overrides global max_parallel_hosts
-        let max_concurrent = task.throttle.unwrap_or(self.config.max_parallel_hosts);
+        // Semaphore to limit concurrent hosts. Task-level throttle overrides the
+        // play's, and either overrides global max_parallel_hosts. With serial the
+        // hosts are one batch, so the limit applies within the batch.
+        let max_concurrent = match task
+            .throttle
+            .or(*self.play_throttle.lock())
+            .unwrap_or(self.config.max_parallel_hosts)
+        {
+            0 => hosts.len().max(1),
+            n => n,
+        };
         let semaphore = Arc::new(Semaphore::new(max_concurrent));
 
         // Determine effective sudo settings for this task
-        // Task-level overrides playbook-level, run_as overrides sudo_user
+        // Task-level overrides playbook-level
         let use_sudo = task.sudo.unwrap_or(playbook_sudo);
-        let sudo_user = task.run_as.clone().or_else(|| playbook_sudo_user.clone());
+        let sudo_user = self.task_sudo_user(task, playbook_sudo_user);
 
         // Create futures for each host
         let event_emitter = self.event_emitter.clone();
@@ -1176,19 +1900,29 @@ impl Scheduler {
                 let pool = self.pool.clone();
                 let modules = self.modules.clone();
                 let callbacks = self.callbacks.clone();
+                let fact_cache = self.fact_cache.clone();
+                let dynamic_groups = self.dynamic_groups.clone();
+                let debugger = self.debugger.clone();
+                let debug_aborted = self.debug_aborted.clone();
+                let async_tracker = self.async_tracker.clone();
                 let emitter = event_emitter.clone();
                 let task = task.clone();
                 let host = (*host).clone();
-                let check_mode = self.config.check_mode;
+                let check_mode = task.check_mode.unwrap_or(self.config.check_mode);
                 let diff_mode = self.config.diff_mode;
+                let verbosity = self.config.verbosity;
                 let sudo = use_sudo;
                 let sudo_user = sudo_user.clone();
 
-                // Get or create context for this host (preserves registered vars across tasks)
+                // Get or create context for this host (preserves registered vars across tasks).
+                // A no_log task's output lines aren't streamed, since they'd show uncensored.
                 let ctx = self
-                    .get_or_create_context(&host, playbook_vars)
+                    .task_context(&host, playbook_vars)
+                    .with_scoped_vars(self.without_extra_vars(&task.vars))
                     .with_check_mode(check_mode)
                     .with_diff_mode(diff_mode)
+                    .with_verbosity(verbosity)
+                    .with_output_stream(emitter.clone().filter(|_| !task.no_log))
                     .with_sudo(sudo, sudo_user.clone());
 
                 async move {
@@ -1203,9 +1937,69 @@ impl Scheduler {
                     callbacks.on_task_start(&host.name, &task.name).await;
 
                     let start = Instant::now();
-                    let result = execute_single_task(&task, &ctx, &pool, &modules, None).await;
+                    let run = || async {
+                        execute_single_task(&task, &ctx, &pool, &modules, Some(&async_tracker))
+                            .await
+                            .map_err(|e| e.with_location(task.location.as_ref()))
+                    };
+                    let mut result = run().await;
+                    if let Some(ref debugger) = debugger {
+                        while let Some(error) = failure_message(&result) {
+                            if debug_aborted.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            match debugger.prompt(&task.name, &host.name, &error, &ctx).await {
+                                DebugAction::Retry => result = run().await,
+                                DebugAction::Continue => break,
+                                DebugAction::Abort => debug_aborted.store(true, Ordering::SeqCst),
+                            }
+                        }
+                    }
                     let duration = start.elapsed();
 
+                    if let (
+                        ModuleCall::SetFact {
+                            cacheable: true, ..
+                        },
+                        Ok(output),
+                    ) = (&task.module, &result)
+                    {
+                        if !output.failed && !output.skipped {
+                            if let Err(e) =
+                                fact_cache.cache_set_facts(&host.name, output.data.clone())
+                            {
+                                eprintln!(
+                                    "Warning: could not cache facts for {}: {}",
+                                    host.name, e
+                                );
+                            }
+                        }
+                    }
+
+                    if let (ModuleCall::GroupBy { .. }, Ok(output)) = (&task.module, &result) {
+                        if let Some(Value::String(group)) = output.data.get("add_group") {
+                            dynamic_groups
+                                .lock()
+                                .push((group.clone(), host.name.clone()));
+                        }
+                    }
+
+                    // Registered results keep the real output; only reporting is censored
+                    let result = if task.no_log {
+                        result.map(|output| output.censored()).map_err(|e| match e {
+                            // Connection errors say nothing about the task's data
+                            NexusError::Unreachable { .. } => e,
+                            _ => NexusError::Runtime {
+                                function: None,
+                                message: NO_LOG_MESSAGE.to_string(),
+                                suggestion: None,
+                                location: None,
+                            },
+                        })
+                    } else {
+                        result
+                    };
+
                     let task_result = match result {
                         Ok(output) => {
                             let tr = TaskResult {
@@ -1213,6 +2007,7 @@ impl Scheduler {
                                 task_name: task.name.clone(),
                                 changed: output.changed,
                                 failed: output.failed,
+                                unreachable: false,
                                 skipped: output.skipped,
                                 stdout: Some(output.stdout.clone()),
                                 stderr: Some(output.stderr.clone()),
@@ -1263,6 +2058,7 @@ impl Scheduler {
                         }
                         Err(e) => {
                             let error_msg = e.to_string();
+                            let unreachable = matches!(e, NexusError::Unreachable { .. });
 
                             // Emit task failed event
                             if let Some(ref emitter) = emitter {
@@ -1282,6 +2078,7 @@ impl Scheduler {
                                 task_name: task.name.clone(),
                                 changed: false,
                                 failed: true,
+                                unreachable,
                                 skipped: false,
                                 stdout: None,
                                 stderr: None,
@@ -1300,6 +2097,15 @@ impl Scheduler {
         // Execute all futures
         let results = join_all(futures).await;
 
+        if self.debug_aborted.load(Ordering::SeqCst) {
+            return Err(NexusError::Runtime {
+                function: None,
+                message: format!("Run aborted from the debugger at task '{}'", task.name),
+                suggestion: None,
+                location: None,
+            });
+        }
+
         Ok(results)
     }
 
@@ -1354,11 +2160,12 @@ impl Scheduler {
     async fn execute_playbook_serial(
         &self,
         playbook: &Playbook,
-        _inventory: &Inventory,
+        inventory: &Inventory,
         all_hosts: &[&Host],
         serial: &Serial,
     ) -> Result<PlayRecap, NexusError> {
         let mut recap = PlayRecap::new();
+        recap.check_mode = self.config.check_mode;
         let start_time = Instant::now();
 
         // Calculate batches based on serial configuration
@@ -1370,16 +2177,25 @@ impl Scheduler {
                 .print_task_header(&format!("SERIAL EXECUTION: {} batch(es)", batches.len()));
         }
 
-        // Collect all handlers
-        let all_handlers = playbook.handlers.clone();
         let handler_registry = Arc::new(HandlerRegistry::from_handlers(&playbook.handlers));
 
         let use_sudo = self.config.sudo || playbook.sudo;
         let tag_filter = self.config.tag_filter.clone().unwrap_or_default();
-        let effective_vars = playbook.vars.clone();
+        let mut effective_vars = magic_vars(inventory, all_hosts);
+        effective_vars.extend(self.resolve_play_vars(playbook)?);
+        self.init_hostvars(all_hosts, &effective_vars);
+        // Whether an earlier play already reached the requested task
+        let started = self.start_at_reached.load(Ordering::SeqCst);
 
         // Execute on each batch sequentially
         for (batch_num, batch) in batches.iter().enumerate() {
+            if self.play_ended() {
+                break;
+            }
+
+            // Every batch starts at the requested task
+            self.start_at_reached.store(started, Ordering::SeqCst);
+
             if self.config.verbose {
                 self.output.lock().print_task_header(&format!(
                     "BATCH {}/{}: {} host(s)",
@@ -1464,11 +2280,12 @@ impl Scheduler {
             }
 
             // Execute handlers for this batch
-            if handler_registry.has_pending() {
+            if handler_registry.has_pending() && !self.play_ended() {
                 if self.config.verbose {
                     self.output.lock().print_task_header("RUNNING HANDLERS");
                 }
 
+                let all_handlers = self.play_handlers.lock().clone();
                 let handler_results = self
                     .execute_handlers(
                         &all_handlers,
@@ -1498,6 +2315,29 @@ impl Scheduler {
     }
 }
 
+/// Play-wide magic variables: `groups`, each group's host names (including
+/// its children's), and `play_hosts`, the names of the hosts the play runs on.
+/// Play vars of the same name take their place.
+fn magic_vars(inventory: &Inventory, hosts: &[&Host]) -> HashMap<String, Value> {
+    let groups = inventory
+        .group_hosts()
+        .into_iter()
+        .map(|(group, hosts)| {
+            let hosts = hosts.into_iter().map(Value::String).collect();
+            (group, Value::List(hosts))
+        })
+        .collect();
+    let play_hosts = hosts
+        .iter()
+        .map(|h| Value::String(h.name.clone()))
+        .collect();
+
+    let mut vars = HashMap::new();
+    vars.insert("groups".to_string(), Value::Dict(groups));
+    vars.insert("play_hosts".to_string(), Value::List(play_hosts));
+    vars
+}
+
 /// Calculate host batches based on serial configuration
 fn calculate_batches<'a>(hosts: &[&'a Host], serial: &Serial) -> Vec<Vec<&'a Host>> {
     let total_hosts = hosts.len();
@@ -1505,44 +2345,69 @@ fn calculate_batches<'a>(hosts: &[&'a Host], serial: &Serial) -> Vec<Vec<&'a Hos
         return vec![];
     }
 
-    match serial {
-        Serial::Count(n) => {
-            // Fixed batch size
-            let batch_size = (*n).min(total_hosts);
-            hosts
-                .chunks(batch_size)
-                .map(|chunk| chunk.to_vec())
-                .collect()
-        }
-        Serial::Percentage(pct) => {
-            // Percentage of hosts per batch
-            let batch_size = ((total_hosts * (*pct as usize)) / 100).max(1);
-            hosts
-                .chunks(batch_size)
-                .map(|chunk| chunk.to_vec())
-                .collect()
-        }
-        Serial::List(sizes) => {
-            // Progressive batches
-            let mut batches = Vec::new();
-            let mut remaining = hosts.to_vec();
+    let sizes = match serial {
+        Serial::Count(n) => vec![SerialBatch::Count(*n)],
+        Serial::Percentage(pct) => vec![SerialBatch::Percentage(*pct)],
+        Serial::List(sizes) if !sizes.is_empty() => sizes.clone(),
+        Serial::List(_) => vec![SerialBatch::Percentage(100)],
+    };
 
-            for size in sizes {
-                if remaining.is_empty() {
-                    break;
-                }
-                let batch_size = (*size).min(remaining.len());
-                let batch = remaining.drain(..batch_size).collect();
-                batches.push(batch);
-            }
+    // Progressive batches: walk the list, then keep using the last size for the rest
+    let mut batches = Vec::new();
+    let mut remaining = hosts;
+    let mut sizes = sizes.iter();
+    let mut size = sizes.next().unwrap();
+    while !remaining.is_empty() {
+        let batch_size = size.size(total_hosts).min(remaining.len());
+        let (batch, rest) = remaining.split_at(batch_size);
+        batches.push(batch.to_vec());
+        remaining = rest;
+        size = sizes.next().unwrap_or(size);
+    }
 
-            // Add remaining hosts in final batch
-            if !remaining.is_empty() {
-                batches.push(remaining);
-            }
+    batches
+}
 
-            batches
-        }
+/// Ask the user for a `vars_prompt` value on the terminal
+fn prompt_for_var(prompt: &VarsPrompt) -> Result<Value, NexusError> {
+    use std::io::Write;
+
+    let label = match prompt.default {
+        Some(ref default) => format!("{} [{}]: ", prompt.prompt, default),
+        None => format!("{}: ", prompt.prompt),
+    };
+
+    let answer = if prompt.private {
+        rpassword::prompt_password(&label)
+    } else {
+        print!("{}", label);
+        std::io::stdout().flush().ok();
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line)
+    }
+    .map_err(|e| NexusError::Io {
+        message: format!("Failed to read value for '{}': {}", prompt.name, e),
+        path: None,
+    })?;
+
+    let answer = answer.trim_end_matches(['\r', '\n']);
+    match (answer.is_empty(), &prompt.default) {
+        (true, Some(default)) => Ok(default.clone()),
+        _ => Ok(Value::String(answer.to_string())),
+    }
+}
+
+/// Why a task run failed, or None if it didn't
+fn failure_message(result: &Result<TaskOutput, NexusError>) -> Option<String> {
+    match result {
+        Ok(output) if output.failed => Some(
+            output
+                .message
+                .clone()
+                .unwrap_or_else(|| "task failed".to_string()),
+        ),
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
     }
 }
 
@@ -1566,17 +2431,23 @@ async fn execute_single_task_with_retry(
     circuit_breakers: Option<&CircuitBreakerRegistry>,
     async_tracker: Option<&AsyncJobTracker>,
 ) -> Result<TaskOutput, NexusError> {
-    // Check when condition
+    // Check when condition, against this host's variables
     if let Some(ref when_expr) = task.when {
         let result = evaluate_expression(when_expr, ctx)?;
         if !result.is_truthy() {
-            return Ok(TaskOutput::skipped());
+            let output = skipped_by_condition();
+            if let Some(ref var_name) = task.register {
+                ctx.register(var_name, output.clone());
+            }
+            return Ok(output);
         }
     }
 
     // Handle async execution
     if let Some(ref async_config) = task.async_config {
-        return execute_async_task(task, ctx, pool, modules, async_config, async_tracker).await;
+        if async_config.async_timeout > 0 {
+            return execute_async_task(task, ctx, pool, async_config, async_tracker).await;
+        }
     }
 
     // Handle loop
@@ -1584,20 +2455,24 @@ async fn execute_single_task_with_retry(
         let loop_value = evaluate_expression(loop_expr, ctx)?;
 
         let items = match loop_value {
+            Value::List(items) if task.loop_nested => cartesian_product(items)?,
             Value::List(items) => items,
             _ => {
                 return Err(NexusError::Runtime {
                     function: None,
                     message: "loop expression must evaluate to a list".to_string(),
                     suggestion: None,
+                    location: None,
                 })
             }
         };
 
         let mut combined_output = TaskOutput::new();
+        // Each iteration's result, with its item, for `register`
+        let mut results = Vec::new();
 
         for (i, item) in items.into_iter().enumerate() {
-            let loop_ctx = ctx.clone_for_task().with_loop_item(item, i);
+            let loop_ctx = ctx.clone_for_task().with_loop_item(item.clone(), i);
 
             let output = execute_task_body_with_retry(
                 task,
@@ -1612,22 +2487,116 @@ async fn execute_single_task_with_retry(
             combined_output.changed = combined_output.changed || output.changed;
             combined_output.failed = combined_output.failed || output.failed;
 
+            let mut result = output.to_value();
+            if let Value::Dict(ref mut fields) = result {
+                fields.insert("item".to_string(), item);
+            }
+            results.push(result);
+
             if !output.stdout.is_empty() {
                 combined_output.stdout.push_str(&output.stdout);
                 combined_output.stdout.push('\n');
             }
+            if let Some(message) = output.message {
+                let combined = combined_output.message.get_or_insert_with(String::new);
+                if !combined.is_empty() {
+                    combined.push('\n');
+                }
+                combined.push_str(&message);
+            }
 
             if output.failed {
                 break;
             }
         }
 
+        // Like Ansible, a looped task is only skipped when every item was
+        combined_output.skipped = !results.is_empty()
+            && results.iter().all(|result| {
+                matches!(result, Value::Dict(fields)
+                    if fields.get("skipped") == Some(&Value::Bool(true)))
+            });
+        combined_output
+            .data
+            .insert("results".to_string(), Value::List(results));
+
+        // Replace the last iteration's result with the whole loop's
+        if let Some(ref var_name) = task.register {
+            ctx.register(var_name, combined_output.clone());
+        }
+
         return Ok(combined_output);
     }
 
     execute_task_body_with_retry(task, ctx, pool, modules, circuit_breakers, async_tracker).await
 }
 
+/// The result of a task whose `when` was false, as registered:
+/// `skipped: true`, `changed: false` and a `skip_reason`
+fn skipped_by_condition() -> TaskOutput {
+    TaskOutput::skipped().with_data(
+        "skip_reason",
+        Value::String("Conditional result was False".to_string()),
+    )
+}
+
+/// Every combination of the given lists, each as a list with one entry per
+/// dimension: `[[a, b], [1, 2]]` gives `[a, 1], [a, 2], [b, 1], [b, 2]`
+fn cartesian_product(lists: Vec<Value>) -> Result<Vec<Value>, NexusError> {
+    if lists.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut combinations: Vec<Vec<Value>> = vec![Vec::new()];
+
+    for list in lists {
+        let Value::List(items) = list else {
+            return Err(NexusError::Runtime {
+                function: None,
+                message: format!(
+                    "loop_nested expects a list of lists, got an entry {:?}",
+                    list
+                ),
+                suggestion: Some(
+                    "Each loop_nested entry must be a list, e.g. [a, b] or ${users}".to_string(),
+                ),
+                location: None,
+            });
+        };
+
+        combinations = combinations
+            .into_iter()
+            .flat_map(|prefix| {
+                items.iter().map(move |item| {
+                    let mut combination = prefix.clone();
+                    combination.push(item.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+
+    Ok(combinations.into_iter().map(Value::List).collect())
+}
+
+/// Open the connection a host uses: SSH, local, docker or WinRM
+fn connect(ctx: &ExecutionContext, pool: &ConnectionPool) -> Result<AnyConnection, NexusError> {
+    #[cfg(feature = "winrm")]
+    use crate::executor::WinRmConnection;
+    use crate::executor::{DockerConnection, LocalConnection};
+
+    Ok(match pool.get_connection_type(&ctx.host) {
+        ConnectionType::Local => AnyConnection::Local(
+            LocalConnection::new(&ctx.host.name).with_command_timeout(pool.command_timeout()),
+        ),
+        ConnectionType::Docker => AnyConnection::Docker(DockerConnection::for_host(&ctx.host)),
+        #[cfg(feature = "winrm")]
+        ConnectionType::WinRm => AnyConnection::WinRm(WinRmConnection::for_host(&ctx.host, pool)?),
+        #[cfg(not(feature = "winrm"))]
+        ConnectionType::WinRm => return Err(crate::executor::winrm_unavailable(&ctx.host)),
+        ConnectionType::Ssh => AnyConnection::Ssh(pool.get(&ctx.host)?),
+    })
+}
+
 /// Execute the body of a task (module call)
 async fn execute_task_body(
     task: &Task,
@@ -1635,39 +2604,87 @@ async fn execute_task_body(
     pool: &ConnectionPool,
     modules: &ModuleExecutor,
 ) -> Result<TaskOutput, NexusError> {
-    use crate::executor::LocalConnection;
-    use crate::modules::AnyConnection;
-
-    // Get appropriate connection type (SSH or local)
-    let conn = match pool.get_connection_type(&ctx.host) {
-        crate::executor::ssh::ConnectionType::Local => {
-            AnyConnection::Local(LocalConnection::new(&ctx.host.name))
-        }
-        crate::executor::ssh::ConnectionType::Ssh => AnyConnection::Ssh(pool.get(&ctx.host)?),
-    };
+    let timeout = task_timeout(task, pool);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut conn = connect(ctx, pool)?;
+    if let Some(deadline) = deadline {
+        conn = conn.with_deadline(deadline);
+    }
 
     // Execute the module
-    let output = modules.execute(&task.module, ctx, &conn).await?;
+    let run = modules.execute_with_pool(&task.module, ctx, &conn, pool);
+    let mut output = match (timeout, deadline) {
+        (Some(timeout), Some(deadline)) => match tokio::time::timeout(timeout, run).await {
+            // An SSH command stopped at the deadline rather than its own timeout
+            Ok(Err(NexusError::Timeout { .. })) if Instant::now() >= deadline => {
+                task_timed_out(timeout)
+            }
+            Ok(result) => result?,
+            Err(_) => task_timed_out(timeout),
+        },
+        _ => run.await?,
+    };
 
-    // Register output if requested
+    // Register output if requested, so changed_when/fail_when can refer to it
     if let Some(ref var_name) = task.register {
         ctx.register(var_name, output.clone());
     }
 
-    // Check fail_when condition
-    if let Some(ref fail_when) = task.fail_when {
-        let result = evaluate_expression(fail_when, ctx)?;
-        if result.is_truthy() {
-            return Ok(TaskOutput::failed(format!(
-                "fail_when condition triggered: {:?}",
-                fail_when
-            )));
+    if apply_result_conditions(task, ctx, &mut output)? {
+        // Keep the registered variable in line with the overridden status
+        if let Some(ref var_name) = task.register {
+            ctx.register(var_name, output.clone());
         }
     }
 
     Ok(output)
 }
 
+/// How long a task may run: its `timeout`, or else the command timeout. wait_for and
+/// reboot wait as long as their own settings allow, so only a `timeout` bounds them.
+fn task_timeout(task: &Task, pool: &ConnectionPool) -> Option<Duration> {
+    match (task.timeout, &task.module) {
+        (Some(timeout), _) => Some(timeout),
+        (None, ModuleCall::WaitFor { .. } | ModuleCall::Reboot { .. }) => None,
+        (None, _) => Some(pool.command_timeout()),
+    }
+}
+
+/// The result of a task stopped after running for `timeout`
+fn task_timed_out(timeout: Duration) -> TaskOutput {
+    TaskOutput::failed(format!("Task timed out after {}s", timeout.as_secs_f64()))
+        .with_data("timed_out", Value::Bool(true))
+}
+
+/// Override `changed`/`failed` from the task's changed_when and fail_when
+/// conditions. Returns true if either condition was evaluated.
+fn apply_result_conditions(
+    task: &Task,
+    ctx: &ExecutionContext,
+    output: &mut TaskOutput,
+) -> Result<bool, NexusError> {
+    if output.skipped || (task.changed_when.is_none() && task.fail_when.is_none()) {
+        return Ok(false);
+    }
+
+    if let Some(ref changed_when) = task.changed_when {
+        output.changed = evaluate_expression(changed_when, ctx)?.is_truthy();
+    }
+
+    if let Some(ref fail_when) = task.fail_when {
+        let failed = evaluate_expression(fail_when, ctx)?.is_truthy();
+        if failed && !output.failed {
+            output.message = Some(format!("fail_when condition triggered: {:?}", fail_when));
+        } else if !failed && output.failed {
+            // The failure was expected; keep rc/stdout/stderr but drop the error
+            output.message = None;
+        }
+        output.failed = failed;
+    }
+
+    Ok(true)
+}
+
 /// Execute task body with retry and circuit breaker support
 async fn execute_task_body_with_retry(
     task: &Task,
@@ -1793,49 +2810,58 @@ async fn execute_task_body_with_retry(
     )))
 }
 
-/// Execute an async task in the background
+/// Execute an async task in the background. With `poll: 0` the task returns
+/// as soon as the job has started; otherwise the job is checked every `poll`
+/// seconds until it finishes or runs past its `async` timeout.
 async fn execute_async_task(
     task: &Task,
     ctx: &ExecutionContext,
     pool: &ConnectionPool,
-    _modules: &ModuleExecutor,
     async_config: &crate::parser::ast::AsyncConfig,
     async_tracker: Option<&AsyncJobTracker>,
 ) -> Result<TaskOutput, NexusError> {
     // Get the command to execute
-    let command = match &task.module {
-        crate::parser::ast::ModuleCall::Command { cmd, .. } => {
-            // Evaluate the command expression
-            let cmd_value = evaluate_expression(cmd, ctx)?;
-            match cmd_value {
-                Value::String(s) => s,
-                _ => {
-                    return Err(NexusError::Runtime {
-                        function: None,
-                        message: "command must be a string".to_string(),
-                        suggestion: None,
-                    });
-                }
-            }
+    let (cmd, environment) = match &task.module {
+        ModuleCall::Command {
+            cmd, environment, ..
+        } => (cmd, environment),
+        ModuleCall::Shell {
+            command,
+            environment,
+            ..
+        } => (command, environment),
+        _ => {
+            return Err(NexusError::Runtime {
+                function: None,
+                message: "async execution is only supported for the command and shell modules"
+                    .to_string(),
+                suggestion: Some("Use 'command:' or 'shell:' for async tasks".to_string()),
+                location: None,
+            });
         }
+    };
+    let command = match evaluate_expression(cmd, ctx)? {
+        Value::String(s) => prefix_environment(&evaluate_environment(environment, ctx)?, &s),
         _ => {
             return Err(NexusError::Runtime {
                 function: None,
-                message: "async execution is only supported for command module".to_string(),
-                suggestion: Some("Use 'command:' module for async tasks".to_string()),
+                message: "command must be a string".to_string(),
+                suggestion: None,
+                location: None,
             });
         }
     };
 
-    // Get SSH connection (async tasks don't support local connections yet)
-    if ctx.host.is_local() {
+    let conn = connect(ctx, pool)?;
+    let conn = conn.as_connection();
+    if conn.is_windows() {
         return Err(NexusError::Runtime {
             function: None,
-            message: "async execution is not supported for local connections".to_string(),
-            suggestion: Some("Remove 'async:' parameter for localhost tasks".to_string()),
+            message: "async execution is not supported for WinRM connections".to_string(),
+            suggestion: Some("Remove 'async:' from tasks that run on Windows hosts".to_string()),
+            location: None,
         });
     }
-    let conn = pool.get(&ctx.host)?;
 
     // Wrap command with sudo if needed
     let final_command = ctx.wrap_command(&command);
@@ -1850,52 +2876,41 @@ async fn execute_async_task(
         function: None,
         message: "async tracker not available".to_string(),
         suggestion: None,
+        location: None,
     })?;
 
     let job_id = tracker
-        .start_job(&conn, &final_command, async_config.async_timeout)
+        .start_job(conn, &final_command, async_config.async_timeout)
         .await?;
 
-    // Fire and forget mode (poll == 0)
-    if async_config.poll == 0 {
-        let mut output = TaskOutput::changed();
-        output.stdout = format!("Async job started (fire and forget): {}", job_id);
-        output
-            .data
-            .insert("job_id".to_string(), Value::String(job_id));
-        output.data.insert("started".to_string(), Value::Bool(true));
-        output
-            .data
-            .insert("finished".to_string(), Value::Bool(false));
+    let output = if async_config.poll == 0 {
+        // Fire and forget: a later async_status task can check on the job
+        TaskOutput::changed()
+            .with_stdout(format!("Async job started (fire and forget): {}", job_id))
+            .with_data("finished", Value::Bool(false))
+    } else {
+        tracker
+            .poll_until_complete(conn, &job_id, async_config.poll, async_config.async_timeout)
+            .await?
+            .with_data("finished", Value::Bool(true))
+    };
+    let mut output = output
+        .with_data("ansible_job_id", Value::String(job_id.clone()))
+        .with_data("job_id", Value::String(job_id))
+        .with_data("started", Value::Bool(true));
 
-        // Register output if requested
+    // Register output if requested
+    if let Some(ref var_name) = task.register {
+        ctx.register(var_name, output.clone());
+    }
+
+    if apply_result_conditions(task, ctx, &mut output)? {
         if let Some(ref var_name) = task.register {
             ctx.register(var_name, output.clone());
         }
-
-        return Ok(output);
-    }
-
-    // Poll for completion
-    let result = tracker
-        .poll_until_complete(&conn, &job_id, async_config.poll, async_config.retries)
-        .await?;
-
-    // Add job_id to output data
-    let mut final_output = result;
-    final_output
-        .data
-        .insert("job_id".to_string(), Value::String(job_id));
-    final_output
-        .data
-        .insert("finished".to_string(), Value::Bool(true));
-
-    // Register output if requested
-    if let Some(ref var_name) = task.register {
-        ctx.register(var_name, final_output.clone());
     }
 
-    Ok(final_output)
+    Ok(output)
 }
 
 #[cfg(test)]
@@ -1908,4 +2923,1502 @@ mod tests {
         assert_eq!(config.max_parallel_hosts, 10);
         assert!(!config.check_mode);
     }
+
+    #[test]
+    fn test_forks() {
+        assert_eq!("25".parse::<Forks>(), Ok(Forks::Count(25)));
+        assert_eq!("0".parse::<Forks>(), Ok(Forks::Count(0)));
+        assert_eq!("AUTO".parse::<Forks>(), Ok(Forks::Auto));
+        assert!("lots".parse::<Forks>().is_err());
+
+        assert_eq!(auto_forks(4, Some(1024)), 64);
+        // 256 open files leave room for 48 hosts
+        assert_eq!(auto_forks(8, Some(256)), 48);
+        assert_eq!(auto_forks(64, Some(1_048_576)), AUTO_FORKS_CEILING);
+        assert_eq!(auto_forks(2, None), 32);
+        assert_eq!(auto_forks(1, Some(10)), 1);
+        assert!((1..=AUTO_FORKS_CEILING).contains(&Forks::Auto.resolve()));
+    }
+
+    #[test]
+    fn test_resolve_play_vars_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("common.yml"),
+            "region: us-east\nport: 8080\nenv: staging\n",
+        )
+        .unwrap();
+
+        let playbook_path = dir.path().join("site.nx.yml");
+        let playbook = crate::parser::parse_playbook(
+            "hosts: all\nvars:\n  port: 80\n  app: web\nvars_files:\n  - common.yml\ntasks: []\n",
+            playbook_path.to_string_lossy().to_string(),
+        )
+        .unwrap();
+
+        let mut config = SchedulerConfig::default();
+        config
+            .extra_vars
+            .insert("env".to_string(), Value::String("prod".to_string()));
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+
+        let vars = scheduler.resolve_play_vars(&playbook).unwrap();
+        assert_eq!(vars.get("app"), Some(&Value::String("web".to_string())));
+        assert_eq!(vars.get("port"), Some(&Value::Int(8080)));
+        assert_eq!(
+            vars.get("region"),
+            Some(&Value::String("us-east".to_string()))
+        );
+        assert_eq!(vars.get("env"), Some(&Value::String("prod".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_meta_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("handler-ran");
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Change something
+    shell: "true"
+    notify: record
+  - meta: flush_handlers
+  - name: Handler already ran
+    shell: "test -f {marker}"
+  - meta: clear_host_errors
+  - meta: end_play
+  - name: Never runs
+    shell: "exit 1"
+
+handlers:
+  - name: record
+    shell: "touch {marker}"
+"#,
+            marker = marker.display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+        let stats = &recap.hosts["localhost"];
+        assert_eq!(stats.failed, 0);
+        assert!(marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_meta_clear_facts_when_is_per_host() {
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Fake a fact
+    set_fact:
+      ansible_marker: kept
+  - meta: clear_facts
+    when: role == "db"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        for (name, role) in [("app1", "web"), ("app2", "db")] {
+            inventory.add_host(
+                Host::new(name)
+                    .with_var("ansible_connection", Value::String("local".to_string()))
+                    .with_var("role", Value::String(role.to_string())),
+            );
+        }
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["app1"].skipped, 1);
+        assert_eq!(recap.hosts["app2"].skipped, 0);
+        let marker = |host: &str| {
+            scheduler
+                .host_contexts
+                .get(host)
+                .unwrap()
+                .get_var("ansible_marker")
+        };
+        assert!(marker("app1").is_some());
+        assert!(marker("app2").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_always_notify_on_unchanged_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Already exists, still notifies
+    file:
+      path: {dir}
+      state: directory
+    always_notify: true
+    notify: always
+  - name: Already exists, no notification
+    file:
+      path: {dir}
+      state: directory
+    notify: never
+
+handlers:
+  - name: always
+    shell: "touch {dir}/always"
+  - name: never
+    shell: "touch {dir}/never"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert!(dir.path().join("always").exists());
+        assert!(!dir.path().join("never").exists());
+    }
+
+    #[tokio::test]
+    async fn test_start_at_task_skips_earlier_tasks() {
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Would fail
+    shell: "exit 1"
+  - name: Setup
+    block:
+      - name: Also skipped
+        shell: "exit 1"
+      - name: Configure nginx
+        shell: "true"
+  - name: Runs after
+    shell: "true"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+
+        let config = SchedulerConfig {
+            start_at_task: Some("Configure nginx".to_string()),
+            ..SchedulerConfig::default()
+        };
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+        let stats = &recap.hosts["localhost"];
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.skipped, 2);
+        assert_eq!(stats.ok + stats.changed, 2);
+
+        let config = SchedulerConfig {
+            start_at_task: Some("No such task".to_string()),
+            ..SchedulerConfig::default()
+        };
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+        let err = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No such task"));
+    }
+
+    #[tokio::test]
+    async fn test_tasks_inherit_block_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Untagged
+    shell: "touch {dir}/untagged"
+  - name: Web
+    tags: [web]
+    block:
+      - name: Untagged inside a tagged block
+        shell: "touch {dir}/web"
+  - name: Cleanup
+    tags: [always]
+    block:
+      - name: Runs whatever the tags
+        shell: "touch {dir}/always"
+  - name: Debugging
+    tags: [never, debug]
+    block:
+      - name: Only with --tags debug
+        shell: "touch {dir}/debug"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+
+        let config = SchedulerConfig {
+            tag_filter: Some(TagFilter::from_args(Some("web"), None)),
+            ..SchedulerConfig::default()
+        };
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert!(!dir.path().join("untagged").exists());
+        assert!(dir.path().join("web").exists());
+        assert!(dir.path().join("always").exists());
+        assert!(!dir.path().join("debug").exists());
+    }
+
+    #[tokio::test]
+    async fn test_execute_plays_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let site = dir.path().join("site.nx.yml");
+        std::fs::write(
+            &site,
+            format!(
+                r#"- hosts: all
+  gather_facts: false
+  tasks:
+    - name: First play
+      shell: "touch {dir}/first"
+
+- hosts: all
+  gather_facts: false
+  tasks:
+    - name: Fails
+      shell: "test -f {dir}/first && exit 1"
+
+- hosts: all
+  gather_facts: false
+  tasks:
+    - name: After a failed play
+      shell: "touch {dir}/never"
+"#,
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+        let plays = crate::parser::parse_playbook_file(&site).unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler.execute_plays(&plays, &inventory).await.unwrap();
+        let stats = &recap.hosts["localhost"];
+        assert_eq!(stats.changed + stats.ok, 1);
+        assert_eq!(stats.failed, 1);
+        assert!(!dir.path().join("never").exists());
+    }
+
+    #[tokio::test]
+    async fn test_group_by_adds_hosts_for_later_plays() {
+        let dir = tempfile::tempdir().unwrap();
+        let site = dir.path().join("site.nx.yml");
+        std::fs::write(
+            &site,
+            format!(
+                r#"- hosts: all
+  gather_facts: false
+  vars:
+    distro: Ubuntu
+  tasks:
+    - name: Group by distribution
+      group_by:
+        key: "distro_${{distro}}"
+
+- hosts: distro_Ubuntu
+  gather_facts: false
+  tasks:
+    - name: Only on Ubuntu
+      shell: "touch {dir}/ubuntu"
+"#,
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+        let plays = crate::parser::parse_playbook_file(&site).unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler.execute_plays(&plays, &inventory).await.unwrap();
+        assert!(!recap.has_failures());
+        assert!(dir.path().join("ubuntu").exists());
+        // The caller's inventory is left as it was
+        assert!(!inventory.groups.contains_key("distro_Ubuntu"));
+    }
+
+    #[test]
+    fn test_calculate_batches() {
+        let hosts: Vec<Host> = (1..=10).map(|i| Host::new(format!("web{}", i))).collect();
+        let hosts: Vec<&Host> = hosts.iter().collect();
+        let sizes = |serial: Serial| -> Vec<usize> {
+            calculate_batches(&hosts, &serial)
+                .iter()
+                .map(|b| b.len())
+                .collect()
+        };
+
+        assert_eq!(
+            sizes(Serial::List(vec![
+                SerialBatch::Count(1),
+                SerialBatch::Percentage(50)
+            ])),
+            vec![1, 5, 4]
+        );
+        // Percentages round up
+        assert_eq!(sizes(Serial::Percentage(30)), vec![3, 3, 3, 1]);
+        assert_eq!(sizes(Serial::Percentage(1)), vec![1; 10]);
+        // The last list entry applies to all remaining hosts
+        assert_eq!(
+            sizes(Serial::List(vec![
+                SerialBatch::Count(1),
+                SerialBatch::Count(3)
+            ])),
+            vec![1, 3, 3, 3]
+        );
+        assert_eq!(sizes(Serial::Count(0)), vec![1; 10]);
+        assert_eq!(sizes(Serial::Count(20)), vec![10]);
+    }
+
+    #[test]
+    fn test_cartesian_product() {
+        let s = |v: &str| Value::String(v.to_string());
+        let product = cartesian_product(vec![
+            Value::List(vec![s("a"), s("b")]),
+            Value::List(vec![s("1"), s("2")]),
+        ])
+        .unwrap();
+        assert_eq!(
+            product,
+            vec![
+                Value::List(vec![s("a"), s("1")]),
+                Value::List(vec![s("a"), s("2")]),
+                Value::List(vec![s("b"), s("1")]),
+                Value::List(vec![s("b"), s("2")]),
+            ]
+        );
+
+        assert!(
+            cartesian_product(vec![Value::List(vec![]), Value::List(vec![s("x")])])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(cartesian_product(vec![s("not a list")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_loop_nested_users_by_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  users: [alice, bob]
+  groups: [wheel, docker, audio]
+
+tasks:
+  - name: Add membership
+    shell: "touch {dir}/${{item[0]}}-${{item[1]}}"
+    loop_nested:
+      - ${{users}}
+      - ${{groups}}
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        let mut created: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        created.sort();
+        assert_eq!(
+            created,
+            vec![
+                "alice-audio",
+                "alice-docker",
+                "alice-wheel",
+                "bob-audio",
+                "bob-docker",
+                "bob-wheel"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_converted_with_dict_loop_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let converted = crate::converter::ExpressionConverter::new()
+            .convert_string("{{ users | dict2items }}")
+            .output;
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  users:
+    alice: admin
+    bob: dev
+
+tasks:
+  - name: Converted with_dict loop
+    shell: "touch {dir}/${{item.key}}-${{item.value}}"
+    loop: {converted}
+
+  - name: Custom field names
+    shell: "touch {dir}/${{item.name}}.${{item.role}}"
+    loop: ${{users | dict2items(key_name='name', value_name='role')}}
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        let mut created: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        created.sort();
+        assert_eq!(
+            created,
+            vec!["alice-admin", "alice.admin", "bob-dev", "bob.dev"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changed_when_and_failed_when_override_result() {
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Nonzero rc is expected
+    shell: "echo ready; exit 3"
+    register: probe
+    failed_when: false
+    changed_when: "probe.rc == 3"
+  - name: Reports no change
+    shell: "true"
+    changed_when: false
+  - name: Changes only when every condition holds
+    shell: "true"
+    changed_when:
+      - "probe.rc == 3"
+      - "probe.rc == 0"
+  - name: Empty lists leave the result alone
+    shell: "true"
+    failed_when: []
+    changed_when: []
+  - name: Fails when any condition holds
+    shell: "true"
+    failed_when:
+      - "probe.rc == 0"
+      - "probe.changed"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        let stats = &recap.hosts["localhost"];
+        assert_eq!(stats.changed, 2);
+        assert_eq!(stats.ok, 2);
+        assert_eq!(stats.failed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_task_timeout_stops_the_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Hangs
+    shell: sleep 3; touch {dir}/finished
+    timeout: 1
+    register: slow
+    failed_when: "not slow.timed_out"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let started = Instant::now();
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+
+        // The shell was killed, not left to finish in the background
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert!(!dir.path().join("finished").exists());
+    }
+
+    #[tokio::test]
+    async fn test_no_log_hides_output_from_callbacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("events.jsonl");
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Read the secret
+    shell: "printf hunter2-secret; echo hunter2-secret >&2"
+    register: secret
+    no_log: true
+  - name: Registered value is intact
+    shell: "test '${secret.stdout}' = hunter2-secret"
+  - name: Failing secret task
+    shell: "echo hunter2-secret; exit 1"
+    no_log: true
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let mut callbacks = CallbackManager::new();
+        callbacks.add(Box::new(
+            crate::plugins::callbacks::JsonLogCallback::new(&log).unwrap(),
+        ));
+        let scheduler = Scheduler::with_callbacks(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+            Arc::new(callbacks),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        let events = std::fs::read_to_string(&log).unwrap();
+        let stats = &recap.hosts["localhost"];
+        assert_eq!(stats.changed, 2);
+        assert_eq!(stats.failed, 1);
+        assert!(!events.contains("hunter2"));
+        assert!(events.contains(NO_LOG_MESSAGE));
+    }
+
+    #[tokio::test]
+    async fn test_no_log_output_is_not_streamed() {
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Read the secret
+    shell: "echo hunter2-secret"
+    no_log: true
+  - name: Say hello
+    shell: "echo hello"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let mut scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let (emitter, mut events) = crate::output::events::create_event_channel();
+        scheduler.set_event_emitter(emitter);
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        let mut streamed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let crate::output::events::ExecutionEvent::StreamLine { line, .. } = event {
+                streamed.push(line);
+            }
+        }
+        assert_eq!(streamed, ["hello"]);
+    }
+
+    #[test]
+    fn test_become_user_overrides_playbook_sudo_user() {
+        let playbook_user = Some("deploy".to_string());
+        let mut task = Task::default();
+        let output = Arc::new(Mutex::new(OutputWriter::silent()));
+
+        let scheduler = Scheduler::new(SchedulerConfig::default(), output.clone());
+        assert_eq!(
+            scheduler.task_sudo_user(&task, &playbook_user).as_deref(),
+            Some("deploy")
+        );
+
+        let config = SchedulerConfig {
+            sudo_user: Some("postgres".to_string()),
+            ..SchedulerConfig::default()
+        };
+        let scheduler = Scheduler::new(config, output);
+        assert_eq!(
+            scheduler.task_sudo_user(&task, &playbook_user).as_deref(),
+            Some("postgres")
+        );
+
+        task.run_as = Some("www-data".to_string());
+        assert_eq!(
+            scheduler.task_sudo_user(&task, &playbook_user).as_deref(),
+            Some("www-data")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_task_check_mode_overrides_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Runs even under --check
+    shell: touch {dir}/real.txt
+    check_mode: false
+  - name: Follows the run
+    shell: touch {dir}/default.txt
+  - name: Never runs
+    shell: touch {dir}/dry.txt
+    check_mode: true
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+
+        for check_mode in [true, false] {
+            let config = SchedulerConfig {
+                check_mode,
+                ..SchedulerConfig::default()
+            };
+            let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+            scheduler
+                .execute_playbook(&playbook, &inventory)
+                .await
+                .unwrap();
+
+            assert!(dir.path().join("real.txt").exists());
+            assert_eq!(dir.path().join("default.txt").exists(), !check_mode);
+            assert!(!dir.path().join("dry.txt").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cacheable_set_fact_survives_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("facts");
+        let set_yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Pick a release
+    set_fact:
+      release: v2
+      ports: [80, 443]
+      cacheable: true
+  - name: Use it in the same play
+    shell: "touch {dir}/${{release}}-${{ports[1]}}"
+"#,
+            dir = dir.path().display()
+        );
+        let use_yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Use it in a later run
+    shell: "touch {dir}/later-${{release}}"
+"#,
+            dir = dir.path().display()
+        );
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let config = || SchedulerConfig {
+            fact_cache_dir: Some(cache_dir.clone()),
+            ..SchedulerConfig::default()
+        };
+
+        let playbook = crate::parser::parse_playbook(&set_yaml, "site.nx.yml".to_string()).unwrap();
+        let scheduler = Scheduler::new(config(), Arc::new(Mutex::new(OutputWriter::silent())));
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        assert!(dir.path().join("v2-443").exists());
+
+        let playbook = crate::parser::parse_playbook(&use_yaml, "site.nx.yml".to_string()).unwrap();
+        let scheduler = Scheduler::new(config(), Arc::new(Mutex::new(OutputWriter::silent())));
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+        assert!(dir.path().join("later-v2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_when_is_evaluated_per_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Web only
+    when: role == "web"
+    block:
+      - name: Probe
+        shell: "echo up"
+        register: probe
+  - name: Probed hosts
+    shell: "touch {dir}/probed-${{inventory_hostname}}"
+    when: not probe.skipped
+  - name: Skipped hosts
+    shell: "touch {dir}/skipped-${{inventory_hostname}}-${{probe.changed}}"
+    when: probe.skipped
+    register: marker
+  - name: Marker registered on every host
+    shell: "touch {dir}/marker-${{inventory_hostname}}-${{marker.skipped}}"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        for (name, role) in [("app1", "web"), ("app2", "db")] {
+            inventory.add_host(
+                Host::new(name)
+                    .with_var("ansible_connection", Value::String("local".to_string()))
+                    .with_var("role", Value::String(role.to_string())),
+            );
+        }
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["app1"].failed, 0);
+        assert_eq!(recap.hosts["app2"].failed, 0);
+        assert_eq!(recap.hosts["app1"].skipped, 1);
+        assert_eq!(recap.hosts["app2"].skipped, 2);
+        let mut created: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        created.sort();
+        assert_eq!(
+            created,
+            [
+                "marker-app1-true",
+                "marker-app2-false",
+                "probed-app1",
+                "skipped-app2-false"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_include_when_is_evaluated_per_host() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("setup.yml"),
+            format!(
+                "- name: Set up\n  command: \"touch {}/included-${{inventory_hostname}}\"\n",
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - include_tasks: setup.yml
+    when: role == "web"
+"#;
+        let playbook_path = dir.path().join("site.nx.yml");
+        let playbook =
+            crate::parser::parse_playbook(yaml, playbook_path.to_string_lossy().to_string())
+                .unwrap();
+
+        let mut inventory = Inventory::new();
+        for (name, role) in [("app1", "db"), ("app2", "web")] {
+            inventory.add_host(
+                Host::new(name)
+                    .with_var("ansible_connection", Value::String("local".to_string()))
+                    .with_var("role", Value::String(role.to_string())),
+            );
+        }
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["app1"].skipped, 1);
+        assert_eq!(recap.hosts["app2"].changed, 1);
+        assert!(!dir.path().join("included-app1").exists());
+        assert!(dir.path().join("included-app2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_block_vars_apply_to_its_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("app.conf.j2"), "package={{ pkg }}\n").unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  pkg: apache
+
+tasks:
+  - name: Web server
+    vars:
+      pkg: nginx
+    when: pkg == "nginx"
+    block:
+      - name: Render config
+        template: {dir}/app.conf.j2
+        dest: {dir}/app.conf
+      - name: Fail
+        shell: "exit 1"
+    rescue:
+      - name: Recover
+        shell: "touch {dir}/rescued-${{pkg}}"
+  - name: After the block
+    shell: "touch {dir}/after-${{pkg}}"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("app.conf")).unwrap(),
+            "package=nginx\n"
+        );
+        assert!(dir.path().join("rescued-nginx").exists());
+        assert!(dir.path().join("after-apache").exists());
+    }
+
+    #[tokio::test]
+    async fn test_task_vars_apply_to_that_task_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  pkg: none
+  libs: [libssl, zlib]
+
+tasks:
+  - name: Per item
+    vars:
+      pkg: "${{item}}-dev"
+    shell: "touch {dir}/${{pkg}}"
+    loop: ${{libs}}
+  - name: Over the block's
+    vars:
+      pkg: task
+    block:
+      - name: Own vars win
+        vars:
+          pkg: own
+        shell: "touch {dir}/block-${{pkg}}"
+  - name: Not leaked
+    shell: "touch {dir}/after-${{pkg}}"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        let mut created: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        created.sort();
+        assert_eq!(
+            created,
+            ["after-none", "block-own", "libssl-dev", "zlib-dev"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hosts_read_each_others_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Pick a role
+    set_fact:
+      db_role: "${{inventory_hostname_short}}-primary"
+  - name: Use db1's role and the groups
+    shell: "touch {dir}/${{hostvars['db1.example.com'].db_role}}-${{groups['web'][0]}}-${{group_names[0]}}-${{play_hosts | length}}"
+    when: inventory_hostname == "web1"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        for (name, group) in [("web1", "web"), ("db1.example.com", "db")] {
+            let mut host =
+                Host::new(name).with_var("ansible_connection", Value::String("local".to_string()));
+            host.groups = vec![group.to_string()];
+            inventory.add_host(host);
+        }
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["web1"].failed, 0);
+        assert!(dir.path().join("db1-primary-web1-web-2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_play_environment_merges_with_task_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  name: world
+environment:
+  GREETING: hello
+  TARGET: play
+
+tasks:
+  - name: Task environment overrides the play's
+    shell: printf '%s %s' "$GREETING" "$TARGET" > {dir}/shell.txt
+    environment:
+      TARGET: "it's ${{name}}"
+  - name: Command inherits the play environment
+    command: env
+    register: env_out
+  - name: Check inherited value
+    shell: "true"
+    fail_when: "'GREETING=hello' not in env_out.stdout"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("shell.txt")).unwrap(),
+            "hello it's world"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_throttle_limits_concurrency_within_serial_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+serial: 10
+throttle: 2
+
+tasks:
+  - name: Limited by the play throttle
+    shell: echo start >> {dir}/play; sleep 0.2; echo end >> {dir}/play
+  - name: Task throttle overrides the play's
+    shell: echo start >> {dir}/task; sleep 0.1; echo end >> {dir}/task
+    throttle: 1
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        for i in 0..10 {
+            inventory.add_host(
+                Host::new(format!("host{}", i))
+                    .with_var("ansible_connection", Value::String("local".to_string())),
+            );
+        }
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        // Highest number of hosts between "start" and "end" at once
+        let max_observed = |name: &str| {
+            let log = std::fs::read_to_string(dir.path().join(name)).unwrap();
+            assert_eq!(log.lines().filter(|l| *l == "start").count(), 10);
+            let mut running = 0;
+            let mut max = 0;
+            for line in log.lines() {
+                running += if line == "start" { 1 } else { -1 };
+                max = max.max(running);
+            }
+            max
+        };
+        assert_eq!(max_observed("play"), 2);
+        assert_eq!(max_observed("task"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_tasks_poll_or_return_a_job_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Wait for the job
+    shell: sleep 1; echo built
+    async: 10
+    poll: 1
+    register: build
+  - name: Start in the background
+    shell: sleep 1; echo later > {dir}/later
+    async: 30
+    poll: 0
+    register: job
+  - name: Check on the job
+    async_status:
+      job_id: ${{job.ansible_job_id}}
+    register: job_result
+    until: ${{job_result.finished}}
+    retries: 10
+    delay: 1
+  - name: Runs past its timeout
+    command: sleep 30
+    async: 1
+    poll: 1
+    register: slow
+    fail_when: false
+  - name: Record results
+    shell: echo "${{build.stdout.trim()}} ${{build.finished}} ${{job.finished}} ${{job_result.rc}} ${{slow.stdout}}" > {dir}/results
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(
+            Host::new("localhost")
+                .with_var("ansible_connection", Value::String("local".to_string())),
+        );
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        let started = Instant::now();
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        // The timed-out job was killed instead of running for 30s
+        assert!(started.elapsed() < Duration::from_secs(20));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("later")).unwrap(),
+            "later\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("results")).unwrap(),
+            "built true false 0 \n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registered_result_fields_in_conditions() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: List things
+    shell: printf 'a\nb\n'; echo careful >&2; exit 3
+    register: listing
+    changed_when: false
+    failed_when: listing.rc != 3
+  - name: Only with output
+    shell: echo "${{listing.stdout_lines | length}} ${{listing.stderr_lines[0]}} ${{listing.exit_code}} ${{listing.changed}} ${{listing.failed}}" > {dir}/seen
+    when: listing.stdout_lines | length > 0
+  - name: Skipped without errors
+    command: "true"
+    register: quiet
+  - name: Never runs
+    shell: touch {dir}/never
+    when: quiet.stderr_lines | length > 0
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(
+            Host::new("localhost")
+                .with_var("ansible_connection", Value::String("local".to_string())),
+        );
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("seen")).unwrap(),
+            "2 careful 3 false false\n"
+        );
+        assert!(!dir.path().join("never").exists());
+    }
+
+    #[tokio::test]
+    async fn test_registered_loop_collects_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+vars:
+  letters: [a, b, c]
+
+tasks:
+  - name: Touch each
+    shell: echo ${{item}}
+    loop: ${{letters}}
+    changed_when: item == "b"
+    register: out
+  - name: Summarize
+    shell: echo "${{out.results | selectattr('changed') | map(attribute='item') | join(',')}} ${{out.changed}} ${{out.failed}} ${{out.results | length}} ${{out.results[2].stdout.trim()}} ${{out.results[0].rc}}" > {dir}/summary
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(
+            Host::new("localhost")
+                .with_var("ansible_connection", Value::String("local".to_string())),
+        );
+        let scheduler = Scheduler::new(
+            SchedulerConfig::default(),
+            Arc::new(Mutex::new(OutputWriter::silent())),
+        );
+        scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("summary")).unwrap(),
+            "b true false 3 c 0\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stdin_is_fed_to_command_and_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Shell reads stdin
+    shell: cat > {dir}/dump.sql
+    stdin: "CREATE TABLE ${{table}};"
+  - name: Command without the trailing newline
+    command: wc -c
+    stdin: abc
+    stdin_add_newline: false
+    register: count
+    fail_when: "count.stdout.trim() != '3'"
+"#,
+            dir = dir.path().display()
+        );
+        let playbook = crate::parser::parse_playbook(&yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        let mut config = SchedulerConfig::default();
+        config
+            .extra_vars
+            .insert("table".to_string(), Value::String("users".to_string()));
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("dump.sql")).unwrap(),
+            "CREATE TABLE users;\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_is_reported_separately() {
+        // Nothing listens on the port, so connecting is refused
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Ping
+    command: "true"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        inventory.add_host(Host::new("down").with_address("127.0.0.2").with_port(port));
+        let config = SchedulerConfig {
+            connection_retries: 0,
+            ..SchedulerConfig::default()
+        };
+        let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+        let recap = scheduler
+            .execute_playbook(&playbook, &inventory)
+            .await
+            .unwrap();
+
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+        assert_eq!(recap.hosts["down"].unreachable, 1);
+        assert_eq!(recap.hosts["down"].failed, 0);
+        assert!(recap.has_failures());
+        assert_eq!(recap.retry_hosts(), vec!["down"]);
+    }
+
+    #[tokio::test]
+    async fn test_limit_restricts_play_hosts() {
+        let yaml = r#"
+hosts: all
+gather_facts: false
+
+tasks:
+  - name: Ping
+    command: "true"
+"#;
+        let playbook = crate::parser::parse_playbook(yaml, "site.nx.yml".to_string()).unwrap();
+
+        // "elsewhere" would be unreachable, so running it at all would show in the recap
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new("localhost"));
+        inventory.add_host(Host::new("elsewhere").with_address("192.0.2.1"));
+        let run = |limit: &str| {
+            let config = SchedulerConfig {
+                limit: Some(limit.to_string()),
+                ..SchedulerConfig::default()
+            };
+            let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+            let (playbook, inventory) = (&playbook, &inventory);
+            async move { scheduler.execute_playbook(playbook, inventory).await }
+        };
+
+        let recap = run("all:!elsewhere").await.unwrap();
+        assert_eq!(recap.hosts.len(), 1);
+        assert_eq!(recap.hosts["localhost"].failed, 0);
+
+        match run("web*").await.unwrap_err() {
+            NexusError::Inventory {
+                message,
+                suggestion,
+            } => {
+                assert_eq!(message, "limit did not match any hosts: web*");
+                assert_eq!(
+                    suggestion.as_deref(),
+                    Some("The limit is applied to the hosts matched by 'all'")
+                );
+            }
+            other => panic!("Expected an inventory error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_play_hosts_applies_pattern_and_limit() {
+        let mut inventory = Inventory::new();
+        for name in ["web1", "web2", "db1"] {
+            let mut host = Host::new(name);
+            host.groups
+                .push(name.trim_end_matches(char::is_numeric).to_string());
+            inventory.add_host(host);
+        }
+        let playbook = crate::parser::parse_playbook(
+            "hosts: web\ntasks:\n  - command: \"true\"\n",
+            "site.nx.yml".to_string(),
+        )
+        .unwrap();
+
+        let names = |limit: Option<&str>| {
+            let config = SchedulerConfig {
+                limit: limit.map(str::to_string),
+                ..SchedulerConfig::default()
+            };
+            let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+            scheduler.play_hosts(&playbook, &inventory).map(|hosts| {
+                let mut names: Vec<String> = hosts.iter().map(|h| h.name.clone()).collect();
+                names.sort();
+                names
+            })
+        };
+
+        assert_eq!(names(None).unwrap(), vec!["web1", "web2"]);
+        assert_eq!(names(Some("all:!web2")).unwrap(), vec!["web1"]);
+        assert!(names(Some("db1")).is_err());
+    }
 }
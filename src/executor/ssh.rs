@@ -4,43 +4,163 @@ use async_trait::async_trait;
 use std::io::{Read, Write};
 use std::net::TcpStream;
 use std::path::Path;
-use std::time::Duration;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
+use base64::engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD};
+use base64::Engine;
 use dashmap::DashMap;
-use ssh2::{KeyboardInteractivePrompt, Session};
+use parking_lot::Mutex;
+use ssh2::{
+    CheckResult, ErrorCode, HashType, HostKeyType, KeyboardInteractivePrompt, KnownHostFileKind,
+    Session,
+};
 
+use super::retry::{calculate_delay, CircuitBreakerRegistry, CircuitState};
 use super::Connection;
 use crate::inventory::Host;
 use crate::output::errors::NexusError;
+use crate::parser::ast::{CircuitBreakerConfig, DelayStrategy};
 
 /// Type of connection to use
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionType {
     Ssh,
     Local,
+    Docker,
+    WinRm,
+}
+
+impl ConnectionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionType::Ssh => "ssh",
+            ConnectionType::Local => "local",
+            ConnectionType::Docker => "docker",
+            ConnectionType::WinRm => "winrm",
+        }
+    }
+}
+
+/// Default time a multiplexed session may sit unused before it is closed
+pub const DEFAULT_MULTIPLEX_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default number of times a transient SSH connection failure is retried
+pub const DEFAULT_CONNECTION_RETRIES: u32 = 3;
+
+/// Delay before the first connection retry; later ones back off exponentially with jitter
+const CONNECTION_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const CONNECTION_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How long a host is skipped after its connection retries ran out
+const CONNECTION_CIRCUIT_RESET: Duration = Duration::from_secs(30);
+
+/// How a host's SSH key is checked against `~/.ssh/known_hosts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostKeyChecking {
+    /// Only connect to hosts whose key is already in known_hosts
+    Strict,
+    /// Record the key of a host seen for the first time, but refuse a changed key
+    #[default]
+    AcceptNew,
+    /// Don't check host keys at all
+    Off,
+}
+
+impl HostKeyChecking {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HostKeyChecking::Strict => "strict",
+            HostKeyChecking::AcceptNew => "accept-new",
+            HostKeyChecking::Off => "off",
+        }
+    }
+}
+
+impl FromStr for HostKeyChecking {
+    type Err = String;
+
+    /// Accepts the OpenSSH `StrictHostKeyChecking` spellings too (`yes`, `no`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" | "yes" => Ok(HostKeyChecking::Strict),
+            "accept-new" | "accept_new" => Ok(HostKeyChecking::AcceptNew),
+            "off" | "no" => Ok(HostKeyChecking::Off),
+            _ => Err(format!(
+                "invalid host key checking mode '{}' (expected strict, accept-new or off)",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do with a host key after looking it up in known_hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostKeyDecision {
+    Trust,
+    /// Trust it and add it to known_hosts
+    Record,
+    Unknown,
+    Changed,
+}
+
+/// Why a connection attempt failed: only transient failures (refused, timed out,
+/// dropped during the handshake) are worth retrying
+enum ConnectFailure {
+    Transient(NexusError),
+    Fatal(NexusError),
 }
 
 /// SSH connection pool for reusing connections
 pub struct ConnectionPool {
     connections: DashMap<String, Vec<PooledConnection>>,
+    /// Persistent sessions shared by all tasks on a host (multiplex mode)
+    sessions: DashMap<String, MultiplexedSession>,
     max_per_host: usize,
     connect_timeout: Duration,
     command_timeout: Duration,
     default_user: Option<String>,
     private_key_path: Option<String>,
     password: Option<String>,
+    /// Run every command as a new channel on one persistent session per host
+    multiplex: bool,
+    /// Close multiplexed sessions that have been unused for this long
+    idle_timeout: Duration,
+    /// Retries after a transient connection failure
+    connect_retries: u32,
+    retry_base_delay: Duration,
+    /// One circuit per host, opened when a connection exhausts its retries
+    circuits: CircuitBreakerRegistry,
+    host_key_checking: HostKeyChecking,
+    /// Serializes known_hosts lookups and updates across concurrent connections
+    known_hosts_lock: Mutex<()>,
+}
+
+/// A persistent session kept open for channel multiplexing
+struct MultiplexedSession {
+    session: Session,
+    host_name: String,
+    last_used: Instant,
 }
 
 impl ConnectionPool {
     pub fn new() -> Self {
         ConnectionPool {
             connections: DashMap::new(),
+            sessions: DashMap::new(),
             max_per_host: 5,
             connect_timeout: Duration::from_secs(30),
             command_timeout: Duration::from_secs(300),
             default_user: None,
             private_key_path: None,
             password: None,
+            multiplex: false,
+            idle_timeout: DEFAULT_MULTIPLEX_IDLE_TIMEOUT,
+            connect_retries: DEFAULT_CONNECTION_RETRIES,
+            retry_base_delay: CONNECTION_RETRY_BASE_DELAY,
+            circuits: CircuitBreakerRegistry::new(),
+            host_key_checking: HostKeyChecking::default(),
+            known_hosts_lock: Mutex::new(()),
         }
     }
 
@@ -59,6 +179,11 @@ impl ConnectionPool {
         self
     }
 
+    /// How long a command may run before it's abandoned
+    pub fn command_timeout(&self) -> Duration {
+        self.command_timeout
+    }
+
     pub fn with_default_user(mut self, user: String) -> Self {
         self.default_user = Some(user);
         self
@@ -74,11 +199,51 @@ impl ConnectionPool {
         self
     }
 
+    /// Enable ControlMaster-style multiplexing: one persistent session per host,
+    /// with each command opened as a new channel on that session
+    pub fn with_multiplex(mut self, enabled: bool) -> Self {
+        self.multiplex = enabled;
+        self
+    }
+
+    /// Set how long a multiplexed session may stay idle before it is closed
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set how host keys are verified against known_hosts
+    pub fn with_host_key_checking(mut self, mode: HostKeyChecking) -> Self {
+        self.host_key_checking = mode;
+        self
+    }
+
+    /// Whether connection multiplexing is enabled
+    /// Retry transient connection failures this many times before giving up
+    pub fn with_connect_retries(mut self, retries: u32) -> Self {
+        self.connect_retries = retries;
+        self
+    }
+
+    /// Delay before the first connection retry (doubled for each further retry)
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_base_delay = delay;
+        self
+    }
+
+    pub fn is_multiplexed(&self) -> bool {
+        self.multiplex
+    }
+
     /// Get a connection to a host (from pool or create new)
     /// Note: This will not be used for local hosts - use get_any_connection instead
     pub fn get(&self, host: &Host) -> Result<SshConnection, NexusError> {
         let key = host.ssh_target();
 
+        if self.multiplex {
+            return self.get_multiplexed(host, key);
+        }
+
         // Try to get from pool
         if let Some(mut conns) = self.connections.get_mut(&key) {
             while let Some(conn) = conns.pop() {
@@ -87,23 +252,117 @@ impl ConnectionPool {
                         inner: conn,
                         pool_key: key,
                         return_to_pool: true,
+                        deadline: None,
                     });
                 }
             }
         }
 
         // Create new connection
-        let conn = self.connect(host)?;
+        let conn = self.connect_with_retry(host)?;
         Ok(SshConnection {
             inner: conn,
             pool_key: key,
             return_to_pool: true,
+            deadline: None,
         })
     }
 
-    /// Get the appropriate connection type for a host (SSH or local)
+    /// Get a channel handle on the persistent session for a host, opening the
+    /// session on first use or when the previous one went idle or dropped
+    fn get_multiplexed(&self, host: &Host, key: String) -> Result<SshConnection, NexusError> {
+        self.prune_idle();
+
+        if let Some(mut entry) = self.sessions.get_mut(&key) {
+            if entry.session.authenticated() {
+                entry.last_used = Instant::now();
+                return Ok(SshConnection {
+                    inner: PooledConnection {
+                        session: entry.session.clone(),
+                        host_name: entry.host_name.clone(),
+                        command_timeout: self.command_timeout,
+                    },
+                    pool_key: key,
+                    return_to_pool: false,
+                    deadline: None,
+                });
+            }
+        }
+        self.sessions.remove(&key);
+
+        let conn = self.connect_with_retry(host)?;
+        self.sessions.insert(
+            key.clone(),
+            MultiplexedSession {
+                session: conn.session.clone(),
+                host_name: conn.host_name.clone(),
+                last_used: Instant::now(),
+            },
+        );
+
+        Ok(SshConnection {
+            inner: conn,
+            pool_key: key,
+            return_to_pool: false,
+            deadline: None,
+        })
+    }
+
+    /// Close multiplexed sessions that have been idle longer than the idle timeout
+    pub fn prune_idle(&self) {
+        let idle_timeout = self.idle_timeout;
+        self.sessions
+            .retain(|_, entry| entry.last_used.elapsed() < idle_timeout);
+    }
+
+    /// Number of persistent multiplexed sessions currently open
+    pub fn multiplexed_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// The user to log in to `host` as: its own, the pool default, then `$USER`
+    pub fn login_password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    pub fn login_user(&self, host: &Host) -> String {
+        if host.user.is_empty() {
+            self.default_user
+                .clone()
+                .or_else(|| std::env::var("USER").ok())
+                .unwrap_or_else(|| "root".to_string())
+        } else {
+            host.user.clone()
+        }
+    }
+
+    /// An OpenSSH command line that reaches `host` on its port with the pool's
+    /// private key and host key checking, for tools that bring their own transport
+    /// (like `rsync -e`). OpenSSH can't be handed a password, so it runs in batch
+    /// mode and authenticates with the agent or a key.
+    pub fn openssh_command(&self, host: &Host) -> String {
+        let mut cmd = format!("ssh -p {} -o BatchMode=yes", host.port);
+        if let Some(ref key) = self.private_key_path {
+            // rsync splits `-e` on spaces but keeps quoted words together
+            cmd.push_str(&format!(" -i '{}'", key));
+        }
+        match self.host_key_checking {
+            HostKeyChecking::Strict => cmd.push_str(" -o StrictHostKeyChecking=yes"),
+            HostKeyChecking::AcceptNew => cmd.push_str(" -o StrictHostKeyChecking=accept-new"),
+            HostKeyChecking::Off => {
+                cmd.push_str(" -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null")
+            }
+        }
+        cmd
+    }
+
+    /// Get the appropriate connection type for a host (SSH, local, docker or WinRM)
     pub fn get_connection_type(&self, host: &Host) -> ConnectionType {
-        if host.is_local() {
+        if host.is_docker() {
+            ConnectionType::Docker
+        } else if host.is_winrm() {
+            ConnectionType::WinRm
+        } else if host.is_local() {
             ConnectionType::Local
         } else {
             ConnectionType::Ssh
@@ -122,50 +381,164 @@ impl ConnectionPool {
         }
     }
 
+    /// Connect, retrying transient failures with exponential backoff and jitter.
+    /// Each failed attempt counts against the host's circuit, which opens once a
+    /// connection has used up its retries; while it is open the host fails fast
+    /// instead of every task waiting through the retries again.
+    ///
+    /// Failures are returned as `NexusError::Unreachable`, so they are reported apart
+    /// from commands failing on a host that was reached.
+    fn connect_with_retry(&self, host: &Host) -> Result<PooledConnection, NexusError> {
+        let circuit = self.circuits.get_or_create(&CircuitBreakerConfig {
+            name: host.ssh_target(),
+            failure_threshold: self.connect_retries + 1,
+            reset_timeout: CONNECTION_CIRCUIT_RESET,
+            success_threshold: 1,
+        });
+        let backoff = DelayStrategy::Exponential {
+            base: self.retry_base_delay,
+            max: CONNECTION_RETRY_MAX_DELAY,
+            jitter: true,
+        };
+
+        let mut attempt = 0;
+        loop {
+            if !circuit.write().should_allow() {
+                let wait = circuit.read().time_until_retry().unwrap_or_default();
+                return Err(NexusError::Unreachable {
+                    host: host.name.clone(),
+                    message: "Host unreachable: earlier connection attempts failed".to_string(),
+                    suggestion: Some(format!(
+                        "Connections to this host are retried again in {}s",
+                        wait.as_secs().max(1)
+                    )),
+                });
+            }
+
+            match self.connect(host, self.connect_timeout) {
+                Ok(conn) => {
+                    circuit.write().record_success();
+                    return Ok(conn);
+                }
+                Err(ConnectFailure::Fatal(e)) => return Err(unreachable(e)),
+                Err(ConnectFailure::Transient(e)) => {
+                    let mut circuit = circuit.write();
+                    circuit.record_failure();
+                    if attempt >= self.connect_retries || circuit.state() == CircuitState::Open {
+                        return Err(unreachable(e));
+                    }
+                }
+            }
+
+            // Connecting is blocking already, so the backoff is too
+            std::thread::sleep(calculate_delay(&backoff, attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Drop the cached connections and multiplexed session for a host, e.g. because
+    /// it is rebooting and they are about to go stale
+    pub fn invalidate(&self, host: &Host) {
+        let key = host.ssh_target();
+        self.connections.remove(&key);
+        self.sessions.remove(&key);
+    }
+
+    /// Make a single connection attempt with its own timeout, bypassing the retries
+    /// and the host's circuit. Used to poll a host that is expected to be down for a
+    /// while; on success the host's circuit is reset and, when multiplexing, the new
+    /// session replaces the old one for later tasks.
+    pub fn reconnect(&self, host: &Host, timeout: Duration) -> Result<SshConnection, NexusError> {
+        let key = host.ssh_target();
+        let conn = self
+            .connect(host, timeout)
+            .map_err(|failure| match failure {
+                ConnectFailure::Transient(e) | ConnectFailure::Fatal(e) => e,
+            })?;
+        self.circuits.remove(&key);
+
+        if self.multiplex {
+            self.sessions.insert(
+                key.clone(),
+                MultiplexedSession {
+                    session: conn.session.clone(),
+                    host_name: conn.host_name.clone(),
+                    last_used: Instant::now(),
+                },
+            );
+        }
+
+        Ok(SshConnection {
+            inner: conn,
+            pool_key: key,
+            return_to_pool: !self.multiplex,
+            deadline: None,
+        })
+    }
+
     /// Create a new SSH connection
-    fn connect(&self, host: &Host) -> Result<PooledConnection, NexusError> {
+    fn connect(&self, host: &Host, timeout: Duration) -> Result<PooledConnection, ConnectFailure> {
         let address = format!("{}:{}", host.address, host.port);
 
         // TCP connection with timeout
         let tcp = TcpStream::connect_timeout(
-            &address.parse().map_err(|e| NexusError::Ssh {
-                host: host.name.clone(),
-                message: format!("Invalid address: {}", e),
-                suggestion: Some("Check the host address format".to_string()),
+            &address.parse().map_err(|e| {
+                ConnectFailure::Fatal(NexusError::Ssh {
+                    host: host.name.clone(),
+                    message: format!("Invalid address: {}", e),
+                    suggestion: Some("Check the host address format".to_string()),
+                })
             })?,
-            self.connect_timeout,
+            timeout,
         )
-        .map_err(|e| NexusError::Ssh {
-            host: host.name.clone(),
-            message: format!("Connection failed: {}", e),
-            suggestion: ssh_connection_suggestion(&e),
+        .map_err(|e| {
+            let error = if e.kind() == std::io::ErrorKind::TimedOut {
+                connect_timed_out(host, &address, timeout)
+            } else {
+                NexusError::Ssh {
+                    host: host.name.clone(),
+                    message: format!("Connection failed: {}", e),
+                    suggestion: ssh_connection_suggestion(&e),
+                }
+            };
+            if is_transient_connect_error(&e) {
+                ConnectFailure::Transient(error)
+            } else {
+                ConnectFailure::Fatal(error)
+            }
         })?;
 
         // SSH session
-        let mut session = Session::new().map_err(|e| NexusError::Ssh {
-            host: host.name.clone(),
-            message: format!("Failed to create SSH session: {}", e),
-            suggestion: None,
+        let mut session = Session::new().map_err(|e| {
+            ConnectFailure::Fatal(NexusError::Ssh {
+                host: host.name.clone(),
+                message: format!("Failed to create SSH session: {}", e),
+                suggestion: None,
+            })
         })?;
 
         session.set_tcp_stream(tcp);
-        session.set_timeout(self.connect_timeout.as_millis() as u32);
-
-        session.handshake().map_err(|e| NexusError::Ssh {
-            host: host.name.clone(),
-            message: format!("SSH handshake failed: {}", e),
-            suggestion: Some("Check SSH service is running on the target".to_string()),
+        session.set_timeout(timeout.as_millis() as u32);
+
+        // A host that is still booting may accept the connection before sshd is ready
+        session.handshake().map_err(|e| {
+            ConnectFailure::Transient(if e.code() == ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) {
+                connect_timed_out(host, &address, timeout)
+            } else {
+                NexusError::Ssh {
+                    host: host.name.clone(),
+                    message: format!("SSH handshake failed: {}", e),
+                    suggestion: Some("Check SSH service is running on the target".to_string()),
+                }
+            })
         })?;
 
+        // Verify the host before sending it any credentials
+        self.verify_host_key(&session, host)
+            .map_err(ConnectFailure::Fatal)?;
+
         // Authentication
-        let user = if host.user.is_empty() {
-            self.default_user
-                .clone()
-                .or_else(|| std::env::var("USER").ok())
-                .unwrap_or_else(|| "root".to_string())
-        } else {
-            host.user.clone()
-        };
+        let user = self.login_user(host);
 
         // Try SSH agent first
         let mut authenticated = false;
@@ -232,24 +605,101 @@ impl ConnectionPool {
         }
 
         if !authenticated {
-            return Err(NexusError::Ssh {
+            return Err(ConnectFailure::Fatal(NexusError::Ssh {
                 host: host.name.clone(),
                 message: "Authentication failed".to_string(),
                 suggestion: Some(
                     "Ensure SSH key is added to agent, specify --private-key, or use --ask-pass for password auth".to_string(),
                 ),
-            });
+            }));
         }
 
+        // From here on the session's timeout bounds each blocking read of a command's
+        // output; exec also enforces it as a deadline for the whole command
+        session.set_timeout(self.command_timeout.as_millis() as u32);
+
         Ok(PooledConnection {
             session,
             host_name: host.name.clone(),
+            command_timeout: self.command_timeout,
         })
     }
 
+    /// Check the server's host key against known_hosts according to `host_key_checking`
+    fn verify_host_key(&self, session: &Session, host: &Host) -> Result<(), NexusError> {
+        if self.host_key_checking == HostKeyChecking::Off {
+            return Ok(());
+        }
+
+        let (key, key_type) = session.host_key().ok_or_else(|| NexusError::Ssh {
+            host: host.name.clone(),
+            message: "Server did not present a host key".to_string(),
+            suggestion: None,
+        })?;
+        let fingerprint = session
+            .host_key_hash(HashType::Sha256)
+            .map(|hash| format!("SHA256:{}", BASE64_NO_PAD.encode(hash)))
+            .unwrap_or_default();
+        let known_hosts = dirs::home_dir()
+            .map(|home| home.join(".ssh/known_hosts"))
+            .ok_or_else(|| NexusError::Ssh {
+                host: host.name.clone(),
+                message: "Cannot locate known_hosts: HOME is not set".to_string(),
+                suggestion: Some("Set HOME, or use --host-key-checking=off".to_string()),
+            })?;
+
+        let _guard = self.known_hosts_lock.lock();
+        let check = check_known_host(&known_hosts, &host.address, host.port, key);
+
+        match host_key_decision(self.host_key_checking, check) {
+            HostKeyDecision::Trust => Ok(()),
+            HostKeyDecision::Record => {
+                record_known_host(&known_hosts, &host.address, host.port, key_type, key).map_err(
+                    |e| NexusError::Ssh {
+                        host: host.name.clone(),
+                        message: format!(
+                            "Failed to add host key to {}: {}",
+                            known_hosts.display(),
+                            e
+                        ),
+                        suggestion: Some("Check permissions on ~/.ssh".to_string()),
+                    },
+                )
+            }
+            HostKeyDecision::Unknown => Err(NexusError::Ssh {
+                host: host.name.clone(),
+                message: format!(
+                    "Host key for {} is not in {} ({} {})",
+                    host.address,
+                    known_hosts.display(),
+                    key_type_name(key_type),
+                    fingerprint
+                ),
+                suggestion: Some(format!(
+                    "Verify the fingerprint, then add it with 'ssh-keyscan -p {} {} >> ~/.ssh/known_hosts' or use --host-key-checking=accept-new",
+                    host.port, host.address
+                )),
+            }),
+            HostKeyDecision::Changed => Err(NexusError::Ssh {
+                host: host.name.clone(),
+                message: format!(
+                    "Host key for {} has changed ({} {}); this could be a man-in-the-middle attack",
+                    host.address,
+                    key_type_name(key_type),
+                    fingerprint
+                ),
+                suggestion: Some(format!(
+                    "If the host was reinstalled, remove the old key with 'ssh-keygen -R {}'",
+                    known_hosts_name(&host.address, host.port)
+                )),
+            }),
+        }
+    }
+
     /// Close all connections
     pub fn close_all(&self) {
         self.connections.clear();
+        self.sessions.clear();
     }
 }
 
@@ -263,6 +713,8 @@ impl Default for ConnectionPool {
 pub struct PooledConnection {
     session: Session,
     host_name: String,
+    /// How long a command may run before it's abandoned
+    command_timeout: Duration,
 }
 
 impl PooledConnection {
@@ -273,6 +725,33 @@ impl PooledConnection {
 
     /// Execute a command on this connection
     pub fn exec(&self, command: &str) -> Result<CommandResult, NexusError> {
+        self.exec_with_stdin(command, None)
+    }
+
+    /// Execute a command, writing `stdin` to it and then closing its input
+    pub fn exec_with_stdin(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+    ) -> Result<CommandResult, NexusError> {
+        self.exec_until(command, stdin, None)
+    }
+
+    /// When a command started now must stop: after the command timeout, or at
+    /// `deadline` if that comes first
+    fn command_deadline(&self, deadline: Option<Instant>) -> Instant {
+        let timeout = Instant::now() + self.command_timeout;
+        deadline.map_or(timeout, |deadline| deadline.min(timeout))
+    }
+
+    /// Execute a command like `exec_with_stdin`, closing its channel if it is still
+    /// running at `deadline`
+    fn exec_until(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        deadline: Option<Instant>,
+    ) -> Result<CommandResult, NexusError> {
         let mut channel = self
             .session
             .channel_session()
@@ -288,11 +767,37 @@ impl PooledConnection {
             suggestion: None,
         })?;
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
+        if let Some(input) = stdin {
+            channel
+                .write_all(input)
+                .and_then(|_| channel.send_eof().map_err(std::io::Error::from))
+                .map_err(|e| NexusError::Ssh {
+                    host: self.host_name.clone(),
+                    message: format!("Failed to write command input: {}", e),
+                    suggestion: None,
+                })?;
+        }
 
-        channel.read_to_string(&mut stdout).ok();
-        channel.stderr().read_to_string(&mut stderr).ok();
+        // A blocking read gives up after the session timeout, so shorten it to fit
+        let deadline = self.command_deadline(deadline);
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        self.session
+            .set_timeout(remaining.as_millis().max(1) as u32);
+        let output = read_before(&mut channel, deadline)
+            .and_then(|stdout| Ok((stdout, read_before(&mut channel.stderr(), deadline)?)));
+        self.session
+            .set_timeout(self.command_timeout.as_millis() as u32);
+        let (stdout, stderr) = match output {
+            Ok(output) => output,
+            Err(TimedOut) => {
+                channel.close().ok();
+                return Err(NexusError::command_timeout(
+                    &self.host_name,
+                    command,
+                    self.command_timeout,
+                ));
+            }
+        };
 
         channel.wait_close().ok();
         let exit_code = channel.exit_status().unwrap_or(-1);
@@ -306,10 +811,26 @@ impl PooledConnection {
 
     /// Execute a command with streaming output
     pub fn exec_streaming<F, G>(
+        &self,
+        command: &str,
+        on_stdout: F,
+        on_stderr: G,
+    ) -> Result<i32, NexusError>
+    where
+        F: FnMut(&[u8]),
+        G: FnMut(&[u8]),
+    {
+        self.exec_streaming_until(command, on_stdout, on_stderr, None)
+    }
+
+    /// Execute a command like `exec_streaming`, closing its channel if it is still
+    /// running at `deadline`
+    fn exec_streaming_until<F, G>(
         &self,
         command: &str,
         mut on_stdout: F,
         mut on_stderr: G,
+        deadline: Option<Instant>,
     ) -> Result<i32, NexusError>
     where
         F: FnMut(&[u8]),
@@ -330,6 +851,8 @@ impl PooledConnection {
             suggestion: None,
         })?;
 
+        let deadline = self.command_deadline(deadline);
+
         // Set non-blocking
         self.session.set_blocking(false);
 
@@ -366,6 +889,16 @@ impl PooledConnection {
                 break;
             }
 
+            if Instant::now() > deadline {
+                self.session.set_blocking(true);
+                channel.close().ok();
+                return Err(NexusError::command_timeout(
+                    &self.host_name,
+                    command,
+                    self.command_timeout,
+                ));
+            }
+
             if !activity {
                 std::thread::sleep(Duration::from_millis(10));
             }
@@ -470,11 +1003,28 @@ pub struct SshConnection {
     inner: PooledConnection,
     pool_key: String,
     return_to_pool: bool,
+    /// Commands still running at this point are stopped, e.g. at a task's timeout
+    deadline: Option<Instant>,
 }
 
 impl SshConnection {
+    /// Stop commands run through this connection at `deadline`, closing their
+    /// channels, as well as at the command timeout
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
     pub fn exec(&self, command: &str) -> Result<CommandResult, NexusError> {
-        self.inner.exec(command)
+        self.inner.exec_until(command, None, self.deadline)
+    }
+
+    pub fn exec_with_stdin(
+        &self,
+        command: &str,
+        stdin: &[u8],
+    ) -> Result<CommandResult, NexusError> {
+        self.inner.exec_until(command, Some(stdin), self.deadline)
     }
 
     pub fn exec_streaming<F, G>(
@@ -487,7 +1037,8 @@ impl SshConnection {
         F: FnMut(&[u8]),
         G: FnMut(&[u8]),
     {
-        self.inner.exec_streaming(command, on_stdout, on_stderr)
+        self.inner
+            .exec_streaming_until(command, on_stdout, on_stderr, self.deadline)
     }
 
     pub fn upload_file(&self, local: &Path, remote: &str) -> Result<(), NexusError> {
@@ -526,6 +1077,47 @@ impl CommandResult {
     }
 }
 
+/// libssh2's error code for a blocking call that ran past the session timeout
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+
+/// A command ran past its deadline
+struct TimedOut;
+
+/// Read `reader` to its end, giving up once `deadline` passes. A read that blocks
+/// longer than the session timeout fails with `TimedOut` too. Other read errors end
+/// the output early, as a dropped channel does.
+fn read_before(reader: &mut impl Read, deadline: Instant) -> Result<String, TimedOut> {
+    let mut output = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => output.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Err(TimedOut),
+            Err(_) => break,
+        }
+        if Instant::now() > deadline {
+            return Err(TimedOut);
+        }
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Connecting to `host` at `address` took longer than `timeout`
+fn connect_timed_out(host: &Host, address: &str, timeout: Duration) -> NexusError {
+    NexusError::Ssh {
+        host: host.name.clone(),
+        message: format!(
+            "Connection timed out after {}s ({})",
+            timeout.as_secs(),
+            address
+        ),
+        suggestion: Some(
+            "Check network connectivity and firewall rules, or raise --timeout".to_string(),
+        ),
+    }
+}
+
 fn ssh_connection_suggestion(e: &std::io::Error) -> Option<String> {
     match e.kind() {
         std::io::ErrorKind::ConnectionRefused => {
@@ -541,6 +1133,124 @@ fn ssh_connection_suggestion(e: &std::io::Error) -> Option<String> {
     }
 }
 
+/// Report a connection failure as the host being unreachable
+fn unreachable(error: NexusError) -> NexusError {
+    match error {
+        NexusError::Ssh {
+            host,
+            message,
+            suggestion,
+        } => NexusError::Unreachable {
+            host,
+            message,
+            suggestion,
+        },
+        other => other,
+    }
+}
+
+fn host_key_decision(mode: HostKeyChecking, check: CheckResult) -> HostKeyDecision {
+    match (mode, check) {
+        (HostKeyChecking::Off, _) | (_, CheckResult::Match) => HostKeyDecision::Trust,
+        (HostKeyChecking::AcceptNew, CheckResult::NotFound) => HostKeyDecision::Record,
+        (_, CheckResult::Mismatch) => HostKeyDecision::Changed,
+        (HostKeyChecking::Strict, CheckResult::NotFound) | (_, CheckResult::Failure) => {
+            HostKeyDecision::Unknown
+        }
+    }
+}
+
+/// Look a host key up in a known_hosts file. A missing file knows no hosts.
+fn check_known_host(path: &Path, address: &str, port: u16, key: &[u8]) -> CheckResult {
+    let Ok(session) = Session::new() else {
+        return CheckResult::Failure;
+    };
+    let Ok(mut known_hosts) = session.known_hosts() else {
+        return CheckResult::Failure;
+    };
+    if path.exists()
+        && known_hosts
+            .read_file(path, KnownHostFileKind::OpenSSH)
+            .is_err()
+    {
+        return CheckResult::Failure;
+    }
+    known_hosts.check_port(address, port, key)
+}
+
+/// Append a host key to a known_hosts file, creating it (and its directory) if needed.
+/// Appending leaves existing entries, comments and markers exactly as they were.
+fn record_known_host(
+    path: &Path,
+    address: &str,
+    port: u16,
+    key_type: HostKeyType,
+    key: &[u8],
+) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+            }
+        }
+    }
+
+    // Start on a new line if the file doesn't end with one
+    let needs_newline = std::fs::read(path)
+        .map(|content| !content.is_empty() && !content.ends_with(b"\n"))
+        .unwrap_or(false);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(
+        file,
+        "{}{} {} {}",
+        if needs_newline { "\n" } else { "" },
+        known_hosts_name(address, port),
+        key_type_name(key_type),
+        BASE64.encode(key)
+    )
+}
+
+/// Hosts on a non-default port are written `[address]:port`, as OpenSSH does
+fn known_hosts_name(address: &str, port: u16) -> String {
+    if port == 22 {
+        address.to_string()
+    } else {
+        format!("[{}]:{}", address, port)
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => "unknown",
+    }
+}
+
+/// Errors a host that is down or still booting produces; worth retrying
+fn is_transient_connect_error(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::HostUnreachable
+            | std::io::ErrorKind::NetworkUnreachable
+    )
+}
+
 /// Simple home directory lookup
 mod dirs {
     use std::path::PathBuf;
@@ -569,9 +1279,13 @@ impl KeyboardInteractivePrompt for PasswordPrompter {
 #[async_trait]
 impl Connection for SshConnection {
     async fn exec(&self, cmd: &str) -> Result<CommandResult, NexusError> {
-        // SSH operations are blocking, so we run them in a blocking task
-        let result = self.inner.exec(cmd)?;
-        Ok(result)
+        // SSH operations are blocking, so a task's timeout can't interrupt them from
+        // outside; the deadline stops them instead
+        SshConnection::exec(self, cmd)
+    }
+
+    async fn exec_with_stdin(&self, cmd: &str, stdin: &str) -> Result<CommandResult, NexusError> {
+        SshConnection::exec_with_stdin(self, cmd, stdin.as_bytes())
     }
 
     async fn exec_streaming(
@@ -580,26 +1294,36 @@ impl Connection for SshConnection {
         on_stdout: Box<dyn Fn(String) + Send + Sync>,
         on_stderr: Box<dyn Fn(String) + Send + Sync>,
     ) -> Result<CommandResult, NexusError> {
-        // Convert byte callbacks to string callbacks
+        // Channel reads are arbitrary chunks; hand the callbacks whole lines, like a
+        // local command, and keep the full output for the result
+        let (mut stdout, mut stdout_pending) = (String::new(), String::new());
+        let (mut stderr, mut stderr_pending) = (String::new(), String::new());
+
         let stdout_callback = |bytes: &[u8]| {
-            if let Ok(s) = std::str::from_utf8(bytes) {
-                on_stdout(s.to_string());
-            }
+            let text = String::from_utf8_lossy(bytes);
+            stdout.push_str(&text);
+            emit_lines(&mut stdout_pending, &text, &*on_stdout);
         };
 
         let stderr_callback = |bytes: &[u8]| {
-            if let Ok(s) = std::str::from_utf8(bytes) {
-                on_stderr(s.to_string());
-            }
+            let text = String::from_utf8_lossy(bytes);
+            stderr.push_str(&text);
+            emit_lines(&mut stderr_pending, &text, &*on_stderr);
         };
 
-        let exit_code = self
-            .inner
-            .exec_streaming(cmd, stdout_callback, stderr_callback)?;
+        let exit_code = self.exec_streaming(cmd, stdout_callback, stderr_callback)?;
+
+        // A last line without a newline
+        if !stdout_pending.is_empty() {
+            on_stdout(stdout_pending);
+        }
+        if !stderr_pending.is_empty() {
+            on_stderr(stderr_pending);
+        }
 
         Ok(CommandResult {
-            stdout: String::new(),
-            stderr: String::new(),
+            stdout,
+            stderr,
             exit_code,
         })
     }
@@ -620,3 +1344,220 @@ impl Connection for SshConnection {
         self.inner.host_name.as_str()
     }
 }
+
+/// Call `emit` for each complete line in `pending` followed by `text`, keeping a
+/// trailing partial line in `pending`
+fn emit_lines(pending: &mut String, text: &str, emit: &(dyn Fn(String) + Send + Sync)) {
+    pending.push_str(text);
+    while let Some(pos) = pending.find('\n') {
+        emit(pending.drain(..=pos).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiplex_builder() {
+        let pool = ConnectionPool::new();
+        assert!(!pool.is_multiplexed());
+        assert_eq!(pool.idle_timeout, DEFAULT_MULTIPLEX_IDLE_TIMEOUT);
+
+        let pool = ConnectionPool::new()
+            .with_multiplex(true)
+            .with_idle_timeout(Duration::from_secs(5));
+        assert!(pool.is_multiplexed());
+        assert_eq!(pool.idle_timeout, Duration::from_secs(5));
+        assert_eq!(pool.multiplexed_sessions(), 0);
+    }
+
+    #[test]
+    fn test_read_before_deadline() {
+        struct Stalled;
+        impl Read for Stalled {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::TimedOut.into())
+            }
+        }
+
+        let later = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            read_before(&mut "done\n".as_bytes(), later).ok().as_deref(),
+            Some("done\n")
+        );
+        // A command that prints nothing until the session timeout
+        assert!(read_before(&mut Stalled, later).is_err());
+        // A command that keeps printing past its deadline
+        assert!(read_before(&mut std::io::repeat(b'y'), Instant::now()).is_err());
+    }
+
+    #[test]
+    fn test_connection_type() {
+        let pool = ConnectionPool::new();
+        let conn = |kind: &str| {
+            Host::new("app").with_var(
+                "ansible_connection",
+                crate::parser::ast::Value::String(kind.into()),
+            )
+        };
+
+        assert_eq!(
+            pool.get_connection_type(&Host::new("web1")),
+            ConnectionType::Ssh
+        );
+        assert_eq!(
+            pool.get_connection_type(&Host::new("localhost")),
+            ConnectionType::Local
+        );
+        assert_eq!(
+            pool.get_connection_type(&conn("local")),
+            ConnectionType::Local
+        );
+        assert_eq!(
+            pool.get_connection_type(&conn("docker")),
+            ConnectionType::Docker
+        );
+        assert_eq!(
+            pool.get_connection_type(&conn("winrm")),
+            ConnectionType::WinRm
+        );
+    }
+
+    #[test]
+    fn test_connection_retries_then_circuit_opens() {
+        // A port nothing listens on refuses connections, like a host whose sshd isn't up yet
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let host = Host::new("booting")
+            .with_address("127.0.0.1")
+            .with_port(port);
+        let pool = ConnectionPool::new()
+            .with_connect_retries(2)
+            .with_retry_delay(Duration::from_millis(20));
+
+        // Two retries back off 20ms and then 40ms (plus jitter)
+        let start = Instant::now();
+        let err = pool.get(&host).err().unwrap();
+        assert!(matches!(err, NexusError::Unreachable { .. }));
+        assert!(err.to_string().contains("Connection failed"));
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        // The host's circuit is now open: later tasks fail fast without retrying
+        let start = Instant::now();
+        let err = pool.get(&host).err().unwrap();
+        assert!(err.to_string().contains("Host unreachable"));
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_host_key_decision() {
+        use HostKeyChecking::*;
+
+        assert_eq!(
+            host_key_decision(Strict, CheckResult::Match),
+            HostKeyDecision::Trust
+        );
+        assert_eq!(
+            host_key_decision(Strict, CheckResult::NotFound),
+            HostKeyDecision::Unknown
+        );
+        assert_eq!(
+            host_key_decision(AcceptNew, CheckResult::NotFound),
+            HostKeyDecision::Record
+        );
+        assert_eq!(
+            host_key_decision(AcceptNew, CheckResult::Mismatch),
+            HostKeyDecision::Changed
+        );
+        assert_eq!(
+            host_key_decision(Off, CheckResult::Mismatch),
+            HostKeyDecision::Trust
+        );
+
+        assert_eq!("accept-new".parse(), Ok(AcceptNew));
+        assert_eq!("no".parse(), Ok(Off));
+        assert!("sometimes".parse::<HostKeyChecking>().is_err());
+    }
+
+    #[test]
+    fn test_record_and_check_known_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".ssh").join("known_hosts");
+        // ssh-ed25519 public key blob: string "ssh-ed25519" then the 32-byte key
+        let key_blob = |fill: u8| {
+            let mut blob = Vec::new();
+            blob.extend_from_slice(&11u32.to_be_bytes());
+            blob.extend_from_slice(b"ssh-ed25519");
+            blob.extend_from_slice(&32u32.to_be_bytes());
+            blob.extend_from_slice(&[fill; 32]);
+            blob
+        };
+        let key = key_blob(1);
+
+        assert!(matches!(
+            check_known_host(&path, "10.0.0.5", 2222, &key),
+            CheckResult::NotFound
+        ));
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "# managed by hand").unwrap();
+        record_known_host(&path, "10.0.0.5", 2222, HostKeyType::Ed25519, &key).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# managed by hand\n[10.0.0.5]:2222 ssh-ed25519 "));
+
+        assert!(matches!(
+            check_known_host(&path, "10.0.0.5", 2222, &key),
+            CheckResult::Match
+        ));
+        assert!(matches!(
+            check_known_host(&path, "10.0.0.5", 2222, &key_blob(2)),
+            CheckResult::Mismatch
+        ));
+        assert!(matches!(
+            check_known_host(&path, "10.0.0.5", 22, &key),
+            CheckResult::NotFound
+        ));
+    }
+
+    /// Measures per-task connection overhead against a real sshd.
+    ///
+    /// Run with: NEXUS_TEST_SSH_HOST=user@127.0.0.1 cargo test -- --ignored multiplex
+    #[test]
+    #[ignore = "requires a local sshd (set NEXUS_TEST_SSH_HOST=user@host)"]
+    fn test_multiplex_reduces_connection_overhead() {
+        let target = std::env::var("NEXUS_TEST_SSH_HOST").expect("NEXUS_TEST_SSH_HOST not set");
+        let (user, address) = target.split_once('@').unwrap_or(("", target.as_str()));
+        let mut host = Host::new("sshd-test").with_address(address);
+        host.user = user.to_string();
+
+        const TASKS: u32 = 10;
+
+        let plain = ConnectionPool::new();
+        let start = Instant::now();
+        for _ in 0..TASKS {
+            let conn = plain.get(&host).unwrap();
+            assert!(conn.exec("true").unwrap().success());
+        }
+        let plain_elapsed = start.elapsed();
+
+        let multiplexed = ConnectionPool::new().with_multiplex(true);
+        let start = Instant::now();
+        for _ in 0..TASKS {
+            let conn = multiplexed.get(&host).unwrap();
+            assert!(conn.exec("true").unwrap().success());
+        }
+        let multiplexed_elapsed = start.elapsed();
+
+        assert_eq!(multiplexed.multiplexed_sessions(), 1);
+        eprintln!(
+            "per-task overhead: plain {:?}, multiplexed {:?}",
+            plain_elapsed / TASKS,
+            multiplexed_elapsed / TASKS
+        );
+        assert!(multiplexed_elapsed < plain_elapsed);
+    }
+}
@@ -18,6 +18,8 @@ pub struct TagFilter {
     tag_groups: HashMap<String, Vec<String>>,
     /// Whether to run untagged tasks (default: true unless tags specified)
     run_untagged: bool,
+    /// Tags of the enclosing blocks, roles and imports, which every task inside carries too
+    inherited_tags: Vec<String>,
 }
 
 impl TagFilter {
@@ -27,6 +29,7 @@ impl TagFilter {
             skip_tags: HashSet::new(),
             tag_groups: Self::default_tag_groups(),
             run_untagged: true,
+            inherited_tags: Vec::new(),
         }
     }
 
@@ -127,34 +130,44 @@ impl TagFilter {
         expanded
     }
 
-    /// Check if a task should run based on its tags
+    /// The filter for the contents of a block, role or import tagged with `tags`:
+    /// its tasks are matched on their own tags plus these, so `always` and `never`
+    /// on the container apply to every task in it
+    pub fn with_inherited_tags(&self, tags: &[String]) -> TagFilter {
+        let mut filter = self.clone();
+        filter.inherited_tags = inherit_tags(&self.inherited_tags, tags);
+        filter
+    }
+
+    /// Check if a task should run based on its tags and the ones it inherits.
+    /// Precedence follows Ansible: a skipped tag always wins, then `always` runs,
+    /// then `never` stays off unless one of its tags is included, and only then are
+    /// the include tags matched.
     pub fn should_run(&self, task_tags: &[String]) -> bool {
+        let task_tags = inherit_tags(&self.inherited_tags, task_tags);
         let task_tags_lower: HashSet<String> = task_tags.iter().map(|t| t.to_lowercase()).collect();
+        let expanded_skip = self.expand_tags(&self.skip_tags);
+        let expanded_include = self.expand_tags(&self.include_tags);
 
-        // Special tag: "always" - always runs unless explicitly skipped
-        if task_tags_lower.contains("always") {
-            let expanded_skip = self.expand_tags(&self.skip_tags);
-            if !expanded_skip.contains("always") {
-                return true;
-            }
+        // Skip tags first: exclusion beats include tags and "always" alike
+        if task_tags_lower
+            .iter()
+            .any(|tag| expanded_skip.contains(tag))
+        {
+            return false;
         }
 
-        // Special tag: "never" - never runs unless explicitly included
-        if task_tags_lower.contains("never") {
-            let expanded_include = self.expand_tags(&self.include_tags);
-            if !expanded_include.contains("never") {
-                return false;
-            }
+        // Special tag: "always" - runs whatever the include tags (it wasn't skipped above)
+        if task_tags_lower.contains("always") {
+            return true;
         }
 
-        // Check skip tags first (exclusion takes priority)
-        if !self.skip_tags.is_empty() {
-            let expanded_skip = self.expand_tags(&self.skip_tags);
-            for skip_tag in &expanded_skip {
-                if task_tags_lower.contains(skip_tag) {
-                    return false;
-                }
-            }
+        // Special tag: "never" - never runs unless "never" or another of its tags is included
+        let included = task_tags_lower
+            .iter()
+            .any(|tag| expanded_include.contains(tag));
+        if task_tags_lower.contains("never") && !included {
+            return false;
         }
 
         // If no include tags specified, use run_untagged setting
@@ -162,49 +175,40 @@ impl TagFilter {
             return self.run_untagged || !task_tags.is_empty();
         }
 
-        // Check if any include tag matches
-        let expanded_include = self.expand_tags(&self.include_tags);
-        for include_tag in &expanded_include {
-            if task_tags_lower.contains(include_tag) {
-                return true;
-            }
-        }
-
-        // No match found
-        false
+        included
     }
 
-    /// Get a human-readable description of the filter
+    /// Get a human-readable description of the filter, with the rules it applies:
+    /// skipped tags win over everything, `always` runs unless skipped, and `never`
+    /// only runs when one of its tags is included
     pub fn describe(&self) -> String {
-        let mut parts = Vec::new();
+        let sorted = |tags: &HashSet<String>| {
+            let mut tags: Vec<&str> = tags.iter().map(|t| t.as_str()).collect();
+            tags.sort_unstable();
+            tags.join(", ")
+        };
 
+        let mut parts = Vec::new();
         if !self.include_tags.is_empty() {
-            parts.push(format!(
-                "include: [{}]",
-                self.include_tags
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
+            parts.push(format!("include: [{}]", sorted(&self.include_tags)));
         }
-
         if !self.skip_tags.is_empty() {
-            parts.push(format!(
-                "skip: [{}]",
-                self.skip_tags
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
+            parts.push(format!("skip: [{}]", sorted(&self.skip_tags)));
         }
-
         if parts.is_empty() {
-            "all tasks".to_string()
-        } else {
-            parts.join(", ")
+            return "all tasks".to_string();
         }
+
+        let always = if self.expand_tags(&self.skip_tags).contains("always") {
+            "'always' tasks skipped"
+        } else {
+            "'always' tasks run"
+        };
+        format!(
+            "{} (skipped tags win; {}; 'never' tasks only when included)",
+            parts.join(", "),
+            always
+        )
     }
 }
 
@@ -264,6 +268,71 @@ mod tests {
         assert!(filter_with_never.should_run(&["never".to_string()]));
     }
 
+    #[test]
+    fn test_precedence() {
+        // (--tags, --skip-tags, task tags, runs)
+        type Case = (
+            Option<&'static str>,
+            Option<&'static str>,
+            &'static [&'static str],
+            bool,
+        );
+        let cases: &[Case] = &[
+            // Skip wins over include
+            (Some("deploy"), Some("slow"), &["deploy", "slow"], false),
+            (Some("deploy"), Some("slow"), &["deploy"], true),
+            (None, Some("slow"), &["slow"], false),
+            (None, Some("slow"), &[], true),
+            // always runs unless skipped, by "always" or by another of its tags
+            (Some("deploy"), None, &["always"], true),
+            (None, Some("always"), &["always"], false),
+            (Some("deploy"), Some("always"), &["always", "deploy"], false),
+            (None, Some("debug"), &["always", "debug"], false),
+            (Some("always"), None, &["always"], true),
+            // never is opt-in, by "never" or another of its tags, and skip still wins
+            (None, None, &["never"], false),
+            (None, None, &["never", "debug"], false),
+            (Some("debug"), None, &["never", "debug"], true),
+            (Some("never"), None, &["never", "debug"], true),
+            (Some("deploy"), None, &["never", "debug"], false),
+            (Some("debug"), Some("never"), &["never", "debug"], false),
+            // A task tagged both always and never is skipped only by --skip-tags
+            (None, None, &["always", "never"], true),
+            // Untagged tasks run only without --tags
+            (None, None, &[], true),
+            (Some("deploy"), None, &[], false),
+            // Groups expand on both sides
+            (Some("@security"), Some("audit"), &["audit"], false),
+            (Some("@security"), Some("audit"), &["hardening"], true),
+        ];
+
+        for (tags, skip, task_tags, expected) in cases {
+            let filter = TagFilter::from_args(*tags, *skip);
+            let task_tags: Vec<String> = task_tags.iter().map(|t| t.to_string()).collect();
+            assert_eq!(
+                filter.should_run(&task_tags),
+                *expected,
+                "--tags {:?} --skip-tags {:?} on {:?}",
+                tags,
+                skip,
+                task_tags
+            );
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(TagFilter::from_args(None, None).describe(), "all tasks");
+        assert_eq!(
+            TagFilter::from_args(Some("web,db"), Some("slow")).describe(),
+            "include: [db, web], skip: [slow] (skipped tags win; 'always' tasks run; \
+             'never' tasks only when included)"
+        );
+        assert!(TagFilter::from_args(None, Some("always"))
+            .describe()
+            .contains("'always' tasks skipped"));
+    }
+
     #[test]
     fn test_tag_groups() {
         let filter = TagFilter::from_args(Some("@security"), None);
@@ -284,6 +353,33 @@ mod tests {
         assert!(filter.should_run(&["Deploy".to_string()]));
     }
 
+    #[test]
+    fn test_inherited_tags() {
+        let filter = TagFilter::from_args(Some("web"), None);
+        let block = filter.with_inherited_tags(&["web".to_string()]);
+        assert!(block.should_run(&["install".to_string()]));
+        assert!(block.should_run(&[]));
+        assert!(!filter.should_run(&["install".to_string()]));
+
+        // always/never on a block apply to everything in it
+        let filter = TagFilter::from_args(Some("deploy"), None);
+        assert!(filter
+            .with_inherited_tags(&["always".to_string()])
+            .should_run(&["cleanup".to_string()]));
+        let filter = TagFilter::from_args(None, None);
+        let debug = filter.with_inherited_tags(&["never".to_string(), "debug".to_string()]);
+        assert!(!debug.should_run(&["verbose".to_string()]));
+        let filter = TagFilter::from_args(Some("debug"), None);
+        assert!(filter
+            .with_inherited_tags(&["never".to_string(), "debug".to_string()])
+            .should_run(&[]));
+
+        // Skipping a role's tag skips its tasks, whatever they are tagged with
+        let filter = TagFilter::from_args(None, Some("nginx"));
+        let role = filter.with_inherited_tags(&["nginx".to_string()]);
+        assert!(!role.should_run(&["config".to_string()]));
+    }
+
     #[test]
     fn test_inherit_tags() {
         let playbook_tags = vec!["production".to_string()];
@@ -0,0 +1,785 @@
+// WinRM connection for Windows hosts: WS-Management shells running PowerShell
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::ntlm;
+use super::{ps_quote, CommandResult, Connection, ConnectionPool};
+use crate::inventory::Host;
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+const SHELL_RESOURCE: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/cmd";
+const ACTION_CREATE: &str = "http://schemas.xmlsoap.org/ws/2004/09/transfer/Create";
+const ACTION_DELETE: &str = "http://schemas.xmlsoap.org/ws/2004/09/transfer/Delete";
+const ACTION_COMMAND: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Command";
+const ACTION_SEND: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Send";
+const ACTION_RECEIVE: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Receive";
+const ACTION_SIGNAL: &str = "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/Signal";
+const SIGNAL_TERMINATE: &str =
+    "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/signal/terminate";
+const STATE_DONE: &str =
+    "http://schemas.microsoft.com/wbem/wsman/1/windows/shell/CommandState/Done";
+
+/// WS-Management fault code for a Receive that timed out with no output yet
+const FAULT_OPERATION_TIMEOUT: &str = "2150858793";
+
+/// How long the server may hold a Receive open waiting for output
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Raw stdin bytes per Send message, well under the default 150KB envelope limit
+const STDIN_CHUNK: usize = 32 * 1024;
+
+/// How to authenticate to the WinRM listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinRmAuth {
+    Basic,
+    Ntlm,
+}
+
+/// Connection that runs PowerShell on a Windows host over WinRM (`ansible_connection: winrm`)
+pub struct WinRmConnection {
+    host_name: String,
+    endpoint: String,
+    user: String,
+    password: String,
+    auth: WinRmAuth,
+    client: reqwest::Client,
+}
+
+impl WinRmConnection {
+    /// Connect to a host's WinRM listener using its inventory vars:
+    /// `ansible_winrm_scheme` (`https` unless the port is 5985), `ansible_port`
+    /// (5986 for https, 5985 for http), `ansible_winrm_transport` (`ntlm` or `basic`),
+    /// `ansible_winrm_server_cert_validation` (`ignore` to accept self-signed
+    /// certificates) and `ansible_password`, falling back to the pool's password
+    pub fn for_host(host: &Host, pool: &ConnectionPool) -> Result<Self, NexusError> {
+        let var = |name: &str| match host.vars.get(name) {
+            Some(Value::String(s)) => Some(s.to_lowercase()),
+            _ => None,
+        };
+        let config_error = |message: String| NexusError::Runtime {
+            function: None,
+            message,
+            suggestion: Some("See the WinRM section of docs/inventory.md".to_string()),
+            location: None,
+        };
+
+        let scheme = match var("ansible_winrm_scheme") {
+            Some(scheme) if scheme == "http" || scheme == "https" => scheme,
+            Some(other) => {
+                return Err(config_error(format!(
+                    "Invalid ansible_winrm_scheme '{}' for {} (expected http or https)",
+                    other, host.name
+                )))
+            }
+            None if host.port == 5985 => "http".to_string(),
+            None => "https".to_string(),
+        };
+        let port = match host.port {
+            // 22 is the inventory's SSH default, not something set for WinRM
+            22 if scheme == "http" => 5985,
+            22 => 5986,
+            port => port,
+        };
+        let auth = match var("ansible_winrm_transport").as_deref() {
+            None | Some("ntlm") => WinRmAuth::Ntlm,
+            Some("basic") => WinRmAuth::Basic,
+            Some(other) => {
+                return Err(config_error(format!(
+                    "Unsupported ansible_winrm_transport '{}' for {} (expected ntlm or basic)",
+                    other, host.name
+                )))
+            }
+        };
+        let password = match host.vars.get("ansible_password") {
+            Some(Value::String(password)) => password.clone(),
+            _ => pool.login_password().map(str::to_string).ok_or_else(|| {
+                config_error(format!(
+                    "No password for WinRM host {}: set ansible_password or pass --ask-pass",
+                    host.name
+                ))
+            })?,
+        };
+
+        let address = if host.address.contains(':') {
+            format!("[{}]", host.address)
+        } else {
+            host.address.clone()
+        };
+        let accept_invalid_certs =
+            var("ansible_winrm_server_cert_validation").as_deref() == Some("ignore");
+        // NTLM authenticates the TCP connection, so all legs must share one
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(accept_invalid_certs)
+            .pool_max_idle_per_host(1)
+            .timeout(OPERATION_TIMEOUT + Duration::from_secs(10))
+            .build()
+            .map_err(|e| config_error(format!("Failed to set up WinRM client: {}", e)))?;
+
+        Ok(WinRmConnection {
+            host_name: host.name.clone(),
+            endpoint: format!("{}://{}:{}/wsman", scheme, address, port),
+            user: pool.login_user(host),
+            password,
+            auth,
+            client,
+        })
+    }
+
+    /// URL of the host's WS-Management endpoint
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    fn error(&self, message: String) -> NexusError {
+        NexusError::Runtime {
+            function: None,
+            message: format!("WinRM error on {}: {}", self.host_name, message),
+            suggestion: Some(
+                "Check that WinRM is enabled on the host and the credentials are correct"
+                    .to_string(),
+            ),
+            location: None,
+        }
+    }
+
+    fn fault_error(&self, fault: &Fault) -> NexusError {
+        self.error(format!("{} (fault {})", fault.message.trim(), fault.code))
+    }
+
+    /// POST a SOAP envelope and return the response
+    async fn post(&self, body: String) -> Result<String, NexusError> {
+        self.post_allowing_fault(body)
+            .await?
+            .map_err(|fault| self.fault_error(&fault))
+    }
+
+    /// POST a SOAP envelope, handing back a WS-Management fault separately so the
+    /// Receive loop can tell a timeout from a real error
+    async fn post_allowing_fault(&self, body: String) -> Result<Result<String, Fault>, NexusError> {
+        let request = |body: String| {
+            self.client
+                .post(&self.endpoint)
+                .header("Content-Type", "application/soap+xml;charset=UTF-8")
+                .body(body)
+        };
+        let send = |req: reqwest::RequestBuilder| async move {
+            req.send()
+                .await
+                .map_err(|e| self.error(format!("request to {} failed: {}", self.endpoint, e)))
+        };
+
+        let response = match self.auth {
+            WinRmAuth::Basic => {
+                let credentials = BASE64.encode(format!("{}:{}", self.user, self.password));
+                send(request(body).header("Authorization", format!("Basic {}", credentials)))
+                    .await?
+            }
+            WinRmAuth::Ntlm => {
+                let negotiate = BASE64.encode(ntlm::negotiate_message());
+                let challenge = send(
+                    request(String::new())
+                        .header("Authorization", format!("Negotiate {}", negotiate)),
+                )
+                .await?;
+                let token = challenge
+                    .headers()
+                    .get_all("WWW-Authenticate")
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .find_map(|value| value.strip_prefix("Negotiate "))
+                    .and_then(|token| BASE64.decode(token.trim()).ok());
+                // Drain the body so the authenticated connection goes back to the pool
+                let status = challenge.status();
+                let _ = challenge.bytes().await;
+                let token = token.ok_or_else(|| {
+                    self.error(format!(
+                        "listener did not offer NTLM authentication ({})",
+                        status
+                    ))
+                })?;
+                let challenge = ntlm::parse_challenge(&token).map_err(|e| self.error(e))?;
+                let authenticate = BASE64.encode(ntlm::authenticate_message(
+                    &challenge,
+                    &self.user,
+                    &self.password,
+                    rand::random(),
+                ));
+                send(request(body).header("Authorization", format!("Negotiate {}", authenticate)))
+                    .await?
+            }
+        };
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| self.error(format!("failed to read response: {}", e)))?;
+
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(self.error(format!("authentication failed for user {}", self.user)));
+        }
+        if status.is_success() {
+            return Ok(Ok(text));
+        }
+        match Fault::parse(&text) {
+            Some(fault) => Ok(Err(fault)),
+            None => Err(self.error(format!("listener returned {}", status))),
+        }
+    }
+
+    fn envelope(
+        &self,
+        action: &str,
+        shell_id: Option<&str>,
+        options: &[(&str, &str)],
+        body: &str,
+    ) -> String {
+        let selector = shell_id
+            .map(|id| {
+                format!(
+                    "<w:SelectorSet><w:Selector Name=\"ShellId\">{}</w:Selector></w:SelectorSet>",
+                    xml_escape(id)
+                )
+            })
+            .unwrap_or_default();
+        let option_set = if options.is_empty() {
+            String::new()
+        } else {
+            let options: String = options
+                .iter()
+                .map(|(name, value)| format!("<w:Option Name=\"{}\">{}</w:Option>", name, value))
+                .collect();
+            format!("<w:OptionSet>{}</w:OptionSet>", options)
+        };
+
+        format!(
+            concat!(
+                "<s:Envelope xmlns:s=\"http://www.w3.org/2003/05/soap-envelope\" ",
+                "xmlns:a=\"http://schemas.xmlsoap.org/ws/2004/08/addressing\" ",
+                "xmlns:w=\"http://schemas.dmtf.org/wbem/wsman/1/wsman.xsd\" ",
+                "xmlns:rsp=\"http://schemas.microsoft.com/wbem/wsman/1/windows/shell\">",
+                "<s:Header>",
+                "<a:To>{endpoint}</a:To>",
+                "<a:ReplyTo><a:Address s:mustUnderstand=\"true\">",
+                "http://schemas.xmlsoap.org/ws/2004/08/addressing/role/anonymous",
+                "</a:Address></a:ReplyTo>",
+                "<a:Action s:mustUnderstand=\"true\">{action}</a:Action>",
+                "<a:MessageID>uuid:{id}</a:MessageID>",
+                "<w:ResourceURI s:mustUnderstand=\"true\">{resource}</w:ResourceURI>",
+                "<w:MaxEnvelopeSize s:mustUnderstand=\"true\">153600</w:MaxEnvelopeSize>",
+                "<w:OperationTimeout>PT{timeout}S</w:OperationTimeout>",
+                "{selector}{options}",
+                "</s:Header>",
+                "<s:Body>{body}</s:Body>",
+                "</s:Envelope>"
+            ),
+            endpoint = xml_escape(&self.endpoint),
+            action = action,
+            id = message_id(),
+            resource = SHELL_RESOURCE,
+            timeout = OPERATION_TIMEOUT.as_secs(),
+            selector = selector,
+            options = option_set,
+            body = body,
+        )
+    }
+
+    /// Run a PowerShell script in a fresh shell, feeding it `stdin` and passing output
+    /// to `on_output` as it arrives
+    async fn run(
+        &self,
+        script: &str,
+        stdin: Option<&str>,
+        on_output: &(dyn Fn(&str, bool) + Send + Sync),
+    ) -> Result<CommandResult, NexusError> {
+        let create = self.envelope(
+            ACTION_CREATE,
+            None,
+            &[("WINRS_NOPROFILE", "FALSE"), ("WINRS_CODEPAGE", "65001")],
+            "<rsp:Shell><rsp:InputStreams>stdin</rsp:InputStreams>\
+             <rsp:OutputStreams>stdout stderr</rsp:OutputStreams></rsp:Shell>",
+        );
+        let response = self.post(create).await?;
+        let shell_id = element_text(&response, "ShellId")
+            .or_else(|| element_text(&response, "Selector"))
+            .ok_or_else(|| self.error("no shell id in Create response".to_string()))?;
+
+        let result = self.run_in_shell(&shell_id, script, stdin, on_output).await;
+
+        let delete = self.envelope(ACTION_DELETE, Some(&shell_id), &[], "");
+        let _ = self.post(delete).await;
+        result
+    }
+
+    async fn run_in_shell(
+        &self,
+        shell_id: &str,
+        script: &str,
+        stdin: Option<&str>,
+        on_output: &(dyn Fn(&str, bool) + Send + Sync),
+    ) -> Result<CommandResult, NexusError> {
+        let command = self.envelope(
+            ACTION_COMMAND,
+            Some(shell_id),
+            &[
+                ("WINRS_CONSOLEMODE_STDIN", "TRUE"),
+                ("WINRS_SKIP_CMD_SHELL", "FALSE"),
+            ],
+            &format!(
+                "<rsp:CommandLine><rsp:Command>powershell.exe</rsp:Command>\
+                 <rsp:Arguments>-NoProfile -NonInteractive -ExecutionPolicy Bypass \
+                 -EncodedCommand {}</rsp:Arguments></rsp:CommandLine>",
+                encode_powershell(script)
+            ),
+        );
+        let response = self.post(command).await?;
+        let command_id = element_text(&response, "CommandId")
+            .ok_or_else(|| self.error("no command id in Command response".to_string()))?;
+
+        if let Some(input) = stdin {
+            let chunks: Vec<&[u8]> = if input.is_empty() {
+                vec![&[]]
+            } else {
+                input.as_bytes().chunks(STDIN_CHUNK).collect()
+            };
+            let last = chunks.len() - 1;
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let end = if i == last { " End=\"true\"" } else { "" };
+                let send = self.envelope(
+                    ACTION_SEND,
+                    Some(shell_id),
+                    &[],
+                    &format!(
+                        "<rsp:Send><rsp:Stream Name=\"stdin\" CommandId=\"{}\"{}>{}</rsp:Stream></rsp:Send>",
+                        xml_escape(&command_id),
+                        end,
+                        BASE64.encode(chunk)
+                    ),
+                );
+                self.post(send).await?;
+            }
+        }
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let exit_code = loop {
+            let receive = self.envelope(
+                ACTION_RECEIVE,
+                Some(shell_id),
+                &[("WSMAN_CMDSHELL_OPTION_KEEPALIVE", "TRUE")],
+                &format!(
+                    "<rsp:Receive><rsp:DesiredStream CommandId=\"{}\">stdout stderr</rsp:DesiredStream></rsp:Receive>",
+                    xml_escape(&command_id)
+                ),
+            );
+            let response = match self.post_allowing_fault(receive).await? {
+                Ok(response) => response,
+                // Nothing to report yet; ask again
+                Err(fault) if fault.code == FAULT_OPERATION_TIMEOUT => continue,
+                Err(fault) => return Err(self.fault_error(&fault)),
+            };
+
+            for stream in elements(&response, "Stream") {
+                let Ok(bytes) = BASE64.decode(stream.text.trim()) else {
+                    continue;
+                };
+                let chunk = String::from_utf8_lossy(&bytes);
+                let is_stderr = stream.attr("Name") == Some("stderr");
+                on_output(&chunk, is_stderr);
+                if is_stderr {
+                    stderr.push_str(&chunk);
+                } else {
+                    stdout.push_str(&chunk);
+                }
+            }
+
+            let done = elements(&response, "CommandState")
+                .iter()
+                .any(|state| state.attr("State") == Some(STATE_DONE));
+            if done {
+                break element_text(&response, "ExitCode")
+                    .and_then(|code| code.trim().parse::<i32>().ok())
+                    .unwrap_or(-1);
+            }
+        };
+
+        let signal = self.envelope(
+            ACTION_SIGNAL,
+            Some(shell_id),
+            &[],
+            &format!(
+                "<rsp:Signal CommandId=\"{}\"><rsp:Code>{}</rsp:Code></rsp:Signal>",
+                xml_escape(&command_id),
+                SIGNAL_TERMINATE
+            ),
+        );
+        let _ = self.post(signal).await;
+
+        Ok(CommandResult {
+            stdout,
+            stderr: clixml_errors(&stderr),
+            exit_code,
+        })
+    }
+}
+
+#[async_trait]
+impl Connection for WinRmConnection {
+    async fn exec(&self, cmd: &str) -> Result<CommandResult, NexusError> {
+        self.run(&wrap_script(cmd), None, &|_, _| {}).await
+    }
+
+    async fn exec_with_stdin(&self, cmd: &str, stdin: &str) -> Result<CommandResult, NexusError> {
+        self.run(&wrap_script(cmd), Some(stdin), &|_, _| {}).await
+    }
+
+    async fn exec_streaming(
+        &self,
+        cmd: &str,
+        on_stdout: Box<dyn Fn(String) + Send + Sync>,
+        on_stderr: Box<dyn Fn(String) + Send + Sync>,
+    ) -> Result<CommandResult, NexusError> {
+        // Receive hands back arbitrary chunks; stderr arrives as CLIXML and is
+        // only reported once the command is done
+        let partial = parking_lot::Mutex::new(String::new());
+        let on_output = |chunk: &str, is_stderr: bool| {
+            if is_stderr {
+                return;
+            }
+            let mut partial = partial.lock();
+            partial.push_str(chunk);
+            while let Some(end) = partial.find('\n') {
+                let line: String = partial.drain(..=end).collect();
+                on_stdout(line);
+            }
+        };
+        let result = self.run(&wrap_script(cmd), None, &on_output).await?;
+
+        let rest = std::mem::take(&mut *partial.lock());
+        if !rest.is_empty() {
+            on_stdout(rest);
+        }
+        for line in result.stderr.lines() {
+            on_stderr(format!("{}\n", line));
+        }
+        Ok(result)
+    }
+
+    async fn read_file(&self, path: &str) -> Result<String, NexusError> {
+        let script = format!(
+            "[Convert]::ToBase64String([IO.File]::ReadAllBytes({}))",
+            ps_quote(path)
+        );
+        let result = self.exec(&script).await?;
+        let io_error = |message: String| NexusError::Io {
+            message,
+            path: Some(PathBuf::from(path)),
+        };
+        if !result.success() {
+            return Err(io_error(format!(
+                "Failed to read file: {}",
+                result.stderr.trim()
+            )));
+        }
+        let bytes = BASE64
+            .decode(result.stdout.trim())
+            .map_err(|e| io_error(format!("Failed to decode file contents: {}", e)))?;
+        String::from_utf8(bytes).map_err(|_| io_error("File is not valid UTF-8".to_string()))
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<(), NexusError> {
+        let script = format!(
+            "$data = [Console]::In.ReadToEnd()\n\
+             [IO.File]::WriteAllBytes({}, [Convert]::FromBase64String($data.Trim()))",
+            ps_quote(path)
+        );
+        let result = self
+            .exec_with_stdin(&script, &BASE64.encode(content))
+            .await?;
+        if result.success() {
+            Ok(())
+        } else {
+            Err(NexusError::Io {
+                message: format!("Failed to write file: {}", result.stderr.trim()),
+                path: Some(PathBuf::from(path)),
+            })
+        }
+    }
+
+    fn host_name(&self) -> &str {
+        &self.host_name
+    }
+
+    fn is_windows(&self) -> bool {
+        true
+    }
+}
+
+/// Make a script's exit code reflect a failed native command or cmdlet, the way
+/// `sh -c` reports the last command's status
+fn wrap_script(script: &str) -> String {
+    format!(
+        "$ProgressPreference = 'SilentlyContinue'\n\
+         {}\n\
+         $ok = $?\n\
+         if ($LASTEXITCODE) {{ exit $LASTEXITCODE }}\n\
+         if (-not $ok) {{ exit 1 }}",
+        script
+    )
+}
+
+/// Base64 of the script as UTF-16LE, for `powershell -EncodedCommand`
+fn encode_powershell(script: &str) -> String {
+    let utf16: Vec<u8> = script
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    BASE64.encode(utf16)
+}
+
+/// PowerShell writes errors over WinRM as CLIXML; turn that back into plain text
+fn clixml_errors(stderr: &str) -> String {
+    let Some(xml) = stderr.trim_start().strip_prefix("#< CLIXML") else {
+        return stderr.to_string();
+    };
+    elements(xml, "S")
+        .iter()
+        .filter(|s| s.attr("S") == Some("Error"))
+        .map(|s| xml_unescape(s.text).replace("_x000D__x000A_", "\n"))
+        .collect::<String>()
+}
+
+/// A WS-Management SOAP fault
+#[derive(Debug)]
+struct Fault {
+    code: String,
+    message: String,
+}
+
+impl Fault {
+    fn parse(xml: &str) -> Option<Fault> {
+        if elements(xml, "Fault").is_empty() {
+            return None;
+        }
+        let code = elements(xml, "WSManFault")
+            .first()
+            .and_then(|fault| fault.attr("Code").map(str::to_string))
+            .unwrap_or_default();
+        let message = element_text(xml, "Message")
+            .or_else(|| element_text(xml, "Text"))
+            .map(|m| strip_tags(&m))
+            .unwrap_or_default();
+        Some(Fault { code, message })
+    }
+}
+
+/// An XML element found by `elements`
+struct Element<'a> {
+    attrs: &'a str,
+    text: &'a str,
+}
+
+impl<'a> Element<'a> {
+    fn attr(&self, name: &str) -> Option<&'a str> {
+        let needle = format!("{}=\"", name);
+        let mut rest = self.attrs;
+        while let Some(start) = rest.find(&needle) {
+            // Don't match `CommandId` when asked for `Id`
+            let preceded_by_space =
+                start == 0 || rest[..start].ends_with(|c: char| c.is_whitespace());
+            let value = &rest[start + needle.len()..];
+            if preceded_by_space {
+                return value.find('"').map(|end| &value[..end]);
+            }
+            rest = value;
+        }
+        None
+    }
+}
+
+/// Every element with the local name `name` (any namespace prefix) in `xml`. The
+/// responses come from one known producer, so this scan stands in for a parser.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<Element<'a>> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let qualified = &rest[..tag_end];
+        let local = qualified.rsplit(':').next().unwrap_or(qualified);
+        if local != name || qualified.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        let attrs = &rest[tag_end..close];
+        if attrs.ends_with('/') {
+            found.push(Element {
+                attrs: attrs.trim_end_matches('/'),
+                text: "",
+            });
+            rest = &rest[close + 1..];
+            continue;
+        }
+        let body = &rest[close + 1..];
+        let end_tag = format!("</{}>", qualified);
+        let text_end = body.find(&end_tag).unwrap_or(body.len());
+        found.push(Element {
+            attrs,
+            text: &body[..text_end],
+        });
+        rest = body;
+    }
+    found
+}
+
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name)
+        .first()
+        .map(|element| xml_unescape(element.text))
+}
+
+fn strip_tags(s: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// A random UUID (version 4) for the WS-Addressing MessageID
+fn message_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn winrm_host(vars: &[(&str, &str)]) -> Host {
+        let mut host = Host::new("win1").with_address("10.0.0.9");
+        host.user = "Administrator".to_string();
+        for (name, value) in vars {
+            host = host.with_var(*name, Value::String(value.to_string()));
+        }
+        host.with_var("ansible_connection", Value::String("winrm".to_string()))
+    }
+
+    #[test]
+    fn test_endpoint_from_host_vars() {
+        let pool = ConnectionPool::new();
+
+        let conn =
+            WinRmConnection::for_host(&winrm_host(&[("ansible_password", "pw")]), &pool).unwrap();
+        assert_eq!(conn.endpoint(), "https://10.0.0.9:5986/wsman");
+        assert_eq!(conn.auth, WinRmAuth::Ntlm);
+        assert!(conn.is_windows());
+
+        let host = winrm_host(&[
+            ("ansible_password", "pw"),
+            ("ansible_winrm_scheme", "http"),
+            ("ansible_winrm_transport", "basic"),
+        ]);
+        let conn = WinRmConnection::for_host(&host, &pool).unwrap();
+        assert_eq!(conn.endpoint(), "http://10.0.0.9:5985/wsman");
+        assert_eq!(conn.auth, WinRmAuth::Basic);
+
+        // Without ansible_password the pool's --ask-pass password is used
+        assert!(WinRmConnection::for_host(&winrm_host(&[]), &pool).is_err());
+        let pool = ConnectionPool::new().with_password("secret".to_string());
+        assert!(WinRmConnection::for_host(&winrm_host(&[]), &pool).is_ok());
+
+        let host = winrm_host(&[
+            ("ansible_password", "pw"),
+            ("ansible_winrm_transport", "kerberos"),
+        ]);
+        assert!(WinRmConnection::for_host(&host, &pool).is_err());
+    }
+
+    #[test]
+    fn test_parse_receive_response() {
+        let response = r#"<s:Envelope><s:Body><rsp:ReceiveResponse>
+            <rsp:Stream Name="stdout" CommandId="AB-1">aGVsbG8K</rsp:Stream>
+            <rsp:Stream Name="stderr" CommandId="AB-1"></rsp:Stream>
+            <rsp:Stream Name="stdout" CommandId="AB-1" End="true"></rsp:Stream>
+            <rsp:CommandState CommandId="AB-1" State="http://schemas.microsoft.com/wbem/wsman/1/windows/shell/CommandState/Done">
+            <rsp:ExitCode>3</rsp:ExitCode></rsp:CommandState>
+            </rsp:ReceiveResponse></s:Body></s:Envelope>"#;
+
+        let streams = elements(response, "Stream");
+        assert_eq!(streams.len(), 3);
+        assert_eq!(streams[0].attr("Name"), Some("stdout"));
+        assert_eq!(streams[0].attr("Id"), None);
+        assert_eq!(BASE64.decode(streams[0].text).unwrap(), b"hello\n");
+        assert_eq!(
+            elements(response, "CommandState")[0].attr("State"),
+            Some(STATE_DONE)
+        );
+        assert_eq!(element_text(response, "ExitCode").as_deref(), Some("3"));
+
+        let fault = r#"<s:Envelope><s:Body><s:Fault><s:Reason><s:Text>timed out</s:Text></s:Reason>
+            <s:Detail><f:WSManFault xmlns:f="x" Code="2150858793" Machine="win1">
+            <f:Message>The WS-Management service cannot complete the operation &amp; timed out.</f:Message>
+            </f:WSManFault></s:Detail></s:Fault></s:Body></s:Envelope>"#;
+        let fault = Fault::parse(fault).unwrap();
+        assert_eq!(fault.code, FAULT_OPERATION_TIMEOUT);
+        assert!(fault.message.contains("operation & timed out"));
+        assert!(Fault::parse(response).is_none());
+    }
+
+    #[test]
+    fn test_powershell_helpers() {
+        assert_eq!(ps_quote("C:\\it's here"), "'C:\\it''s here'");
+        // "dir" as UTF-16LE
+        assert_eq!(encode_powershell("dir"), "ZABpAHIA");
+
+        let clixml = "#< CLIXML\r\n<Objs Version=\"1.1.0.1\"><S S=\"Error\">oops_x000D__x000A_</S>\
+                      <S S=\"progress\">ignored</S></Objs>";
+        assert_eq!(clixml_errors(clixml), "oops\n");
+        assert_eq!(clixml_errors("plain error"), "plain error");
+
+        let id = message_id();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+    }
+}
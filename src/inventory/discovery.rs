@@ -1,20 +1,32 @@
+use super::mdns::{self, MdnsService};
+use super::scan_state::ScanState;
+use super::snmp::{self, SnmpSystemInfo, SnmpVersion};
 use crate::output::errors::NexusError;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
 use std::net::{IpAddr, Ipv4Addr};
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 
+/// How often a running scan writes its progress to the scan state file
+const SCAN_STATE_SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
 /// Network scanner for discovering hosts on a network
 pub struct NetworkScanner {
     pub timeout: Duration,
     pub concurrent_probes: usize,
     pub fingerprint: bool,
     pub probe_type: ProbeType,
+    /// Where `scan_subnet` records its progress, so an interrupted scan can resume
+    pub scan_state_file: Option<PathBuf>,
 }
 
 /// Represents a discovered host on the network
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveredHost {
     pub address: IpAddr,
     pub hostname: Option<String>,
@@ -23,11 +35,23 @@ pub struct DiscoveredHost {
     pub fingerprint: Option<Fingerprint>,
     pub first_seen: DateTime<Utc>,
     pub last_seen: DateTime<Utc>,
+    #[serde(
+        rename = "response_time_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
     pub response_time: Duration,
+    /// sysDescr/sysName from an SNMP probe
+    pub snmp: Option<SnmpSystemInfo>,
+    /// Hardware address, known when the host came from the ARP cache
+    pub mac: Option<String>,
+    /// Services the host advertises over mDNS
+    #[serde(default)]
+    pub mdns_services: Vec<MdnsService>,
 }
 
 /// Information about an open port on a host
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenPort {
     pub port: u16,
     pub service: Option<String>,
@@ -35,7 +59,7 @@ pub struct OpenPort {
 }
 
 /// OS classification information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsClassification {
     pub os_family: String,            // linux, windows, bsd
     pub distribution: Option<String>, // ubuntu, rhel, debian
@@ -43,7 +67,7 @@ pub struct OsClassification {
 }
 
 /// Fingerprint information gathered from the host
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fingerprint {
     pub ssh_banner: Option<String>,
     pub tcp_timestamps: Option<bool>,
@@ -63,6 +87,11 @@ pub enum ProbeType {
     Ssh,
     Ping,
     TcpPorts(Vec<u16>),
+    /// SNMP GET of sysDescr/sysName, for network gear; SSH is checked as well
+    Snmp {
+        community: String,
+        version: SnmpVersion,
+    },
 }
 
 impl NetworkScanner {
@@ -73,6 +102,7 @@ impl NetworkScanner {
             concurrent_probes: 100,
             fingerprint: true,
             probe_type: ProbeType::Ssh,
+            scan_state_file: None,
         }
     }
 
@@ -82,95 +112,184 @@ impl NetworkScanner {
         self
     }
 
+    /// Record progress in `path` and skip what it says was already probed
+    pub fn with_scan_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.scan_state_file = Some(path.into());
+        self
+    }
+
     /// Get the ports to scan based on probe type
     fn get_probe_ports(&self) -> Vec<u16> {
         match &self.probe_type {
             ProbeType::Ssh => vec![22],
             ProbeType::Ping => vec![22, 80, 443], // TCP ping to common ports
             ProbeType::TcpPorts(ports) => ports.clone(),
+            ProbeType::Snmp { .. } => vec![22],
         }
     }
 
-    /// Scan a subnet using CIDR notation (e.g., "192.168.1.0/24")
+    /// Scan a subnet using CIDR notation (e.g., "192.168.1.0/24").
+    ///
+    /// With a scan state file, addresses it lists as probed are skipped and their
+    /// hosts reused; progress is saved every few seconds while the scan runs.
     pub async fn scan_subnet(&self, cidr: &str) -> Result<Vec<DiscoveredHost>, NexusError> {
         let ips = parse_cidr(cidr)?;
-        let mut discovered = Vec::new();
+
+        let mut state = match &self.scan_state_file {
+            Some(path) => Some(ScanState::load(path)?),
+            None => None,
+        };
+        let progress = state
+            .as_mut()
+            .map(|s| s.subnets.entry(cidr.to_string()).or_default().clone())
+            .unwrap_or_default();
+        if progress.complete {
+            return Ok(progress.hosts);
+        }
+        let mut probed = progress.probed;
+        let mut discovered = progress.hosts;
 
         // Use semaphore to limit concurrent probes
         let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(self.concurrent_probes));
-        let mut tasks = Vec::new();
+        let mut tasks = JoinSet::new();
 
         let ports = self.get_probe_ports();
         let require_ssh = matches!(self.probe_type, ProbeType::Ssh);
+        let snmp = match &self.probe_type {
+            ProbeType::Snmp { community, version } => Some((community.clone(), *version)),
+            _ => None,
+        };
 
-        for ip in ips {
+        for ip in ips.into_iter().filter(|ip| !probed.contains(ip)) {
             let sem_clone = sem.clone();
             let timeout_duration = self.timeout;
             let fingerprint = self.fingerprint;
             let ports_clone = ports.clone();
+            let snmp = snmp.clone();
 
-            tasks.push(tokio::spawn(async move {
+            tasks.spawn(async move {
                 let _permit = sem_clone.acquire().await.unwrap();
-                let host =
-                    Self::probe_host_internal(ip, &ports_clone, timeout_duration, fingerprint)
-                        .await;
+                let host = Self::probe_host_internal(
+                    ip,
+                    &ports_clone,
+                    snmp.as_ref().map(|(c, v)| (c.as_str(), *v)),
+                    timeout_duration,
+                    fingerprint,
+                )
+                .await;
 
                 // For SSH probe type, only return hosts with port 22 open
-                if require_ssh {
+                let host = if require_ssh {
                     host.filter(|h| h.open_ports.iter().any(|p| p.port == 22))
                 } else {
                     host
-                }
-            }));
+                };
+                (ip, host)
+            });
         }
 
-        // Collect results
-        for task in tasks {
-            if let Ok(Some(host)) = task.await {
-                discovered.push(host);
+        // Collect results as probes finish, saving progress along the way
+        let mut last_save = Instant::now();
+        while let Some(result) = tasks.join_next().await {
+            if let Ok((ip, host)) = result {
+                probed.insert(ip);
+                discovered.extend(host);
+            }
+            if last_save.elapsed() >= SCAN_STATE_SAVE_INTERVAL {
+                self.save_progress(&mut state, cidr, &probed, &discovered, false);
+                last_save = Instant::now();
             }
         }
+        discovered.sort_by_key(|host| host.address);
+        self.save_progress(&mut state, cidr, &probed, &discovered, true);
 
         Ok(discovered)
     }
 
+    /// Store a subnet's progress in the scan state file. Failing to save only costs
+    /// the ability to resume, so it's a warning rather than a reason to stop.
+    fn save_progress(
+        &self,
+        state: &mut Option<ScanState>,
+        cidr: &str,
+        probed: &HashSet<IpAddr>,
+        hosts: &[DiscoveredHost],
+        complete: bool,
+    ) {
+        let (Some(state), Some(path)) = (state.as_mut(), &self.scan_state_file) else {
+            return;
+        };
+        let progress = state.subnets.entry(cidr.to_string()).or_default();
+        progress.probed.clone_from(probed);
+        progress.hosts = hosts.to_vec();
+        progress.complete = complete;
+        if let Err(e) = state.save(path) {
+            eprintln!("Warning: Failed to save scan state: {}", e);
+        }
+    }
+
     /// Probe a specific host on given ports
     pub async fn probe_host(&self, addr: IpAddr, ports: &[u16]) -> Option<DiscoveredHost> {
-        Self::probe_host_internal(addr, ports, self.timeout, self.fingerprint).await
+        Self::probe_host_internal(addr, ports, None, self.timeout, self.fingerprint).await
     }
 
-    /// Internal probe implementation
+    /// Internal probe implementation. With `snmp` (community and version), a host
+    /// answering SNMP is reported even when none of the ports are open.
     async fn probe_host_internal(
         addr: IpAddr,
         ports: &[u16],
+        snmp: Option<(&str, SnmpVersion)>,
         timeout_duration: Duration,
         do_fingerprint: bool,
     ) -> Option<DiscoveredHost> {
         let start = std::time::Instant::now();
-        let mut open_ports = Vec::new();
-
-        // Probe each port
-        for &port in ports {
-            if let Ok(Ok(_stream)) =
-                timeout(timeout_duration, TcpStream::connect((addr, port))).await
-            {
-                let mut open_port = OpenPort {
-                    port,
-                    service: identify_service(port),
-                    banner: None,
-                };
 
-                // Try to grab banner if fingerprinting is enabled
-                if do_fingerprint && port == 22 {
-                    open_port.banner = Self::grab_ssh_banner(addr, port, timeout_duration).await;
+        // The SNMP query runs alongside the port probes rather than adding its timeout
+        let snmp_query = async {
+            match snmp {
+                Some((community, version)) => {
+                    snmp::query_system(addr, community, version, timeout_duration).await
                 }
+                None => None,
+            }
+        };
+        let port_probes = async {
+            let mut open_ports = Vec::new();
+
+            // Probe each port
+            for &port in ports {
+                if let Ok(Ok(_stream)) =
+                    timeout(timeout_duration, TcpStream::connect((addr, port))).await
+                {
+                    let mut open_port = OpenPort {
+                        port,
+                        service: identify_service(port),
+                        banner: None,
+                    };
+
+                    // Try to grab banner if fingerprinting is enabled
+                    if do_fingerprint && port == 22 {
+                        open_port.banner =
+                            Self::grab_ssh_banner(addr, port, timeout_duration).await;
+                    }
 
-                open_ports.push(open_port);
+                    open_ports.push(open_port);
+                }
             }
-        }
+            open_ports
+        };
+        // The TTL of an echo reply hints at the OS; it needs ping, as TCP hides it
+        let ttl_probe = async {
+            if do_fingerprint {
+                Self::probe_ttl(addr, timeout_duration).await
+            } else {
+                None
+            }
+        };
+        let (snmp_info, open_ports, ttl) = tokio::join!(snmp_query, port_probes, ttl_probe);
 
-        // Only return if we found at least one open port
-        if open_ports.is_empty() {
+        // Only return if we found at least one open port or an SNMP agent
+        if open_ports.is_empty() && snmp_info.is_none() {
             return None;
         }
 
@@ -178,26 +297,36 @@ impl NetworkScanner {
         let now = Utc::now();
 
         let fingerprint = if do_fingerprint {
-            Some(Self::fingerprint_host(&open_ports))
+            Some(Self::fingerprint_host(&open_ports, ttl))
         } else {
             None
         };
 
-        let os_classification = if do_fingerprint {
-            Some(Self::classify_os(&open_ports, fingerprint.as_ref()))
-        } else {
-            None
+        // sysDescr names the OS outright, so it's used even without --fingerprint
+        let os_classification = match snmp_info.as_ref().and_then(Self::classify_snmp) {
+            Some(os) => Some(os),
+            None if do_fingerprint => Some(Self::classify_os(&open_ports, fingerprint.as_ref())),
+            None => None,
+        };
+
+        // Network gear rarely has reverse DNS, but it knows its own name
+        let hostname = match Self::resolve_hostname(addr).await {
+            Some(name) => Some(name),
+            None => snmp_info.as_ref().and_then(|info| info.sys_name.clone()),
         };
 
         Some(DiscoveredHost {
             address: addr,
-            hostname: Self::resolve_hostname(addr).await,
+            hostname,
             open_ports,
             os_classification,
             fingerprint,
             first_seen: now,
             last_seen: now,
             response_time,
+            snmp: snmp_info,
+            mac: None,
+            mdns_services: Vec::new(),
         })
     }
 
@@ -224,8 +353,33 @@ impl NetworkScanner {
         }
     }
 
+    /// Send one ICMP echo request with the system ping and read the reply's TTL
+    async fn probe_ttl(addr: IpAddr, timeout_duration: Duration) -> Option<u8> {
+        let wait_secs = timeout_duration.as_secs().max(1).to_string();
+        let wait_millis = timeout_duration.as_millis().max(1).to_string();
+        let addr = addr.to_string();
+        let args: Vec<&str> = if cfg!(windows) {
+            vec!["-n", "1", "-w", &wait_millis, &addr]
+        } else if cfg!(target_os = "macos") {
+            vec!["-c", "1", "-t", &wait_secs, &addr]
+        } else {
+            vec!["-c", "1", "-W", &wait_secs, &addr]
+        };
+
+        let output = tokio::process::Command::new("ping")
+            .args(&args)
+            .kill_on_drop(true)
+            .output();
+        // Some pings ignore their own deadline for unreachable hosts
+        let output = timeout(timeout_duration + Duration::from_secs(1), output)
+            .await
+            .ok()?
+            .ok()?;
+        parse_ping_ttl(&String::from_utf8_lossy(&output.stdout))
+    }
+
     /// Create a fingerprint from discovered information
-    fn fingerprint_host(open_ports: &[OpenPort]) -> Fingerprint {
+    fn fingerprint_host(open_ports: &[OpenPort], ttl: Option<u8>) -> Fingerprint {
         let ssh_banner = open_ports
             .iter()
             .find(|p| p.port == 22)
@@ -234,41 +388,38 @@ impl NetworkScanner {
         Fingerprint {
             ssh_banner,
             tcp_timestamps: None, // Would require raw socket access
-            ttl: None,            // Would require raw socket access
+            ttl,
         }
     }
 
-    /// Classify the OS based on fingerprint information
+    /// Classify the OS based on fingerprint information. The SSH banner names the
+    /// distribution; a TTL that agrees with it raises the confidence, one that
+    /// doesn't lowers it, and a TTL alone gives a rough guess at the family.
     fn classify_os(open_ports: &[OpenPort], fingerprint: Option<&Fingerprint>) -> OsClassification {
         let mut os_family = "unknown".to_string();
         let mut distribution = None;
         let mut confidence: f32 = 0.0;
 
-        // Check SSH banner for OS hints
-        if let Some(fp) = fingerprint {
-            if let Some(banner) = &fp.ssh_banner {
-                let banner_lower = banner.to_lowercase();
-
-                if banner_lower.contains("ubuntu") {
-                    os_family = "linux".to_string();
-                    distribution = Some("ubuntu".to_string());
-                    confidence = 0.9;
-                } else if banner_lower.contains("debian") {
-                    os_family = "linux".to_string();
-                    distribution = Some("debian".to_string());
-                    confidence = 0.85;
-                } else if banner_lower.contains("rhel") || banner_lower.contains("centos") {
-                    os_family = "linux".to_string();
-                    distribution = Some("rhel".to_string());
-                    confidence = 0.85;
-                } else if banner_lower.contains("openssh") {
-                    os_family = "linux".to_string();
-                    confidence = 0.6;
-                } else if banner_lower.contains("windows") {
-                    os_family = "windows".to_string();
-                    confidence = 0.8;
-                }
+        let banner_guess = fingerprint
+            .and_then(|fp| fp.ssh_banner.as_deref())
+            .and_then(classify_ssh_banner);
+        let ttl_family = fingerprint.and_then(|fp| fp.ttl).map(ttl_os_family);
+
+        match (banner_guess, ttl_family) {
+            (Some((family, dist, banner_confidence)), ttl) => {
+                confidence = match ttl {
+                    Some(ttl) if ttl.matches(family) => (banner_confidence + 0.08).min(0.99),
+                    Some(_) => banner_confidence - 0.15,
+                    None => banner_confidence,
+                };
+                os_family = family.to_string();
+                distribution = dist.map(str::to_string);
+            }
+            (None, Some(ttl)) => {
+                os_family = ttl.family().to_string();
+                confidence = 0.5;
             }
+            (None, None) => {}
         }
 
         // Check for Windows-specific ports
@@ -284,6 +435,46 @@ impl NetworkScanner {
         }
     }
 
+    /// Classify the OS from an SNMP sysDescr, such as "Cisco IOS Software, ..." or
+    /// "Linux web1 5.15.0-91-generic ..."
+    fn classify_snmp(info: &SnmpSystemInfo) -> Option<OsClassification> {
+        let descr = info.sys_descr.as_deref()?.to_lowercase();
+
+        // Network operating systems first; some of them mention Linux too
+        let known: &[(&str, &str, Option<&str>)] = &[
+            ("cisco ios xr", "network", Some("iosxr")),
+            ("cisco nx-os", "network", Some("nxos")),
+            ("cisco adaptive security", "network", Some("asa")),
+            ("cisco", "network", Some("ios")),
+            ("junos", "network", Some("junos")),
+            ("juniper", "network", Some("junos")),
+            ("arista", "network", Some("eos")),
+            ("routeros", "network", Some("routeros")),
+            ("fortigate", "network", Some("fortios")),
+            ("pan-os", "network", Some("panos")),
+            ("procurve", "network", Some("procurve")),
+            ("windows", "windows", None),
+            ("freebsd", "bsd", Some("freebsd")),
+            ("openbsd", "bsd", Some("openbsd")),
+            ("netbsd", "bsd", Some("netbsd")),
+            ("ubuntu", "linux", Some("ubuntu")),
+            ("debian", "linux", Some("debian")),
+            ("el7", "linux", Some("rhel")),
+            ("el8", "linux", Some("rhel")),
+            ("el9", "linux", Some("rhel")),
+            ("linux", "linux", None),
+        ];
+
+        known
+            .iter()
+            .find(|(needle, _, _)| descr.contains(needle))
+            .map(|(_, family, distribution)| OsClassification {
+                os_family: family.to_string(),
+                distribution: distribution.map(str::to_string),
+                confidence: if distribution.is_some() { 0.95 } else { 0.9 },
+            })
+    }
+
     /// Attempt to resolve hostname from IP
     async fn resolve_hostname(addr: IpAddr) -> Option<String> {
         // Perform reverse DNS lookup in a blocking task
@@ -316,6 +507,31 @@ impl Default for NetworkScanner {
 
 /// Parse CIDR notation into a list of IP addresses
 fn parse_cidr(cidr: &str) -> Result<Vec<IpAddr>, NexusError> {
+    let (network, prefix_len) = parse_subnet(cidr)?;
+
+    // Calculate number of hosts
+    let num_hosts = 2u32.pow((32 - prefix_len) as u32);
+
+    // Limit to reasonable subnet sizes
+    if num_hosts > 65536 {
+        return Err(NexusError::Inventory {
+            message: format!("Subnet too large: {} hosts", num_hosts),
+            suggestion: Some("Use a prefix length of /16 or higher".to_string()),
+        });
+    }
+
+    let mut ips = Vec::new();
+    for i in 1..num_hosts - 1 {
+        // Skip network and broadcast addresses
+        let ip_u32 = network + i;
+        ips.push(IpAddr::V4(Ipv4Addr::from(ip_u32)));
+    }
+
+    Ok(ips)
+}
+
+/// Parse CIDR notation into the network address and prefix length
+fn parse_subnet(cidr: &str) -> Result<(u32, u8), NexusError> {
     let parts: Vec<&str> = cidr.split('/').collect();
 
     if parts.len() != 2 {
@@ -342,29 +558,259 @@ fn parse_cidr(cidr: &str) -> Result<Vec<IpAddr>, NexusError> {
         });
     }
 
-    // Calculate number of hosts
-    let num_hosts = 2u32.pow((32 - prefix_len) as u32);
+    Ok((u32::from(base_ip) & subnet_mask(prefix_len), prefix_len))
+}
 
-    // Limit to reasonable subnet sizes
-    if num_hosts > 65536 {
-        return Err(NexusError::Inventory {
-            message: format!("Subnet too large: {} hosts", num_hosts),
-            suggestion: Some("Use a prefix length of /16 or higher".to_string()),
-        });
+fn subnet_mask(prefix_len: u8) -> u32 {
+    // A shift by 32 would overflow
+    match prefix_len {
+        0 => 0,
+        len => !0u32 << (32 - len),
     }
+}
 
-    let base_u32 = u32::from(base_ip);
-    let mask = !0u32 << (32 - prefix_len);
-    let network = base_u32 & mask;
+/// OS family suggested by a reply's TTL, from the initial TTL it counted down from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TtlFamily {
+    /// 64: Linux, the BSDs and macOS
+    Unix,
+    /// 128
+    Windows,
+    /// 255: routers and switches (and Solaris)
+    Network,
+}
 
-    let mut ips = Vec::new();
-    for i in 1..num_hosts - 1 {
-        // Skip network and broadcast addresses
-        let ip_u32 = network + i;
-        ips.push(IpAddr::V4(Ipv4Addr::from(ip_u32)));
+impl TtlFamily {
+    fn family(self) -> &'static str {
+        match self {
+            TtlFamily::Unix => "linux",
+            TtlFamily::Windows => "windows",
+            TtlFamily::Network => "network",
+        }
     }
 
-    Ok(ips)
+    fn matches(self, os_family: &str) -> bool {
+        match self {
+            TtlFamily::Unix => matches!(os_family, "linux" | "bsd" | "macos"),
+            TtlFamily::Windows => os_family == "windows",
+            TtlFamily::Network => os_family == "network",
+        }
+    }
+}
+
+/// Hosts start at 64, 128 or 255 and each hop takes one off, so the initial TTL is
+/// the smallest of those at or above the one observed
+fn ttl_os_family(ttl: u8) -> TtlFamily {
+    match ttl {
+        0..=64 => TtlFamily::Unix,
+        65..=128 => TtlFamily::Windows,
+        _ => TtlFamily::Network,
+    }
+}
+
+/// Read the TTL from ping output: `ttl=63` on Unix, `TTL=127` on Windows
+fn parse_ping_ttl(output: &str) -> Option<u8> {
+    let lower = output.to_lowercase();
+    let rest = &lower[lower.find("ttl=")? + 4..];
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Guess (family, distribution, confidence) from an SSH identification string such
+/// as `SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6`
+fn classify_ssh_banner(banner: &str) -> Option<(&'static str, Option<&'static str>, f32)> {
+    let banner = banner.to_lowercase();
+
+    // Most specific first: every OpenSSH banner mentions openssh
+    let known: &[(&str, &str, Option<&str>, f32)] = &[
+        ("ubuntu", "linux", Some("ubuntu"), 0.9),
+        ("debian", "linux", Some("debian"), 0.85),
+        ("raspbian", "linux", Some("debian"), 0.85),
+        ("rhel", "linux", Some("rhel"), 0.85),
+        ("centos", "linux", Some("rhel"), 0.85),
+        ("freebsd", "bsd", Some("freebsd"), 0.85),
+        ("for_windows", "windows", None, 0.8),
+        ("windows", "windows", None, 0.8),
+        ("cisco", "network", Some("ios"), 0.85),
+        ("rosssh", "network", Some("routeros"), 0.85),
+        ("dropbear", "linux", None, 0.5),
+        ("openssh", "linux", None, 0.6),
+    ];
+
+    known
+        .iter()
+        .find(|(needle, _, _, _)| banner.contains(needle))
+        .map(|&(_, family, distribution, confidence)| (family, distribution, confidence))
+}
+
+/// Browse the local link over mDNS/DNS-SD, waiting `wait` for answers to each round
+/// of queries. Hosts are named after the target of their service records.
+pub async fn discover_mdns(wait: Duration) -> Result<Vec<DiscoveredHost>, NexusError> {
+    let now = Utc::now();
+    Ok(mdns::browse(wait)
+        .await?
+        .into_iter()
+        .map(|host| DiscoveredHost {
+            address: host.address,
+            hostname: host.hostname,
+            open_ports: Vec::new(),
+            os_classification: None,
+            fingerprint: None,
+            first_seen: now,
+            last_seen: now,
+            response_time: Duration::ZERO,
+            snmp: None,
+            mac: None,
+            mdns_services: host.services,
+        })
+        .collect())
+}
+
+/// Combine hosts found more than once, e.g. by a scan and over mDNS, keeping the
+/// order in which addresses first appear
+pub fn merge_discovered(hosts: Vec<DiscoveredHost>) -> Vec<DiscoveredHost> {
+    let mut merged: Vec<DiscoveredHost> = Vec::new();
+    for host in hosts {
+        let Some(existing) = merged.iter_mut().find(|h| h.address == host.address) else {
+            merged.push(host);
+            continue;
+        };
+        existing.hostname = existing.hostname.take().or(host.hostname);
+        existing.mac = existing.mac.take().or(host.mac);
+        existing.snmp = existing.snmp.take().or(host.snmp);
+        existing.os_classification = existing.os_classification.take().or(host.os_classification);
+        existing.fingerprint = existing.fingerprint.take().or(host.fingerprint);
+        for port in host.open_ports {
+            if !existing.open_ports.iter().any(|p| p.port == port.port) {
+                existing.open_ports.push(port);
+            }
+        }
+        existing.mdns_services.extend(host.mdns_services);
+    }
+    merged
+}
+
+/// List the hosts in the local ARP cache without sending anything on the network.
+/// With `cidr`, only addresses inside that subnet are reported.
+pub fn discover_from_arp_cache(cidr: Option<&str>) -> Result<Vec<DiscoveredHost>, NexusError> {
+    // Nothing is probed, so any subnet size is fine
+    let subnet = cidr.map(parse_subnet).transpose()?;
+    let in_subnet = |addr: &IpAddr| match (subnet, addr) {
+        (None, _) => true,
+        (Some((network, prefix_len)), IpAddr::V4(v4)) => {
+            u32::from(*v4) & subnet_mask(prefix_len) == network
+        }
+        (Some(_), IpAddr::V6(_)) => false,
+    };
+
+    let now = Utc::now();
+    Ok(read_arp_cache()?
+        .into_iter()
+        .filter(|(addr, _)| in_subnet(addr))
+        .map(|(address, mac)| DiscoveredHost {
+            address,
+            hostname: None,
+            open_ports: Vec::new(),
+            os_classification: None,
+            fingerprint: None,
+            first_seen: now,
+            last_seen: now,
+            response_time: Duration::ZERO,
+            snmp: None,
+            mac: Some(mac),
+            mdns_services: Vec::new(),
+        })
+        .collect())
+}
+
+/// Read (address, MAC) pairs from /proc/net/arp, or from `arp -a` where that
+/// doesn't exist
+fn read_arp_cache() -> Result<Vec<(IpAddr, String)>, NexusError> {
+    if let Ok(content) = std::fs::read_to_string("/proc/net/arp") {
+        return Ok(parse_proc_net_arp(&content));
+    }
+
+    // -n skips reverse lookups, which would send DNS queries; Windows doesn't resolve
+    let args: &[&str] = if cfg!(windows) { &["-a"] } else { &["-an"] };
+    let output = std::process::Command::new("arp")
+        .args(args)
+        .output()
+        .map_err(|e| NexusError::Inventory {
+            message: format!("Failed to read the ARP cache: {}", e),
+            suggestion: Some(
+                "Passive discovery needs /proc/net/arp or the arp command".to_string(),
+            ),
+        })?;
+    Ok(parse_arp_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse /proc/net/arp, skipping incomplete entries (flags 0x0)
+fn parse_proc_net_arp(content: &str) -> Vec<(IpAddr, String)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (addr, flags, mac) = (fields.first()?, fields.get(2)?, fields.get(3)?);
+            if *flags == "0x0" {
+                return None;
+            }
+            Some((addr.parse().ok()?, normalize_mac(mac)?))
+        })
+        .collect()
+}
+
+/// Parse `arp -a` output in the BSD/macOS form (`? (10.0.0.1) at 0:1a:2b:3c:4d:5e on en0`)
+/// or the Windows one (`  10.0.0.1   00-1a-2b-3c-4d-5e   dynamic`)
+fn parse_arp_output(output: &str) -> Vec<(IpAddr, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut addr = None;
+            let mut mac = None;
+            for token in line.split_whitespace() {
+                let token = token.trim_start_matches('(').trim_end_matches(')');
+                if addr.is_none() {
+                    addr = token.parse::<IpAddr>().ok();
+                } else if mac.is_none() {
+                    mac = normalize_mac(token);
+                }
+            }
+            Some((addr?, mac?))
+        })
+        .collect()
+}
+
+/// Lowercase, colon-separated and zero-padded; `None` for anything that isn't a
+/// MAC address or is all zeros
+fn normalize_mac(mac: &str) -> Option<String> {
+    let octets: Vec<u8> = mac
+        .split([':', '-'])
+        .map(|octet| {
+            if octet.is_empty() || octet.len() > 2 {
+                return None;
+            }
+            u8::from_str_radix(octet, 16).ok()
+        })
+        .collect::<Option<_>>()?;
+    if octets.len() != 6 || octets.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(
+        octets
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
 }
 
 /// Identify common services by port number
@@ -404,4 +850,162 @@ mod tests {
         assert_eq!(identify_service(80), Some("http".to_string()));
         assert_eq!(identify_service(9999), None);
     }
+
+    #[test]
+    fn test_classify_snmp() {
+        let info = |descr: &str| SnmpSystemInfo {
+            sys_descr: Some(descr.to_string()),
+            sys_name: None,
+        };
+
+        let os = NetworkScanner::classify_snmp(&info(
+            "Cisco IOS Software, C2960 Software (C2960-LANBASEK9-M), Version 15.0(2)SE",
+        ))
+        .unwrap();
+        assert_eq!(os.os_family, "network");
+        assert_eq!(os.distribution.as_deref(), Some("ios"));
+
+        let os = NetworkScanner::classify_snmp(&info(
+            "Linux web1 5.15.0-91-generic #101-Ubuntu SMP x86_64",
+        ))
+        .unwrap();
+        assert_eq!(os.os_family, "linux");
+        assert_eq!(os.distribution.as_deref(), Some("ubuntu"));
+
+        assert!(NetworkScanner::classify_snmp(&info("Printer firmware 2.1")).is_none());
+        assert!(NetworkScanner::classify_snmp(&SnmpSystemInfo::default()).is_none());
+    }
+
+    #[test]
+    fn test_parse_arp_cache() {
+        let proc = "IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         AA:BB:CC:DD:EE:01     *        eth0
+192.168.1.7      0x1         0x0         00:00:00:00:00:00     *        eth0
+10.0.0.5         0x1         0x2         aa:bb:cc:dd:ee:05     *        eth1
+";
+        let entries = parse_proc_net_arp(proc);
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "192.168.1.1".parse().unwrap(),
+                    "aa:bb:cc:dd:ee:01".to_string()
+                ),
+                ("10.0.0.5".parse().unwrap(), "aa:bb:cc:dd:ee:05".to_string()),
+            ]
+        );
+
+        let bsd = "? (192.168.1.1) at 0:1a:2b:3c:4d:5e on en0 ifscope [ethernet]
+? (192.168.1.9) at (incomplete) on en0 ifscope [ethernet]
+";
+        assert_eq!(
+            parse_arp_output(bsd),
+            vec![(
+                "192.168.1.1".parse().unwrap(),
+                "00:1a:2b:3c:4d:5e".to_string()
+            )]
+        );
+
+        let windows = "Interface: 192.168.1.20 --- 0xb
+  Internet Address      Physical Address      Type
+  192.168.1.1           00-1a-2b-3c-4d-5e     dynamic
+  224.0.0.22            01-00-5e-00-00-16     static
+";
+        let entries = parse_arp_output(windows);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1, "00:1a:2b:3c:4d:5e");
+    }
+
+    #[test]
+    fn test_classify_os_combines_banner_and_ttl() {
+        let fingerprint = |banner: Option<&str>, ttl: Option<u8>| Fingerprint {
+            ssh_banner: banner.map(str::to_string),
+            tcp_timestamps: None,
+            ttl,
+        };
+        let classify = |fp: Fingerprint| NetworkScanner::classify_os(&[], Some(&fp));
+
+        let ubuntu = "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6";
+        let banner_only = classify(fingerprint(Some(ubuntu), None));
+        assert_eq!(banner_only.os_family, "linux");
+        assert_eq!(banner_only.distribution.as_deref(), Some("ubuntu"));
+
+        // One hop away from a Linux host agrees with the banner
+        let agreeing = classify(fingerprint(Some(ubuntu), Some(63)));
+        assert!(agreeing.confidence > banner_only.confidence);
+        let disagreeing = classify(fingerprint(Some(ubuntu), Some(127)));
+        assert!(disagreeing.confidence < banner_only.confidence);
+        assert_eq!(disagreeing.os_family, "linux");
+
+        let windows = classify(fingerprint(
+            Some("SSH-2.0-OpenSSH_for_Windows_8.1"),
+            Some(128),
+        ));
+        assert_eq!(windows.os_family, "windows");
+        assert!(windows.confidence > 0.8);
+
+        let switch = classify(fingerprint(None, Some(254)));
+        assert_eq!(switch.os_family, "network");
+        assert_eq!(switch.confidence, 0.5);
+
+        assert_eq!(classify(fingerprint(None, None)).os_family, "unknown");
+    }
+
+    #[test]
+    fn test_parse_ping_ttl() {
+        assert_eq!(
+            parse_ping_ttl("64 bytes from 10.0.0.1: icmp_seq=1 ttl=63 time=0.4 ms"),
+            Some(63)
+        );
+        assert_eq!(
+            parse_ping_ttl("Reply from 10.0.0.1: bytes=32 time<1ms TTL=128"),
+            Some(128)
+        );
+        assert_eq!(parse_ping_ttl("1 packets transmitted, 0 received"), None);
+    }
+
+    #[tokio::test]
+    async fn test_scan_subnet_resumes_from_state() {
+        use crate::inventory::scan_state::SubnetProgress;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("scan.json");
+        let now = Utc::now();
+        let found = DiscoveredHost {
+            address: "10.9.9.1".parse().unwrap(),
+            hostname: Some("earlier".to_string()),
+            open_ports: vec![],
+            os_classification: None,
+            fingerprint: None,
+            first_seen: now,
+            last_seen: now,
+            response_time: Duration::from_millis(3),
+            snmp: None,
+            mac: None,
+            mdns_services: Vec::new(),
+        };
+
+        // Both addresses of the /30 were probed before the interruption
+        let mut state = ScanState::default();
+        state.subnets.insert(
+            "10.9.9.0/30".to_string(),
+            SubnetProgress {
+                probed: ["10.9.9.1", "10.9.9.2"]
+                    .iter()
+                    .map(|ip| ip.parse().unwrap())
+                    .collect(),
+                hosts: vec![found],
+                complete: false,
+            },
+        );
+        state.save(&state_file).unwrap();
+
+        let scanner = NetworkScanner::new().with_scan_state_file(&state_file);
+        let hosts = scanner.scan_subnet("10.9.9.0/30").await.unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].hostname.as_deref(), Some("earlier"));
+
+        let saved = ScanState::load(&state_file).unwrap();
+        assert!(saved.subnets["10.9.9.0/30"].complete);
+    }
 }
@@ -1,4 +1,5 @@
 use super::discovery::NetworkScanner;
+use super::scan_state::default_discovery_dir;
 use crate::output::errors::NexusError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -74,7 +75,9 @@ impl DiscoveryDaemon {
             watch_subnets: subnets,
             interval,
             notifiers: vec![Notifier::Stdout],
-            state_file: PathBuf::from("/tmp/nexus_discovery_state.json"),
+            // Not under /tmp, which is cleared on reboot: every host would then be
+            // reported as new on the first scan after a restart
+            state_file: default_discovery_dir().join("daemon-state.json"),
             scanner: NetworkScanner::new(),
             state: DiscoveryState {
                 hosts: HashMap::new(),
@@ -202,9 +205,12 @@ impl DiscoveryDaemon {
         // Compare with previous state to generate events
         let events = self.compare_state(&new_hosts);
 
-        // Update state
-        self.state.hosts.clear();
-        for host in new_hosts {
+        // Update state, remembering when hosts already known were first seen
+        let previous = std::mem::take(&mut self.state.hosts);
+        for mut host in new_hosts {
+            if let Some(old) = previous.get(&host.address) {
+                host.first_seen = old.first_seen;
+            }
             self.state.hosts.insert(host.address, host);
         }
         self.state.last_scan = Some(Utc::now());
@@ -386,6 +392,7 @@ impl DiscoveryDaemon {
                     function: Some("webhook".to_string()),
                     message: format!("Failed to send webhook: {}", e),
                     suggestion: Some("Check webhook URL and network connectivity".to_string()),
+                    location: None,
                 })?;
 
         if !response.status().is_success() {
@@ -393,6 +400,7 @@ impl DiscoveryDaemon {
                 function: Some("webhook".to_string()),
                 message: format!("Webhook returned status {}", response.status()),
                 suggestion: None,
+                location: None,
             });
         }
 
@@ -533,4 +541,28 @@ mod tests {
         assert!(json.contains("host_discovered"));
         assert!(json.contains("192.168.1.10"));
     }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let host = HostState {
+            address: "192.168.1.10".parse().unwrap(),
+            hostname: None,
+            open_ports: vec![22],
+            first_seen: Utc::now(),
+            last_seen: Utc::now(),
+        };
+
+        let mut daemon = DiscoveryDaemon::new(vec![], Duration::from_secs(60))
+            .with_state_file(state_file.clone());
+        daemon.state.hosts.insert(host.address, host.clone());
+        daemon.save_state().unwrap();
+
+        // A restarted daemon knows the host, so it isn't reported as new
+        let mut restarted =
+            DiscoveryDaemon::new(vec![], Duration::from_secs(60)).with_state_file(state_file);
+        restarted.load_state().unwrap();
+        assert!(restarted.compare_state(&[host]).is_empty());
+    }
 }
@@ -0,0 +1,333 @@
+// Export of discovered hosts for `nexus discover --save-to`
+
+use super::discovery::DiscoveredHost;
+use super::{Host, HostGroup, Inventory};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// File formats discovered hosts can be saved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// Ansible-style YAML inventory
+    #[default]
+    Ansible,
+    /// The discovered hosts as a JSON array, with everything the scan found
+    Json,
+    /// Nexus inventory with top-level `hosts` and `groups`
+    Nexus,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ansible" | "yaml" => Ok(ExportFormat::Ansible),
+            "json" => Ok(ExportFormat::Json),
+            "nexus" => Ok(ExportFormat::Nexus),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Render discovered hosts in `format`
+pub fn export_discovered(
+    hosts: &[DiscoveredHost],
+    format: ExportFormat,
+) -> Result<String, NexusError> {
+    match format {
+        ExportFormat::Ansible => ansible_yaml(&discovered_to_inventory(hosts)),
+        ExportFormat::Json => serde_json::to_string_pretty(hosts)
+            .map(|json| json + "\n")
+            .map_err(serialize_error),
+        ExportFormat::Nexus => nexus_yaml(&discovered_to_inventory(hosts)),
+    }
+}
+
+/// Write discovered hosts to `path` in `format`
+pub fn save_discovered(
+    hosts: &[DiscoveredHost],
+    path: &Path,
+    format: ExportFormat,
+) -> Result<(), NexusError> {
+    let content = export_discovered(hosts, format)?;
+    std::fs::write(path, content).map_err(|e| NexusError::Io {
+        message: format!("Failed to write inventory file: {}", e),
+        path: Some(path.to_path_buf()),
+    })
+}
+
+/// Convert discovered hosts to inventory, all in a `discovered` group
+pub fn discovered_to_inventory(hosts: &[DiscoveredHost]) -> Inventory {
+    let mut inventory = Inventory::new();
+
+    for discovered in hosts {
+        let name = discovered
+            .hostname
+            .clone()
+            .unwrap_or_else(|| discovered.address.to_string());
+
+        let mut host = Host::new(name.clone()).with_address(discovered.address.to_string());
+
+        // Set SSH port if available
+        if let Some(ssh_port) = discovered.open_ports.iter().find(|p| p.port == 22) {
+            host.port = ssh_port.port;
+        }
+
+        // Add discovered metadata as variables
+        if let Some(ref os) = discovered.os_classification {
+            host.vars.insert(
+                "discovered_os_family".to_string(),
+                Value::String(os.os_family.clone()),
+            );
+            if let Some(ref dist) = os.distribution {
+                host.vars.insert(
+                    "discovered_os_dist".to_string(),
+                    Value::String(dist.clone()),
+                );
+            }
+            host.vars.insert(
+                "discovered_os_confidence".to_string(),
+                Value::String(format!("{:.2}", os.confidence)),
+            );
+        }
+
+        if !discovered.open_ports.is_empty() {
+            let ports_str = discovered
+                .open_ports
+                .iter()
+                .map(|p| p.port.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            host.vars.insert(
+                "discovered_open_ports".to_string(),
+                Value::String(ports_str),
+            );
+        }
+
+        if let Some(ref mac) = discovered.mac {
+            host.vars
+                .insert("discovered_mac".to_string(), Value::String(mac.clone()));
+        }
+
+        if !discovered.mdns_services.is_empty() {
+            let services = discovered
+                .mdns_services
+                .iter()
+                .map(|s| s.service_type.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+            host.vars.insert(
+                "discovered_mdns_services".to_string(),
+                Value::String(services),
+            );
+        }
+
+        if let Some(ref fp) = discovered.fingerprint {
+            if let Some(ref banner) = fp.ssh_banner {
+                host.vars.insert(
+                    "discovered_ssh_banner".to_string(),
+                    Value::String(banner.clone()),
+                );
+            }
+            if let Some(ttl) = fp.ttl {
+                host.vars
+                    .insert("discovered_ttl".to_string(), Value::Int(ttl.into()));
+            }
+        }
+
+        host.groups.push("discovered".to_string());
+
+        inventory.add_host(host);
+    }
+
+    // Create "discovered" group
+    let discovered_group = HostGroup {
+        name: "discovered".to_string(),
+        hosts: hosts
+            .iter()
+            .map(|h| h.hostname.clone().unwrap_or_else(|| h.address.to_string()))
+            .collect(),
+        children: Vec::new(),
+        vars: HashMap::new(),
+    };
+    inventory.add_group(discovered_group);
+
+    inventory
+}
+
+/// Ansible-style YAML, with `ansible_host`/`ansible_port` under `all.hosts`
+fn ansible_yaml(inventory: &Inventory) -> Result<String, NexusError> {
+    let mut yaml_map: HashMap<String, serde_yaml::Value> = HashMap::new();
+
+    // Add hosts to "all" group
+    let mut all_hosts = HashMap::new();
+    for host in inventory.hosts.values() {
+        let mut host_map = HashMap::new();
+        host_map.insert(
+            "ansible_host".to_string(),
+            serde_yaml::Value::String(host.address.clone()),
+        );
+        host_map.insert(
+            "ansible_port".to_string(),
+            serde_yaml::Value::Number(host.port.into()),
+        );
+
+        if !host.user.is_empty() {
+            host_map.insert(
+                "ansible_user".to_string(),
+                serde_yaml::Value::String(host.user.clone()),
+            );
+        }
+
+        // Add custom vars
+        for (key, val) in &host.vars {
+            let yaml_val = match val {
+                Value::String(s) => serde_yaml::Value::String(s.clone()),
+                Value::Int(n) => serde_yaml::Value::Number((*n).into()),
+                Value::Float(f) => serde_yaml::Value::String(f.to_string()),
+                Value::Bool(b) => serde_yaml::Value::Bool(*b),
+                Value::List(l) => serde_yaml::Value::String(format!("{:?}", l)),
+                Value::Dict(_) | Value::Null => serde_yaml::Value::String(format!("{:?}", val)),
+            };
+            host_map.insert(key.clone(), yaml_val);
+        }
+
+        all_hosts.insert(
+            host.name.clone(),
+            serde_yaml::Value::Mapping(
+                host_map
+                    .into_iter()
+                    .map(|(k, v)| (serde_yaml::Value::String(k), v))
+                    .collect(),
+            ),
+        );
+    }
+
+    let mut all_group = HashMap::new();
+    all_group.insert(
+        "hosts".to_string(),
+        serde_yaml::Value::Mapping(
+            all_hosts
+                .into_iter()
+                .map(|(k, v)| (serde_yaml::Value::String(k), v))
+                .collect(),
+        ),
+    );
+
+    yaml_map.insert(
+        "all".to_string(),
+        serde_yaml::Value::Mapping(
+            all_group
+                .into_iter()
+                .map(|(k, v)| (serde_yaml::Value::String(k), v))
+                .collect(),
+        ),
+    );
+
+    serde_yaml::to_string(&yaml_map).map_err(serialize_error)
+}
+
+/// Nexus inventory: a `hosts` list with `address`, `port`, `user`, `groups` and
+/// vars inline
+fn nexus_yaml(inventory: &Inventory) -> Result<String, NexusError> {
+    let mut host_names: Vec<&String> = inventory.hosts.keys().collect();
+    host_names.sort();
+
+    let mut hosts = Vec::new();
+    for name in host_names {
+        let host = &inventory.hosts[name];
+        let mut entry = serde_yaml::Mapping::new();
+        entry.insert("name".into(), host.name.clone().into());
+        entry.insert("address".into(), host.address.clone().into());
+        entry.insert("port".into(), u64::from(host.port).into());
+        if !host.user.is_empty() {
+            entry.insert("user".into(), host.user.clone().into());
+        }
+        if !host.groups.is_empty() {
+            entry.insert("groups".into(), host.groups.clone().into());
+        }
+
+        let mut vars: Vec<(&String, &Value)> = host.vars.iter().collect();
+        vars.sort_by_key(|(key, _)| *key);
+        for (key, value) in vars {
+            let value = serde_yaml::to_value(value).map_err(serialize_error)?;
+            entry.insert(key.clone().into(), value);
+        }
+        hosts.push(serde_yaml::Value::Mapping(entry));
+    }
+
+    let mut root = serde_yaml::Mapping::new();
+    root.insert("hosts".into(), hosts.into());
+    serde_yaml::to_string(&root).map_err(serialize_error)
+}
+
+fn serialize_error(e: impl std::fmt::Display) -> NexusError {
+    NexusError::Runtime {
+        function: None,
+        message: format!("Failed to serialize inventory: {}", e),
+        suggestion: None,
+        location: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::{parse_inventory, OpenPort, OsClassification};
+    use chrono::Utc;
+    use std::time::Duration;
+
+    fn discovered() -> Vec<DiscoveredHost> {
+        let now = Utc::now();
+        vec![DiscoveredHost {
+            address: "192.168.1.10".parse().unwrap(),
+            hostname: Some("web1".to_string()),
+            open_ports: vec![OpenPort {
+                port: 22,
+                service: Some("ssh".to_string()),
+                banner: Some("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3".to_string()),
+            }],
+            os_classification: Some(OsClassification {
+                os_family: "linux".to_string(),
+                distribution: Some("ubuntu".to_string()),
+                confidence: 0.9,
+            }),
+            fingerprint: None,
+            first_seen: now,
+            last_seen: now,
+            response_time: Duration::from_millis(12),
+            snmp: None,
+            mac: None,
+            mdns_services: Vec::new(),
+        }]
+    }
+
+    #[test]
+    fn test_export_json() {
+        let json = export_discovered(&discovered(), ExportFormat::Json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let host = &parsed[0];
+        assert_eq!(host["address"], "192.168.1.10");
+        assert_eq!(host["open_ports"][0]["service"], "ssh");
+        assert_eq!(host["os_classification"]["distribution"], "ubuntu");
+        assert_eq!(host["response_time_ms"], 12);
+    }
+
+    #[test]
+    fn test_export_nexus_reads_back() {
+        let yaml = export_discovered(&discovered(), ExportFormat::Nexus).unwrap();
+        let inventory = parse_inventory(&yaml).unwrap();
+
+        let host = &inventory.hosts["web1"];
+        assert_eq!(host.address, "192.168.1.10");
+        assert_eq!(host.port, 22);
+        assert_eq!(
+            host.vars.get("discovered_os_family"),
+            Some(&Value::String("linux".to_string()))
+        );
+        assert_eq!(inventory.groups["discovered"].hosts, vec!["web1"]);
+    }
+}
@@ -5,12 +5,12 @@
 // - `--list` returns full inventory as JSON
 // - `--host <hostname>` returns host-specific vars (optional)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 
 use super::{Host, HostGroup, Inventory};
 use crate::output::errors::NexusError;
@@ -238,6 +238,7 @@ impl DynamicInventory {
             });
         }
 
+        inventory.validate_groups()?;
         Ok(inventory)
     }
 
@@ -265,6 +266,151 @@ impl DynamicInventory {
     }
 }
 
+/// Render `hosts` of `inventory` the way a dynamic inventory script answers `--list`:
+/// every group with its hosts, children and vars, `all` listing the top-level groups
+/// (plus `ungrouped`), and each host's own vars under `_meta.hostvars`. Groups whose
+/// hosts were all left out by a pattern are dropped.
+pub fn inventory_list_json(inventory: &Inventory, hosts: &[&Host]) -> JsonValue {
+    let selected: HashSet<&str> = hosts.iter().map(|h| h.name.as_str()).collect();
+    let selected_in = |group: &HostGroup| -> Vec<String> {
+        group
+            .hosts
+            .iter()
+            .filter(|h| selected.contains(h.as_str()))
+            .cloned()
+            .collect()
+    };
+
+    // A group stays if it has a selected host or a child that stays; declared but
+    // empty groups stay as well. Iterated to a fixed point so cyclic children end.
+    let mut kept: HashSet<&str> = inventory
+        .groups
+        .values()
+        .filter(|g| g.name != "all")
+        .filter(|g| !selected_in(g).is_empty() || (g.hosts.is_empty() && g.children.is_empty()))
+        .map(|g| g.name.as_str())
+        .collect();
+    loop {
+        let before = kept.len();
+        for group in inventory.groups.values() {
+            if group.name != "all" && group.children.iter().any(|c| kept.contains(c.as_str())) {
+                kept.insert(group.name.as_str());
+            }
+        }
+        if kept.len() == before {
+            break;
+        }
+    }
+
+    let mut output = serde_json::Map::new();
+    let mut grouped: HashSet<&str> = HashSet::new();
+    for name in &kept {
+        let group = &inventory.groups[*name];
+        let group_hosts = selected_in(group);
+        let children: Vec<&String> = group
+            .children
+            .iter()
+            .filter(|c| kept.contains(c.as_str()))
+            .collect();
+        grouped.extend(
+            group
+                .hosts
+                .iter()
+                .map(|h| h.as_str())
+                .filter(|h| selected.contains(h)),
+        );
+
+        let mut entry = serde_json::Map::new();
+        if !group_hosts.is_empty() {
+            entry.insert("hosts".to_string(), json!(group_hosts));
+        }
+        if !children.is_empty() {
+            entry.insert("children".to_string(), json!(children));
+        }
+        if !group.vars.is_empty() {
+            entry.insert("vars".to_string(), vars_to_json(&group.vars));
+        }
+        output.insert(name.to_string(), JsonValue::Object(entry));
+    }
+
+    // Hosts in no group other than `all` go in `ungrouped`, as Ansible does
+    let mut ungrouped: Vec<&str> = hosts
+        .iter()
+        .map(|h| h.name.as_str())
+        .filter(|h| !grouped.contains(h))
+        .collect();
+    ungrouped.sort();
+    if !ungrouped.is_empty() {
+        let entry = output
+            .entry("ungrouped".to_string())
+            .or_insert_with(|| json!({}));
+        entry["hosts"] = json!(ungrouped);
+    }
+
+    let mut top_level: Vec<&str> = output
+        .keys()
+        .map(|k| k.as_str())
+        .filter(|name| {
+            !kept
+                .iter()
+                .any(|g| inventory.groups[*g].children.iter().any(|c| c == name))
+        })
+        .collect();
+    top_level.sort();
+    let mut all = serde_json::Map::new();
+    all.insert("children".to_string(), json!(top_level));
+    if let Some(vars) = inventory.groups.get("all").map(|g| &g.vars) {
+        if !vars.is_empty() {
+            all.insert("vars".to_string(), vars_to_json(vars));
+        }
+    }
+
+    let hostvars: serde_json::Map<String, JsonValue> = hosts
+        .iter()
+        .map(|host| {
+            let mut vars = connection_vars(inventory, host);
+            if let JsonValue::Object(own) = vars_to_json(&host.vars) {
+                vars.extend(own);
+            }
+            (host.name.clone(), JsonValue::Object(vars))
+        })
+        .collect();
+
+    output.insert("all".to_string(), JsonValue::Object(all));
+    output.insert("_meta".to_string(), json!({ "hostvars": hostvars }));
+    JsonValue::Object(output)
+}
+
+/// The vars a dynamic inventory script answers `--host <name>` with: the host's
+/// effective vars (all, group and host vars merged) plus its connection settings.
+/// `None` if the inventory has no such host.
+pub fn host_vars_json(inventory: &Inventory, name: &str) -> Option<JsonValue> {
+    let host = inventory.get_host(name)?;
+    let mut vars = connection_vars(inventory, host);
+    if let JsonValue::Object(effective) = vars_to_json(&inventory.get_host_vars(host)) {
+        vars.extend(effective);
+    }
+    Some(JsonValue::Object(vars))
+}
+
+/// `ansible_host`, `ansible_port` and (when known) `ansible_user` for `host`
+fn connection_vars(inventory: &Inventory, host: &Host) -> serde_json::Map<String, JsonValue> {
+    let mut vars = serde_json::Map::new();
+    vars.insert("ansible_host".to_string(), json!(host.address));
+    vars.insert("ansible_port".to_string(), json!(host.port));
+    let user = Some(host.user.as_str())
+        .filter(|u| !u.is_empty())
+        .or(inventory.default_user.as_deref());
+    if let Some(user) = user {
+        vars.insert("ansible_user".to_string(), json!(user));
+    }
+    vars
+}
+
+fn vars_to_json(vars: &HashMap<String, Value>) -> JsonValue {
+    serde_json::to_value(vars).unwrap_or_else(|_| json!({}))
+}
+
 /// Convert serde_json::Value to our internal Value type
 fn json_to_value(json: &JsonValue) -> Value {
     match json {
@@ -380,4 +526,53 @@ mod tests {
         assert!(webservers.children.contains(&"frontend".to_string()));
         assert!(webservers.children.contains(&"backend".to_string()));
     }
+
+    #[test]
+    fn test_list_json_round_trips() {
+        let mut inventory = Inventory::new();
+        inventory.default_user = Some("admin".to_string());
+        for (name, group) in [("web1", "web"), ("web2", "web"), ("db1", "db")] {
+            let mut host = Host::new(name).with_address(format!("10.0.0.{}", name.len()));
+            host.groups.push(group.to_string());
+            inventory.add_host(host.with_var("rack", Value::Int(4)));
+        }
+        inventory.add_host(Host::new("lonely").with_port(2222).with_user("ops"));
+        let mut prod = HostGroup::new("prod");
+        prod.children = vec!["web".to_string(), "db".to_string()];
+        prod.vars
+            .insert("env".to_string(), Value::String("prod".to_string()));
+        inventory.add_group(prod);
+
+        let mut hosts: Vec<&Host> = inventory.hosts.values().collect();
+        hosts.sort_by(|a, b| a.name.cmp(&b.name));
+        let json = inventory_list_json(&inventory, &hosts);
+        assert_eq!(json["all"]["children"], json!(["prod", "ungrouped"]));
+        assert_eq!(json["ungrouped"]["hosts"], json!(["lonely"]));
+        assert_eq!(json["_meta"]["hostvars"]["web1"]["ansible_user"], "admin");
+        assert_eq!(json["_meta"]["hostvars"]["web1"]["rack"], 4);
+
+        let dynamic = DynamicInventory::new(PathBuf::from("/tmp/inventory.py"));
+        let parsed = dynamic.parse_list_output(&json.to_string()).unwrap();
+        assert_eq!(parsed.hosts.len(), 4);
+        assert_eq!(parsed.hosts["lonely"].port, 2222);
+        assert_eq!(parsed.hosts["lonely"].user, "ops");
+        assert_eq!(parsed.groups["prod"].children, vec!["web", "db"]);
+        let web1 = &parsed.hosts["web1"];
+        assert_eq!(web1.address, "10.0.0.4");
+        assert_eq!(web1.vars.get("rack"), Some(&Value::Int(4)));
+        assert_eq!(
+            parsed.groups["prod"].vars.get("env"),
+            Some(&Value::String("prod".to_string()))
+        );
+
+        // Only the db host: the web group goes, prod stays through db
+        let db1 = inventory.get_host("db1").unwrap();
+        let json = inventory_list_json(&inventory, &[db1]);
+        assert!(json.get("web").is_none());
+        assert_eq!(json["prod"]["children"], json!(["db"]));
+
+        let vars = host_vars_json(&inventory, "web2").unwrap();
+        assert_eq!(vars["ansible_host"], "10.0.0.4");
+        assert!(host_vars_json(&inventory, "nope").is_none());
+    }
 }
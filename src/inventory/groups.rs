@@ -1,7 +1,10 @@
 // Group filtering and pattern matching for inventory
 
+use std::collections::{HashMap, HashSet};
+
 use super::{Host, Inventory};
-use crate::parser::ast::HostPattern;
+use crate::output::errors::NexusError;
+use crate::parser::ast::{HostPattern, Value};
 
 /// Filter options for host selection
 #[derive(Debug, Clone, Default)]
@@ -114,6 +117,206 @@ pub fn host_matches_group(inventory: &Inventory, host: &Host, group_name: &str)
     false
 }
 
+/// Resolve a `--limit` expression to the names of the hosts it selects.
+///
+/// Terms are separated by `,` or `:`; each is a host or group name, or a host name
+/// wildcard (`web*`). Plain terms are unioned, then `&term` intersects and `!term`
+/// excludes. `@path` reads host names from a file, one per line (e.g. a retry file).
+pub fn resolve_limit(inventory: &Inventory, limit: &str) -> Result<Vec<String>, NexusError> {
+    let mut included: Vec<String> = Vec::new();
+    let mut intersections: Vec<HashSet<String>> = Vec::new();
+    let mut exclusions: HashSet<String> = HashSet::new();
+
+    for term in limit.split([',', ':']).map(str::trim) {
+        if term.is_empty() {
+            continue;
+        }
+
+        if let Some(path) = term.strip_prefix('@') {
+            let content = std::fs::read_to_string(path).map_err(|e| NexusError::Io {
+                message: format!("Failed to read limit file: {}", e),
+                path: Some(path.into()),
+            })?;
+            included.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        } else if let Some(name) = term.strip_prefix('&') {
+            intersections.push(limit_term_hosts(inventory, name).into_iter().collect());
+        } else if let Some(name) = term.strip_prefix('!') {
+            exclusions.extend(limit_term_hosts(inventory, name));
+        } else {
+            included.extend(limit_term_hosts(inventory, term));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    included.retain(|name| {
+        seen.insert(name.clone())
+            && !exclusions.contains(name)
+            && intersections.iter().all(|set| set.contains(name))
+    });
+
+    Ok(included)
+}
+
+/// Hosts named by a single limit term: a group, a host, or a host name wildcard
+fn limit_term_hosts(inventory: &Inventory, term: &str) -> Vec<String> {
+    if term == "all" {
+        return expand_pattern(inventory, &HostPattern::All);
+    }
+
+    if term.contains('*') || term.contains('?') {
+        let pattern = regex::escape(term).replace(r"\*", ".*").replace(r"\?", ".");
+        return match regex::Regex::new(&format!("^{}$", pattern)) {
+            Ok(re) => {
+                let mut names: Vec<String> = inventory
+                    .hosts
+                    .keys()
+                    .filter(|name| re.is_match(name))
+                    .cloned()
+                    .collect();
+                names.sort();
+                names
+            }
+            Err(_) => vec![],
+        };
+    }
+
+    expand_pattern(inventory, &HostPattern::Group(term.to_string()))
+}
+
+/// Render the group tree under `root` like `ansible-inventory --graph`: `@group:`
+/// lines with child groups first, then host leaves, each level indented by `|  `.
+/// Under `all` the top-level groups are listed, plus `ungrouped` for hosts in no
+/// other group. With `show_vars`, host and group vars follow as `{key = value}`.
+/// A child already on the path is named but not expanded again, so cycles end.
+pub fn inventory_graph(
+    inventory: &Inventory,
+    root: &str,
+    show_vars: bool,
+) -> Result<String, NexusError> {
+    if root != "all" && !inventory.groups.contains_key(root) {
+        return Err(NexusError::Inventory {
+            message: format!("Group '{}' not found in inventory", root),
+            suggestion: Some("--graph takes a group name, e.g. 'all' or 'webservers'".into()),
+        });
+    }
+
+    let mut out = String::new();
+    graph_group(inventory, root, 0, &mut Vec::new(), show_vars, &mut out);
+    Ok(out)
+}
+
+fn graph_group<'a>(
+    inventory: &'a Inventory,
+    name: &'a str,
+    depth: usize,
+    path: &mut Vec<&'a str>,
+    show_vars: bool,
+    out: &mut String,
+) {
+    out.push_str(&format!("{}@{}:\n", graph_prefix(depth), name));
+    if path.contains(&name) {
+        return;
+    }
+    path.push(name);
+
+    let group = inventory.groups.get(name);
+    let (mut children, mut hosts): (Vec<&str>, Vec<&str>) = match (name, group) {
+        ("all", _) => (top_level_groups(inventory), Vec::new()),
+        ("ungrouped", None) => (Vec::new(), ungrouped_hosts(inventory)),
+        (_, Some(group)) => (
+            group.children.iter().map(String::as_str).collect(),
+            group.hosts.iter().map(String::as_str).collect(),
+        ),
+        (_, None) => (Vec::new(), Vec::new()),
+    };
+    children.sort();
+    children.dedup();
+    hosts.sort();
+    hosts.dedup();
+
+    for child in children {
+        if child == "ungrouped" || inventory.groups.contains_key(child) {
+            graph_group(inventory, child, depth + 1, path, show_vars, out);
+        }
+    }
+    for host in hosts {
+        out.push_str(&format!("{}{}\n", graph_prefix(depth + 1), host));
+        if show_vars {
+            if let Some(host) = inventory.hosts.get(host) {
+                graph_vars(&host.vars, depth + 2, out);
+            }
+        }
+    }
+    if let (true, Some(group)) = (show_vars, group) {
+        graph_vars(&group.vars, depth + 1, out);
+    }
+
+    path.pop();
+}
+
+fn graph_prefix(depth: usize) -> String {
+    match depth {
+        0 => String::new(),
+        _ => format!("{}|--", "|  ".repeat(depth - 1)),
+    }
+}
+
+fn graph_vars(vars: &HashMap<String, Value>, depth: usize, out: &mut String) {
+    let mut keys: Vec<&String> = vars.keys().collect();
+    keys.sort();
+    for key in keys {
+        out.push_str(&format!(
+            "{}{{{} = {}}}\n",
+            graph_prefix(depth),
+            key,
+            vars[key]
+        ));
+    }
+}
+
+/// Groups that aren't a child of another group (besides `all`), plus `ungrouped`
+/// when some host is in no group but `all`
+fn top_level_groups(inventory: &Inventory) -> Vec<&str> {
+    let nested: HashSet<&str> = inventory
+        .groups
+        .values()
+        .filter(|g| g.name != "all")
+        .flat_map(|g| g.children.iter().map(String::as_str))
+        .collect();
+    let mut top: Vec<&str> = inventory
+        .groups
+        .keys()
+        .map(String::as_str)
+        .filter(|name| *name != "all" && !nested.contains(name))
+        .collect();
+    if !inventory.groups.contains_key("ungrouped") && !ungrouped_hosts(inventory).is_empty() {
+        top.push("ungrouped");
+    }
+    top
+}
+
+/// Hosts that no group other than `all` lists
+fn ungrouped_hosts(inventory: &Inventory) -> Vec<&str> {
+    let grouped: HashSet<&str> = inventory
+        .groups
+        .values()
+        .filter(|g| g.name != "all")
+        .flat_map(|g| g.hosts.iter().map(String::as_str))
+        .collect();
+    inventory
+        .hosts
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !grouped.contains(name))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,6 +380,69 @@ mod tests {
         assert_eq!(filtered.len(), 3);
     }
 
+    #[test]
+    fn test_resolve_limit() {
+        let inv = create_test_inventory();
+        let sorted = |limit: &str| {
+            let mut names = resolve_limit(&inv, limit).unwrap();
+            names.sort();
+            names
+        };
+
+        assert_eq!(sorted("web1"), vec!["web1"]);
+        assert_eq!(sorted("web1,db1"), vec!["db1", "web1"]);
+        assert_eq!(sorted("webservers:databases"), vec!["db1", "web1", "web2"]);
+        assert_eq!(sorted("all:!webservers"), vec!["db1", "staging1"]);
+        assert_eq!(sorted("web*:&webservers:!web2"), vec!["web1"]);
+        assert!(sorted("nosuchhost").is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let retry = dir.path().join("site.retry");
+        std::fs::write(&retry, "web2\nstaging1\n").unwrap();
+        assert_eq!(
+            sorted(&format!("@{}", retry.display())),
+            vec!["staging1", "web2"]
+        );
+        assert!(resolve_limit(&inv, "@/nonexistent/site.retry").is_err());
+    }
+
+    #[test]
+    fn test_inventory_graph() {
+        let mut inv = create_test_inventory();
+        inv.add_host(Host::new("lonely"));
+        inv.groups.get_mut("all").unwrap().children = vec!["staging".into()];
+        let mut prod = HostGroup::new("production");
+        prod.children = vec!["webservers".into(), "databases".into()];
+        prod.vars.insert("tier".into(), "gold".into());
+        inv.add_group(prod);
+        // A cycle is named but not followed
+        inv.groups.get_mut("databases").unwrap().children = vec!["production".into()];
+
+        assert_eq!(
+            inventory_graph(&inv, "all", false).unwrap(),
+            "@all:\n\
+             |--@staging:\n\
+             |  |--staging1\n\
+             |--@ungrouped:\n\
+             |  |--lonely\n"
+        );
+        assert_eq!(
+            inventory_graph(&inv, "production", true).unwrap(),
+            "@production:\n\
+             |--@databases:\n\
+             |  |--@production:\n\
+             |  |--db1\n\
+             |  |  |--{env = prod}\n\
+             |--@webservers:\n\
+             |  |--web1\n\
+             |  |  |--{env = prod}\n\
+             |  |--web2\n\
+             |  |  |--{env = prod}\n\
+             |--{tier = gold}\n"
+        );
+        assert!(inventory_graph(&inv, "nosuchgroup", false).is_err());
+    }
+
     #[test]
     fn test_parse_host_pattern() {
         assert_eq!(parse_host_pattern("all"), HostPattern::All);
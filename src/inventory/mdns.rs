@@ -0,0 +1,421 @@
+// Minimal mDNS/DNS-SD browser for discovery - PTR, SRV, TXT and A records only
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::time::{timeout_at, Instant};
+
+use crate::output::errors::NexusError;
+
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+/// Lists the service types advertised on the link (RFC 6763 section 9)
+const SERVICE_ENUMERATION: &str = "_services._dns-sd._udp.local";
+
+/// Browsed even when nothing answers the service enumeration query
+const COMMON_SERVICE_TYPES: &[&str] = &[
+    "_workstation._tcp.local",
+    "_ssh._tcp.local",
+    "_sftp-ssh._tcp.local",
+    "_http._tcp.local",
+    "_https._tcp.local",
+    "_ipp._tcp.local",
+    "_ipps._tcp.local",
+    "_printer._tcp.local",
+    "_pdl-datastream._tcp.local",
+    "_googlecast._tcp.local",
+    "_airplay._tcp.local",
+    "_raop._tcp.local",
+    "_smb._tcp.local",
+    "_afpovertcp._tcp.local",
+    "_device-info._tcp.local",
+];
+
+/// Follow-up rounds for service types and addresses learned from earlier answers
+const MAX_ROUNDS: usize = 3;
+
+// Record types
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+
+/// A service instance a host advertises, such as `_ipp._tcp` on port 631
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MdnsService {
+    /// Service type without the domain, e.g. `_ipp._tcp`
+    pub service_type: String,
+    /// Instance name, e.g. `Office LaserJet`
+    pub instance: String,
+    pub port: u16,
+    /// TXT record strings, e.g. `md=Chromecast`
+    #[serde(default)]
+    pub txt: Vec<String>,
+}
+
+/// Everything one address advertised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MdnsHost {
+    pub(crate) address: IpAddr,
+    pub(crate) hostname: Option<String>,
+    pub(crate) services: Vec<MdnsService>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RecordData {
+    A(Ipv4Addr),
+    Ptr(String),
+    Srv { port: u16, target: String },
+    Txt(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    name: String,
+    data: RecordData,
+}
+
+/// Browse the local link for `wait` per round and group what answered by address
+pub(crate) async fn browse(wait: Duration) -> Result<Vec<MdnsHost>, NexusError> {
+    let socket_error = |e: std::io::Error| NexusError::Inventory {
+        message: format!("mDNS query failed: {}", e),
+        suggestion: Some("mDNS needs UDP multicast to 224.0.0.251:5353 on the local link".into()),
+    };
+    // Queries from a port other than 5353 get unicast replies (RFC 6762 section 6.7),
+    // so this works alongside a local responder such as avahi
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(socket_error)?;
+    socket.set_multicast_ttl_v4(255).map_err(socket_error)?;
+
+    let mut answers: Vec<(IpAddr, Record)> = Vec::new();
+    let mut asked: HashSet<(String, u16)> = HashSet::new();
+    let mut questions: Vec<(String, u16)> = std::iter::once(SERVICE_ENUMERATION)
+        .chain(COMMON_SERVICE_TYPES.iter().copied())
+        .map(|name| (name.to_string(), TYPE_PTR))
+        .collect();
+
+    for _ in 0..MAX_ROUNDS {
+        if questions.is_empty() {
+            break;
+        }
+        // Keep datagrams well under the usual 1500-byte MTU
+        for chunk in questions.chunks(12) {
+            socket
+                .send_to(&encode_query(chunk), (MDNS_GROUP, MDNS_PORT))
+                .await
+                .map_err(socket_error)?;
+        }
+        asked.extend(questions.drain(..));
+
+        let deadline = Instant::now() + wait;
+        let mut buffer = [0u8; 9000];
+        while let Ok(Ok((n, from))) = timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+            if let Some(records) = parse_response(&buffer[..n]) {
+                answers.extend(records.into_iter().map(|r| (from.ip(), r)));
+            }
+        }
+
+        questions = follow_up_questions(&answers, &asked);
+    }
+
+    Ok(collect_hosts(&answers))
+}
+
+/// Service types from the enumeration answers and SRV targets without an address
+fn follow_up_questions(
+    answers: &[(IpAddr, Record)],
+    asked: &HashSet<(String, u16)>,
+) -> Vec<(String, u16)> {
+    let resolved: HashSet<&str> = answers
+        .iter()
+        .filter(|(_, r)| matches!(r.data, RecordData::A(_)))
+        .map(|(_, r)| r.name.as_str())
+        .collect();
+
+    let mut questions = Vec::new();
+    for (_, record) in answers {
+        let question = match &record.data {
+            RecordData::Ptr(service_type) if record.name == SERVICE_ENUMERATION => {
+                (service_type.clone(), TYPE_PTR)
+            }
+            RecordData::Srv { target, .. } if !resolved.contains(target.as_str()) => {
+                (target.clone(), TYPE_A)
+            }
+            _ => continue,
+        };
+        if !asked.contains(&question) && !questions.contains(&question) {
+            questions.push(question);
+        }
+    }
+    questions
+}
+
+/// Join PTR, SRV, TXT and A records into hosts. A service's address comes from the
+/// A record of its SRV target, else from whoever sent the answer.
+fn collect_hosts(answers: &[(IpAddr, Record)]) -> Vec<MdnsHost> {
+    let mut addresses: HashMap<&str, IpAddr> = HashMap::new();
+    let mut srv: HashMap<&str, (u16, &str, IpAddr)> = HashMap::new();
+    let mut txt: HashMap<&str, &[String]> = HashMap::new();
+    for (from, record) in answers {
+        match &record.data {
+            RecordData::A(addr) => {
+                addresses.insert(&record.name, IpAddr::V4(*addr));
+            }
+            RecordData::Srv { port, target } => {
+                srv.insert(&record.name, (*port, target, *from));
+            }
+            RecordData::Txt(strings) => {
+                txt.insert(&record.name, strings);
+            }
+            RecordData::Ptr(_) => {}
+        }
+    }
+
+    // Sorted by address so the result doesn't depend on arrival order
+    let mut hosts: BTreeMap<IpAddr, MdnsHost> = BTreeMap::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for (_, record) in answers {
+        let RecordData::Ptr(instance) = &record.data else {
+            continue;
+        };
+        if record.name == SERVICE_ENUMERATION || !seen.insert(instance) {
+            continue;
+        }
+        let Some(&(port, target, from)) = srv.get(instance.as_str()) else {
+            continue;
+        };
+        let address = addresses.get(target).copied().unwrap_or(from);
+
+        let service_type = record.name.trim_end_matches(".local");
+        let host = hosts.entry(address).or_insert_with(|| MdnsHost {
+            address,
+            hostname: None,
+            services: Vec::new(),
+        });
+        host.hostname.get_or_insert_with(|| target.to_string());
+        host.services.push(MdnsService {
+            service_type: service_type.to_string(),
+            instance: instance
+                .strip_suffix(&format!(".{}", record.name))
+                .unwrap_or(instance)
+                .to_string(),
+            port,
+            txt: txt
+                .get(instance.as_str())
+                .map(|t| t.to_vec())
+                .unwrap_or_default(),
+        });
+    }
+
+    hosts.into_values().collect()
+}
+
+/// A standard query for `questions` (name, type), class IN
+fn encode_query(questions: &[(String, u16)]) -> Vec<u8> {
+    let mut packet = vec![0, 0, 0, 0]; // id, flags
+    packet.extend((questions.len() as u16).to_be_bytes());
+    packet.extend([0, 0, 0, 0, 0, 0]); // answer, authority, additional counts
+    for (name, qtype) in questions {
+        for label in name.split('.').filter(|l| !l.is_empty()) {
+            packet.push(label.len() as u8);
+            packet.extend(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend(qtype.to_be_bytes());
+        packet.extend(1u16.to_be_bytes());
+    }
+    packet
+}
+
+/// The records of a response, from all three sections. Queries and malformed
+/// packets give `None`; record types other than A, PTR, SRV and TXT are skipped.
+fn parse_response(packet: &[u8]) -> Option<Vec<Record>> {
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let records = [6, 8, 10]
+        .iter()
+        .map(|&offset| read_u16(packet, offset).map(usize::from))
+        .sum::<Option<usize>>()?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+
+    let mut parsed = Vec::new();
+    for _ in 0..records {
+        let (name, next) = read_name(packet, pos)?;
+        let rtype = read_u16(packet, next)?;
+        let rdlength = usize::from(read_u16(packet, next + 8)?);
+        let start = next + 10;
+        let rdata = packet.get(start..start + rdlength)?;
+        pos = start + rdlength;
+
+        let data = match rtype {
+            TYPE_A if rdlength == 4 => {
+                RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))
+            }
+            TYPE_PTR => RecordData::Ptr(read_name(packet, start)?.0),
+            TYPE_SRV => RecordData::Srv {
+                port: read_u16(packet, start + 4)?,
+                target: read_name(packet, start + 6)?.0,
+            },
+            TYPE_TXT => RecordData::Txt(read_txt(rdata)),
+            _ => continue,
+        };
+        parsed.push(Record { name, data });
+    }
+    Some(parsed)
+}
+
+/// Read a possibly compressed name at `pos`; returns it without the trailing dot,
+/// and the position after it
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Each pointer must go backwards, which rules out loops
+    let mut limit = pos;
+    loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let target = usize::from(read_u16(packet, pos)? & 0x3fff);
+                if target >= limit {
+                    return None;
+                }
+                end.get_or_insert(pos + 2);
+                limit = target;
+                pos = target;
+            }
+            len => {
+                let label = packet.get(pos + 1..pos + 1 + usize::from(len))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + usize::from(len);
+            }
+        }
+    }
+}
+
+fn read_txt(rdata: &[u8]) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = rdata;
+    while let Some((&len, tail)) = rest.split_first() {
+        let Some(text) = tail.get(..usize::from(len)) else {
+            break;
+        };
+        if !text.is_empty() {
+            strings.push(String::from_utf8_lossy(text).into_owned());
+        }
+        rest = &tail[usize::from(len)..];
+    }
+    strings
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    let bytes = packet.get(pos..pos + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response carrying `records` (name, type, rdata), without compression
+    fn response(records: &[(&str, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0];
+        packet.extend((records.len() as u16).to_be_bytes());
+        packet.extend([0, 0, 0, 0]);
+        for (name, rtype, rdata) in records {
+            packet.extend(&encode_query(&[(name.to_string(), *rtype)])[12..]);
+            packet.extend([0, 0, 0, 120]); // ttl
+            packet.extend((rdata.len() as u16).to_be_bytes());
+            packet.extend(rdata);
+        }
+        packet
+    }
+
+    fn name(name: &str) -> Vec<u8> {
+        let encoded = encode_query(&[(name.to_string(), 0)]);
+        encoded[12..encoded.len() - 4].to_vec()
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let query = encode_query(&[("_ipp._tcp.local".to_string(), TYPE_PTR)]);
+        assert_eq!(&query[..12], &[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..17], &[4, b'_', b'i', b'p', b'p']);
+        assert_eq!(&query[query.len() - 5..], &[0, 0, 12, 0, 1]);
+        // Queries aren't responses
+        assert!(parse_response(&query).is_none());
+    }
+
+    #[test]
+    fn test_read_name_follows_pointers() {
+        // "local" at 12, then "printer" + pointer to it
+        let mut packet = vec![0; 12];
+        packet.extend([5, b'l', b'o', b'c', b'a', b'l', 0]);
+        packet.extend([7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 0xc0, 12]);
+        assert_eq!(
+            read_name(&packet, 19),
+            Some(("printer.local".to_string(), 29))
+        );
+
+        // A pointer to itself
+        let mut looping = vec![0; 12];
+        looping.extend([0xc0, 12]);
+        assert_eq!(read_name(&looping, 12), None);
+    }
+
+    #[test]
+    fn test_browse_answers_become_hosts() {
+        let instance = "Office LaserJet._ipp._tcp.local";
+        let mut srv = vec![0, 0, 0, 0, 0x02, 0x77]; // priority, weight, port 631
+        srv.extend(name("laserjet.local"));
+        let packet = response(&[
+            ("_ipp._tcp.local", TYPE_PTR, name(instance)),
+            (instance, TYPE_SRV, srv),
+            (instance, TYPE_TXT, b"\x0bty=LaserJet\x06rp=ipp".to_vec()),
+            ("laserjet.local", TYPE_A, vec![192, 168, 1, 40]),
+            (
+                "_services._dns-sd._udp.local",
+                TYPE_PTR,
+                name("_ipp._tcp.local"),
+            ),
+        ]);
+
+        let records = parse_response(&packet).unwrap();
+        assert_eq!(records.len(), 5);
+
+        // Sent by a sleep proxy, but the A record says where the printer is
+        let from: IpAddr = "192.168.1.2".parse().unwrap();
+        let answers: Vec<(IpAddr, Record)> = records.into_iter().map(|r| (from, r)).collect();
+        let hosts = collect_hosts(&answers);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].address, "192.168.1.40".parse::<IpAddr>().unwrap());
+        assert_eq!(hosts[0].hostname.as_deref(), Some("laserjet.local"));
+        assert_eq!(
+            hosts[0].services,
+            vec![MdnsService {
+                service_type: "_ipp._tcp".to_string(),
+                instance: "Office LaserJet".to_string(),
+                port: 631,
+                txt: vec!["ty=LaserJet".to_string(), "rp=ipp".to_string()],
+            }]
+        );
+
+        // Everything needed is known; _ipp._tcp was already asked for
+        let asked = [("_ipp._tcp.local".to_string(), TYPE_PTR)].into();
+        assert!(follow_up_questions(&answers, &asked).is_empty());
+    }
+}
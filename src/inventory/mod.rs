@@ -2,16 +2,24 @@
 
 mod discovery;
 mod discovery_daemon;
+mod discovery_export;
 mod discovery_profile;
 mod dynamic;
 mod groups;
+mod mdns;
+mod scan_state;
+mod snmp;
 mod static_inv;
 
 pub use discovery::*;
 pub use discovery_daemon::*;
+pub use discovery_export::*;
 pub use discovery_profile::*;
 pub use dynamic::*;
 pub use groups::*;
+pub use mdns::MdnsService;
+pub use scan_state::{default_discovery_dir, scan_state_path};
+pub use snmp::{SnmpSystemInfo, SnmpVersion};
 pub use static_inv::*;
 
 use std::collections::HashMap;
@@ -95,6 +103,16 @@ impl Host {
             || self.address == "::1"
     }
 
+    /// Check if this host is a container reached with `ansible_connection: docker`
+    pub fn is_docker(&self) -> bool {
+        matches!(self.vars.get("ansible_connection"), Some(Value::String(conn)) if conn == "docker")
+    }
+
+    /// Check if this host is a Windows machine reached with `ansible_connection: winrm`
+    pub fn is_winrm(&self) -> bool {
+        matches!(self.vars.get("ansible_connection"), Some(Value::String(conn)) if conn == "winrm")
+    }
+
     /// Create a localhost host for delegation
     pub fn localhost() -> Self {
         Host::new("localhost")
@@ -178,6 +196,7 @@ impl Inventory {
                     function: None,
                     message: "Failed to receive result from dynamic inventory thread".to_string(),
                     suggestion: None,
+                    location: None,
                 })?
             }
             Err(_) => {
@@ -186,6 +205,7 @@ impl Inventory {
                     function: None,
                     message: format!("Failed to create async runtime: {}", e),
                     suggestion: None,
+                    location: None,
                 })?;
                 rt.block_on(dynamic.load())
             }
@@ -311,6 +331,24 @@ impl Inventory {
         self.groups.insert(group.name.clone(), group);
     }
 
+    /// Add an existing host to a group, creating the group if needed
+    pub fn add_host_to_group(&mut self, host: &str, group: &str) {
+        let Some(entry) = self.hosts.get_mut(host) else {
+            return;
+        };
+        if !entry.groups.iter().any(|g| g == group) {
+            entry.groups.push(group.to_string());
+        }
+        let hosts = &mut self
+            .groups
+            .entry(group.to_string())
+            .or_insert_with(|| HostGroup::new(group))
+            .hosts;
+        if !hosts.iter().any(|h| h == host) {
+            hosts.push(host.to_string());
+        }
+    }
+
     /// Get hosts matching a pattern
     pub fn get_hosts(&self, pattern: &HostPattern) -> Vec<&Host> {
         match pattern {
@@ -318,7 +356,9 @@ impl Inventory {
             HostPattern::Group(name) => {
                 // First try to find a group with this name
                 if let Some(group) = self.groups.get(name) {
-                    self.expand_group(group)
+                    // Loaded inventories are checked for cycles (validate_groups);
+                    // a cyclic group built by hand matches nothing
+                    self.expand_group(group).unwrap_or_default()
                 } else if let Some(host) = self.hosts.get(name) {
                     // If no group found, check if it's a direct hostname
                     vec![host]
@@ -353,8 +393,22 @@ impl Inventory {
         self.hosts.get(name)
     }
 
-    /// Expand a group to its hosts (including children)
-    fn expand_group(&self, group: &HostGroup) -> Vec<&Host> {
+    /// Expand a group to its hosts (including children). Fails if a group turns out
+    /// to be its own descendant.
+    fn expand_group<'a>(&'a self, group: &'a HostGroup) -> Result<Vec<&'a Host>, NexusError> {
+        self.expand_group_within(group, &mut Vec::new())
+    }
+
+    fn expand_group_within<'a>(
+        &'a self,
+        group: &'a HostGroup,
+        path: &mut Vec<&'a str>,
+    ) -> Result<Vec<&'a Host>, NexusError> {
+        if path.contains(&group.name.as_str()) {
+            return Err(group_cycle_error(path, &group.name));
+        }
+        path.push(&group.name);
+
         let mut hosts = Vec::new();
 
         // Direct hosts
@@ -367,15 +421,53 @@ impl Inventory {
         // Child groups (recursive)
         for child_name in &group.children {
             if let Some(child) = self.groups.get(child_name) {
-                hosts.extend(self.expand_group(child));
+                hosts.extend(self.expand_group_within(child, path)?);
             }
         }
+        path.pop();
 
         // Deduplicate
         let mut seen = std::collections::HashSet::new();
         hosts.retain(|h| seen.insert(h.name.clone()));
 
-        hosts
+        Ok(hosts)
+    }
+
+    /// Check that no group is its own descendant through `children`. Run when an
+    /// inventory is loaded, so a cycle is reported up front instead of when the
+    /// group is used.
+    pub fn validate_groups(&self) -> Result<(), NexusError> {
+        let mut names: Vec<&String> = self.groups.keys().collect();
+        names.sort();
+
+        let mut checked = std::collections::HashSet::new();
+        for name in names {
+            self.check_group_cycle(name, &mut Vec::new(), &mut checked)?;
+        }
+        Ok(())
+    }
+
+    fn check_group_cycle<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+        checked: &mut std::collections::HashSet<&'a str>,
+    ) -> Result<(), NexusError> {
+        if path.contains(&name) {
+            return Err(group_cycle_error(path, name));
+        }
+        if checked.contains(name) {
+            return Ok(());
+        }
+        if let Some(group) = self.groups.get(name) {
+            path.push(name);
+            for child in &group.children {
+                self.check_group_cycle(child, path, checked)?;
+            }
+            path.pop();
+        }
+        checked.insert(name);
+        Ok(())
     }
 
     /// Match hosts against a complex pattern
@@ -399,7 +491,7 @@ impl Inventory {
                 let group_hosts: std::collections::HashSet<_> = self
                     .groups
                     .get(group_name)
-                    .map(|g| self.expand_group(g))
+                    .and_then(|g| self.expand_group(g).ok())
                     .unwrap_or_default()
                     .into_iter()
                     .map(|h| &h.name)
@@ -411,7 +503,7 @@ impl Inventory {
                 let group_hosts: std::collections::HashSet<_> = self
                     .groups
                     .get(group_name)
-                    .map(|g| self.expand_group(g))
+                    .and_then(|g| self.expand_group(g).ok())
                     .unwrap_or_default()
                     .into_iter()
                     .map(|h| &h.name)
@@ -423,7 +515,7 @@ impl Inventory {
                 let group_hosts = self
                     .groups
                     .get(part)
-                    .map(|g| self.expand_group(g))
+                    .and_then(|g| self.expand_group(g).ok())
                     .unwrap_or_default();
 
                 if first {
@@ -477,6 +569,40 @@ impl Inventory {
     pub fn group_names(&self) -> Vec<&str> {
         self.groups.keys().map(|s| s.as_str()).collect()
     }
+
+    /// Each group's host names, sorted, including those of its child groups.
+    /// `all` has every host.
+    pub fn group_hosts(&self) -> HashMap<String, Vec<String>> {
+        let mut groups: HashMap<String, Vec<String>> = self
+            .groups
+            .values()
+            .map(|group| {
+                let hosts = self.expand_group(group).unwrap_or_default();
+                let mut names: Vec<String> = hosts.iter().map(|h| h.name.clone()).collect();
+                names.sort();
+                (group.name.clone(), names)
+            })
+            .collect();
+
+        let mut all: Vec<String> = self.hosts.keys().cloned().collect();
+        all.sort();
+        groups.insert("all".to_string(), all);
+        groups
+    }
+}
+
+/// Error for a group reached again while expanding `path` (outermost first)
+fn group_cycle_error(path: &[&str], name: &str) -> NexusError {
+    let start = path.iter().position(|g| *g == name).unwrap_or(0);
+    let mut cycle: Vec<&str> = path[start..].to_vec();
+    cycle.push(name);
+    NexusError::Inventory {
+        message: format!("Cyclic group children: {}", cycle.join(" -> ")),
+        suggestion: Some(format!(
+            "Remove one of these 'children' entries so '{}' is not its own descendant",
+            name
+        )),
+    }
 }
 
 #[cfg(test)]
@@ -525,5 +651,50 @@ mod tests {
         // Test group selection
         let webs = inv.get_hosts(&HostPattern::Group("webservers".to_string()));
         assert_eq!(webs.len(), 2);
+
+        let mut site = HostGroup::new("site");
+        site.children = vec!["webservers".to_string(), "databases".to_string()];
+        inv.add_group(site);
+        let groups = inv.group_hosts();
+        assert_eq!(groups["site"], ["db1", "web1", "web2"]);
+        assert_eq!(groups["all"], ["db1", "web1", "web2"]);
+        assert_eq!(groups["databases"], ["db1"]);
+    }
+
+    #[test]
+    fn test_cyclic_group_children() {
+        let yaml = r#"
+all:
+  children:
+    site:
+      children:
+        web:
+          hosts:
+            web1:
+          children:
+            site:
+"#;
+        let err = parse_inventory(yaml).unwrap_err().to_string();
+        assert!(err.contains("site -> web -> site"), "{}", err);
+
+        // Built by hand, the cycle surfaces when the group is expanded
+        let mut inv = Inventory::new();
+        inv.add_host(Host::new("web1"));
+        for (name, child) in [("a", "b"), ("b", "c"), ("c", "a")] {
+            let mut group = HostGroup::new(name);
+            group.hosts = vec!["web1".to_string()];
+            group.children = vec![child.to_string()];
+            inv.add_group(group);
+        }
+        let err = inv.expand_group(&inv.groups["b"]).unwrap_err().to_string();
+        assert!(err.contains("b -> c -> a -> b"), "{}", err);
+        assert!(inv.validate_groups().is_err());
+        assert!(inv
+            .get_hosts(&HostPattern::Group("a".to_string()))
+            .is_empty());
+
+        inv.groups.get_mut("c").unwrap().children.clear();
+        assert!(inv.validate_groups().is_ok());
+        assert_eq!(inv.expand_group(&inv.groups["a"]).unwrap().len(), 1);
     }
 }
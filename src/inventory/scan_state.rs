@@ -0,0 +1,84 @@
+// Scan state for resuming interrupted discovery scans
+
+use super::discovery::DiscoveredHost;
+use crate::output::errors::NexusError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// Directory for discovery state: `~/.nexus/discovery`
+pub fn default_discovery_dir() -> PathBuf {
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join(".nexus")
+        .join("discovery")
+}
+
+/// Scan state file for a `nexus discover` of `subnets`, so separate scans don't
+/// resume each other's progress
+pub fn scan_state_path(subnets: &[String]) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(subnets.join(",").as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    default_discovery_dir().join(format!("scan-{}.json", &hash[..16]))
+}
+
+/// Which addresses of each subnet have been probed so far, and what was found
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ScanState {
+    pub(crate) subnets: HashMap<String, SubnetProgress>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct SubnetProgress {
+    pub(crate) probed: HashSet<IpAddr>,
+    pub(crate) hosts: Vec<DiscoveredHost>,
+    /// Every address was probed; a resumed scan reuses the hosts as they are
+    pub(crate) complete: bool,
+}
+
+impl ScanState {
+    /// Load the state at `path`; a missing file is an empty state
+    pub(crate) fn load(path: &Path) -> Result<Self, NexusError> {
+        if !path.exists() {
+            return Ok(ScanState::default());
+        }
+
+        let content = std::fs::read_to_string(path).map_err(|e| NexusError::Io {
+            message: format!("Failed to read scan state: {}", e),
+            path: Some(path.to_path_buf()),
+        })?;
+
+        serde_json::from_str(&content).map_err(|e| NexusError::Inventory {
+            message: format!("Failed to parse scan state: {}", e),
+            suggestion: Some(format!("Delete {} to start the scan over", path.display())),
+        })
+    }
+
+    /// Write the state to `path`, replacing the previous file in one step so an
+    /// interruption mid-write can't leave it truncated
+    pub(crate) fn save(&self, path: &Path) -> Result<(), NexusError> {
+        let json = serde_json::to_string(self).map_err(|e| NexusError::Inventory {
+            message: format!("Failed to serialize scan state: {}", e),
+            suggestion: None,
+        })?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| NexusError::Io {
+                message: format!("Failed to create state directory: {}", e),
+                path: Some(parent.to_path_buf()),
+            })?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| NexusError::Io {
+                message: format!("Failed to write scan state: {}", e),
+                path: Some(path.to_path_buf()),
+            })
+    }
+}
@@ -0,0 +1,285 @@
+// Minimal SNMP client for discovery - a single GET of sysDescr.0 and sysName.0
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+/// sysDescr.0 (1.3.6.1.2.1.1.1.0)
+const SYS_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 1, 0];
+/// sysName.0 (1.3.6.1.2.1.1.5.0)
+const SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5, 0];
+
+const SNMP_PORT: u16 = 161;
+
+// BER tags
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const GET_REQUEST: u8 = 0xa0;
+const GET_RESPONSE: u8 = 0xa2;
+
+/// SNMP protocol version; both use a community string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnmpVersion {
+    V1,
+    #[default]
+    V2c,
+}
+
+impl SnmpVersion {
+    fn wire_value(self) -> u8 {
+        match self {
+            SnmpVersion::V1 => 0,
+            SnmpVersion::V2c => 1,
+        }
+    }
+}
+
+/// What a host reported about itself over SNMP
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnmpSystemInfo {
+    pub sys_descr: Option<String>,
+    pub sys_name: Option<String>,
+}
+
+/// Ask `addr` for its sysDescr and sysName. Returns `None` when nothing answers
+/// within `timeout_duration` or the answer isn't a valid response.
+pub(crate) async fn query_system(
+    addr: IpAddr,
+    community: &str,
+    version: SnmpVersion,
+    timeout_duration: Duration,
+) -> Option<SnmpSystemInfo> {
+    let bind: SocketAddr = match addr {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await.ok()?;
+    socket.connect((addr, SNMP_PORT)).await.ok()?;
+
+    let request_id = rand::random::<u16>() as u32;
+    let request = encode_get_request(community, version, request_id, &[SYS_DESCR, SYS_NAME]);
+    socket.send(&request).await.ok()?;
+
+    let mut buffer = [0u8; 2048];
+    // Skip stray datagrams (e.g. late answers to an earlier probe) until ours arrives
+    timeout(timeout_duration, async {
+        loop {
+            let n = socket.recv(&mut buffer).await.ok()?;
+            if let Some(info) = decode_get_response(&buffer[..n], request_id) {
+                return Some(info);
+            }
+        }
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Encode a GetRequest for `oids` with NULL values
+fn encode_get_request(
+    community: &str,
+    version: SnmpVersion,
+    request_id: u32,
+    oids: &[&[u32]],
+) -> Vec<u8> {
+    let varbinds: Vec<u8> = oids
+        .iter()
+        .flat_map(|oid| {
+            let mut varbind = tlv(OBJECT_IDENTIFIER, &encode_oid(oid));
+            varbind.extend(tlv(NULL, &[]));
+            tlv(SEQUENCE, &varbind)
+        })
+        .collect();
+
+    let mut pdu = tlv(INTEGER, &encode_integer(request_id));
+    pdu.extend(tlv(INTEGER, &[0])); // error-status
+    pdu.extend(tlv(INTEGER, &[0])); // error-index
+    pdu.extend(tlv(SEQUENCE, &varbinds));
+
+    let mut message = tlv(INTEGER, &[version.wire_value()]);
+    message.extend(tlv(OCTET_STRING, community.as_bytes()));
+    message.extend(tlv(GET_REQUEST, &pdu));
+    tlv(SEQUENCE, &message)
+}
+
+/// Decode a GetResponse to our request. String values of sysDescr and sysName are
+/// kept; errors and exceptions like noSuchObject leave them unset.
+fn decode_get_response(data: &[u8], request_id: u32) -> Option<SnmpSystemInfo> {
+    let (tag, message, _) = read_tlv(data)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (_, _version, rest) = expect_tlv(message, INTEGER)?;
+    let (_, _community, rest) = expect_tlv(rest, OCTET_STRING)?;
+    let (_, pdu, _) = expect_tlv(rest, GET_RESPONSE)?;
+
+    let (_, id, rest) = expect_tlv(pdu, INTEGER)?;
+    if decode_integer(id)? != request_id {
+        return None;
+    }
+    let (_, _error_status, rest) = expect_tlv(rest, INTEGER)?;
+    let (_, _error_index, rest) = expect_tlv(rest, INTEGER)?;
+    let (_, mut varbinds, _) = expect_tlv(rest, SEQUENCE)?;
+
+    let (descr_oid, name_oid) = (encode_oid(SYS_DESCR), encode_oid(SYS_NAME));
+    let mut info = SnmpSystemInfo::default();
+    while !varbinds.is_empty() {
+        let (_, varbind, rest) = expect_tlv(varbinds, SEQUENCE)?;
+        varbinds = rest;
+
+        let (_, oid, value) = expect_tlv(varbind, OBJECT_IDENTIFIER)?;
+        let (value_tag, value, _) = read_tlv(value)?;
+        if value_tag != OCTET_STRING {
+            continue;
+        }
+        let text = String::from_utf8_lossy(value).trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        if oid == descr_oid.as_slice() {
+            info.sys_descr = Some(text);
+        } else if oid == name_oid.as_slice() {
+            info.sys_name = Some(text);
+        }
+    }
+    Some(info)
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Split `data` into its first element's tag, content and what follows
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first & 0x80 == 0 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        (len, &rest[count..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+fn expect_tlv(data: &[u8], tag: u8) -> Option<(u8, &[u8], &[u8])> {
+    read_tlv(data).filter(|(t, _, _)| *t == tag)
+}
+
+/// Non-negative integer, without superfluous leading zero bytes
+fn encode_integer(value: u32) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|&b| b == 0)
+        .collect();
+    // A set high bit would make it negative
+    if !matches!(bytes.first(), Some(&b) if b & 0x80 == 0) {
+        bytes.insert(0, 0);
+    }
+    bytes
+}
+
+fn decode_integer(bytes: &[u8]) -> Option<u32> {
+    if bytes.is_empty() || bytes.len() > 5 {
+        return None;
+    }
+    Some(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64) as u32)
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut out = vec![(oid[0] * 40 + oid[1]) as u8];
+    for &arc in &oid[2..] {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        out.extend(chunk.into_iter().rev());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_get_request() {
+        let request = encode_get_request("public", SnmpVersion::V2c, 1, &[SYS_NAME]);
+        assert_eq!(
+            request,
+            vec![
+                0x30, 0x26, // message
+                0x02, 0x01, 0x01, // version 2c
+                0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', // community
+                0xa0, 0x19, // GetRequest
+                0x02, 0x01, 0x01, // request-id
+                0x02, 0x01, 0x00, 0x02, 0x01, 0x00, // error-status, error-index
+                0x30, 0x0e, 0x30, 0x0c, // varbinds
+                0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05, 0x00, // sysName.0
+                0x05, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_get_response() {
+        let varbind = |oid: &[u32], tag: u8, value: &[u8]| {
+            let mut content = tlv(OBJECT_IDENTIFIER, &encode_oid(oid));
+            content.extend(tlv(tag, value));
+            tlv(SEQUENCE, &content)
+        };
+        // Long enough to need a long-form length
+        let descr = format!("Cisco IOS Software, C2960 Software {}", "x".repeat(120));
+        let mut varbinds = varbind(SYS_DESCR, OCTET_STRING, descr.as_bytes());
+        varbinds.extend(varbind(SYS_NAME, OCTET_STRING, b"core-sw1"));
+
+        let mut pdu = tlv(INTEGER, &encode_integer(300));
+        pdu.extend(tlv(INTEGER, &[0]));
+        pdu.extend(tlv(INTEGER, &[0]));
+        pdu.extend(tlv(SEQUENCE, &varbinds));
+        let mut message = tlv(INTEGER, &[1]);
+        message.extend(tlv(OCTET_STRING, b"public"));
+        message.extend(tlv(GET_RESPONSE, &pdu));
+        let response = tlv(SEQUENCE, &message);
+
+        let info = decode_get_response(&response, 300).unwrap();
+        assert_eq!(info.sys_descr.as_deref(), Some(descr.as_str()));
+        assert_eq!(info.sys_name.as_deref(), Some("core-sw1"));
+
+        // Someone else's answer, or garbage
+        assert!(decode_get_response(&response, 301).is_none());
+        assert!(decode_get_response(&response[..20], 300).is_none());
+    }
+}
@@ -56,6 +56,7 @@ pub fn parse_inventory(content: &str) -> Result<Inventory, NexusError> {
         }
     }
 
+    inventory.validate_groups()?;
     Ok(inventory)
 }
 
@@ -1,6 +1,6 @@
 // Nexus CLI - Next-Generation Infrastructure Automation
 
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
@@ -11,14 +11,18 @@ use colored::*;
 use parking_lot::Mutex;
 
 use nexus::converter::{ConversionOptions, ConversionReport, Converter, IssueSeverity};
-use nexus::executor::{Scheduler, SchedulerConfig, TagFilter};
+use nexus::executor::{
+    FactCache, FactCategory, Forks, HostKeyChecking, Scheduler, SchedulerConfig, TagFilter,
+};
 use nexus::inventory::{
-    DiscoveredHost, DiscoveryDaemon, Host, HostGroup, Inventory, NetworkScanner, Notifier,
-    ProbeType,
+    discover_from_arp_cache, discover_mdns, merge_discovered, save_discovered, scan_state_path,
+    DiscoveredHost, DiscoveryDaemon, ExportFormat, Inventory, NetworkScanner, Notifier, ProbeType,
+    SnmpVersion,
 };
 use nexus::output::{NexusError, OutputFormat, OutputWriter};
-use nexus::parser::ast::{HostPattern, Playbook, TaskOrBlock, Value};
-use nexus::parser::{parse_playbook_file, parse_playbook_file_with_vault};
+use nexus::parser::ast::{HostPattern, Playbook, TaskOrBlock};
+use nexus::parser::{parse_extra_vars, parse_playbook_file, parse_playbook_file_with_vault};
+use nexus::vault::{parse_vault_id, VaultSecrets, DEFAULT_VAULT_ID};
 
 #[derive(Parser)]
 #[command(
@@ -33,21 +37,26 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Enable verbose output
-    #[arg(short, long, global = true)]
-    verbose: bool,
+    /// Enable verbose output (repeat for more, e.g. -vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
 
     /// Quiet mode - only show errors
     #[arg(short, long, global = true)]
     quiet: bool,
 
-    /// Output format (text or json)
+    /// Output format (text, json or junit)
     #[arg(long, global = true, default_value = "text")]
     output_format: String,
+
+    /// Where to write the JUnit XML report (implies --output-format junit)
+    #[arg(long, global = true)]
+    junit_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 #[command(disable_colored_help = true)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Run a playbook
     Run {
@@ -74,6 +83,10 @@ enum Commands {
         #[arg(short, long)]
         limit: Option<String>,
 
+        /// Set variables: key=value, @file.yml, or a JSON/YAML object (can repeat)
+        #[arg(short = 'e', long = "extra-vars")]
+        extra_vars: Vec<String>,
+
         /// Run in check mode (dry run)
         #[arg(short, long)]
         check: bool,
@@ -82,14 +95,35 @@ enum Commands {
         #[arg(short = 'D', long)]
         diff: bool,
 
-        /// Maximum parallel hosts
+        /// Maximum parallel hosts: a number, 0 for all hosts at once, or auto
+        /// to size it from the CPU count and open file limit
         #[arg(long, default_value = "10")]
-        forks: usize,
+        forks: Forks,
 
         /// SSH connection timeout in seconds
         #[arg(long, default_value = "30")]
         timeout: u64,
 
+        /// Seconds a command may run on a host before it fails as timed out
+        #[arg(long, value_name = "SECONDS", default_value = "300")]
+        command_timeout: u64,
+
+        /// Reuse one SSH session per host for all tasks (ControlMaster-style)
+        #[arg(long)]
+        ssh_multiplex: bool,
+
+        /// Close multiplexed SSH sessions after this many idle seconds
+        #[arg(long, default_value = "60")]
+        ssh_idle_timeout: u64,
+
+        /// Retry refused or timed-out SSH connections this many times, with backoff
+        #[arg(long, default_value_t = nexus::executor::DEFAULT_CONNECTION_RETRIES)]
+        connection_retries: u32,
+
+        /// Verify SSH host keys against ~/.ssh/known_hosts: strict, accept-new or off
+        #[arg(long, default_value = "accept-new")]
+        host_key_checking: HostKeyChecking,
+
         /// Path to SSH private key
         #[arg(long)]
         private_key: Option<PathBuf>,
@@ -110,6 +144,11 @@ enum Commands {
         #[arg(short = 's', long)]
         sudo: bool,
 
+        /// User to run sudo tasks as (overrides the playbook's sudo_user; a task's
+        /// run_as still wins)
+        #[arg(long, value_name = "USER")]
+        become_user: Option<String>,
+
         /// Prompt for sudo password
         #[arg(short = 'K', long)]
         ask_sudo_pass: bool,
@@ -122,6 +161,28 @@ enum Commands {
         #[arg(long)]
         skip_tags: Option<String>,
 
+        /// List the tasks that would run, with their tags, without running them
+        #[arg(long)]
+        list_tasks: bool,
+
+        /// List all tags used by the playbook without running it
+        #[arg(long)]
+        list_tags: bool,
+
+        /// List the hosts each play would run on, after its hosts pattern and --limit,
+        /// without connecting to them
+        #[arg(long)]
+        list_hosts: bool,
+
+        /// Parse the playbook and every role and task file it imports, reporting all
+        /// errors, without running it
+        #[arg(long)]
+        syntax_check: bool,
+
+        /// Skip all tasks before the first task with this name
+        #[arg(long, value_name = "TASK")]
+        start_at_task: Option<String>,
+
         /// Vault password for decrypting secrets
         #[arg(long)]
         vault_password: Option<String>,
@@ -130,6 +191,10 @@ enum Commands {
         #[arg(long)]
         vault_password_file: Option<PathBuf>,
 
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
         /// Prompt for vault password
         #[arg(long)]
         ask_vault_pass: bool,
@@ -138,6 +203,10 @@ enum Commands {
         #[arg(long = "callback")]
         callbacks: Vec<String>,
 
+        /// Also write each host's task results as JSON to <DIR>/<hostname>
+        #[arg(long, value_name = "DIR")]
+        tree: Option<PathBuf>,
+
         /// Enable checkpoints (save progress for resume)
         #[arg(long)]
         checkpoint: bool,
@@ -150,6 +219,30 @@ enum Commands {
         #[arg(long)]
         resume_from: Option<PathBuf>,
 
+        /// Cache gathered facts on disk and reuse them on later runs
+        #[arg(long)]
+        fact_caching: bool,
+
+        /// Fact cache directory (default: ~/.nexus/facts)
+        #[arg(long, requires = "fact_caching")]
+        fact_cache_dir: Option<PathBuf>,
+
+        /// Seconds cached facts stay valid
+        #[arg(long, default_value = "7200", requires = "fact_caching")]
+        fact_cache_ttl: u64,
+
+        /// Fact categories to gather, e.g. '!hardware,network' (a play's gather_subset wins)
+        #[arg(long, value_name = "SUBSET", value_delimiter = ',')]
+        gather_subset: Vec<String>,
+
+        /// Directories to search for custom modules, before the playbook's library/ (repeatable, or ':'-separated)
+        #[arg(long, value_name = "DIR", value_delimiter = ':')]
+        module_path: Vec<PathBuf>,
+
+        /// On a failed task, prompt to print variables (p), retry (r), continue (c) or quit (q)
+        #[arg(long)]
+        debug_on_failure: bool,
+
         /// Enable live TUI dashboard
         #[arg(long)]
         tui: bool,
@@ -159,6 +252,18 @@ enum Commands {
     Validate {
         /// Path to the playbook file
         playbook: PathBuf,
+
+        /// Also check roles, includes, handlers and variables, failing on any problem
+        #[arg(long)]
+        strict: bool,
+
+        /// Inventory whose host and group variables count as defined (with --strict)
+        #[arg(short, long)]
+        inventory: Option<PathBuf>,
+
+        /// Directories to search for custom modules, before the playbook's library/ (repeatable, or ':'-separated)
+        #[arg(long, value_name = "DIR", value_delimiter = ':')]
+        module_path: Vec<PathBuf>,
     },
 
     /// List hosts in inventory
@@ -174,6 +279,18 @@ enum Commands {
         /// Show host variables
         #[arg(long)]
         vars: bool,
+
+        /// Print the matching hosts as Ansible dynamic inventory JSON
+        #[arg(long)]
+        list: bool,
+
+        /// Print one host's variables as JSON
+        #[arg(long, value_name = "NAME", conflicts_with = "list")]
+        host: Option<String>,
+
+        /// Show the group tree under the pattern's group
+        #[arg(long, conflicts_with_all = ["list", "host"])]
+        graph: bool,
     },
 
     /// Parse and display a playbook
@@ -215,6 +332,10 @@ enum Commands {
         #[arg(short, long)]
         limit: Option<String>,
 
+        /// Set variables: key=value, @file.yml, or a JSON/YAML object (can repeat)
+        #[arg(short = 'e', long = "extra-vars")]
+        extra_vars: Vec<String>,
+
         /// SSH user
         #[arg(short, long)]
         user: Option<String>,
@@ -243,6 +364,65 @@ enum Commands {
         #[arg(short = 's', long)]
         sudo: bool,
 
+        /// User to run sudo tasks as (overrides the playbook's sudo_user; a task's
+        /// run_as still wins)
+        #[arg(long, value_name = "USER")]
+        become_user: Option<String>,
+
+        /// Vault password for decrypting secrets
+        #[arg(long)]
+        vault_password: Option<String>,
+
+        /// File containing vault password
+        #[arg(long)]
+        vault_password_file: Option<PathBuf>,
+
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
+        /// Prompt for vault password
+        #[arg(long)]
+        ask_vault_pass: bool,
+    },
+
+    /// Show the file and template changes a playbook would make, without changing anything
+    Diff {
+        /// Path to the playbook file
+        playbook: PathBuf,
+
+        /// Path to the inventory file
+        #[arg(short, long)]
+        inventory: Option<PathBuf>,
+
+        /// Comma-separated host list (alternative to inventory file)
+        #[arg(short = 'H', long)]
+        hosts: Option<String>,
+
+        /// Limit to specific hosts
+        #[arg(short, long)]
+        limit: Option<String>,
+
+        /// Set variables: key=value, @file.yml, or a JSON/YAML object (can repeat)
+        #[arg(short = 'e', long = "extra-vars")]
+        extra_vars: Vec<String>,
+
+        /// SSH user
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// SSH password
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Prompt for SSH password
+        #[arg(short = 'k', long)]
+        ask_pass: bool,
+
+        /// Path to SSH private key
+        #[arg(long)]
+        private_key: Option<PathBuf>,
+
         /// Vault password for decrypting secrets
         #[arg(long)]
         vault_password: Option<String>,
@@ -251,9 +431,17 @@ enum Commands {
         #[arg(long)]
         vault_password_file: Option<PathBuf>,
 
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
         /// Prompt for vault password
         #[arg(long)]
         ask_vault_pass: bool,
+
+        /// Exit with status 2 when changes are pending (0 when none, 1 on errors)
+        #[arg(long)]
+        detailed_exitcode: bool,
     },
 
     /// Discover hosts on a network
@@ -274,7 +462,11 @@ enum Commands {
         #[arg(long)]
         from_arp: bool,
 
-        /// Probe type: ssh, ping, or tcp:port1,port2
+        /// Browse the local link for mDNS/Bonjour services
+        #[arg(long)]
+        mdns: bool,
+
+        /// Probe type: ssh, ping, tcp:port1,port2, or snmp:community[:v1|v2c]
         #[arg(long, default_value = "ssh")]
         probe: String,
 
@@ -286,10 +478,18 @@ enum Commands {
         #[arg(long)]
         fingerprint: bool,
 
+        /// Continue an interrupted scan of the same subnets instead of starting over
+        #[arg(long)]
+        resume_scan: bool,
+
         /// Save discovered hosts to inventory file
         #[arg(long)]
         save_to: Option<PathBuf>,
 
+        /// Format for --save-to: ansible, json, or nexus
+        #[arg(long, default_value = "ansible")]
+        format: String,
+
         /// Filter expression (e.g., "port:22 AND os:linux")
         #[arg(long)]
         filter: Option<String>,
@@ -368,10 +568,6 @@ enum Commands {
         #[arg(short, long)]
         quiet: bool,
 
-        /// Detailed conversion log
-        #[arg(short, long)]
-        verbose: bool,
-
         /// Assessment mode - scan and report without converting
         #[arg(long)]
         assess: bool,
@@ -404,11 +600,19 @@ enum CheckpointAction {
 #[derive(Subcommand)]
 #[command(disable_colored_help = true)]
 enum VaultAction {
-    /// Encrypt a file
+    /// Encrypt a file, or with --recursive every vars file under a directory
     Encrypt {
-        /// File to encrypt
+        /// File to encrypt (a directory with --recursive)
         file: PathBuf,
 
+        /// Encrypt every YAML file under the directory, skipping encrypted ones
+        #[arg(short, long, conflicts_with = "output")]
+        recursive: bool,
+
+        /// With --recursive, encrypt files whose name matches this pattern (e.g. '*.env')
+        #[arg(long, requires = "recursive")]
+        pattern: Option<String>,
+
         /// Vault password
         #[arg(long)]
         vault_password: Option<String>,
@@ -417,16 +621,59 @@ enum VaultAction {
         #[arg(long)]
         vault_password_file: Option<PathBuf>,
 
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
+        /// Vault ID whose password encrypts the file (required with several --vault-id)
+        #[arg(long, value_name = "ID")]
+        encrypt_vault_id: Option<String>,
+
         /// Output file (default: overwrites input)
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
 
-    /// Decrypt a file
+    /// Encrypt a single value as an inline !vault string for a vars file
+    #[command(name = "encrypt_string", alias = "encrypt-string")]
+    EncryptString {
+        /// Value to encrypt (read from stdin when omitted)
+        value: Option<String>,
+
+        /// Variable name to emit the value under
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Vault password
+        #[arg(long)]
+        vault_password: Option<String>,
+
+        /// File containing vault password
+        #[arg(long)]
+        vault_password_file: Option<PathBuf>,
+
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
+        /// Vault ID whose password encrypts the value (required with several --vault-id)
+        #[arg(long, value_name = "ID")]
+        encrypt_vault_id: Option<String>,
+    },
+
+    /// Decrypt a file, or with --recursive every vault file under a directory
     Decrypt {
-        /// File to decrypt
+        /// File to decrypt (a directory with --recursive)
         file: PathBuf,
 
+        /// Decrypt every encrypted YAML file under the directory
+        #[arg(short, long, conflicts_with = "output")]
+        recursive: bool,
+
+        /// With --recursive, decrypt files whose name matches this pattern (e.g. '*.env')
+        #[arg(long, requires = "recursive")]
+        pattern: Option<String>,
+
         /// Vault password
         #[arg(long)]
         vault_password: Option<String>,
@@ -435,6 +682,10 @@ enum VaultAction {
         #[arg(long)]
         vault_password_file: Option<PathBuf>,
 
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
         /// Output file (default: overwrites input)
         #[arg(short, long)]
         output: Option<PathBuf>,
@@ -452,6 +703,37 @@ enum VaultAction {
         /// File containing vault password
         #[arg(long)]
         vault_password_file: Option<PathBuf>,
+
+        /// Vault ID and password source, e.g. prod@prod-pass.txt or dev@prompt (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+    },
+
+    /// Re-encrypt files with a new password
+    Rekey {
+        /// Files, directories or patterns (e.g. 'group_vars/*.yml') to rekey
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Current vault password
+        #[arg(long)]
+        vault_password: Option<String>,
+
+        /// File containing the current vault password
+        #[arg(long)]
+        vault_password_file: Option<PathBuf>,
+
+        /// Current vault ID and password source (repeatable)
+        #[arg(long = "vault-id", value_name = "ID@SOURCE")]
+        vault_ids: Vec<String>,
+
+        /// New vault password
+        #[arg(long)]
+        new_vault_password: Option<String>,
+
+        /// File containing the new vault password
+        #[arg(long)]
+        new_vault_password_file: Option<PathBuf>,
     },
 }
 
@@ -467,6 +749,11 @@ async fn main() {
         );
         OutputFormat::Text
     });
+    let output_format = if cli.junit_file.is_some() {
+        OutputFormat::JUnit
+    } else {
+        output_format
+    };
 
     let result = match cli.command {
         Commands::Run {
@@ -476,27 +763,51 @@ async fn main() {
             discover,
             discover_filter,
             limit,
+            extra_vars,
             check,
             diff,
             forks,
             timeout,
+            command_timeout,
+            ssh_multiplex,
+            ssh_idle_timeout,
+            connection_retries,
+            host_key_checking,
             private_key,
             user,
             password,
             ask_pass,
             sudo,
+            become_user,
             ask_sudo_pass,
             tags,
             skip_tags,
+            list_tasks,
+            list_tags,
+            list_hosts,
+            syntax_check,
+            start_at_task,
             vault_password,
             vault_password_file,
+            vault_ids,
             ask_vault_pass,
-            callbacks,
+            mut callbacks,
+            tree,
             checkpoint,
             resume,
             resume_from,
+            fact_caching,
+            fact_cache_dir,
+            fact_cache_ttl,
+            gather_subset,
+            module_path,
+            debug_on_failure,
             tui,
         } => {
+            // --tree is the tree callback under its Ansible name
+            if let Some(dir) = tree {
+                callbacks.push(format!("tree:{}", dir.display()));
+            }
             run_playbook(
                 playbook,
                 inventory,
@@ -504,38 +815,72 @@ async fn main() {
                 discover,
                 discover_filter,
                 limit,
+                extra_vars,
                 check,
                 diff,
                 forks,
                 timeout,
+                command_timeout,
+                ssh_multiplex,
+                ssh_idle_timeout,
+                connection_retries,
+                host_key_checking,
                 private_key,
                 user,
                 password,
                 ask_pass,
                 sudo,
+                become_user,
                 ask_sudo_pass,
                 tags,
                 skip_tags,
+                list_tasks,
+                list_tags,
+                list_hosts,
+                syntax_check,
+                start_at_task,
                 vault_password,
                 vault_password_file,
+                vault_ids,
                 ask_vault_pass,
                 callbacks,
                 checkpoint,
                 resume,
                 resume_from,
+                fact_caching.then(|| fact_cache_dir.unwrap_or_else(FactCache::default_cache_dir)),
+                fact_cache_ttl,
+                gather_subset,
+                module_path,
+                debug_on_failure,
                 tui,
                 cli.verbose,
                 cli.quiet,
                 output_format,
+                cli.junit_file,
             )
             .await
         }
-        Commands::Validate { playbook } => validate_playbook(playbook),
+        Commands::Validate {
+            playbook,
+            strict,
+            inventory,
+            module_path,
+        } => validate_playbook(playbook, strict, inventory, module_path),
         Commands::Inventory {
             inventory,
             pattern,
             vars,
-        } => list_inventory(inventory, &pattern, vars),
+            list,
+            host,
+            graph,
+        } => {
+            if graph {
+                graph_inventory(inventory, &pattern, vars)
+            } else {
+                let as_json = list || output_format == OutputFormat::Json;
+                list_inventory(inventory, &pattern, vars, as_json, host.as_deref())
+            }
+        }
         Commands::Parse { playbook, format } => parse_and_display(playbook, &format),
         Commands::Vault { action } => handle_vault_command(action),
         Commands::Checkpoint { action } => handle_checkpoint_command(action),
@@ -544,6 +889,7 @@ async fn main() {
             inventory,
             hosts,
             limit,
+            extra_vars,
             user,
             password,
             ask_pass,
@@ -551,8 +897,10 @@ async fn main() {
             diff,
             yes,
             sudo,
+            become_user,
             vault_password,
             vault_password_file,
+            vault_ids,
             ask_vault_pass,
         } => {
             handle_plan_command(
@@ -560,6 +908,7 @@ async fn main() {
                 inventory,
                 hosts,
                 limit,
+                extra_vars,
                 user,
                 password,
                 ask_pass,
@@ -567,10 +916,46 @@ async fn main() {
                 diff,
                 yes,
                 sudo,
+                become_user,
                 vault_password,
                 vault_password_file,
+                vault_ids,
                 ask_vault_pass,
-                cli.verbose,
+                cli.verbose > 0,
+            )
+            .await
+        }
+        Commands::Diff {
+            playbook,
+            inventory,
+            hosts,
+            limit,
+            extra_vars,
+            user,
+            password,
+            ask_pass,
+            private_key,
+            vault_password,
+            vault_password_file,
+            vault_ids,
+            ask_vault_pass,
+            detailed_exitcode,
+        } => {
+            handle_diff_command(
+                playbook,
+                inventory,
+                hosts,
+                limit,
+                extra_vars,
+                user,
+                password,
+                ask_pass,
+                private_key,
+                vault_password,
+                vault_password_file,
+                vault_ids,
+                ask_vault_pass,
+                detailed_exitcode,
             )
             .await
         }
@@ -579,10 +964,13 @@ async fn main() {
             subnets_from,
             passive,
             from_arp,
+            mdns,
             probe,
             profile,
             fingerprint,
+            resume_scan,
             save_to,
+            format,
             filter,
             via,
             timeout,
@@ -597,10 +985,13 @@ async fn main() {
                 subnets_from,
                 passive,
                 from_arp,
+                mdns,
                 probe,
                 profile,
                 fingerprint,
+                resume_scan,
                 save_to,
+                format,
                 filter,
                 via,
                 timeout,
@@ -624,7 +1015,6 @@ async fn main() {
             report,
             strict,
             quiet,
-            verbose,
             assess,
         } => handle_convert_command(
             source,
@@ -638,7 +1028,8 @@ async fn main() {
             report,
             strict,
             quiet,
-            verbose,
+            // Detailed conversion log
+            cli.verbose > 0,
             assess,
         ),
     };
@@ -658,6 +1049,9 @@ async fn main() {
 /// 4. Playbook-embedded hosts (HostPattern::Inline)
 /// 5. Implicit localhost (when playbook has hosts: localhost -> HostPattern::Localhost)
 /// 6. Error if none available
+///
+/// For a playbook with several plays, `playbook` is the first one; every play
+/// resolves its own host pattern against the inventory returned here.
 async fn resolve_inventory(
     inventory_path: Option<&Path>,
     cli_hosts: Option<&str>,
@@ -724,6 +1118,7 @@ async fn resolve_inventory(
              --hosts/-H for a comma-separated host list, or define hosts inline in your playbook"
                 .to_string(),
         ),
+        location: None,
     })
 }
 
@@ -784,31 +1179,52 @@ async fn run_playbook(
     cli_hosts: Option<String>,
     discover_subnet: Option<String>,
     discover_filter: Option<String>,
-    _limit: Option<String>,
+    limit: Option<String>,
+    extra_vars: Vec<String>,
     check: bool,
     diff: bool,
-    forks: usize,
+    forks: Forks,
     timeout: u64,
+    command_timeout: u64,
+    ssh_multiplex: bool,
+    ssh_idle_timeout: u64,
+    connection_retries: u32,
+    host_key_checking: HostKeyChecking,
     private_key: Option<PathBuf>,
     user: Option<String>,
     password: Option<String>,
     ask_pass: bool,
     sudo: bool,
+    become_user: Option<String>,
     ask_sudo_pass: bool,
     tags: Option<String>,
     skip_tags: Option<String>,
+    list_tasks: bool,
+    list_tags: bool,
+    list_hosts: bool,
+    syntax_check: bool,
+    start_at_task: Option<String>,
     vault_password: Option<String>,
     vault_password_file: Option<PathBuf>,
+    vault_ids: Vec<String>,
     ask_vault_pass: bool,
     callback_specs: Vec<String>,
     enable_checkpoints: bool,
     resume: bool,
     resume_from: Option<PathBuf>,
+    fact_cache_dir: Option<PathBuf>,
+    fact_cache_ttl: u64,
+    gather_subset: Vec<String>,
+    module_paths: Vec<PathBuf>,
+    debug_on_failure: bool,
     use_tui: bool,
-    verbose: bool,
+    verbosity: u8,
     quiet: bool,
     output_format: OutputFormat,
+    junit_file: Option<PathBuf>,
 ) -> Result<(), NexusError> {
+    let verbose = verbosity > 0;
+
     // Handle SSH password prompting
     let ssh_password = if ask_pass {
         Some(prompt_password("SSH Password: ")?)
@@ -823,20 +1239,42 @@ async fn run_playbook(
         None
     };
 
-    // Handle vault password
-    let vault_pass = get_vault_password(vault_password, vault_password_file, ask_vault_pass)?;
+    // Handle vault passwords
+    let vault_secrets = get_vault_secrets(
+        vault_password,
+        vault_password_file,
+        &vault_ids,
+        ask_vault_pass,
+    )?;
+
+    let extra_vars = parse_extra_vars(&extra_vars, Some(&vault_secrets))?;
+    nexus::parser::set_module_paths(module_paths.clone());
+
+    // Like listing, this only needs the playbook and the files it pulls in
+    if syntax_check {
+        return syntax_check_playbook(&playbook_path, &vault_secrets);
+    }
+
+    let listing = list_tasks || list_tags;
 
     // Print banner (skip in TUI mode - it has its own header)
-    if !quiet && !use_tui {
+    if !quiet && !use_tui && !listing && !list_hosts {
         print_banner();
     }
 
     // Parse playbook (with vault support)
-    let playbook = if let Some(ref password) = vault_pass {
-        parse_playbook_file_with_vault(&playbook_path, Some(password))?
-    } else {
-        parse_playbook_file(&playbook_path)?
-    };
+    let plays = parse_playbook_file_with_vault(&playbook_path, Some(&vault_secrets))?;
+
+    // Listing only needs the playbook: stop before resolving inventory or connecting
+    if listing {
+        let tag_filter = TagFilter::from_args(tags.as_deref(), skip_tags.as_deref());
+        let config = SchedulerConfig {
+            vault_secrets,
+            extra_vars,
+            ..SchedulerConfig::default()
+        };
+        return list_playbook(&plays, &playbook_path, config, &tag_filter, list_tags);
+    }
 
     // Resolve inventory from various sources
     let inventory = resolve_inventory(
@@ -844,18 +1282,37 @@ async fn run_playbook(
         cli_hosts.as_deref(),
         discover_subnet.as_deref(),
         discover_filter.as_deref(),
-        &playbook,
+        &plays[0],
         user.as_deref(),
     )
     .await?;
 
+    // Listing hosts needs the inventory, but stops before connecting
+    if list_hosts {
+        let config = SchedulerConfig {
+            limit,
+            ..SchedulerConfig::default()
+        };
+        return list_play_hosts(&plays, &playbook_path, &inventory, config);
+    }
+
     // Create output handler (silent when TUI is active to avoid conflicting output)
     let output = if use_tui {
         Arc::new(Mutex::new(OutputWriter::silent()))
+    } else if let Some(path) = junit_file {
+        Arc::new(Mutex::new(OutputWriter::junit(path, verbose, quiet)))
     } else {
         Arc::new(Mutex::new(OutputWriter::new(output_format, verbose, quiet)))
     };
 
+    // Catch a bad --gather-subset before connecting to anything
+    FactCategory::resolve_subset(&gather_subset).map_err(|e| NexusError::Runtime {
+        function: None,
+        message: format!("Invalid --gather-subset: {}", e),
+        suggestion: Some("Use category names such as network or !hardware".to_string()),
+        location: None,
+    })?;
+
     // Create tag filter if tags specified
     let tag_filter = if tags.is_some() || skip_tags.is_some() {
         Some(TagFilter::from_args(tags.as_deref(), skip_tags.as_deref()))
@@ -864,12 +1321,8 @@ async fn run_playbook(
     };
 
     // Print tag filter info if verbose (but not in TUI mode)
-    if verbose && !use_tui && tag_filter.is_some() {
-        println!(
-            "  {} {}",
-            "Tag filter:".cyan(),
-            tag_filter.as_ref().unwrap().describe()
-        );
+    if let Some(filter) = tag_filter.as_ref().filter(|_| verbose && !use_tui) {
+        println!("  {} {}", "Tag filter:".cyan(), filter.describe());
     }
 
     // Create callback manager and load plugins
@@ -900,24 +1353,60 @@ async fn run_playbook(
         }
     }
 
+    let max_parallel_hosts = forks.resolve();
+    if forks == Forks::Auto && verbose && !use_tui {
+        println!(
+            "  {} Using {} forks (auto, at most {})",
+            "✓".green(),
+            max_parallel_hosts,
+            nexus::executor::AUTO_FORKS_CEILING
+        );
+    }
+
+    // The debugger needs someone at the terminal, and text output around its prompt
+    let interactive = !use_tui
+        && output_format != OutputFormat::Json
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal();
+    if debug_on_failure && !interactive {
+        eprintln!(
+            "Warning: --debug-on-failure is ignored with --tui, JSON output or without a terminal"
+        );
+    }
+
     // Create scheduler config
     let config = SchedulerConfig {
-        max_parallel_hosts: forks,
+        max_parallel_hosts,
         max_parallel_tasks: 1,
         connect_timeout: Duration::from_secs(timeout),
-        command_timeout: Duration::from_secs(300),
+        command_timeout: Duration::from_secs(command_timeout),
         check_mode: check,
         diff_mode: diff,
         verbose,
+        verbosity,
         ssh_password,
         ssh_private_key: private_key.map(|p| p.to_string_lossy().to_string()),
         ssh_user: user,
         sudo,
+        sudo_user: become_user,
         sudo_password,
         tag_filter,
         enable_checkpoints,
         resume,
         resume_from,
+        ssh_multiplex,
+        ssh_idle_timeout: Duration::from_secs(ssh_idle_timeout),
+        connection_retries,
+        host_key_checking,
+        vault_secrets,
+        extra_vars,
+        start_at_task,
+        fact_cache_dir,
+        fact_cache_ttl: Duration::from_secs(fact_cache_ttl),
+        limit,
+        gather_subset,
+        module_paths: module_search_path(module_paths, &playbook_path),
+        debug_on_failure: debug_on_failure && interactive,
     };
 
     // Create scheduler with callbacks
@@ -942,16 +1431,30 @@ async fn run_playbook(
         });
 
         // Execute playbook (events will be sent to TUI)
-        let recap_result = scheduler.execute_playbook(&playbook, &inventory).await;
+        let recap_result = scheduler.execute_plays(&plays, &inventory).await;
 
         // Wait for TUI to finish (it will auto-exit after playbook complete event)
         let _ = tui_handle.await;
 
         recap_result?
     } else {
-        scheduler.execute_playbook(&playbook, &inventory).await?
+        scheduler.execute_plays(&plays, &inventory).await?
     };
 
+    // Record the hosts to re-target with `--limit @<file>`
+    let retry_hosts = recap.retry_hosts();
+    if !retry_hosts.is_empty() {
+        let retry_path = retry_file_path(&playbook_path);
+        match std::fs::write(&retry_path, format!("{}\n", retry_hosts.join("\n"))) {
+            Ok(()) => eprintln!("to retry, use: --limit @{}", retry_path.display()),
+            Err(e) => eprintln!(
+                "Warning: could not write retry file {}: {}",
+                retry_path.display(),
+                e
+            ),
+        }
+    }
+
     // Exit with error if there were failures
     if recap.has_failures() {
         std::process::exit(2);
@@ -960,38 +1463,126 @@ async fn run_playbook(
     Ok(())
 }
 
-fn validate_playbook(playbook_path: PathBuf) -> Result<(), NexusError> {
+/// `--module-path` directories, then the `library/` directory next to the playbook
+fn module_search_path(mut paths: Vec<PathBuf>, playbook_path: &Path) -> Vec<PathBuf> {
+    let library = playbook_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("library");
+    if library.is_dir() {
+        paths.push(library);
+    }
+    paths
+}
+
+/// `site.nx.yml` -> `site.retry`, next to the playbook
+fn retry_file_path(playbook_path: &std::path::Path) -> PathBuf {
+    let name = playbook_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = name
+        .strip_suffix(".yml")
+        .or_else(|| name.strip_suffix(".yaml"))
+        .unwrap_or(&name);
+    let stem = stem.strip_suffix(".nx").unwrap_or(stem);
+    playbook_path.with_file_name(format!("{}.retry", stem))
+}
+
+fn validate_playbook(
+    playbook_path: PathBuf,
+    strict: bool,
+    inventory_path: Option<PathBuf>,
+    module_paths: Vec<PathBuf>,
+) -> Result<(), NexusError> {
     println!("{} {}", "Validating:".cyan(), playbook_path.display());
 
-    let playbook = parse_playbook_file(&playbook_path)?;
+    nexus::parser::set_module_paths(module_paths);
+    let plays = parse_playbook_file(&playbook_path)?;
 
     println!("{} Playbook is valid", "✓".green());
+    for playbook in &plays {
+        println!();
+        if let Some(ref name) = playbook.name {
+            println!("  {} {}", "Play:".dimmed(), name);
+        }
+        println!("  {} {:?}", "Hosts:".dimmed(), playbook.hosts);
+        println!("  {} {}", "Tasks:".dimmed(), playbook.tasks.len());
+        println!("  {} {}", "Handlers:".dimmed(), playbook.handlers.len());
+        println!(
+            "  {} {}",
+            "Functions:".dimmed(),
+            playbook
+                .functions
+                .as_ref()
+                .map(|f| f.functions.len())
+                .unwrap_or(0)
+        );
+    }
+
+    if !strict {
+        return Ok(());
+    }
+
+    let mut known_vars = std::collections::HashSet::new();
+    if let Some(path) = inventory_path {
+        let inventory = Inventory::from_file(&path)?;
+        for host in inventory.hosts.values() {
+            known_vars.extend(inventory.get_host_vars(host).into_keys());
+        }
+    }
+
+    let issues: Vec<_> = plays
+        .iter()
+        .flat_map(|playbook| nexus::parser::validate_strict(playbook, &known_vars))
+        .collect();
     println!();
-    println!("  {} {:?}", "Hosts:".dimmed(), playbook.hosts);
-    println!("  {} {}", "Tasks:".dimmed(), playbook.tasks.len());
-    println!("  {} {}", "Handlers:".dimmed(), playbook.handlers.len());
-    println!(
-        "  {} {}",
-        "Functions:".dimmed(),
-        playbook
-            .functions
-            .as_ref()
-            .map(|f| f.functions.len())
-            .unwrap_or(0)
-    );
+    if issues.is_empty() {
+        println!("{} No problems found", "✓".green());
+        return Ok(());
+    }
 
-    Ok(())
+    println!("{} {} problem(s) found:", "✗".red(), issues.len());
+    for issue in &issues {
+        println!("  {}", issue);
+    }
+    Err(NexusError::Runtime {
+        function: None,
+        message: format!("Strict validation found {} problem(s)", issues.len()),
+        suggestion: None,
+        location: None,
+    })
 }
 
 fn list_inventory(
     inventory_path: PathBuf,
     pattern: &str,
     show_vars: bool,
+    as_json: bool,
+    host: Option<&str>,
 ) -> Result<(), NexusError> {
     let inventory = Inventory::from_file(&inventory_path)?;
 
+    if let Some(name) = host {
+        let vars = nexus::inventory::host_vars_json(&inventory, name).ok_or_else(|| {
+            NexusError::Inventory {
+                message: format!("Host '{}' not found in inventory", name),
+                suggestion: Some("Run 'nexus inventory -i <file>' to list hosts".to_string()),
+            }
+        })?;
+        println!("{}", to_pretty_json(&vars)?);
+        return Ok(());
+    }
+
     let pattern = nexus::inventory::parse_host_pattern(pattern);
-    let hosts = inventory.get_hosts(&pattern);
+    let mut hosts = inventory.get_hosts(&pattern);
+
+    if as_json {
+        hosts.sort_by(|a, b| a.name.cmp(&b.name));
+        let list = nexus::inventory::inventory_list_json(&inventory, &hosts);
+        println!("{}", to_pretty_json(&list)?);
+        return Ok(());
+    }
 
     println!(
         "{} {} host(s) matching '{:?}'",
@@ -1037,12 +1628,194 @@ fn list_inventory(
     Ok(())
 }
 
-fn parse_and_display(playbook_path: PathBuf, format: &str) -> Result<(), NexusError> {
-    let playbook = parse_playbook_file(&playbook_path)?;
-
+fn graph_inventory(
+    inventory_path: PathBuf,
+    group: &str,
+    show_vars: bool,
+) -> Result<(), NexusError> {
+    let inventory = Inventory::from_file(&inventory_path)?;
+    print!(
+        "{}",
+        nexus::inventory::inventory_graph(&inventory, group, show_vars)?
+    );
+    Ok(())
+}
+
+fn to_pretty_json(value: &serde_json::Value) -> Result<String, NexusError> {
+    serde_json::to_string_pretty(value).map_err(|e| NexusError::Runtime {
+        function: None,
+        message: format!("Failed to serialize inventory: {}", e),
+        suggestion: None,
+        location: None,
+    })
+}
+
+/// Print the tasks (or the set of tags) a run would cover, honouring --tags/--skip-tags
+fn syntax_check_playbook(
+    playbook_path: &Path,
+    vault_secrets: &VaultSecrets,
+) -> Result<(), NexusError> {
+    let report = nexus::parser::syntax_check(playbook_path, Some(vault_secrets));
+
+    println!();
+    println!("{} {}", "playbook:".bold(), playbook_path.display());
+    println!(
+        "  {} {} file(s), {} role(s)",
+        "Checked:".dimmed(),
+        report.files.len(),
+        report.roles.len()
+    );
+    println!();
+
+    if report.errors.is_empty() {
+        println!("{} Syntax OK", "✓".green());
+        return Ok(());
+    }
+
+    for error in &report.errors {
+        eprintln!("{}", error);
+    }
+    Err(NexusError::Runtime {
+        function: None,
+        message: format!("Syntax check found {} error(s)", report.errors.len()),
+        suggestion: None,
+        location: None,
+    })
+}
+
+/// Print the hosts each play would run on (`nexus run --list-hosts`)
+fn list_play_hosts(
+    plays: &[Playbook],
+    playbook_path: &Path,
+    inventory: &Inventory,
+    config: SchedulerConfig,
+) -> Result<(), NexusError> {
+    let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+
+    println!();
+    println!("{} {}", "playbook:".bold(), playbook_path.display());
+
+    for (n, playbook) in plays.iter().enumerate() {
+        // A pattern matching nothing (yet) may name a group `group_by` creates at run time
+        let mut hosts: Vec<&str> = if inventory.get_hosts(&playbook.hosts).is_empty() {
+            Vec::new()
+        } else {
+            scheduler
+                .play_hosts(playbook, inventory)?
+                .iter()
+                .map(|h| h.name.as_str())
+                .collect()
+        };
+        hosts.sort_unstable();
+
+        println!();
+        if plays.len() > 1 {
+            println!(
+                "  {} {}",
+                format!("play #{}:", n + 1).bold(),
+                playbook.name.as_deref().unwrap_or(&playbook.source_file)
+            );
+        }
+        println!(
+            "  {} {}",
+            "pattern:".cyan(),
+            host_pattern_label(&playbook.hosts)
+        );
+        println!("  {}", format!("hosts ({}):", hosts.len()).green());
+        for host in hosts {
+            println!("    {}", host);
+        }
+    }
+
+    Ok(())
+}
+
+/// A play's `hosts:` as written
+fn host_pattern_label(pattern: &HostPattern) -> String {
+    match pattern {
+        HostPattern::All => "all".to_string(),
+        HostPattern::Group(name) | HostPattern::Pattern(name) => name.clone(),
+        HostPattern::Localhost => "localhost".to_string(),
+        HostPattern::Inline(hosts) => format!("{} inline hosts", hosts.len()),
+    }
+}
+
+fn list_playbook(
+    plays: &[Playbook],
+    playbook_path: &Path,
+    config: SchedulerConfig,
+    tag_filter: &TagFilter,
+    tags_only: bool,
+) -> Result<(), NexusError> {
+    let scheduler = Scheduler::new(config, Arc::new(Mutex::new(OutputWriter::silent())));
+    scheduler.add_playbook_role_path(playbook_path);
+
+    println!();
+    println!("{} {}", "playbook:".bold(), playbook_path.display());
+
+    for (n, playbook) in plays.iter().enumerate() {
+        // Roles of an imported playbook can sit next to it
+        scheduler.add_playbook_role_path(Path::new(&playbook.source_file));
+        let tasks: Vec<_> = scheduler
+            .list_tasks(playbook)?
+            .into_iter()
+            .filter(|task| tag_filter.should_run(&task.tags))
+            .collect();
+
+        println!();
+        if plays.len() > 1 {
+            println!(
+                "  {} {}",
+                format!("play #{}:", n + 1).bold(),
+                playbook.name.as_deref().unwrap_or(&playbook.source_file)
+            );
+        }
+
+        if tags_only {
+            let tags: std::collections::BTreeSet<&str> = tasks
+                .iter()
+                .flat_map(|task| task.tags.iter().map(|t| t.as_str()))
+                .collect();
+            let tags: Vec<&str> = tags.into_iter().collect();
+            println!("  {} [{}]", "TASK TAGS:".cyan(), tags.join(", "));
+            continue;
+        }
+
+        let width = tasks.iter().map(|t| t.name.len()).max().unwrap_or(0);
+        let mut section = None;
+        for task in &tasks {
+            if section != Some(&task.section) {
+                println!("  {}:", task.section.green());
+                section = Some(&task.section);
+            }
+            println!(
+                "    {:width$}  {} [{}]",
+                task.name,
+                "TAGS:".dimmed(),
+                task.tags.join(", "),
+                width = width
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_and_display(playbook_path: PathBuf, format: &str) -> Result<(), NexusError> {
+    for playbook in parse_playbook_file(&playbook_path)? {
+        display_play(&playbook, format)?;
+    }
+    Ok(())
+}
+
+/// Print one play of a parsed playbook
+fn display_play(playbook: &Playbook, format: &str) -> Result<(), NexusError> {
     match format {
         "yaml" => {
             println!("{}:", "Playbook".green());
+            if let Some(ref name) = playbook.name {
+                println!("  {} {}", "name:".dimmed(), name);
+            }
             println!("  {} {:?}", "hosts:".dimmed(), playbook.hosts);
             println!();
 
@@ -1134,6 +1907,7 @@ fn parse_and_display(playbook_path: PathBuf, format: &str) -> Result<(), NexusEr
                 function: None,
                 message: format!("Unknown format: {}", format),
                 suggestion: Some("Use 'yaml' or 'json'".to_string()),
+                location: None,
             });
         }
     }
@@ -1172,6 +1946,7 @@ fn prompt_password(prompt: &str) -> Result<String, NexusError> {
         function: None,
         message: format!("Failed to read password: {}", e),
         suggestion: Some("Try using --password instead of --ask-pass".to_string()),
+        location: None,
     })?;
 
     // Trim any trailing whitespace/newlines
@@ -1185,33 +1960,54 @@ fn prompt_password(prompt: &str) -> Result<String, NexusError> {
             function: None,
             message: "Password cannot be empty".to_string(),
             suggestion: Some("Enter a password when prompted".to_string()),
+            location: None,
         });
     }
 
     Ok(password)
 }
 
-fn get_vault_password(
+/// Read a password from a file, ignoring surrounding whitespace
+fn read_password_file(file: &Path) -> Result<String, NexusError> {
+    Ok(std::fs::read_to_string(file)
+        .map_err(|e| NexusError::Io {
+            message: format!("Failed to read vault password file: {}", e),
+            path: Some(file.to_path_buf()),
+        })?
+        .trim()
+        .to_string())
+}
+
+/// Collect vault passwords: `--vault-password`/`--vault-password-file` under the default
+/// vault ID, plus one per `--vault-id ID@SOURCE` where the source is `prompt` or a file.
+/// With `ask_vault_pass` the default password is prompted for when nothing was given.
+fn get_vault_secrets(
     vault_password: Option<String>,
     vault_password_file: Option<PathBuf>,
+    vault_ids: &[String],
     ask_vault_pass: bool,
-) -> Result<Option<String>, NexusError> {
+) -> Result<VaultSecrets, NexusError> {
+    let mut secrets = VaultSecrets::new();
     if let Some(password) = vault_password {
-        Ok(Some(password))
+        secrets.add(DEFAULT_VAULT_ID, password);
     } else if let Some(file) = vault_password_file {
-        let password = std::fs::read_to_string(&file)
-            .map_err(|e| NexusError::Io {
-                message: format!("Failed to read vault password file: {}", e),
-                path: Some(file),
-            })?
-            .trim()
-            .to_string();
-        Ok(Some(password))
-    } else if ask_vault_pass {
-        Ok(Some(prompt_password("Vault Password: ")?))
-    } else {
-        Ok(None)
+        secrets.add(DEFAULT_VAULT_ID, read_password_file(&file)?);
     }
+
+    for spec in vault_ids {
+        let (id, source) = parse_vault_id(spec);
+        let password = if source == "prompt" {
+            prompt_password(&format!("Vault Password ({}): ", id))?
+        } else {
+            read_password_file(Path::new(source))?
+        };
+        secrets.add(id, password);
+    }
+
+    if secrets.is_empty() && ask_vault_pass {
+        secrets.add(DEFAULT_VAULT_ID, prompt_password("Vault Password: ")?);
+    }
+    Ok(secrets)
 }
 
 fn handle_vault_command(action: VaultAction) -> Result<(), NexusError> {
@@ -1220,25 +2016,52 @@ fn handle_vault_command(action: VaultAction) -> Result<(), NexusError> {
     match action {
         VaultAction::Encrypt {
             file,
+            recursive,
+            pattern,
             vault_password,
             vault_password_file,
+            vault_ids,
+            encrypt_vault_id,
             output,
         } => {
-            let password = get_vault_password(vault_password, vault_password_file, true)?
-                .ok_or_else(|| NexusError::Runtime {
+            let secrets = get_vault_secrets(vault_password, vault_password_file, &vault_ids, true)?;
+
+            if recursive {
+                let summary = vault::encrypt_tree(
+                    &file,
+                    &secrets,
+                    encrypt_vault_id.as_deref(),
+                    pattern.as_deref(),
+                )
+                .map_err(|e| NexusError::Runtime {
                     function: None,
-                    message: "Vault password required".to_string(),
-                    suggestion: Some("Use --vault-password or --vault-password-file".to_string()),
+                    message: format!("Encryption failed: {}", e),
+                    suggestion: None,
+                    location: None,
                 })?;
+                for path in &summary.processed {
+                    println!("{} {}", "Encrypted:".cyan(), path.display());
+                }
+                println!(
+                    "{} {} file(s) encrypted, {} already encrypted",
+                    "✓".green(),
+                    summary.processed.len(),
+                    summary.skipped.len()
+                );
+                return Ok(());
+            }
 
             println!("{} {}", "Encrypting:".cyan(), file.display());
 
             let output_path = output.as_ref().unwrap_or(&file);
 
-            vault::encrypt_file(&file, &password).map_err(|e| NexusError::Runtime {
-                function: None,
-                message: format!("Encryption failed: {}", e),
-                suggestion: None,
+            vault::encrypt_file(&file, &secrets, encrypt_vault_id.as_deref()).map_err(|e| {
+                NexusError::Runtime {
+                    function: None,
+                    message: format!("Encryption failed: {}", e),
+                    suggestion: None,
+                    location: None,
+                }
             })?;
 
             // If output path is different, move the encrypted file
@@ -1253,27 +2076,92 @@ fn handle_vault_command(action: VaultAction) -> Result<(), NexusError> {
             Ok(())
         }
 
+        VaultAction::EncryptString {
+            value,
+            name,
+            vault_password,
+            vault_password_file,
+            vault_ids,
+            encrypt_vault_id,
+        } => {
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    let mut value = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut value).map_err(
+                        |e| NexusError::Io {
+                            message: format!("Failed to read value from stdin: {}", e),
+                            path: None,
+                        },
+                    )?;
+                    value.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+
+            let secrets = get_vault_secrets(vault_password, vault_password_file, &vault_ids, true)?;
+
+            let block = vault::encrypt_inline(
+                &value,
+                &secrets,
+                encrypt_vault_id.as_deref(),
+                name.as_deref(),
+            )
+            .map_err(|e| NexusError::Runtime {
+                function: None,
+                message: format!("Encryption failed: {}", e),
+                suggestion: None,
+                location: None,
+            })?;
+
+            print!("{}", block);
+            Ok(())
+        }
+
         VaultAction::Decrypt {
             file,
+            recursive,
+            pattern,
             vault_password,
             vault_password_file,
+            vault_ids,
             output,
         } => {
-            let password = get_vault_password(vault_password, vault_password_file, true)?
-                .ok_or_else(|| NexusError::Runtime {
-                    function: None,
-                    message: "Vault password required".to_string(),
-                    suggestion: Some("Use --vault-password or --vault-password-file".to_string()),
-                })?;
+            let secrets = get_vault_secrets(vault_password, vault_password_file, &vault_ids, true)?;
+
+            if recursive {
+                let summary =
+                    vault::decrypt_tree(&file, &secrets, pattern.as_deref()).map_err(|e| {
+                        NexusError::Runtime {
+                            function: None,
+                            message: format!("Decryption failed: {}", e),
+                            suggestion: Some(
+                                "Check that the password is correct; no files were changed"
+                                    .to_string(),
+                            ),
+                            location: None,
+                        }
+                    })?;
+                for path in &summary.processed {
+                    println!("{} {}", "Decrypted:".cyan(), path.display());
+                }
+                println!(
+                    "{} {} file(s) decrypted, {} not encrypted",
+                    "✓".green(),
+                    summary.processed.len(),
+                    summary.skipped.len()
+                );
+                return Ok(());
+            }
 
             println!("{} {}", "Decrypting:".cyan(), file.display());
 
             let output_path = output.as_ref().unwrap_or(&file);
 
-            vault::decrypt_file(&file, &password).map_err(|e| NexusError::Runtime {
+            vault::decrypt_file(&file, &secrets).map_err(|e| NexusError::Runtime {
                 function: None,
                 message: format!("Decryption failed: {}", e),
                 suggestion: Some("Check that the password is correct".to_string()),
+                location: None,
             })?;
 
             // If output path is different, move the decrypted file
@@ -1292,23 +2180,70 @@ fn handle_vault_command(action: VaultAction) -> Result<(), NexusError> {
             file,
             vault_password,
             vault_password_file,
+            vault_ids,
         } => {
-            let password = get_vault_password(vault_password, vault_password_file, true)?
-                .ok_or_else(|| NexusError::Runtime {
-                    function: None,
-                    message: "Vault password required".to_string(),
-                    suggestion: Some("Use --vault-password or --vault-password-file".to_string()),
-                })?;
+            let secrets = get_vault_secrets(vault_password, vault_password_file, &vault_ids, true)?;
 
-            let content = vault::view_file(&file, &password).map_err(|e| NexusError::Runtime {
+            let content = vault::view_file(&file, &secrets).map_err(|e| NexusError::Runtime {
                 function: None,
                 message: format!("Failed to view file: {}", e),
                 suggestion: Some("Check that the password is correct".to_string()),
+                location: None,
             })?;
 
             println!("{}", content);
             Ok(())
         }
+
+        VaultAction::Rekey {
+            files,
+            vault_password,
+            vault_password_file,
+            vault_ids,
+            new_vault_password,
+            new_vault_password_file,
+        } => {
+            let files = vault::find_vault_files(&files).map_err(|e| NexusError::Runtime {
+                function: None,
+                message: format!("Failed to find vault files: {}", e),
+                suggestion: None,
+                location: None,
+            })?;
+            if files.is_empty() {
+                return Err(NexusError::Runtime {
+                    function: None,
+                    message: "No vault-encrypted files found".to_string(),
+                    suggestion: None,
+                    location: None,
+                });
+            }
+
+            let secrets = get_vault_secrets(vault_password, vault_password_file, &vault_ids, true)?;
+            let new_password = match (new_vault_password, new_vault_password_file) {
+                (Some(password), _) => password,
+                (None, Some(file)) => read_password_file(&file)?,
+                (None, None) => prompt_password("New Vault Password: ")?,
+            };
+
+            vault::rekey_files(&files, &secrets, &new_password).map_err(|e| {
+                NexusError::Runtime {
+                    function: None,
+                    message: format!("Rekey failed: {}", e),
+                    suggestion: Some(
+                        "Check the current password and that every file is vault-encrypted"
+                            .to_string(),
+                    ),
+                    location: None,
+                }
+            })?;
+
+            println!(
+                "{} {} file(s) rekeyed successfully",
+                "✓".green(),
+                files.len()
+            );
+            Ok(())
+        }
     }
 }
 
@@ -1433,6 +2368,7 @@ fn handle_checkpoint_command(action: CheckpointAction) -> Result<(), NexusError>
                     function: None,
                     message: "Must specify either --playbook or --older-than".to_string(),
                     suggestion: Some("Use 'nexus checkpoint clean --older-than 7' or 'nexus checkpoint clean playbook.yml'".to_string()),
+                    location: None,
                 });
             }
 
@@ -1447,6 +2383,7 @@ async fn handle_plan_command(
     inventory_path: Option<PathBuf>,
     cli_hosts: Option<String>,
     limit: Option<String>,
+    extra_vars: Vec<String>,
     user: Option<String>,
     password: Option<String>,
     ask_pass: bool,
@@ -1454,8 +2391,10 @@ async fn handle_plan_command(
     show_diff: bool,
     auto_approve: bool,
     sudo: bool,
+    become_user: Option<String>,
     vault_password: Option<String>,
     vault_password_file: Option<PathBuf>,
+    vault_ids: Vec<String>,
     ask_vault_pass: bool,
     verbose: bool,
 ) -> Result<(), NexusError> {
@@ -1469,18 +2408,25 @@ async fn handle_plan_command(
         password
     };
 
-    // Handle vault password
-    let vault_pass = get_vault_password(vault_password, vault_password_file, ask_vault_pass)?;
+    // Handle vault passwords
+    let vault_secrets = get_vault_secrets(
+        vault_password,
+        vault_password_file,
+        &vault_ids,
+        ask_vault_pass,
+    )?;
 
     // Print banner
     print_banner();
 
     // Parse playbook (with vault support)
-    let playbook = if let Some(ref password) = vault_pass {
-        parse_playbook_file_with_vault(&playbook_path, Some(password))?
-    } else {
-        parse_playbook_file(&playbook_path)?
-    };
+    let mut plays = parse_playbook_file_with_vault(&playbook_path, Some(&vault_secrets))?;
+
+    // Extra vars take precedence over playbook vars when planning too
+    let extra_vars = parse_extra_vars(&extra_vars, Some(&vault_secrets))?;
+    for playbook in &mut plays {
+        playbook.vars.extend(extra_vars.clone());
+    }
 
     // Resolve inventory from various sources
     let inventory = resolve_inventory(
@@ -1488,7 +2434,7 @@ async fn handle_plan_command(
         cli_hosts.as_deref(),
         None, // discover_subnet not supported in plan command
         None, // discover_filter not supported in plan command
-        &playbook,
+        &plays[0],
         user.as_deref(),
     )
     .await?;
@@ -1508,20 +2454,21 @@ async fn handle_plan_command(
             .map(|p| p.to_string_lossy().to_string()),
     };
 
-    // Generate plan
+    // Generate and display a plan per play
     let generator = PlanGenerator::new();
-    let plan = generator
-        .generate_plan(&playbook, &inventory, ssh_config, limit.as_deref())
-        .await?;
-
-    // Display the plan
-    display_plan(&plan, show_diff);
+    for playbook in &plays {
+        let plan = generator
+            .generate_plan(playbook, &inventory, ssh_config.clone(), limit.as_deref())
+            .await?;
+        display_plan(&plan, show_diff);
+    }
 
     // Prompt for confirmation
     let proceed = prompt_confirmation(auto_approve).map_err(|e| NexusError::Runtime {
         function: None,
         message: format!("Failed to read confirmation: {}", e),
         suggestion: None,
+        location: None,
     })?;
 
     if !proceed {
@@ -1549,21 +2496,36 @@ async fn handle_plan_command(
         check_mode: false,
         diff_mode: show_diff,
         verbose,
+        verbosity: u8::from(verbose),
         ssh_password,
         ssh_private_key: private_key.map(|p| p.to_string_lossy().to_string()),
         ssh_user,
         sudo,
+        sudo_user: become_user,
         sudo_password: None,
         tag_filter: None,
         enable_checkpoints: false,
         resume: false,
         resume_from: None,
+        ssh_multiplex: false,
+        ssh_idle_timeout: nexus::executor::DEFAULT_MULTIPLEX_IDLE_TIMEOUT,
+        connection_retries: nexus::executor::DEFAULT_CONNECTION_RETRIES,
+        host_key_checking: HostKeyChecking::default(),
+        vault_secrets,
+        extra_vars,
+        start_at_task: None,
+        fact_cache_dir: None,
+        fact_cache_ttl: nexus::executor::DEFAULT_FACT_CACHE_TTL,
+        limit,
+        gather_subset: Vec::new(),
+        module_paths: module_search_path(Vec::new(), &playbook_path),
+        debug_on_failure: false,
     };
 
     let scheduler = Scheduler::new(config, output.clone());
     scheduler.add_playbook_role_path(&playbook_path);
 
-    let recap = scheduler.execute_playbook(&playbook, &inventory).await?;
+    let recap = scheduler.execute_plays(&plays, &inventory).await?;
 
     // Exit with error if there were failures
     if recap.has_failures() {
@@ -1573,16 +2535,89 @@ async fn handle_plan_command(
     Ok(())
 }
 
+/// `nexus diff`: generate the plan and print only what would change, never executing it
+#[allow(clippy::too_many_arguments)]
+async fn handle_diff_command(
+    playbook_path: PathBuf,
+    inventory_path: Option<PathBuf>,
+    cli_hosts: Option<String>,
+    limit: Option<String>,
+    extra_vars: Vec<String>,
+    user: Option<String>,
+    password: Option<String>,
+    ask_pass: bool,
+    private_key: Option<PathBuf>,
+    vault_password: Option<String>,
+    vault_password_file: Option<PathBuf>,
+    vault_ids: Vec<String>,
+    ask_vault_pass: bool,
+    detailed_exitcode: bool,
+) -> Result<(), NexusError> {
+    use nexus::executor::{PlanGenerator, SshConfig};
+    use nexus::output::plan::display_diff;
+
+    let ssh_password = if ask_pass {
+        Some(prompt_password("SSH Password: ")?)
+    } else {
+        password
+    };
+    let vault_secrets = get_vault_secrets(
+        vault_password,
+        vault_password_file,
+        &vault_ids,
+        ask_vault_pass,
+    )?;
+
+    let mut plays = parse_playbook_file_with_vault(&playbook_path, Some(&vault_secrets))?;
+    let extra_vars = parse_extra_vars(&extra_vars, Some(&vault_secrets))?;
+    for playbook in &mut plays {
+        playbook.vars.extend(extra_vars.clone());
+    }
+
+    let inventory = resolve_inventory(
+        inventory_path.as_deref(),
+        cli_hosts.as_deref(),
+        None,
+        None,
+        &plays[0],
+        user.as_deref(),
+    )
+    .await?;
+
+    let ssh_config = SshConfig {
+        user,
+        password: ssh_password,
+        private_key: private_key.map(|p| p.to_string_lossy().to_string()),
+    };
+    let generator = PlanGenerator::new();
+    let mut has_changes = false;
+    for playbook in &plays {
+        let plan = generator
+            .generate_plan(playbook, &inventory, ssh_config.clone(), limit.as_deref())
+            .await?;
+        display_diff(&plan);
+        has_changes |= plan.has_changes();
+    }
+
+    if detailed_exitcode && has_changes {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn handle_discover_command(
     subnet: Option<String>,
     subnets_from: Option<PathBuf>,
-    _passive: bool,
-    _from_arp: bool,
+    passive: bool,
+    from_arp: bool,
+    mdns: bool,
     probe: String,
     _profile: Option<PathBuf>,
     fingerprint: bool,
+    resume_scan: bool,
     save_to: Option<PathBuf>,
+    format: String,
     filter: Option<String>,
     _via: Option<String>,
     timeout: u64,
@@ -1592,12 +2627,25 @@ async fn handle_discover_command(
     interval: String,
     notify_on_change: Option<String>,
 ) -> Result<(), NexusError> {
-    // Validate inputs - requires either --subnet or --subnets-from or daemon mode
-    if subnet.is_none() && subnets_from.is_none() && !daemon {
+    let export_format: ExportFormat = format.parse().map_err(|_| NexusError::Runtime {
+        function: None,
+        message: format!("Unknown inventory format: {}", format),
+        suggestion: Some("Use 'ansible', 'json', or 'nexus'".to_string()),
+        location: None,
+    })?;
+
+    // The ARP cache is the only passive source, so either flag selects it
+    let passive = passive || from_arp;
+
+    // Validate inputs - requires either --subnet or --subnets-from or daemon mode;
+    // passive discovery without a subnet lists the whole ARP cache, and mDNS is
+    // link-local so it has no subnet
+    if subnet.is_none() && subnets_from.is_none() && !daemon && !passive && !mdns {
         return Err(NexusError::Runtime {
             function: None,
             message: "No subnet specified".to_string(),
             suggestion: Some("Use --subnet, --subnets-from, or --daemon with --watch".to_string()),
+            location: None,
         });
     }
 
@@ -1638,6 +2686,7 @@ async fn handle_discover_command(
                 function: None,
                 message: "No subnets to watch in daemon mode".to_string(),
                 suggestion: Some("Use --watch or --subnet to specify subnets".to_string()),
+                location: None,
             });
         }
 
@@ -1657,31 +2706,80 @@ async fn handle_discover_command(
     }
 
     // Normal discovery mode (non-daemon)
-    println!("{}", "Starting network discovery...".cyan());
-
-    // Parse probe type
-    let probe_type = parse_probe_type(&probe)?;
-
-    // Create scanner
-    let scanner = NetworkScanner {
-        timeout: Duration::from_millis(timeout),
-        concurrent_probes: parallel,
-        fingerprint,
-        probe_type,
-    };
-
-    // Scan subnets
     let mut all_hosts = Vec::new();
 
-    for subnet_str in &subnets {
-        println!("  {} Scanning {}...", "→".cyan(), subnet_str);
+    if mdns {
+        println!("{}", "Starting mDNS discovery...".cyan());
 
-        let hosts = scanner.scan_subnet(subnet_str).await?;
+        let hosts = discover_mdns(Duration::from_millis(timeout)).await?;
 
         println!("    {} Found {} host(s)", "✓".green(), hosts.len());
         all_hosts.extend(hosts);
     }
 
+    if passive {
+        println!("{}", "Starting passive discovery (ARP cache)...".cyan());
+
+        if subnets.is_empty() {
+            println!("  {} Reading all entries...", "→".cyan());
+            let hosts = discover_from_arp_cache(None)?;
+            println!("    {} Found {} host(s)", "✓".green(), hosts.len());
+            all_hosts.extend(hosts);
+        }
+        for subnet_str in &subnets {
+            println!("  {} Reading {}...", "→".cyan(), subnet_str);
+
+            let hosts = discover_from_arp_cache(Some(subnet_str))?;
+
+            println!("    {} Found {} host(s)", "✓".green(), hosts.len());
+            all_hosts.extend(hosts);
+        }
+    } else if !subnets.is_empty() {
+        println!("{}", "Starting network discovery...".cyan());
+
+        // Parse probe type
+        let probe_type = parse_probe_type(&probe)?;
+
+        // Progress is saved as the scan runs so an interrupted scan can be resumed
+        let state_file = scan_state_path(&subnets);
+        if state_file.exists() {
+            if resume_scan {
+                println!("  {} Resuming the interrupted scan", "→".cyan());
+            } else {
+                println!(
+                    "  {} Starting over; use --resume-scan to continue the interrupted scan",
+                    "!".yellow()
+                );
+                let _ = std::fs::remove_file(&state_file);
+            }
+        }
+
+        // Create scanner
+        let scanner = NetworkScanner {
+            timeout: Duration::from_millis(timeout),
+            concurrent_probes: parallel,
+            fingerprint,
+            probe_type,
+            scan_state_file: Some(state_file.clone()),
+        };
+
+        // Scan subnets
+        for subnet_str in &subnets {
+            println!("  {} Scanning {}...", "→".cyan(), subnet_str);
+
+            let hosts = scanner.scan_subnet(subnet_str).await?;
+
+            println!("    {} Found {} host(s)", "✓".green(), hosts.len());
+            all_hosts.extend(hosts);
+        }
+
+        // Finished, so there's nothing left to resume
+        let _ = std::fs::remove_file(&state_file);
+    }
+
+    // A host can turn up both over mDNS and in a scan
+    let all_hosts = merge_discovered(all_hosts);
+
     // Apply filter if specified
     let filtered_hosts = if let Some(filter_expr) = filter {
         apply_filter(&all_hosts, &filter_expr)?
@@ -1709,6 +2807,10 @@ async fn handle_discover_command(
             println!("    {} {}", "Hostname:".dimmed(), hostname);
         }
 
+        if let Some(mac) = &host.mac {
+            println!("    {} {}", "MAC:".dimmed(), mac);
+        }
+
         if !host.open_ports.is_empty() {
             let ports: Vec<String> = host
                 .open_ports
@@ -1742,6 +2844,19 @@ async fn handle_discover_command(
             println!("    {} {}", "OS:".dimmed(), os_str);
         }
 
+        if !host.mdns_services.is_empty() {
+            let services: Vec<String> = host
+                .mdns_services
+                .iter()
+                .map(|s| format!("{} ({}, port {})", s.service_type, s.instance, s.port))
+                .collect();
+            println!("    {} {}", "Services:".dimmed(), services.join(", "));
+        }
+
+        if let Some(descr) = host.snmp.as_ref().and_then(|s| s.sys_descr.as_ref()) {
+            println!("    {} {}", "SNMP:".dimmed(), descr);
+        }
+
         if host.open_ports.iter().any(|p| p.port == 22) {
             println!("    {} {}", "SSH:".dimmed(), "accessible".green());
         }
@@ -1753,8 +2868,7 @@ async fn handle_discover_command(
     if let Some(output_path) = save_to {
         println!("{} Saving to inventory file...", "→".cyan());
 
-        let inventory = convert_to_inventory(&filtered_hosts);
-        save_inventory_to_file(&inventory, &output_path)?;
+        save_discovered(&filtered_hosts, &output_path, export_format)?;
 
         println!("  {} Saved to {}", "✓".green(), output_path.display());
     }
@@ -1771,6 +2885,7 @@ fn parse_interval(interval: &str) -> Result<Duration, NexusError> {
             function: None,
             message: "Empty interval".to_string(),
             suggestion: Some("Use format like '5m', '1h', or '30s'".to_string()),
+            location: None,
         });
     }
 
@@ -1784,6 +2899,7 @@ fn parse_interval(interval: &str) -> Result<Duration, NexusError> {
         function: None,
         message: format!("Invalid interval number: {}", num_str),
         suggestion: Some("Use a positive integer".to_string()),
+        location: None,
     })?;
 
     let multiplier = match unit.trim() {
@@ -1796,6 +2912,7 @@ fn parse_interval(interval: &str) -> Result<Duration, NexusError> {
                 function: None,
                 message: format!("Unknown time unit: {}", unit),
                 suggestion: Some("Use s, m, h, or d".to_string()),
+                location: None,
             });
         }
     };
@@ -1820,13 +2937,20 @@ fn parse_notifier(spec: &str) -> Result<Notifier, NexusError> {
             function: None,
             message: format!("Invalid notifier specification: {}", spec),
             suggestion: Some("Use webhook:URL, file:PATH, or stdout".to_string()),
+            location: None,
         })
     }
 }
 
-/// Parse probe type specification (ssh, ping, or tcp:port1,port2)
+/// Parse probe type specification (ssh, ping, tcp:port1,port2, or snmp:community[:version])
 fn parse_probe_type(probe: &str) -> Result<ProbeType, NexusError> {
-    let probe = probe.trim().to_lowercase();
+    let probe = probe.trim();
+    let lower = probe.to_lowercase();
+    if lower == "snmp" || lower.starts_with("snmp:") {
+        // The community is case-sensitive
+        return parse_snmp_probe(probe.get(5..).unwrap_or(""));
+    }
+    let probe = lower;
 
     match probe.as_str() {
         "ssh" => Ok(ProbeType::Ssh),
@@ -1844,22 +2968,56 @@ fn parse_probe_type(probe: &str) -> Result<ProbeType, NexusError> {
                     function: None,
                     message: "No ports specified".to_string(),
                     suggestion: Some("Use format like 'tcp:22,80,443'".to_string()),
+                    location: None,
                 }),
                 Err(_) => Err(NexusError::Runtime {
                     function: None,
                     message: format!("Invalid port in probe specification: {}", ports_str),
                     suggestion: Some("Ports must be numbers between 1 and 65535".to_string()),
+                    location: None,
                 }),
             }
         }
         _ => Err(NexusError::Runtime {
             function: None,
             message: format!("Unknown probe type: {}", probe),
-            suggestion: Some("Use 'ssh', 'ping', or 'tcp:port1,port2'".to_string()),
+            suggestion: Some(
+                "Use 'ssh', 'ping', 'tcp:port1,port2', or 'snmp:community'".to_string(),
+            ),
+            location: None,
         }),
     }
 }
 
+/// Parse the part after `snmp:`, a community with an optional `:v1` or `:v2c`
+fn parse_snmp_probe(spec: &str) -> Result<ProbeType, NexusError> {
+    let (community, version) = match spec.rsplit_once(':') {
+        Some((community, version)) => (community, Some(version)),
+        None => (spec, None),
+    };
+    let version = match version.map(|v| v.to_lowercase()).as_deref() {
+        None | Some("v2c") | Some("2c") | Some("2") => SnmpVersion::V2c,
+        Some("v1") | Some("1") => SnmpVersion::V1,
+        Some(other) => {
+            return Err(NexusError::Runtime {
+                function: None,
+                message: format!("Unsupported SNMP version: {}", other),
+                suggestion: Some("Use 'snmp:public:v1' or 'snmp:public:v2c'".to_string()),
+                location: None,
+            })
+        }
+    };
+
+    Ok(ProbeType::Snmp {
+        community: if community.is_empty() {
+            "public".to_string()
+        } else {
+            community.to_string()
+        },
+        version,
+    })
+}
+
 /// Apply filter expression to hosts
 fn apply_filter(
     hosts: &[DiscoveredHost],
@@ -1879,7 +3037,7 @@ fn apply_filter(
 /// Check if host matches filter expression
 fn matches_filter(host: &DiscoveredHost, filter_expr: &str) -> bool {
     // Simple filter implementation
-    // Supports: "port:22", "os:linux", "ssh:true", "port:22 AND os:linux"
+    // Supports: "port:22", "os:linux", "ssh:true", "service:ipp", "port:22 AND os:linux"
 
     for condition in filter_expr.split("AND") {
         let condition = condition.trim();
@@ -1905,6 +3063,24 @@ fn matches_filter(host: &DiscoveredHost, filter_expr: &str) -> bool {
             } else {
                 return false;
             }
+        } else if let Some(service) = condition.strip_prefix("service:") {
+            // Open port services, or mDNS types with or without the underscores
+            // and protocol: "service:ipp" matches "_ipp._tcp"
+            let service = service.trim().to_lowercase();
+            let short = |t: &str| {
+                let name = t.split('.').next().unwrap_or(t);
+                name.trim_start_matches('_').to_lowercase()
+            };
+            let on_port = host
+                .open_ports
+                .iter()
+                .any(|p| p.service.as_deref() == Some(service.as_str()));
+            let advertised = host.mdns_services.iter().any(|s| {
+                s.service_type.to_lowercase() == service || short(&s.service_type) == service
+            });
+            if !on_port && !advertised {
+                return false;
+            }
         } else if condition.starts_with("ssh:") {
             let ssh_str = condition.strip_prefix("ssh:").unwrap().trim();
             let ssh_required = ssh_str == "true" || ssh_str == "yes";
@@ -1917,160 +3093,6 @@ fn matches_filter(host: &DiscoveredHost, filter_expr: &str) -> bool {
     true
 }
 
-/// Convert discovered hosts to inventory
-fn convert_to_inventory(hosts: &[DiscoveredHost]) -> Inventory {
-    let mut inventory = Inventory::new();
-
-    for discovered in hosts {
-        let name = discovered
-            .hostname
-            .clone()
-            .unwrap_or_else(|| discovered.address.to_string());
-
-        let mut host = Host::new(name.clone()).with_address(discovered.address.to_string());
-
-        // Set SSH port if available
-        if let Some(ssh_port) = discovered.open_ports.iter().find(|p| p.port == 22) {
-            host.port = ssh_port.port;
-        }
-
-        // Add discovered metadata as variables
-        if let Some(ref os) = discovered.os_classification {
-            host.vars.insert(
-                "discovered_os_family".to_string(),
-                Value::String(os.os_family.clone()),
-            );
-            if let Some(ref dist) = os.distribution {
-                host.vars.insert(
-                    "discovered_os_dist".to_string(),
-                    Value::String(dist.clone()),
-                );
-            }
-            host.vars.insert(
-                "discovered_os_confidence".to_string(),
-                Value::String(format!("{:.2}", os.confidence)),
-            );
-        }
-
-        if !discovered.open_ports.is_empty() {
-            let ports_str = discovered
-                .open_ports
-                .iter()
-                .map(|p| p.port.to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            host.vars.insert(
-                "discovered_open_ports".to_string(),
-                Value::String(ports_str),
-            );
-        }
-
-        host.groups.push("discovered".to_string());
-
-        inventory.add_host(host);
-    }
-
-    // Create "discovered" group
-    let discovered_group = HostGroup {
-        name: "discovered".to_string(),
-        hosts: hosts
-            .iter()
-            .map(|h| h.hostname.clone().unwrap_or_else(|| h.address.to_string()))
-            .collect(),
-        children: Vec::new(),
-        vars: std::collections::HashMap::new(),
-    };
-    inventory.add_group(discovered_group);
-
-    inventory
-}
-
-/// Save inventory to YAML file
-fn save_inventory_to_file(inventory: &Inventory, path: &Path) -> Result<(), NexusError> {
-    use std::collections::HashMap;
-
-    let mut yaml_map: HashMap<String, serde_yaml::Value> = HashMap::new();
-
-    // Add hosts to "all" group
-    let mut all_hosts = HashMap::new();
-    for host in inventory.hosts.values() {
-        let mut host_map = HashMap::new();
-        host_map.insert(
-            "ansible_host".to_string(),
-            serde_yaml::Value::String(host.address.clone()),
-        );
-        host_map.insert(
-            "ansible_port".to_string(),
-            serde_yaml::Value::Number(host.port.into()),
-        );
-
-        if !host.user.is_empty() {
-            host_map.insert(
-                "ansible_user".to_string(),
-                serde_yaml::Value::String(host.user.clone()),
-            );
-        }
-
-        // Add custom vars
-        for (key, val) in &host.vars {
-            let yaml_val = match val {
-                Value::String(s) => serde_yaml::Value::String(s.clone()),
-                Value::Int(n) => serde_yaml::Value::Number((*n).into()),
-                Value::Float(f) => serde_yaml::Value::String(f.to_string()),
-                Value::Bool(b) => serde_yaml::Value::Bool(*b),
-                Value::List(l) => serde_yaml::Value::String(format!("{:?}", l)),
-                Value::Dict(_) | Value::Null => serde_yaml::Value::String(format!("{:?}", val)),
-            };
-            host_map.insert(key.clone(), yaml_val);
-        }
-
-        all_hosts.insert(
-            host.name.clone(),
-            serde_yaml::Value::Mapping(
-                host_map
-                    .into_iter()
-                    .map(|(k, v)| (serde_yaml::Value::String(k), v))
-                    .collect(),
-            ),
-        );
-    }
-
-    let mut all_group = HashMap::new();
-    all_group.insert(
-        "hosts".to_string(),
-        serde_yaml::Value::Mapping(
-            all_hosts
-                .into_iter()
-                .map(|(k, v)| (serde_yaml::Value::String(k), v))
-                .collect(),
-        ),
-    );
-
-    yaml_map.insert(
-        "all".to_string(),
-        serde_yaml::Value::Mapping(
-            all_group
-                .into_iter()
-                .map(|(k, v)| (serde_yaml::Value::String(k), v))
-                .collect(),
-        ),
-    );
-
-    // Write to file
-    let yaml_string = serde_yaml::to_string(&yaml_map).map_err(|e| NexusError::Runtime {
-        function: None,
-        message: format!("Failed to serialize inventory: {}", e),
-        suggestion: None,
-    })?;
-
-    std::fs::write(path, yaml_string).map_err(|e| NexusError::Io {
-        message: format!("Failed to write inventory file: {}", e),
-        path: Some(path.to_path_buf()),
-    })?;
-
-    Ok(())
-}
-
 #[allow(clippy::too_many_arguments)]
 fn handle_convert_command(
     source: PathBuf,
@@ -2102,6 +3124,7 @@ fn handle_convert_command(
             suggestion: Some(
                 "Verify the path to the Ansible playbook or project directory".to_string(),
             ),
+            location: None,
         });
     }
 
@@ -0,0 +1,119 @@
+// Assert module - fail the task unless every condition holds
+
+use async_trait::async_trait;
+
+use super::Module;
+use crate::executor::{ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{Assertion, Expression, Value};
+use crate::runtime::evaluate_expression;
+
+pub struct AssertModule;
+
+impl Default for AssertModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssertModule {
+    pub fn new() -> Self {
+        AssertModule
+    }
+
+    /// Evaluate the conditions in order and fail on the first falsy one, naming it
+    /// after `fail_msg`. Conditions are read-only, so they're checked in check mode too.
+    pub fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        that: &[Assertion],
+        fail_msg: Option<&Expression>,
+        success_msg: Option<&Expression>,
+        quiet: bool,
+    ) -> Result<TaskOutput, NexusError> {
+        for assertion in that {
+            if !evaluate_expression(&assertion.condition, ctx)?.is_truthy() {
+                let message = match fail_msg {
+                    Some(msg) => evaluate_expression(msg, ctx)?.to_string(),
+                    None => "Assertion failed".to_string(),
+                };
+                return Ok(TaskOutput::failed(format!(
+                    "{} (failed condition: {})",
+                    message, assertion.source
+                ))
+                .with_data("assertion", Value::String(assertion.source.clone()))
+                .with_data("evaluated_to", Value::Bool(false)));
+            }
+        }
+
+        let mut output = TaskOutput::success();
+        if !quiet {
+            output.message = Some(match success_msg {
+                Some(msg) => evaluate_expression(msg, ctx)?.to_string(),
+                None => "All assertions passed".to_string(),
+            });
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl Module for AssertModule {
+    fn name(&self) -> &'static str {
+        "assert"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Host;
+    use crate::parser::expressions::parse_expression;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn assertion(source: &str) -> Assertion {
+        Assertion {
+            source: source.to_string(),
+            condition: parse_expression(source).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_assert_reports_failed_condition() {
+        let mut vars = HashMap::new();
+        vars.insert("port".to_string(), Value::Int(8080));
+        let ctx = ExecutionContext::new(Arc::new(Host::new("web1")), vars);
+        let module = AssertModule::new();
+
+        let passing = [assertion("port > 0"), assertion("port < 65536")];
+        let output = module
+            .execute_with_params(&ctx, &passing, None, None, false)
+            .unwrap();
+        assert!(!output.failed);
+        assert_eq!(output.message.as_deref(), Some("All assertions passed"));
+        let output = module
+            .execute_with_params(&ctx, &passing, None, None, true)
+            .unwrap();
+        assert!(output.message.is_none());
+
+        let failing = [assertion("port > 0"), assertion("port < 1024")];
+        let fail_msg = Expression::String("port must be privileged".to_string());
+        let output = module
+            .execute_with_params(&ctx, &failing, Some(&fail_msg), None, false)
+            .unwrap();
+        assert!(output.failed);
+        assert_eq!(
+            output.message.as_deref(),
+            Some("port must be privileged (failed condition: port < 1024)")
+        );
+    }
+}
@@ -3,7 +3,7 @@
 use async_trait::async_trait;
 
 use super::Module;
-use crate::executor::{AsyncJobTracker, ExecutionContext, SshConnection, TaskOutput};
+use crate::executor::{AsyncJobTracker, Connection, ExecutionContext, SshConnection, TaskOutput};
 use crate::output::errors::NexusError;
 use crate::parser::ast::Value;
 
@@ -21,7 +21,7 @@ impl AsyncStatusModule {
     pub async fn execute_with_job_id(
         &self,
         _ctx: &ExecutionContext,
-        conn: &SshConnection,
+        conn: &dyn Connection,
         job_id: &str,
     ) -> Result<TaskOutput, NexusError> {
         let status = self.tracker.check_status(conn, job_id).await?;
@@ -110,6 +110,7 @@ impl Module for AsyncStatusModule {
             function: None,
             message: "async_status module requires 'job_id' parameter".to_string(),
             suggestion: Some("Use: async_status: { job_id: <job_id> }".to_string()),
+            location: None,
         })
     }
 }
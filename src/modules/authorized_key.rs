@@ -0,0 +1,584 @@
+// Authorized key module - manage SSH public keys in a user's authorized_keys
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+
+use super::{capitalize, join_lines, module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::diff::Diff;
+use crate::output::errors::NexusError;
+use crate::parser::ast::{AuthorizedKeyState, Value};
+
+/// Key types sshd accepts; anything else in front of the key is an options field
+const KEY_TYPE_PREFIXES: &[&str] = &["ssh-", "ecdsa-", "sk-ssh-", "sk-ecdsa-"];
+
+/// Prints `<octal mode> <owner>` with GNU stat, falling back to BSD stat
+const STAT_COMMAND: &str =
+    "stat -c '%a %U' {path} 2>/dev/null || stat -f '%Lp %Su' {path} 2>/dev/null";
+
+pub struct AuthorizedKeyModule;
+
+impl Default for AuthorizedKeyModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One key line: `[options] type body [comment]`
+#[derive(Debug, Clone, PartialEq)]
+struct AuthorizedKey {
+    options: Option<String>,
+    key_type: String,
+    body: String,
+    comment: Option<String>,
+}
+
+impl AuthorizedKey {
+    fn to_line(&self) -> String {
+        let mut line = String::new();
+        if let Some(ref options) = self.options {
+            line.push_str(options);
+            line.push(' ');
+        }
+        line.push_str(&self.key_type);
+        line.push(' ');
+        line.push_str(&self.body);
+        if let Some(ref comment) = self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        line
+    }
+}
+
+/// The rewritten file and what changed in it
+#[derive(Debug, PartialEq)]
+struct KeyUpdate {
+    content: String,
+    added: usize,
+    removed: usize,
+}
+
+impl AuthorizedKeyModule {
+    pub fn new() -> Self {
+        AuthorizedKeyModule
+    }
+
+    /// Add or remove `keys` in the authorized_keys file of `user`.
+    ///
+    /// Keys are matched on their key body, so a different comment doesn't count as a
+    /// change. With `manage_dir`, the file's directory is created and both get the
+    /// permissions sshd insists on (0700 and 0600, owned by `user`).
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        user: &str,
+        keys: &[String],
+        state: AuthorizedKeyState,
+        path: Option<String>,
+        exclusive: bool,
+        manage_dir: bool,
+    ) -> Result<TaskOutput, NexusError> {
+        let keys = keys
+            .iter()
+            .flat_map(|k| k.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                parse_key_line(line).ok_or_else(|| {
+                    module_error(
+                        "authorized_key",
+                        conn,
+                        format!("Invalid public key: {}", line),
+                        None,
+                        Some(
+                            "Use the contents of a .pub file, e.g. 'ssh-ed25519 AAAA... user@host'",
+                        ),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(module_error(
+                "authorized_key",
+                conn,
+                "No public keys given".to_string(),
+                None,
+                Some("Set key to one or more public keys"),
+            ));
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => format!(
+                "{}/.ssh/authorized_keys",
+                self.home_dir(ctx, conn, user).await?
+            ),
+        };
+        let dir = match path.rsplit_once('/') {
+            Some(("", _)) => "/".to_string(),
+            Some((dir, _)) => dir.to_string(),
+            None => ".".to_string(),
+        };
+        let path_quoted = shell_quote(&path);
+        let dir_quoted = shell_quote(&dir);
+
+        let current = conn
+            .exec(&ctx.wrap_command(&format!("cat {} 2>/dev/null", path_quoted)))
+            .await?
+            .stdout;
+        let update = update_keys(&current, &keys, state, exclusive);
+
+        // Permissions only matter for a file that exists or is about to
+        let file_exists = update.is_some() || !current.is_empty();
+        let manage = manage_dir && state == AuthorizedKeyState::Present;
+        let mut fixes = Vec::new();
+        if manage {
+            match self.stat(ctx, conn, &dir).await? {
+                None => fixes.push(format!("create {}", dir)),
+                Some(stat) if stat != ("700".to_string(), user.to_string()) => {
+                    fixes.push(format!("set permissions on {}", dir))
+                }
+                Some(_) => {}
+            }
+            if file_exists {
+                match self.stat(ctx, conn, &path).await? {
+                    Some(stat) if stat == ("600".to_string(), user.to_string()) => {}
+                    _ if update.is_some() => {}
+                    _ => fixes.push(format!("set permissions on {}", path)),
+                }
+            }
+        }
+
+        if update.is_none() && fixes.is_empty() {
+            return Ok(TaskOutput::success()
+                .with_stdout(format!("{} is up to date", path))
+                .with_data("keyfile", Value::String(path)));
+        }
+
+        let mut steps = fixes.clone();
+        if let Some(ref update) = update {
+            steps.push(format!(
+                "update {} ({} added, {} removed)",
+                path, update.added, update.removed
+            ));
+        }
+
+        let diff = update.as_ref().filter(|_| ctx.diff_mode).map(|update| {
+            Diff::new(
+                &current,
+                &update.content,
+                &format!("{} (before)", path),
+                &format!("{} (after)", path),
+            )
+        });
+
+        // Check mode
+        if ctx.check_mode {
+            let mut output = TaskOutput::changed()
+                .with_stdout(format!("Would {}", steps.join(", ")))
+                .with_data("keyfile", Value::String(path));
+            output.diff = diff;
+            return Ok(output);
+        }
+
+        if manage {
+            let user_quoted = shell_quote(user);
+            self.run(
+                ctx,
+                conn,
+                &format!(
+                    "mkdir -p {dir} && chown {user} {dir} && chmod 700 {dir}",
+                    dir = dir_quoted,
+                    user = user_quoted
+                ),
+                &format!("prepare {}", dir),
+            )
+            .await?;
+        }
+
+        if let Some(ref update) = update {
+            let result = conn
+                .exec_with_stdin(
+                    &ctx.wrap_command(&format!("cat > {}", path_quoted)),
+                    &update.content,
+                )
+                .await?;
+            if !result.success() {
+                return Err(module_error(
+                    "authorized_key",
+                    conn,
+                    format!("Failed to write {}", path),
+                    Some(result.stderr),
+                    Some("Writing another user's keys usually needs root; set sudo: true on the task"),
+                ));
+            }
+        }
+
+        if manage && file_exists {
+            self.run(
+                ctx,
+                conn,
+                &format!(
+                    "chown {} {path} && chmod 600 {path}",
+                    shell_quote(user),
+                    path = path_quoted
+                ),
+                &format!("set permissions on {}", path),
+            )
+            .await?;
+        }
+
+        let mut output = TaskOutput::changed()
+            .with_stdout(capitalize(&steps.join(", ")))
+            .with_data("keyfile", Value::String(path));
+        output.diff = diff;
+        Ok(output)
+    }
+
+    /// The user's home directory from the passwd database
+    async fn home_dir(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        user: &str,
+    ) -> Result<String, NexusError> {
+        let user_quoted = shell_quote(user);
+        let result = conn
+            .exec(&ctx.wrap_command(&format!(
+                "getent passwd {user} 2>/dev/null || grep '^'{user}':' /etc/passwd",
+                user = user_quoted
+            )))
+            .await?;
+
+        result
+            .stdout
+            .lines()
+            .next()
+            .and_then(|line| line.split(':').nth(5))
+            .filter(|home| !home.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                module_error(
+                    "authorized_key",
+                    conn,
+                    format!("User {} not found", user),
+                    None,
+                    Some("Create the user first, or set path to the authorized_keys file"),
+                )
+            })
+    }
+
+    /// Octal mode and owner of `path`, or `None` if it doesn't exist
+    async fn stat(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+    ) -> Result<Option<(String, String)>, NexusError> {
+        let result = conn
+            .exec(&ctx.wrap_command(&STAT_COMMAND.replace("{path}", &shell_quote(path))))
+            .await?;
+        Ok(result
+            .stdout
+            .trim()
+            .split_once(' ')
+            .map(|(mode, owner)| (mode.to_string(), owner.to_string())))
+    }
+
+    async fn run(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        cmd: &str,
+        what: &str,
+    ) -> Result<(), NexusError> {
+        let result = conn.exec(&ctx.wrap_command(cmd)).await?;
+        if result.success() {
+            return Ok(());
+        }
+        Err(module_error(
+            "authorized_key",
+            conn,
+            format!("Failed to {}: exit code {}", what, result.exit_code),
+            Some(result.stderr),
+            Some("Managing another user's keys usually needs root; set sudo: true on the task"),
+        ))
+    }
+}
+
+#[async_trait]
+impl Module for AuthorizedKeyModule {
+    fn name(&self) -> &'static str {
+        "authorized_key"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+fn parse_key_line(line: &str) -> Option<AuthorizedKey> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (first, rest) = split_field(line);
+    let (options, key_type, rest) = if is_key_type(first) {
+        (None, first, rest)
+    } else {
+        let (key_type, rest) = split_field(rest);
+        (Some(first.to_string()), key_type, rest)
+    };
+    let (body, comment) = split_field(rest);
+    if !is_key_type(key_type) || body.is_empty() {
+        return None;
+    }
+
+    Some(AuthorizedKey {
+        options,
+        key_type: key_type.to_string(),
+        body: body.to_string(),
+        comment: (!comment.is_empty()).then(|| comment.to_string()),
+    })
+}
+
+fn is_key_type(field: &str) -> bool {
+    KEY_TYPE_PREFIXES
+        .iter()
+        .any(|prefix| field.starts_with(prefix))
+}
+
+/// Split off the first whitespace-separated field; options may quote spaces,
+/// as in `command="echo hi",no-pty`
+fn split_field(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let mut in_quotes = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => return (&s[..i], s[i..].trim()),
+            _ => {}
+        }
+    }
+    (s, "")
+}
+
+/// Apply `keys` to the file content. Returns `None` when nothing changes.
+///
+/// A present key whose options differ is rewritten in place; `exclusive` drops
+/// every other key. Comment and blank lines are kept.
+fn update_keys(
+    content: &str,
+    keys: &[AuthorizedKey],
+    state: AuthorizedKeyState,
+    exclusive: bool,
+) -> Option<KeyUpdate> {
+    let wanted: HashSet<&str> = keys.iter().map(|k| k.body.as_str()).collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut added = 0;
+    let mut removed = 0;
+    let mut rewritten = false;
+
+    for line in content.lines() {
+        let Some(existing) = parse_key_line(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+
+        let listed = wanted.contains(existing.body.as_str());
+        let keep = match state {
+            AuthorizedKeyState::Present => listed || !exclusive,
+            AuthorizedKeyState::Absent => !listed,
+        };
+        if !keep {
+            removed += 1;
+            continue;
+        }
+
+        match keys.iter().find(|k| k.body == existing.body) {
+            Some(key)
+                if state == AuthorizedKeyState::Present && key.options != existing.options =>
+            {
+                lines.push(
+                    AuthorizedKey {
+                        options: key.options.clone(),
+                        ..existing.clone()
+                    }
+                    .to_line(),
+                );
+                rewritten = true;
+            }
+            _ => lines.push(line.to_string()),
+        }
+        seen.insert(existing.body);
+    }
+
+    if state == AuthorizedKeyState::Present {
+        for key in keys {
+            if seen.insert(key.body.clone()) {
+                lines.push(key.to_line());
+                added += 1;
+            }
+        }
+    }
+
+    if added == 0 && removed == 0 && !rewritten {
+        return None;
+    }
+    Some(KeyUpdate {
+        content: join_lines(&lines),
+        added,
+        removed,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAlice alice@laptop";
+    const BOB: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQBob bob@desktop";
+
+    fn keys(lines: &[&str]) -> Vec<AuthorizedKey> {
+        lines.iter().map(|l| parse_key_line(l).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_key_line() {
+        let key = parse_key_line(
+            r#"from="10.0.0.0/8",command="echo hi there" ssh-ed25519 AAAAkey me@host"#,
+        )
+        .unwrap();
+        assert_eq!(
+            key.options.as_deref(),
+            Some(r#"from="10.0.0.0/8",command="echo hi there""#)
+        );
+        assert_eq!(key.key_type, "ssh-ed25519");
+        assert_eq!(key.body, "AAAAkey");
+        assert_eq!(key.comment.as_deref(), Some("me@host"));
+
+        assert_eq!(parse_key_line("ssh-ed25519 AAAAkey").unwrap().comment, None);
+        assert_eq!(parse_key_line("# comment"), None);
+        assert_eq!(parse_key_line("not a key"), None);
+    }
+
+    #[test]
+    fn test_update_keys_matches_on_key_body() {
+        let content = format!("# managed\n{}\n", ALICE);
+
+        // Same key with a different comment is already present
+        let renamed = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAlice alice@work";
+        assert_eq!(
+            update_keys(
+                &content,
+                &keys(&[renamed]),
+                AuthorizedKeyState::Present,
+                false
+            ),
+            None
+        );
+
+        let update =
+            update_keys(&content, &keys(&[BOB]), AuthorizedKeyState::Present, false).unwrap();
+        assert_eq!(update.content, format!("# managed\n{}\n{}\n", ALICE, BOB));
+        assert_eq!((update.added, update.removed), (1, 0));
+
+        let update = update_keys(
+            &update.content,
+            &keys(&[BOB]),
+            AuthorizedKeyState::Present,
+            true,
+        )
+        .unwrap();
+        assert_eq!(update.content, format!("# managed\n{}\n", BOB));
+        assert_eq!((update.added, update.removed), (0, 1));
+
+        let update = update_keys(
+            &content,
+            &keys(&[renamed]),
+            AuthorizedKeyState::Absent,
+            false,
+        )
+        .unwrap();
+        assert_eq!(update.content, "# managed\n");
+        assert_eq!(
+            update_keys(&content, &keys(&[BOB]), AuthorizedKeyState::Absent, false),
+            None
+        );
+
+        let restricted = format!("no-pty {}", ALICE);
+        let update = update_keys(
+            &content,
+            &keys(&[&restricted]),
+            AuthorizedKeyState::Present,
+            false,
+        )
+        .unwrap();
+        assert_eq!(update.content, format!("# managed\n{}\n", restricted));
+    }
+
+    #[tokio::test]
+    async fn test_present_creates_key_file_with_permissions() {
+        use crate::executor::LocalConnection;
+        use crate::inventory::Host;
+        use std::collections::HashMap;
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".ssh").join("authorized_keys");
+        let path_str = path.to_string_lossy().to_string();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let conn = LocalConnection::new("localhost");
+        let user = conn.exec("id -un").await.unwrap().stdout.trim().to_string();
+        let module = AuthorizedKeyModule::new();
+        let run = |keys: String, exclusive: bool| {
+            let (module, ctx, conn, user) = (&module, &ctx, &conn, &user);
+            let path = path_str.clone();
+            async move {
+                module
+                    .execute_with_params(
+                        ctx,
+                        conn,
+                        user,
+                        &[keys],
+                        AuthorizedKeyState::Present,
+                        Some(path),
+                        exclusive,
+                        true,
+                    )
+                    .await
+            }
+        };
+
+        // Several keys in one value, one per line
+        let output = run(format!("{}\n{}", ALICE, BOB), false).await.unwrap();
+        assert!(output.changed);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n{}\n", ALICE, BOB)
+        );
+        let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        assert!(!run(BOB.to_string(), false).await.unwrap().changed);
+
+        let output = run(BOB.to_string(), true).await.unwrap();
+        assert!(output.changed);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", BOB)
+        );
+    }
+}
@@ -0,0 +1,385 @@
+// Blockinfile module - manage a marked block of lines in a file
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::diff::Diff;
+use crate::output::errors::NexusError;
+use crate::parser::ast::BlockInFileState;
+
+/// Marker used when the task doesn't set one; `{mark}` becomes BEGIN or END
+pub const DEFAULT_MARKER: &str = "# {mark} ANSIBLE MANAGED BLOCK";
+
+pub struct BlockInFileModule;
+
+impl Default for BlockInFileModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where a new block goes when the file doesn't have one yet
+#[derive(Debug, Clone)]
+enum InsertAt {
+    Eof,
+    Bof,
+    /// After the last line matching the pattern, or at the end if none does
+    After(Regex),
+    /// Before the last line matching the pattern, or at the end if none does
+    Before(Regex),
+}
+
+impl BlockInFileModule {
+    pub fn new() -> Self {
+        BlockInFileModule
+    }
+
+    /// Insert, update or remove the block between the BEGIN and END markers in `path`.
+    ///
+    /// An existing block is replaced in place; lines outside the markers are never touched.
+    /// An empty `block` removes the block, like `state: absent`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        block: &str,
+        marker: &str,
+        state: BlockInFileState,
+        insertafter: Option<&str>,
+        insertbefore: Option<&str>,
+        create: bool,
+    ) -> Result<TaskOutput, NexusError> {
+        if !marker.contains("{mark}") {
+            return Err(module_error(
+                "blockinfile",
+                conn,
+                format!("marker '{}' has no {{mark}} placeholder", marker),
+                None,
+                Some("Include {mark} so the BEGIN and END markers differ, e.g. '# {mark} app config'"),
+            ));
+        }
+        let begin = marker.replace("{mark}", "BEGIN");
+        let end = marker.replace("{mark}", "END");
+
+        let insert_at = match (insertafter, insertbefore) {
+            (Some("EOF"), _) | (None, None) => InsertAt::Eof,
+            (_, Some("BOF")) => InsertAt::Bof,
+            (Some(pattern), _) => InsertAt::After(self.compile(conn, pattern)?),
+            (None, Some(pattern)) => InsertAt::Before(self.compile(conn, pattern)?),
+        };
+
+        let path_quoted = shell_quote(path);
+        let read = conn
+            .exec(&ctx.wrap_command(&format!("cat {}", path_quoted)))
+            .await?;
+        let exists = read.success();
+        let current = if exists { read.stdout } else { String::new() };
+
+        let present = state == BlockInFileState::Present && !block.is_empty();
+        if !exists && present && !create {
+            return Err(module_error(
+                "blockinfile",
+                conn,
+                format!("{} does not exist", path),
+                None,
+                Some("Set create: true to create the file"),
+            ));
+        }
+
+        let block_lines: Vec<&str> = if present {
+            block.lines().collect()
+        } else {
+            vec![]
+        };
+        let Some(new_content) = apply_block(&current, &begin, &end, &block_lines, &insert_at)
+        else {
+            return Ok(TaskOutput::success().with_stdout(if present {
+                format!("Block already present in {}", path)
+            } else {
+                format!("No block in {}", path)
+            }));
+        };
+
+        // (what happened, what would happen) for the task output
+        let (message, intent) = match (exists, present, current.contains(&begin)) {
+            (false, _, _) => ("File created", "create"),
+            (true, false, _) => ("Block removed", "remove the block from"),
+            (true, true, true) => ("Block updated", "update the block in"),
+            (true, true, false) => ("Block inserted", "insert the block into"),
+        };
+
+        let diff = ctx.diff_mode.then(|| {
+            Diff::new(
+                &current,
+                &new_content,
+                &format!("{} (before)", path),
+                &format!("{} (after)", path),
+            )
+        });
+
+        // Check mode
+        if ctx.check_mode {
+            let mut output =
+                TaskOutput::changed().with_stdout(format!("Would {} {}", intent, path));
+            output.diff = diff;
+            return Ok(output);
+        }
+
+        if !exists {
+            let dir = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+            if !dir.is_empty() {
+                let result = conn
+                    .exec(&ctx.wrap_command(&format!("mkdir -p {}", shell_quote(dir))))
+                    .await?;
+                if !result.success() {
+                    return Err(module_error(
+                        "blockinfile",
+                        conn,
+                        format!("Failed to create directory {}", dir),
+                        Some(result.stderr),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        // Rewrite in place so the file keeps its owner and mode
+        let result = conn
+            .exec_with_stdin(
+                &ctx.wrap_command(&format!("cat > {}", path_quoted)),
+                &new_content,
+            )
+            .await?;
+        if !result.success() {
+            return Err(module_error(
+                "blockinfile",
+                conn,
+                format!("Failed to write {}", path),
+                Some(result.stderr),
+                Some("Check permissions, or set sudo: true on the task"),
+            ));
+        }
+
+        let mut output = TaskOutput::changed().with_stdout(message);
+        output.diff = diff;
+        Ok(output)
+    }
+
+    fn compile(&self, conn: &dyn Connection, pattern: &str) -> Result<Regex, NexusError> {
+        Regex::new(pattern).map_err(|e| {
+            module_error(
+                "blockinfile",
+                conn,
+                format!(
+                    "Invalid insertafter/insertbefore pattern '{}': {}",
+                    pattern, e
+                ),
+                None,
+                None,
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl Module for BlockInFileModule {
+    fn name(&self) -> &'static str {
+        "blockinfile"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+/// Put `block` between the markers in `content`, or drop the block when it's empty.
+/// Returns `None` when the content wouldn't change.
+///
+/// A complete block is replaced where it stands. A lone BEGIN or END marker (or an
+/// END before the BEGIN) is treated as a corrupt block: the stray markers are
+/// dropped and a fresh block is inserted at `insert_at`.
+fn apply_block(
+    content: &str,
+    begin: &str,
+    end: &str,
+    block: &[&str],
+    insert_at: &InsertAt,
+) -> Option<String> {
+    let original: Vec<&str> = content.lines().collect();
+    let begin_index = original.iter().position(|line| *line == begin);
+    let end_index = original.iter().position(|line| *line == end);
+
+    let mut lines = original.clone();
+    let position = match (begin_index, end_index) {
+        (Some(b), Some(e)) if b < e => {
+            lines.drain(b..=e);
+            b
+        }
+        _ => {
+            lines.retain(|line| *line != begin && *line != end);
+            insert_position(&lines, insert_at)
+        }
+    };
+
+    if !block.is_empty() {
+        let mut marked = Vec::with_capacity(block.len() + 2);
+        marked.push(begin);
+        marked.extend_from_slice(block);
+        marked.push(end);
+        lines.splice(position..position, marked);
+    }
+
+    if lines == original {
+        return None;
+    }
+    if lines.is_empty() {
+        return Some(String::new());
+    }
+    Some(format!("{}\n", lines.join("\n")))
+}
+
+fn insert_position(lines: &[&str], insert_at: &InsertAt) -> usize {
+    let last_match = |pattern: &Regex| lines.iter().rposition(|line| pattern.is_match(line));
+    match insert_at {
+        InsertAt::Eof => lines.len(),
+        InsertAt::Bof => 0,
+        InsertAt::After(pattern) => last_match(pattern).map_or(lines.len(), |i| i + 1),
+        InsertAt::Before(pattern) => last_match(pattern).unwrap_or(lines.len()),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEGIN: &str = "# BEGIN ANSIBLE MANAGED BLOCK";
+    const END: &str = "# END ANSIBLE MANAGED BLOCK";
+
+    fn apply(content: &str, block: &[&str], insert_at: &InsertAt) -> Option<String> {
+        apply_block(content, BEGIN, END, block, insert_at)
+    }
+
+    #[test]
+    fn test_apply_block_inserts_and_updates_in_place() {
+        let hosts = "127.0.0.1 localhost\n::1 localhost\n";
+
+        let inserted = apply(hosts, &["10.0.0.5 db"], &InsertAt::Eof).unwrap();
+        assert_eq!(
+            inserted,
+            format!("{}{}\n10.0.0.5 db\n{}\n", hosts, BEGIN, END)
+        );
+        assert_eq!(apply(&inserted, &["10.0.0.5 db"], &InsertAt::Eof), None);
+
+        // An existing block stays where it is, whatever insert_at says
+        let updated = apply(&inserted, &["10.0.0.6 db"], &InsertAt::Bof).unwrap();
+        assert_eq!(
+            updated,
+            format!("{}{}\n10.0.0.6 db\n{}\n", hosts, BEGIN, END)
+        );
+
+        assert_eq!(apply(&updated, &[], &InsertAt::Eof).unwrap(), hosts);
+        assert_eq!(apply(hosts, &[], &InsertAt::Eof), None);
+    }
+
+    #[test]
+    fn test_apply_block_insert_positions() {
+        let config = "[main]\nkey = 1\n[extra]\nother = 2\n";
+        let after = InsertAt::After(Regex::new(r"^key =").unwrap());
+        assert_eq!(
+            apply(config, &["new = 3"], &after).unwrap(),
+            format!(
+                "[main]\nkey = 1\n{}\nnew = 3\n{}\n[extra]\nother = 2\n",
+                BEGIN, END
+            )
+        );
+
+        let before = InsertAt::Before(Regex::new(r"^\[extra\]").unwrap());
+        assert_eq!(
+            apply(config, &["new = 3"], &before).unwrap(),
+            format!(
+                "[main]\nkey = 1\n{}\nnew = 3\n{}\n[extra]\nother = 2\n",
+                BEGIN, END
+            )
+        );
+
+        assert!(apply(config, &["x"], &InsertAt::Bof)
+            .unwrap()
+            .starts_with(&format!("{}\nx\n{}\n[main]", BEGIN, END)));
+
+        // No match falls back to the end of the file
+        let nowhere = InsertAt::After(Regex::new("^missing").unwrap());
+        assert!(apply(config, &["x"], &nowhere)
+            .unwrap()
+            .ends_with(&format!("other = 2\n{}\nx\n{}\n", BEGIN, END)));
+    }
+
+    #[test]
+    fn test_apply_block_rewrites_corrupt_markers() {
+        let only_begin = format!("a\n{}\nold\nb\n", BEGIN);
+        assert_eq!(
+            apply(&only_begin, &["new"], &InsertAt::Eof).unwrap(),
+            format!("a\nold\nb\n{}\nnew\n{}\n", BEGIN, END)
+        );
+
+        let swapped = format!("{}\na\n{}\n", END, BEGIN);
+        assert_eq!(
+            apply(&swapped, &["new"], &InsertAt::Bof).unwrap(),
+            format!("{}\nnew\n{}\na\n", BEGIN, END)
+        );
+
+        let only_end = format!("a\n{}\n", END);
+        assert_eq!(apply(&only_end, &[], &InsertAt::Eof).unwrap(), "a\n");
+    }
+
+    #[tokio::test]
+    async fn test_blockinfile_on_local_file() {
+        use crate::executor::LocalConnection;
+        use crate::inventory::Host;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conf.d").join("app.conf");
+        let path = path.to_string_lossy().to_string();
+
+        let mut ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        ctx.diff_mode = true;
+        let conn = LocalConnection::new("localhost");
+        let module = BlockInFileModule::new();
+        let run = |block: &'static str, create: bool| {
+            module.execute_with_params(
+                &ctx,
+                &conn,
+                &path,
+                block,
+                "# {mark} app",
+                BlockInFileState::Present,
+                None,
+                None,
+                create,
+            )
+        };
+
+        assert!(run("port = 8080", false).await.is_err());
+
+        let output = run("port = 8080", true).await.unwrap();
+        assert!(output.changed);
+        assert!(output.diff.unwrap().unified.contains("+port = 8080"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# BEGIN app\nport = 8080\n# END app\n"
+        );
+
+        assert!(!run("port = 8080", false).await.unwrap().changed);
+        assert!(run("port = 9090", false).await.unwrap().changed);
+        assert!(run("", false).await.unwrap().changed);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+}
@@ -2,7 +2,10 @@
 
 use async_trait::async_trait;
 
-use super::Module;
+use super::{
+    exec_task_command, path_exists_command, prefix_environment, prefix_powershell_environment,
+    Module,
+};
 use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
 use crate::output::errors::NexusError;
 
@@ -19,6 +22,7 @@ impl CommandModule {
         CommandModule
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn execute_with_params(
         &self,
         ctx: &ExecutionContext,
@@ -26,6 +30,8 @@ impl CommandModule {
         command: &str,
         creates: Option<String>,
         removes: Option<String>,
+        environment: &[(String, String)],
+        stdin: Option<String>,
     ) -> Result<TaskOutput, NexusError> {
         // Check mode
         if ctx.check_mode {
@@ -42,7 +48,7 @@ impl CommandModule {
         // Check 'creates' condition - skip if file exists
         if let Some(ref creates_path) = creates {
             let exists = conn
-                .exec(&format!("test -e '{}'", creates_path))
+                .exec(&path_exists_command(conn, creates_path))
                 .await?
                 .success();
             if exists {
@@ -54,7 +60,7 @@ impl CommandModule {
         // Check 'removes' condition - skip if file doesn't exist
         if let Some(ref removes_path) = removes {
             let exists = conn
-                .exec(&format!("test -e '{}'", removes_path))
+                .exec(&path_exists_command(conn, removes_path))
                 .await?
                 .success();
             if !exists {
@@ -63,11 +69,16 @@ impl CommandModule {
             }
         }
 
-        // Wrap command with sudo if needed
-        let final_command = ctx.wrap_command(command);
+        // Wrap command with sudo if needed; the environment goes inside so sudo keeps it.
+        // A Windows host runs the command in PowerShell as the WinRM user.
+        let final_command = if conn.is_windows() {
+            prefix_powershell_environment(environment, command)
+        } else {
+            ctx.wrap_command(&prefix_environment(environment, command))
+        };
 
         // Execute the command
-        let result = conn.exec(&final_command).await?;
+        let result = exec_task_command(ctx, conn, &final_command, stdin.as_deref()).await?;
 
         if result.success() {
             Ok(TaskOutput::changed()
@@ -0,0 +1,270 @@
+// Custom modules - run executables from the module path with a JSON protocol
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+/// Runs modules that aren't built in: scripts found on the module path, copied to
+/// the host and run with their arguments as a JSON object on stdin. They answer with
+/// a JSON object on stdout, like Ansible modules (`changed`, `failed`, `msg`, ...).
+#[derive(Default)]
+pub struct CustomModule {
+    paths: Vec<PathBuf>,
+}
+
+impl CustomModule {
+    /// Look modules up in `paths`, in order
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        CustomModule { paths }
+    }
+
+    /// The script for module `name`: a file called `name`, or `name` with any
+    /// extension (`name.py`, `name.sh`), in the first directory that has one
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        self.paths.iter().find_map(|dir| {
+            let exact = dir.join(name);
+            if exact.is_file() {
+                return Some(exact);
+            }
+            let mut candidates: Vec<PathBuf> = std::fs::read_dir(dir)
+                .ok()?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.is_file() && p.file_stem().is_some_and(|stem| stem == name))
+                .collect();
+            candidates.sort();
+            candidates.into_iter().next()
+        })
+    }
+
+    /// Copy module `name` to a temporary file on the host, run it with `args` (plus
+    /// `_ansible_check_mode` and `_ansible_diff`) as JSON on stdin, and turn the JSON it
+    /// prints into the task result. In check mode the module isn't run.
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        name: &str,
+        args: Value,
+    ) -> Result<TaskOutput, NexusError> {
+        let script = self.find(name).ok_or_else(|| {
+            let searched = self
+                .paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            module_error(
+                name,
+                conn,
+                format!(
+                    "Unknown module '{}': not built in and not found in the module path ({})",
+                    name,
+                    if searched.is_empty() {
+                        "empty"
+                    } else {
+                        &searched
+                    }
+                ),
+                None,
+                Some("Check the module name, or add its directory with --module-path"),
+            )
+        })?;
+
+        if ctx.check_mode {
+            return Ok(TaskOutput::changed().with_stdout(format!(
+                "Would run module {} ({})",
+                name,
+                script.display()
+            )));
+        }
+        if conn.is_windows() {
+            return Err(module_error(
+                name,
+                conn,
+                "Custom modules are not supported on Windows hosts".to_string(),
+                None,
+                None,
+            ));
+        }
+
+        let source = std::fs::read_to_string(&script).map_err(|e| NexusError::Io {
+            message: format!("Failed to read module {}: {}", name, e),
+            path: Some(script.clone()),
+        })?;
+
+        let mut input = match args {
+            Value::Dict(map) => map,
+            _ => HashMap::new(),
+        };
+        input.insert(
+            "_ansible_check_mode".to_string(),
+            Value::Bool(ctx.check_mode),
+        );
+        input.insert("_ansible_diff".to_string(), Value::Bool(ctx.diff_mode));
+        let input = serde_json::to_string(&input).map_err(|e| {
+            module_error(
+                name,
+                conn,
+                format!("Failed to encode arguments: {}", e),
+                None,
+                None,
+            )
+        })?;
+
+        let staged = conn.exec("mktemp /tmp/nexus-module-XXXXXX").await?;
+        if !staged.success() {
+            return Err(module_error(
+                name,
+                conn,
+                "Failed to create a temporary file for the module".to_string(),
+                Some(staged.stderr),
+                None,
+            ));
+        }
+        let remote = staged.stdout.trim().to_string();
+        let quoted = shell_quote(&remote);
+
+        let result = async {
+            conn.write_file(&remote, &source).await?;
+            conn.exec(&format!("chmod 700 {}", quoted)).await?;
+            conn.exec_with_stdin(&ctx.wrap_command(&quoted), &input)
+                .await
+        }
+        .await;
+        conn.exec(&format!("rm -f {}", quoted)).await?;
+        let result = result?;
+
+        parse_module_result(&result.stdout).ok_or_else(|| {
+            let output = if result.stderr.trim().is_empty() {
+                result.stdout.clone()
+            } else {
+                result.stderr.clone()
+            };
+            module_error(
+                name,
+                conn,
+                format!(
+                    "Module {} did not return a JSON result (exit code {})",
+                    name, result.exit_code
+                ),
+                Some(output),
+                Some("Custom modules must print a JSON object such as {\"changed\": false}"),
+            )
+        })
+    }
+}
+
+/// The task result for a module's JSON output. `changed`, `failed`, `msg`, `rc`,
+/// `stdout` and `stderr` fill in the result itself; every key is also registered.
+fn parse_module_result(stdout: &str) -> Option<TaskOutput> {
+    // Tolerate anything printed before the JSON object, e.g. by a login profile
+    let json = stdout.trim();
+    let json = json.get(json.find('{')?..)?;
+    let result: HashMap<String, Value> = serde_json::from_str(json).ok()?;
+
+    let flag = |key: &str| result.get(key).is_some_and(Value::is_truthy);
+    let text = |key: &str| match result.get(key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None,
+    };
+
+    let mut output = if flag("failed") {
+        TaskOutput::failed(text("msg").unwrap_or_else(|| "Module failed".to_string()))
+    } else if flag("changed") {
+        TaskOutput::changed()
+    } else {
+        TaskOutput::success()
+    };
+    if let Some(msg) = text("msg") {
+        output.message = Some(msg);
+    }
+    if let Some(Value::Int(rc)) = result.get("rc") {
+        output.exit_code = *rc as i32;
+    }
+    output.stdout = text("stdout").unwrap_or_default();
+    output.stderr = text("stderr").unwrap_or_default();
+    output.data.extend(result);
+    Some(output)
+}
+#[async_trait]
+impl Module for CustomModule {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_module_result() {
+        let output =
+            parse_module_result("motd\n{\"changed\": true, \"msg\": \"updated\", \"lines\": 3}\n")
+                .unwrap();
+        assert!(output.changed && !output.failed);
+        assert_eq!(output.message.as_deref(), Some("updated"));
+        assert_eq!(output.data["lines"], Value::Int(3));
+
+        let output =
+            parse_module_result("{\"failed\": true, \"msg\": \"no quota\", \"rc\": 2}").unwrap();
+        assert!(output.failed);
+        assert_eq!(output.exit_code, 2);
+        assert_eq!(output.message.as_deref(), Some("no quota"));
+
+        assert!(parse_module_result("Traceback (most recent call last)").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_custom_module_reads_args_from_stdin() {
+        let library = tempfile::tempdir().unwrap();
+        let script = library.path().join("greet.sh");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nname=$(sed 's/.*\"name\":\"\\([^\"]*\\)\".*/\\1/')\n\
+             echo \"{\\\"changed\\\": true, \\\"greeting\\\": \\\"hello $name\\\"}\"\n",
+        )
+        .unwrap();
+
+        let module = CustomModule::new(vec![PathBuf::from("/nonexistent"), library.path().into()]);
+        assert_eq!(module.find("greet"), Some(script));
+        assert_eq!(module.find("missing"), None);
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let conn = LocalConnection::new("localhost");
+        let mut args = HashMap::new();
+        args.insert("name".to_string(), Value::String("nexus".to_string()));
+
+        let output = module
+            .execute_with_params(&ctx, &conn, "greet", Value::Dict(args))
+            .await
+            .unwrap();
+        assert!(output.changed);
+        assert_eq!(
+            output.data["greeting"],
+            Value::String("hello nexus".to_string())
+        );
+
+        let err = module
+            .execute_with_params(&ctx, &conn, "missing", Value::Dict(HashMap::new()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown module 'missing'"));
+    }
+}
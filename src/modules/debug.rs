@@ -0,0 +1,127 @@
+// Debug module - report a message or a variable's value
+
+use async_trait::async_trait;
+
+use super::Module;
+use crate::executor::{ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{Expression, Value};
+use crate::runtime::evaluate_expression;
+
+/// Shown for `var:` naming a variable that isn't set, instead of failing the task
+const UNDEFINED_MESSAGE: &str = "VARIABLE IS NOT DEFINED!";
+
+pub struct DebugModule;
+
+impl Default for DebugModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugModule {
+    pub fn new() -> Self {
+        DebugModule
+    }
+
+    /// Report `msg`, or the value of `var` as pretty JSON, once the run's verbosity
+    /// reaches `verbosity`. Below the threshold the task is skipped.
+    pub fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        msg: Option<&Expression>,
+        var: Option<&Expression>,
+        verbosity: u8,
+    ) -> Result<TaskOutput, NexusError> {
+        if ctx.verbosity < verbosity {
+            return Ok(TaskOutput::skipped());
+        }
+
+        let text = match (msg, var) {
+            (_, Some(var)) => match var {
+                Expression::Variable(path) if ctx.get_nested_var(path).is_none() => {
+                    UNDEFINED_MESSAGE.to_string()
+                }
+                _ => pretty_json(&evaluate_expression(var, ctx)?),
+            },
+            (Some(msg), None) => evaluate_expression(msg, ctx)?.to_string(),
+            (None, None) => "Hello world!".to_string(),
+        };
+
+        let mut output = TaskOutput::success().with_data("msg", Value::String(text.clone()));
+        output.message = Some(text);
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl Module for DebugModule {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+/// Strings are shown as they are; everything else as JSON with sorted keys
+fn pretty_json(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_value(other)
+            .and_then(|json| serde_json::to_string_pretty(&json))
+            .unwrap_or_else(|_| other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Host;
+    use crate::parser::expressions::parse_expression;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_debug_var_and_verbosity() {
+        let mut vars = HashMap::new();
+        vars.insert(
+            "ports".to_string(),
+            Value::List(vec![Value::Int(80), Value::Int(443)]),
+        );
+        let ctx = ExecutionContext::new(Arc::new(Host::new("web1")), vars);
+        let module = DebugModule::new();
+
+        let ports = parse_expression("ports").unwrap();
+        let output = module
+            .execute_with_params(&ctx, None, Some(&ports), 0)
+            .unwrap();
+        assert_eq!(output.message.as_deref(), Some("[\n  80,\n  443\n]"));
+        assert!(!output.changed);
+
+        let missing = parse_expression("nope").unwrap();
+        let output = module
+            .execute_with_params(&ctx, None, Some(&missing), 0)
+            .unwrap();
+        assert_eq!(output.message.as_deref(), Some(UNDEFINED_MESSAGE));
+
+        // Only shown from -vv on
+        let msg = Expression::String("details".to_string());
+        assert!(
+            module
+                .execute_with_params(&ctx, Some(&msg), None, 2)
+                .unwrap()
+                .skipped
+        );
+        let ctx = ctx.with_verbosity(2);
+        let output = module
+            .execute_with_params(&ctx, Some(&msg), None, 2)
+            .unwrap();
+        assert_eq!(output.message.as_deref(), Some("details"));
+    }
+}
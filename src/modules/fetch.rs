@@ -0,0 +1,178 @@
+// Fetch module - copy a remote file to the control node
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use super::{module_error, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+pub struct FetchModule;
+
+impl Default for FetchModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FetchModule {
+    pub fn new() -> Self {
+        FetchModule
+    }
+
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        src: &str,
+        dest: &str,
+        flat: bool,
+    ) -> Result<TaskOutput, NexusError> {
+        let local = local_path(conn.host_name(), src, dest, flat);
+
+        let content = conn.read_file(src).await.map_err(|e| {
+            module_error(
+                "fetch",
+                conn,
+                format!("Failed to read {}", src),
+                Some(e.to_string()),
+                Some("Check that the file exists and is readable"),
+            )
+        })?;
+
+        let up_to_date = tokio::fs::read_to_string(&local)
+            .await
+            .map(|existing| existing == content)
+            .unwrap_or(false);
+
+        let output = if up_to_date {
+            TaskOutput::success().with_stdout(format!("{} is up to date", local.display()))
+        } else if ctx.check_mode {
+            TaskOutput::changed().with_stdout(format!("Would fetch {} to {}", src, local.display()))
+        } else {
+            write_local(conn, &local, &content).await?;
+            TaskOutput::changed().with_stdout(format!("Fetched {} to {}", src, local.display()))
+        };
+
+        Ok(output
+            .with_data("src", Value::String(src.to_string()))
+            .with_data("dest", Value::String(local.to_string_lossy().to_string())))
+    }
+}
+
+/// Where a fetched file is stored locally.
+/// Like Ansible, files land in `dest/<host>/<src>` unless `flat` is set, in which
+/// case `dest` is the file itself, or the directory to put it in when it ends with `/`.
+fn local_path(host: &str, src: &str, dest: &str, flat: bool) -> PathBuf {
+    if flat {
+        if dest.ends_with('/') {
+            let name = Path::new(src).file_name().unwrap_or_default();
+            return Path::new(dest).join(name);
+        }
+        return PathBuf::from(dest);
+    }
+    Path::new(dest).join(host).join(src.trim_start_matches('/'))
+}
+
+async fn write_local(conn: &dyn Connection, path: &Path, content: &str) -> Result<(), NexusError> {
+    let io_error = |action: &str, e: std::io::Error| {
+        module_error(
+            "fetch",
+            conn,
+            format!("Failed to {} {}", action, path.display()),
+            Some(e.to_string()),
+            None,
+        )
+    };
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| io_error("create directory for", e))?;
+    }
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| io_error("write", e))
+}
+#[async_trait]
+impl Module for FetchModule {
+    fn name(&self) -> &'static str {
+        "fetch"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_local_path() {
+        assert_eq!(
+            local_path("web1", "/etc/hosts", "backups", false),
+            PathBuf::from("backups/web1/etc/hosts")
+        );
+        assert_eq!(
+            local_path("web1", "/etc/hosts", "backups/", true),
+            PathBuf::from("backups/hosts")
+        );
+        assert_eq!(
+            local_path("web1", "/etc/hosts", "backups/web1-hosts", true),
+            PathBuf::from("backups/web1-hosts")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_creates_directories_and_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.conf");
+        std::fs::write(&src, "port = 80\n").unwrap();
+        let dest = dir.path().join("fetched");
+        let src = src.to_string_lossy().to_string();
+        let dest = dest.to_string_lossy().to_string();
+
+        let conn = LocalConnection::new("localhost");
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let module = FetchModule::new();
+
+        let check = module
+            .execute_with_params(
+                &ctx.clone_for_task().with_check_mode(true),
+                &conn,
+                &src,
+                &dest,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(check.changed);
+        assert!(!Path::new(&dest).exists());
+
+        let first = module
+            .execute_with_params(&ctx, &conn, &src, &dest, false)
+            .await
+            .unwrap();
+        assert!(first.changed);
+        let local = local_path("localhost", &src, &dest, false);
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), "port = 80\n");
+
+        let second = module
+            .execute_with_params(&ctx, &conn, &src, &dest, false)
+            .await
+            .unwrap();
+        assert!(!second.changed);
+    }
+}
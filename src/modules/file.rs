@@ -3,9 +3,9 @@
 use async_trait::async_trait;
 use std::path::Path;
 
-use super::Module;
-use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
-use crate::output::diff::generate_unified_diff;
+use super::{path_exists_command, shell_quote, Module};
+use crate::executor::{ps_quote, Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::diff::Diff;
 use crate::output::errors::{ModuleError, NexusError};
 use crate::parser::ast::FileState;
 
@@ -34,7 +34,32 @@ impl FileModule {
         owner: Option<String>,
         group: Option<String>,
         mode: Option<String>,
+        recurse: bool,
     ) -> Result<TaskOutput, NexusError> {
+        if conn.is_windows() {
+            return self
+                .execute_windows(ctx, conn, path, state, source, content, owner, group, mode)
+                .await;
+        }
+
+        // Reject a malformed mode before touching anything; the symbolic clauses
+        // themselves can't fail whatever the file's current mode turns out to be
+        if let Some(ref m) = mode {
+            resolve_mode(m, 0, false).map_err(|e| {
+                NexusError::Module(Box::new(ModuleError {
+                    module: "file".to_string(),
+                    task_name: String::new(),
+                    host: conn.host_name().to_string(),
+                    message: e,
+                    stderr: None,
+                    suggestion: Some(
+                        "Use an octal mode like \"0644\" or symbolic clauses like \"u=rw,go=r\""
+                            .to_string(),
+                    ),
+                }))
+            })?;
+        }
+
         // Check mode
         if ctx.check_mode {
             let action = match state {
@@ -68,76 +93,25 @@ impl FileModule {
                 if let Some(ref m) = mode {
                     msg.push_str(&format!(" mode={}", m));
                 }
-            }
-
-            // Check if there would be changes in check mode
-            let mut _has_changes = false;
-            let mut diff_str = String::new();
-
-            if state == FileState::File {
-                if let Some(ref content_str) = content {
-                    // Check if file exists and content differs
-                    let exists = conn
-                        .exec(&format!("test -f {}", shell_quote(path)))
-                        .await?
-                        .success();
-                    let old_content = if exists {
-                        conn.read_file(path).await.ok()
-                    } else {
-                        None
-                    };
-
-                    // Determine if there are changes
-                    _has_changes = if let Some(ref old) = old_content {
-                        old != content_str
-                    } else {
-                        // File doesn't exist, so it would be created
-                        true
-                    };
-
-                    // Generate diff if diff_mode is enabled and there are changes
-                    if ctx.diff_mode && _has_changes {
-                        diff_str = if let Some(old) = old_content {
-                            generate_unified_diff(
-                                &old,
-                                content_str,
-                                &format!("{} (before)", path),
-                                &format!("{} (after)", path),
-                            )
-                        } else {
-                            // New file - show all content as additions
-                            generate_unified_diff(
-                                "",
-                                content_str,
-                                "/dev/null",
-                                &format!("{} (new)", path),
-                            )
-                        };
-                    }
-                } else if source.is_some() {
-                    // For source files, assume there might be changes in check mode
-                    _has_changes = true;
-                } else {
-                    // Just ensuring file exists
-                    let exists = conn
-                        .exec(&format!("test -f {}", shell_quote(path)))
-                        .await?
-                        .success();
-                    _has_changes = !exists;
+                if recurse {
+                    msg.push_str(" recursively");
                 }
-            } else {
-                // For other states, assume there might be changes
-                _has_changes = true;
             }
 
-            let mut output = if _has_changes {
+            let (has_changes, diff) = self
+                .check_changes(
+                    ctx, conn, path, state, &source, &content, &owner, &group, &mode, recurse,
+                )
+                .await?;
+
+            let mut output = if has_changes {
                 TaskOutput::changed().with_stdout(msg)
             } else {
                 TaskOutput::success().with_stdout(format!("{} (no changes)", msg))
             };
 
-            if !diff_str.is_empty() {
-                output = output.with_diff(diff_str);
+            if let Some(diff) = diff {
+                output = output.with_diff(diff);
             }
 
             return Ok(output);
@@ -149,7 +123,7 @@ impl FileModule {
                     .await
             }
             FileState::Directory => {
-                self.ensure_directory(ctx, conn, path, owner, group, mode)
+                self.ensure_directory(ctx, conn, path, owner, group, mode, recurse)
                     .await
             }
             FileState::Link => {
@@ -171,6 +145,93 @@ impl FileModule {
         }
     }
 
+    /// Whether applying the task would change `path`, and with `--diff` the diff of
+    /// the content it would write, found without changing anything. Drives check mode.
+    #[allow(clippy::too_many_arguments)]
+    async fn check_changes(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        state: FileState,
+        source: &Option<String>,
+        content: &Option<String>,
+        owner: &Option<String>,
+        group: &Option<String>,
+        mode: &Option<String>,
+        recurse: bool,
+    ) -> Result<(bool, Option<Diff>), NexusError> {
+        let quoted = shell_quote(path);
+        match state {
+            FileState::File => {
+                let exists = conn.exec(&format!("test -f {}", quoted)).await?.success();
+                let desired = match (content, source) {
+                    (Some(content), _) => Some(content.clone()),
+                    (None, Some(source)) => {
+                        let local_path = Path::new(source);
+                        Some(
+                            std::fs::read_to_string(local_path).map_err(|e| NexusError::Io {
+                                message: format!("Failed to read source file: {}", e),
+                                path: Some(local_path.to_path_buf()),
+                            })?,
+                        )
+                    }
+                    (None, None) => None,
+                };
+
+                let mut changed = !exists;
+                let mut diff = None;
+                if let Some(desired) = desired {
+                    let old = if exists {
+                        conn.read_file(path).await.ok()
+                    } else {
+                        None
+                    };
+                    if old.as_deref() != Some(desired.as_str()) {
+                        changed = true;
+                        if ctx.diff_mode {
+                            diff = Some(match old {
+                                Some(old) => Diff::new(
+                                    &old,
+                                    &desired,
+                                    &format!("{} (before)", path),
+                                    &format!("{} (after)", path),
+                                ),
+                                None => {
+                                    Diff::new("", &desired, "/dev/null", &format!("{} (new)", path))
+                                }
+                            });
+                        }
+                    }
+                }
+                if exists && !changed {
+                    changed = attributes_differ(ctx, conn, path, owner, group, mode, false).await?;
+                }
+                Ok((changed, diff))
+            }
+            FileState::Directory => {
+                let exists = conn.exec(&format!("test -d {}", quoted)).await?.success();
+                let changed = !exists
+                    || attributes_differ(ctx, conn, path, owner, group, mode, recurse).await?;
+                Ok((changed, None))
+            }
+            FileState::Link => {
+                let result = conn.exec(&format!("readlink {}", quoted)).await?;
+                let current = result.success().then(|| result.stdout.trim().to_string());
+                Ok((current.as_deref() != source.as_deref(), None))
+            }
+            FileState::Absent => {
+                let exists = conn
+                    .exec(&format!("test -e {0} -o -L {0}", quoted))
+                    .await?
+                    .success();
+                Ok((exists, None))
+            }
+            // Touching always updates the timestamps
+            FileState::Touch => Ok((true, None)),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn ensure_file(
         &self,
@@ -185,7 +246,7 @@ impl FileModule {
     ) -> Result<TaskOutput, NexusError> {
         let mut changed = false;
         let mut output_lines = Vec::new();
-        let mut diff_output: Option<String> = None;
+        let mut diff_output: Option<Diff> = None;
 
         // Check if file exists
         let exists = conn
@@ -214,7 +275,7 @@ impl FileModule {
                 // Generate diff before writing
                 if ctx.diff_mode {
                     if let Some(old) = old_content {
-                        diff_output = Some(generate_unified_diff(
+                        diff_output = Some(Diff::new(
                             &old,
                             &content,
                             &format!("{} (before)", path),
@@ -222,7 +283,7 @@ impl FileModule {
                         ));
                     } else if !exists {
                         // New file - show all content as additions
-                        diff_output = Some(generate_unified_diff(
+                        diff_output = Some(Diff::new(
                             "",
                             &content,
                             "/dev/null",
@@ -305,7 +366,7 @@ impl FileModule {
                 // Generate diff before writing
                 if ctx.diff_mode {
                     if let Some(old) = old_content {
-                        diff_output = Some(generate_unified_diff(
+                        diff_output = Some(Diff::new(
                             &old,
                             &local_content,
                             &format!("{} (before)", path),
@@ -313,7 +374,7 @@ impl FileModule {
                         ));
                     } else if !exists {
                         // New file - show all content as additions
-                        diff_output = Some(generate_unified_diff(
+                        diff_output = Some(Diff::new(
                             "",
                             &local_content,
                             "/dev/null",
@@ -336,22 +397,9 @@ impl FileModule {
 
         // Set permissions
         if let Some(ref m) = mode {
-            let current_mode = get_file_mode(conn, path).await?;
-            if current_mode.as_deref() != Some(m.as_str()) {
-                let cmd = format!("chmod {} {}", m, shell_quote(path));
-                let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
-                if !result.success() {
-                    return Err(NexusError::Module(Box::new(ModuleError {
-                        module: "file".to_string(),
-                        task_name: String::new(),
-                        host: conn.host_name().to_string(),
-                        message: format!("Failed to set mode on {}", path),
-                        stderr: Some(result.stderr),
-                        suggestion: None,
-                    })));
-                }
+            if let Some(set) = apply_mode(ctx, conn, path, m, false).await? {
                 changed = true;
-                output_lines.push(format!("Set mode {} on {}", m, path));
+                output_lines.push(format!("Set mode {} on {}", set, path));
             }
         }
 
@@ -395,6 +443,7 @@ impl FileModule {
         Ok(output)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn ensure_directory(
         &self,
         ctx: &ExecutionContext,
@@ -403,6 +452,7 @@ impl FileModule {
         owner: Option<String>,
         group: Option<String>,
         mode: Option<String>,
+        recurse: bool,
     ) -> Result<TaskOutput, NexusError> {
         let mut changed = false;
         let mut output_lines = Vec::new();
@@ -430,13 +480,25 @@ impl FileModule {
             output_lines.push(format!("Created directory {}", path));
         }
 
+        if recurse {
+            let changes = self
+                .set_tree_attributes(ctx, conn, path, &owner, &group, &mode)
+                .await?;
+            changed |= !changes.is_empty();
+            output_lines.extend(changes);
+            let output = if changed {
+                TaskOutput::changed()
+            } else {
+                TaskOutput::success()
+            };
+            return Ok(output.with_stdout(output_lines.join("\n")));
+        }
+
         // Set mode
-        if let Some(m) = mode {
-            let cmd = format!("chmod {} {}", m, shell_quote(path));
-            let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
-            if result.success() {
+        if let Some(ref m) = mode {
+            if let Some(set) = apply_mode(ctx, conn, path, m, true).await? {
                 changed = true;
-                output_lines.push(format!("Set mode {} on {}", m, path));
+                output_lines.push(format!("Set mode {} on {}", set, path));
             }
         }
 
@@ -543,6 +605,85 @@ impl FileModule {
         Ok(TaskOutput::changed().with_stdout(format!("Removed {}", path)))
     }
 
+    /// `chown -R`/`chmod -R` a directory tree, comparing every entry's owner, group
+    /// and permissions before and after to tell whether anything changed. Returns a
+    /// line for each attribute that changed.
+    async fn set_tree_attributes(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        owner: &Option<String>,
+        group: &Option<String>,
+        mode: &Option<String>,
+    ) -> Result<Vec<String>, NexusError> {
+        let snapshot_cmd = ctx.wrap_command(&format!(
+            "find {} -printf '%m %u:%g %p\\n'",
+            shell_quote(path)
+        ));
+        let mut changes = Vec::new();
+
+        if owner.is_some() || group.is_some() {
+            let ownership = match (owner, group) {
+                (Some(o), Some(g)) => format!("{}:{}", o, g),
+                (Some(o), None) => o.clone(),
+                (None, Some(g)) => format!(":{}", g),
+                (None, None) => unreachable!(),
+            };
+            let before = conn.exec(&snapshot_cmd).await?.stdout;
+            self.run_recursive(
+                ctx,
+                conn,
+                &format!("chown -R {} {}", ownership, shell_quote(path)),
+                path,
+            )
+            .await?;
+            if conn.exec(&snapshot_cmd).await?.stdout != before {
+                changes.push(format!(
+                    "Set ownership {} recursively on {}",
+                    ownership, path
+                ));
+            }
+        }
+
+        if let Some(m) = mode {
+            let before = conn.exec(&snapshot_cmd).await?.stdout;
+            self.run_recursive(
+                ctx,
+                conn,
+                &format!("chmod -R {} {}", shell_quote(m), shell_quote(path)),
+                path,
+            )
+            .await?;
+            if conn.exec(&snapshot_cmd).await?.stdout != before {
+                changes.push(format!("Set mode {} recursively on {}", m, path));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    async fn run_recursive(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        cmd: &str,
+        path: &str,
+    ) -> Result<(), NexusError> {
+        let result = conn.exec(&ctx.wrap_command(cmd)).await?;
+        if result.success() {
+            return Ok(());
+        }
+        Err(NexusError::Module(Box::new(ModuleError {
+            module: "file".to_string(),
+            task_name: String::new(),
+            host: conn.host_name().to_string(),
+            message: format!("Failed to set attributes recursively on {}", path),
+            stderr: Some(result.stderr),
+            suggestion: None,
+        })))
+    }
+
     async fn touch_file(
         &self,
         ctx: &ExecutionContext,
@@ -581,10 +722,11 @@ impl FileModule {
         }
 
         // Set mode
-        if let Some(m) = mode {
-            let cmd = format!("chmod {} {}", m, shell_quote(path));
-            conn.exec(&ctx.wrap_command(&cmd)).await?;
-            changed = true;
+        if let Some(ref m) = mode {
+            if let Some(set) = apply_mode(ctx, conn, path, m, false).await? {
+                changed = true;
+                output_lines.push(format!("Set mode {} on {}", set, path));
+            }
         }
 
         // Set ownership
@@ -610,6 +752,163 @@ impl FileModule {
     }
 }
 
+impl FileModule {
+    /// Manage a file on a Windows host: contents go through the connection and
+    /// everything else is PowerShell. Unix ownership, modes and symlinks don't apply.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_windows(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        state: FileState,
+        source: Option<String>,
+        content: Option<String>,
+        owner: Option<String>,
+        group: Option<String>,
+        mode: Option<String>,
+    ) -> Result<TaskOutput, NexusError> {
+        if owner.is_some() || group.is_some() || mode.is_some() {
+            return Err(windows_error(
+                conn,
+                "owner, group and mode are not supported on Windows hosts".to_string(),
+                None,
+                Some("Manage ACLs with a command task running icacls or Set-Acl"),
+            ));
+        }
+
+        let quoted = ps_quote(path);
+        let would =
+            |action: String| Ok(TaskOutput::changed().with_stdout(format!("Would {}", action)));
+
+        match state {
+            FileState::File => {
+                let desired = match (content, source) {
+                    (Some(content), _) => Some(content),
+                    (None, Some(source)) => Some(std::fs::read_to_string(&source).map_err(
+                        |e| NexusError::Io {
+                            message: format!("Failed to read source file: {}", e),
+                            path: Some(source.into()),
+                        },
+                    )?),
+                    (None, None) => None,
+                };
+                let current = conn.read_file(path).await.ok();
+                let Some(desired) = desired else {
+                    // Only make sure the file exists
+                    if current.is_some() {
+                        return Ok(TaskOutput::success());
+                    }
+                    if ctx.check_mode {
+                        return would(format!("ensure file {} exists", path));
+                    }
+                    run_powershell(
+                        conn,
+                        &format!("New-Item -ItemType File -Force -Path {} | Out-Null", quoted),
+                    )
+                    .await?;
+                    return Ok(TaskOutput::changed().with_stdout(format!("Created file {}", path)));
+                };
+
+                if current.as_deref() == Some(desired.as_str()) {
+                    return Ok(TaskOutput::success());
+                }
+                let diff = ctx.diff_mode.then(|| {
+                    Diff::new(
+                        current.as_deref().unwrap_or(""),
+                        &desired,
+                        &format!("{} (before)", path),
+                        &format!("{} (after)", path),
+                    )
+                });
+                let mut output = if ctx.check_mode {
+                    TaskOutput::changed().with_stdout(format!("Would create/update file {}", path))
+                } else {
+                    if let Some((parent, _)) =
+                        path.rsplit_once(['\\', '/']).filter(|(p, _)| !p.is_empty())
+                    {
+                        run_powershell(
+                            conn,
+                            &format!(
+                                "New-Item -ItemType Directory -Force -Path {} | Out-Null",
+                                ps_quote(parent)
+                            ),
+                        )
+                        .await?;
+                    }
+                    conn.write_file(path, &desired).await?;
+                    TaskOutput::changed().with_stdout(format!(
+                        "{} file {}",
+                        if current.is_some() {
+                            "Updated"
+                        } else {
+                            "Created"
+                        },
+                        path
+                    ))
+                };
+                if let Some(diff) = diff {
+                    output = output.with_diff(diff);
+                }
+                Ok(output)
+            }
+            FileState::Directory => {
+                let check = format!(
+                    "if (-not (Test-Path -LiteralPath {} -PathType Container)) {{ exit 1 }}",
+                    quoted
+                );
+                if conn.exec(&check).await?.success() {
+                    return Ok(TaskOutput::success());
+                }
+                if ctx.check_mode {
+                    return would(format!("create directory {}", path));
+                }
+                run_powershell(
+                    conn,
+                    &format!(
+                        "New-Item -ItemType Directory -Force -Path {} | Out-Null",
+                        quoted
+                    ),
+                )
+                .await?;
+                Ok(TaskOutput::changed().with_stdout(format!("Created directory {}", path)))
+            }
+            FileState::Absent => {
+                if !conn.exec(&path_exists_command(conn, path)).await?.success() {
+                    return Ok(TaskOutput::success());
+                }
+                if ctx.check_mode {
+                    return would(format!("remove {}", path));
+                }
+                run_powershell(
+                    conn,
+                    &format!("Remove-Item -LiteralPath {} -Recurse -Force", quoted),
+                )
+                .await?;
+                Ok(TaskOutput::changed().with_stdout(format!("Removed {}", path)))
+            }
+            FileState::Touch => {
+                if ctx.check_mode {
+                    return would(format!("touch {}", path));
+                }
+                let script = format!(
+                    "if (Test-Path -LiteralPath {0}) {{ (Get-Item -LiteralPath {0}).LastWriteTime = Get-Date }} \
+                     else {{ New-Item -ItemType File -Path {0} | Out-Null }}",
+                    quoted
+                );
+                run_powershell(conn, &script).await?;
+                Ok(TaskOutput::changed().with_stdout(format!("Touched {}", path)))
+            }
+            FileState::Link => Err(windows_error(
+                conn,
+                "state: link is not supported on Windows hosts".to_string(),
+                None,
+                None,
+            )),
+        }
+    }
+}
+
 #[async_trait]
 impl Module for FileModule {
     fn name(&self) -> &'static str {
@@ -625,19 +924,371 @@ impl Module for FileModule {
     }
 }
 
-/// Get the mode of a file
-async fn get_file_mode(conn: &dyn Connection, path: &str) -> Result<Option<String>, NexusError> {
+/// Get the permission bits of a file
+async fn get_file_mode(conn: &dyn Connection, path: &str) -> Result<Option<u32>, NexusError> {
     let result = conn
         .exec(&format!("stat -c '%a' {} 2>/dev/null", shell_quote(path)))
         .await?;
     if result.success() {
-        Ok(Some(result.stdout.trim().to_string()))
+        Ok(u32::from_str_radix(result.stdout.trim(), 8).ok())
     } else {
         Ok(None)
     }
 }
 
-/// Shell-quote a string for safe use in commands
-fn shell_quote(s: &str) -> String {
-    format!("'{}'", s.replace('\'', "'\\''"))
+/// Whether `path` (and with `recurse` everything under it) has an owner, group or
+/// mode other than the ones asked for. Owners and groups match by name or id.
+async fn attributes_differ(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    path: &str,
+    owner: &Option<String>,
+    group: &Option<String>,
+    mode: &Option<String>,
+    recurse: bool,
+) -> Result<bool, NexusError> {
+    if owner.is_none() && group.is_none() && mode.is_none() {
+        return Ok(false);
+    }
+    let cmd = format!(
+        "find {} {}-printf '%m %u %U %g %G %y\\n'",
+        shell_quote(path),
+        if recurse { "" } else { "-maxdepth 0 " }
+    );
+    let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+    if !result.success() {
+        // Can't be inspected, so assume the task would have something to do
+        return Ok(true);
+    }
+
+    for line in result.stdout.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let [current_mode, user, uid, group_name, gid, kind] = fields.as_slice() else {
+            return Ok(true);
+        };
+        if owner.as_deref().is_some_and(|o| o != *user && o != *uid)
+            || group
+                .as_deref()
+                .is_some_and(|g| g != *group_name && g != *gid)
+        {
+            return Ok(true);
+        }
+        if let Some(m) = mode {
+            let current = u32::from_str_radix(current_mode, 8).unwrap_or(0);
+            if resolve_mode(m, current, *kind == "d") != Ok(current) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Bring `path` to the permissions `mode` asks for, relative to its current mode for
+/// symbolic clauses. Returns the octal mode set, or `None` if it already had it.
+async fn apply_mode(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    path: &str,
+    mode: &str,
+    is_dir: bool,
+) -> Result<Option<String>, NexusError> {
+    let current = get_file_mode(conn, path).await?;
+    let error = |message: String, stderr: Option<String>| {
+        NexusError::Module(Box::new(ModuleError {
+            module: "file".to_string(),
+            task_name: String::new(),
+            host: conn.host_name().to_string(),
+            message,
+            stderr,
+            suggestion: None,
+        }))
+    };
+
+    let desired = resolve_mode(mode, current.unwrap_or(0), is_dir).map_err(|e| error(e, None))?;
+    if current == Some(desired) {
+        return Ok(None);
+    }
+
+    let octal = format!("{:04o}", desired);
+    let cmd = format!("chmod {} {}", octal, shell_quote(path));
+    let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+    if !result.success() {
+        return Err(error(
+            format!("Failed to set mode on {}", path),
+            Some(result.stderr),
+        ));
+    }
+    Ok(Some(octal))
+}
+
+/// Resolve a `mode` parameter to permission bits. Octal modes (`0644`, `644`, `4755`)
+/// are taken as they are; symbolic ones (`u+x,go-w`, `u=rw,go=r`, `a+X`) are applied
+/// clause by clause to `current`, the way chmod does.
+pub(crate) fn resolve_mode(mode: &str, current: u32, is_dir: bool) -> Result<u32, String> {
+    let mode = mode.trim();
+    if !mode.is_empty() && mode.chars().all(|c| c.is_ascii_digit()) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)
+            .ok_or_else(|| format!("Invalid octal mode '{}'", mode));
+    }
+
+    let invalid = |clause: &str| format!("Invalid symbolic mode clause '{}' in '{}'", clause, mode);
+    let mut bits = current & 0o7777;
+    for clause in mode.split(',') {
+        let who_end = clause
+            .find(['+', '-', '='])
+            .ok_or_else(|| invalid(clause))?;
+
+        // The bits each class owns, including its setuid/setgid/sticky bit
+        let mut who = 0;
+        for c in clause[..who_end].chars() {
+            who |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return Err(invalid(clause)),
+            };
+        }
+        if who == 0 {
+            who = 0o7777;
+        }
+
+        // One or more operator/permission pairs, e.g. `u+r-w`
+        let mut rest = &clause[who_end..];
+        while let Some(op) = rest.chars().next() {
+            let perms_end = rest[1..]
+                .find(['+', '-', '='])
+                .map_or(rest.len(), |i| i + 1);
+            let perms = &rest[1..perms_end];
+            rest = &rest[perms_end..];
+
+            let mut change = 0;
+            for c in perms.chars() {
+                change |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if is_dir || bits & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    // Copy another class's current permissions, as in `g=u`
+                    'u' => (bits >> 6 & 0o7) * 0o111,
+                    'g' => (bits >> 3 & 0o7) * 0o111,
+                    'o' => (bits & 0o7) * 0o111,
+                    _ => return Err(invalid(clause)),
+                };
+            }
+            change &= who;
+
+            match op {
+                '+' => bits |= change,
+                '-' => bits &= !change,
+                _ => bits = (bits & !who) | change,
+            }
+        }
+    }
+    Ok(bits)
+}
+
+/// Run a PowerShell script on a Windows host, failing with its error output
+async fn run_powershell(conn: &dyn Connection, script: &str) -> Result<(), NexusError> {
+    let result = conn.exec(script).await?;
+    if result.success() {
+        Ok(())
+    } else {
+        Err(windows_error(
+            conn,
+            format!("PowerShell command failed: {}", script),
+            Some(result.stderr),
+            None,
+        ))
+    }
+}
+
+fn windows_error(
+    conn: &dyn Connection,
+    message: String,
+    stderr: Option<String>,
+    suggestion: Option<&str>,
+) -> NexusError {
+    NexusError::Module(Box::new(ModuleError {
+        module: "file".to_string(),
+        task_name: String::new(),
+        host: conn.host_name().to_string(),
+        message,
+        stderr,
+        suggestion: suggestion.map(|s| s.to_string()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_octal_mode() {
+        assert_eq!(resolve_mode("0644", 0o777, false), Ok(0o644));
+        assert_eq!(resolve_mode("644", 0, false), Ok(0o644));
+        assert_eq!(resolve_mode("4755", 0, false), Ok(0o4755));
+        assert!(resolve_mode("0999", 0, false).is_err());
+        assert!(resolve_mode("77777", 0, false).is_err());
+    }
+
+    #[test]
+    fn test_resolve_symbolic_mode() {
+        assert_eq!(resolve_mode("u+x", 0o644, false), Ok(0o744));
+        assert_eq!(resolve_mode("o-rwx", 0o757, false), Ok(0o750));
+        assert_eq!(resolve_mode("u+x,g-w", 0o664, false), Ok(0o744));
+        assert_eq!(resolve_mode("u=rw,go=r", 0o777, false), Ok(0o644));
+        assert_eq!(resolve_mode("a+r", 0o600, false), Ok(0o644));
+        assert_eq!(resolve_mode("+x", 0o644, false), Ok(0o755));
+        assert_eq!(resolve_mode("u+r-w", 0o200, false), Ok(0o400));
+        assert_eq!(resolve_mode("g=u", 0o640, false), Ok(0o660));
+        assert_eq!(resolve_mode("go=", 0o755, false), Ok(0o700));
+        assert_eq!(resolve_mode("u+s,+t", 0o755, false), Ok(0o5755));
+
+        // X only adds execute to directories and files something can already execute
+        assert_eq!(resolve_mode("a+X", 0o644, false), Ok(0o644));
+        assert_eq!(resolve_mode("a+X", 0o644, true), Ok(0o755));
+        assert_eq!(resolve_mode("a+X", 0o744, false), Ok(0o755));
+
+        assert!(resolve_mode("u+q", 0, false).is_err());
+        assert!(resolve_mode("z+x", 0, false).is_err());
+        assert!(resolve_mode("rw", 0, false).is_err());
+        assert!(resolve_mode("", 0, false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recursive_mode_reports_changes() {
+        use crate::executor::LocalConnection;
+        use crate::inventory::Host;
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("site");
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        let page = root.join("assets").join("app.js");
+        std::fs::write(&page, "").unwrap();
+        std::fs::set_permissions(&page, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let ctx = ExecutionContext::new(
+            Arc::new(Host::new("localhost")),
+            std::collections::HashMap::new(),
+        );
+        let conn = LocalConnection::new("localhost");
+        let module = FileModule::new();
+        let run = || {
+            module.execute_with_params(
+                &ctx,
+                &conn,
+                root.to_str().unwrap(),
+                FileState::Directory,
+                None,
+                None,
+                None,
+                None,
+                Some("go+rX".to_string()),
+                true,
+            )
+        };
+
+        let output = run().await.unwrap();
+        assert!(output.changed);
+        let mode = std::fs::metadata(&page).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+
+        let output = run().await.unwrap();
+        assert!(!output.changed);
+    }
+
+    #[tokio::test]
+    async fn test_check_mode_reports_only_real_changes() {
+        use crate::executor::LocalConnection;
+        use crate::inventory::Host;
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let conf = dir.path().join("app.conf");
+        std::fs::write(&conf, "port = 80\n").unwrap();
+        std::fs::set_permissions(&conf, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let source = dir.path().join("app.conf.src");
+        std::fs::write(&source, "port = 80\n").unwrap();
+        let link = dir.path().join("current");
+        std::os::unix::fs::symlink(&conf, &link).unwrap();
+
+        let ctx = ExecutionContext::new(
+            Arc::new(Host::new("localhost")),
+            std::collections::HashMap::new(),
+        )
+        .with_check_mode(true);
+        let conn = LocalConnection::new("localhost");
+        let module = FileModule::new();
+        let path = |p: &std::path::Path| p.to_string_lossy().to_string();
+        let check =
+            |path: String, state: FileState, source: Option<String>, mode: Option<&'static str>| {
+                let (ctx, conn, module) = (&ctx, &conn, &module);
+                async move {
+                    module
+                        .execute_with_params(
+                            ctx,
+                            conn,
+                            &path,
+                            state,
+                            source,
+                            None,
+                            None,
+                            None,
+                            mode.map(str::to_string),
+                            false,
+                        )
+                        .await
+                        .unwrap()
+                        .changed
+                }
+            };
+
+        let conf_path = path(&conf);
+        assert!(
+            !check(
+                conf_path.clone(),
+                FileState::File,
+                Some(path(&source)),
+                None
+            )
+            .await
+        );
+        assert!(!check(conf_path.clone(), FileState::File, None, Some("0644")).await);
+        assert!(check(conf_path.clone(), FileState::File, None, Some("0600")).await);
+        assert!(!check(path(dir.path()), FileState::Directory, None, None).await);
+        assert!(
+            check(
+                path(&dir.path().join("new")),
+                FileState::Directory,
+                None,
+                None
+            )
+            .await
+        );
+        assert!(!check(path(&link), FileState::Link, Some(conf_path.clone()), None).await);
+        assert!(check(path(&link), FileState::Link, Some(path(&source)), None).await);
+        assert!(check(conf_path.clone(), FileState::Absent, None, None).await);
+        assert!(
+            !check(
+                path(&dir.path().join("gone")),
+                FileState::Absent,
+                None,
+                None
+            )
+            .await
+        );
+
+        std::fs::write(&source, "port = 8080\n").unwrap();
+        assert!(check(conf_path, FileState::File, Some(path(&source)), None).await);
+        assert_eq!(std::fs::read_to_string(&conf).unwrap(), "port = 80\n");
+    }
 }
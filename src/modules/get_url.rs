@@ -0,0 +1,421 @@
+// Get URL module - download files on the remote host with checksum verification
+
+use async_trait::async_trait;
+
+use super::file::resolve_mode;
+use super::{module_error, run_checked, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+
+pub struct GetUrlModule;
+
+impl Default for GetUrlModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A parsed `algorithm:digest` checksum
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Checksum {
+    algorithm: String,
+    digest: String,
+}
+
+impl Checksum {
+    /// Parse a checksum spec such as `sha256:abc123...`
+    fn parse(spec: &str) -> Option<Self> {
+        let (algorithm, digest) = spec.split_once(':')?;
+        let algorithm = algorithm.trim().to_lowercase();
+        let digest = digest.trim().to_lowercase();
+        if digest.is_empty() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        match algorithm.as_str() {
+            "md5" | "sha1" | "sha224" | "sha256" | "sha384" | "sha512" => {
+                Some(Checksum { algorithm, digest })
+            }
+            _ => None,
+        }
+    }
+
+    /// Remote command that prints the digest of `path`
+    fn command(&self, path: &str) -> String {
+        format!(
+            "{}sum {} | cut -d' ' -f1",
+            self.algorithm,
+            shell_quote(path)
+        )
+    }
+}
+
+impl GetUrlModule {
+    pub fn new() -> Self {
+        GetUrlModule
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        url: &str,
+        dest: &str,
+        checksum: Option<String>,
+        owner: Option<String>,
+        group: Option<String>,
+        mode: Option<String>,
+    ) -> Result<TaskOutput, NexusError> {
+        let checksum = checksum
+            .map(|spec| {
+                Checksum::parse(&spec).ok_or_else(|| {
+                    module_error(
+                        "get_url",
+                        conn,
+                        format!("Invalid checksum: {}", spec),
+                        None,
+                        Some("Use the form 'sha256:<hex digest>'"),
+                    )
+                })
+            })
+            .transpose()?;
+
+        // Reject a malformed mode before touching anything
+        if let Some(ref m) = mode {
+            resolve_mode(m, 0, false).map_err(|e| {
+                module_error(
+                    "get_url",
+                    conn,
+                    e,
+                    None,
+                    Some("Use an octal mode like \"0644\" or symbolic clauses like \"u=rw,go=r\""),
+                )
+            })?;
+        }
+        let ownership = match (&owner, &group) {
+            (Some(o), Some(g)) => Some(format!("{}:{}", o, g)),
+            (Some(o), None) => Some(o.clone()),
+            (None, Some(g)) => Some(format!(":{}", g)),
+            (None, None) => None,
+        };
+
+        // Decide whether the existing file can be kept
+        let exists = conn
+            .exec(&format!("test -f {}", shell_quote(dest)))
+            .await?
+            .success();
+        let up_to_date = match (&checksum, exists) {
+            (_, false) => false,
+            (None, true) => true,
+            (Some(sum), true) => remote_digest(ctx, conn, sum, dest).await? == sum.digest,
+        };
+
+        // Check mode
+        if ctx.check_mode {
+            if !up_to_date {
+                return Ok(TaskOutput::changed()
+                    .with_stdout(format!("Would download {} to {}", url, dest)));
+            }
+            let mut would = Vec::new();
+            if let Some(ref m) = mode {
+                if desired_mode(conn, dest, m).await?.is_some() {
+                    would.push(format!("Would set mode {} on {}", m, dest));
+                }
+            }
+            if ownership.is_some() && !owned_by(conn, dest, &owner, &group).await? {
+                would.push(format!(
+                    "Would set ownership {} on {}",
+                    ownership.as_deref().unwrap_or_default(),
+                    dest
+                ));
+            }
+            return Ok(if would.is_empty() {
+                TaskOutput::success().with_stdout(format!("{} is up to date", dest))
+            } else {
+                TaskOutput::changed().with_stdout(would.join("\n"))
+            });
+        }
+
+        let mut changed = false;
+        let mut output_lines = Vec::new();
+
+        if up_to_date {
+            output_lines.push(format!("{} is up to date", dest));
+        } else {
+            self.download(ctx, conn, url, dest, checksum.as_ref())
+                .await?;
+            changed = true;
+            output_lines.push(format!("Downloaded {} to {}", url, dest));
+        }
+
+        if let Some(ref m) = mode {
+            if let Some(bits) = desired_mode(conn, dest, m).await? {
+                run_checked(
+                    "get_url",
+                    ctx,
+                    conn,
+                    &format!("chmod {:04o} {}", bits, shell_quote(dest)),
+                    &format!("Failed to set mode on {}", dest),
+                    None,
+                )
+                .await?;
+                changed = true;
+                output_lines.push(format!("Set mode {} on {}", m, dest));
+            }
+        }
+
+        if let Some(ref ownership) = ownership {
+            if !owned_by(conn, dest, &owner, &group).await? {
+                run_checked(
+                    "get_url",
+                    ctx,
+                    conn,
+                    &format!("chown {} {}", shell_quote(ownership), shell_quote(dest)),
+                    &format!("Failed to set ownership on {}", dest),
+                    None,
+                )
+                .await?;
+                changed = true;
+                output_lines.push(format!("Set ownership {} on {}", ownership, dest));
+            }
+        }
+
+        let output = if changed {
+            TaskOutput::changed()
+        } else {
+            TaskOutput::success()
+        };
+        Ok(output.with_stdout(output_lines.join("\n")))
+    }
+
+    /// Download to a temporary file next to `dest`, verify it, then move it into place
+    async fn download(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        url: &str,
+        dest: &str,
+        checksum: Option<&Checksum>,
+    ) -> Result<(), NexusError> {
+        let fetch = if conn.exec("command -v curl").await?.success() {
+            "curl -fsSL -o"
+        } else if conn.exec("command -v wget").await?.success() {
+            "wget -q -O"
+        } else {
+            return Err(module_error(
+                "get_url",
+                conn,
+                "Neither curl nor wget is available".to_string(),
+                None,
+                Some("Install curl or wget on the target host"),
+            ));
+        };
+
+        let tmp = format!("{}.nexus-tmp", dest);
+        let result = conn
+            .exec(&ctx.wrap_command(&format!(
+                "{} {} {}",
+                fetch,
+                shell_quote(&tmp),
+                shell_quote(url)
+            )))
+            .await?;
+        if !result.success() {
+            conn.exec(&ctx.wrap_command(&format!("rm -f {}", shell_quote(&tmp))))
+                .await?;
+            return Err(module_error(
+                "get_url",
+                conn,
+                format!("Failed to download {}", url),
+                Some(result.stderr),
+                Some("Check the URL and network access from the target host"),
+            ));
+        }
+
+        if let Some(sum) = checksum {
+            let actual = remote_digest(ctx, conn, sum, &tmp).await?;
+            if actual != sum.digest {
+                conn.exec(&ctx.wrap_command(&format!("rm -f {}", shell_quote(&tmp))))
+                    .await?;
+                return Err(module_error(
+                    "get_url",
+                    conn,
+                    format!(
+                        "Checksum mismatch for {}: expected {}:{}, got {}",
+                        url, sum.algorithm, sum.digest, actual
+                    ),
+                    None,
+                    Some("Verify the checksum or the download source"),
+                ));
+            }
+        }
+
+        run_checked(
+            "get_url",
+            ctx,
+            conn,
+            &format!("mv -f {} {}", shell_quote(&tmp), shell_quote(dest)),
+            &format!("Failed to move download into place at {}", dest),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Compute the digest of a remote file
+async fn remote_digest(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    checksum: &Checksum,
+    path: &str,
+) -> Result<String, NexusError> {
+    let result = conn
+        .exec(&ctx.wrap_command(&checksum.command(path)))
+        .await?;
+    Ok(result.stdout.trim().to_lowercase())
+}
+
+/// The permission bits `mode` asks for, or `None` when `path` already has them
+async fn desired_mode(
+    conn: &dyn Connection,
+    path: &str,
+    mode: &str,
+) -> Result<Option<u32>, NexusError> {
+    let stat = conn
+        .exec(&format!("stat -c '%a' {}", shell_quote(path)))
+        .await?;
+    let current = u32::from_str_radix(stat.stdout.trim(), 8).ok();
+    let desired = resolve_mode(mode, current.unwrap_or(0), false)
+        .map_err(|e| module_error("get_url", conn, e, None, None))?;
+    Ok((current != Some(desired)).then_some(desired))
+}
+
+/// Whether `path` already has the requested owner and group
+async fn owned_by(
+    conn: &dyn Connection,
+    path: &str,
+    owner: &Option<String>,
+    group: &Option<String>,
+) -> Result<bool, NexusError> {
+    let current = conn
+        .exec(&format!("stat -c '%U:%G' {}", shell_quote(path)))
+        .await?;
+    let (current_owner, current_group) = current
+        .stdout
+        .trim()
+        .split_once(':')
+        .map(|(o, g)| (o.to_string(), g.to_string()))
+        .unwrap_or_default();
+    Ok(owner.as_ref().map_or(true, |o| *o == current_owner)
+        && group.as_ref().map_or(true, |g| *g == current_group))
+}
+
+#[async_trait]
+impl Module for GetUrlModule {
+    fn name(&self) -> &'static str {
+        "get_url"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_checksum_parse() {
+        let sum = Checksum::parse("SHA256:ABCDEF0123").unwrap();
+        assert_eq!(sum.algorithm, "sha256");
+        assert_eq!(sum.digest, "abcdef0123");
+        assert_eq!(sum.command("/tmp/f"), "sha256sum '/tmp/f' | cut -d' ' -f1");
+
+        assert!(Checksum::parse("crc32:abcd").is_none());
+        assert!(Checksum::parse("sha256:not-hex").is_none());
+        assert!(Checksum::parse("abcdef").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_with_matching_checksum_is_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("payload");
+        std::fs::write(&dest, "hello\n").unwrap();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let output = GetUrlModule::new()
+            .execute_with_params(
+                &ctx,
+                &LocalConnection::new("localhost"),
+                "http://invalid.example/payload",
+                &dest.to_string_lossy(),
+                Some(
+                    "sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+                        .to_string(),
+                ),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(!output.changed);
+        assert!(output.stdout.contains("up to date"));
+    }
+
+    #[tokio::test]
+    async fn test_check_mode_compares_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("payload");
+        std::fs::write(&dest, "hello\n").unwrap();
+        std::fs::set_permissions(&dest, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new())
+            .with_check_mode(true);
+        let conn = LocalConnection::new("localhost");
+        let dest_str = dest.to_string_lossy().to_string();
+        let run = |mode: &'static str| {
+            let (ctx, conn, dest) = (&ctx, &conn, &dest_str);
+            async move {
+                GetUrlModule::new()
+                    .execute_with_params(
+                        ctx,
+                        conn,
+                        "http://invalid.example/payload",
+                        dest,
+                        None,
+                        None,
+                        None,
+                        Some(mode.to_string()),
+                    )
+                    .await
+            }
+        };
+
+        assert!(!run("0644").await.unwrap().changed);
+        assert!(!run("u=rw,go=r").await.unwrap().changed);
+        let output = run("0600").await.unwrap();
+        assert!(output.changed);
+        assert!(output.stdout.contains("Would set mode 0600"));
+        assert_eq!(
+            std::fs::metadata(&dest).unwrap().permissions().mode() & 0o7777,
+            0o644
+        );
+
+        // A mode that is neither octal nor symbolic never reaches the shell
+        assert!(run("0644; touch /tmp/x").await.is_err());
+    }
+}
@@ -0,0 +1,84 @@
+// Group by module - add hosts to inventory groups built while the playbook runs
+
+use async_trait::async_trait;
+
+use super::Module;
+use crate::executor::{ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{Expression, Value};
+use crate::runtime::evaluate_expression;
+
+pub struct GroupByModule;
+
+impl Default for GroupByModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GroupByModule {
+    pub fn new() -> Self {
+        GroupByModule
+    }
+
+    /// Evaluate `key` to the name of the group this host belongs in, returned as the
+    /// task's `add_group`. Adding the host to the inventory is up to the scheduler,
+    /// which owns it. Nothing changes on the host, so this runs in check mode.
+    pub fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        key: &Expression,
+    ) -> Result<TaskOutput, NexusError> {
+        let group = group_name(&evaluate_expression(key, ctx)?.to_string());
+        if group.is_empty() {
+            return Ok(TaskOutput::failed(
+                "group_by key evaluated to an empty group name",
+            ));
+        }
+        Ok(TaskOutput::success()
+            .with_stdout(format!("Added to group {}", group))
+            .with_data("add_group", Value::String(group)))
+    }
+}
+
+/// A usable group name for `key`: characters other than letters, digits and
+/// underscores become underscores, like Ansible (`distro-Ubuntu 24` -> `distro_Ubuntu_24`)
+fn group_name(key: &str) -> String {
+    key.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Module for GroupByModule {
+    fn name(&self) -> &'static str {
+        "group_by"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_name() {
+        assert_eq!(group_name("distro_Ubuntu"), "distro_Ubuntu");
+        assert_eq!(group_name(" os-Debian 12.4 "), "os_Debian_12_4");
+        assert_eq!(group_name(""), "");
+    }
+}
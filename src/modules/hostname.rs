@@ -0,0 +1,175 @@
+// Hostname module - set the system hostname
+
+use async_trait::async_trait;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::facts::update_fact;
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+/// How the hostname is set on a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostnameStrategy {
+    /// `hostnamectl set-hostname`, on hosts running systemd
+    Systemd,
+    /// Write `/etc/hostname` and apply it with `hostname`, e.g. Alpine or containers
+    File,
+}
+
+pub struct HostnameModule;
+
+impl Default for HostnameModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostnameModule {
+    pub fn new() -> Self {
+        HostnameModule
+    }
+
+    /// Set the hostname to `name`, for the running system and in `/etc/hostname` so
+    /// it survives a reboot. Uses `hostnamectl` where systemd runs, otherwise writes
+    /// `/etc/hostname` and runs `hostname`. The task reports `changed` only when either
+    /// differed, and afterwards `ansible_hostname` and `ansible_hostname_short` hold
+    /// the new name.
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        name: &str,
+    ) -> Result<TaskOutput, NexusError> {
+        if conn.is_windows() {
+            return Err(module_error(
+                "hostname",
+                conn,
+                "hostname does not support Windows hosts".to_string(),
+                None,
+                None,
+            ));
+        }
+        let name = name.trim();
+        if !is_valid_hostname(name) {
+            return Err(module_error(
+                "hostname",
+                conn,
+                format!("Invalid hostname '{}'", name),
+                None,
+                Some("Use letters, digits, hyphens and dots, e.g. web01.example.com"),
+            ));
+        }
+
+        let current = conn.exec("hostname").await?.stdout.trim().to_string();
+        let persisted = conn
+            .exec("cat /etc/hostname 2>/dev/null")
+            .await?
+            .stdout
+            .trim()
+            .to_string();
+        if current == name && persisted == name {
+            return Ok(TaskOutput::success()
+                .with_stdout(format!("Hostname is already {}", name))
+                .with_data("hostname", Value::String(name.to_string())));
+        }
+
+        if ctx.check_mode {
+            return Ok(TaskOutput::changed()
+                .with_stdout(format!("Would set hostname to {} (was {})", name, current))
+                .with_data("hostname", Value::String(name.to_string())));
+        }
+
+        let quoted = shell_quote(name);
+        let cmd = match detect_strategy(conn).await? {
+            HostnameStrategy::Systemd => format!("hostnamectl set-hostname {}", quoted),
+            HostnameStrategy::File => {
+                format!("echo {0} > /etc/hostname && hostname {0}", quoted)
+            }
+        };
+        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+        if !result.success() {
+            return Err(module_error(
+                "hostname",
+                conn,
+                format!("Failed to set hostname to {}", name),
+                Some(result.stderr),
+                Some("Setting the hostname needs root; run the task with sudo"),
+            ));
+        }
+
+        update_fact(ctx, "hostname", Value::String(name.to_string()));
+        update_fact(
+            ctx,
+            "hostname_short",
+            Value::String(short_hostname(name).to_string()),
+        );
+        Ok(TaskOutput::changed()
+            .with_stdout(format!("Set hostname to {} (was {})", name, current))
+            .with_data("hostname", Value::String(name.to_string())))
+    }
+}
+
+/// `hostnamectl` where systemd is running, `/etc/hostname` everywhere else
+async fn detect_strategy(conn: &dyn Connection) -> Result<HostnameStrategy, NexusError> {
+    let systemd = conn
+        .exec("command -v hostnamectl >/dev/null 2>&1 && test -d /run/systemd/system")
+        .await?
+        .success();
+    Ok(if systemd {
+        HostnameStrategy::Systemd
+    } else {
+        HostnameStrategy::File
+    })
+}
+
+/// A hostname made of dot-separated labels of letters, digits and inner hyphens
+fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// The first label of a hostname (`web01.example.com` -> `web01`)
+fn short_hostname(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+#[async_trait]
+impl Module for HostnameModule {
+    fn name(&self) -> &'static str {
+        "hostname"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostname_validation() {
+        assert!(is_valid_hostname("web01"));
+        assert!(is_valid_hostname("web-01.example.com"));
+        assert!(!is_valid_hostname(""));
+        assert!(!is_valid_hostname("-web"));
+        assert!(!is_valid_hostname("web..example.com"));
+        assert!(!is_valid_hostname("web_01"));
+        assert!(!is_valid_hostname("web01; reboot"));
+
+        assert_eq!(short_hostname("web01.example.com"), "web01");
+        assert_eq!(short_hostname("web01"), "web01");
+    }
+}
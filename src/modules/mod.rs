@@ -1,34 +1,85 @@
 // Built-in modules for Nexus
 
+mod assert;
 mod async_status;
+mod authorized_key;
+mod blockinfile;
 mod command;
+mod custom;
+mod debug;
+mod fetch;
 mod file;
+mod get_url;
+mod group_by;
+mod hostname;
+mod mount;
 mod package;
+mod pip;
+mod reboot;
 mod service;
+mod set_fact;
 mod shell;
+mod slurp;
+mod stat;
+mod synchronize;
+mod systemd;
 pub mod template;
+mod timezone;
 mod user;
+mod wait_for;
 
+pub use assert::AssertModule;
 pub use async_status::AsyncStatusModule;
+pub use authorized_key::AuthorizedKeyModule;
+pub use blockinfile::BlockInFileModule;
 pub use command::CommandModule;
+pub use custom::CustomModule;
+pub use debug::DebugModule;
+pub use fetch::FetchModule;
 pub use file::FileModule;
+pub use get_url::GetUrlModule;
+pub use group_by::GroupByModule;
+pub use hostname::HostnameModule;
+pub use mount::MountModule;
 pub use package::PackageModule;
+pub use pip::PipModule;
+pub use reboot::RebootModule;
 pub use service::ServiceModule;
+pub use set_fact::SetFactModule;
 pub use shell::ShellModule;
-pub use template::TemplateEngine;
-pub use user::UserModule;
+pub use slurp::SlurpModule;
+pub use stat::StatModule;
+pub use synchronize::SynchronizeModule;
+pub use systemd::SystemdModule;
+pub use template::{render_template_file, TemplateEngine};
+pub use timezone::TimezoneModule;
+pub use user::{UserModule, UserParams};
+pub use wait_for::WaitForModule;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
 
 use async_trait::async_trait;
 
-use crate::executor::{Connection, ExecutionContext, LocalConnection, SshConnection, TaskOutput};
+#[cfg(feature = "winrm")]
+use crate::executor::WinRmConnection;
+use crate::executor::{
+    ps_quote, CommandResult, Connection, ConnectionPool, DockerConnection, ExecutionContext,
+    LocalConnection, SshConnection, TaskOutput,
+};
+use crate::output::diff::Diff;
 use crate::output::errors::{ModuleError, NexusError};
-use crate::parser::ast::ModuleCall;
+use crate::parser::ast::{Expression, ModuleCall};
 use crate::runtime::evaluate_expression;
 
 /// Wrapper for different connection types
 pub enum AnyConnection {
     Ssh(SshConnection),
     Local(LocalConnection),
+    Docker(DockerConnection),
+    #[cfg(feature = "winrm")]
+    WinRm(WinRmConnection),
 }
 
 impl AnyConnection {
@@ -37,6 +88,18 @@ impl AnyConnection {
         match self {
             AnyConnection::Ssh(conn) => conn,
             AnyConnection::Local(conn) => conn,
+            AnyConnection::Docker(conn) => conn,
+            #[cfg(feature = "winrm")]
+            AnyConnection::WinRm(conn) => conn,
+        }
+    }
+
+    /// Stop SSH commands still running at `deadline`. Other connections run commands
+    /// as futures (or child processes killed on drop), so dropping them stops them.
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        match self {
+            AnyConnection::Ssh(conn) => AnyConnection::Ssh(conn.with_deadline(deadline)),
+            conn => conn,
         }
     }
 }
@@ -58,22 +121,135 @@ pub trait Module: Send + Sync {
 /// Module executor that dispatches to the appropriate module
 pub struct ModuleExecutor {
     package: PackageModule,
+    pip: PipModule,
     service: ServiceModule,
+    systemd: SystemdModule,
     file: FileModule,
     command: CommandModule,
     shell: ShellModule,
     user: UserModule,
+    wait_for: WaitForModule,
+    get_url: GetUrlModule,
+    slurp: SlurpModule,
+    stat: StatModule,
+    async_status: AsyncStatusModule,
+    fetch: FetchModule,
+    mount: MountModule,
+    reboot: RebootModule,
+    synchronize: SynchronizeModule,
+    authorized_key: AuthorizedKeyModule,
+    blockinfile: BlockInFileModule,
+    hostname: HostnameModule,
+    timezone: TimezoneModule,
+    debug: DebugModule,
+    assert: AssertModule,
+    set_fact: SetFactModule,
+    group_by: GroupByModule,
+    custom: CustomModule,
 }
 
 impl ModuleExecutor {
     pub fn new() -> Self {
         ModuleExecutor {
             package: PackageModule::new(),
+            pip: PipModule::new(),
             service: ServiceModule::new(),
+            systemd: SystemdModule::new(),
             file: FileModule::new(),
             command: CommandModule::new(),
             shell: ShellModule::new(),
             user: UserModule::new(),
+            wait_for: WaitForModule::new(),
+            get_url: GetUrlModule::new(),
+            slurp: SlurpModule::new(),
+            stat: StatModule::new(),
+            async_status: AsyncStatusModule::new(),
+            fetch: FetchModule::new(),
+            mount: MountModule::new(),
+            reboot: RebootModule::new(),
+            synchronize: SynchronizeModule::new(),
+            authorized_key: AuthorizedKeyModule::new(),
+            blockinfile: BlockInFileModule::new(),
+            hostname: HostnameModule::new(),
+            timezone: TimezoneModule::new(),
+            debug: DebugModule::new(),
+            assert: AssertModule::new(),
+            set_fact: SetFactModule::new(),
+            group_by: GroupByModule::new(),
+            custom: CustomModule::default(),
+        }
+    }
+
+    /// Look up modules that aren't built in in `paths`, in order
+    pub fn with_module_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.custom = CustomModule::new(paths);
+        self
+    }
+
+    /// Execute a module call that may need to manage the host's pooled connections,
+    /// like `reboot` dropping and re-establishing them or `synchronize` running rsync
+    /// over the same SSH settings
+    pub async fn execute_with_pool(
+        &self,
+        module_call: &ModuleCall,
+        ctx: &ExecutionContext,
+        conn: &AnyConnection,
+        pool: &ConnectionPool,
+    ) -> Result<TaskOutput, NexusError> {
+        match module_call {
+            ModuleCall::Reboot {
+                reboot_timeout,
+                connect_timeout,
+                post_reboot_delay,
+                test_command,
+            } => {
+                let test_command = match test_command {
+                    Some(cmd) => evaluate_expression(cmd, ctx)?.to_string(),
+                    None => "whoami".to_string(),
+                };
+                self.reboot
+                    .execute_with_params(
+                        ctx,
+                        conn,
+                        pool,
+                        *reboot_timeout,
+                        *connect_timeout,
+                        *post_reboot_delay,
+                        &test_command,
+                    )
+                    .await
+            }
+            ModuleCall::Synchronize {
+                src,
+                dest,
+                mode,
+                delete,
+                archive,
+                checksum,
+                rsync_opts,
+            } => {
+                let src = evaluate_expression(src, ctx)?.to_string();
+                let dest = evaluate_expression(dest, ctx)?.to_string();
+                let rsync_opts = rsync_opts
+                    .iter()
+                    .map(|opt| evaluate_expression(opt, ctx).map(|v| v.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.synchronize
+                    .execute_with_params(
+                        ctx,
+                        conn,
+                        pool,
+                        &src,
+                        &dest,
+                        *mode,
+                        *delete,
+                        *archive,
+                        *checksum,
+                        &rsync_opts,
+                    )
+                    .await
+            }
+            _ => self.execute(module_call, ctx, conn).await,
         }
     }
 
@@ -85,10 +261,22 @@ impl ModuleExecutor {
         conn: &AnyConnection,
     ) -> Result<TaskOutput, NexusError> {
         match module_call {
-            ModuleCall::Package { name, state } => {
-                let name_val = evaluate_expression(name, ctx)?;
+            ModuleCall::Package {
+                name,
+                state,
+                update_cache,
+                autoremove,
+            } => {
+                let names = evaluate_names(name, ctx)?;
                 self.package
-                    .execute_with_params(ctx, conn.as_connection(), &name_val.to_string(), *state)
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &names,
+                        *state,
+                        *update_cache,
+                        *autoremove,
+                    )
                     .await
             }
 
@@ -109,6 +297,64 @@ impl ModuleExecutor {
                     .await
             }
 
+            ModuleCall::Systemd {
+                name,
+                state,
+                enabled,
+                daemon_reload,
+                masked,
+                scope,
+            } => {
+                let name_val = name
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?
+                    .map(|v| v.to_string());
+                self.systemd
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        name_val.as_deref(),
+                        *state,
+                        *enabled,
+                        *daemon_reload,
+                        *masked,
+                        *scope,
+                    )
+                    .await
+            }
+
+            ModuleCall::Pip {
+                name,
+                state,
+                version,
+                virtualenv,
+                virtualenv_command,
+                requirements,
+                executable,
+            } => {
+                let names = evaluate_names(name, ctx)?;
+                let eval_opt = |e: &Option<crate::parser::ast::Expression>| {
+                    e.as_ref()
+                        .map(|e| evaluate_expression(e, ctx).map(|v| v.to_string()))
+                        .transpose()
+                };
+
+                self.pip
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &names,
+                        *state,
+                        eval_opt(version)?,
+                        eval_opt(virtualenv)?,
+                        eval_opt(virtualenv_command)?,
+                        eval_opt(requirements)?,
+                        eval_opt(executable)?,
+                    )
+                    .await
+            }
+
             ModuleCall::File {
                 path,
                 state,
@@ -117,7 +363,16 @@ impl ModuleExecutor {
                 owner,
                 group,
                 mode,
+                recurse,
+                creates,
+                removes,
             } => {
+                if let Some(skipped) =
+                    skip_for_guards(ctx, conn.as_connection(), creates, removes).await?
+                {
+                    return Ok(skipped);
+                }
+
                 let path_val = evaluate_expression(path, ctx)?;
                 let source_val = source
                     .as_ref()
@@ -151,6 +406,7 @@ impl ModuleExecutor {
                         owner_val.as_ref().map(|v| v.to_string()),
                         group_val.as_ref().map(|v| v.to_string()),
                         mode_val.as_ref().map(|v| v.to_string()),
+                        *recurse,
                     )
                     .await
             }
@@ -159,6 +415,9 @@ impl ModuleExecutor {
                 cmd,
                 creates,
                 removes,
+                environment,
+                stdin,
+                stdin_add_newline,
             } => {
                 let cmd_val = evaluate_expression(cmd, ctx)?;
                 let creates_val = creates
@@ -177,6 +436,8 @@ impl ModuleExecutor {
                         &cmd_val.to_string(),
                         creates_val.as_ref().map(|v| v.to_string()),
                         removes_val.as_ref().map(|v| v.to_string()),
+                        &evaluate_environment(environment, ctx)?,
+                        evaluate_stdin(stdin, *stdin_add_newline, ctx)?,
                     )
                     .await
             }
@@ -186,6 +447,9 @@ impl ModuleExecutor {
                 chdir,
                 creates,
                 removes,
+                environment,
+                stdin,
+                stdin_add_newline,
             } => {
                 let cmd_val = evaluate_expression(command, ctx)?;
                 let chdir_val = chdir
@@ -209,6 +473,8 @@ impl ModuleExecutor {
                         chdir_val.as_ref().map(|v| v.to_string()),
                         creates_val.as_ref().map(|v| v.to_string()),
                         removes_val.as_ref().map(|v| v.to_string()),
+                        &evaluate_environment(environment, ctx)?,
+                        evaluate_stdin(stdin, *stdin_add_newline, ctx)?,
                     )
                     .await
             }
@@ -219,46 +485,70 @@ impl ModuleExecutor {
                 uid,
                 gid,
                 groups,
+                append,
                 shell,
                 home,
                 create_home,
+                system,
+                password,
+                password_expire_max,
+                password_expire_min,
+                expires,
+                generate_ssh_key,
+                ssh_key_type,
+                ssh_key_file,
             } => {
-                let name_val = evaluate_expression(name, ctx)?;
-                let uid_val = uid
-                    .as_ref()
-                    .map(|e| evaluate_expression(e, ctx))
-                    .transpose()?;
-                let gid_val = gid
-                    .as_ref()
-                    .map(|e| evaluate_expression(e, ctx))
-                    .transpose()?;
-                let shell_val = shell
-                    .as_ref()
-                    .map(|e| evaluate_expression(e, ctx))
-                    .transpose()?;
-                let home_val = home
-                    .as_ref()
-                    .map(|e| evaluate_expression(e, ctx))
-                    .transpose()?;
+                let name_val = evaluate_expression(name, ctx)?.to_string();
+                let eval = |expr: &Option<Expression>| {
+                    expr.as_ref()
+                        .map(|e| evaluate_expression(e, ctx))
+                        .transpose()
+                };
+                let string =
+                    |expr: &Option<Expression>| eval(expr).map(|v| v.map(|v| v.to_string()));
+                let number =
+                    |expr: &Option<Expression>| eval(expr).map(|v| v.and_then(|v| v.as_i64()));
 
                 let groups_val: Result<Vec<_>, _> = groups
                     .iter()
                     .map(|e| evaluate_expression(e, ctx).map(|v| v.to_string()))
                     .collect();
+                let expires_val = match eval(expires)? {
+                    Some(value) => Some(user::parse_expires(&value).map_err(|message| {
+                        NexusError::Module(Box::new(ModuleError {
+                            module: "user".to_string(),
+                            task_name: String::new(),
+                            host: conn.as_connection().host_name().to_string(),
+                            message,
+                            stderr: None,
+                            suggestion: Some(
+                                "Use a date such as 2026-12-31, or -1 to remove the expiry"
+                                    .to_string(),
+                            ),
+                        }))
+                    })?),
+                    None => None,
+                };
 
+                let params = UserParams {
+                    uid: number(uid)?.map(|i| i as u32),
+                    gid: number(gid)?.map(|i| i as u32),
+                    groups: groups_val?,
+                    append: *append,
+                    shell: string(shell)?,
+                    home: string(home)?,
+                    create_home: *create_home,
+                    system: *system,
+                    password: string(password)?,
+                    password_expire_max: number(password_expire_max)?,
+                    password_expire_min: number(password_expire_min)?,
+                    expires: expires_val,
+                    generate_ssh_key: *generate_ssh_key,
+                    ssh_key_type: string(ssh_key_type)?,
+                    ssh_key_file: string(ssh_key_file)?,
+                };
                 self.user
-                    .execute_with_params(
-                        ctx,
-                        conn.as_connection(),
-                        &name_val.to_string(),
-                        *state,
-                        uid_val.as_ref().and_then(|v| v.as_i64()).map(|i| i as u32),
-                        gid_val.as_ref().and_then(|v| v.as_i64()).map(|i| i as u32),
-                        groups_val?,
-                        shell_val.as_ref().map(|v| v.to_string()),
-                        home_val.as_ref().map(|v| v.to_string()),
-                        *create_home,
-                    )
+                    .execute_with_params(ctx, conn.as_connection(), &name_val, *state, &params)
                     .await
             }
 
@@ -268,6 +558,7 @@ impl ModuleExecutor {
                     function: Some(name.clone()),
                     message: "Function execution not yet implemented".to_string(),
                     suggestion: None,
+                    location: None,
                 })
             }
 
@@ -277,7 +568,16 @@ impl ModuleExecutor {
                 owner,
                 group,
                 mode,
+                validate,
+                creates,
+                removes,
             } => {
+                if let Some(skipped) =
+                    skip_for_guards(ctx, conn.as_connection(), creates, removes).await?
+                {
+                    return Ok(skipped);
+                }
+
                 let src_val = evaluate_expression(src, ctx)?;
                 let dest_val = evaluate_expression(dest, ctx)?;
                 let owner_val = owner
@@ -292,8 +592,12 @@ impl ModuleExecutor {
                     .as_ref()
                     .map(|e| evaluate_expression(e, ctx))
                     .transpose()?;
+                let validate_val = validate
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
 
-                // Check mode - skip template rendering and just report intent
+                // Check mode - render and compare, but leave the destination alone
                 if ctx.check_mode {
                     let mut msg = format!("Would deploy template {} to {}", src_val, dest_val);
                     if owner_val.is_some() || group_val.is_some() || mode_val.is_some() {
@@ -308,47 +612,401 @@ impl ModuleExecutor {
                             msg.push_str(&format!(" mode={}", m));
                         }
                     }
-                    return Ok(TaskOutput::changed().with_stdout(msg));
+                    if let Some(ref v) = validate_val {
+                        msg.push_str(&format!(" after validating with '{}'", v));
+                    }
+                    let rendered = render_template(&src_val.to_string(), ctx)?;
+                    let dest_string = dest_val.to_string();
+                    let mut output = self
+                        .file
+                        .execute_with_params(
+                            ctx,
+                            conn.as_connection(),
+                            &dest_string,
+                            crate::parser::ast::FileState::File,
+                            None,
+                            Some(rendered),
+                            owner_val.as_ref().map(|v| v.to_string()),
+                            group_val.as_ref().map(|v| v.to_string()),
+                            mode_val.as_ref().map(|v| v.to_string()),
+                            false,
+                        )
+                        .await?;
+                    if !output.changed {
+                        msg.push_str(" (no changes)");
+                    }
+                    output.stdout = msg;
+                    return Ok(output);
                 }
 
-                // Read and process the template file
-                let src_string = src_val.to_string();
-                let src_path = std::path::Path::new(&src_string);
-                if !src_path.exists() {
-                    return Err(NexusError::Io {
-                        message: format!("Template file not found: {}", src_val),
-                        path: Some(src_path.to_path_buf()),
-                    });
-                }
+                let rendered = render_template(&src_val.to_string(), ctx)?;
+                let dest_string = dest_val.to_string();
 
-                // Render template with context
-                let mut engine = TemplateEngine::new();
-                // Add the template's directory to search paths for includes
-                if let Some(parent) = src_path.parent() {
-                    engine.add_search_path(parent.to_string_lossy().to_string());
-                }
+                let current = if ctx.diff_mode || validate_val.is_some() {
+                    conn.as_connection().read_file(&dest_string).await.ok()
+                } else {
+                    None
+                };
+                let content_diff = ctx
+                    .diff_mode
+                    .then(|| template_diff(current.as_deref(), &rendered, &dest_string))
+                    .flatten();
 
-                let rendered = engine.render_file(src_path, ctx)?;
+                // Validate a staged copy and move it into place before the file module
+                // sees it, so a rejected template never touches the destination
+                let mut validated = false;
+                if let Some(ref cmd) = validate_val {
+                    if current.as_deref() != Some(rendered.as_str()) {
+                        deploy_validated(
+                            ctx,
+                            conn.as_connection(),
+                            &dest_string,
+                            &rendered,
+                            &cmd.to_string(),
+                        )
+                        .await?;
+                        validated = true;
+                    }
+                }
 
                 // Write the rendered content to the destination
-                self.file
+                let mut output = self
+                    .file
                     .execute_with_params(
                         ctx,
                         conn.as_connection(),
-                        &dest_val.to_string(),
+                        &dest_string,
                         crate::parser::ast::FileState::File,
                         None, // Don't use source, we have content
                         Some(rendered),
                         owner_val.as_ref().map(|v| v.to_string()),
                         group_val.as_ref().map(|v| v.to_string()),
                         mode_val.as_ref().map(|v| v.to_string()),
+                        false,
+                    )
+                    .await?;
+
+                if validated {
+                    output.changed = true;
+                    let line = format!("Validated and deployed {}", dest_string);
+                    output.stdout = if output.stdout.is_empty() {
+                        line
+                    } else {
+                        format!("{}\n{}", line, output.stdout)
+                    };
+                }
+                if content_diff.is_some() {
+                    output.diff = content_diff;
+                }
+
+                Ok(output)
+            }
+
+            ModuleCall::WaitFor {
+                host,
+                port,
+                path,
+                state,
+                timeout,
+                delay,
+            } => {
+                let host_val = host
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+                let port_val = port
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+                let path_val = path
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+
+                let port_num = match port_val {
+                    Some(v) => Some(v.to_string().parse::<u16>().map_err(|_| {
+                        NexusError::Module(Box::new(ModuleError {
+                            module: "wait_for".to_string(),
+                            task_name: String::new(),
+                            host: ctx.host.name.clone(),
+                            message: format!("Invalid port: {}", v),
+                            stderr: None,
+                            suggestion: Some(
+                                "Port must be a number between 1 and 65535".to_string(),
+                            ),
+                        }))
+                    })?),
+                    None => None,
+                };
+
+                self.wait_for
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        matches!(conn, AnyConnection::Local(_)),
+                        host_val.as_ref().map(|v| v.to_string()),
+                        port_num,
+                        path_val.as_ref().map(|v| v.to_string()),
+                        *state,
+                        *timeout,
+                        *delay,
                     )
                     .await
             }
 
+            ModuleCall::GetUrl {
+                url,
+                dest,
+                checksum,
+                owner,
+                group,
+                mode,
+            } => {
+                let url_val = evaluate_expression(url, ctx)?;
+                let dest_val = evaluate_expression(dest, ctx)?;
+                let checksum_val = checksum
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+                let owner_val = owner
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+                let group_val = group
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+                let mode_val = mode
+                    .as_ref()
+                    .map(|e| evaluate_expression(e, ctx))
+                    .transpose()?;
+
+                self.get_url
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &url_val.to_string(),
+                        &dest_val.to_string(),
+                        checksum_val.as_ref().map(|v| v.to_string()),
+                        owner_val.as_ref().map(|v| v.to_string()),
+                        group_val.as_ref().map(|v| v.to_string()),
+                        mode_val.as_ref().map(|v| v.to_string()),
+                    )
+                    .await
+            }
+
+            ModuleCall::Slurp { src } => {
+                let src_val = evaluate_expression(src, ctx)?;
+                self.slurp
+                    .execute_with_params(ctx, conn.as_connection(), &src_val.to_string())
+                    .await
+            }
+
+            ModuleCall::Stat { path, get_checksum } => {
+                let path_val = evaluate_expression(path, ctx)?;
+                self.stat
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &path_val.to_string(),
+                        *get_checksum,
+                    )
+                    .await
+            }
+
+            ModuleCall::AsyncStatus { job_id } => {
+                let job_id = evaluate_expression(job_id, ctx)?.to_string();
+                self.async_status
+                    .execute_with_job_id(ctx, conn.as_connection(), &job_id)
+                    .await
+            }
+
+            ModuleCall::Fetch { src, dest, flat } => {
+                let src_val = evaluate_expression(src, ctx)?;
+                let dest_val = evaluate_expression(dest, ctx)?;
+                self.fetch
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &src_val.to_string(),
+                        &dest_val.to_string(),
+                        *flat,
+                    )
+                    .await
+            }
+
+            // Meta actions act on scheduler state and are handled there
+            ModuleCall::Meta { action } => {
+                Ok(TaskOutput::success().with_stdout(format!("meta: {}", action.as_str())))
+            }
+
+            ModuleCall::Mount {
+                path,
+                src,
+                fstype,
+                opts,
+                dump,
+                passno,
+                fstab,
+                state,
+            } => {
+                let eval = |expr: &Option<crate::parser::ast::Expression>| {
+                    expr.as_ref()
+                        .map(|e| evaluate_expression(e, ctx).map(|v| v.to_string()))
+                        .transpose()
+                };
+                let path_val = evaluate_expression(path, ctx)?.to_string();
+                let fstab_val = eval(fstab)?.unwrap_or_else(|| "/etc/fstab".to_string());
+
+                self.mount
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &path_val,
+                        eval(src)?,
+                        eval(fstype)?,
+                        eval(opts)?,
+                        eval(dump)?,
+                        eval(passno)?,
+                        &fstab_val,
+                        *state,
+                    )
+                    .await
+            }
+
+            ModuleCall::AuthorizedKey {
+                user,
+                key,
+                state,
+                path,
+                exclusive,
+                manage_dir,
+            } => {
+                let user_val = evaluate_expression(user, ctx)?.to_string();
+                let keys = evaluate_names(key, ctx)?;
+                let path_val = path
+                    .as_ref()
+                    .map(|p| evaluate_expression(p, ctx).map(|v| v.to_string()))
+                    .transpose()?;
+
+                self.authorized_key
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &user_val,
+                        &keys,
+                        *state,
+                        path_val,
+                        *exclusive,
+                        *manage_dir,
+                    )
+                    .await
+            }
+
+            ModuleCall::BlockInFile {
+                path,
+                block,
+                marker,
+                state,
+                insertafter,
+                insertbefore,
+                create,
+            } => {
+                let eval = |expr: &Option<crate::parser::ast::Expression>| {
+                    expr.as_ref()
+                        .map(|e| evaluate_expression(e, ctx).map(|v| v.to_string()))
+                        .transpose()
+                };
+                let path_val = evaluate_expression(path, ctx)?.to_string();
+                let block_val = eval(block)?.unwrap_or_default();
+                let marker_val =
+                    eval(marker)?.unwrap_or_else(|| blockinfile::DEFAULT_MARKER.to_string());
+
+                self.blockinfile
+                    .execute_with_params(
+                        ctx,
+                        conn.as_connection(),
+                        &path_val,
+                        &block_val,
+                        &marker_val,
+                        *state,
+                        eval(insertafter)?.as_deref(),
+                        eval(insertbefore)?.as_deref(),
+                        *create,
+                    )
+                    .await
+            }
+
+            ModuleCall::Hostname { name } => {
+                let name_val = evaluate_expression(name, ctx)?.to_string();
+                self.hostname
+                    .execute_with_params(ctx, conn.as_connection(), &name_val)
+                    .await
+            }
+
+            ModuleCall::Timezone { name } => {
+                let name_val = evaluate_expression(name, ctx)?.to_string();
+                self.timezone
+                    .execute_with_params(ctx, conn.as_connection(), &name_val)
+                    .await
+            }
+
+            ModuleCall::Debug {
+                msg,
+                var,
+                verbosity,
+            } => self
+                .debug
+                .execute_with_params(ctx, msg.as_ref(), var.as_ref(), *verbosity),
+
+            ModuleCall::Assert {
+                that,
+                fail_msg,
+                success_msg,
+                quiet,
+            } => self.assert.execute_with_params(
+                ctx,
+                that,
+                fail_msg.as_ref(),
+                success_msg.as_ref(),
+                *quiet,
+            ),
+
+            // Storing cacheable facts is up to the scheduler, which owns the fact cache
+            ModuleCall::SetFact { facts, .. } => self.set_fact.execute_with_params(ctx, facts),
+
+            // Adding the host to the group is up to the scheduler, which owns the inventory
+            ModuleCall::GroupBy { key } => self.group_by.execute_with_params(ctx, key),
+
+            ModuleCall::Custom { name, args } => {
+                let args = evaluate_expression(args, ctx)?;
+                self.custom
+                    .execute_with_params(ctx, conn.as_connection(), name, args)
+                    .await
+            }
+
+            // Reconnecting after the reboot needs the pool, see execute_with_pool
+            ModuleCall::Reboot { .. } => Err(NexusError::Module(Box::new(ModuleError {
+                module: "reboot".to_string(),
+                task_name: String::new(),
+                host: conn.as_connection().host_name().to_string(),
+                message: "reboot can only run with access to the connection pool".to_string(),
+                stderr: None,
+                suggestion: None,
+            }))),
+
+            // rsync reuses the pool's SSH settings, see execute_with_pool
+            ModuleCall::Synchronize { .. } => Err(NexusError::Module(Box::new(ModuleError {
+                module: "synchronize".to_string(),
+                task_name: String::new(),
+                host: conn.as_connection().host_name().to_string(),
+                message: "synchronize can only run with access to the connection pool".to_string(),
+                stderr: None,
+                suggestion: None,
+            }))),
+
             ModuleCall::Facts { categories } => {
-                use crate::executor::facts::{FactCategory, FactGatherer};
-                use std::collections::HashMap;
+                use crate::executor::facts::{ansible_fact_vars, FactCategory, FactGatherer};
 
                 // Check mode - skip fact gathering and just report intent
                 if ctx.check_mode {
@@ -359,17 +1017,7 @@ impl ModuleExecutor {
                 // Parse category strings into FactCategory enum
                 let fact_categories: Vec<FactCategory> = categories
                     .iter()
-                    .filter_map(|cat| match cat.to_lowercase().as_str() {
-                        "system" => Some(FactCategory::System),
-                        "hardware" => Some(FactCategory::Hardware),
-                        "network" => Some(FactCategory::Network),
-                        "mounts" => Some(FactCategory::Mounts),
-                        "packages" => Some(FactCategory::Packages),
-                        "services" => Some(FactCategory::Services),
-                        "environment" => Some(FactCategory::Environment),
-                        "all" => Some(FactCategory::All),
-                        _ => None,
-                    })
+                    .filter_map(|cat| FactCategory::from_name(cat))
                     .collect();
 
                 // If no valid categories or empty, gather all
@@ -382,42 +1030,22 @@ impl ModuleExecutor {
                 // Gather facts - currently only supported for SSH connections
                 let facts = match conn {
                     AnyConnection::Ssh(ssh_conn) => {
-                        FactGatherer::gather(ssh_conn, &cats_to_gather)?
+                        FactGatherer::gather(ssh_conn, &cats_to_gather).await?
                     }
-                    AnyConnection::Local(_) => {
-                        // TODO: Implement local fact gathering
+                    _ => {
+                        // TODO: Implement fact gathering for other connection types
                         return Err(NexusError::Runtime {
                             function: Some("facts".to_string()),
-                            message: "Fact gathering not yet implemented for local connections"
+                            message: "Fact gathering is only implemented for SSH connections"
                                 .to_string(),
                             suggestion: Some("Use SSH connection for fact gathering".to_string()),
+                            location: None,
                         });
                     }
                 };
 
-                // Convert facts to Ansible-compatible names and store in context
-                let mut ansible_facts = HashMap::new();
-                for (key, value) in &facts {
-                    // Map internal fact names to ansible_* names
-                    let ansible_key = match key.as_str() {
-                        "hostname" => "ansible_hostname",
-                        "hostname_short" => "ansible_hostname_short",
-                        "os_family" => "ansible_os_family",
-                        "os_name" => "ansible_distribution",
-                        "os_version" => "ansible_distribution_version",
-                        "kernel_version" => "ansible_kernel",
-                        "architecture" => "ansible_architecture",
-                        "cpu_count" => "ansible_processor_count",
-                        "memory_total_mb" => "ansible_memtotal_mb",
-                        "default_ipv4" => "ansible_default_ipv4_address",
-                        "interfaces" => "ansible_interfaces",
-                        _ => key.as_str(),
-                    };
-                    ansible_facts.insert(ansible_key.to_string(), value.clone());
-                }
-
-                // Store all facts in context variables
-                for (key, value) in ansible_facts {
+                // Store all facts in context variables, under their Ansible names
+                for (key, value) in ansible_fact_vars(&facts) {
                     ctx.set_var(&key, value);
                 }
 
@@ -435,12 +1063,339 @@ impl ModuleExecutor {
     }
 }
 
+/// Render the template at `src` on the control node, with its directory on the
+/// include search path
+fn render_template(src: &str, ctx: &ExecutionContext) -> Result<String, NexusError> {
+    let src_path = std::path::Path::new(src);
+    if !src_path.exists() {
+        return Err(NexusError::Io {
+            message: format!("Template file not found: {}", src),
+            path: Some(src_path.to_path_buf()),
+        });
+    }
+
+    render_template_file(src_path, ctx)
+}
+
+/// Unified diff from the file currently at `dest` (`None` if there is none) to the
+/// rendered template, or `None` if the content wouldn't change
+fn template_diff(current: Option<&str>, rendered: &str, dest: &str) -> Option<Diff> {
+    match current {
+        Some(old) if old == rendered => None,
+        Some(old) => Some(Diff::new(
+            old,
+            rendered,
+            &format!("{} (before)", dest),
+            &format!("{} (after)", dest),
+        )),
+        None => Some(Diff::new(
+            "",
+            rendered,
+            "/dev/null",
+            &format!("{} (new)", dest),
+        )),
+    }
+}
+
+/// Stage rendered content next to `dest`, run the validator against it, and move it
+/// into place only if validation passes. On failure the staged file is removed and
+/// the validator's stderr is returned.
+async fn deploy_validated(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    dest: &str,
+    content: &str,
+    validate: &str,
+) -> Result<(), NexusError> {
+    let module_error = |message: String, stderr: Option<String>, suggestion: Option<String>| {
+        NexusError::Module(Box::new(ModuleError {
+            module: "template".to_string(),
+            task_name: String::new(),
+            host: conn.host_name().to_string(),
+            message,
+            stderr,
+            suggestion,
+        }))
+    };
+
+    if !validate.contains("%s") {
+        return Err(module_error(
+            format!("validate command must contain %s: {}", validate),
+            None,
+            Some("Use %s where the rendered file path goes, e.g. 'nginx -t -c %s'".to_string()),
+        ));
+    }
+
+    let staged = format!("{}.nexus-validate", dest);
+    let quoted = format!("'{}'", staged.replace('\'', "'\\''"));
+
+    // SFTP can't use sudo, so write through tee when escalating
+    if ctx.sudo {
+        let encoded = base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            content.as_bytes(),
+        );
+        let cmd = format!("echo {} | base64 -d | tee {} > /dev/null", encoded, quoted);
+        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+        if !result.success() {
+            return Err(module_error(
+                format!("Failed to stage template at {}", staged),
+                Some(result.stderr),
+                None,
+            ));
+        }
+    } else {
+        conn.write_file(&staged, content).await?;
+    }
+
+    let result = conn
+        .exec(&ctx.wrap_command(&validate.replace("%s", &quoted)))
+        .await?;
+    if !result.success() {
+        conn.exec(&ctx.wrap_command(&format!("rm -f {}", quoted)))
+            .await?;
+        let stderr = if result.stderr.trim().is_empty() {
+            result.stdout
+        } else {
+            result.stderr
+        };
+        return Err(module_error(
+            format!("Validation failed for {}: {}", dest, validate),
+            Some(stderr),
+            Some("The destination was left unchanged".to_string()),
+        ));
+    }
+
+    let dest_quoted = format!("'{}'", dest.replace('\'', "'\\''"));
+    let result = conn
+        .exec(&ctx.wrap_command(&format!("mv -f {} {}", quoted, dest_quoted)))
+        .await?;
+    if !result.success() {
+        return Err(module_error(
+            format!("Failed to move validated template into place at {}", dest),
+            Some(result.stderr),
+            None,
+        ));
+    }
+
+    Ok(())
+}
+
 impl Default for ModuleExecutor {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Evaluate name expressions (packages, keys), flattening any that produce a list
+pub(crate) fn evaluate_names(
+    exprs: &[crate::parser::ast::Expression],
+    ctx: &ExecutionContext,
+) -> Result<Vec<String>, NexusError> {
+    let mut names = Vec::new();
+    for expr in exprs {
+        match evaluate_expression(expr, ctx)? {
+            crate::parser::ast::Value::List(items) => {
+                names.extend(items.iter().map(|v| v.to_string()))
+            }
+            v => names.push(v.to_string()),
+        }
+    }
+    Ok(names)
+}
+
+/// Evaluate the play environment merged with a task's own, which wins on conflicts.
+/// Sorted by name so the command line is stable.
+pub(crate) fn evaluate_environment(
+    task_environment: &HashMap<String, crate::parser::ast::Expression>,
+    ctx: &ExecutionContext,
+) -> Result<Vec<(String, String)>, NexusError> {
+    let mut merged = ctx.environment.clone();
+    merged.extend(task_environment.clone());
+
+    let mut environment = merged
+        .iter()
+        .map(|(name, expr)| Ok((name.clone(), evaluate_expression(expr, ctx)?.to_string())))
+        .collect::<Result<Vec<_>, NexusError>>()?;
+    environment.sort();
+    Ok(environment)
+}
+
+/// Evaluate a command's `stdin`, appending a newline when `add_newline` is set
+fn evaluate_stdin(
+    stdin: &Option<crate::parser::ast::Expression>,
+    add_newline: bool,
+    ctx: &ExecutionContext,
+) -> Result<Option<String>, NexusError> {
+    let Some(expr) = stdin else {
+        return Ok(None);
+    };
+    let mut data = evaluate_expression(expr, ctx)?.to_string();
+    if add_newline {
+        data.push('\n');
+    }
+    Ok(Some(data))
+}
+
+/// Shell-quote a string for safe use in commands
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// A `module` error on `conn`'s host
+pub(crate) fn module_error(
+    module: &str,
+    conn: &dyn Connection,
+    message: String,
+    stderr: Option<String>,
+    suggestion: Option<&str>,
+) -> NexusError {
+    NexusError::Module(Box::new(ModuleError {
+        module: module.to_string(),
+        task_name: String::new(),
+        host: conn.host_name().to_string(),
+        message,
+        stderr,
+        suggestion: suggestion.map(|s| s.to_string()),
+    }))
+}
+
+/// Run a command and turn a non-zero exit into a `module` error, returning stdout
+pub(crate) async fn run_checked(
+    module: &str,
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    cmd: &str,
+    message: &str,
+    suggestion: Option<&str>,
+) -> Result<String, NexusError> {
+    let result = conn.exec(&ctx.wrap_command(cmd)).await?;
+    if result.success() {
+        Ok(result.stdout)
+    } else {
+        Err(module_error(
+            module,
+            conn,
+            message.to_string(),
+            Some(result.stderr),
+            suggestion,
+        ))
+    }
+}
+
+/// Upper-case the first character, for messages built from lower-case fragments
+pub(crate) fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Join lines into file content, with a trailing newline unless there are none
+pub(crate) fn join_lines(lines: &[String]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+/// Prefix a command with `NAME='value'` assignments for the remote shell
+pub(crate) fn prefix_environment(environment: &[(String, String)], command: &str) -> String {
+    let mut prefixed = String::new();
+    for (name, value) in environment {
+        prefixed.push_str(&format!("{}={} ", name, shell_quote(value)));
+    }
+    prefixed.push_str(command);
+    prefixed
+}
+
+/// Prefix a PowerShell command with `$env:NAME = 'value'` assignments, for Windows hosts
+pub(crate) fn prefix_powershell_environment(
+    environment: &[(String, String)],
+    command: &str,
+) -> String {
+    let mut prefixed = String::new();
+    for (name, value) in environment {
+        prefixed.push_str(&format!("$env:{} = {}; ", name, ps_quote(value)));
+    }
+    prefixed.push_str(command);
+    prefixed
+}
+
+/// A command that succeeds when `path` exists on the target, in the target's shell
+pub(crate) fn path_exists_command(conn: &dyn Connection, path: &str) -> String {
+    if conn.is_windows() {
+        format!(
+            "if (-not (Test-Path -LiteralPath {})) {{ exit 1 }}",
+            ps_quote(path)
+        )
+    } else {
+        format!("test -e {}", shell_quote(path))
+    }
+}
+
+/// The `ok` result for a task whose `creates` path already exists or whose `removes`
+/// path is absent on the host, or `None` if the task should run
+pub(crate) async fn skip_for_guards(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    creates: &Option<Expression>,
+    removes: &Option<Expression>,
+) -> Result<Option<TaskOutput>, NexusError> {
+    if let Some(creates) = creates {
+        let path = evaluate_expression(creates, ctx)?.to_string();
+        if conn
+            .exec(&path_exists_command(conn, &path))
+            .await?
+            .success()
+        {
+            return Ok(Some(
+                TaskOutput::success().with_stdout(format!("Skipped - {} already exists", path)),
+            ));
+        }
+    }
+    if let Some(removes) = removes {
+        let path = evaluate_expression(removes, ctx)?.to_string();
+        if !conn
+            .exec(&path_exists_command(conn, &path))
+            .await?
+            .success()
+        {
+            return Ok(Some(
+                TaskOutput::success().with_stdout(format!("Skipped - {} does not exist", path)),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+/// Run a command or shell task's command, streaming its output lines to the
+/// context's output stream when there is one
+pub(crate) async fn exec_task_command(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    command: &str,
+    stdin: Option<&str>,
+) -> Result<CommandResult, NexusError> {
+    match (stdin, &ctx.output_stream) {
+        (Some(input), _) => conn.exec_with_stdin(command, input).await,
+        (None, Some(stream)) => {
+            let line_sink = |is_stderr: bool| -> Box<dyn Fn(String) + Send + Sync> {
+                let stream = stream.clone();
+                let host = ctx.host.name.clone();
+                Box::new(move |line: String| {
+                    let line = line.trim_end_matches(['\r', '\n']).to_string();
+                    stream.stream_line(host.clone(), line, is_stderr)
+                })
+            };
+            conn.exec_streaming(command, line_sink(false), line_sink(true))
+                .await
+        }
+        (None, None) => conn.exec(command).await,
+    }
+}
+
 /// Detect the package manager on a system
 pub async fn detect_package_manager(conn: &dyn Connection) -> Result<PackageManager, NexusError> {
     // Check for various package managers
@@ -482,6 +1437,35 @@ pub enum PackageManager {
 }
 
 impl PackageManager {
+    /// The manager's name, as reported in the `package_manager` fact
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageManager::Dnf => "dnf",
+            PackageManager::Yum => "yum",
+            PackageManager::Apt => "apt",
+            PackageManager::Zypper => "zypper",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Apk => "apk",
+        }
+    }
+
+    /// Print every installed package as a `name version` line
+    pub fn list_installed_cmd(&self) -> &'static str {
+        match self {
+            PackageManager::Dnf | PackageManager::Yum | PackageManager::Zypper => {
+                "rpm -qa --qf '%{NAME} %{VERSION}-%{RELEASE}\\n' 2>/dev/null"
+            }
+            PackageManager::Apt => {
+                "dpkg-query -W -f='${Status}|${Package} ${Version}\\n' 2>/dev/null | sed -n 's/^install ok installed|//p'"
+            }
+            PackageManager::Pacman => "pacman -Q 2>/dev/null",
+            // apk prints name-version-rN
+            PackageManager::Apk => {
+                "apk info -v 2>/dev/null | sed -E 's/^(.*)-([^-]+-r[0-9]+)$/\\1 \\2/'"
+            }
+        }
+    }
+
     pub fn install_cmd(&self, package: &str) -> String {
         match self {
             PackageManager::Dnf => format!("dnf install -y {}", package),
@@ -524,6 +1508,86 @@ impl PackageManager {
         }
     }
 
+    /// Format a `name=version` pin the way this package manager expects
+    pub fn pin_spec(&self, package: &str, version: &str) -> String {
+        match self {
+            PackageManager::Dnf | PackageManager::Yum => format!("{}-{}", package, version),
+            _ => format!("{}={}", package, version),
+        }
+    }
+
+    /// Print the installed version of a package; fails if it is not installed
+    pub fn installed_version_cmd(&self, package: &str) -> String {
+        match self {
+            PackageManager::Dnf | PackageManager::Yum | PackageManager::Zypper => {
+                format!(
+                    "rpm -q --qf '%{{VERSION}}-%{{RELEASE}}\\n' {} 2>/dev/null",
+                    package
+                )
+            }
+            PackageManager::Apt => format!(
+                "dpkg-query -W -f='${{Status}}|${{Version}}' {} 2>/dev/null | sed -n 's/^install ok installed|//p' | grep .",
+                package
+            ),
+            PackageManager::Pacman => {
+                format!("pacman -Q {} 2>/dev/null | cut -d' ' -f2 | grep .", package)
+            }
+            PackageManager::Apk => format!(
+                "apk info -e {0} >/dev/null 2>&1 && apk info -d {0} 2>/dev/null | head -n1 | cut -d' ' -f1",
+                package
+            ),
+        }
+    }
+
+    /// Succeed only if a newer version of an installed package is available. Used in
+    /// check mode to tell whether `state: latest` would upgrade anything.
+    pub fn upgradable_cmd(&self, package: &str) -> String {
+        match self {
+            PackageManager::Dnf | PackageManager::Yum => format!(
+                "{} -q check-update {} >/dev/null 2>&1; test $? -eq 100",
+                self.as_str(),
+                package
+            ),
+            PackageManager::Apt => format!(
+                "apt-get -s install --only-upgrade {} 2>/dev/null | grep -q '^Inst '",
+                package
+            ),
+            PackageManager::Zypper => {
+                format!("zypper -q list-updates 2>/dev/null | grep -qw {}", package)
+            }
+            PackageManager::Pacman => format!("pacman -Qu {} >/dev/null 2>&1", package),
+            PackageManager::Apk => {
+                format!("apk version -l '<' {} 2>/dev/null | grep -q '<'", package)
+            }
+        }
+    }
+
+    /// Refresh the package metadata cache
+    pub fn update_cache_cmd(&self) -> &'static str {
+        match self {
+            PackageManager::Dnf => "dnf makecache -y",
+            PackageManager::Yum => "yum makecache -y",
+            PackageManager::Apt => "DEBIAN_FRONTEND=noninteractive apt-get update",
+            PackageManager::Zypper => "zypper --non-interactive refresh",
+            PackageManager::Pacman => "pacman -Sy --noconfirm",
+            PackageManager::Apk => "apk update",
+        }
+    }
+
+    /// Remove dependencies that are no longer needed, if the manager supports it
+    pub fn autoremove_cmd(&self) -> Option<&'static str> {
+        match self {
+            PackageManager::Dnf => Some("dnf autoremove -y"),
+            PackageManager::Yum => Some("yum autoremove -y"),
+            PackageManager::Apt => Some("DEBIAN_FRONTEND=noninteractive apt-get autoremove -y"),
+            PackageManager::Pacman => {
+                Some("pacman -Qdtq | pacman -Rns --noconfirm - 2>/dev/null || true")
+            }
+            // zypper and apk drop orphaned dependencies on removal
+            PackageManager::Zypper | PackageManager::Apk => None,
+        }
+    }
+
     pub fn check_installed_cmd(&self, package: &str) -> String {
         match self {
             PackageManager::Dnf | PackageManager::Yum => {
@@ -544,3 +1608,202 @@ impl PackageManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn local_context() -> ExecutionContext {
+        ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new())
+    }
+
+    #[tokio::test]
+    async fn test_deploy_validated_rejects_without_touching_dest() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("app.conf");
+        std::fs::write(&dest, "old\n").unwrap();
+        let dest = dest.to_string_lossy().to_string();
+
+        let err = deploy_validated(
+            &local_context(),
+            &LocalConnection::new("localhost"),
+            &dest,
+            "broken\n",
+            "grep -q valid %s || { echo 'syntax error' >&2; exit 1; }",
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("Validation failed"));
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "old\n");
+        assert!(!std::path::Path::new(&format!("{}.nexus-validate", dest)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_validated_moves_into_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("app.conf").to_string_lossy().to_string();
+
+        deploy_validated(
+            &local_context(),
+            &LocalConnection::new("localhost"),
+            &dest,
+            "valid\n",
+            "grep -q valid %s",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "valid\n");
+    }
+
+    #[tokio::test]
+    async fn test_template_diff_in_check_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.conf.j2");
+        std::fs::write(&src, "port = {{ port }}\n").unwrap();
+        let dest = dir.path().join("app.conf");
+        std::fs::write(&dest, "port = 80\n").unwrap();
+
+        let mut vars = HashMap::new();
+        vars.insert(
+            "port".to_string(),
+            crate::parser::ast::Value::String("8080".to_string()),
+        );
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), vars)
+            .with_check_mode(true)
+            .with_diff_mode(true);
+        let call = ModuleCall::Template {
+            src: Expression::string(src.to_string_lossy()),
+            dest: Expression::string(dest.to_string_lossy()),
+            owner: None,
+            group: None,
+            mode: None,
+            validate: None,
+            creates: None,
+            removes: None,
+        };
+        let conn = AnyConnection::Local(LocalConnection::new("localhost"));
+
+        let output = ModuleExecutor::new()
+            .execute(&call, &ctx, &conn)
+            .await
+            .unwrap();
+        assert!(output.changed);
+        let diff = output.diff.unwrap();
+        assert!(diff.unified.contains("-port = 80\n"));
+        assert!(diff.unified.contains("+port = 8080\n"));
+        assert_eq!(diff.before, "port = 80\n");
+        assert_eq!(diff.after, "port = 8080\n");
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "port = 80\n");
+
+        std::fs::write(&dest, "port = 8080\n").unwrap();
+        let output = ModuleExecutor::new()
+            .execute(&call, &ctx, &conn)
+            .await
+            .unwrap();
+        assert!(!output.changed);
+        assert!(output.diff.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_creates_and_removes_guards() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("provisioned");
+        let dest = dir.path().join("motd");
+        let conn = AnyConnection::Local(LocalConnection::new("localhost"));
+        let call = |creates: Option<&std::path::Path>, removes: Option<&std::path::Path>| {
+            let path = |p: &std::path::Path| Expression::string(p.to_string_lossy());
+            ModuleCall::File {
+                path: path(&dest),
+                state: crate::parser::ast::FileState::File,
+                source: None,
+                content: Some(Expression::string("welcome\n")),
+                owner: None,
+                group: None,
+                mode: None,
+                recurse: false,
+                creates: creates.map(path),
+                removes: removes.map(path),
+            }
+        };
+        let executor = ModuleExecutor::new();
+
+        // removes: a missing path skips the task
+        let output = executor
+            .execute(&call(None, Some(&marker)), &local_context(), &conn)
+            .await
+            .unwrap();
+        assert!(!output.changed);
+        assert!(output.stdout.contains("does not exist"));
+        assert!(!dest.exists());
+
+        std::fs::write(&marker, "").unwrap();
+        let output = executor
+            .execute(&call(Some(&marker), None), &local_context(), &conn)
+            .await
+            .unwrap();
+        assert!(!output.changed);
+        assert!(output.stdout.contains("already exists"));
+        assert!(!dest.exists());
+
+        let output = executor
+            .execute(&call(None, Some(&marker)), &local_context(), &conn)
+            .await
+            .unwrap();
+        assert!(output.changed);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "welcome\n");
+    }
+
+    #[tokio::test]
+    async fn test_path_exists_command_quotes_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("it's done");
+        let conn = LocalConnection::new("localhost");
+        let command = path_exists_command(&conn, &marker.to_string_lossy());
+
+        let result = conn.exec(&command).await.unwrap();
+        assert!(!result.success());
+
+        std::fs::write(&marker, "").unwrap();
+        let result = conn.exec(&command).await.unwrap();
+        assert!(result.success());
+    }
+
+    #[tokio::test]
+    async fn test_exec_task_command_streams_lines() {
+        use crate::output::events::{create_event_channel, ExecutionEvent};
+
+        let (emitter, mut rx) = create_event_channel();
+        let ctx = local_context().with_output_stream(Some(emitter));
+        let result = exec_task_command(
+            &ctx,
+            &LocalConnection::new("localhost"),
+            "echo one; echo two >&2",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.stdout, "one\n");
+        assert_eq!(result.stderr, "two\n");
+
+        let mut lines = Vec::new();
+        while let Ok(ExecutionEvent::StreamLine {
+            host,
+            line,
+            is_stderr,
+        }) = rx.try_recv()
+        {
+            assert_eq!(host, "localhost");
+            lines.push((line, is_stderr));
+        }
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![("one".to_string(), false), ("two".to_string(), true)]
+        );
+    }
+}
@@ -0,0 +1,438 @@
+// Mount module - manage /etc/fstab entries and the live mount state
+
+use async_trait::async_trait;
+
+use super::{capitalize, join_lines, module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::diff::Diff;
+use crate::output::errors::NexusError;
+use crate::parser::ast::{MountState, Value};
+
+pub struct MountModule;
+
+impl Default for MountModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One line of fstab: `src path fstype opts dump passno`
+#[derive(Debug, Clone, PartialEq)]
+struct FstabEntry {
+    src: String,
+    path: String,
+    fstype: String,
+    opts: String,
+    dump: String,
+    passno: String,
+}
+
+impl FstabEntry {
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            escape_field(&self.src),
+            escape_field(&self.path),
+            self.fstype,
+            self.opts,
+            self.dump,
+            self.passno
+        )
+    }
+}
+
+/// What has to happen to the live mount
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MountAction {
+    Mount,
+    Unmount,
+    Remount,
+}
+
+impl MountModule {
+    pub fn new() -> Self {
+        MountModule
+    }
+
+    /// Bring the fstab entry for `path` and its mount state in line with `state`.
+    ///
+    /// fstab is backed up next to itself before it is rewritten, and the task only
+    /// reports `changed` when the fstab line or the mount state actually changed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        src: Option<String>,
+        fstype: Option<String>,
+        opts: Option<String>,
+        dump: Option<String>,
+        passno: Option<String>,
+        fstab: &str,
+        state: MountState,
+    ) -> Result<TaskOutput, NexusError> {
+        let fstab_quoted = shell_quote(fstab);
+        let path_quoted = shell_quote(path);
+
+        let current = conn
+            .exec(&ctx.wrap_command(&format!("cat {} 2>/dev/null", fstab_quoted)))
+            .await?
+            .stdout;
+        let mounted = is_mounted(&conn.exec("mount").await?.stdout, path);
+
+        let updated = match state {
+            MountState::Present | MountState::Mounted => {
+                let (Some(src), Some(fstype)) = (src, fstype) else {
+                    return Err(module_error(
+                        "mount",
+                        conn,
+                        format!("state {} needs both src and fstype", state.as_str()),
+                        None,
+                        Some("Add src (e.g. /dev/sdb1) and fstype (e.g. ext4)"),
+                    ));
+                };
+                let entry = FstabEntry {
+                    src,
+                    path: path.to_string(),
+                    fstype,
+                    opts: opts.unwrap_or_else(|| "defaults".to_string()),
+                    dump: dump.unwrap_or_else(|| "0".to_string()),
+                    passno: passno.unwrap_or_else(|| "0".to_string()),
+                };
+                set_fstab_entry(&current, &entry)
+            }
+            MountState::Absent => remove_fstab_entry(&current, path),
+            MountState::Unmounted | MountState::Remounted => None,
+        };
+
+        let action = match state {
+            MountState::Mounted if !mounted => Some(MountAction::Mount),
+            // New options only take effect on a remount
+            MountState::Mounted if updated.is_some() => Some(MountAction::Remount),
+            MountState::Unmounted | MountState::Absent if mounted => Some(MountAction::Unmount),
+            MountState::Remounted if mounted => Some(MountAction::Remount),
+            MountState::Remounted => Some(MountAction::Mount),
+            _ => None,
+        };
+
+        if updated.is_none() && action.is_none() {
+            return Ok(TaskOutput::success().with_stdout(format!(
+                "{} is already {}",
+                path,
+                state.as_str()
+            )));
+        }
+
+        // (what would happen, what happened) for the task output
+        let mut steps = Vec::new();
+        if updated.is_some() {
+            steps.push(("update", "updated", fstab));
+        }
+        match action {
+            Some(MountAction::Mount) => steps.push(("mount", "mounted", path)),
+            Some(MountAction::Unmount) => steps.push(("unmount", "unmounted", path)),
+            Some(MountAction::Remount) => steps.push(("remount", "remounted", path)),
+            None => {}
+        }
+        let describe = |past: bool| {
+            steps
+                .iter()
+                .map(|(verb, done, target)| {
+                    format!("{} {}", if past { done } else { verb }, target)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let diff = updated.as_ref().filter(|_| ctx.diff_mode).map(|new| {
+            Diff::new(
+                &current,
+                new,
+                &format!("{} (before)", fstab),
+                &format!("{} (after)", fstab),
+            )
+        });
+
+        // Check mode
+        if ctx.check_mode {
+            let mut output =
+                TaskOutput::changed().with_stdout(format!("Would {}", describe(false)));
+            output.diff = diff;
+            return Ok(output);
+        }
+
+        let mut output = TaskOutput::changed();
+
+        // Unmount before the entry goes away, so a failure leaves fstab untouched
+        if action == Some(MountAction::Unmount) {
+            self.run(ctx, conn, &format!("umount {}", path_quoted), "unmount")
+                .await?;
+        }
+
+        if let Some(ref new_content) = updated {
+            if !current.is_empty() {
+                let backup = format!(
+                    "{}.{}~",
+                    fstab,
+                    chrono::Local::now().format("%Y-%m-%d@%H:%M:%S")
+                );
+                self.run(
+                    ctx,
+                    conn,
+                    &format!("cp -p {} {}", fstab_quoted, shell_quote(&backup)),
+                    "back up fstab",
+                )
+                .await?;
+                output = output.with_data("backup_file", Value::String(backup));
+            }
+
+            // Rewrite in place so the file keeps its owner, mode and SELinux label
+            let result = conn
+                .exec_with_stdin(
+                    &ctx.wrap_command(&format!("cat > {}", fstab_quoted)),
+                    new_content,
+                )
+                .await?;
+            if !result.success() {
+                return Err(module_error(
+                    "mount",
+                    conn,
+                    format!("Failed to write {}", fstab),
+                    Some(result.stderr),
+                    None,
+                ));
+            }
+        }
+
+        match action {
+            Some(MountAction::Mount) => {
+                self.run(
+                    ctx,
+                    conn,
+                    &format!("mkdir -p {} && mount {}", path_quoted, path_quoted),
+                    "mount",
+                )
+                .await?;
+            }
+            Some(MountAction::Remount) => {
+                self.run(
+                    ctx,
+                    conn,
+                    &format!("mount -o remount {}", path_quoted),
+                    "remount",
+                )
+                .await?;
+            }
+            Some(MountAction::Unmount) | None => {}
+        }
+
+        output.diff = diff;
+        Ok(output.with_stdout(capitalize(&describe(true))))
+    }
+
+    async fn run(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        cmd: &str,
+        what: &str,
+    ) -> Result<(), NexusError> {
+        let result = conn.exec(&ctx.wrap_command(cmd)).await?;
+        if result.success() {
+            return Ok(());
+        }
+        Err(module_error(
+            "mount",
+            conn,
+            format!("Failed to {}: exit code {}", what, result.exit_code),
+            Some(result.stderr),
+            Some("Mounting usually needs root; set sudo: true on the task"),
+        ))
+    }
+}
+
+#[async_trait]
+impl Module for MountModule {
+    fn name(&self) -> &'static str {
+        "mount"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+fn parse_fstab_line(line: &str) -> Option<FstabEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    Some(FstabEntry {
+        src: unescape_field(fields[0]),
+        path: unescape_field(fields[1]),
+        fstype: fields[2].to_string(),
+        opts: fields[3].to_string(),
+        dump: fields.get(4).unwrap_or(&"0").to_string(),
+        passno: fields.get(5).unwrap_or(&"0").to_string(),
+    })
+}
+
+/// Add or replace the entry for `entry.path`. Returns the new content, or `None`
+/// when the existing entry already matches.
+fn set_fstab_entry(content: &str, entry: &FstabEntry) -> Option<String> {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let existing = lines.iter().position(|line| {
+        parse_fstab_line(line).is_some_and(|existing| existing.path == entry.path)
+    });
+
+    match existing {
+        Some(index) if parse_fstab_line(&lines[index]).as_ref() == Some(entry) => return None,
+        Some(index) => lines[index] = entry.to_line(),
+        None => lines.push(entry.to_line()),
+    }
+    Some(join_lines(&lines))
+}
+
+/// Remove every entry for `path`. Returns the new content, or `None` if there was none.
+fn remove_fstab_entry(content: &str, path: &str) -> Option<String> {
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let kept: Vec<String> = lines
+        .iter()
+        .filter(|line| !parse_fstab_line(line).is_some_and(|entry| entry.path == path))
+        .cloned()
+        .collect();
+
+    (kept.len() != lines.len()).then(|| join_lines(&kept))
+}
+/// Whether `mount` output lists `path` as a mount point. Linux prints
+/// `src on /path type ext4 (opts)`, BSD and macOS `src on /path (ufs, local)`.
+fn is_mounted(mount_output: &str, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    let path = if path.is_empty() { "/" } else { path };
+    mount_output.lines().any(|line| {
+        line.split_once(" on ").is_some_and(|(_, rest)| {
+            rest.strip_prefix(path)
+                .is_some_and(|tail| tail.starts_with(" type ") || tail.starts_with(" ("))
+        })
+    })
+}
+
+/// fstab separates fields by whitespace, so spaces in paths are written as `\040`
+fn escape_field(field: &str) -> String {
+    field.replace(' ', "\\040").replace('\t', "\\011")
+}
+
+fn unescape_field(field: &str) -> String {
+    field.replace("\\040", " ").replace("\\011", "\t")
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FSTAB: &str =
+        "# /etc/fstab\nUUID=abc / ext4 defaults 0 1\n/dev/sdb1 /data xfs noatime 0 2\n";
+
+    fn data_entry(opts: &str) -> FstabEntry {
+        FstabEntry {
+            src: "/dev/sdb1".to_string(),
+            path: "/data".to_string(),
+            fstype: "xfs".to_string(),
+            opts: opts.to_string(),
+            dump: "0".to_string(),
+            passno: "2".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_set_fstab_entry_is_idempotent() {
+        assert_eq!(set_fstab_entry(FSTAB, &data_entry("noatime")), None);
+
+        let updated = set_fstab_entry(FSTAB, &data_entry("noatime,nodev")).unwrap();
+        assert_eq!(
+            updated,
+            "# /etc/fstab\nUUID=abc / ext4 defaults 0 1\n/dev/sdb1 /data xfs noatime,nodev 0 2\n"
+        );
+
+        let mut backups = data_entry("defaults");
+        backups.path = "/mnt/my backups".to_string();
+        let appended = set_fstab_entry(FSTAB, &backups).unwrap();
+        assert!(appended.ends_with("/dev/sdb1 /mnt/my\\040backups xfs defaults 0 2\n"));
+        assert_eq!(set_fstab_entry(&appended, &backups), None);
+    }
+
+    #[test]
+    fn test_remove_fstab_entry() {
+        assert_eq!(
+            remove_fstab_entry(FSTAB, "/data").unwrap(),
+            "# /etc/fstab\nUUID=abc / ext4 defaults 0 1\n"
+        );
+        assert_eq!(remove_fstab_entry(FSTAB, "/srv"), None);
+    }
+
+    #[tokio::test]
+    async fn test_present_manages_fstab_with_backup() {
+        use crate::executor::LocalConnection;
+        use crate::inventory::Host;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let fstab = dir.path().join("fstab");
+        std::fs::write(&fstab, FSTAB).unwrap();
+        let fstab = fstab.to_string_lossy().to_string();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let conn = LocalConnection::new("localhost");
+        let module = MountModule::new();
+        let run = |opts: &'static str| {
+            module.execute_with_params(
+                &ctx,
+                &conn,
+                "/data",
+                Some("/dev/sdb1".to_string()),
+                Some("xfs".to_string()),
+                Some(opts.to_string()),
+                None,
+                Some("2".to_string()),
+                &fstab,
+                MountState::Present,
+            )
+        };
+
+        let output = run("noatime").await.unwrap();
+        assert!(!output.changed);
+
+        let output = run("noatime,nodev").await.unwrap();
+        assert!(output.changed);
+        let Some(Value::String(backup)) = output.data.get("backup_file") else {
+            panic!("expected a backup_file");
+        };
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), FSTAB);
+        assert!(std::fs::read_to_string(&fstab)
+            .unwrap()
+            .contains("/dev/sdb1 /data xfs noatime,nodev 0 2"));
+
+        assert!(!run("noatime,nodev").await.unwrap().changed);
+    }
+
+    #[test]
+    fn test_is_mounted() {
+        let linux = "/dev/sda1 on / type ext4 (rw,relatime)\n/dev/sdb1 on /data type xfs (rw)\n";
+        assert!(is_mounted(linux, "/data"));
+        assert!(is_mounted(linux, "/data/"));
+        assert!(!is_mounted(linux, "/dat"));
+        assert!(!is_mounted(linux, "/srv"));
+
+        let bsd = "/dev/ada0p2 on / (ufs, local)\n/dev/ada1p1 on /data (ufs, local)\n";
+        assert!(is_mounted(bsd, "/data"));
+    }
+}
@@ -2,10 +2,10 @@
 
 use async_trait::async_trait;
 
-use super::{detect_package_manager, Module, PackageManager};
+use super::{detect_package_manager, run_checked, shell_quote, Module, PackageManager};
 use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
-use crate::output::errors::{ModuleError, NexusError};
-use crate::parser::ast::PackageState;
+use crate::output::errors::NexusError;
+use crate::parser::ast::{PackageState, Value};
 
 pub struct PackageModule {
     cached_manager: std::sync::RwLock<Option<PackageManager>>,
@@ -17,6 +17,45 @@ impl Default for PackageModule {
     }
 }
 
+/// A requested package with an optional `name=version` pin
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PackageSpec {
+    name: String,
+    version: Option<String>,
+}
+
+impl PackageSpec {
+    fn parse(spec: &str) -> Self {
+        match spec.trim().split_once('=') {
+            Some((name, version)) => PackageSpec {
+                name: name.trim().to_string(),
+                version: Some(version.trim_start_matches('=').trim().to_string())
+                    .filter(|v| !v.is_empty()),
+            },
+            None => PackageSpec {
+                name: spec.trim().to_string(),
+                version: None,
+            },
+        }
+    }
+
+    /// The argument passed to the package manager
+    fn install_arg(&self, manager: PackageManager) -> String {
+        match self.version {
+            Some(ref v) => shell_quote(&manager.pin_spec(&self.name, v)),
+            None => shell_quote(&self.name),
+        }
+    }
+
+    /// Whether an installed version satisfies the pin
+    fn satisfied_by(&self, installed: &str) -> bool {
+        match self.version {
+            Some(ref wanted) => version_matches(installed, wanted),
+            None => true,
+        }
+    }
+}
+
 impl PackageModule {
     pub fn new() -> Self {
         PackageModule {
@@ -28,20 +67,12 @@ impl PackageModule {
         &self,
         ctx: &ExecutionContext,
         conn: &dyn Connection,
-        name: &str,
+        names: &[String],
         state: PackageState,
+        update_cache: bool,
+        autoremove: bool,
     ) -> Result<TaskOutput, NexusError> {
-        // Check mode - just report what would happen
-        if ctx.check_mode {
-            let action = match state {
-                PackageState::Installed => "install",
-                PackageState::Latest => "upgrade to latest version of",
-                PackageState::Absent => "remove",
-            };
-            return Ok(
-                TaskOutput::changed().with_stdout(format!("Would {} package: {}", action, name))
-            );
-        }
+        let specs: Vec<PackageSpec> = names.iter().map(|n| PackageSpec::parse(n)).collect();
 
         // Detect package manager (cached)
         let cached = *self.cached_manager.read().unwrap();
@@ -53,95 +84,257 @@ impl PackageModule {
             m
         };
 
-        // Check current state
-        let check_cmd = manager.check_installed_cmd(name);
-        let is_installed = conn.exec(&check_cmd).await?.success();
+        // Query the installed version of every package up front
+        let mut before = Vec::with_capacity(specs.len());
+        for spec in &specs {
+            before.push(installed_version(conn, manager, &spec.name).await?);
+        }
 
-        match state {
-            PackageState::Installed => {
-                if is_installed {
-                    return Ok(TaskOutput::success()
-                        .with_stdout(format!("Package {} is already installed", name)));
+        // Split into one batch per package manager invocation
+        let mut to_install = Vec::new();
+        let mut to_upgrade = Vec::new();
+        let mut to_remove = Vec::new();
+        for (spec, current) in specs.iter().zip(&before) {
+            match (state, current) {
+                (PackageState::Installed, None) => to_install.push(spec),
+                (PackageState::Installed, Some(v)) if !spec.satisfied_by(v) => {
+                    to_install.push(spec)
                 }
+                (PackageState::Latest, None) => to_install.push(spec),
+                (PackageState::Latest, Some(_)) => to_upgrade.push(spec),
+                (PackageState::Absent, Some(_)) => to_remove.push(spec),
+                _ => {}
+            }
+        }
 
-                let cmd = manager.install_cmd(name);
-                let final_cmd = ctx.wrap_command(&cmd);
-                let result = conn.exec(&final_cmd).await?;
-
-                if result.success() {
-                    Ok(TaskOutput::changed()
-                        .with_stdout(result.stdout)
-                        .with_stderr(result.stderr))
-                } else {
-                    Err(NexusError::Module(Box::new(ModuleError {
-                        module: "package".to_string(),
-                        task_name: format!("Install {}", name),
-                        host: conn.host_name().to_string(),
-                        message: format!("Failed to install package {}", name),
-                        stderr: Some(result.stderr),
-                        suggestion: Some(
-                            "Check package name and repository configuration".to_string(),
-                        ),
-                    })))
+        // Check mode - just report what would happen
+        if ctx.check_mode {
+            // `latest` only upgrades packages that have a newer version available
+            let mut upgradable = Vec::with_capacity(to_upgrade.len());
+            for spec in to_upgrade {
+                let cmd = manager.upgradable_cmd(&shell_quote(&spec.name));
+                if conn.exec(&cmd).await?.success() {
+                    upgradable.push(spec);
                 }
             }
+            let to_upgrade = upgradable;
 
-            PackageState::Latest => {
-                let cmd = manager.update_cmd(name);
-                let final_cmd = ctx.wrap_command(&cmd);
-                let result = conn.exec(&final_cmd).await?;
-
-                if result.success() {
-                    // Check if anything was actually updated
-                    let changed = !result.stdout.contains("Nothing to do")
-                        && !result.stdout.contains("0 upgraded");
-
-                    if changed {
-                        Ok(TaskOutput::changed()
-                            .with_stdout(result.stdout)
-                            .with_stderr(result.stderr))
-                    } else {
-                        Ok(TaskOutput::success()
-                            .with_stdout(format!("Package {} is already at latest version", name)))
-                    }
-                } else {
-                    Err(NexusError::Module(Box::new(ModuleError {
-                        module: "package".to_string(),
-                        task_name: format!("Update {}", name),
-                        host: conn.host_name().to_string(),
-                        message: format!("Failed to update package {}", name),
-                        stderr: Some(result.stderr),
-                        suggestion: None,
-                    })))
-                }
+            let mut lines = Vec::new();
+            let describe = |batch: &[&PackageSpec]| {
+                batch
+                    .iter()
+                    .map(|s| match s.version {
+                        Some(ref v) => format!("{}={}", s.name, v),
+                        None => s.name.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if update_cache {
+                lines.push("Would update package cache".to_string());
+            }
+            if !to_install.is_empty() {
+                lines.push(format!("Would install package: {}", describe(&to_install)));
             }
+            if !to_upgrade.is_empty() {
+                lines.push(format!(
+                    "Would upgrade to latest version of package: {}",
+                    describe(&to_upgrade)
+                ));
+            }
+            if !to_remove.is_empty() {
+                lines.push(format!("Would remove package: {}", describe(&to_remove)));
+            }
+            let pending = !to_install.is_empty() || !to_upgrade.is_empty() || !to_remove.is_empty();
+            if !pending {
+                lines.push(format!(
+                    "Packages already {}: {}",
+                    state_word(&state),
+                    names.join(", ")
+                ));
+            }
+            let output = if pending {
+                TaskOutput::changed()
+            } else {
+                TaskOutput::success()
+            };
+            return Ok(output.with_stdout(lines.join("\n")));
+        }
 
-            PackageState::Absent => {
-                if !is_installed {
-                    return Ok(TaskOutput::success()
-                        .with_stdout(format!("Package {} is not installed", name)));
-                }
+        let mut output_lines = Vec::new();
+
+        if update_cache {
+            run_checked(
+                "package",
+                ctx,
+                conn,
+                manager.update_cache_cmd(),
+                "Failed to update package cache",
+                None,
+            )
+            .await?;
+            output_lines.push("Updated package cache".to_string());
+        }
+
+        if !to_install.is_empty() {
+            let args: Vec<String> = to_install.iter().map(|s| s.install_arg(manager)).collect();
+            run_checked(
+                "package",
+                ctx,
+                conn,
+                &manager.install_cmd(&args.join(" ")),
+                &format!("Failed to install package {}", batch_names(&to_install)),
+                Some("Check package name and repository configuration"),
+            )
+            .await?;
+        }
+
+        if !to_upgrade.is_empty() {
+            let args: Vec<String> = to_upgrade.iter().map(|s| shell_quote(&s.name)).collect();
+            run_checked(
+                "package",
+                ctx,
+                conn,
+                &manager.update_cmd(&args.join(" ")),
+                &format!("Failed to update package {}", batch_names(&to_upgrade)),
+                None,
+            )
+            .await?;
+        }
+
+        if !to_remove.is_empty() {
+            let args: Vec<String> = to_remove.iter().map(|s| shell_quote(&s.name)).collect();
+            run_checked(
+                "package",
+                ctx,
+                conn,
+                &manager.remove_cmd(&args.join(" ")),
+                &format!("Failed to remove package {}", batch_names(&to_remove)),
+                None,
+            )
+            .await?;
+        }
 
-                let cmd = manager.remove_cmd(name);
-                let final_cmd = ctx.wrap_command(&cmd);
-                let result = conn.exec(&final_cmd).await?;
-
-                if result.success() {
-                    Ok(TaskOutput::changed()
-                        .with_stdout(result.stdout)
-                        .with_stderr(result.stderr))
-                } else {
-                    Err(NexusError::Module(Box::new(ModuleError {
-                        module: "package".to_string(),
-                        task_name: format!("Remove {}", name),
-                        host: conn.host_name().to_string(),
-                        message: format!("Failed to remove package {}", name),
-                        stderr: Some(result.stderr),
-                        suggestion: None,
-                    })))
+        // Compare installed versions to report exactly which packages changed
+        let mut changed_packages = Vec::new();
+        for (spec, old) in specs.iter().zip(&before) {
+            let touched = to_install.contains(&spec)
+                || to_upgrade.contains(&spec)
+                || to_remove.contains(&spec);
+            if !touched {
+                continue;
+            }
+            let new = installed_version(conn, manager, &spec.name).await?;
+            let line = match (old, &new) {
+                (None, Some(v)) => format!("Installed {} {}", spec.name, v),
+                (Some(_), None) => format!("Removed {}", spec.name),
+                (Some(o), Some(n)) if o != n => format!("Changed {} {} -> {}", spec.name, o, n),
+                _ => continue,
+            };
+            output_lines.push(line);
+            changed_packages.push(Value::String(spec.name.clone()));
+        }
+
+        let mut changed = !changed_packages.is_empty();
+
+        if autoremove {
+            if let Some(cmd) = manager.autoremove_cmd() {
+                let stdout = run_checked(
+                    "package",
+                    ctx,
+                    conn,
+                    cmd,
+                    "Failed to remove unused dependencies",
+                    None,
+                )
+                .await?;
+                if autoremove_removed_something(&stdout) {
+                    changed = true;
+                    output_lines.push("Removed unused dependencies".to_string());
                 }
             }
         }
+
+        if output_lines.is_empty() || changed_packages.is_empty() {
+            output_lines.push(format!(
+                "Packages already {}: {}",
+                state_word(&state),
+                names.join(", ")
+            ));
+        }
+
+        let output = if changed {
+            TaskOutput::changed()
+        } else {
+            TaskOutput::success()
+        };
+        Ok(output
+            .with_stdout(output_lines.join("\n"))
+            .with_data("changed_packages", Value::List(changed_packages)))
+    }
+}
+
+/// Installed version of a package, or None when it is not installed
+async fn installed_version(
+    conn: &dyn Connection,
+    manager: PackageManager,
+    name: &str,
+) -> Result<Option<String>, NexusError> {
+    let result = conn
+        .exec(&manager.installed_version_cmd(&shell_quote(name)))
+        .await?;
+    if !result.success() {
+        return Ok(None);
+    }
+    let version = result.stdout.lines().next().unwrap_or("").trim();
+    if version.is_empty() {
+        return Ok(None);
+    }
+    // apk reports name-version
+    let version = version
+        .strip_prefix(&format!("{}-", name))
+        .unwrap_or(version);
+    Ok(Some(version.to_string()))
+}
+
+/// Match an installed version against a pin such as `1.24.0` or `1.24.*`.
+/// Epochs are ignored and a bare upstream version matches any release suffix.
+fn version_matches(installed: &str, wanted: &str) -> bool {
+    let installed = installed
+        .split_once(':')
+        .map(|(_, v)| v)
+        .unwrap_or(installed);
+
+    if let Some(prefix) = wanted.strip_suffix('*') {
+        return installed.starts_with(prefix);
+    }
+
+    installed == wanted
+        || installed
+            .strip_prefix(wanted)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Whether an autoremove run actually removed packages
+fn autoremove_removed_something(stdout: &str) -> bool {
+    !stdout.trim().is_empty()
+        && !stdout.contains("Nothing to do")
+        && !stdout.contains(" 0 to remove")
+}
+
+fn batch_names(batch: &[&PackageSpec]) -> String {
+    batch
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn state_word(state: &PackageState) -> &'static str {
+    match state {
+        PackageState::Installed => "installed",
+        PackageState::Latest => "at latest version",
+        PackageState::Absent => "absent",
     }
 }
 
@@ -161,15 +354,6 @@ impl Module for PackageModule {
     }
 }
 
-#[allow(dead_code)]
-fn state_action(state: &PackageState) -> &'static str {
-    match state {
-        PackageState::Installed => "install",
-        PackageState::Latest => "update",
-        PackageState::Absent => "remove",
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,4 +368,26 @@ mod tests {
         assert!(dnf.install_cmd("nginx").contains("dnf install"));
         assert!(dnf.check_installed_cmd("nginx").contains("rpm"));
     }
+
+    #[test]
+    fn test_pinned_specs_per_manager() {
+        let spec = PackageSpec::parse("nginx=1.24.0");
+        assert_eq!(spec.name, "nginx");
+        assert_eq!(spec.version.as_deref(), Some("1.24.0"));
+        assert_eq!(spec.install_arg(PackageManager::Apt), "'nginx=1.24.0'");
+        assert_eq!(spec.install_arg(PackageManager::Dnf), "'nginx-1.24.0'");
+
+        let spec = PackageSpec::parse("git");
+        assert!(spec.version.is_none());
+        assert_eq!(spec.install_arg(PackageManager::Apt), "'git'");
+    }
+
+    #[test]
+    fn test_version_matches() {
+        assert!(version_matches("1.24.0-1ubuntu1", "1.24.0"));
+        assert!(version_matches("1:1.24.0-1", "1.24.*"));
+        assert!(version_matches("1.24.0", "1.24.0"));
+        assert!(!version_matches("1.24.10", "1.24.1"));
+        assert!(!version_matches("1.22.1-9", "1.24.*"));
+    }
 }
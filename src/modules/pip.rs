@@ -0,0 +1,325 @@
+// Pip module - manage Python packages, optionally inside a virtualenv
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use super::{module_error, run_checked, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{PipState, Value};
+
+/// Command used to create a missing virtualenv
+const DEFAULT_VIRTUALENV_COMMAND: &str = "python3 -m venv";
+
+pub struct PipModule;
+
+impl Default for PipModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A package requirement such as `requests==2.31.0` or `flask>=2`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PackageSpec {
+    /// Distribution name as given to pip
+    name: String,
+    /// Full requirement string passed to `pip install`
+    requirement: String,
+    /// Exact version when pinned with `==`
+    pinned: Option<String>,
+}
+
+impl PackageSpec {
+    fn parse(spec: &str, version: Option<&str>) -> Self {
+        let spec = spec.trim();
+        let end = spec
+            .find(|c: char| "=<>!~[; ".contains(c))
+            .unwrap_or(spec.len());
+        let name = spec[..end].to_string();
+
+        if let Some(version) = version {
+            return PackageSpec {
+                requirement: format!("{}=={}", name, version),
+                pinned: Some(version.to_string()),
+                name,
+            };
+        }
+
+        let pinned = spec[end..]
+            .trim()
+            .strip_prefix("==")
+            .map(|v| v.trim().to_string());
+        PackageSpec {
+            name,
+            requirement: spec.to_string(),
+            pinned,
+        }
+    }
+}
+
+impl PipModule {
+    pub fn new() -> Self {
+        PipModule
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        names: &[String],
+        state: PipState,
+        version: Option<String>,
+        virtualenv: Option<String>,
+        virtualenv_command: Option<String>,
+        requirements: Option<String>,
+        executable: Option<String>,
+    ) -> Result<TaskOutput, NexusError> {
+        if version.is_some() && names.len() != 1 {
+            return Err(module_error(
+                "pip",
+                conn,
+                "'version' can only be used with a single package".to_string(),
+                None,
+                Some("Pin each package instead, e.g. requests==2.31.0"),
+            ));
+        }
+
+        let specs: Vec<PackageSpec> = names
+            .iter()
+            .map(|n| PackageSpec::parse(n, version.as_deref()))
+            .collect();
+
+        let mut changed = false;
+        let mut output_lines = Vec::new();
+
+        // Create the virtualenv first if it is missing
+        let pip = match virtualenv {
+            Some(ref venv) => {
+                let pip = format!("{}/bin/pip", venv);
+                if !conn
+                    .exec(&format!("test -x {}", shell_quote(&pip)))
+                    .await?
+                    .success()
+                {
+                    let create = format!(
+                        "{} {}",
+                        virtualenv_command
+                            .as_deref()
+                            .unwrap_or(DEFAULT_VIRTUALENV_COMMAND),
+                        shell_quote(venv)
+                    );
+                    if ctx.check_mode {
+                        output_lines.push(format!("Would create virtualenv {}", venv));
+                    } else {
+                        run_checked(
+                            "pip",
+                            ctx,
+                            conn,
+                            &create,
+                            "Failed to create virtualenv",
+                            None,
+                        )
+                        .await?;
+                        output_lines.push(format!("Created virtualenv {}", venv));
+                    }
+                    changed = true;
+                }
+                shell_quote(&pip)
+            }
+            None => executable.unwrap_or_else(|| "pip3".to_string()),
+        };
+
+        if let Some(ref file) = requirements {
+            if ctx.check_mode {
+                output_lines.push(format!("Would install requirements from {}", file));
+                changed = true;
+            } else {
+                let upgrade = if state == PipState::Latest {
+                    " --upgrade"
+                } else {
+                    ""
+                };
+                let stdout = run_checked(
+                    "pip",
+                    ctx,
+                    conn,
+                    &format!("{} install{} -r {}", pip, upgrade, shell_quote(file)),
+                    &format!("Failed to install requirements from {}", file),
+                    None,
+                )
+                .await?;
+                if stdout.contains("Successfully installed") {
+                    changed = true;
+                    output_lines.push(format!("Installed requirements from {}", file));
+                } else {
+                    output_lines.push(format!("Requirements from {} already satisfied", file));
+                }
+            }
+        }
+
+        let mut versions = HashMap::new();
+
+        for spec in &specs {
+            let before = installed_version(conn, &pip, &spec.name).await?;
+
+            let action = match state {
+                PipState::Present => match (&before, &spec.pinned) {
+                    (None, _) => Some("install"),
+                    (Some(current), Some(wanted)) if current != wanted => Some("install"),
+                    _ => None,
+                },
+                PipState::Latest => Some("upgrade"),
+                PipState::Absent => before.as_ref().map(|_| "uninstall"),
+            };
+
+            let Some(action) = action else {
+                if let Some(v) = before {
+                    versions.insert(spec.name.clone(), Value::String(v));
+                }
+                continue;
+            };
+
+            if ctx.check_mode {
+                // An upgrade may be a no-op, but pip can't tell without installing
+                output_lines.push(format!("Would {} {}", action, spec.requirement));
+                changed = true;
+                continue;
+            }
+
+            let cmd = match action {
+                "install" => format!("{} install {}", pip, shell_quote(&spec.requirement)),
+                "upgrade" => format!(
+                    "{} install --upgrade {}",
+                    pip,
+                    shell_quote(&spec.requirement)
+                ),
+                _ => format!("{} uninstall -y {}", pip, shell_quote(&spec.name)),
+            };
+            run_checked(
+                "pip",
+                ctx,
+                conn,
+                &cmd,
+                &format!("Failed to {} {}", action, spec.requirement),
+                None,
+            )
+            .await?;
+
+            let after = installed_version(conn, &pip, &spec.name).await?;
+            if after != before {
+                changed = true;
+                output_lines.push(match (&before, &after) {
+                    (_, None) => format!("Uninstalled {}", spec.name),
+                    (None, Some(v)) => format!("Installed {} {}", spec.name, v),
+                    (Some(old), Some(new)) => format!("Upgraded {} {} -> {}", spec.name, old, new),
+                });
+            } else {
+                output_lines.push(format!("{} is up to date", spec.name));
+            }
+            if let Some(v) = after {
+                versions.insert(spec.name.clone(), Value::String(v));
+            }
+        }
+
+        let output = if changed {
+            TaskOutput::changed()
+        } else {
+            TaskOutput::success()
+        };
+        Ok(output
+            .with_stdout(output_lines.join("\n"))
+            .with_data("versions", Value::Dict(versions)))
+    }
+}
+
+/// Installed version of a distribution according to `pip show`
+async fn installed_version(
+    conn: &dyn Connection,
+    pip: &str,
+    name: &str,
+) -> Result<Option<String>, NexusError> {
+    let result = conn
+        .exec(&format!("{} show {} 2>/dev/null", pip, shell_quote(name)))
+        .await?;
+    if !result.success() {
+        return Ok(None);
+    }
+    Ok(parse_show_version(&result.stdout))
+}
+
+fn parse_show_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("Version:"))
+        .map(|v| v.trim().to_string())
+}
+
+#[async_trait]
+impl Module for PipModule {
+    fn name(&self) -> &'static str {
+        "pip"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_package_spec_parse() {
+        let spec = PackageSpec::parse("requests==2.31.0", None);
+        assert_eq!(spec.name, "requests");
+        assert_eq!(spec.pinned.as_deref(), Some("2.31.0"));
+
+        let spec = PackageSpec::parse("flask>=2.0", None);
+        assert_eq!(spec.name, "flask");
+        assert_eq!(spec.requirement, "flask>=2.0");
+        assert!(spec.pinned.is_none());
+
+        let spec = PackageSpec::parse("django", Some("4.2.7"));
+        assert_eq!(spec.requirement, "django==4.2.7");
+        assert_eq!(spec.pinned.as_deref(), Some("4.2.7"));
+    }
+
+    #[test]
+    fn test_parse_show_version() {
+        let output = "Name: requests\nVersion: 2.31.0\nSummary: HTTP for Humans.\n";
+        assert_eq!(parse_show_version(output).as_deref(), Some("2.31.0"));
+        assert_eq!(parse_show_version("WARNING: not found"), None);
+    }
+
+    #[tokio::test]
+    async fn test_absent_package_is_unchanged() {
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let output = PipModule::new()
+            .execute_with_params(
+                &ctx,
+                &LocalConnection::new("localhost"),
+                &["nexus-surely-not-installed".to_string()],
+                PipState::Absent,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(!output.changed);
+    }
+}
@@ -0,0 +1,224 @@
+// Reboot module - reboot the host and wait for it to come back
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::{module_error, AnyConnection, Module};
+use crate::executor::{Connection, ConnectionPool, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+/// Interval between reconnection attempts while the host is down
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Runs in the background so the command returns before sshd goes away
+const REBOOT_COMMAND: &str = "nohup sh -c 'sleep 1; shutdown -r now || reboot' >/dev/null 2>&1 &";
+
+/// Prints the kernel's boot ID and the boot time in seconds since the epoch
+const BOOT_STATE_COMMAND: &str =
+    "cat /proc/sys/kernel/random/boot_id 2>/dev/null; awk '/^btime/ {print $2}' /proc/stat 2>/dev/null";
+
+/// Identifies one boot of the host, to tell a rebooted host from one that never went down
+#[derive(Debug, Clone, PartialEq)]
+struct BootState {
+    id: String,
+    time: Option<i64>,
+}
+
+pub struct RebootModule;
+
+impl Default for RebootModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RebootModule {
+    pub fn new() -> Self {
+        RebootModule
+    }
+
+    /// Reboot the host, then poll for it to come back until `test_command` succeeds
+    /// or `reboot_timeout` seconds elapse.
+    ///
+    /// The host's cached connections are dropped from `pool` before the reboot, and
+    /// each poll opens a fresh one with `connect_timeout`, which later tasks reuse.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &AnyConnection,
+        pool: &ConnectionPool,
+        reboot_timeout: u64,
+        connect_timeout: u64,
+        post_reboot_delay: u64,
+        test_command: &str,
+    ) -> Result<TaskOutput, NexusError> {
+        // Check mode
+        if ctx.check_mode {
+            return Ok(TaskOutput::changed().with_stdout(format!(
+                "Would reboot and wait up to {}s for the host to come back",
+                reboot_timeout
+            )));
+        }
+
+        let AnyConnection::Ssh(ssh) = conn else {
+            return Err(module_error(
+                "reboot",
+                conn.as_connection(),
+                "Refusing to reboot the control node".to_string(),
+                None,
+                Some("Run reboot against remote hosts over SSH"),
+            ));
+        };
+
+        let before = boot_state(ssh).await;
+        let start = Instant::now();
+
+        // The connection may drop before the command returns; that's the reboot happening
+        let went_down = match ssh.exec(&ctx.wrap_command(REBOOT_COMMAND)) {
+            Ok(result) if !result.success() => {
+                return Err(module_error(
+                    "reboot",
+                    ssh,
+                    format!("Reboot command exited with code {}", result.exit_code),
+                    Some(result.stderr),
+                    Some("Rebooting usually needs root; set sudo: true on the task"),
+                ));
+            }
+            Ok(_) => false,
+            Err(_) => true,
+        };
+        pool.invalidate(&ctx.host);
+
+        if post_reboot_delay > 0 {
+            tokio::time::sleep(Duration::from_secs(post_reboot_delay)).await;
+        }
+
+        let deadline = start + Duration::from_secs(reboot_timeout);
+        let mut went_down = went_down;
+
+        loop {
+            match pool.reconnect(&ctx.host, Duration::from_secs(connect_timeout)) {
+                Err(_) => went_down = true,
+                Ok(new_conn) => {
+                    let after = boot_state(&new_conn).await;
+                    let rebooted = match (&before, &after) {
+                        (Some(before), Some(after)) => before.id != after.id,
+                        // Without a boot ID, seeing the host drop off is all there is to go on
+                        _ => went_down,
+                    };
+
+                    if !rebooted {
+                        // Still the old boot: shutdown hasn't got to sshd yet
+                        pool.invalidate(&ctx.host);
+                    } else if new_conn
+                        .exec(&ctx.wrap_command(test_command))
+                        .is_ok_and(|result| result.success())
+                    {
+                        return Ok(rebooted_output(
+                            start.elapsed(),
+                            before.and_then(|b| b.time),
+                            after.and_then(|a| a.time),
+                        ));
+                    }
+                }
+            }
+
+            let elapsed = start.elapsed();
+            if Instant::now() >= deadline {
+                return Ok(TaskOutput::failed(format!(
+                    "Timeout waiting for the host to come back after reboot (elapsed {}s)",
+                    elapsed.as_secs()
+                ))
+                .with_data("elapsed", Value::Int(elapsed.as_secs() as i64)));
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Module for RebootModule {
+    fn name(&self) -> &'static str {
+        "reboot"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+/// Read the host's boot ID and boot time, if the host exposes them (Linux does)
+async fn boot_state(conn: &dyn Connection) -> Option<BootState> {
+    let result = conn.exec(BOOT_STATE_COMMAND).await.ok()?;
+    parse_boot_state(&result.stdout)
+}
+
+fn parse_boot_state(stdout: &str) -> Option<BootState> {
+    let mut lines = stdout.lines().map(str::trim);
+    let id = lines.next().filter(|id| !id.is_empty())?.to_string();
+    let time = lines.next().and_then(|t| t.parse().ok());
+    Some(BootState { id, time })
+}
+
+fn rebooted_output(elapsed: Duration, before: Option<i64>, after: Option<i64>) -> TaskOutput {
+    let mut stdout = format!("Host rebooted and came back after {}s", elapsed.as_secs());
+    let mut output = TaskOutput::changed()
+        .with_data("rebooted", Value::Bool(true))
+        .with_data("elapsed", Value::Int(elapsed.as_secs() as i64));
+
+    if let (Some(before), Some(after)) = (before, after) {
+        stdout.push_str(&format!(" (boot time moved {}s)", after - before));
+        output = output.with_data("boot_time_delta", Value::Int(after - before));
+    }
+    if let Some(after) = after {
+        output = output.with_data("boot_time", Value::Int(after));
+    }
+
+    output.with_stdout(stdout)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_boot_state() {
+        let state = parse_boot_state("3f1c0c8e-5b1d-4a7e-9a43-1f0f5c2e8b11\n1760600000\n").unwrap();
+        assert_eq!(state.id, "3f1c0c8e-5b1d-4a7e-9a43-1f0f5c2e8b11");
+        assert_eq!(state.time, Some(1760600000));
+
+        assert_eq!(parse_boot_state(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_reboot_refuses_control_node() {
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new());
+        let conn = AnyConnection::Local(LocalConnection::new("localhost"));
+        let pool = ConnectionPool::new();
+
+        let err = RebootModule::new()
+            .execute_with_params(&ctx, &conn, &pool, 600, 5, 0, "whoami")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("control node"));
+
+        let mut check_ctx = ctx.clone();
+        check_ctx.check_mode = true;
+        let output = RebootModule::new()
+            .execute_with_params(&check_ctx, &conn, &pool, 600, 5, 0, "whoami")
+            .await
+            .unwrap();
+        assert!(output.changed);
+    }
+}
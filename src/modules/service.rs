@@ -28,21 +28,31 @@ impl ServiceModule {
         state: ServiceState,
         enabled: Option<bool>,
     ) -> Result<TaskOutput, NexusError> {
-        // Check mode
+        // Get current state
+        let current_state = get_service_state(conn, name).await?;
+
+        // Check mode - report whether the service would change
         if ctx.check_mode {
             let mut msg = format!("Would {} service: {}", state_action(&state), name);
             if let Some(en) = enabled {
                 msg.push_str(&format!(", enabled={}", en));
             }
-            return Ok(TaskOutput::changed().with_stdout(msg));
+            let would_change = match state {
+                ServiceState::Running => !current_state.running,
+                ServiceState::Stopped => current_state.running,
+                ServiceState::Restarted | ServiceState::Reloaded => true,
+            } || enabled.is_some_and(|en| en != current_state.enabled);
+            let output = if would_change {
+                TaskOutput::changed().with_stdout(msg)
+            } else {
+                TaskOutput::success().with_stdout(format!("{} (no changes)", msg))
+            };
+            return Ok(output);
         }
 
         let mut changed = false;
         let mut output_lines = Vec::new();
 
-        // Get current state
-        let current_state = get_service_state(conn, name).await?;
-
         // Handle state changes
         match state {
             ServiceState::Running => {
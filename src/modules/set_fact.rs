@@ -0,0 +1,89 @@
+// Set fact module - set host variables from expressions
+
+use async_trait::async_trait;
+
+use super::Module;
+use crate::executor::{ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Expression;
+use crate::runtime::evaluate_expression;
+
+pub struct SetFactModule;
+
+impl Default for SetFactModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetFactModule {
+    pub fn new() -> Self {
+        SetFactModule
+    }
+
+    /// Evaluate every value before setting any, so facts in the same task see the
+    /// variables as they were. The values are returned as the task's data, which is
+    /// what `cacheable` stores. Nothing changes on the host, so this runs in check mode.
+    pub fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        facts: &[(String, Expression)],
+    ) -> Result<TaskOutput, NexusError> {
+        let values = facts
+            .iter()
+            .map(|(name, expr)| Ok((name, evaluate_expression(expr, ctx)?)))
+            .collect::<Result<Vec<_>, NexusError>>()?;
+
+        let mut output = TaskOutput::success();
+        for (name, value) in values {
+            ctx.set_var(name.clone(), value.clone());
+            output = output.with_data(name.clone(), value);
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl Module for SetFactModule {
+    fn name(&self) -> &'static str {
+        "set_fact"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Host;
+    use crate::parser::ast::Value;
+    use crate::parser::expressions::parse_expression;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_set_fact_evaluates_before_setting() {
+        let mut vars = HashMap::new();
+        vars.insert("port".to_string(), Value::Int(80));
+        let ctx = ExecutionContext::new(Arc::new(Host::new("web1")), vars);
+
+        let facts = vec![
+            ("port".to_string(), parse_expression("port + 8000").unwrap()),
+            ("old_port".to_string(), parse_expression("port").unwrap()),
+        ];
+        let output = SetFactModule::new()
+            .execute_with_params(&ctx, &facts)
+            .unwrap();
+
+        assert!(!output.changed);
+        assert!(matches!(ctx.get_var("port"), Some(Value::Int(8080))));
+        assert!(matches!(ctx.get_var("old_port"), Some(Value::Int(80))));
+        assert!(matches!(output.data.get("old_port"), Some(Value::Int(80))));
+    }
+}
@@ -3,7 +3,7 @@
 
 use async_trait::async_trait;
 
-use super::Module;
+use super::{exec_task_command, prefix_environment, Module};
 use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
 use crate::output::errors::NexusError;
 
@@ -20,6 +20,7 @@ impl ShellModule {
         ShellModule
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn execute_with_params(
         &self,
         ctx: &ExecutionContext,
@@ -28,6 +29,8 @@ impl ShellModule {
         chdir: Option<String>,
         creates: Option<String>,
         removes: Option<String>,
+        environment: &[(String, String)],
+        stdin: Option<String>,
     ) -> Result<TaskOutput, NexusError> {
         // Check mode
         if ctx.check_mode {
@@ -80,13 +83,16 @@ impl ShellModule {
         // Wrap the command in /bin/sh -c to allow shell features
         // Escape single quotes in the command
         let escaped_command = command.replace('\'', "'\\''");
-        shell_cmd.push_str(&format!("/bin/sh -c '{}'", escaped_command));
+        shell_cmd.push_str(&prefix_environment(
+            environment,
+            &format!("/bin/sh -c '{}'", escaped_command),
+        ));
 
         // Wrap command with sudo if needed
         let final_command = ctx.wrap_command(&shell_cmd);
 
         // Execute the command
-        let result = conn.exec(&final_command).await?;
+        let result = exec_task_command(ctx, conn, &final_command, stdin.as_deref()).await?;
 
         if result.success() {
             Ok(TaskOutput::changed()
@@ -0,0 +1,97 @@
+// Slurp module - read a remote file into a registered variable
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use super::{module_error, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+pub struct SlurpModule;
+
+impl Default for SlurpModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlurpModule {
+    pub fn new() -> Self {
+        SlurpModule
+    }
+
+    /// Read `src` and return it as base64 `content` (like Ansible) plus the decoded `text`.
+    /// Reading has no side effects, so this runs the same way in check mode.
+    pub async fn execute_with_params(
+        &self,
+        _ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        src: &str,
+    ) -> Result<TaskOutput, NexusError> {
+        let text = conn.read_file(src).await.map_err(|e| {
+            module_error(
+                "slurp",
+                conn,
+                format!("Failed to read {}", src),
+                Some(e.to_string()),
+                Some("Check that the file exists and is readable"),
+            )
+        })?;
+
+        Ok(TaskOutput::success()
+            .with_data("content", Value::String(BASE64.encode(text.as_bytes())))
+            .with_data("encoding", Value::String("base64".to_string()))
+            .with_data("source", Value::String(src.to_string()))
+            .with_data("text", Value::String(text)))
+    }
+}
+#[async_trait]
+impl Module for SlurpModule {
+    fn name(&self) -> &'static str {
+        "slurp"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_slurp_encodes_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("motd");
+        std::fs::write(&src, "hello\n").unwrap();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new())
+            .with_check_mode(true);
+        let output = SlurpModule::new()
+            .execute_with_params(
+                &ctx,
+                &LocalConnection::new("localhost"),
+                &src.to_string_lossy(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!output.changed);
+        assert_eq!(
+            output.data["content"],
+            Value::String("aGVsbG8K".to_string())
+        );
+        assert_eq!(output.data["text"], Value::String("hello\n".to_string()));
+    }
+}
@@ -0,0 +1,217 @@
+// Stat module - report a remote path's metadata in a registered variable
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+/// `stat -c` format for the fields `parse_stat_output` reads, `|`-separated
+const STAT_FORMAT: &str = "%F|%s|%a|%u|%g|%U|%G|%Y";
+
+pub struct StatModule;
+
+impl Default for StatModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatModule {
+    pub fn new() -> Self {
+        StatModule
+    }
+
+    /// Look up `path` and return its metadata as a `stat` dict, like Ansible: `exists`,
+    /// and for a path that exists `isdir`, `isreg`, `islnk`, `size`, `mode`, `uid`,
+    /// `gid`, `pw_name`, `gr_name` and `mtime`. Symlinks are not followed. With
+    /// `get_checksum` a regular file also gets its SHA-1 `checksum`. Nothing is
+    /// changed, so this runs the same way in check mode.
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        path: &str,
+        get_checksum: bool,
+    ) -> Result<TaskOutput, NexusError> {
+        if conn.is_windows() {
+            return Err(module_error(
+                "stat",
+                conn,
+                "stat does not support Windows hosts".to_string(),
+                None,
+                None,
+            ));
+        }
+
+        let quoted = shell_quote(path);
+        let exists = conn
+            .exec(&ctx.wrap_command(&format!("test -e {0} -o -L {0}", quoted)))
+            .await?
+            .success();
+
+        let mut stat = HashMap::new();
+        stat.insert("path".to_string(), Value::String(path.to_string()));
+        stat.insert("exists".to_string(), Value::Bool(exists));
+        if !exists {
+            return Ok(TaskOutput::success().with_data("stat", Value::Dict(stat)));
+        }
+
+        let result = conn
+            .exec(&ctx.wrap_command(&format!("stat -c '{}' {}", STAT_FORMAT, quoted)))
+            .await?;
+        if !result.success() {
+            return Err(module_error(
+                "stat",
+                conn,
+                format!("Failed to stat {}", path),
+                Some(result.stderr),
+                Some("Check that the path is readable, or run the task with sudo"),
+            ));
+        }
+        let fields = parse_stat_output(&result.stdout).ok_or_else(|| {
+            module_error(
+                "stat",
+                conn,
+                format!("Unexpected stat output for {}", path),
+                Some(result.stdout.clone()),
+                None,
+            )
+        })?;
+        let is_regular = fields.get("isreg") == Some(&Value::Bool(true));
+        stat.extend(fields);
+
+        if get_checksum && is_regular {
+            let result = conn
+                .exec(&ctx.wrap_command(&format!("sha1sum {}", quoted)))
+                .await?;
+            if !result.success() {
+                return Err(module_error(
+                    "stat",
+                    conn,
+                    format!("Failed to checksum {}", path),
+                    Some(result.stderr),
+                    None,
+                ));
+            }
+            let checksum = result.stdout.split_whitespace().next().unwrap_or_default();
+            stat.insert("checksum".to_string(), Value::String(checksum.to_string()));
+        }
+
+        Ok(TaskOutput::success().with_data("stat", Value::Dict(stat)))
+    }
+}
+
+/// Turn one line of `stat -c STAT_FORMAT` output into `stat` fields
+fn parse_stat_output(output: &str) -> Option<HashMap<String, Value>> {
+    let fields: Vec<&str> = output.trim_end_matches('\n').split('|').collect();
+    let [kind, size, mode, uid, gid, user, group, mtime] = fields.as_slice() else {
+        return None;
+    };
+
+    let mut stat = HashMap::new();
+    stat.insert("isdir".to_string(), Value::Bool(*kind == "directory"));
+    stat.insert(
+        "isreg".to_string(),
+        Value::Bool(kind.starts_with("regular")),
+    );
+    stat.insert("islnk".to_string(), Value::Bool(*kind == "symbolic link"));
+    stat.insert("size".to_string(), Value::Int(size.parse().ok()?));
+    let mode = u32::from_str_radix(mode, 8).ok()?;
+    stat.insert("mode".to_string(), Value::String(format!("{:04o}", mode)));
+    stat.insert("uid".to_string(), Value::Int(uid.parse().ok()?));
+    stat.insert("gid".to_string(), Value::Int(gid.parse().ok()?));
+    stat.insert("pw_name".to_string(), Value::String(user.to_string()));
+    stat.insert("gr_name".to_string(), Value::String(group.to_string()));
+    stat.insert("mtime".to_string(), Value::Int(mtime.parse().ok()?));
+    Some(stat)
+}
+#[async_trait]
+impl Module for StatModule {
+    fn name(&self) -> &'static str {
+        "stat"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_stat_output() {
+        let stat =
+            parse_stat_output("regular empty file|0|600|0|0|root|root|1700000000\n").unwrap();
+        assert_eq!(stat["isreg"], Value::Bool(true));
+        assert_eq!(stat["isdir"], Value::Bool(false));
+        assert_eq!(stat["mode"], Value::String("0600".to_string()));
+        assert_eq!(stat["mtime"], Value::Int(1_700_000_000));
+
+        let stat = parse_stat_output("symbolic link|11|777|0|0|root|root|1700000000\n").unwrap();
+        assert_eq!(stat["islnk"], Value::Bool(true));
+        assert_eq!(stat["mode"], Value::String("0777".to_string()));
+
+        assert!(parse_stat_output("directory|4096|755|0|0\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stat_reports_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.conf");
+        std::fs::write(&file, "hello\n").unwrap();
+
+        let ctx = ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new())
+            .with_check_mode(true);
+        let conn = LocalConnection::new("localhost");
+        let module = StatModule::new();
+
+        let output = module
+            .execute_with_params(&ctx, &conn, &file.to_string_lossy(), true)
+            .await
+            .unwrap();
+        assert!(!output.changed);
+        let Value::Dict(ref stat) = output.data["stat"] else {
+            panic!("stat should be a dict");
+        };
+        assert_eq!(stat["exists"], Value::Bool(true));
+        assert_eq!(stat["isreg"], Value::Bool(true));
+        assert_eq!(stat["size"], Value::Int(6));
+        assert_eq!(
+            stat["checksum"],
+            Value::String("f572d396fae9206628714fb2ce00f72e94f2258f".to_string())
+        );
+
+        let output = module
+            .execute_with_params(&ctx, &conn, &dir.path().to_string_lossy(), true)
+            .await
+            .unwrap();
+        let Value::Dict(ref stat) = output.data["stat"] else {
+            panic!("stat should be a dict");
+        };
+        assert_eq!(stat["isdir"], Value::Bool(true));
+        assert!(!stat.contains_key("checksum"));
+
+        let missing = dir.path().join("missing");
+        let output = module
+            .execute_with_params(&ctx, &conn, &missing.to_string_lossy(), false)
+            .await
+            .unwrap();
+        let Value::Dict(ref stat) = output.data["stat"] else {
+            panic!("stat should be a dict");
+        };
+        assert_eq!(stat["exists"], Value::Bool(false));
+        assert!(!stat.contains_key("isreg"));
+    }
+}
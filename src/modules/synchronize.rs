@@ -0,0 +1,340 @@
+// Synchronize module - rsync directories between the control node and the host
+
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::{module_error, AnyConnection, Module};
+use crate::executor::{Connection, ConnectionPool, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{SyncMode, Value};
+
+/// Prefix of the line rsync prints for every item it transfers, deletes or updates
+const CHANGED_MARKER: &str = "<<CHANGED>>";
+
+pub struct SynchronizeModule;
+
+impl Default for SynchronizeModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What an rsync run should do, turned into its arguments by `args`
+#[derive(Debug, Clone)]
+struct RsyncRun<'a> {
+    from: String,
+    to: String,
+    /// `-e` transport for a remote host; `None` copies between local paths
+    ssh: Option<String>,
+    /// `--rsync-path` on the host, to run rsync there with sudo
+    remote_rsync: Option<String>,
+    delete: bool,
+    archive: bool,
+    checksum: bool,
+    dry_run: bool,
+    rsync_opts: &'a [String],
+}
+
+impl RsyncRun<'_> {
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--delay-updates".to_string(),
+            format!("--out-format={}%i %n%L", CHANGED_MARKER),
+        ];
+        args.push(
+            if self.archive {
+                "--archive"
+            } else {
+                "--recursive"
+            }
+            .to_string(),
+        );
+        if self.delete {
+            args.push("--delete-after".to_string());
+        }
+        if self.checksum {
+            args.push("--checksum".to_string());
+        }
+        if self.dry_run {
+            args.push("--dry-run".to_string());
+        }
+        if let Some(ref ssh) = self.ssh {
+            args.push("-e".to_string());
+            args.push(ssh.clone());
+        }
+        if let Some(ref path) = self.remote_rsync {
+            args.push(format!("--rsync-path={}", path));
+        }
+        args.extend(self.rsync_opts.iter().cloned());
+        args.push(self.from.clone());
+        args.push(self.to.clone());
+        args
+    }
+}
+
+impl SynchronizeModule {
+    pub fn new() -> Self {
+        SynchronizeModule
+    }
+
+    /// Run rsync on the control node, pushing `src` to `dest` on the host or pulling
+    /// `src` from it. A host reached over SSH is addressed the way the pool connects
+    /// to it, a docker host through `docker exec`; with a local connection both paths
+    /// are local. In check mode rsync runs with `--dry-run`, so the result still
+    /// lists what would change.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &AnyConnection,
+        pool: &ConnectionPool,
+        src: &str,
+        dest: &str,
+        mode: SyncMode,
+        delete: bool,
+        archive: bool,
+        checksum: bool,
+        rsync_opts: &[String],
+    ) -> Result<TaskOutput, NexusError> {
+        let target = conn.as_connection();
+        ensure_local_rsync(target).await?;
+
+        let (remote_prefix, ssh) = match conn {
+            AnyConnection::Ssh(_) => {
+                let host = &ctx.host;
+                let prefix = format!("{}@{}:", pool.login_user(host), rsync_host(&host.address));
+                (Some(prefix), Some(pool.openssh_command(host)))
+            }
+            // rsync treats `container:` as the "host" and starts itself there through the transport
+            AnyConnection::Docker(docker) => (
+                Some(format!("{}:", docker.container())),
+                Some("docker exec -i".to_string()),
+            ),
+            #[cfg(feature = "winrm")]
+            AnyConnection::WinRm(_) => {
+                return Err(module_error(
+                    "synchronize",
+                    target,
+                    "synchronize does not support WinRM hosts".to_string(),
+                    None,
+                    Some("Copy files to Windows hosts with the file or template module"),
+                ))
+            }
+            AnyConnection::Local(_) => (None, None),
+        };
+
+        let mut remote_rsync = None;
+        if remote_prefix.is_some() {
+            let found = target.exec("command -v rsync").await?.success();
+            if !found {
+                return Err(module_error(
+                    "synchronize",
+                    target,
+                    format!("rsync is not installed on {}", target.host_name()),
+                    None,
+                    Some("Install rsync on the host, e.g. with the package module"),
+                ));
+            }
+            remote_rsync = ctx.sudo.then(|| match ctx.sudo_user {
+                Some(ref user) => format!("sudo -n -u {} rsync", user),
+                None => "sudo -n rsync".to_string(),
+            });
+        }
+
+        let remote = |path: &str| match remote_prefix {
+            Some(ref prefix) => format!("{}{}", prefix, path),
+            None => path.to_string(),
+        };
+        let (from, to) = match mode {
+            SyncMode::Push => (src.to_string(), remote(dest)),
+            SyncMode::Pull => (remote(src), dest.to_string()),
+        };
+
+        let run = RsyncRun {
+            from,
+            to,
+            ssh,
+            remote_rsync,
+            delete,
+            archive,
+            checksum,
+            dry_run: ctx.check_mode,
+            rsync_opts,
+        };
+        let args = run.args();
+
+        let output = Command::new("rsync")
+            .args(&args)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                module_error(
+                    "synchronize",
+                    target,
+                    "Failed to run rsync".to_string(),
+                    Some(e.to_string()),
+                    None,
+                )
+            })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+        if !output.status.success() {
+            return Err(module_error(
+                "synchronize",
+                target,
+                format!(
+                    "rsync from {} to {} failed ({})",
+                    run.from, run.to, output.status
+                ),
+                Some(stderr),
+                None,
+            ));
+        }
+
+        let changes = itemized_changes(&stdout);
+        let result = if changes.is_empty() {
+            TaskOutput::success()
+        } else {
+            TaskOutput::changed()
+        };
+
+        Ok(result
+            .with_stdout(changes.join("\n"))
+            .with_stderr(stderr)
+            .with_data("cmd", Value::String(format!("rsync {}", args.join(" "))))
+            .with_data(
+                "changes",
+                Value::List(changes.into_iter().map(Value::String).collect()),
+            ))
+    }
+}
+
+/// Fail with a clear message when the control node has no rsync to run
+async fn ensure_local_rsync(conn: &dyn Connection) -> Result<(), NexusError> {
+    match Command::new("rsync").arg("--version").output().await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(module_error(
+            "synchronize",
+            conn,
+            "rsync is not installed on the control node".to_string(),
+            None,
+            Some("Install rsync where nexus runs (e.g. apt-get install rsync)"),
+        )),
+        Err(e) => Err(module_error(
+            "synchronize",
+            conn,
+            "Failed to run rsync".to_string(),
+            Some(e.to_string()),
+            None,
+        )),
+    }
+}
+
+/// An address as rsync expects it before `:path`, with IPv6 in brackets
+fn rsync_host(address: &str) -> String {
+    if address.contains(':') && !address.starts_with('[') {
+        format!("[{}]", address)
+    } else {
+        address.to_string()
+    }
+}
+
+/// The items rsync reported as transferred, deleted or updated, such as
+/// `>f+++++++++ index.html` or `*deleting   old.css`
+fn itemized_changes(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(CHANGED_MARKER))
+        .map(|item| item.to_string())
+        .collect()
+}
+#[async_trait]
+impl Module for SynchronizeModule {
+    fn name(&self) -> &'static str {
+        "synchronize"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rsync_args() {
+        let opts = vec!["--exclude=.git".to_string()];
+        let run = RsyncRun {
+            from: "dist/".to_string(),
+            to: "deploy@10.0.0.5:/var/www/app/".to_string(),
+            ssh: Some("ssh -p 2222 -o BatchMode=yes".to_string()),
+            remote_rsync: Some("sudo -n rsync".to_string()),
+            delete: true,
+            archive: true,
+            checksum: true,
+            dry_run: true,
+            rsync_opts: &opts,
+        };
+        assert_eq!(
+            run.args(),
+            vec![
+                "--delay-updates",
+                "--out-format=<<CHANGED>>%i %n%L",
+                "--archive",
+                "--delete-after",
+                "--checksum",
+                "--dry-run",
+                "-e",
+                "ssh -p 2222 -o BatchMode=yes",
+                "--rsync-path=sudo -n rsync",
+                "--exclude=.git",
+                "dist/",
+                "deploy@10.0.0.5:/var/www/app/",
+            ]
+        );
+
+        let local = RsyncRun {
+            ssh: None,
+            remote_rsync: None,
+            archive: false,
+            delete: false,
+            checksum: false,
+            dry_run: false,
+            rsync_opts: &[],
+            ..run
+        };
+        assert_eq!(local.args()[2], "--recursive");
+        assert!(!local.args().contains(&"-e".to_string()));
+    }
+
+    #[test]
+    fn test_itemized_changes() {
+        let stdout = "\
+<<CHANGED>>.d..t...... ./
+<<CHANGED>>>f+++++++++ index.html
+<<CHANGED>>*deleting   old.css
+
+sent 1,234 bytes  received 56 bytes
+";
+        assert_eq!(
+            itemized_changes(stdout),
+            vec![
+                ".d..t...... ./",
+                ">f+++++++++ index.html",
+                "*deleting   old.css"
+            ]
+        );
+        assert!(itemized_changes("sent 120 bytes  received 12 bytes\n").is_empty());
+
+        assert_eq!(rsync_host("10.0.0.5"), "10.0.0.5");
+        assert_eq!(rsync_host("fe80::1"), "[fe80::1]");
+    }
+}
@@ -0,0 +1,339 @@
+// Systemd module - manage units with systemd-specific operations
+
+use async_trait::async_trait;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{ServiceState, SystemdScope, Value};
+
+pub struct SystemdModule;
+
+impl Default for SystemdModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unit state as reported by `systemctl is-active` / `is-enabled`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UnitState {
+    active: String,
+    enabled: String,
+}
+
+impl UnitState {
+    fn is_active(&self) -> bool {
+        self.active == "active"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled == "enabled"
+    }
+
+    fn is_masked(&self) -> bool {
+        self.enabled.starts_with("masked")
+    }
+}
+
+impl SystemdModule {
+    pub fn new() -> Self {
+        SystemdModule
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        name: Option<&str>,
+        state: Option<ServiceState>,
+        enabled: Option<bool>,
+        daemon_reload: bool,
+        masked: Option<bool>,
+        scope: SystemdScope,
+    ) -> Result<TaskOutput, NexusError> {
+        // Refuse early on hosts that are not booted with systemd
+        if !conn.exec("test -d /run/systemd/system").await?.success() {
+            return Err(module_error(
+                "systemd",
+                conn,
+                "systemd is not the init system on this host".to_string(),
+                None,
+                Some("Use the service module for non-systemd hosts"),
+            ));
+        }
+
+        let systemctl = match scope {
+            SystemdScope::System => "systemctl",
+            SystemdScope::User => "systemctl --user",
+        };
+
+        let Some(name) = name else {
+            // Without a unit only daemon_reload makes sense
+            if !daemon_reload {
+                return Err(module_error(
+                    "systemd",
+                    conn,
+                    "systemd module requires 'name' unless daemon_reload is set".to_string(),
+                    None,
+                    Some("Add name: <unit>"),
+                ));
+            }
+            if ctx.check_mode {
+                return Ok(check_mode_report(None, true, &[]));
+            }
+            run(ctx, conn, systemctl, "daemon-reload", None).await?;
+            return Ok(TaskOutput::changed().with_stdout("Reloaded systemd daemon"));
+        };
+
+        let before = unit_state(conn, systemctl, name).await?;
+        let actions = plan_actions(&before, state, enabled, masked);
+
+        // Check mode
+        if ctx.check_mode {
+            return Ok(check_mode_report(Some(name), daemon_reload, &actions));
+        }
+
+        let mut output_lines = Vec::new();
+
+        if daemon_reload {
+            run(ctx, conn, systemctl, "daemon-reload", None).await?;
+            output_lines.push("Reloaded systemd daemon".to_string());
+        }
+
+        for action in &actions {
+            if *action == "reload" {
+                // Not every unit supports reload
+                if run(ctx, conn, systemctl, "reload", Some(name))
+                    .await
+                    .is_err()
+                {
+                    run(ctx, conn, systemctl, "reload-or-restart", Some(name)).await?;
+                }
+            } else {
+                run(ctx, conn, systemctl, action, Some(name)).await?;
+            }
+            output_lines.push(format!("{} {}", past_tense(action), name));
+        }
+
+        let after = unit_state(conn, systemctl, name).await?;
+        let forced = actions.iter().any(|a| matches!(*a, "restart" | "reload"));
+        let changed = daemon_reload || forced || before != after;
+
+        if actions.is_empty() {
+            output_lines.push(format!("Unit {} is already in the desired state", name));
+        }
+
+        let output = if changed {
+            TaskOutput::changed()
+        } else {
+            TaskOutput::success()
+        };
+        Ok(output
+            .with_stdout(output_lines.join("\n"))
+            .with_data("active_state", Value::String(after.active))
+            .with_data("enabled_state", Value::String(after.enabled)))
+    }
+}
+
+/// What a run would do, without doing it. A daemon-reload always runs when asked
+/// for, so it counts as a change just like any planned unit action.
+fn check_mode_report(name: Option<&str>, daemon_reload: bool, actions: &[&str]) -> TaskOutput {
+    let mut lines: Vec<String> = Vec::new();
+    if daemon_reload {
+        lines.push("Would reload systemd daemon".to_string());
+    }
+    if let Some(name) = name {
+        lines.extend(actions.iter().map(|a| format!("Would {} {}", a, name)));
+        if actions.is_empty() {
+            lines.push(format!("Unit {} is already in the desired state", name));
+        }
+    }
+    let output = if daemon_reload || !actions.is_empty() {
+        TaskOutput::changed()
+    } else {
+        TaskOutput::success()
+    };
+    output.with_stdout(lines.join("\n"))
+}
+
+/// Work out the systemctl verbs needed to reach the desired state.
+/// Masking is handled first so a unit can be unmasked and then started.
+fn plan_actions(
+    current: &UnitState,
+    state: Option<ServiceState>,
+    enabled: Option<bool>,
+    masked: Option<bool>,
+) -> Vec<&'static str> {
+    let mut actions = Vec::new();
+
+    match masked {
+        Some(true) if !current.is_masked() => actions.push("mask"),
+        Some(false) if current.is_masked() => actions.push("unmask"),
+        _ => {}
+    }
+
+    match enabled {
+        Some(true) if !current.is_enabled() => actions.push("enable"),
+        Some(false) if current.is_enabled() => actions.push("disable"),
+        _ => {}
+    }
+
+    match state {
+        Some(ServiceState::Running) if !current.is_active() => actions.push("start"),
+        Some(ServiceState::Stopped) if current.is_active() => actions.push("stop"),
+        Some(ServiceState::Restarted) => actions.push("restart"),
+        Some(ServiceState::Reloaded) => actions.push("reload"),
+        _ => {}
+    }
+
+    actions
+}
+
+fn past_tense(action: &str) -> &'static str {
+    match action {
+        "mask" => "Masked",
+        "unmask" => "Unmasked",
+        "enable" => "Enabled",
+        "disable" => "Disabled",
+        "start" => "Started",
+        "stop" => "Stopped",
+        "restart" => "Restarted",
+        _ => "Reloaded",
+    }
+}
+
+/// Query the active and enabled state of a unit
+async fn unit_state(
+    conn: &dyn Connection,
+    systemctl: &str,
+    name: &str,
+) -> Result<UnitState, NexusError> {
+    let active = conn
+        .exec(&format!(
+            "{} is-active {} 2>/dev/null || true",
+            systemctl,
+            shell_quote(name)
+        ))
+        .await?;
+    let enabled = conn
+        .exec(&format!(
+            "{} is-enabled {} 2>/dev/null || true",
+            systemctl,
+            shell_quote(name)
+        ))
+        .await?;
+
+    Ok(UnitState {
+        active: active.stdout.trim().to_string(),
+        enabled: enabled.stdout.trim().to_string(),
+    })
+}
+
+/// Run a systemctl verb and turn a non-zero exit into a module error
+async fn run(
+    ctx: &ExecutionContext,
+    conn: &dyn Connection,
+    systemctl: &str,
+    verb: &str,
+    name: Option<&str>,
+) -> Result<(), NexusError> {
+    let cmd = match name {
+        Some(n) => format!("{} {} {}", systemctl, verb, shell_quote(n)),
+        None => format!("{} {}", systemctl, verb),
+    };
+    let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+    if result.success() {
+        return Ok(());
+    }
+
+    let suggestion = name.map(|n| format!("Check unit logs with: journalctl -u {}", n));
+    Err(module_error(
+        "systemd",
+        conn,
+        format!("'{}' failed", cmd),
+        Some(result.stderr),
+        suggestion.as_deref(),
+    ))
+}
+#[async_trait]
+impl Module for SystemdModule {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(active: &str, enabled: &str) -> UnitState {
+        UnitState {
+            active: active.to_string(),
+            enabled: enabled.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_actions_is_idempotent() {
+        let current = unit("active", "enabled");
+        assert!(plan_actions(
+            &current,
+            Some(ServiceState::Running),
+            Some(true),
+            Some(false)
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_plan_actions_unmasks_before_starting() {
+        let current = unit("inactive", "masked");
+        assert_eq!(
+            plan_actions(
+                &current,
+                Some(ServiceState::Running),
+                Some(true),
+                Some(false)
+            ),
+            vec!["unmask", "enable", "start"]
+        );
+    }
+
+    #[test]
+    fn test_plan_actions_restart_always_runs() {
+        let current = unit("active", "disabled");
+        assert_eq!(
+            plan_actions(&current, Some(ServiceState::Restarted), None, Some(true)),
+            vec!["mask", "restart"]
+        );
+        assert_eq!(
+            plan_actions(&current, Some(ServiceState::Stopped), Some(false), None),
+            vec!["stop"]
+        );
+    }
+
+    #[test]
+    fn test_check_mode_reports_daemon_reload_as_changed() {
+        let output = check_mode_report(None, true, &[]);
+        assert!(output.changed);
+        assert_eq!(output.stdout, "Would reload systemd daemon");
+
+        let output = check_mode_report(Some("nginx.service"), true, &[]);
+        assert!(output.changed);
+        assert!(output.stdout.contains("Would reload systemd daemon"));
+
+        let output = check_mode_report(Some("nginx.service"), false, &[]);
+        assert!(!output.changed);
+        assert!(output.stdout.contains("already in the desired state"));
+    }
+}
@@ -20,6 +20,8 @@ use std::path::Path;
 use crate::executor::ExecutionContext;
 use crate::output::errors::NexusError;
 use crate::parser::ast::Value;
+use crate::parser::expressions::parse_expression;
+use crate::runtime::{evaluate_expression, FilterArg};
 
 /// Template engine for Nexus
 pub struct TemplateEngine {
@@ -210,6 +212,7 @@ impl TemplateEngine {
             function: None,
             message: format!("Template not found: {}", name),
             suggestion: Some(format!("Searched paths: {:?}", self.search_paths)),
+            location: None,
         })
     }
 
@@ -523,7 +526,9 @@ impl TemplateEngine {
                 // Parse filter name and arguments
                 if let Some(paren_pos) = filter.find('(') {
                     let name = filter[..paren_pos].trim().to_string();
-                    let args_str = filter[paren_pos + 1..].trim_end_matches(')');
+                    let args_str = filter[paren_pos + 1..]
+                        .strip_suffix(')')
+                        .unwrap_or(&filter[paren_pos + 1..]);
                     let args: Vec<String> = if args_str.is_empty() {
                         Vec::new()
                     } else {
@@ -591,6 +596,29 @@ impl TemplateEngine {
         }
     }
 
+    /// Evaluate filter arguments, splitting out `key=value` keyword arguments
+    fn evaluate_filter_args(
+        &self,
+        args: &[String],
+        ctx: &ExecutionContext,
+    ) -> Result<(Vec<Value>, HashMap<String, Value>), NexusError> {
+        let mut values = Vec::new();
+        let mut kwargs = HashMap::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((key, val))
+                    if !val.starts_with('=')
+                        && !key.trim().is_empty()
+                        && key.trim().chars().all(|c| c.is_alphanumeric() || c == '_') =>
+                {
+                    kwargs.insert(key.trim().to_string(), self.evaluate_simple_expr(val, ctx)?);
+                }
+                _ => values.push(self.evaluate_simple_expr(arg, ctx)?),
+            }
+        }
+        Ok((values, kwargs))
+    }
+
     /// Apply a filter to a value
     fn apply_filter(
         &self,
@@ -627,6 +655,7 @@ impl TemplateEngine {
                         function: None,
                         message: "replace filter requires 2 arguments".to_string(),
                         suggestion: Some("Use: {{ value | replace('old', 'new') }}".to_string()),
+                        location: None,
                     })
                 }
             }
@@ -658,6 +687,7 @@ impl TemplateEngine {
                         function: None,
                         message: "join filter requires a list".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -685,6 +715,7 @@ impl TemplateEngine {
                         function: None,
                         message: "length filter requires string, list, or dict".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 };
                 Ok(Value::Int(len as i64))
@@ -699,6 +730,7 @@ impl TemplateEngine {
                         function: None,
                         message: "first filter requires list or string".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -710,6 +742,7 @@ impl TemplateEngine {
                         function: None,
                         message: "last filter requires list or string".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -721,6 +754,7 @@ impl TemplateEngine {
                         function: None,
                         message: "reverse filter requires list or string".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -737,6 +771,7 @@ impl TemplateEngine {
                         function: None,
                         message: "sort filter requires list".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -762,6 +797,7 @@ impl TemplateEngine {
                         function: None,
                         message: "unique filter requires list".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -817,6 +853,7 @@ impl TemplateEngine {
                         function: None,
                         message: "abs filter requires number".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
@@ -834,59 +871,25 @@ impl TemplateEngine {
                         function: None,
                         message: "round filter requires number".to_string(),
                         suggestion: None,
+                        location: None,
                     }),
                 }
             }
 
-            // Regex
-            "regex_search" => {
-                if let Some(pattern) = args.first() {
-                    let s = self.value_to_string(value);
-                    let re = Regex::new(pattern).map_err(|e| NexusError::Runtime {
-                        function: None,
-                        message: format!("Invalid regex: {}", e),
-                        suggestion: None,
-                    })?;
-                    if let Some(captures) = re.captures(&s) {
-                        if captures.len() > 1 {
-                            // Return captured group(s)
-                            let groups: Vec<Value> = captures.iter()
-                                .skip(1)
-                                .map(|m| m.map(|m| Value::String(m.as_str().to_string())).unwrap_or(Value::Null))
-                                .collect();
-                            Ok(Value::List(groups))
-                        } else {
-                            Ok(Value::String(captures[0].to_string()))
-                        }
-                    } else {
-                        Ok(Value::Null)
-                    }
-                } else {
-                    Err(NexusError::Runtime {
-                        function: None,
-                        message: "regex_search requires pattern argument".to_string(),
-                        suggestion: None,
-                    })
-                }
+            // Regex, encoding and hashing (shared with ${...} functions)
+            "regex_replace" | "regex_search" | "regex_findall" | "b64encode" | "b64decode"
+            | "hash" | "password_hash" | "dict2items" | "items2dict" => {
+                let (values, kwargs) = self.evaluate_filter_args(args, ctx)?;
+                let mut call_args = vec![value.clone()];
+                call_args.extend(values);
+                crate::runtime::call_builtin(filter, call_args, kwargs)
             }
-            "regex_replace" => {
-                if args.len() >= 2 {
-                    let s = self.value_to_string(value);
-                    let pattern = &args[0];
-                    let replacement = &args[1];
-                    let re = Regex::new(pattern).map_err(|e| NexusError::Runtime {
-                        function: None,
-                        message: format!("Invalid regex: {}", e),
-                        suggestion: None,
-                    })?;
-                    Ok(Value::String(re.replace_all(&s, replacement.as_str()).to_string()))
-                } else {
-                    Err(NexusError::Runtime {
-                        function: None,
-                        message: "regex_replace requires pattern and replacement".to_string(),
-                        suggestion: None,
-                    })
-                }
+
+            // Higher-order filters (shared with ${...} expressions)
+            "map" | "select" | "reject" | "selectattr" | "rejectattr" => {
+                let (values, kwargs) = self.evaluate_filter_args(args, ctx)?;
+                let args: Vec<FilterArg> = values.into_iter().map(FilterArg::Value).collect();
+                crate::runtime::apply_filter(value, filter, &args, &kwargs, ctx)
             }
 
             // File path operations
@@ -914,7 +917,8 @@ impl TemplateEngine {
             _ => Err(NexusError::Runtime {
                 function: None,
                 message: format!("Unknown filter: {}", filter),
-                suggestion: Some("Available filters: upper, lower, trim, replace, default, length, join, split, first, last, sort, unique, tojson, escape, regex_search, regex_replace, basename, dirname".to_string()),
+                suggestion: Some("Available filters: upper, lower, trim, replace, default, length, join, split, first, last, sort, unique, tojson, escape, map, select, reject, selectattr, rejectattr, regex_search, regex_replace, regex_findall, b64encode, b64decode, hash, password_hash, dict2items, items2dict, basename, dirname".to_string()),
+                location: None,
             }),
         }
     }
@@ -971,6 +975,16 @@ impl TemplateEngine {
             return Ok(Value::Null);
         }
 
+        // Function calls, such as lookup('env', 'HOME'), go through the expression
+        // evaluator with the template's loop variables in scope
+        if is_function_call(expr) {
+            let call_ctx = ctx.clone_for_task();
+            for (name, value) in &self.local_vars {
+                call_ctx.set_var(name.clone(), value.clone());
+            }
+            return parse_expression(expr).and_then(|e| evaluate_expression(&e, &call_ctx));
+        }
+
         // List literal [a, b, c]
         if expr.starts_with('[') && expr.ends_with(']') {
             let inner = &expr[1..expr.len() - 1];
@@ -982,7 +996,12 @@ impl TemplateEngine {
         }
 
         // Variable lookup with attribute access: vars.hostname, host.address
-        let parts: Vec<&str> = expr.split('.').collect();
+        let mut parts: Vec<&str> = expr.split('.').collect();
+
+        // `vars.port` is `port`, as in ${vars.port}
+        if parts.len() > 1 && parts[0] == "vars" && !self.local_vars.contains_key("vars") {
+            parts.remove(0);
+        }
 
         // First check local template variables (takes precedence)
         let mut value = if let Some(v) = self.local_vars.get(parts[0]) {
@@ -1078,6 +1097,16 @@ impl Default for TemplateEngine {
     }
 }
 
+/// Render the template file at `path`, looking up includes and parent templates next
+/// to it. Used by the template module and the `template` lookup.
+pub fn render_template_file(path: &Path, ctx: &ExecutionContext) -> Result<String, NexusError> {
+    let mut engine = TemplateEngine::new();
+    if let Some(parent) = path.parent() {
+        engine.add_search_path(parent.to_string_lossy().to_string());
+    }
+    engine.render_file(path, ctx)
+}
+
 /// Simple URL encoding (without external crate)
 fn urlencoding_encode(s: &str) -> String {
     let mut result = String::new();
@@ -1095,6 +1124,16 @@ fn urlencoding_encode(s: &str) -> String {
     result
 }
 
+/// Whether `expr` is a function call such as `lookup('env', 'HOME')`
+fn is_function_call(expr: &str) -> bool {
+    let Some((name, _)) = expr.split_once('(') else {
+        return false;
+    };
+    expr.ends_with(')')
+        && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1132,6 +1171,39 @@ mod tests {
         assert_eq!(result, "Hello World!");
     }
 
+    #[test]
+    fn test_lookup_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let motd = dir.path().join("motd");
+        std::fs::write(&motd, "Welcome\n").unwrap();
+        std::env::set_var("NEXUS_TEMPLATE_LOOKUP", "staging");
+
+        let mut engine = TemplateEngine::new();
+        let ctx = test_ctx();
+        ctx.set_var(
+            "paths",
+            Value::List(vec![Value::String(motd.to_string_lossy().to_string())]),
+        );
+
+        let result = engine
+            .render(
+                "env={{ lookup('env', 'NEXUS_TEMPLATE_LOOKUP') }} \
+                 {% for path in paths %}{{ lookup('file', path) }}{% endfor %}",
+                &ctx,
+            )
+            .unwrap();
+        assert_eq!(result, "env=staging Welcome");
+    }
+
+    #[test]
+    fn test_vars_prefix() {
+        let mut engine = TemplateEngine::new();
+        let ctx = test_ctx();
+
+        let result = engine.render("Hello {{ vars.name }}!", &ctx).unwrap();
+        assert_eq!(result, "Hello World!");
+    }
+
     #[test]
     fn test_filter_upper() {
         let mut engine = TemplateEngine::new();
@@ -1214,4 +1286,82 @@ mod tests {
         let result = engine.render("{{ items | length }}", &ctx).unwrap();
         assert_eq!(result, "3");
     }
+
+    #[test]
+    fn test_regex_filters() {
+        let mut engine = TemplateEngine::new();
+        let ctx = test_ctx();
+
+        let result = engine
+            .render(r"{{ name | regex_replace('(W)(orld)', '\2-\1') }}", &ctx)
+            .unwrap();
+        assert_eq!(result, "orld-W");
+
+        let result = engine
+            .render("{{ name | regex_search('WOR', ignorecase=True) }}", &ctx)
+            .unwrap();
+        assert_eq!(result, "Wor");
+
+        let result = engine
+            .render("{{ name | regex_findall('[lo]') | join(',') }}", &ctx)
+            .unwrap();
+        assert_eq!(result, "o,l");
+    }
+
+    #[test]
+    fn test_encoding_and_hash_filters() {
+        let mut engine = TemplateEngine::new();
+        let ctx = test_ctx();
+
+        let result = engine
+            .render("{{ name | b64encode }} {{ 'V29ybGQ=' | b64decode }}", &ctx)
+            .unwrap();
+        assert_eq!(result, "V29ybGQ= World");
+
+        let result = engine.render("{{ name | hash('sha256') }}", &ctx).unwrap();
+        assert_eq!(
+            result,
+            "78ae647dc5544d227130a0682a51e30bc7777fbb6d8a8f17007463a3ecd1d524"
+        );
+
+        let result = engine
+            .render(
+                "{{ 'Hello world!' | password_hash('sha512', 'saltstring') }}",
+                &ctx,
+            )
+            .unwrap();
+        assert!(result.starts_with("$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8"));
+    }
+
+    #[test]
+    fn test_higher_order_filters() {
+        let mut engine = TemplateEngine::new();
+        let ctx = test_ctx();
+        let user = |name: &str, admin: bool| {
+            let mut map = HashMap::new();
+            map.insert("name".to_string(), Value::String(name.to_string()));
+            map.insert("admin".to_string(), Value::Bool(admin));
+            Value::Dict(map)
+        };
+        ctx.set_var(
+            "users",
+            Value::List(vec![user("alice", true), user("bob", false)]),
+        );
+
+        let result = engine
+            .render(
+                "{{ users | selectattr('admin', 'equalto', True) | map(attribute='name') | join(',') }}",
+                &ctx,
+            )
+            .unwrap();
+        assert_eq!(result, "alice");
+
+        let result = engine
+            .render(
+                "{{ items | reject('equalto', 'b') | map('upper') | join('') }}",
+                &ctx,
+            )
+            .unwrap();
+        assert_eq!(result, "AC");
+    }
 }
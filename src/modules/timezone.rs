@@ -0,0 +1,197 @@
+// Timezone module - set the system timezone
+
+use async_trait::async_trait;
+
+use super::{module_error, shell_quote, Module};
+use crate::executor::facts::update_fact;
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::Value;
+
+/// Where timezone data is installed
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/// How the timezone is set on a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimezoneStrategy {
+    /// `timedatectl set-timezone`, on hosts running systemd
+    Systemd,
+    /// Point the `/etc/localtime` symlink at the zone file, e.g. Alpine or containers
+    Link,
+}
+
+pub struct TimezoneModule;
+
+impl Default for TimezoneModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimezoneModule {
+    pub fn new() -> Self {
+        TimezoneModule
+    }
+
+    /// Set the timezone to `name`, a zone from the tz database such as `Europe/Berlin`.
+    /// Uses `timedatectl` where systemd runs, otherwise links `/etc/localtime` to the
+    /// zone file; `/etc/timezone` is kept in step where it exists (Debian, Ubuntu). The
+    /// task reports `changed` only when the zone differed, and afterwards the
+    /// `timezone` fact holds the new zone.
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        name: &str,
+    ) -> Result<TaskOutput, NexusError> {
+        if conn.is_windows() {
+            return Err(module_error(
+                "timezone",
+                conn,
+                "timezone does not support Windows hosts".to_string(),
+                None,
+                None,
+            ));
+        }
+        let name = name.trim();
+        let zone_file = format!("{}/{}", ZONEINFO, name);
+        let known = is_valid_zone_name(name)
+            && conn
+                .exec(&format!("test -f {}", shell_quote(&zone_file)))
+                .await?
+                .success();
+        if !known {
+            return Err(module_error(
+                "timezone",
+                conn,
+                format!("Unknown timezone '{}'", name),
+                None,
+                Some("Use a zone from /usr/share/zoneinfo such as Europe/Berlin or UTC, and install tzdata if it is missing"),
+            ));
+        }
+
+        let link = conn.exec("readlink /etc/localtime").await?;
+        let current = link
+            .success()
+            .then(|| zone_from_link(&link.stdout))
+            .flatten();
+        let etc_timezone = conn.exec("cat /etc/timezone 2>/dev/null").await?;
+        let etc_timezone = etc_timezone
+            .success()
+            .then(|| etc_timezone.stdout.trim().to_string());
+        let was = current.clone().unwrap_or_else(|| "unknown".to_string());
+
+        let in_sync = etc_timezone.as_deref().map_or(true, |tz| tz == name);
+        if current.as_deref() == Some(name) && in_sync {
+            return Ok(TaskOutput::success()
+                .with_stdout(format!("Timezone is already {}", name))
+                .with_data("timezone", Value::String(name.to_string())));
+        }
+
+        if ctx.check_mode {
+            return Ok(TaskOutput::changed()
+                .with_stdout(format!("Would set timezone to {} (was {})", name, was))
+                .with_data("timezone", Value::String(name.to_string())));
+        }
+
+        let quoted = shell_quote(name);
+        let mut cmd = match detect_strategy(conn).await? {
+            TimezoneStrategy::Systemd => format!("timedatectl set-timezone {}", quoted),
+            TimezoneStrategy::Link => {
+                format!("ln -sf {} /etc/localtime", shell_quote(&zone_file))
+            }
+        };
+        if etc_timezone.is_some() {
+            cmd.push_str(&format!(" && echo {} > /etc/timezone", quoted));
+        }
+        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+        if !result.success() {
+            return Err(module_error(
+                "timezone",
+                conn,
+                format!("Failed to set timezone to {}", name),
+                Some(result.stderr),
+                Some("Setting the timezone needs root; run the task with sudo"),
+            ));
+        }
+
+        update_fact(ctx, "timezone", Value::String(name.to_string()));
+        Ok(TaskOutput::changed()
+            .with_stdout(format!("Set timezone to {} (was {})", name, was))
+            .with_data("timezone", Value::String(name.to_string())))
+    }
+}
+
+/// `timedatectl` where systemd is running, the `/etc/localtime` link everywhere else
+async fn detect_strategy(conn: &dyn Connection) -> Result<TimezoneStrategy, NexusError> {
+    let systemd = conn
+        .exec("command -v timedatectl >/dev/null 2>&1 && test -d /run/systemd/system")
+        .await?
+        .success();
+    Ok(if systemd {
+        TimezoneStrategy::Systemd
+    } else {
+        TimezoneStrategy::Link
+    })
+}
+
+/// A zone name that stays inside the zoneinfo directory (`Europe/Berlin`, `UTC`,
+/// `Etc/GMT+5`)
+fn is_valid_zone_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('/')
+        && name
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c))
+}
+
+/// The zone an `/etc/localtime` symlink points at
+/// (`../usr/share/zoneinfo/Europe/Berlin` -> `Europe/Berlin`)
+fn zone_from_link(target: &str) -> Option<String> {
+    let (_, zone) = target.trim().split_once("zoneinfo/")?;
+    let zone = zone.strip_prefix("posix/").unwrap_or(zone);
+    (!zone.is_empty()).then(|| zone.to_string())
+}
+#[async_trait]
+impl Module for TimezoneModule {
+    fn name(&self) -> &'static str {
+        "timezone"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_names() {
+        assert!(is_valid_zone_name("Europe/Berlin"));
+        assert!(is_valid_zone_name("UTC"));
+        assert!(is_valid_zone_name("Etc/GMT+5"));
+        assert!(!is_valid_zone_name(""));
+        assert!(!is_valid_zone_name("/etc/passwd"));
+        assert!(!is_valid_zone_name("../../etc/passwd"));
+        assert!(!is_valid_zone_name("Europe/Berlin; reboot"));
+
+        assert_eq!(
+            zone_from_link("../usr/share/zoneinfo/Europe/Berlin\n").as_deref(),
+            Some("Europe/Berlin")
+        );
+        assert_eq!(
+            zone_from_link("/usr/share/zoneinfo/posix/UTC").as_deref(),
+            Some("UTC")
+        );
+        assert_eq!(zone_from_link("/etc/localtime.bak"), None);
+    }
+}
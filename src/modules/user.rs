@@ -1,11 +1,17 @@
 // User module - manage system users
 
 use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::collections::BTreeSet;
 
-use super::Module;
+use super::{shell_quote, Module};
 use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::diff::Diff;
 use crate::output::errors::{ModuleError, NexusError};
-use crate::parser::ast::UserState;
+use crate::parser::ast::{UserState, Value};
+
+/// Shown in diffs in place of password hashes
+const MASKED_PASSWORD: &str = "********";
 
 pub struct UserModule;
 
@@ -15,206 +21,366 @@ impl Default for UserModule {
     }
 }
 
+/// What a `user` task manages besides the name and state. `None` (or an empty
+/// `groups`) leaves that attribute as it is.
+#[derive(Debug, Clone, Default)]
+pub struct UserParams {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Supplementary groups
+    pub groups: Vec<String>,
+    /// Add `groups` to the current supplementary groups instead of replacing them
+    pub append: bool,
+    pub shell: Option<String>,
+    pub home: Option<String>,
+    pub create_home: Option<bool>,
+    /// Create a system account (`useradd -r`); has no effect on existing accounts
+    pub system: bool,
+    /// Password hash as stored in /etc/shadow, e.g. from the `password_hash` filter
+    pub password: Option<String>,
+    /// Maximum days between password changes
+    pub password_expire_max: Option<i64>,
+    /// Minimum days between password changes
+    pub password_expire_min: Option<i64>,
+    /// Account expiry date; `Some(None)` removes the expiry
+    pub expires: Option<Option<NaiveDate>>,
+    /// Create an SSH key pair for the account unless the key file exists
+    pub generate_ssh_key: bool,
+    /// `ssh-keygen -t` type (default: rsa)
+    pub ssh_key_type: Option<String>,
+    /// Key file, relative to the home directory (default: `.ssh/id_<type>`)
+    pub ssh_key_file: Option<String>,
+}
+
 impl UserModule {
     pub fn new() -> Self {
         UserModule
     }
 
-    #[allow(clippy::too_many_arguments)]
+    /// Create, update or remove `name`. The account's current state is read first,
+    /// also in check mode, so check mode only reports real changes; in diff mode the
+    /// changed attributes are attached as a diff.
     pub async fn execute_with_params(
         &self,
         ctx: &ExecutionContext,
         conn: &dyn Connection,
         name: &str,
         state: UserState,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        groups: Vec<String>,
-        shell: Option<String>,
-        home: Option<String>,
-        create_home: Option<bool>,
+        params: &UserParams,
     ) -> Result<TaskOutput, NexusError> {
-        // Check mode
-        if ctx.check_mode {
-            let action = match state {
-                UserState::Present => "create/update",
-                UserState::Absent => "remove",
-            };
-            return Ok(
-                TaskOutput::changed().with_stdout(format!("Would {} user: {}", action, name))
-            );
-        }
-
         // Check if user exists
         let user_exists = conn
             .exec(&format!("id {} >/dev/null 2>&1", name))
             .await?
             .success();
+        let current = if user_exists {
+            Some(self.get_user_info(ctx, conn, name).await?)
+        } else {
+            None
+        };
 
-        match state {
-            UserState::Present => {
-                if user_exists {
-                    self.update_user(ctx, conn, name, uid, gid, groups, shell, home)
-                        .await
-                } else {
-                    self.create_user(ctx, conn, name, uid, gid, groups, shell, home, create_home)
-                        .await
-                }
+        let output = match (state, current) {
+            (UserState::Present, Some(current)) => {
+                self.update_user(ctx, conn, name, &current, params).await?
             }
-            UserState::Absent => {
-                if user_exists {
-                    self.remove_user(ctx, conn, name).await
-                } else {
-                    Ok(TaskOutput::success().with_stdout(format!("User {} does not exist", name)))
-                }
+            (UserState::Present, None) => self.create_user(ctx, conn, name, params).await?,
+            (UserState::Absent, Some(current)) => {
+                return self.remove_user(ctx, conn, name, &current).await
+            }
+            (UserState::Absent, None) => {
+                return Ok(
+                    TaskOutput::success().with_stdout(format!("User {} does not exist", name))
+                )
             }
+        };
+
+        if !params.generate_ssh_key {
+            return Ok(output);
         }
+        self.ensure_ssh_key(ctx, conn, name, params, output).await
     }
 
-    #[allow(clippy::too_many_arguments)]
     async fn create_user(
         &self,
         ctx: &ExecutionContext,
         conn: &dyn Connection,
         name: &str,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        groups: Vec<String>,
-        shell: Option<String>,
-        home: Option<String>,
-        create_home: Option<bool>,
+        params: &UserParams,
     ) -> Result<TaskOutput, NexusError> {
         let mut cmd = format!("useradd {}", name);
+        // Only what the task sets is known before the account exists
+        let mut attributes = vec![("name", name.to_string())];
 
-        if let Some(u) = uid {
+        if let Some(u) = params.uid {
             cmd.push_str(&format!(" -u {}", u));
+            attributes.push(("uid", u.to_string()));
         }
 
-        if let Some(g) = gid {
+        if let Some(g) = params.gid {
             cmd.push_str(&format!(" -g {}", g));
+            attributes.push(("gid", g.to_string()));
         }
 
-        if !groups.is_empty() {
-            cmd.push_str(&format!(" -G {}", groups.join(",")));
+        if let Some(ref h) = params.home {
+            cmd.push_str(&format!(" -d {}", h));
+            attributes.push(("home", h.clone()));
         }
 
-        if let Some(s) = shell {
+        if let Some(ref s) = params.shell {
             cmd.push_str(&format!(" -s {}", s));
+            attributes.push(("shell", s.clone()));
         }
 
-        if let Some(h) = home {
-            cmd.push_str(&format!(" -d {}", h));
+        if !params.groups.is_empty() {
+            cmd.push_str(&format!(" -G {}", params.groups.join(",")));
+            attributes.push(("groups", sorted_groups(&params.groups)));
         }
 
-        if create_home.unwrap_or(true) {
+        if params.system {
+            cmd.push_str(" -r");
+            attributes.push(("system", "true".to_string()));
+        }
+
+        if let Some(ref password) = params.password {
+            cmd.push_str(&format!(" -p {}", shell_quote(password)));
+            attributes.push(("password", MASKED_PASSWORD.to_string()));
+        }
+
+        if let Some(expires) = params.expires {
+            cmd.push_str(&format!(" -e {}", shell_quote(&expiry_arg(expires))));
+            attributes.push(("expires", format_expiry(expires)));
+        }
+
+        if params.create_home.unwrap_or(!params.system) {
             cmd.push_str(" -m");
         } else {
             cmd.push_str(" -M");
         }
 
-        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+        let chage = chage_command(name, params.password_expire_max, params.password_expire_min);
+        if let Some(days) = params.password_expire_min {
+            attributes.push(("password_expire_min", days.to_string()));
+        }
+        if let Some(days) = params.password_expire_max {
+            attributes.push(("password_expire_max", days.to_string()));
+        }
 
-        if result.success() {
-            Ok(TaskOutput::changed().with_stdout(format!("Created user {}", name)))
+        let diff = ctx.diff_mode.then(|| {
+            Diff::new(
+                "",
+                &attribute_lines(&attributes),
+                "/dev/null",
+                &format!("user {} (new)", name),
+            )
+        });
+
+        let output = if ctx.check_mode {
+            TaskOutput::changed().with_stdout(format!("Would create user {}", name))
         } else {
-            Err(NexusError::Module(Box::new(ModuleError {
-                module: "user".to_string(),
-                task_name: format!("Create user {}", name),
-                host: conn.host_name().to_string(),
-                message: format!("Failed to create user {}", name),
-                stderr: Some(result.stderr),
-                suggestion: None,
-            })))
-        }
+            for cmd in std::iter::once(cmd).chain(chage) {
+                let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+                if !result.success() {
+                    return Err(NexusError::Module(Box::new(ModuleError {
+                        module: "user".to_string(),
+                        task_name: format!("Create user {}", name),
+                        host: conn.host_name().to_string(),
+                        message: format!("Failed to create user {}", name),
+                        stderr: Some(result.stderr),
+                        suggestion: None,
+                    })));
+                }
+            }
+            TaskOutput::changed().with_stdout(format!("Created user {}", name))
+        };
+
+        Ok(match diff {
+            Some(diff) => output.with_diff(diff),
+            None => output,
+        })
     }
 
-    #[allow(clippy::too_many_arguments)]
     async fn update_user(
         &self,
         ctx: &ExecutionContext,
         conn: &dyn Connection,
         name: &str,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        groups: Vec<String>,
-        shell: Option<String>,
-        home: Option<String>,
+        current: &UserInfo,
+        params: &UserParams,
     ) -> Result<TaskOutput, NexusError> {
         let mut changes = Vec::new();
+        let mut desired = current.clone();
 
-        // Get current user info
-        let current = self.get_user_info(conn, name).await?;
+        // usermod options for what differs
+        let mut opts = String::new();
 
-        // Build usermod command if changes needed
-        let mut cmd = format!("usermod {}", name);
-        let mut has_changes = false;
-
-        if let Some(u) = uid {
+        if let Some(u) = params.uid {
             if current.uid != u {
-                cmd.push_str(&format!(" -u {}", u));
+                opts.push_str(&format!(" -u {}", u));
                 changes.push(format!("UID: {} -> {}", current.uid, u));
-                has_changes = true;
+                desired.uid = u;
             }
         }
 
-        if let Some(g) = gid {
+        if let Some(g) = params.gid {
             if current.gid != g {
-                cmd.push_str(&format!(" -g {}", g));
+                opts.push_str(&format!(" -g {}", g));
                 changes.push(format!("GID: {} -> {}", current.gid, g));
-                has_changes = true;
+                desired.gid = g;
             }
         }
 
-        if !groups.is_empty() {
-            // Check if groups are different
-            let current_groups: std::collections::HashSet<_> = current.groups.iter().collect();
-            let desired_groups: std::collections::HashSet<_> = groups.iter().collect();
+        if !params.groups.is_empty() {
+            let current_groups: BTreeSet<&String> = current.groups.iter().collect();
+            let mut desired_groups: BTreeSet<&String> = params.groups.iter().collect();
+            if params.append {
+                desired_groups.extend(&current_groups);
+            }
 
             if current_groups != desired_groups {
-                cmd.push_str(&format!(" -G {}", groups.join(",")));
-                changes.push(format!("Groups: {:?} -> {:?}", current.groups, groups));
-                has_changes = true;
+                let added: Vec<&String> = desired_groups
+                    .difference(&current_groups)
+                    .copied()
+                    .collect();
+                if params.append {
+                    let added: Vec<&str> = added.iter().map(|g| g.as_str()).collect();
+                    opts.push_str(&format!(" -a -G {}", added.join(",")));
+                } else {
+                    opts.push_str(&format!(" -G {}", params.groups.join(",")));
+                }
+                let removed = current_groups
+                    .difference(&desired_groups)
+                    .map(|g| format!("-{}", g));
+                changes.push(format!(
+                    "Groups: {}",
+                    added
+                        .iter()
+                        .map(|g| format!("+{}", g))
+                        .chain(removed)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                desired.groups = desired_groups.into_iter().cloned().collect();
             }
         }
 
-        if let Some(s) = shell {
-            if current.shell != s {
-                cmd.push_str(&format!(" -s {}", s));
+        if let Some(ref s) = params.shell {
+            if current.shell != *s {
+                opts.push_str(&format!(" -s {}", s));
                 changes.push(format!("Shell: {} -> {}", current.shell, s));
-                has_changes = true;
+                desired.shell = s.clone();
             }
         }
 
-        if let Some(h) = home {
-            if current.home != h {
-                cmd.push_str(&format!(" -d {}", h));
+        if let Some(ref h) = params.home {
+            if current.home != *h {
+                opts.push_str(&format!(" -d {}", h));
                 changes.push(format!("Home: {} -> {}", current.home, h));
-                has_changes = true;
+                desired.home = h.clone();
             }
         }
 
-        if !has_changes {
+        // Without read access to /etc/shadow the current values are unknown, so
+        // whatever the task sets is applied
+        let mut shadow = current.shadow.clone().unwrap_or_default();
+        let mut password_changed = false;
+
+        if let Some(ref password) = params.password {
+            if current.shadow.as_ref().map(|s| &s.password) != Some(password) {
+                opts.push_str(&format!(" -p {}", shell_quote(password)));
+                changes.push("Password: updated".to_string());
+                shadow.password = password.clone();
+                password_changed = true;
+            }
+        }
+
+        if let Some(expires) = params.expires {
+            if current.shadow.as_ref().map(|s| s.expires) != Some(expires) {
+                opts.push_str(&format!(
+                    " --expiredate {}",
+                    shell_quote(&expiry_arg(expires))
+                ));
+                changes.push(format!(
+                    "Expires: {} -> {}",
+                    current
+                        .shadow
+                        .as_ref()
+                        .map_or("unknown".to_string(), |s| format_expiry(s.expires)),
+                    format_expiry(expires)
+                ));
+                shadow.expires = expires;
+            }
+        }
+
+        let mut max_days = None;
+        if let Some(days) = params.password_expire_max {
+            if current.shadow.as_ref().map(|s| s.max_days) != Some(Some(days)) {
+                changes.push(format!("Password max age: {} days", days));
+                shadow.max_days = Some(days);
+                max_days = Some(days);
+            }
+        }
+        let mut min_days = None;
+        if let Some(days) = params.password_expire_min {
+            if current.shadow.as_ref().map(|s| s.min_days) != Some(Some(days)) {
+                changes.push(format!("Password min age: {} days", days));
+                shadow.min_days = Some(days);
+                min_days = Some(days);
+            }
+        }
+        if params.password.is_some()
+            || params.expires.is_some()
+            || params.password_expire_max.is_some()
+            || params.password_expire_min.is_some()
+        {
+            desired.shadow = Some(shadow);
+        }
+
+        if changes.is_empty() {
             return Ok(TaskOutput::success().with_stdout(format!("User {} is up to date", name)));
         }
 
-        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+        let mut commands = Vec::new();
+        if !opts.is_empty() {
+            commands.push(format!("usermod{} {}", opts, name));
+        }
+        commands.extend(chage_command(name, max_days, min_days));
 
-        if result.success() {
-            Ok(TaskOutput::changed().with_stdout(format!(
-                "Updated user {}: {}",
+        let output = if ctx.check_mode {
+            TaskOutput::changed().with_stdout(format!(
+                "Would update user {}: {}",
                 name,
                 changes.join(", ")
-            )))
+            ))
         } else {
-            Err(NexusError::Module(Box::new(ModuleError {
-                module: "user".to_string(),
-                task_name: format!("Update user {}", name),
-                host: conn.host_name().to_string(),
-                message: format!("Failed to update user {}", name),
-                stderr: Some(result.stderr),
-                suggestion: None,
-            })))
+            for cmd in &commands {
+                let result = conn.exec(&ctx.wrap_command(cmd)).await?;
+                if !result.success() {
+                    return Err(NexusError::Module(Box::new(ModuleError {
+                        module: "user".to_string(),
+                        task_name: format!("Update user {}", name),
+                        host: conn.host_name().to_string(),
+                        message: format!("Failed to update user {}", name),
+                        stderr: Some(result.stderr),
+                        suggestion: None,
+                    })));
+                }
+            }
+            TaskOutput::changed().with_stdout(format!(
+                "Updated user {}: {}",
+                name,
+                changes.join(", ")
+            ))
+        };
+
+        if !ctx.diff_mode {
+            return Ok(output);
         }
+        Ok(output.with_diff(Diff::new(
+            &attribute_lines(&current.attributes(name, false)),
+            &attribute_lines(&desired.attributes(name, password_changed)),
+            &format!("user {} (before)", name),
+            &format!("user {} (after)", name),
+        )))
     }
 
     async fn remove_user(
@@ -222,26 +388,121 @@ impl UserModule {
         ctx: &ExecutionContext,
         conn: &dyn Connection,
         name: &str,
+        current: &UserInfo,
     ) -> Result<TaskOutput, NexusError> {
-        let cmd = format!("userdel -r {}", name);
-        let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
-
-        if result.success() {
-            Ok(TaskOutput::changed().with_stdout(format!("Removed user {}", name)))
+        let output = if ctx.check_mode {
+            TaskOutput::changed().with_stdout(format!("Would remove user {}", name))
         } else {
-            Err(NexusError::Module(Box::new(ModuleError {
-                module: "user".to_string(),
-                task_name: format!("Remove user {}", name),
-                host: conn.host_name().to_string(),
-                message: format!("Failed to remove user {}", name),
-                stderr: Some(result.stderr),
-                suggestion: None,
-            })))
+            let cmd = format!("userdel -r {}", name);
+            let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+            if !result.success() {
+                return Err(NexusError::Module(Box::new(ModuleError {
+                    module: "user".to_string(),
+                    task_name: format!("Remove user {}", name),
+                    host: conn.host_name().to_string(),
+                    message: format!("Failed to remove user {}", name),
+                    stderr: Some(result.stderr),
+                    suggestion: None,
+                })));
+            }
+            TaskOutput::changed().with_stdout(format!("Removed user {}", name))
+        };
+
+        if !ctx.diff_mode {
+            return Ok(output);
+        }
+        Ok(output.with_diff(Diff::new(
+            &attribute_lines(&current.attributes(name, false)),
+            "",
+            &format!("user {} (before)", name),
+            "/dev/null",
+        )))
+    }
+
+    /// Generate the account's SSH key pair unless the key file already exists, and
+    /// register `ssh_key_file` and `ssh_public_key`
+    async fn ensure_ssh_key(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        name: &str,
+        params: &UserParams,
+        output: TaskOutput,
+    ) -> Result<TaskOutput, NexusError> {
+        let key_type = params.ssh_key_type.as_deref().unwrap_or("rsa");
+        let home = match params.home {
+            Some(ref home) => home.clone(),
+            None => {
+                let entry = conn.exec(&format!("getent passwd {}", name)).await?;
+                entry
+                    .stdout
+                    .trim()
+                    .split(':')
+                    .nth(5)
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("/home/{}", name))
+            }
+        };
+        let key_file = ssh_key_path(&home, key_type, params.ssh_key_file.as_deref());
+        let quoted = shell_quote(&key_file);
+
+        let exists = conn
+            .exec(&ctx.wrap_command(&format!("test -f {}", quoted)))
+            .await?
+            .success();
+
+        let mut output = output.with_data("ssh_key_file", Value::String(key_file.clone()));
+        if !exists {
+            if ctx.check_mode {
+                output.changed = true;
+                output.stdout = format!("{}\nWould generate SSH key {}", output.stdout, key_file);
+                return Ok(output);
+            }
+
+            let dir = match key_file.rsplit_once('/') {
+                Some((dir, _)) if !dir.is_empty() => dir.to_string(),
+                _ => home.clone(),
+            };
+            let cmd = format!(
+                "mkdir -p {dir} && chmod 700 {dir} && \
+                 ssh-keygen -q -t {key_type} -N '' -C {comment} -f {key} && \
+                 chown {name}: {dir} {key} {key}.pub",
+                dir = shell_quote(&dir),
+                key_type = shell_quote(key_type),
+                comment = shell_quote(&format!("{}@{}", name, conn.host_name())),
+                key = quoted,
+                name = name,
+            );
+            let result = conn.exec(&ctx.wrap_command(&cmd)).await?;
+            if !result.success() {
+                return Err(NexusError::Module(Box::new(ModuleError {
+                    module: "user".to_string(),
+                    task_name: format!("Generate SSH key for {}", name),
+                    host: conn.host_name().to_string(),
+                    message: format!("Failed to generate SSH key {}", key_file),
+                    stderr: Some(result.stderr),
+                    suggestion: Some("Check that ssh-keygen is installed on the host".to_string()),
+                })));
+            }
+            output.changed = true;
+            output.stdout = format!("{}\nGenerated SSH key {}", output.stdout, key_file);
         }
+
+        let public_key = conn
+            .exec(&ctx.wrap_command(&format!("cat {}.pub", quoted)))
+            .await?;
+        if public_key.success() {
+            output = output.with_data(
+                "ssh_public_key",
+                Value::String(public_key.stdout.trim().to_string()),
+            );
+        }
+        Ok(output)
     }
 
     async fn get_user_info(
         &self,
+        ctx: &ExecutionContext,
         conn: &dyn Connection,
         name: &str,
     ) -> Result<UserInfo, NexusError> {
@@ -276,38 +537,189 @@ impl UserModule {
         let home = parts[5].to_string();
         let shell = parts[6].to_string();
 
-        // Get groups
-        let groups_result = conn.exec(&format!("groups {}", name)).await?;
+        // Supplementary groups, as managed by `usermod -G`: all groups but the primary one
+        let primary = conn.exec(&format!("id -gn {}", name)).await?;
+        let groups_result = conn.exec(&format!("id -Gn {}", name)).await?;
         let groups: Vec<String> = if groups_result.success() {
             groups_result
                 .stdout
-                .trim()
-                .split(':')
-                .next_back()
-                .unwrap_or("")
                 .split_whitespace()
+                .filter(|group| *group != primary.stdout.trim())
                 .map(String::from)
                 .collect()
         } else {
             Vec::new()
         };
 
+        // The shadow entry usually needs root; without it password and expiry are unknown
+        let shadow = conn
+            .exec(&ctx.wrap_command(&format!("getent shadow {}", name)))
+            .await?;
+        let shadow = if shadow.success() {
+            parse_shadow(&shadow.stdout)
+        } else {
+            None
+        };
+
         Ok(UserInfo {
             uid,
             gid,
             home,
             shell,
             groups,
+            shadow,
         })
     }
 }
 
+#[derive(Debug, Clone)]
 struct UserInfo {
     uid: u32,
     gid: u32,
     home: String,
     shell: String,
     groups: Vec<String>,
+    shadow: Option<ShadowEntry>,
+}
+
+/// The fields of an /etc/shadow entry the module manages
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ShadowEntry {
+    password: String,
+    min_days: Option<i64>,
+    max_days: Option<i64>,
+    expires: Option<NaiveDate>,
+}
+
+impl UserInfo {
+    /// The account's attributes in the order diffs show them. Password hashes are
+    /// masked; `password_changed` marks a new one.
+    fn attributes(&self, name: &str, password_changed: bool) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![
+            ("name", name.to_string()),
+            ("uid", self.uid.to_string()),
+            ("gid", self.gid.to_string()),
+            ("home", self.home.clone()),
+            ("shell", self.shell.clone()),
+            ("groups", sorted_groups(&self.groups)),
+        ];
+        if let Some(ref shadow) = self.shadow {
+            let password = if password_changed {
+                format!("{} (changed)", MASKED_PASSWORD)
+            } else if shadow.password.is_empty() {
+                "(none)".to_string()
+            } else {
+                MASKED_PASSWORD.to_string()
+            };
+            let days = |days: Option<i64>| days.map_or("(none)".to_string(), |d| d.to_string());
+            attributes.push(("password", password));
+            attributes.push(("expires", format_expiry(shadow.expires)));
+            attributes.push(("password_expire_min", days(shadow.min_days)));
+            attributes.push(("password_expire_max", days(shadow.max_days)));
+        }
+        attributes
+    }
+}
+
+/// Parse `getent shadow` output: name:password:lastchg:min:max:warn:inactive:expire:
+fn parse_shadow(line: &str) -> Option<ShadowEntry> {
+    let fields: Vec<&str> = line.trim().split(':').collect();
+    if fields.len() < 8 {
+        return None;
+    }
+    let days = |field: &str| field.parse::<i64>().ok();
+    Some(ShadowEntry {
+        password: fields[1].to_string(),
+        min_days: days(fields[3]),
+        max_days: days(fields[4]),
+        expires: days(fields[7]).and_then(|d| {
+            NaiveDate::from_ymd_opt(1970, 1, 1)?.checked_add_days(chrono::Days::new(d as u64))
+        }),
+    })
+}
+
+/// The `expires` parameter as a date: epoch seconds (as in Ansible) or `YYYY-MM-DD`;
+/// a negative number, `never` or an empty string removes the expiry
+pub(crate) fn parse_expires(value: &Value) -> Result<Option<NaiveDate>, String> {
+    let from_epoch = |secs: f64| {
+        if secs < 0.0 {
+            return Ok(None);
+        }
+        chrono::DateTime::from_timestamp(secs as i64, 0)
+            .map(|t| Some(t.date_naive()))
+            .ok_or_else(|| format!("expires is out of range: {}", secs))
+    };
+    match value {
+        Value::Int(secs) => from_epoch(*secs as f64),
+        Value::Float(secs) => from_epoch(*secs),
+        Value::String(s) if s.is_empty() || s == "never" => Ok(None),
+        Value::String(s) => match s.parse::<f64>() {
+            Ok(secs) => from_epoch(secs),
+            Err(_) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| {
+                    format!(
+                        "expires must be a date (YYYY-MM-DD) or epoch seconds, got '{}'",
+                        s
+                    )
+                }),
+        },
+        Value::Null => Ok(None),
+        other => Err(format!(
+            "expires must be a date (YYYY-MM-DD) or epoch seconds, got {}",
+            other
+        )),
+    }
+}
+
+/// `useradd -e`/`usermod --expiredate` argument; empty clears the expiry
+fn expiry_arg(expires: Option<NaiveDate>) -> String {
+    expires.map_or(String::new(), |d| d.format("%Y-%m-%d").to_string())
+}
+
+fn format_expiry(expires: Option<NaiveDate>) -> String {
+    expires.map_or("never".to_string(), |d| d.format("%Y-%m-%d").to_string())
+}
+
+/// `chage` for the password age limits, if any are set
+fn chage_command(name: &str, max_days: Option<i64>, min_days: Option<i64>) -> Option<String> {
+    if max_days.is_none() && min_days.is_none() {
+        return None;
+    }
+    let mut cmd = "chage".to_string();
+    if let Some(days) = max_days {
+        cmd.push_str(&format!(" -M {}", days));
+    }
+    if let Some(days) = min_days {
+        cmd.push_str(&format!(" -m {}", days));
+    }
+    cmd.push_str(&format!(" {}", name));
+    Some(cmd)
+}
+
+/// The private key path: `file` relative to `home` (absolute paths as they are),
+/// `.ssh/id_<type>` by default
+fn ssh_key_path(home: &str, key_type: &str, file: Option<&str>) -> String {
+    let file = file.map_or(format!(".ssh/id_{}", key_type), String::from);
+    if file.starts_with('/') {
+        file
+    } else {
+        format!("{}/{}", home.trim_end_matches('/'), file)
+    }
+}
+
+/// `attribute: value` lines, the text user diffs compare
+fn attribute_lines(attributes: &[(&str, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(attribute, value)| format!("{}: {}\n", attribute, value))
+        .collect()
+}
+
+/// Groups in a stable order, so a reordered list doesn't show up as a change
+fn sorted_groups(groups: &[String]) -> String {
+    let groups: BTreeSet<&str> = groups.iter().map(|g| g.as_str()).collect();
+    groups.into_iter().collect::<Vec<_>>().join(", ")
 }
 
 #[async_trait]
@@ -324,3 +736,90 @@ impl Module for UserModule {
         unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_attribute_diff() {
+        let current = UserInfo {
+            uid: 1001,
+            gid: 1001,
+            home: "/home/deploy".to_string(),
+            shell: "/bin/bash".to_string(),
+            groups: vec!["docker".to_string(), "adm".to_string()],
+            shadow: None,
+        };
+        let desired = UserInfo {
+            shell: "/bin/zsh".to_string(),
+            groups: vec!["sudo".to_string(), "docker".to_string()],
+            ..current.clone()
+        };
+
+        let diff = Diff::new(
+            &attribute_lines(&current.attributes("deploy", false)),
+            &attribute_lines(&desired.attributes("deploy", false)),
+            "user deploy (before)",
+            "user deploy (after)",
+        );
+        let lines: Vec<&str> = diff.unified.lines().collect();
+        for line in [
+            "-shell: /bin/bash",
+            "+shell: /bin/zsh",
+            "-groups: adm, docker",
+            "+groups: docker, sudo",
+            " uid: 1001",
+        ] {
+            assert!(lines.contains(&line), "{} missing from:\n{}", line, diff);
+        }
+        assert!(!lines.iter().any(|l| l.starts_with("-home")));
+    }
+
+    #[test]
+    fn test_parse_shadow_and_expires() {
+        let shadow = parse_shadow("deploy:$6$salt$hash:19700:1:90:7::20818:\n").unwrap();
+        assert_eq!(shadow.password, "$6$salt$hash");
+        assert_eq!(shadow.min_days, Some(1));
+        assert_eq!(shadow.max_days, Some(90));
+        assert_eq!(shadow.expires, NaiveDate::from_ymd_opt(2026, 12, 31));
+        assert_eq!(
+            parse_shadow("deploy:!:19700::::::\n").unwrap().expires,
+            None
+        );
+
+        let date = NaiveDate::from_ymd_opt(2026, 12, 31);
+        assert_eq!(
+            parse_expires(&Value::String("2026-12-31".to_string())),
+            Ok(date)
+        );
+        assert_eq!(parse_expires(&Value::Int(1_798_675_200)), Ok(date));
+        assert_eq!(parse_expires(&Value::Int(-1)), Ok(None));
+        assert_eq!(parse_expires(&Value::String("never".to_string())), Ok(None));
+        assert!(parse_expires(&Value::String("next year".to_string())).is_err());
+
+        assert_eq!(expiry_arg(date), "2026-12-31");
+        assert_eq!(expiry_arg(None), "");
+        assert_eq!(
+            chage_command("deploy", Some(90), None).as_deref(),
+            Some("chage -M 90 deploy")
+        );
+        assert_eq!(chage_command("deploy", None, None), None);
+    }
+
+    #[test]
+    fn test_ssh_key_path() {
+        assert_eq!(
+            ssh_key_path("/home/deploy/", "ed25519", None),
+            "/home/deploy/.ssh/id_ed25519"
+        );
+        assert_eq!(
+            ssh_key_path("/home/deploy", "rsa", Some("keys/deploy")),
+            "/home/deploy/keys/deploy"
+        );
+        assert_eq!(
+            ssh_key_path("/home/deploy", "rsa", Some("/etc/keys/deploy")),
+            "/etc/keys/deploy"
+        );
+    }
+}
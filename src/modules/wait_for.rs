@@ -0,0 +1,306 @@
+// Wait-for module - block until a port or file reaches the desired state
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::{capitalize, Module};
+use crate::executor::{Connection, ExecutionContext, SshConnection, TaskOutput};
+use crate::output::errors::NexusError;
+use crate::parser::ast::{Value, WaitForState};
+
+/// Interval between successive probes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct WaitForModule;
+
+impl Default for WaitForModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the module is waiting on
+enum Target<'a> {
+    Port { host: &'a str, port: u16 },
+    Path(&'a str),
+    Sleep,
+}
+
+impl WaitForModule {
+    pub fn new() -> Self {
+        WaitForModule
+    }
+
+    /// Poll until the target reaches `state` or `timeout` seconds elapse.
+    ///
+    /// Port probes run through `conn`, so they originate from the managed host.
+    /// When `on_control_node` is set the TCP connect is made directly from here.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn execute_with_params(
+        &self,
+        ctx: &ExecutionContext,
+        conn: &dyn Connection,
+        on_control_node: bool,
+        host: Option<String>,
+        port: Option<u16>,
+        path: Option<String>,
+        state: WaitForState,
+        timeout: u64,
+        delay: u64,
+    ) -> Result<TaskOutput, NexusError> {
+        let host = host.unwrap_or_else(|| "127.0.0.1".to_string());
+        let target = match (port, path.as_deref()) {
+            (Some(port), _) => Target::Port { host: &host, port },
+            (None, Some(path)) => Target::Path(path),
+            (None, None) => Target::Sleep,
+        };
+        let description = describe(&target, state);
+
+        // Check mode
+        if ctx.check_mode {
+            return Ok(TaskOutput::success()
+                .with_stdout(format!("Would wait up to {}s for {}", timeout, description)));
+        }
+
+        let start = Instant::now();
+
+        if delay > 0 {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+        }
+
+        // Without a port or path there is nothing to poll, just sleep for the timeout
+        if let Target::Sleep = target {
+            tokio::time::sleep(Duration::from_secs(timeout)).await;
+            return Ok(TaskOutput::success()
+                .with_stdout(format!("Slept for {}s", timeout))
+                .with_data("elapsed", Value::Int(start.elapsed().as_secs() as i64)));
+        }
+
+        let deadline = start + Duration::from_secs(delay + timeout);
+
+        loop {
+            let reached = match target {
+                Target::Port { host, port } => {
+                    let open = if on_control_node {
+                        tcp_port_open(host, port).await
+                    } else {
+                        conn.exec(&port_probe_command(host, port)).await?.success()
+                    };
+                    open == matches!(state, WaitForState::Started | WaitForState::Present)
+                }
+                Target::Path(path) => {
+                    let exists = conn.exec(&path_probe_command(path)).await?.success();
+                    exists == matches!(state, WaitForState::Started | WaitForState::Present)
+                }
+                Target::Sleep => unreachable!(),
+            };
+
+            let elapsed = start.elapsed();
+            if reached {
+                return Ok(TaskOutput::success()
+                    .with_stdout(format!(
+                        "{} after {}s",
+                        capitalize(&description),
+                        elapsed.as_secs()
+                    ))
+                    .with_data("elapsed", Value::Int(elapsed.as_secs() as i64)));
+            }
+
+            if Instant::now() >= deadline {
+                return Ok(TaskOutput::failed(format!(
+                    "Timeout waiting for {} (elapsed {}s)",
+                    description,
+                    elapsed.as_secs()
+                ))
+                .with_data("elapsed", Value::Int(elapsed.as_secs() as i64)));
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Human-readable description of the wait condition
+fn describe(target: &Target<'_>, state: WaitForState) -> String {
+    match target {
+        Target::Port { host, port } => {
+            let verb = match state {
+                WaitForState::Started | WaitForState::Present => "to accept connections",
+                WaitForState::Stopped | WaitForState::Absent => "to stop accepting connections",
+            };
+            format!("port {}:{} {}", host, port, verb)
+        }
+        Target::Path(path) => {
+            let verb = match state {
+                WaitForState::Started | WaitForState::Present => "to exist",
+                WaitForState::Stopped | WaitForState::Absent => "to be removed",
+            };
+            format!("path {} {}", path, verb)
+        }
+        Target::Sleep => "timeout".to_string(),
+    }
+}
+/// Shell probe that succeeds when a TCP connect to host:port succeeds.
+/// Prefers `nc`, falling back to bash's /dev/tcp.
+fn port_probe_command(host: &str, port: u16) -> String {
+    let host = host.replace('\'', "'\\''");
+    format!(
+        "if command -v nc >/dev/null 2>&1; then nc -z -w 1 '{host}' {port}; \
+         else timeout 1 bash -c 'exec 3<>/dev/tcp/{host}/{port}' 2>/dev/null; fi",
+        host = host,
+        port = port
+    )
+}
+
+/// Shell probe that succeeds when the path exists
+fn path_probe_command(path: &str) -> String {
+    format!("test -e '{}'", path.replace('\'', "'\\''"))
+}
+
+/// Attempt a TCP connect from the control node
+async fn tcp_port_open(host: &str, port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(POLL_INTERVAL, tokio::net::TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+#[async_trait]
+impl Module for WaitForModule {
+    fn name(&self) -> &'static str {
+        "wait_for"
+    }
+
+    async fn execute(
+        &self,
+        _ctx: &ExecutionContext,
+        _conn: &SshConnection,
+    ) -> Result<TaskOutput, NexusError> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::LocalConnection;
+    use crate::inventory::Host;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn local_context() -> ExecutionContext {
+        ExecutionContext::new(Arc::new(Host::new("localhost")), HashMap::new())
+    }
+
+    #[test]
+    fn test_probe_commands() {
+        assert_eq!(path_probe_command("/tmp/ready"), "test -e '/tmp/ready'");
+        let probe = port_probe_command("db.local", 5432);
+        assert!(probe.contains("nc -z -w 1 'db.local' 5432"));
+        assert!(probe.contains("/dev/tcp/db.local/5432"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_path_present() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ready");
+        std::fs::write(&path, "").unwrap();
+
+        let output = WaitForModule::new()
+            .execute_with_params(
+                &local_context(),
+                &LocalConnection::new("localhost"),
+                true,
+                None,
+                None,
+                Some(path.to_string_lossy().to_string()),
+                WaitForState::Present,
+                5,
+                0,
+            )
+            .await
+            .unwrap();
+
+        assert!(!output.failed);
+        assert!(!output.changed);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_port_times_out() {
+        // Bind then drop to get a port that is very likely closed
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let output = WaitForModule::new()
+            .execute_with_params(
+                &local_context(),
+                &LocalConnection::new("localhost"),
+                true,
+                None,
+                Some(port),
+                None,
+                WaitForState::Started,
+                1,
+                0,
+            )
+            .await
+            .unwrap();
+
+        assert!(output.failed);
+        let message = output.message.unwrap();
+        assert!(message.contains("Timeout waiting for port"));
+        assert!(message.contains("elapsed"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_port_started() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let output = WaitForModule::new()
+            .execute_with_params(
+                &local_context(),
+                &LocalConnection::new("localhost"),
+                true,
+                None,
+                Some(port),
+                None,
+                WaitForState::Started,
+                5,
+                0,
+            )
+            .await
+            .unwrap();
+
+        assert!(!output.failed);
+        assert!(output.stdout.starts_with("Port 127.0.0.1:"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_check_mode() {
+        let ctx = local_context().with_check_mode(true);
+        let output = WaitForModule::new()
+            .execute_with_params(
+                &ctx,
+                &LocalConnection::new("localhost"),
+                false,
+                Some("db".to_string()),
+                Some(5432),
+                None,
+                WaitForState::Started,
+                30,
+                0,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            output.stdout,
+            "Would wait up to 30s for port db:5432 to accept connections"
+        );
+    }
+}
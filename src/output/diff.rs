@@ -1,7 +1,51 @@
 // Unified diff generation for file changes
 
+use std::fmt;
+
+use serde::Serialize;
 use similar::{ChangeTag, TextDiff};
 
+/// A change to a file (or another text, like a user's attributes) as its content
+/// before and after, plus the unified diff text output shows. JSON output emits all
+/// three, so tools don't have to parse the unified diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diff {
+    pub before: String,
+    pub after: String,
+    pub unified: String,
+}
+
+impl Diff {
+    /// The diff from `before` to `after`, with `before_label` and `after_label` as the
+    /// unified diff's file names
+    pub fn new(before: &str, after: &str, before_label: &str, after_label: &str) -> Self {
+        Diff {
+            before: before.to_string(),
+            after: after.to_string(),
+            unified: generate_unified_diff(before, after, before_label, after_label),
+        }
+    }
+
+    /// A diff whose content must not be shown (`no_log`): every field is `message`
+    pub fn hidden(message: &str) -> Self {
+        Diff {
+            before: message.to_string(),
+            after: message.to_string(),
+            unified: message.to_string(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unified.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.unified)
+    }
+}
+
 /// Generate a unified diff between two file contents
 ///
 /// Returns a formatted diff string showing line-by-line changes
@@ -111,6 +155,19 @@ mod tests {
         assert!(diff.contains("+line 2 modified"));
     }
 
+    #[test]
+    fn test_diff_keeps_before_and_after() {
+        let diff = Diff::new("port = 80\n", "port = 8080\n", "app.conf", "app.conf");
+
+        assert_eq!(diff.before, "port = 80\n");
+        assert_eq!(diff.after, "port = 8080\n");
+        assert!(diff.to_string().contains("+port = 8080"));
+        assert_eq!(
+            serde_json::to_value(&diff).unwrap()["unified"],
+            serde_json::Value::String(diff.unified.clone())
+        );
+    }
+
     #[test]
     fn test_diff_summary() {
         let old = "line 1\nline 2\nline 3\n";
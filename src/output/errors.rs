@@ -2,9 +2,12 @@
 
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use colored::*;
 
+use crate::parser::ast::SourceLocation;
+
 /// All error types in Nexus
 #[derive(Debug)]
 pub enum NexusError {
@@ -24,6 +27,13 @@ pub enum NexusError {
         suggestion: Option<String>,
     },
 
+    /// The host could not be connected to (as opposed to a command failing on it)
+    Unreachable {
+        host: String,
+        message: String,
+        suggestion: Option<String>,
+    },
+
     /// Module execution errors
     Module(Box<ModuleError>),
 
@@ -45,6 +55,8 @@ pub enum NexusError {
         function: Option<String>,
         message: String,
         suggestion: Option<String>,
+        /// The task being run when the error happened
+        location: Option<SourceLocation>,
     },
 
     /// Task failure (fail_when triggered)
@@ -54,10 +66,14 @@ pub enum NexusError {
         condition: String,
     },
 
-    /// Timeout errors
+    /// An operation on a host that ran out of time
     Timeout {
+        host: String,
         operation: String,
         duration_secs: u64,
+        /// What was running, e.g. the command
+        detail: Option<String>,
+        suggestion: Option<String>,
     },
 }
 
@@ -93,6 +109,62 @@ pub enum ParseErrorKind {
 
 impl std::error::Error for NexusError {}
 
+/// Longest command shown in a timeout error
+const MAX_COMMAND_DETAIL: usize = 120;
+
+impl NexusError {
+    /// A command on `host` that was still running after `timeout`. Only the command's
+    /// first line is shown, shortened if it's long.
+    pub fn command_timeout(host: &str, command: &str, timeout: Duration) -> Self {
+        let command = command.trim();
+        let first_line = command.lines().next().unwrap_or_default();
+        let mut detail: String = first_line.chars().take(MAX_COMMAND_DETAIL).collect();
+        if detail.len() < command.len() {
+            detail.push_str("...");
+        }
+        NexusError::Timeout {
+            host: host.to_string(),
+            operation: "command".to_string(),
+            duration_secs: timeout.as_secs(),
+            detail: Some(detail),
+            suggestion: Some(
+                "Raise --command-timeout, or run long tasks in the background with async"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Point a runtime or parse error at the task it came from, unless it already
+    /// says where it happened
+    pub fn with_location(self, location: Option<&SourceLocation>) -> Self {
+        let Some(loc) = location else {
+            return self;
+        };
+        match self {
+            NexusError::Runtime {
+                function,
+                message,
+                suggestion,
+                location: None,
+            } => NexusError::Runtime {
+                function,
+                message,
+                suggestion,
+                location: Some(loc.clone()),
+            },
+            NexusError::Parse(mut err)
+                if err.line.is_none() && err.file.as_deref().map_or(true, |f| f == loc.file) =>
+            {
+                err.file = Some(loc.file.clone());
+                err.line = Some(loc.line);
+                err.column = Some(loc.column);
+                NexusError::Parse(err)
+            }
+            other => other,
+        }
+    }
+}
+
 impl fmt::Display for NexusError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -144,6 +216,22 @@ impl fmt::Display for NexusError {
                 Ok(())
             }
 
+            NexusError::Unreachable {
+                host,
+                message,
+                suggestion,
+            } => {
+                writeln!(f, "{}: {}", "UNREACHABLE".red().bold(), message)?;
+                writeln!(f, "  {} {}", "Host:".dimmed(), host)?;
+
+                if let Some(suggestion) = suggestion {
+                    writeln!(f)?;
+                    writeln!(f, "{}: {}", "Hint".yellow().bold(), suggestion)?;
+                }
+
+                Ok(())
+            }
+
             NexusError::Module(err) => {
                 writeln!(f, "{}: {}", "MODULE ERROR".red().bold(), err.message)?;
                 writeln!(f, "  {} {}", "Module:".dimmed(), err.module)?;
@@ -202,8 +290,19 @@ impl fmt::Display for NexusError {
                 function,
                 message,
                 suggestion,
+                location,
             } => {
-                writeln!(f, "{}: {}", "RUNTIME ERROR".red().bold(), message)?;
+                match location {
+                    Some(loc) => writeln!(
+                        f,
+                        "{}: {}:{}: {}",
+                        "RUNTIME ERROR".red().bold(),
+                        loc.file,
+                        loc.line,
+                        message
+                    )?,
+                    None => writeln!(f, "{}: {}", "RUNTIME ERROR".red().bold(), message)?,
+                }
 
                 if let Some(func) = function {
                     writeln!(f, "  {} {}", "Function:".dimmed(), func)?;
@@ -230,16 +329,30 @@ impl fmt::Display for NexusError {
             }
 
             NexusError::Timeout {
+                host,
                 operation,
                 duration_secs,
+                detail,
+                suggestion,
             } => {
-                writeln!(
+                write!(
                     f,
-                    "{}: {} timed out after {}s",
+                    "{}: {} timed out after {}s on host {}",
                     "TIMEOUT".red().bold(),
                     operation,
-                    duration_secs
+                    duration_secs,
+                    host
                 )?;
+                match detail {
+                    Some(detail) => writeln!(f, ": {}", detail)?,
+                    None => writeln!(f)?,
+                }
+
+                if let Some(suggestion) = suggestion {
+                    writeln!(f)?;
+                    writeln!(f, "{}: {}", "Hint".yellow().bold(), suggestion)?;
+                }
+
                 Ok(())
             }
         }
@@ -288,7 +401,7 @@ pub fn format_source_error(source: &str, line: usize, column: usize, message: &s
 /// Suggest common fixes for errors
 pub fn suggest_fix(error: &NexusError) -> Option<String> {
     match error {
-        NexusError::Ssh { message, .. } => {
+        NexusError::Ssh { message, .. } | NexusError::Unreachable { message, .. } => {
             if message.contains("connection refused") {
                 Some("Ensure SSH service is running on the target host".to_string())
             } else if message.contains("timeout") {
@@ -337,4 +450,41 @@ mod tests {
         assert!(clean_output.contains("test.nx.yaml:12:5"));
         assert!(clean_output.contains("package"));
     }
+
+    #[test]
+    fn test_with_location() {
+        let loc = SourceLocation {
+            file: "site.nx.yaml".to_string(),
+            line: 42,
+            column: 5,
+        };
+        let err = NexusError::Runtime {
+            function: None,
+            message: "Variable not found: foo".to_string(),
+            suggestion: None,
+            location: None,
+        }
+        .with_location(Some(&loc));
+        let output = console::strip_ansi_codes(&err.to_string()).to_string();
+        assert!(output.contains("site.nx.yaml:42: Variable not found: foo"));
+
+        // The innermost location wins
+        let other = SourceLocation {
+            line: 7,
+            ..loc.clone()
+        };
+        let err = err.with_location(Some(&other));
+        assert!(matches!(err, NexusError::Runtime { location: Some(l), .. } if l.line == 42));
+
+        let err = NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::UnknownModule,
+            message: "Unknown module 'packages'".to_string(),
+            file: Some("site.nx.yaml".to_string()),
+            line: None,
+            column: None,
+            suggestion: None,
+        }))
+        .with_location(Some(&loc));
+        assert!(matches!(err, NexusError::Parse(e) if e.line == Some(42) && e.column == Some(5)));
+    }
 }
@@ -39,6 +39,13 @@ pub enum ExecutionEvent {
     /// Log output from a task
     Log { host: String, message: String },
 
+    /// A line of a running command's stdout or stderr
+    StreamLine {
+        host: String,
+        line: String,
+        is_stderr: bool,
+    },
+
     /// Playbook execution completed
     PlaybookComplete { recap: PlayRecap },
 }
@@ -71,7 +78,7 @@ impl From<&TaskResult> for TaskStatus {
 }
 
 /// Event emitter for sending execution events
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct EventEmitter {
     tx: mpsc::UnboundedSender<ExecutionEvent>,
 }
@@ -129,6 +136,15 @@ impl EventEmitter {
         let _ = self.tx.send(ExecutionEvent::Log { host, message });
     }
 
+    /// Emit a line of streaming command output
+    pub fn stream_line(&self, host: String, line: String, is_stderr: bool) {
+        let _ = self.tx.send(ExecutionEvent::StreamLine {
+            host,
+            line,
+            is_stderr,
+        });
+    }
+
     /// Emit a playbook complete event
     pub fn playbook_complete(&self, recap: PlayRecap) {
         let _ = self.tx.send(ExecutionEvent::PlaybookComplete { recap });
@@ -5,6 +5,7 @@ use std::time::Duration;
 
 use serde_json::json;
 
+use super::diff::Diff;
 use super::terminal::{PlayRecap, TaskResult};
 
 /// JSON output manager for machine-readable logging
@@ -60,7 +61,15 @@ impl JsonOutput {
             return;
         }
 
-        let status = if result.failed {
+        self.emit_json(&self.task_complete_event(result));
+    }
+
+    /// The `task_complete` event for a result. A diff is an object with the
+    /// `before` and `after` content and the `unified` diff.
+    fn task_complete_event(&self, result: &TaskResult) -> serde_json::Value {
+        let status = if result.unreachable {
+            "unreachable"
+        } else if result.failed {
             "failed"
         } else if result.changed {
             "changed"
@@ -113,11 +122,11 @@ impl JsonOutput {
             obj.insert("result".to_string(), json!(result_data));
         }
 
-        self.emit_json(&event);
+        event
     }
 
     /// Print a colorized diff (no-op for JSON, handled in print_task_result)
-    pub fn print_diff(&self, _diff: &str) {
+    pub fn print_diff(&self, _diff: &Diff) {
         // Diff is included in task_complete event
     }
 
@@ -136,6 +145,7 @@ impl JsonOutput {
                     "changed": stats.changed,
                     "failed": stats.failed,
                     "skipped": stats.skipped,
+                    "unreachable": stats.unreachable,
                 }),
             );
         }
@@ -146,8 +156,10 @@ impl JsonOutput {
             "hosts": hosts_stats,
             "total_duration_ms": recap.total_duration.as_millis(),
             "total_failed": recap.total_failed(),
+            "total_unreachable": recap.total_unreachable(),
             "total_changed": recap.total_changed(),
             "has_failures": recap.has_failures(),
+            "check_mode": recap.check_mode,
         });
 
         self.emit_json(&event);
@@ -220,6 +232,24 @@ mod tests {
         output.print_task_result(&result);
     }
 
+    #[test]
+    fn test_task_complete_event_has_structured_diff() {
+        let output = JsonOutput::new(true, false);
+        let mut result = TaskResult::changed("host1", "Configure app");
+        result.diff = Some(Diff::new(
+            "port = 80\n",
+            "port = 8080\n",
+            "app.conf (before)",
+            "app.conf (after)",
+        ));
+
+        let event = output.task_complete_event(&result);
+        let diff = &event["result"]["diff"];
+        assert_eq!(diff["before"], "port = 80\n");
+        assert_eq!(diff["after"], "port = 8080\n");
+        assert!(diff["unified"].as_str().unwrap().contains("+port = 8080"));
+    }
+
     #[test]
     fn test_json_output_playbook_header() {
         let output = JsonOutput::new(false, false);
@@ -0,0 +1,264 @@
+// JUnit XML output for CI integration
+
+use std::path::PathBuf;
+
+use indicatif::{MultiProgress, ProgressBar};
+use parking_lot::Mutex;
+
+use super::diff::Diff;
+use super::terminal::{PlayRecap, TaskResult, TerminalOutput};
+
+/// File written when `--junit-file` is not given
+pub const DEFAULT_JUNIT_FILE: &str = "nexus-junit.xml";
+
+/// JUnit output manager.
+///
+/// XML can't be streamed, so results are collected and the report is written at
+/// `print_recap`: one testsuite per host and one testcase per task. The regular
+/// terminal output is still printed so CI logs stay readable.
+pub struct JUnitOutput {
+    terminal: TerminalOutput,
+    path: PathBuf,
+    playbook: Mutex<String>,
+    results: Mutex<Vec<TaskResult>>,
+}
+
+impl JUnitOutput {
+    pub fn new(path: impl Into<PathBuf>, verbose: bool, quiet: bool) -> Self {
+        JUnitOutput {
+            terminal: TerminalOutput::new(verbose, quiet),
+            path: path.into(),
+            playbook: Mutex::new(String::new()),
+            results: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Print a header for a playbook run
+    pub fn print_playbook_header(&self, playbook: &str, hosts_count: usize) {
+        *self.playbook.lock() = playbook.to_string();
+        self.terminal.print_playbook_header(playbook, hosts_count);
+    }
+
+    /// Print a task header
+    pub fn print_task_header(&self, task_name: &str) {
+        self.terminal.print_task_header(task_name);
+    }
+
+    /// Create a progress bar for a host
+    pub fn create_host_progress(&self, host: &str) -> ProgressBar {
+        self.terminal.create_host_progress(host)
+    }
+
+    /// Print a task result and record it for the report
+    pub fn print_task_result(&self, result: &TaskResult) {
+        self.results.lock().push(result.clone());
+        self.terminal.print_task_result(result);
+    }
+
+    /// Start the progress bar for a play
+    pub fn start_progress(&self, hosts: usize, tasks_per_host: usize) {
+        self.terminal.start_progress(hosts, tasks_per_host);
+    }
+
+    /// Run `f` with the progress bar hidden
+    pub fn suspended<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.terminal.suspended(f)
+    }
+
+    /// Count a task result towards the progress bar
+    pub fn advance_progress(&self, result: &TaskResult) {
+        self.terminal.advance_progress(result);
+    }
+
+    /// Print a colorized diff
+    pub fn print_diff(&self, diff: &Diff) {
+        self.terminal.print_diff(diff);
+    }
+
+    /// Print the play recap summary and write the JUnit report
+    pub fn print_recap(&self, recap: &PlayRecap) {
+        self.terminal.print_recap(recap);
+
+        let xml = self.render(recap);
+        let written = match self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(parent) => std::fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::write(&self.path, xml));
+        if let Err(e) = written {
+            eprintln!(
+                "Failed to write JUnit report to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    /// Print streaming output from a command
+    pub fn print_streaming_output(&self, host: &str, line: &str, is_stderr: bool) {
+        self.terminal.print_streaming_output(host, line, is_stderr);
+    }
+
+    pub fn multi_progress(&self) -> &MultiProgress {
+        self.terminal.multi_progress()
+    }
+
+    /// Render the collected results as a JUnit XML document
+    fn render(&self, recap: &PlayRecap) -> String {
+        let results = self.results.lock();
+        let playbook = self.playbook.lock();
+
+        // Hosts in the recap without results (e.g. unreachable) still get a suite
+        let mut hosts: Vec<&str> = recap.hosts.keys().map(|h| h.as_str()).collect();
+        for result in results.iter() {
+            if !hosts.contains(&result.host.as_str()) {
+                hosts.push(&result.host);
+            }
+        }
+        hosts.sort();
+
+        let total_failures = results.iter().filter(|r| r.failed).count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">\n",
+            escape(&playbook),
+            results.len(),
+            total_failures,
+            recap.total_duration.as_secs_f64()
+        ));
+
+        for host in hosts {
+            let cases: Vec<&TaskResult> = results.iter().filter(|r| r.host == host).collect();
+            let failures = cases.iter().filter(|r| r.failed).count();
+            let skipped = cases.iter().filter(|r| r.skipped && !r.failed).count();
+            let time: f64 = cases.iter().map(|r| r.duration.as_secs_f64()).sum();
+
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">\n",
+                escape(host),
+                cases.len(),
+                failures,
+                skipped,
+                time
+            ));
+            for case in cases {
+                render_testcase(&mut xml, case);
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+fn render_testcase(xml: &mut String, result: &TaskResult) {
+    xml.push_str(&format!(
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+        escape(&result.task_name),
+        escape(&result.host),
+        result.duration.as_secs_f64()
+    ));
+
+    let stdout = result.stdout.as_deref().unwrap_or_default();
+    let stderr = result.stderr.as_deref().unwrap_or_default();
+
+    if result.failed {
+        // Output goes in system-out/system-err below
+        let message = escape(result.message.as_deref().unwrap_or("Task failed"));
+        xml.push_str(">\n");
+        xml.push_str(&format!(
+            "      <failure message=\"{}\">{}</failure>\n",
+            message, message
+        ));
+    } else if result.skipped {
+        xml.push_str(">\n");
+        match result.message.as_deref() {
+            Some(message) => xml.push_str(&format!(
+                "      <skipped message=\"{}\"/>\n",
+                escape(message)
+            )),
+            None => xml.push_str("      <skipped/>\n"),
+        }
+    } else if !stdout.is_empty() || !stderr.is_empty() {
+        xml.push_str(">\n");
+    } else {
+        xml.push_str("/>\n");
+        return;
+    }
+
+    if !stdout.is_empty() {
+        xml.push_str(&format!(
+            "      <system-out>{}</system-out>\n",
+            escape(stdout)
+        ));
+    }
+    if !stderr.is_empty() {
+        xml.push_str(&format!(
+            "      <system-err>{}</system-err>\n",
+            escape(stderr)
+        ));
+    }
+    xml.push_str("    </testcase>\n");
+}
+
+/// Escape text for XML attributes and content, dropping characters XML 1.0 forbids
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_junit_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports/report.xml");
+        let output = JUnitOutput::new(&path, false, true);
+
+        output.print_playbook_header("site.nx.yml", 2);
+        let results = [
+            TaskResult::changed("web1", "Install <nginx>")
+                .with_duration(Duration::from_millis(1500)),
+            TaskResult::failed("web1", "Start nginx", "exit code 1")
+                .with_stderr("bind: address in use"),
+            TaskResult::skipped("db1", "Install nginx"),
+        ];
+        let mut recap = PlayRecap::new();
+        for result in &results {
+            output.print_task_result(result);
+            recap.record(result);
+        }
+        output.print_recap(&recap);
+
+        let xml = std::fs::read_to_string(&path).unwrap();
+        assert!(xml
+            .contains("<testsuites name=\"site.nx.yml\" tests=\"3\" failures=\"1\" errors=\"0\""));
+        assert!(xml.contains(
+            "<testsuite name=\"db1\" tests=\"1\" failures=\"0\" errors=\"0\" skipped=\"1\" time=\"0.000\">"
+        ));
+        assert!(xml.contains(
+            "<testcase name=\"Install &lt;nginx&gt;\" classname=\"web1\" time=\"1.500\"/>"
+        ));
+        assert!(xml.contains("<failure message=\"exit code 1\">exit code 1</failure>"));
+        assert!(xml.contains("<system-err>bind: address in use</system-err>"));
+        assert!(xml.contains("<skipped/>"));
+        // Suites are ordered by host name
+        assert!(xml.find("\"db1\"").unwrap() < xml.find("\"web1\"").unwrap());
+    }
+}
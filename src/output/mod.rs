@@ -7,6 +7,7 @@ pub mod diff;
 pub mod errors;
 pub mod events;
 pub mod json_output;
+pub mod junit_output;
 pub mod plan;
 pub mod terminal;
 pub mod tui;
@@ -15,6 +16,7 @@ pub use diff::*;
 pub use errors::*;
 pub use events::*;
 pub use json_output::*;
+pub use junit_output::*;
 pub use plan::*;
 pub use terminal::*;
 pub use tui::*;
@@ -27,6 +29,8 @@ pub enum OutputFormat {
     Text,
     /// Machine-readable JSON output (NDJSON format)
     Json,
+    /// Text output plus a JUnit XML report for CI
+    JUnit,
 }
 
 impl std::str::FromStr for OutputFormat {
@@ -36,15 +40,17 @@ impl std::str::FromStr for OutputFormat {
         match s.to_lowercase().as_str() {
             "text" => Ok(OutputFormat::Text),
             "json" => Ok(OutputFormat::Json),
+            "junit" => Ok(OutputFormat::JUnit),
             _ => Err(()),
         }
     }
 }
 
-/// Unified output writer supporting both text and JSON formats
+/// Unified output writer supporting text, JSON and JUnit formats
 pub enum OutputWriter {
     Text(TerminalOutput),
     Json(JsonOutput),
+    JUnit(JUnitOutput),
     /// Silent mode for TUI - suppresses all output
     Silent,
 }
@@ -54,9 +60,15 @@ impl OutputWriter {
         match format {
             OutputFormat::Text => OutputWriter::Text(TerminalOutput::new(verbose, quiet)),
             OutputFormat::Json => OutputWriter::Json(JsonOutput::new(verbose, quiet)),
+            OutputFormat::JUnit => OutputWriter::junit(DEFAULT_JUNIT_FILE, verbose, quiet),
         }
     }
 
+    /// Create a JUnit writer that writes its report to `path`
+    pub fn junit(path: impl Into<std::path::PathBuf>, verbose: bool, quiet: bool) -> Self {
+        OutputWriter::JUnit(JUnitOutput::new(path, verbose, quiet))
+    }
+
     /// Create a silent output writer (for TUI mode)
     pub fn silent() -> Self {
         OutputWriter::Silent
@@ -66,6 +78,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.print_playbook_header(playbook, hosts_count),
             OutputWriter::Json(output) => output.print_playbook_header(playbook, hosts_count),
+            OutputWriter::JUnit(output) => output.print_playbook_header(playbook, hosts_count),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
@@ -74,6 +87,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.print_task_header(task_name),
             OutputWriter::Json(output) => output.print_task_header(task_name),
+            OutputWriter::JUnit(output) => output.print_task_header(task_name),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
@@ -82,6 +96,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.create_host_progress(host),
             OutputWriter::Json(_output) => ProgressBar::hidden(),
+            OutputWriter::JUnit(output) => output.create_host_progress(host),
             OutputWriter::Silent => ProgressBar::hidden(),
         }
     }
@@ -90,14 +105,43 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.print_task_result(result),
             OutputWriter::Json(output) => output.print_task_result(result),
+            OutputWriter::JUnit(output) => output.print_task_result(result),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
 
-    pub fn print_diff(&self, diff: &str) {
+    /// Start the progress bar for a play (text output in an interactive terminal only)
+    pub fn start_progress(&self, hosts: usize, tasks_per_host: usize) {
+        match self {
+            OutputWriter::Text(output) => output.start_progress(hosts, tasks_per_host),
+            OutputWriter::JUnit(output) => output.start_progress(hosts, tasks_per_host),
+            OutputWriter::Json(_) | OutputWriter::Silent => {}
+        }
+    }
+
+    /// Count a task result towards the progress bar
+    pub fn advance_progress(&self, result: &TaskResult) {
+        match self {
+            OutputWriter::Text(output) => output.advance_progress(result),
+            OutputWriter::JUnit(output) => output.advance_progress(result),
+            OutputWriter::Json(_) | OutputWriter::Silent => {}
+        }
+    }
+
+    /// Run `f` with the progress bar hidden, e.g. while prompting on the terminal
+    pub fn suspend_progress<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self {
+            OutputWriter::Text(output) => output.suspended(f),
+            OutputWriter::JUnit(output) => output.suspended(f),
+            OutputWriter::Json(_) | OutputWriter::Silent => f(),
+        }
+    }
+
+    pub fn print_diff(&self, diff: &Diff) {
         match self {
             OutputWriter::Text(output) => output.print_diff(diff),
             OutputWriter::Json(output) => output.print_diff(diff),
+            OutputWriter::JUnit(output) => output.print_diff(diff),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
@@ -106,6 +150,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.print_recap(recap),
             OutputWriter::Json(output) => output.print_recap(recap),
+            OutputWriter::JUnit(output) => output.print_recap(recap),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
@@ -114,6 +159,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.print_streaming_output(host, line, is_stderr),
             OutputWriter::Json(output) => output.print_streaming_output(host, line, is_stderr),
+            OutputWriter::JUnit(output) => output.print_streaming_output(host, line, is_stderr),
             OutputWriter::Silent => {} // No output in TUI mode
         }
     }
@@ -122,6 +168,7 @@ impl OutputWriter {
         match self {
             OutputWriter::Text(output) => output.multi_progress(),
             OutputWriter::Json(_output) => &JSON_NO_OP_MULTI_PROGRESS,
+            OutputWriter::JUnit(output) => output.multi_progress(),
             OutputWriter::Silent => &JSON_NO_OP_MULTI_PROGRESS,
         }
     }
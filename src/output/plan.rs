@@ -3,7 +3,7 @@
 use colored::*;
 use std::collections::HashMap;
 
-use crate::executor::{ChangeType, ExecutionPlan, HostPlan};
+use crate::executor::{ChangeType, ExecutionPlan, HostPlan, PlannedChange};
 
 /// Display an execution plan with Terraform-style formatting
 pub fn display_plan(plan: &ExecutionPlan, show_diff: bool) {
@@ -40,6 +40,55 @@ pub fn display_plan(plan: &ExecutionPlan, show_diff: bool) {
     display_summary(plan);
 }
 
+/// Display only what a plan would change (`nexus diff`), with content diffs.
+/// A change that is identical on several hosts, such as the same template, is shown once.
+pub fn display_diff(plan: &ExecutionPlan) {
+    let pending = group_pending_changes(&plan.host_plans);
+
+    println!();
+    if pending.is_empty() {
+        println!("{}", "No changes. Hosts match the playbook.".green());
+        println!();
+    }
+
+    for (change, hosts) in &pending {
+        println!("  {}:", hosts.join(", ").white().bold());
+        display_change(change, true);
+        println!();
+    }
+
+    display_summary(plan);
+}
+
+/// Collect the changes that would modify hosts, merging identical ones across hosts.
+/// Unchanged tasks and modules the planner can't check are left out.
+fn group_pending_changes(host_plans: &[HostPlan]) -> Vec<(&PlannedChange, Vec<&str>)> {
+    let mut pending: Vec<(&PlannedChange, Vec<&str>)> = Vec::new();
+
+    for host_plan in host_plans {
+        for change in &host_plan.changes {
+            if matches!(
+                change.change_type,
+                ChangeType::NoChange | ChangeType::Unknown
+            ) {
+                continue;
+            }
+
+            let existing = pending.iter_mut().find(|(other, _)| {
+                other.signature() == change.signature()
+                    && other.diff == change.diff
+                    && other.desired_state == change.desired_state
+            });
+            match existing {
+                Some((_, hosts)) => hosts.push(&host_plan.host),
+                None => pending.push((change, vec![&host_plan.host])),
+            }
+        }
+    }
+
+    pending
+}
+
 /// Group hosts by their change signatures
 fn group_hosts_by_changes(host_plans: &[HostPlan]) -> Vec<(String, Vec<&HostPlan>)> {
     let mut groups: HashMap<String, Vec<&HostPlan>> = HashMap::new();
@@ -240,6 +289,64 @@ pub fn prompt_confirmation(auto_approve: bool) -> Result<bool, std::io::Error> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_group_pending_changes_merges_identical_diffs() {
+        let change = |task: &str, change_type, diff: Option<&str>| PlannedChange {
+            task_name: task.to_string(),
+            module: "template".to_string(),
+            change_type,
+            current_state: None,
+            desired_state: None,
+            diff: diff.map(str::to_string),
+            is_dangerous: false,
+            danger_reason: None,
+        };
+        let host_plan = |host: &str, changes| HostPlan {
+            host: host.to_string(),
+            changes,
+            estimated_duration: std::time::Duration::ZERO,
+        };
+
+        let plans = vec![
+            host_plan(
+                "web1",
+                vec![
+                    change("Deploy config", ChangeType::Modify, Some("-a\n+b\n")),
+                    change("Install nginx", ChangeType::NoChange, None),
+                ],
+            ),
+            host_plan(
+                "web2",
+                vec![change(
+                    "Deploy config",
+                    ChangeType::Modify,
+                    Some("-a\n+b\n"),
+                )],
+            ),
+            host_plan(
+                "web3",
+                vec![change(
+                    "Deploy config",
+                    ChangeType::Modify,
+                    Some("-c\n+b\n"),
+                )],
+            ),
+        ];
+
+        let pending = group_pending_changes(&plans);
+        let summary: Vec<(&str, Vec<&str>)> = pending
+            .iter()
+            .map(|(c, hosts)| (c.diff.as_deref().unwrap(), hosts.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("-a\n+b\n", vec!["web1", "web2"]),
+                ("-c\n+b\n", vec!["web3"])
+            ]
+        );
+    }
+
     #[test]
     fn test_format_duration() {
         assert_eq!(
@@ -1,5 +1,7 @@
 // Rich terminal output for Nexus
 
+use std::collections::HashMap;
+use std::io::IsTerminal;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -8,23 +10,119 @@ use colored::*;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use parking_lot::Mutex;
 
+use super::diff::Diff;
+
 /// Terminal output manager
 pub struct TerminalOutput {
     multi_progress: MultiProgress,
     verbose: bool,
     quiet: bool,
     is_tty: bool,
+    /// Progress of the current play, shown below the output in interactive terminals
+    progress: Mutex<Option<RunProgress>>,
+}
+
+/// Completed tasks of a play, for the progress bar
+struct RunProgress {
+    bar: ProgressBar,
+    started: Instant,
+    hosts: usize,
+    tasks_per_host: usize,
+    /// Completed tasks per host
+    host_tasks: HashMap<String, usize>,
+    /// Hosts that won't run anything more, e.g. unreachable ones
+    hosts_stopped: usize,
 }
 
 impl TerminalOutput {
     pub fn new(verbose: bool, quiet: bool) -> Self {
-        let is_tty = atty::is(atty::Stream::Stdout);
+        let is_tty = std::io::stdout().is_terminal();
 
         TerminalOutput {
             multi_progress: MultiProgress::new(),
             verbose,
             quiet,
             is_tty,
+            progress: Mutex::new(None),
+        }
+    }
+
+    /// Show a progress bar for a play of `tasks_per_host` tasks on `hosts` hosts,
+    /// with elapsed time and an ETA. Only in interactive terminals and not with `--quiet`.
+    pub fn start_progress(&self, hosts: usize, tasks_per_host: usize) {
+        if self.quiet || !self.is_tty || !std::io::stderr().is_terminal() {
+            return;
+        }
+        if let Some(previous) = self.progress.lock().take() {
+            previous.bar.finish_and_clear();
+        }
+        if hosts == 0 || tasks_per_host == 0 {
+            return;
+        }
+
+        let bar = self
+            .multi_progress
+            .add(ProgressBar::new((hosts * tasks_per_host) as u64));
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] {bar:30.cyan/blue} {pos}/{len} tasks  {msg}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.set_message(progress_message(0, hosts * tasks_per_host, 0, hosts, None));
+        bar.enable_steady_tick(Duration::from_secs(1));
+
+        *self.progress.lock() = Some(RunProgress {
+            bar,
+            started: Instant::now(),
+            hosts,
+            tasks_per_host,
+            host_tasks: HashMap::new(),
+            hosts_stopped: 0,
+        });
+    }
+
+    /// Count a task result towards the progress bar
+    pub fn advance_progress(&self, result: &TaskResult) {
+        let mut progress = self.progress.lock();
+        let Some(progress) = progress.as_mut() else {
+            return;
+        };
+
+        let done = progress.host_tasks.entry(result.host.clone()).or_default();
+        *done += 1;
+        if result.unreachable {
+            progress.hosts_stopped += 1;
+        }
+
+        // Includes and loops can run more tasks than were counted up front
+        let bar = &progress.bar;
+        bar.inc(1);
+        if bar.position() > bar.length().unwrap_or(0) {
+            bar.set_length(bar.position());
+        }
+
+        let hosts_done = progress
+            .host_tasks
+            .values()
+            .filter(|&&n| n >= progress.tasks_per_host)
+            .count()
+            + progress.hosts_stopped;
+        bar.set_message(progress_message(
+            bar.position() as usize,
+            bar.length().unwrap_or(0) as usize,
+            hosts_done.min(progress.hosts),
+            progress.hosts,
+            Some(progress.started.elapsed()),
+        ));
+    }
+
+    /// Run `f` with the progress bar hidden, so printed lines don't mix with it
+    pub fn suspended<R>(&self, f: impl FnOnce() -> R) -> R {
+        if self.progress.lock().is_some() {
+            self.multi_progress.suspend(f)
+        } else {
+            f()
         }
     }
 
@@ -34,14 +132,16 @@ impl TerminalOutput {
             return;
         }
 
-        println!();
-        println!(
-            "{} {} ({} hosts)",
-            "PLAY".green().bold(),
-            playbook.cyan(),
-            hosts_count
-        );
-        println!("{}", "─".repeat(60).dimmed());
+        self.suspended(|| {
+            println!();
+            println!(
+                "{} {} ({} hosts)",
+                "PLAY".green().bold(),
+                playbook.cyan(),
+                hosts_count
+            );
+            println!("{}", "─".repeat(60).dimmed());
+        });
     }
 
     /// Print a task header
@@ -50,8 +150,10 @@ impl TerminalOutput {
             return;
         }
 
-        println!();
-        println!("{} {}", "TASK".yellow().bold(), task_name);
+        self.suspended(|| {
+            println!();
+            println!("{} {}", "TASK".yellow().bold(), task_name);
+        });
     }
 
     /// Create a progress bar for a host
@@ -80,8 +182,13 @@ impl TerminalOutput {
         if self.quiet && !result.failed {
             return;
         }
+        self.suspended(|| self.write_task_result(result));
+    }
 
-        let status = if result.failed {
+    fn write_task_result(&self, result: &TaskResult) {
+        let status = if result.unreachable {
+            "UNREACHABLE".red().bold()
+        } else if result.failed {
             "FAILED".red().bold()
         } else if result.changed {
             "CHANGED".yellow()
@@ -118,26 +225,31 @@ impl TerminalOutput {
                     }
                 }
             }
+        }
 
-            if let Some(msg) = &result.message {
-                if !msg.is_empty() {
-                    println!("      {}", msg);
-                }
+        // Failure reasons and messages meant for the user, such as `debug` output
+        if let Some(msg) = &result.message {
+            for line in msg.lines() {
+                println!("      {}", line);
             }
         }
 
         // Display diff if present
         if let Some(diff) = &result.diff {
             if !diff.is_empty() {
-                self.print_diff(diff);
+                self.write_diff(diff);
             }
         }
     }
 
     /// Print a colorized diff
-    pub fn print_diff(&self, diff: &str) {
+    pub fn print_diff(&self, diff: &Diff) {
+        self.suspended(|| self.write_diff(diff));
+    }
+
+    fn write_diff(&self, diff: &Diff) {
         println!();
-        for line in diff.lines() {
+        for line in diff.unified.lines() {
             if line.starts_with("---") || line.starts_with("+++") {
                 println!("      {}", line.bold());
             } else if line.starts_with("@@") {
@@ -155,12 +267,19 @@ impl TerminalOutput {
 
     /// Print the play recap summary
     pub fn print_recap(&self, recap: &PlayRecap) {
+        if let Some(progress) = self.progress.lock().take() {
+            progress.bar.finish_and_clear();
+        }
         if self.quiet {
             return;
         }
 
         println!();
-        println!("{}", "PLAY RECAP".green().bold());
+        if recap.check_mode {
+            println!("{}", "PLAY RECAP (check mode)".green().bold());
+        } else {
+            println!("{}", "PLAY RECAP".green().bold());
+        }
         println!("{}", "─".repeat(60).dimmed());
 
         for (host, stats) in &recap.hosts {
@@ -176,17 +295,28 @@ impl TerminalOutput {
                 format!("failed={}", stats.failed).normal()
             };
             let skipped = format!("skipped={}", stats.skipped).cyan();
+            let unreachable = if stats.unreachable > 0 {
+                format!("unreachable={}", stats.unreachable).red().bold()
+            } else {
+                format!("unreachable={}", stats.unreachable).normal()
+            };
 
             println!(
-                "{:<30} : {}    {}    {}    {}",
+                "{:<30} : {}    {}    {}    {}    {}",
                 host.white().bold(),
                 ok,
                 changed,
+                unreachable,
                 failed,
                 skipped
             );
         }
 
+        if let Some(summary) = recap.check_summary() {
+            println!();
+            println!("{}", summary.yellow());
+        }
+
         // Print overall timing
         println!();
         println!("Total time: {:.2}s", recap.total_duration.as_secs_f64());
@@ -199,11 +329,13 @@ impl TerminalOutput {
         }
 
         let prefix = format!("[{}]", host).dimmed();
-        if is_stderr {
-            println!("{} {}", prefix, line.red());
-        } else {
-            println!("{} {}", prefix, line);
-        }
+        self.suspended(|| {
+            if is_stderr {
+                println!("{} {}", prefix, line.red());
+            } else {
+                println!("{} {}", prefix, line);
+            }
+        });
     }
 
     /// Get the multi-progress bar for concurrent operations
@@ -219,13 +351,15 @@ pub struct TaskResult {
     pub task_name: String,
     pub changed: bool,
     pub failed: bool,
+    /// The host could not be connected to; such results are also `failed`
+    pub unreachable: bool,
     pub skipped: bool,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
     pub message: Option<String>,
     pub duration: Duration,
     /// Diff output for file changes
-    pub diff: Option<String>,
+    pub diff: Option<Diff>,
 }
 
 impl Default for TaskResult {
@@ -235,6 +369,7 @@ impl Default for TaskResult {
             task_name: String::new(),
             changed: false,
             failed: false,
+            unreachable: false,
             skipped: false,
             stdout: None,
             stderr: None,
@@ -277,6 +412,17 @@ impl TaskResult {
         }
     }
 
+    pub fn unreachable(
+        host: impl Into<String>,
+        task_name: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        TaskResult {
+            unreachable: true,
+            ..TaskResult::failed(host, task_name, message)
+        }
+    }
+
     pub fn skipped(host: impl Into<String>, task_name: impl Into<String>) -> Self {
         TaskResult {
             host: host.into(),
@@ -309,11 +455,14 @@ pub struct HostStats {
     pub changed: usize,
     pub failed: usize,
     pub skipped: usize,
+    pub unreachable: usize,
 }
 
 impl HostStats {
     pub fn record(&mut self, result: &TaskResult) {
-        if result.failed {
+        if result.unreachable {
+            self.unreachable += 1;
+        } else if result.failed {
             self.failed += 1;
         } else if result.skipped {
             self.skipped += 1;
@@ -330,6 +479,8 @@ impl HostStats {
 pub struct PlayRecap {
     pub hosts: std::collections::HashMap<String, HostStats>,
     pub total_duration: Duration,
+    /// Ran with `--check`, so the `changed` counts are changes that would be made
+    pub check_mode: bool,
 }
 
 impl PlayRecap {
@@ -344,14 +495,62 @@ impl PlayRecap {
             .record(result);
     }
 
+    /// Add the stats of a later play to this one
+    pub fn merge(&mut self, other: PlayRecap) {
+        for (host, stats) in other.hosts {
+            let total = self.hosts.entry(host).or_default();
+            total.ok += stats.ok;
+            total.changed += stats.changed;
+            total.failed += stats.failed;
+            total.skipped += stats.skipped;
+            total.unreachable += stats.unreachable;
+        }
+        self.total_duration += other.total_duration;
+        self.check_mode |= other.check_mode;
+    }
+
+    /// For a check mode run, how many changes would be made and on how many hosts
+    pub fn check_summary(&self) -> Option<String> {
+        if !self.check_mode {
+            return None;
+        }
+        let changes = self.total_changed();
+        let hosts = self.hosts.values().filter(|s| s.changed > 0).count();
+        Some(format!(
+            "Check mode: {} change{} would be made on {} host{}; nothing was changed",
+            changes,
+            if changes == 1 { "" } else { "s" },
+            hosts,
+            if hosts == 1 { "" } else { "s" }
+        ))
+    }
+
     pub fn has_failures(&self) -> bool {
-        self.hosts.values().any(|s| s.failed > 0)
+        self.hosts
+            .values()
+            .any(|s| s.failed > 0 || s.unreachable > 0)
     }
 
     pub fn total_failed(&self) -> usize {
         self.hosts.values().map(|s| s.failed).sum()
     }
 
+    pub fn total_unreachable(&self) -> usize {
+        self.hosts.values().map(|s| s.unreachable).sum()
+    }
+
+    /// Hosts that failed or were unreachable, sorted, for a retry file
+    pub fn retry_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = self
+            .hosts
+            .iter()
+            .filter(|(_, s)| s.failed > 0 || s.unreachable > 0)
+            .map(|(host, _)| host.clone())
+            .collect();
+        hosts.sort();
+        hosts
+    }
+
     pub fn total_changed(&self) -> usize {
         self.hosts.values().map(|s| s.changed).sum()
     }
@@ -413,15 +612,31 @@ impl StreamingOutput {
     }
 }
 
-/// Helper for checking if stdout is a TTY
-mod atty {
-    pub enum Stream {
-        Stdout,
-    }
+/// Status after the progress bar: hosts done and a naive ETA from the average
+/// time per completed task so far
+fn progress_message(
+    done: usize,
+    total: usize,
+    hosts_done: usize,
+    hosts: usize,
+    elapsed: Option<Duration>,
+) -> String {
+    let eta = match elapsed {
+        Some(elapsed) if done > 0 => {
+            let remaining = total.saturating_sub(done) as u32;
+            format_duration(elapsed / done as u32 * remaining)
+        }
+        _ => "--:--".to_string(),
+    };
+    format!("hosts {}/{} done  ETA {}", hosts_done, hosts, eta)
+}
 
-    pub fn is(_stream: Stream) -> bool {
-        // Simple check - could be enhanced with proper atty crate
-        std::env::var("TERM").is_ok()
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
     }
 }
 
@@ -429,6 +644,24 @@ mod atty {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_progress_message_eta() {
+        assert_eq!(
+            progress_message(0, 20, 0, 4, None),
+            "hosts 0/4 done  ETA --:--"
+        );
+        // 5 tasks in 50s, so 15 more take about 150s
+        assert_eq!(
+            progress_message(5, 20, 1, 4, Some(Duration::from_secs(50))),
+            "hosts 1/4 done  ETA 02:30"
+        );
+        assert_eq!(
+            progress_message(20, 20, 4, 4, Some(Duration::from_secs(7300))),
+            "hosts 4/4 done  ETA 00:00"
+        );
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+
     #[test]
     fn test_task_result_builders() {
         let ok = TaskResult::ok("host1", "Install nginx");
@@ -455,4 +688,40 @@ mod tests {
         assert_eq!(recap.total_failed(), 1);
         assert_eq!(recap.total_changed(), 1);
     }
+
+    #[test]
+    fn test_unreachable_counted_apart_from_failed() {
+        let mut recap = PlayRecap::new();
+        recap.record(&TaskResult::ok("web1", "task1"));
+        recap.record(&TaskResult::unreachable(
+            "web2",
+            "task1",
+            "Connection refused",
+        ));
+        recap.record(&TaskResult::failed("db1", "task1", "error"));
+
+        assert_eq!(recap.hosts["web2"].unreachable, 1);
+        assert_eq!(recap.hosts["web2"].failed, 0);
+        assert_eq!(recap.total_unreachable(), 1);
+        assert_eq!(recap.total_failed(), 1);
+        assert_eq!(recap.retry_hosts(), vec!["db1", "web2"]);
+    }
+
+    #[test]
+    fn test_check_mode_summary() {
+        let mut recap = PlayRecap::new();
+        recap.record(&TaskResult::changed("web1", "task1"));
+        recap.record(&TaskResult::changed("web1", "task2"));
+        recap.record(&TaskResult::ok("web2", "task1"));
+        assert_eq!(recap.check_summary(), None);
+
+        recap.merge(PlayRecap {
+            check_mode: true,
+            ..PlayRecap::new()
+        });
+        assert_eq!(
+            recap.check_summary().as_deref(),
+            Some("Check mode: 2 changes would be made on 1 host; nothing was changed")
+        );
+    }
 }
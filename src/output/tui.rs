@@ -130,6 +130,13 @@ impl LogEntry {
     }
 }
 
+/// A line of a host's command output
+#[derive(Debug, Clone)]
+pub struct OutputLine {
+    pub line: String,
+    pub is_stderr: bool,
+}
+
 /// State of the TUI application
 pub struct TuiState {
     pub playbook_name: String,
@@ -146,6 +153,13 @@ pub struct TuiState {
     pub final_recap: Option<PlayRecap>,
     pub log_scroll: usize,
     pub max_logs: usize,
+    /// Recent stdout/stderr of each host's current task
+    pub output: HashMap<String, VecDeque<OutputLine>>,
+    pub max_output_lines: usize,
+    /// Index into `host_order` of the host whose output is shown
+    pub selected_host: Option<usize>,
+    /// Lines scrolled back from the end of the selected host's output
+    pub output_scroll: usize,
 }
 
 impl Default for TuiState {
@@ -171,15 +185,29 @@ impl TuiState {
             final_recap: None,
             log_scroll: 0,
             max_logs: 1000,
+            output: HashMap::new(),
+            max_output_lines: 1000,
+            selected_host: None,
+            output_scroll: 0,
         }
     }
 
+    /// Start showing a play; a later play of the same playbook replaces the previous
+    /// one's hosts and progress but keeps the log
     pub fn init_playbook(&mut self, name: String, hosts: Vec<String>, total_tasks: usize) {
         self.playbook_name = name;
         self.num_hosts = hosts.len();
         // Total tasks = unique tasks × number of hosts
         self.total_tasks = total_tasks * hosts.len();
+        self.completed_tasks = 0;
         self.start_time = Instant::now();
+        self.final_elapsed = None;
+        self.is_complete = false;
+        self.final_recap = None;
+        self.hosts.clear();
+        self.host_order.clear();
+        self.output.clear();
+        self.clear_host_selection();
 
         for host in hosts {
             self.host_order.push(host.clone());
@@ -191,6 +219,11 @@ impl TuiState {
         if let Some(host_state) = self.hosts.get_mut(&host) {
             host_state.start_task(task.clone());
         }
+        // The output panel follows the current task
+        self.output.remove(&host);
+        if self.selected_host_name() == Some(host.as_str()) {
+            self.output_scroll = 0;
+        }
         self.current_task = task.clone();
         self.add_log(host.clone(), format!("Starting: {}", task));
     }
@@ -236,6 +269,67 @@ impl TuiState {
         }
     }
 
+    pub fn stream_line(&mut self, host: String, line: String, is_stderr: bool) {
+        let lines = self.output.entry(host).or_default();
+        lines.push_back(OutputLine { line, is_stderr });
+        while lines.len() > self.max_output_lines {
+            lines.pop_front();
+        }
+    }
+
+    pub fn selected_host_name(&self) -> Option<&str> {
+        self.selected_host
+            .and_then(|i| self.host_order.get(i))
+            .map(String::as_str)
+    }
+
+    /// Select the previous host, or the last one when none is selected
+    pub fn select_previous_host(&mut self) {
+        if self.host_order.is_empty() {
+            return;
+        }
+        self.selected_host = Some(match self.selected_host {
+            Some(i) if i > 0 => i - 1,
+            Some(i) => i,
+            None => self.host_order.len() - 1,
+        });
+        self.output_scroll = 0;
+    }
+
+    /// Select the next host, or the first one when none is selected
+    pub fn select_next_host(&mut self) {
+        if self.host_order.is_empty() {
+            return;
+        }
+        self.selected_host = Some(match self.selected_host {
+            Some(i) => (i + 1).min(self.host_order.len() - 1),
+            None => 0,
+        });
+        self.output_scroll = 0;
+    }
+
+    /// Back to the log view
+    pub fn clear_host_selection(&mut self) {
+        self.selected_host = None;
+        self.output_scroll = 0;
+    }
+
+    /// Output lines of the selected host
+    pub fn selected_output(&self) -> Option<&VecDeque<OutputLine>> {
+        self.output.get(self.selected_host_name()?)
+    }
+
+    /// Scroll the selected host's output back by a page
+    pub fn page_output_up(&mut self, page: usize) {
+        let len = self.selected_output().map_or(0, VecDeque::len);
+        self.output_scroll = (self.output_scroll + page).min(len.saturating_sub(page));
+    }
+
+    /// Scroll the selected host's output forward by a page, towards the newest lines
+    pub fn page_output_down(&mut self, page: usize) {
+        self.output_scroll = self.output_scroll.saturating_sub(page);
+    }
+
     pub fn playbook_complete(&mut self, recap: PlayRecap) {
         self.is_complete = true;
         self.final_elapsed = Some(self.start_time.elapsed()); // Freeze timer
@@ -262,6 +356,9 @@ impl TuiState {
     }
 }
 
+/// Lines moved by PageUp/PageDown in the output panel
+const OUTPUT_PAGE: usize = 10;
+
 /// TUI Application
 pub struct TuiApp {
     state: TuiState,
@@ -283,6 +380,7 @@ impl TuiApp {
             function: None,
             message: format!("Failed to enable raw mode: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         let mut stdout = stdout();
@@ -290,6 +388,7 @@ impl TuiApp {
             function: None,
             message: format!("Failed to enter alternate screen: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         let backend = CrosstermBackend::new(stdout);
@@ -297,6 +396,7 @@ impl TuiApp {
             function: None,
             message: format!("Failed to create terminal: {}", e),
             suggestion: None,
+            location: None,
         })?;
 
         let result = self.run_loop(&mut terminal).await;
@@ -322,6 +422,7 @@ impl TuiApp {
                     function: None,
                     message: format!("Failed to draw terminal: {}", e),
                     suggestion: None,
+                    location: None,
                 })?;
 
             // Check for events (non-blocking)
@@ -334,19 +435,30 @@ impl TuiApp {
                 function: None,
                 message: format!("Failed to poll events: {}", e),
                 suggestion: None,
+                location: None,
             })? {
                 if let Event::Key(key) = event::read().map_err(|e| NexusError::Runtime {
                     function: None,
                     message: format!("Failed to read event: {}", e),
                     suggestion: None,
+                    location: None,
                 })? {
                     match key.code {
                         KeyCode::Char('q') | KeyCode::Esc => {
                             // Allow exit any time - playbook continues in background if not complete
                             break;
                         }
-                        KeyCode::Up => self.state.scroll_up(),
-                        KeyCode::Down => self.state.scroll_down(),
+                        KeyCode::Up => self.state.select_previous_host(),
+                        KeyCode::Down => self.state.select_next_host(),
+                        KeyCode::Left | KeyCode::Backspace => self.state.clear_host_selection(),
+                        KeyCode::PageUp if self.state.selected_host.is_some() => {
+                            self.state.page_output_up(OUTPUT_PAGE)
+                        }
+                        KeyCode::PageDown if self.state.selected_host.is_some() => {
+                            self.state.page_output_down(OUTPUT_PAGE)
+                        }
+                        KeyCode::PageUp => self.state.scroll_up(),
+                        KeyCode::PageDown => self.state.scroll_down(),
                         _ => {}
                     }
                 }
@@ -385,6 +497,13 @@ impl TuiApp {
             ExecutionEvent::Log { host, message } => {
                 self.state.add_log(host, message);
             }
+            ExecutionEvent::StreamLine {
+                host,
+                line,
+                is_stderr,
+            } => {
+                self.state.stream_line(host, line, is_stderr);
+            }
             ExecutionEvent::PlaybookComplete { recap } => {
                 self.state.playbook_complete(recap);
             }
@@ -470,7 +589,8 @@ impl TuiApp {
             .state
             .host_order
             .iter()
-            .filter_map(|host_name| {
+            .enumerate()
+            .filter_map(|(i, host_name)| {
                 self.state.hosts.get(host_name).map(|host| {
                     let symbol = host.status_symbol();
                     let color = host.status_color();
@@ -487,7 +607,12 @@ impl TuiApp {
                         Span::styled(status, Style::default().fg(color)),
                     ]);
 
-                    ListItem::new(line)
+                    let item = ListItem::new(line);
+                    if self.state.selected_host == Some(i) {
+                        item.style(Style::default().add_modifier(Modifier::REVERSED))
+                    } else {
+                        item
+                    }
                 })
             })
             .collect();
@@ -512,7 +637,7 @@ impl TuiApp {
 
             self.render_progress(f, chunks[0]);
             self.render_recap(f, chunks[1]);
-            self.render_logs(f, chunks[2]);
+            self.render_logs_or_output(f, chunks[2]);
         } else {
             // Normal execution view
             let chunks = Layout::default()
@@ -524,7 +649,7 @@ impl TuiApp {
                 .split(area);
 
             self.render_progress(f, chunks[0]);
-            self.render_logs(f, chunks[1]);
+            self.render_logs_or_output(f, chunks[1]);
         }
     }
 
@@ -636,10 +761,57 @@ impl TuiApp {
         }
     }
 
+    /// Render the selected host's output, or the logs when no host is selected
+    fn render_logs_or_output(&self, f: &mut Frame, area: Rect) {
+        match self.state.selected_host_name() {
+            Some(host) => self.render_output(f, area, host),
+            None => self.render_logs(f, area),
+        }
+    }
+
+    /// Render the selected host's stdout (white) and stderr (red) for its current task
+    fn render_output(&self, f: &mut Frame, area: Rect, host: &str) {
+        let visible_height = area.height.saturating_sub(2) as usize;
+        let lines = self.state.selected_output();
+        let len = lines.map_or(0, VecDeque::len);
+        let end = len.saturating_sub(self.state.output_scroll);
+        let start = end.saturating_sub(visible_height);
+
+        let items: Vec<ListItem> = lines
+            .into_iter()
+            .flatten()
+            .skip(start)
+            .take(end - start)
+            .map(|output| {
+                let color = if output.is_stderr {
+                    Color::Red
+                } else {
+                    Color::White
+                };
+                ListItem::new(Span::styled(
+                    output.line.as_str(),
+                    Style::default().fg(color),
+                ))
+            })
+            .collect();
+
+        let title = if self.state.output_scroll > 0 {
+            format!(
+                "Output: {} ({} lines back, PgUp/PgDn to page)",
+                host, self.state.output_scroll
+            )
+        } else {
+            format!("Output: {} (PgUp/PgDn to page, ← for logs)", host)
+        };
+        let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+
+        f.render_widget(list, area);
+    }
+
     /// Render logs panel
     fn render_logs(&self, f: &mut Frame, area: Rect) {
         let log_area = Block::default()
-            .title("Log Output (↑/↓ to scroll)")
+            .title("Log Output (PgUp/PgDn to scroll)")
             .borders(Borders::ALL)
             .inner(area);
 
@@ -658,7 +830,7 @@ impl TuiApp {
 
         let list = List::new(logs).block(
             Block::default()
-                .title("Log Output (↑/↓ to scroll)")
+                .title("Log Output (PgUp/PgDn to scroll)")
                 .borders(Borders::ALL),
         );
 
@@ -668,12 +840,12 @@ impl TuiApp {
     /// Render footer
     fn render_footer(&self, f: &mut Frame, area: Rect) {
         let footer = if self.state.is_complete {
-            Paragraph::new("Press 'q' or ESC to exit")
+            Paragraph::new("Press 'q' or ESC to exit | ↑/↓ select host | PgUp/PgDn scroll")
                 .style(Style::default().fg(Color::Green))
                 .alignment(Alignment::Center)
         } else {
             Paragraph::new(
-                "Press 'q' to quit (playbook will continue in background) | ↑/↓ scroll logs",
+                "Press 'q' to quit (playbook will continue in background) | ↑/↓ select host | PgUp/PgDn scroll",
             )
             .style(Style::default().fg(Color::Gray))
             .alignment(Alignment::Center)
@@ -25,8 +25,28 @@ pub enum Serial {
     Count(usize),
     /// Run on a percentage of hosts at a time (e.g., serial: "25%")
     Percentage(u8),
-    /// Progressive batches - run on different batch sizes (e.g., serial: [1, 5, 10])
-    List(Vec<usize>),
+    /// Progressive batches - run on different batch sizes (e.g., serial: [1, 5, "50%"]).
+    /// The last size is reused for all remaining hosts.
+    List(Vec<SerialBatch>),
+}
+
+/// One batch size in a progressive `serial` list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerialBatch {
+    Count(usize),
+    Percentage(u8),
+}
+
+impl SerialBatch {
+    /// Number of hosts in the batch out of `total_hosts`.
+    /// Percentages round up, and every batch has at least one host.
+    pub fn size(&self, total_hosts: usize) -> usize {
+        let size = match self {
+            SerialBatch::Count(n) => *n,
+            SerialBatch::Percentage(pct) => (total_hosts * *pct as usize).div_ceil(100),
+        };
+        size.max(1)
+    }
 }
 
 /// Execution strategy - controls how tasks are executed across hosts
@@ -43,8 +63,14 @@ pub enum ExecutionStrategy {
 #[derive(Debug, Clone)]
 pub struct Playbook {
     pub source_file: String,
+    /// The play's `name:`, shown in its header instead of the file name
+    pub name: Option<String>,
     pub hosts: HostPattern,
     pub vars: HashMap<String, Value>,
+    /// Variable files loaded before the play runs (relative to the playbook)
+    pub vars_files: Vec<String>,
+    /// Variables prompted for interactively before the play runs
+    pub vars_prompt: Vec<VarsPrompt>,
     pub tasks: Vec<TaskOrBlock>,
     pub handlers: Vec<Handler>,
     pub functions: Option<FunctionBlock>,
@@ -60,6 +86,8 @@ pub struct Playbook {
     pub post_tasks: Vec<TaskOrBlock>,
     /// Auto-gather facts at play start
     pub gather_facts: bool,
+    /// Fact categories to gather, e.g. `["!hardware", "network"]` (empty = all)
+    pub gather_subset: Vec<String>,
     /// Connection type (local, ssh, etc.)
     pub connection: Option<String>,
     /// Serial execution - run on N hosts at a time (rolling deployment)
@@ -68,6 +96,21 @@ pub struct Playbook {
     pub throttle: Option<usize>,
     /// Execution strategy (linear vs free)
     pub strategy: ExecutionStrategy,
+    /// Environment variables for command and shell tasks, overridden by a task's own
+    pub environment: HashMap<String, Expression>,
+}
+
+/// A `vars_prompt` entry
+#[derive(Debug, Clone)]
+pub struct VarsPrompt {
+    /// Variable to set
+    pub name: String,
+    /// Text shown to the user
+    pub prompt: String,
+    /// Hide input while typing
+    pub private: bool,
+    /// Value used when the answer is empty
+    pub default: Option<Value>,
 }
 
 /// Either a Task or a Block - unified representation in playbooks
@@ -95,6 +138,20 @@ pub enum HostPattern {
     Localhost,
 }
 
+impl std::fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostPattern::All => write!(f, "all"),
+            HostPattern::Group(name) | HostPattern::Pattern(name) => write!(f, "{}", name),
+            HostPattern::Inline(hosts) => {
+                let names: Vec<&str> = hosts.iter().map(|h| h.name.as_str()).collect();
+                write!(f, "{}", names.join(", "))
+            }
+            HostPattern::Localhost => write!(f, "localhost"),
+        }
+    }
+}
+
 /// Inline host definition for playbook-embedded hosts
 #[derive(Debug, Clone, PartialEq)]
 pub struct InlineHost {
@@ -120,7 +177,11 @@ pub struct Task {
     pub fail_when: Option<Expression>,
     pub changed_when: Option<Expression>,
     pub notify: Vec<String>,
+    /// Notify handlers whenever the task succeeds, not only when it changed something
+    pub always_notify: bool,
     pub loop_expr: Option<Expression>,
+    /// Iterate over every combination of the lists in `loop_expr` (`loop_nested`)
+    pub loop_nested: bool,
     pub loop_var: String,
     pub location: Option<SourceLocation>,
     /// Override sudo for this task (None = use playbook default)
@@ -141,6 +202,12 @@ pub struct Task {
     pub delegate_to: Option<Expression>,
     /// Store facts from delegate (default: false)
     pub delegate_facts: bool,
+    /// Hide the task's output from the terminal, JSON output and callbacks
+    pub no_log: bool,
+    /// Force check mode on or off for this task (None = follow `--check`)
+    pub check_mode: Option<bool>,
+    /// Variables for this task only, over block, play and role vars
+    pub vars: HashMap<String, Expression>,
 }
 
 // ============================================================================
@@ -195,6 +262,8 @@ pub struct Block {
     pub rescue: Vec<Task>,
     /// Tasks that always run regardless of outcome (the "finally" section)
     pub always: Vec<Task>,
+    /// Variables for the block's tasks, over play and role vars
+    pub vars: HashMap<String, Expression>,
     /// Conditional execution for the entire block
     pub when: Option<Expression>,
     /// Tags for filtering block execution
@@ -281,8 +350,6 @@ pub struct AsyncConfig {
     pub async_timeout: u64,
     /// Seconds between status checks (0 = no polling, fire and forget)
     pub poll: u64,
-    /// Maximum poll attempts before giving up
-    pub retries: u32,
 }
 
 impl Default for Task {
@@ -293,13 +360,18 @@ impl Default for Task {
                 cmd: Expression::String(String::new()),
                 creates: None,
                 removes: None,
+                environment: HashMap::new(),
+                stdin: None,
+                stdin_add_newline: true,
             },
             when: None,
             register: None,
             fail_when: None,
             changed_when: None,
             notify: Vec::new(),
+            always_notify: false,
             loop_expr: None,
+            loop_nested: false,
             loop_var: "item".to_string(),
             location: None,
             sudo: None,
@@ -311,17 +383,23 @@ impl Default for Task {
             throttle: None,
             delegate_to: None,
             delegate_facts: false,
+            no_log: false,
+            check_mode: None,
+            vars: HashMap::new(),
         }
     }
 }
 
 /// Module invocation types
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum ModuleCall {
-    /// package: nginx, state: installed
+    /// package: [nginx, git=1:2.39*], state: installed
     Package {
-        name: Expression,
+        name: Vec<Expression>,
         state: PackageState,
+        update_cache: bool,
+        autoremove: bool,
     },
     /// service: nginx, state: running
     Service {
@@ -338,12 +416,24 @@ pub enum ModuleCall {
         owner: Option<Expression>,
         group: Option<Expression>,
         mode: Option<Expression>,
+        /// Apply owner, group and mode to everything under a directory (`state: directory` only)
+        recurse: bool,
+        /// Skip the task when this path already exists on the host
+        creates: Option<Expression>,
+        /// Skip the task when this path doesn't exist on the host
+        removes: Option<Expression>,
     },
     /// command: ls -la
     Command {
         cmd: Expression,
         creates: Option<Expression>,
         removes: Option<Expression>,
+        /// Variables set for the command, from the task's `environment:`
+        environment: HashMap<String, Expression>,
+        /// Data written to the command's standard input
+        stdin: Option<Expression>,
+        /// Append a newline to `stdin` (default: true, like Ansible)
+        stdin_add_newline: bool,
     },
     /// user: jdoe
     User {
@@ -352,9 +442,21 @@ pub enum ModuleCall {
         uid: Option<Expression>,
         gid: Option<Expression>,
         groups: Vec<Expression>,
+        /// Add `groups` to the current ones instead of replacing them
+        append: bool,
         shell: Option<Expression>,
         home: Option<Expression>,
         create_home: Option<bool>,
+        system: bool,
+        /// Password hash, as stored in /etc/shadow
+        password: Option<Expression>,
+        password_expire_max: Option<Expression>,
+        password_expire_min: Option<Expression>,
+        /// Account expiry: `YYYY-MM-DD` or epoch seconds, `-1` to remove it
+        expires: Option<Expression>,
+        generate_ssh_key: bool,
+        ssh_key_type: Option<Expression>,
+        ssh_key_file: Option<Expression>,
     },
     /// run: function_name()
     RunFunction { name: String, args: Vec<Expression> },
@@ -365,6 +467,12 @@ pub enum ModuleCall {
         owner: Option<Expression>,
         group: Option<Expression>,
         mode: Option<Expression>,
+        /// Command run against the rendered file before it is moved into place (`%s` = path)
+        validate: Option<Expression>,
+        /// Skip the task when this path already exists on the host
+        creates: Option<Expression>,
+        /// Skip the task when this path doesn't exist on the host
+        removes: Option<Expression>,
     },
     /// Facts gathering module
     Facts { categories: Vec<String> },
@@ -374,12 +482,168 @@ pub enum ModuleCall {
         chdir: Option<Expression>,
         creates: Option<Expression>,
         removes: Option<Expression>,
+        /// Variables set for the command, from the task's `environment:`
+        environment: HashMap<String, Expression>,
+        /// Data written to the command's standard input
+        stdin: Option<Expression>,
+        /// Append a newline to `stdin` (default: true, like Ansible)
+        stdin_add_newline: bool,
+    },
+    /// wait_for: port 8080 / path /var/run/app.pid
+    WaitFor {
+        host: Option<Expression>,
+        port: Option<Expression>,
+        path: Option<Expression>,
+        state: WaitForState,
+        timeout: u64,
+        delay: u64,
+    },
+    /// get_url: https://example.com/file.tar.gz, dest: /tmp/file.tar.gz
+    GetUrl {
+        url: Expression,
+        dest: Expression,
+        checksum: Option<Expression>,
+        owner: Option<Expression>,
+        group: Option<Expression>,
+        mode: Option<Expression>,
+    },
+    /// systemd: nginx, daemon_reload: true, masked: false
+    Systemd {
+        name: Option<Expression>,
+        state: Option<ServiceState>,
+        enabled: Option<bool>,
+        daemon_reload: bool,
+        masked: Option<bool>,
+        scope: SystemdScope,
+    },
+    /// pip: [requests, flask], virtualenv: /opt/app/venv
+    Pip {
+        name: Vec<Expression>,
+        state: PipState,
+        version: Option<Expression>,
+        virtualenv: Option<Expression>,
+        virtualenv_command: Option<Expression>,
+        requirements: Option<Expression>,
+        executable: Option<Expression>,
+    },
+    /// slurp: /etc/motd - read a remote file into the registered result
+    Slurp { src: Expression },
+    /// stat: /etc/app.conf - report a remote path's metadata in the registered result
+    Stat {
+        path: Expression,
+        /// Include the SHA-1 `checksum` of a regular file
+        get_checksum: bool,
+    },
+    /// async_status: job_id: ${job.ansible_job_id} - check on a job started with `async`
+    AsyncStatus { job_id: Expression },
+    /// fetch: /etc/hosts, dest: backups/ - copy a remote file to the control node
+    Fetch {
+        src: Expression,
+        dest: Expression,
+        flat: bool,
+    },
+    /// synchronize: {src: dist/, dest: /var/www/app/} - rsync between the control node and the host
+    Synchronize {
+        src: Expression,
+        dest: Expression,
+        mode: SyncMode,
+        /// Remove files from `dest` that aren't in `src`
+        delete: bool,
+        /// rsync `--archive` (permissions, times, links, ...); plain `--recursive` without it
+        archive: bool,
+        /// Compare files by checksum instead of size and modification time
+        checksum: bool,
+        /// Extra rsync arguments, one per entry
+        rsync_opts: Vec<Expression>,
+    },
+    /// meta: flush_handlers - control actions handled by the scheduler
+    Meta { action: MetaAction },
+    /// mount: /data, src: /dev/sdb1, fstype: xfs, state: mounted
+    Mount {
+        path: Expression,
+        src: Option<Expression>,
+        fstype: Option<Expression>,
+        opts: Option<Expression>,
+        dump: Option<Expression>,
+        passno: Option<Expression>,
+        /// fstab file to manage (default: /etc/fstab)
+        fstab: Option<Expression>,
+        state: MountState,
+    },
+    /// reboot: {reboot_timeout: 600} - reboot and wait for the host to come back
+    Reboot {
+        reboot_timeout: u64,
+        connect_timeout: u64,
+        post_reboot_delay: u64,
+        test_command: Option<Expression>,
+    },
+    /// authorized_key: {user: deploy, key: "ssh-ed25519 AAAA..."}
+    AuthorizedKey {
+        user: Expression,
+        /// One or more public keys; a value may hold several, one per line
+        key: Vec<Expression>,
+        state: AuthorizedKeyState,
+        /// authorized_keys file to manage (default: ~user/.ssh/authorized_keys)
+        path: Option<Expression>,
+        /// Remove every key that isn't listed
+        exclusive: bool,
+        /// Create the file's directory and set 0700/0600 permissions owned by the user
+        manage_dir: bool,
+    },
+    /// blockinfile: /etc/hosts, block: "10.0.0.5 db" - manage a marked block of lines
+    BlockInFile {
+        path: Expression,
+        block: Option<Expression>,
+        /// Marker line template, `{mark}` becomes BEGIN/END (default: `# {mark} ANSIBLE MANAGED BLOCK`)
+        marker: Option<Expression>,
+        state: BlockInFileState,
+        /// Regex (or EOF) to insert a new block after
+        insertafter: Option<Expression>,
+        /// Regex (or BOF) to insert a new block before
+        insertbefore: Option<Expression>,
+        create: bool,
+    },
+    /// hostname: web01.example.com - set the system hostname
+    Hostname { name: Expression },
+    /// timezone: Europe/Berlin - set the system timezone
+    Timezone { name: Expression },
+    /// debug: {var: result} - report a message or a variable's value
+    Debug {
+        msg: Option<Expression>,
+        /// Variable (or expression) to show, as pretty JSON
+        var: Option<Expression>,
+        /// Only report at this many `-v` flags or more
+        verbosity: u8,
+    },
+    /// assert: {that: ["${port > 0}"]} - fail unless every condition holds
+    Assert {
+        that: Vec<Assertion>,
+        fail_msg: Option<Expression>,
+        success_msg: Option<Expression>,
+        /// Don't report anything when every condition holds
+        quiet: bool,
+    },
+    /// set_fact: {release: "${version}", cacheable: true} - set host variables
+    SetFact {
+        /// Names and values, in the order written
+        facts: Vec<(String, Expression)>,
+        /// Also keep them in the fact cache for later plays and runs
+        cacheable: bool,
+    },
+    /// group_by: {key: "distro_${ansible_distribution}"} - add the host to a group
+    /// that later plays can target
+    GroupBy { key: Expression },
+    /// my_module: {name: web} - run a script found on the module path
+    Custom {
+        name: String,
+        /// The module's parameters, passed to it as a JSON object
+        args: Expression,
     },
 }
 
 impl ModuleCall {
     /// Get the module name as a string
-    pub fn module_name(&self) -> &'static str {
+    pub fn module_name(&self) -> &str {
         match self {
             ModuleCall::Package { .. } => "package",
             ModuleCall::Service { .. } => "service",
@@ -390,6 +654,27 @@ impl ModuleCall {
             ModuleCall::Template { .. } => "template",
             ModuleCall::Facts { .. } => "facts",
             ModuleCall::Shell { .. } => "shell",
+            ModuleCall::WaitFor { .. } => "wait_for",
+            ModuleCall::GetUrl { .. } => "get_url",
+            ModuleCall::Systemd { .. } => "systemd",
+            ModuleCall::Pip { .. } => "pip",
+            ModuleCall::Slurp { .. } => "slurp",
+            ModuleCall::Stat { .. } => "stat",
+            ModuleCall::AsyncStatus { .. } => "async_status",
+            ModuleCall::Fetch { .. } => "fetch",
+            ModuleCall::Synchronize { .. } => "synchronize",
+            ModuleCall::Meta { .. } => "meta",
+            ModuleCall::Mount { .. } => "mount",
+            ModuleCall::Reboot { .. } => "reboot",
+            ModuleCall::AuthorizedKey { .. } => "authorized_key",
+            ModuleCall::BlockInFile { .. } => "blockinfile",
+            ModuleCall::Hostname { .. } => "hostname",
+            ModuleCall::Timezone { .. } => "timezone",
+            ModuleCall::Debug { .. } => "debug",
+            ModuleCall::Assert { .. } => "assert",
+            ModuleCall::SetFact { .. } => "set_fact",
+            ModuleCall::GroupBy { .. } => "group_by",
+            ModuleCall::Custom { name, .. } => name,
         }
     }
 }
@@ -428,11 +713,121 @@ pub enum UserState {
     Absent,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuthorizedKeyState {
+    #[default]
+    Present,
+    Absent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockInFileState {
+    #[default]
+    Present,
+    Absent,
+}
+
+/// One `assert` condition, with its source text for reporting which one failed
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    pub source: String,
+    pub condition: Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PipState {
+    #[default]
+    Present,
+    Absent,
+    Latest,
+}
+
+/// Direction of a `synchronize` transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// From the control node to the host
+    #[default]
+    Push,
+    /// From the host to the control node
+    Pull,
+}
+
+/// Which systemd instance manages the unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SystemdScope {
+    #[default]
+    System,
+    /// The calling user's service manager (`systemctl --user`)
+    User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitForState {
+    #[default]
+    Started,
+    Stopped,
+    Present,
+    Absent,
+}
+
+/// Desired state for the mount module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountState {
+    /// fstab entry present and mounted
+    Mounted,
+    /// Not mounted; fstab is left alone
+    Unmounted,
+    /// fstab entry present; the mount is left alone
+    Present,
+    /// No fstab entry and not mounted
+    Absent,
+    /// Remounted, e.g. to apply changed options
+    Remounted,
+}
+
+impl MountState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MountState::Mounted => "mounted",
+            MountState::Unmounted => "unmounted",
+            MountState::Present => "present",
+            MountState::Absent => "absent",
+            MountState::Remounted => "remounted",
+        }
+    }
+}
+
+/// Scheduler control actions for `meta:` tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaAction {
+    /// Run pending handlers now instead of at the end of the play
+    FlushHandlers,
+    /// Drop gathered facts for the targeted hosts
+    ClearFacts,
+    /// Stop the current play without failing
+    EndPlay,
+    /// Clear failed state for the targeted hosts; a no-op, as a failed task stops the play
+    ClearHostErrors,
+}
+
+impl MetaAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetaAction::FlushHandlers => "flush_handlers",
+            MetaAction::ClearFacts => "clear_facts",
+            MetaAction::EndPlay => "end_play",
+            MetaAction::ClearHostErrors => "clear_host_errors",
+        }
+    }
+}
+
 /// Handler definition
 #[derive(Debug, Clone)]
 pub struct Handler {
     pub name: String,
     pub module: ModuleCall,
+    /// Topics that trigger this handler in addition to its name
+    pub listen: Vec<String>,
     pub location: Option<SourceLocation>,
 }
 
@@ -634,11 +1029,12 @@ pub enum Expression {
     List(Vec<Expression>),
     /// Dict literal: {"key": "value"}
     Dict(Vec<(Expression, Expression)>),
-    /// Filter expression: items | filter(x => x.active)
+    /// Filter expression: items | filter(x => x.active), users | map(attribute="name")
     Filter {
         input: Box<Expression>,
         filter_name: String,
-        predicate: Option<Box<Expression>>,
+        args: Vec<Expression>,
+        kwargs: HashMap<String, Expression>,
     },
     /// Lambda/arrow function: x => x.active
     Lambda {
@@ -249,11 +249,35 @@ fn parse_unary(pair: pest::iterators::Pair<Rule>) -> Result<Expression, NexusErr
                 operand: Box::new(operand),
             })
         }
-        Rule::postfix => parse_postfix(first),
+        Rule::filtered => parse_filtered(first),
         _ => unreachable!("Unexpected rule in unary: {:?}", first.as_rule()),
     }
 }
 
+fn parse_filtered(pair: pest::iterators::Pair<Rule>) -> Result<Expression, NexusError> {
+    let mut inner = pair.into_inner();
+    let mut expr = parse_postfix(inner.next().unwrap())?;
+
+    for filter_call in inner {
+        let filter_name = filter_call
+            .clone()
+            .into_inner()
+            .next()
+            .unwrap()
+            .as_str()
+            .to_string();
+        let (args, kwargs) = parse_args(filter_call)?;
+        expr = Expression::Filter {
+            input: Box::new(expr),
+            filter_name,
+            args,
+            kwargs,
+        };
+    }
+
+    Ok(expr)
+}
+
 fn parse_postfix(pair: pest::iterators::Pair<Rule>) -> Result<Expression, NexusError> {
     let mut inner = pair.into_inner();
     let mut expr = parse_primary(inner.next().unwrap())?;
@@ -282,6 +306,16 @@ fn parse_postfix(pair: pest::iterators::Pair<Rule>) -> Result<Expression, NexusE
                         args: args.0,
                         kwargs: args.1,
                     },
+                    // `users.keys()` is lexed as the dotted variable `users.keys`
+                    Expression::Variable(mut path) if path.len() > 1 => {
+                        let method = path.pop().unwrap();
+                        Expression::MethodCall {
+                            object: Box::new(Expression::Variable(path)),
+                            method,
+                            args: args.0,
+                            kwargs: args.1,
+                        }
+                    }
                     _ => {
                         return Err(NexusError::Parse(Box::new(ParseError {
                             kind: ParseErrorKind::InvalidExpression,
@@ -324,6 +358,11 @@ fn parse_args(
     for arg in pair.into_inner() {
         if arg.as_rule() == Rule::args {
             for inner_arg in arg.into_inner() {
+                // `arg` wraps either a kwarg or a positional expression
+                let inner_arg = match inner_arg.as_rule() {
+                    Rule::arg => inner_arg.into_inner().next().unwrap(),
+                    _ => inner_arg,
+                };
                 match inner_arg.as_rule() {
                     Rule::kwarg => {
                         let mut kw_inner = inner_arg.into_inner();
@@ -331,10 +370,6 @@ fn parse_args(
                         let value = parse_or_expr(kw_inner.next().unwrap())?;
                         kwargs.insert(name, value);
                     }
-                    Rule::arg => {
-                        let expr = parse_or_expr(inner_arg.into_inner().next().unwrap())?;
-                        positional.push(expr);
-                    }
                     _ => {
                         let expr = parse_or_expr(inner_arg)?;
                         positional.push(expr);
@@ -486,6 +521,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_method_call_on_dotted_variable() {
+        let expr = parse_expression("host.vars.keys()").unwrap();
+        if let Expression::MethodCall { object, method, .. } = expr {
+            assert_eq!(method, "keys");
+            assert!(matches!(*object, Expression::Variable(ref p) if p == &["host", "vars"]));
+        } else {
+            panic!("Expected MethodCall, got {:?}", expr);
+        }
+    }
+
     #[test]
     fn test_comparison() {
         let expr = parse_expression("x > 5").unwrap();
@@ -8,7 +8,11 @@ use serde_yaml::Value as YamlValue;
 
 use super::ast::*;
 use super::expressions::{has_interpolation, parse_interpolated_string};
-use super::yaml::{convert_vars, extract_yaml_error_location, parse_condition, yaml_to_expression};
+use super::lines::{locate_tasks, matching, scan_task_list};
+use super::yaml::{
+    convert_condition, convert_expression_vars, convert_loop, convert_vars,
+    extract_yaml_error_location, parse_condition, yaml_to_expression, ConditionValue,
+};
 use crate::output::errors::{NexusError, ParseError, ParseErrorKind};
 
 /// Raw task structure for parsing (subset of full RawTask)
@@ -19,11 +23,15 @@ struct RawTaskFile {
     #[serde(rename = "when")]
     when_condition: Option<String>,
     register: Option<String>,
-    fail_when: Option<String>,
-    changed_when: Option<String>,
+    #[serde(alias = "failed_when")]
+    fail_when: Option<ConditionValue>,
+    changed_when: Option<ConditionValue>,
     notify: Option<NotifyValue>,
+    always_notify: Option<bool>,
     #[serde(rename = "loop")]
     loop_expr: Option<String>,
+    #[serde(alias = "with_nested")]
+    loop_nested: Option<YamlValue>,
     loop_var: Option<String>,
     sudo: Option<bool>,
     #[serde(rename = "as")]
@@ -40,6 +48,8 @@ struct RawTaskFile {
     throttle: Option<usize>,
     delegate_to: Option<String>,
     delegate_facts: Option<bool>,
+    no_log: Option<bool>,
+    check_mode: Option<bool>,
     block: Option<Vec<RawTaskFile>>,
     rescue: Option<Vec<RawTaskFile>>,
     always: Option<Vec<RawTaskFile>>,
@@ -205,14 +215,22 @@ pub fn parse_task_file(path: &Path) -> Result<Vec<TaskOrBlock>, NexusError> {
         }))
     })?;
 
-    // Convert each raw task to TaskOrBlock
-    let tasks: Result<Vec<TaskOrBlock>, NexusError> = raw_tasks
+    // Convert each raw task to TaskOrBlock, pointing errors at the task's line
+    let source_file = path.to_string_lossy();
+    let lines = scan_task_list(&content);
+    let lines = matching(&lines, raw_tasks.len());
+    let mut tasks = raw_tasks
         .into_iter()
         .enumerate()
-        .map(|(index, raw)| convert_task_file(raw, path.to_string_lossy().as_ref(), index))
-        .collect();
+        .map(|(index, raw)| {
+            convert_task_file(raw, &source_file, index).map_err(|e| {
+                e.with_location(lines.get(index).map(|l| l.location(&source_file)).as_ref())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    locate_tasks(&mut tasks, lines, &source_file);
 
-    tasks
+    Ok(tasks)
 }
 
 /// Convert a RawTaskFile to a TaskOrBlock
@@ -266,17 +284,25 @@ fn convert_task_file(
         .transpose()?;
 
     // Parse loop
-    let loop_expr = raw.loop_expr.map(|l| parse_condition(&l)).transpose()?;
+    let (loop_expr, loop_nested) = convert_loop(raw.loop_expr, raw.loop_nested)?;
     let loop_var = raw.loop_var.unwrap_or_else(|| "item".to_string());
 
     // Parse register
     let register = raw.register;
 
     // Parse fail_when
-    let fail_when = raw.fail_when.map(|f| parse_condition(&f)).transpose()?;
+    let fail_when = raw
+        .fail_when
+        .map(|c| convert_condition(c, BinaryOperator::Or))
+        .transpose()?
+        .flatten();
 
     // Parse changed_when
-    let changed_when = raw.changed_when.map(|c| parse_condition(&c)).transpose()?;
+    let changed_when = raw
+        .changed_when
+        .map(|c| convert_condition(c, BinaryOperator::And))
+        .transpose()?
+        .flatten();
 
     // Parse notify
     let notify = match raw.notify {
@@ -304,9 +330,6 @@ fn convert_task_file(
         })
         .transpose()?;
 
-    // Get line/column for location
-    let (line, column) = (0, 0);
-
     // Build the task
     Ok(TaskOrBlock::Task(Box::new(Task {
         name,
@@ -314,10 +337,12 @@ fn convert_task_file(
         when,
         register,
         loop_expr,
+        loop_nested,
         loop_var,
         fail_when,
         changed_when,
         notify,
+        always_notify: raw.always_notify.unwrap_or(false),
         sudo: raw.sudo,
         run_as: raw.run_as,
         tags,
@@ -326,7 +351,6 @@ fn convert_task_file(
             Some(AsyncConfig {
                 async_timeout,
                 poll: raw.poll.unwrap_or(10),
-                retries: raw.retries.unwrap_or(30),
             })
         } else {
             None
@@ -335,11 +359,14 @@ fn convert_task_file(
         throttle: raw.throttle,
         delegate_to,
         delegate_facts: raw.delegate_facts.unwrap_or(false),
-        location: Some(SourceLocation {
-            file: source_file.to_string(),
-            line,
-            column,
-        }),
+        no_log: raw.no_log.unwrap_or(false),
+        check_mode: raw.check_mode,
+        vars: raw
+            .vars
+            .map(convert_expression_vars)
+            .transpose()?
+            .unwrap_or_default(),
+        location: None, // set from the file's lines by parse_task_file
     })))
 }
 
@@ -422,11 +449,18 @@ fn convert_block_file(
         None => vec![],
     };
 
+    let vars = raw
+        .vars
+        .map(convert_expression_vars)
+        .transpose()?
+        .unwrap_or_default();
+
     Ok(TaskOrBlock::Block(Block {
         name,
         block: block_tasks?,
         rescue: rescue_tasks?,
         always: always_tasks?,
+        vars,
         when,
         tags,
         location: None,
@@ -463,6 +497,9 @@ fn parse_module_call_from_raw(
             cmd,
             creates: None,
             removes: None,
+            environment: HashMap::new(),
+            stdin: None,
+            stdin_add_newline: true,
         });
     }
 
@@ -0,0 +1,320 @@
+// Source line numbers for tasks and handlers. serde_yaml doesn't keep positions,
+// so the entries of each task list are found by scanning the YAML text.
+
+use super::ast::{Block, SourceLocation, Task, TaskOrBlock};
+
+/// Where one entry of a task list starts, and the entries of its block sections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ItemLines {
+    /// 1-based line of the entry's `-`
+    pub(crate) line: usize,
+    /// 1-based column of the entry's `-`
+    pub(crate) column: usize,
+    pub(crate) block: Vec<ItemLines>,
+    pub(crate) rescue: Vec<ItemLines>,
+    pub(crate) always: Vec<ItemLines>,
+}
+
+impl ItemLines {
+    pub(crate) fn location(&self, file: &str) -> SourceLocation {
+        SourceLocation {
+            file: file.to_string(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+/// The task lists of a playbook
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PlaybookLines {
+    pub(crate) tasks: Vec<ItemLines>,
+    pub(crate) pre_tasks: Vec<ItemLines>,
+    pub(crate) post_tasks: Vec<ItemLines>,
+    pub(crate) handlers: Vec<ItemLines>,
+}
+
+impl PlaybookLines {
+    /// Find the task lists of a playbook that is a single play (a mapping)
+    pub(crate) fn scan(content: &str) -> Self {
+        let lines = significant_lines(content);
+        match lines.first() {
+            Some(first) => Self::from_keys(mapping_keys(&lines, first.indent)),
+            None => PlaybookLines::default(),
+        }
+    }
+
+    /// Find the plays of a playbook that is a list of them: where each entry starts,
+    /// and its task lists
+    pub(crate) fn scan_plays(content: &str) -> Vec<(ItemLines, PlaybookLines)> {
+        let lines = significant_lines(content);
+        entries(&lines)
+            .into_iter()
+            .map(|(dash, body)| {
+                let entry = ItemLines {
+                    line: dash.number,
+                    column: dash.indent + 1,
+                    ..Default::default()
+                };
+                (entry, Self::from_keys(entry_keys(dash, body)))
+            })
+            .collect()
+    }
+
+    fn from_keys(keys: Vec<(&str, &[Line])>) -> Self {
+        let mut found = PlaybookLines::default();
+        for (key, value) in keys {
+            let list = match key {
+                "tasks" => &mut found.tasks,
+                "pre_tasks" => &mut found.pre_tasks,
+                "post_tasks" => &mut found.post_tasks,
+                "handlers" => &mut found.handlers,
+                _ => continue,
+            };
+            *list = sequence_items(value);
+        }
+        found
+    }
+}
+
+/// Find the entries of a file that is just a list of tasks (or handlers)
+pub(crate) fn scan_task_list(content: &str) -> Vec<ItemLines> {
+    sequence_items(&significant_lines(content))
+}
+
+/// `lines` if it has one entry per item of a list of `count`; otherwise the scan
+/// went wrong (e.g. a flow-style list) and no lines are better than wrong ones
+pub(crate) fn matching(lines: &[ItemLines], count: usize) -> &[ItemLines] {
+    if lines.len() == count {
+        lines
+    } else {
+        &[]
+    }
+}
+
+/// Set the location of each task (and of the tasks in its blocks) from `lines`
+pub(crate) fn locate_tasks(tasks: &mut [TaskOrBlock], lines: &[ItemLines], file: &str) {
+    let lines = matching(lines, tasks.len());
+    for (task, item) in tasks.iter_mut().zip(lines) {
+        let location = Some(item.location(file));
+        match task {
+            TaskOrBlock::Task(task) => task.location = location,
+            TaskOrBlock::Block(block) => locate_block(block, item, file),
+            TaskOrBlock::Import(import) => import.location = location,
+            TaskOrBlock::Include(include) => include.location = location,
+        }
+    }
+}
+
+fn locate_block(block: &mut Block, item: &ItemLines, file: &str) {
+    block.location = Some(item.location(file));
+    for (tasks, lines) in [
+        (&mut block.block, &item.block),
+        (&mut block.rescue, &item.rescue),
+        (&mut block.always, &item.always),
+    ] {
+        locate_block_tasks(tasks, lines, file);
+    }
+}
+
+fn locate_block_tasks(tasks: &mut [Task], lines: &[ItemLines], file: &str) {
+    let lines = matching(lines, tasks.len());
+    for (task, item) in tasks.iter_mut().zip(lines) {
+        task.location = Some(item.location(file));
+    }
+}
+
+/// A line that isn't blank, a comment or a document marker
+#[derive(Debug)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    /// The line without its indentation
+    text: &'a str,
+}
+
+fn significant_lines(content: &str) -> Vec<Line<'_>> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, raw)| {
+            let text = raw.trim_start_matches(' ');
+            let trimmed = text.trim_end();
+            if trimmed.is_empty()
+                || trimmed.starts_with('#')
+                || trimmed == "---"
+                || trimmed == "..."
+            {
+                return None;
+            }
+            Some(Line {
+                number: i + 1,
+                indent: raw.len() - text.len(),
+                text,
+            })
+        })
+        .collect()
+}
+
+fn is_dash(text: &str) -> bool {
+    text.trim_end() == "-" || text.starts_with("- ")
+}
+
+/// The key of a `key:` or `key: value` line
+fn key_of(text: &str) -> Option<&str> {
+    let (key, rest) = text.split_once(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then(|| key.trim())
+}
+
+/// The lines making up the value of a key at `indent`: deeper lines, or a list
+/// written at the key's own indentation
+fn value_lines<'a, 'b>(after: &'b [Line<'a>], indent: usize) -> &'b [Line<'a>] {
+    let same_level_list = after
+        .first()
+        .is_some_and(|l| l.indent == indent && is_dash(l.text));
+    let end = after
+        .iter()
+        .position(|l| {
+            l.indent < indent || (l.indent == indent && !(same_level_list && is_dash(l.text)))
+        })
+        .unwrap_or(after.len());
+    &after[..end]
+}
+
+/// The entries of the block-style list in `lines`: each one's dash line and the
+/// lines after it that belong to it
+fn entries<'a, 'b>(lines: &'b [Line<'a>]) -> Vec<(&'b Line<'a>, &'b [Line<'a>])> {
+    let Some(first) = lines.first().filter(|l| is_dash(l.text)) else {
+        return Vec::new();
+    };
+    let indent = first.indent;
+
+    let starts: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.indent == indent && is_dash(l.text))
+        .map(|(i, _)| i)
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+            (&lines[start], &lines[start + 1..end])
+        })
+        .collect()
+}
+
+fn sequence_items(lines: &[Line]) -> Vec<ItemLines> {
+    entries(lines)
+        .into_iter()
+        .map(|(dash, body)| {
+            let mut item = ItemLines {
+                line: dash.number,
+                column: dash.indent + 1,
+                ..Default::default()
+            };
+            for (key, value) in entry_keys(dash, body) {
+                let section = match key {
+                    "block" => &mut item.block,
+                    "rescue" => &mut item.rescue,
+                    "always" => &mut item.always,
+                    _ => continue,
+                };
+                *section = sequence_items(value);
+            }
+            item
+        })
+        .collect()
+}
+
+/// The keys of a mapping at `indent` in `lines`, each with the lines of its value
+fn mapping_keys<'a, 'b>(lines: &'b [Line<'a>], indent: usize) -> Vec<(&'a str, &'b [Line<'a>])> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.indent == indent)
+        .filter_map(|(i, l)| Some((key_of(l.text)?, value_lines(&lines[i + 1..], indent))))
+        .collect()
+}
+
+/// The keys of a list entry that is a mapping, starting at `dash` and continuing
+/// with `body`, each with the lines of its value
+fn entry_keys<'a, 'b>(dash: &Line<'a>, body: &'b [Line<'a>]) -> Vec<(&'a str, &'b [Line<'a>])> {
+    // The entry's keys line up with the first one, after the dash
+    let rest = dash.text[1..].trim_start_matches(' ');
+    if rest.trim_end().is_empty() {
+        return match body.first() {
+            Some(first) => mapping_keys(body, first.indent),
+            None => Vec::new(),
+        };
+    }
+    let indent = dash.indent + dash.text.len() - rest.len();
+
+    let mut keys: Vec<_> = key_of(rest)
+        .map(|key| (key, value_lines(body, indent)))
+        .into_iter()
+        .collect();
+    keys.extend(mapping_keys(body, indent));
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_playbook_task_lines() {
+        let content = "\
+---
+hosts: all
+
+tasks:
+  # First one
+  - name: Install
+    package: nginx
+
+  - name: Guarded
+    block:
+      - name: Inner
+        command: |
+          - not a task
+    rescue:
+    - command: echo rescued
+    always:
+      - debug: msg=done
+handlers:
+- name: restart
+  service: name=nginx state=restarted
+";
+        let lines = PlaybookLines::scan(content);
+        assert_eq!(
+            lines.tasks.iter().map(|t| t.line).collect::<Vec<_>>(),
+            vec![6, 9]
+        );
+        assert_eq!(lines.tasks[0].column, 3);
+        let block = &lines.tasks[1];
+        assert_eq!(block.block.iter().map(|t| t.line).collect::<Vec<_>>(), [11]);
+        assert_eq!(
+            block.rescue.iter().map(|t| t.line).collect::<Vec<_>>(),
+            [15]
+        );
+        assert_eq!(
+            block.always.iter().map(|t| t.line).collect::<Vec<_>>(),
+            [17]
+        );
+        assert_eq!(lines.handlers.len(), 1);
+        assert_eq!(lines.handlers[0].line, 19);
+        assert!(lines.pre_tasks.is_empty());
+
+        let task_file = "- name: one\n  command: a\n-\n  name: two\n  command: b\n";
+        assert_eq!(
+            scan_task_list(task_file)
+                .iter()
+                .map(|t| t.line)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+}
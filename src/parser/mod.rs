@@ -4,7 +4,10 @@ pub mod ast;
 pub mod expressions;
 pub mod functions;
 pub mod include;
+mod lines;
 pub mod roles;
+pub mod syntax;
+pub mod validate;
 pub mod yaml;
 
 pub use ast::*;
@@ -12,4 +15,9 @@ pub use expressions::{has_interpolation, parse_expression, parse_interpolated_st
 pub use functions::parse_functions_block;
 pub use include::{convert_import_tasks, convert_include_tasks, parse_task_file};
 pub use roles::{load_role, RoleResolver};
-pub use yaml::{parse_playbook, parse_playbook_file, parse_playbook_file_with_vault};
+pub use syntax::{syntax_check, SyntaxReport};
+pub use validate::{validate_strict, ValidationIssue};
+pub use yaml::{
+    load_vars_file, parse_extra_vars, parse_playbook, parse_playbook_file,
+    parse_playbook_file_with_vault, set_module_paths,
+};
@@ -22,7 +22,8 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use super::ast::*;
-use super::yaml::parse_playbook;
+use super::lines::{scan_task_list, PlaybookLines};
+use super::yaml::parse_playbook_with_lines;
 use crate::output::errors::{NexusError, ParseError, ParseErrorKind};
 
 /// Role search paths
@@ -56,7 +57,7 @@ impl RoleResolver {
     pub fn add_playbook_relative_path(&mut self, playbook_path: &Path) {
         if let Some(parent) = playbook_path.parent() {
             let roles_dir = parent.join("roles");
-            if roles_dir.exists() {
+            if roles_dir.exists() && !self.search_paths.contains(&roles_dir) {
                 self.search_paths.insert(0, roles_dir);
             }
         }
@@ -399,7 +400,17 @@ fn load_role_tasks(role_path: &Path) -> Result<Vec<TaskOrBlock>, NexusError> {
     }))
     .unwrap();
 
-    let playbook = parse_playbook(&playbook_yaml, tasks_file.to_string_lossy().to_string())?;
+    // Line numbers come from the role's own file, not the generated playbook
+    let lines = PlaybookLines {
+        tasks: scan_task_list(&content),
+        ..Default::default()
+    };
+    let playbook = parse_playbook_with_lines(
+        &playbook_yaml,
+        tasks_file.to_string_lossy().to_string(),
+        None,
+        &lines,
+    )?;
     Ok(playbook.tasks)
 }
 
@@ -442,7 +453,16 @@ fn load_role_handlers(role_path: &Path) -> Result<Vec<Handler>, NexusError> {
     }))
     .unwrap();
 
-    let playbook = parse_playbook(&playbook_yaml, handlers_file.to_string_lossy().to_string())?;
+    let lines = PlaybookLines {
+        handlers: scan_task_list(&content),
+        ..Default::default()
+    };
+    let playbook = parse_playbook_with_lines(
+        &playbook_yaml,
+        handlers_file.to_string_lossy().to_string(),
+        None,
+        &lines,
+    )?;
     Ok(playbook.handlers)
 }
 
@@ -0,0 +1,192 @@
+// Recursive syntax check for `nexus run --syntax-check`: parse a playbook and every
+// file it pulls in statically, without connecting anywhere
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::ast::{Expression, Playbook, TaskOrBlock};
+use super::include::parse_task_file;
+use super::roles::RoleResolver;
+use super::yaml::PlayLoader;
+use crate::output::errors::NexusError;
+use crate::vault::VaultSecrets;
+
+/// What `syntax_check` parsed and the errors it found
+#[derive(Debug, Default)]
+pub struct SyntaxReport {
+    /// The playbooks, then the task files, that were parsed
+    pub files: Vec<PathBuf>,
+    /// Roles that were loaded, dependencies included
+    pub roles: Vec<String>,
+    pub errors: Vec<NexusError>,
+}
+
+/// Parse the playbook at `path` and the playbooks it imports, then load every role
+/// their plays use (with dependencies) and every `import_tasks` and static
+/// `include_tasks` file, recursively. Errors are
+/// collected rather than returned on the first one; a file that fails to parse
+/// can't be looked into, but its siblings still are. Includes whose file name
+/// depends on variables are left for runtime.
+pub fn syntax_check(path: &Path, vault_secrets: Option<&VaultSecrets>) -> SyntaxReport {
+    let mut checker = SyntaxChecker {
+        playbook_dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        resolver: RoleResolver::new(),
+        seen: HashSet::new(),
+        report: SyntaxReport::default(),
+    };
+    checker.resolver.add_playbook_relative_path(path);
+
+    let mut loader = PlayLoader::new(vault_secrets, true);
+    let plays = loader.load(path);
+    checker.report.files = loader.files;
+    checker.report.errors = loader.errors;
+
+    match plays {
+        Ok(plays) => {
+            for play in &plays {
+                checker.check_play(play);
+            }
+        }
+        Err(e) => checker.report.errors.insert(0, e),
+    }
+    checker.report
+}
+
+struct SyntaxChecker {
+    playbook_dir: PathBuf,
+    resolver: RoleResolver,
+    /// Task files and roles already checked, so shared ones are parsed once
+    seen: HashSet<String>,
+    report: SyntaxReport,
+}
+
+impl SyntaxChecker {
+    fn check_play(&mut self, play: &Playbook) {
+        // Roles of an imported playbook can sit next to it
+        self.resolver
+            .add_playbook_relative_path(Path::new(&play.source_file));
+
+        self.check_tasks(&play.pre_tasks);
+        for role_ref in &play.roles {
+            self.check_role(&role_ref.role);
+        }
+        self.check_tasks(&play.tasks);
+        self.check_tasks(&play.post_tasks);
+    }
+
+    fn check_role(&mut self, name: &str) {
+        let order = match self.resolver.resolve_dependencies(name) {
+            Ok(order) => order,
+            Err(e) => {
+                self.report.errors.push(e);
+                return;
+            }
+        };
+
+        for role_name in order {
+            if !self.seen.insert(format!("role:{}", role_name)) {
+                continue;
+            }
+            // Already loaded by resolve_dependencies
+            let Ok(role) = self.resolver.resolve(&role_name).cloned() else {
+                continue;
+            };
+            self.report.roles.push(role_name);
+            self.check_tasks(&role.tasks);
+        }
+    }
+
+    fn check_tasks(&mut self, items: &[TaskOrBlock]) {
+        for item in items {
+            match item {
+                // Blocks only hold plain tasks, which parsed along with their file
+                TaskOrBlock::Task(_) | TaskOrBlock::Block(_) => {}
+                TaskOrBlock::Import(import) => self.check_file(PathBuf::from(&import.file)),
+                TaskOrBlock::Include(include) => {
+                    if let Expression::String(file) = &include.file {
+                        let path = Path::new(file);
+                        if path.is_absolute() {
+                            self.check_file(path.to_path_buf());
+                        } else {
+                            self.check_file(self.playbook_dir.join(path));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_file(&mut self, path: PathBuf) {
+        if !self.seen.insert(path.display().to_string()) {
+            return;
+        }
+        self.report.files.push(path.clone());
+        match parse_task_file(&path) {
+            Ok(tasks) => self.check_tasks(&tasks),
+            Err(e) => self.report.errors.push(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_check_collects_errors_from_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "site.yml",
+            r#"
+hosts: all
+roles:
+  - web
+tasks:
+  - import_tasks: good.yml
+  - import_tasks: broken.yml
+  - include_tasks: typo.yml
+  - include_tasks: "${env}.yml"
+"#,
+        );
+        write("good.yml", "- import_tasks: nested.yml\n");
+        write("nested.yml", "- name: Nested\n  command: echo ok\n");
+        write("broken.yml", "- name: Broken\n  command: [unclosed\n");
+        write("typo.yml", "- name: Typo\n  packages: nginx\n");
+        write(
+            "roles/web/tasks/main.yml",
+            "- name: Role task\n  command: echo ok\n",
+        );
+        write("roles/web/meta/main.yml", "dependencies:\n  - base\n");
+
+        let report = syntax_check(&dir.path().join("site.yml"), None);
+
+        let names: Vec<String> = report
+            .files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "site.yml",
+                "good.yml",
+                "nested.yml",
+                "broken.yml",
+                "typo.yml"
+            ]
+        );
+        assert!(report.roles.is_empty());
+
+        let errors: Vec<String> = report.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].contains("Role 'base' not found"));
+        assert!(errors[1].contains("broken.yml"));
+        assert!(errors[2].contains("Unknown or unsupported module"));
+        assert!(errors[2].contains("typo.yml:1:1"));
+    }
+}
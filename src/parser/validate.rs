@@ -0,0 +1,865 @@
+// Static checks for `nexus validate --strict`: references, handlers and variables
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::ast::{
+    Expression, Handler, ModuleCall, Playbook, SourceLocation, StringPart, Task, TaskOrBlock,
+};
+use super::include::parse_task_file;
+use super::roles::RoleResolver;
+use super::yaml::load_vars_file;
+use crate::output::errors::NexusError;
+
+/// Variables every task can use without defining them
+const BUILTIN_VARS: &[&str] = &[
+    "host",
+    "inventory_hostname",
+    "inventory_hostname_short",
+    "groups",
+    "group_names",
+    "hostvars",
+    "play_hosts",
+];
+
+/// Top-level variables set by fact gathering (`gather_facts` or a `facts` task)
+const FACT_VARS: &[&str] = &[
+    "hostname",
+    "hostname_short",
+    "os_family",
+    "os_name",
+    "os_version",
+    "os_pretty_name",
+    "os_codename",
+    "release",
+    "kernel_version",
+    "architecture",
+    "cpu_count",
+    "cpu_model",
+    "memory_total_kb",
+    "memory_total_mb",
+    "memory_total_gb",
+    "memory_free_kb",
+    "memory_available_kb",
+    "swap_total_kb",
+    "interfaces",
+    "default_ipv4",
+    "default_gateway",
+    "all_ipv4_addresses",
+    "dns_servers",
+    "mounts",
+    "block_devices",
+    "package_manager",
+    "installed_packages_count",
+    "running_services",
+    "has_systemd",
+    "date_time",
+    "timezone",
+    "uptime",
+    "home",
+    "shell",
+    "user",
+    "path",
+];
+
+/// A problem found by `validate_strict`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// File the offending task (or reference) is in
+    pub file: String,
+    pub line: Option<usize>,
+    /// Name of the task or handler, if the problem is in one
+    pub task: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: ", self.file, line)?,
+            None => write!(f, "{}: ", self.file)?,
+        }
+        if let Some(task) = &self.task {
+            write!(f, "task '{}': ", task)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Check a playbook beyond what parsing does, without connecting anywhere:
+///
+/// - roles, `import_tasks`, static `include_tasks` and `vars_files` must exist and parse
+/// - every `notify` must name a handler (or a handler's `listen` topic)
+/// - variables used in expressions must be defined somewhere: play vars, vars files,
+///   prompts, role defaults/vars, include vars, `register`, `set_fact`, facts when
+///   they're gathered, or `known_vars` (e.g. inventory variables)
+///
+/// Conditions (`when`, `changed_when`, ...) are parsed while loading, so a broken one
+/// in the playbook already fails to load and one in a task file is reported here.
+/// The variable check ignores order: a variable registered by a later task counts.
+pub fn validate_strict(playbook: &Playbook, known_vars: &HashSet<String>) -> Vec<ValidationIssue> {
+    let playbook_path = Path::new(&playbook.source_file);
+    let mut resolver = RoleResolver::new();
+    resolver.add_playbook_relative_path(playbook_path);
+
+    let mut validator = Validator {
+        playbook_dir: playbook_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        resolver,
+        issues: Vec::new(),
+        tasks: Vec::new(),
+        handlers: Vec::new(),
+        import_stack: Vec::new(),
+    };
+    validator.run(playbook, known_vars);
+
+    // A role listed twice (or shared as a dependency) would report everything twice
+    let mut issues = validator.issues;
+    let mut seen = HashSet::new();
+    issues.retain(|issue| seen.insert(issue.to_string()));
+    issues
+}
+
+/// A task (or dynamic include) to check
+struct CheckedTask {
+    file: String,
+    line: Option<usize>,
+    name: String,
+    notify: Vec<String>,
+    /// Expressions it evaluates
+    exprs: Vec<Expression>,
+    /// Variables only it can see: role, import and loop variables
+    local_vars: HashSet<String>,
+    /// Variables it sets for later tasks (`register`, `set_fact`)
+    sets_vars: Vec<String>,
+    gathers_facts: bool,
+}
+
+struct Validator {
+    playbook_dir: PathBuf,
+    resolver: RoleResolver,
+    issues: Vec<ValidationIssue>,
+    tasks: Vec<CheckedTask>,
+    handlers: Vec<(String, Handler)>,
+    /// Task files being imported, to stop at one that imports itself
+    import_stack: Vec<PathBuf>,
+}
+
+impl Validator {
+    fn run(&mut self, playbook: &Playbook, known_vars: &HashSet<String>) {
+        let file = playbook.source_file.clone();
+        let mut defined: HashSet<String> = known_vars.clone();
+        defined.extend(BUILTIN_VARS.iter().map(|v| v.to_string()));
+        defined.extend(playbook.vars.keys().cloned());
+        defined.extend(playbook.vars_prompt.iter().map(|p| p.name.clone()));
+
+        // An encrypted vars file hides its variable names, so nothing can be called undefined
+        let mut check_vars = true;
+        for vars_file in &playbook.vars_files {
+            let path = self.playbook_dir.join(vars_file);
+            if !path.exists() {
+                self.issue(
+                    &file,
+                    None,
+                    format!("vars file not found: {}", path.display()),
+                );
+            } else if is_vault_file(&path) {
+                check_vars = false;
+            } else {
+                match load_vars_file(&path, None) {
+                    Ok(vars) => defined.extend(vars.into_keys()),
+                    Err(e) => self.issue(
+                        &file,
+                        None,
+                        format!("cannot load {}: {}", path.display(), error_summary(&e)),
+                    ),
+                }
+            }
+        }
+
+        let no_vars = HashSet::new();
+        self.collect(&playbook.pre_tasks, &file, &no_vars);
+        for role_ref in &playbook.roles {
+            let order = match self.resolver.resolve_dependencies(&role_ref.role) {
+                Ok(order) => order,
+                Err(e) => {
+                    self.issue(&file, None, error_summary(&e));
+                    continue;
+                }
+            };
+            for role_name in order {
+                let Ok(role) = self.resolver.resolve(&role_name).cloned() else {
+                    continue;
+                };
+                let mut role_vars: HashSet<String> = role.defaults.keys().cloned().collect();
+                role_vars.extend(role.vars.keys().cloned());
+                role_vars.extend(role_ref.vars.keys().cloned());
+                role_vars.extend(
+                    role.meta
+                        .dependencies
+                        .iter()
+                        .flat_map(|d| d.vars.keys().cloned()),
+                );
+                role_vars.extend(["role_name".to_string(), "role_path".to_string()]);
+                if role.templates_path.is_some() {
+                    role_vars.insert("role_templates_path".to_string());
+                }
+                if role.files_path.is_some() {
+                    role_vars.insert("role_files_path".to_string());
+                }
+
+                let role_file = format!("{}/tasks/main.yml", role.path);
+                self.collect(&role.tasks, &role_file, &role_vars);
+                let handlers_file = format!("{}/handlers/main.yml", role.path);
+                self.handlers.extend(
+                    role.handlers
+                        .iter()
+                        .map(|h| (handlers_file.clone(), h.clone())),
+                );
+            }
+        }
+        self.collect(&playbook.tasks, &file, &no_vars);
+        self.collect(&playbook.post_tasks, &file, &no_vars);
+        self.handlers
+            .extend(playbook.handlers.iter().map(|h| (file.clone(), h.clone())));
+
+        // Registered results and set_fact values are visible to every later task
+        for task in &self.tasks {
+            defined.extend(task.sets_vars.iter().cloned());
+        }
+        if playbook.gather_facts || self.tasks.iter().any(|t| t.gathers_facts) {
+            defined.extend(FACT_VARS.iter().map(|v| v.to_string()));
+        }
+
+        let topics: HashSet<&str> = self
+            .handlers
+            .iter()
+            .flat_map(|(_, h)| {
+                std::iter::once(h.name.as_str()).chain(h.listen.iter().map(String::as_str))
+            })
+            .collect();
+
+        let mut issues = Vec::new();
+        for task in &self.tasks {
+            let report = |issues: &mut Vec<ValidationIssue>, message: String| {
+                issues.push(ValidationIssue {
+                    file: task.file.clone(),
+                    line: task.line,
+                    task: Some(task.name.clone()),
+                    message,
+                });
+            };
+
+            for name in &task.notify {
+                if !topics.contains(name.as_str()) {
+                    report(
+                        &mut issues,
+                        format!("notifies undefined handler '{}'", name),
+                    );
+                }
+            }
+
+            if check_vars {
+                let exprs: Vec<&Expression> = task.exprs.iter().collect();
+                for name in undefined_vars(&exprs, &defined, &task.local_vars) {
+                    report(&mut issues, format!("uses undefined variable '{}'", name));
+                }
+            }
+        }
+
+        if check_vars {
+            for (handler_file, handler) in &self.handlers {
+                let exprs = module_expressions(&handler.module);
+                for name in undefined_vars(&exprs, &defined, &HashSet::new()) {
+                    issues.push(ValidationIssue {
+                        file: handler_file.clone(),
+                        line: line_of(&handler.location),
+                        task: Some(handler.name.clone()),
+                        message: format!("uses undefined variable '{}'", name),
+                    });
+                }
+            }
+        }
+        self.issues.extend(issues);
+    }
+
+    /// Gather the tasks of `items`, following blocks, imports and static includes
+    fn collect(&mut self, items: &[TaskOrBlock], file: &str, local_vars: &HashSet<String>) {
+        for item in items {
+            match item {
+                TaskOrBlock::Task(task) => self.push_task(task, None, file, local_vars),
+                TaskOrBlock::Block(block) => {
+                    let mut vars = local_vars.clone();
+                    vars.extend(block.vars.keys().cloned());
+                    for task in block.block.iter().chain(&block.rescue).chain(&block.always) {
+                        self.push_task(task, block.when.as_ref(), file, &vars);
+                    }
+                }
+                TaskOrBlock::Import(import) => {
+                    let line = line_of(&import.location);
+                    let mut vars = local_vars.clone();
+                    vars.extend(import.vars.keys().cloned());
+                    // The parser already resolved the path against the playbook
+                    self.collect_file(PathBuf::from(&import.file), file, line, &vars);
+                }
+                TaskOrBlock::Include(include) => {
+                    let line = line_of(&include.location);
+                    let mut loop_vars = local_vars.clone();
+                    if include.loop_expr.is_some() {
+                        loop_vars.insert(include.loop_var.clone());
+                    }
+
+                    // The include's own expressions are checked like a task's
+                    let mut exprs = vec![include.file.clone()];
+                    exprs.extend(include.when.iter().chain(&include.loop_expr).cloned());
+                    exprs.extend(include.vars.values().cloned());
+                    self.tasks.push(CheckedTask {
+                        file: file.to_string(),
+                        line,
+                        name: "include_tasks".to_string(),
+                        notify: Vec::new(),
+                        exprs,
+                        local_vars: loop_vars.clone(),
+                        sets_vars: Vec::new(),
+                        gathers_facts: false,
+                    });
+
+                    let mut vars = loop_vars;
+                    vars.extend(include.vars.keys().cloned());
+
+                    // A file named by an expression is only known at runtime
+                    if let Expression::String(path) = &include.file {
+                        let resolved = if Path::new(path).is_absolute() {
+                            PathBuf::from(path)
+                        } else {
+                            self.playbook_dir.join(path)
+                        };
+                        self.collect_file(resolved, file, line, &vars);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gather the tasks of a task file that `from` imports or includes
+    fn collect_file(
+        &mut self,
+        resolved: PathBuf,
+        from: &str,
+        line: Option<usize>,
+        vars: &HashSet<String>,
+    ) {
+        if !resolved.exists() {
+            self.issue(
+                from,
+                line,
+                format!("task file not found: {}", resolved.display()),
+            );
+            return;
+        }
+        if self.import_stack.contains(&resolved) {
+            self.issue(
+                from,
+                line,
+                format!("{} includes itself", resolved.display()),
+            );
+            return;
+        }
+
+        match parse_task_file(&resolved) {
+            Ok(tasks) => {
+                self.import_stack.push(resolved.clone());
+                self.collect(&tasks, &resolved.display().to_string(), vars);
+                self.import_stack.pop();
+            }
+            Err(e) => self.issue(
+                from,
+                line,
+                format!("cannot load {}: {}", resolved.display(), error_summary(&e)),
+            ),
+        }
+    }
+
+    /// Queue `task` for checking; `block_when` is the condition of its enclosing block
+    fn push_task(
+        &mut self,
+        task: &Task,
+        block_when: Option<&Expression>,
+        file: &str,
+        local_vars: &HashSet<String>,
+    ) {
+        let mut local_vars = local_vars.clone();
+        if task.loop_expr.is_some() {
+            local_vars.insert(task.loop_var.clone());
+        }
+        local_vars.extend(task.vars.keys().cloned());
+
+        let mut sets_vars: Vec<String> = task.register.iter().cloned().collect();
+        if let ModuleCall::SetFact { facts, .. } = &task.module {
+            sets_vars.extend(facts.iter().map(|(name, _)| name.clone()));
+        }
+
+        let mut exprs: Vec<Expression> = block_when.into_iter().cloned().collect();
+        exprs.extend(task_expressions(task).into_iter().cloned());
+        exprs.extend(task.vars.values().cloned());
+        self.tasks.push(CheckedTask {
+            file: file.to_string(),
+            line: line_of(&task.location),
+            name: task.name.clone(),
+            notify: task.notify.clone(),
+            exprs,
+            local_vars,
+            sets_vars,
+            gathers_facts: matches!(task.module, ModuleCall::Facts { .. }),
+        });
+    }
+
+    fn issue(&mut self, file: &str, line: Option<usize>, message: String) {
+        self.issues.push(ValidationIssue {
+            file: file.to_string(),
+            line,
+            task: None,
+            message,
+        });
+    }
+}
+
+/// One line for a load error, where its Display form spans several with hints
+fn error_summary(error: &NexusError) -> String {
+    match error {
+        NexusError::Parse(e) => e.message.clone(),
+        NexusError::Io {
+            message,
+            path: Some(path),
+        } => format!("{}: {}", message, path.display()),
+        NexusError::Io { message, .. }
+        | NexusError::Inventory { message, .. }
+        | NexusError::Runtime { message, .. } => message.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Line of a location, where 0 means the parser didn't know it
+fn line_of(location: &Option<SourceLocation>) -> Option<usize> {
+    location.as_ref().map(|l| l.line).filter(|&line| line > 0)
+}
+
+fn is_vault_file(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| content.trim_start().starts_with("$ANSIBLE_VAULT"))
+        .unwrap_or(false)
+}
+
+/// Names used by `exprs` that are neither `defined` nor `visible`, sorted and deduplicated
+fn undefined_vars(
+    exprs: &[&Expression],
+    defined: &HashSet<String>,
+    visible: &HashSet<String>,
+) -> Vec<String> {
+    let mut used = Vec::new();
+    for expr in exprs {
+        collect_vars(expr, &mut Vec::new(), &mut used);
+    }
+    used.sort();
+    used.dedup();
+    used.retain(|name| {
+        !defined.contains(name) && !visible.contains(name) && !name.starts_with("ansible_")
+    });
+    used
+}
+
+/// Root names of the variables `expr` reads. `bound` holds lambda parameters in scope.
+/// The input of a `default` filter may be missing on purpose, so it isn't counted.
+fn collect_vars(expr: &Expression, bound: &mut Vec<String>, used: &mut Vec<String>) {
+    let mut walk = |e: &Expression, bound: &mut Vec<String>| collect_vars(e, bound, used);
+    match expr {
+        Expression::String(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::Null => {}
+        Expression::Variable(path) => {
+            let root = match path.first().map(String::as_str) {
+                Some("vars") if path.len() > 1 => &path[1],
+                Some(_) => &path[0],
+                None => return,
+            };
+            if !bound.contains(root) {
+                used.push(root.clone());
+            }
+        }
+        Expression::InterpolatedString(parts) => {
+            for part in parts {
+                if let StringPart::Expression(e) = part {
+                    walk(e, bound);
+                }
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            walk(left, bound);
+            walk(right, bound);
+        }
+        Expression::UnaryOp { operand, .. } => walk(operand, bound),
+        Expression::FunctionCall { args, kwargs, .. } => {
+            for e in args.iter().chain(kwargs.values()) {
+                walk(e, bound);
+            }
+        }
+        Expression::MethodCall {
+            object,
+            args,
+            kwargs,
+            ..
+        } => {
+            walk(object, bound);
+            for e in args.iter().chain(kwargs.values()) {
+                walk(e, bound);
+            }
+        }
+        Expression::Index { object, index } => {
+            walk(object, bound);
+            walk(index, bound);
+        }
+        Expression::Attribute { object, .. } => walk(object, bound),
+        Expression::List(items) => {
+            for e in items {
+                walk(e, bound);
+            }
+        }
+        Expression::Dict(items) => {
+            for (k, v) in items {
+                walk(k, bound);
+                walk(v, bound);
+            }
+        }
+        Expression::Filter {
+            input,
+            filter_name,
+            args,
+            kwargs,
+        } => {
+            if filter_name != "default" {
+                walk(input, bound);
+            }
+            for e in args.iter().chain(kwargs.values()) {
+                walk(e, bound);
+            }
+        }
+        Expression::Lambda { params, body } => {
+            let depth = bound.len();
+            bound.extend(params.iter().cloned());
+            walk(body, bound);
+            bound.truncate(depth);
+        }
+        Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            walk(condition, bound);
+            walk(then_expr, bound);
+            walk(else_expr, bound);
+        }
+    }
+}
+
+/// Every expression a task evaluates: its keywords and its module's parameters
+fn task_expressions(task: &Task) -> Vec<&Expression> {
+    let mut exprs: Vec<&Expression> = [
+        &task.when,
+        &task.fail_when,
+        &task.changed_when,
+        &task.loop_expr,
+        &task.delegate_to,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    exprs.extend(module_expressions(&task.module));
+    exprs
+}
+
+fn module_expressions(module: &ModuleCall) -> Vec<&Expression> {
+    let mut exprs: Vec<&Expression> = Vec::new();
+    match module {
+        ModuleCall::Package { name, .. } | ModuleCall::AuthorizedKey { key: name, .. } => {
+            exprs.extend(name)
+        }
+        ModuleCall::Service { name, .. }
+        | ModuleCall::Slurp { src: name }
+        | ModuleCall::Stat { path: name, .. }
+        | ModuleCall::Hostname { name }
+        | ModuleCall::Timezone { name }
+        | ModuleCall::AsyncStatus { job_id: name } => exprs.push(name),
+        ModuleCall::File {
+            path,
+            source,
+            content,
+            owner,
+            group,
+            mode,
+            creates,
+            removes,
+            ..
+        } => {
+            exprs.push(path);
+            exprs.extend(
+                [source, content, owner, group, mode, creates, removes]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        ModuleCall::Command {
+            cmd,
+            creates,
+            removes,
+            environment,
+            stdin,
+            ..
+        } => {
+            exprs.push(cmd);
+            exprs.extend([creates, removes, stdin].into_iter().flatten());
+            exprs.extend(environment.values());
+        }
+        ModuleCall::Shell {
+            command,
+            chdir,
+            creates,
+            removes,
+            environment,
+            stdin,
+            ..
+        } => {
+            exprs.push(command);
+            exprs.extend([chdir, creates, removes, stdin].into_iter().flatten());
+            exprs.extend(environment.values());
+        }
+        ModuleCall::User {
+            name,
+            uid,
+            gid,
+            groups,
+            shell,
+            home,
+            password,
+            password_expire_max,
+            password_expire_min,
+            expires,
+            ssh_key_type,
+            ssh_key_file,
+            ..
+        } => {
+            exprs.push(name);
+            exprs.extend(
+                [
+                    uid,
+                    gid,
+                    shell,
+                    home,
+                    password,
+                    password_expire_max,
+                    password_expire_min,
+                    expires,
+                    ssh_key_type,
+                    ssh_key_file,
+                ]
+                .into_iter()
+                .flatten(),
+            );
+            exprs.extend(groups);
+        }
+        ModuleCall::RunFunction { args, .. } => exprs.extend(args),
+        ModuleCall::Template {
+            src,
+            dest,
+            owner,
+            group,
+            mode,
+            validate,
+            creates,
+            removes,
+        } => {
+            exprs.extend([src, dest]);
+            exprs.extend(
+                [owner, group, mode, validate, creates, removes]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        ModuleCall::Facts { .. } | ModuleCall::Meta { .. } => {}
+        ModuleCall::WaitFor {
+            host, port, path, ..
+        } => exprs.extend([host, port, path].into_iter().flatten()),
+        ModuleCall::GetUrl {
+            url,
+            dest,
+            checksum,
+            owner,
+            group,
+            mode,
+        } => {
+            exprs.extend([url, dest]);
+            exprs.extend([checksum, owner, group, mode].into_iter().flatten());
+        }
+        ModuleCall::Systemd { name, .. } => exprs.extend(name),
+        ModuleCall::Pip {
+            name,
+            version,
+            virtualenv,
+            virtualenv_command,
+            requirements,
+            executable,
+            ..
+        } => {
+            exprs.extend(name);
+            exprs.extend(
+                [
+                    version,
+                    virtualenv,
+                    virtualenv_command,
+                    requirements,
+                    executable,
+                ]
+                .into_iter()
+                .flatten(),
+            );
+        }
+        ModuleCall::Fetch { src, dest, .. } => exprs.extend([src, dest]),
+        ModuleCall::Synchronize {
+            src,
+            dest,
+            rsync_opts,
+            ..
+        } => {
+            exprs.extend([src, dest]);
+            exprs.extend(rsync_opts);
+        }
+        ModuleCall::Mount {
+            path,
+            src,
+            fstype,
+            opts,
+            dump,
+            passno,
+            fstab,
+            ..
+        } => {
+            exprs.push(path);
+            exprs.extend(
+                [src, fstype, opts, dump, passno, fstab]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        ModuleCall::Reboot { test_command, .. } => exprs.extend(test_command),
+        ModuleCall::BlockInFile {
+            path,
+            block,
+            marker,
+            insertafter,
+            insertbefore,
+            ..
+        } => {
+            exprs.push(path);
+            exprs.extend(
+                [block, marker, insertafter, insertbefore]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        ModuleCall::Debug { msg, var, .. } => exprs.extend([msg, var].into_iter().flatten()),
+        ModuleCall::Assert {
+            that,
+            fail_msg,
+            success_msg,
+            ..
+        } => {
+            exprs.extend(that.iter().map(|a| &a.condition));
+            exprs.extend([fail_msg, success_msg].into_iter().flatten());
+        }
+        ModuleCall::SetFact { facts, .. } => exprs.extend(facts.iter().map(|(_, e)| e)),
+        ModuleCall::GroupBy { key } => exprs.push(key),
+        ModuleCall::Custom { args, .. } => exprs.push(args),
+    }
+    exprs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_playbook;
+
+    #[test]
+    fn test_validate_strict_reports_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("extra.yml"),
+            "- name: From file\n  command: echo ${app_dir} ${missing_in_file}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("vars.yml"), "release: 3\n").unwrap();
+
+        let playbook_path = dir.path().join("site.yml");
+        let yaml = r#"
+hosts: all
+vars:
+  app_dir: /opt/app
+vars_files:
+  - vars.yml
+tasks:
+  - name: Uses defined vars
+    command: echo ${app_dir} ${release} ${inventory_hostname} ${result.stdout}
+    register: result
+    notify: restart app
+  - name: Loops
+    command: echo ${item} ${nope | default('x')} ${names | map(n => n.upper())}
+    loop: ${[1, 2]}
+  - name: Broken
+    command: echo ${undefined_thing}
+    when: other_missing > 1
+    notify: no such handler
+  - import_tasks: extra.yml
+  - include_tasks: missing.yml
+handlers:
+  - name: Restart
+    listen: restart app
+    command: echo restarting
+"#;
+        let playbook = parse_playbook(yaml, playbook_path.display().to_string()).unwrap();
+
+        let mut known = HashSet::new();
+        known.insert("names".to_string());
+        let messages: Vec<String> = validate_strict(&playbook, &known)
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+
+        let site = playbook_path.display().to_string();
+        let extra = dir.path().join("extra.yml").display().to_string();
+        let missing = dir.path().join("missing.yml").display().to_string();
+        assert_eq!(
+            messages,
+            vec![
+                format!("{}:20: task file not found: {}", site, missing),
+                format!(
+                    "{}:15: task 'Broken': notifies undefined handler 'no such handler'",
+                    site
+                ),
+                format!(
+                    "{}:15: task 'Broken': uses undefined variable 'other_missing'",
+                    site
+                ),
+                format!(
+                    "{}:15: task 'Broken': uses undefined variable 'undefined_thing'",
+                    site
+                ),
+                format!(
+                    "{}:1: task 'From file': uses undefined variable 'missing_in_file'",
+                    extra
+                ),
+            ]
+        );
+    }
+}
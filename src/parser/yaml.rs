@@ -1,21 +1,31 @@
 // YAML playbook parser
 
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use super::ast::*;
 use super::expressions::{has_interpolation, parse_expression, parse_interpolated_string};
 use super::functions::parse_functions_block;
+use super::lines::{locate_tasks, matching, ItemLines, PlaybookLines};
+use crate::modules::CustomModule;
 use crate::output::errors::{NexusError, ParseError, ParseErrorKind};
+use crate::vault::VaultSecrets;
 
 /// Raw YAML playbook structure (before AST conversion)
 #[derive(Debug, Deserialize)]
 struct RawPlaybook {
+    name: Option<String>,
     hosts: Option<RawHostsValue>,
     vars: Option<HashMap<String, YamlValue>>,
+    /// External variable files, relative to the playbook
+    vars_files: Option<Vec<String>>,
+    /// Variables to prompt for before the play runs
+    vars_prompt: Option<Vec<RawVarsPrompt>>,
     tasks: Option<Vec<RawTask>>,
     handlers: Option<Vec<RawHandler>>,
     functions: Option<String>,
@@ -31,6 +41,8 @@ struct RawPlaybook {
     post_tasks: Option<Vec<RawTask>>,
     /// Auto-gather facts at play start
     gather_facts: Option<bool>,
+    /// Fact categories to gather (list or comma-separated string)
+    gather_subset: Option<TagsValue>,
     /// Connection type (local, ssh, etc.)
     connection: Option<String>,
     /// Serial execution configuration
@@ -39,6 +51,17 @@ struct RawPlaybook {
     throttle: Option<usize>,
     /// Execution strategy
     strategy: Option<String>,
+    /// Environment variables for command and shell tasks
+    environment: Option<YamlValue>,
+}
+
+/// Raw vars_prompt entry
+#[derive(Debug, Deserialize)]
+struct RawVarsPrompt {
+    name: String,
+    prompt: Option<String>,
+    private: Option<bool>,
+    default: Option<YamlValue>,
 }
 
 /// Hosts value can be either a string pattern or a list of inline hosts
@@ -75,8 +98,16 @@ enum RawSerial {
     Count(usize),
     /// Percentage string (e.g., serial: "25%")
     Percentage(String),
-    /// Progressive batches (e.g., serial: [1, 5, 10])
-    List(Vec<usize>),
+    /// Progressive batches (e.g., serial: [1, 5, "50%"])
+    List(Vec<RawSerialBatch>),
+}
+
+/// One entry of a progressive serial list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawSerialBatch {
+    Count(usize),
+    Percentage(String),
 }
 
 /// Role reference - can be a simple string or object with vars
@@ -102,11 +133,17 @@ struct RawTask {
     #[serde(rename = "when")]
     when_condition: Option<String>,
     register: Option<String>,
-    fail_when: Option<String>,
-    changed_when: Option<String>,
+    #[serde(alias = "failed_when")]
+    fail_when: Option<ConditionValue>,
+    changed_when: Option<ConditionValue>,
     notify: Option<NotifyValue>,
+    /// Notify handlers even when the task reports no change
+    always_notify: Option<bool>,
     #[serde(rename = "loop")]
     loop_expr: Option<String>,
+    /// Lists to iterate over in every combination (cartesian product)
+    #[serde(alias = "with_nested")]
+    loop_nested: Option<YamlValue>,
     loop_var: Option<String>,
     /// Override sudo for this task
     sudo: Option<bool>,
@@ -134,6 +171,10 @@ struct RawTask {
     delegate_to: Option<String>,
     /// Store facts from delegate (default: false)
     delegate_facts: Option<bool>,
+    /// Hide output (stdout, stderr, diff, messages) from reporting
+    no_log: Option<bool>,
+    /// Run in check mode (true) or for real (false) regardless of `--check`
+    check_mode: Option<bool>,
     /// Block tasks (main execution) - if present, this is a block
     block: Option<Vec<RawTask>>,
     /// Rescue tasks (error handling)
@@ -223,58 +264,503 @@ enum NotifyValue {
     Multiple(Vec<String>),
 }
 
+/// A task condition written as an expression, a plain YAML boolean, or a list of either
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ConditionValue {
+    Bool(bool),
+    Single(String),
+    Multiple(Vec<ConditionValue>),
+}
+
+/// Parse a `changed_when`/`fail_when` value into an expression. The conditions of
+/// a list are joined with `op`: `fail_when` fails when any holds (`Or`), and
+/// `changed_when` reports a change only when all do (`And`). An empty list is no
+/// condition at all, so the module's own status stands.
+pub(crate) fn convert_condition(
+    value: ConditionValue,
+    op: BinaryOperator,
+) -> Result<Option<Expression>, NexusError> {
+    match value {
+        ConditionValue::Bool(b) => Ok(Some(Expression::Boolean(b))),
+        ConditionValue::Single(s) => parse_condition(&s).map(Some),
+        ConditionValue::Multiple(items) => {
+            let mut combined: Option<Expression> = None;
+            for item in items {
+                let Some(next) = convert_condition(item, op)? else {
+                    continue;
+                };
+                combined = Some(match combined {
+                    Some(acc) => Expression::BinaryOp {
+                        left: Box::new(acc),
+                        op,
+                        right: Box::new(next),
+                    },
+                    None => next,
+                });
+            }
+            Ok(combined)
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct RawHandler {
     name: Option<String>,
+    /// Notification topics this handler also responds to
+    listen: Option<NotifyValue>,
     #[serde(flatten)]
     module: HashMap<String, YamlValue>,
 }
 
-/// Parse a playbook from a file
-pub fn parse_playbook_file(path: &Path) -> Result<Playbook, NexusError> {
+/// Parse a playbook file into its plays
+pub fn parse_playbook_file(path: &Path) -> Result<Vec<Playbook>, NexusError> {
     parse_playbook_file_with_vault(path, None)
 }
 
-/// Parse a playbook from a file with optional vault password
+/// Parse a playbook file into its plays, with optional vault passwords.
+///
+/// The file is either a single play (a mapping) or a list whose entries are plays
+/// or `import_playbook: other.yml`, which splices in that file's plays (relative to
+/// the importing file).
 pub fn parse_playbook_file_with_vault(
     path: &Path,
-    vault_password: Option<&str>,
-) -> Result<Playbook, NexusError> {
-    let content = std::fs::read_to_string(path).map_err(|e| NexusError::Io {
-        message: format!("Failed to read playbook file: {}", e),
-        path: Some(path.to_path_buf()),
-    })?;
+    vault_secrets: Option<&VaultSecrets>,
+) -> Result<Vec<Playbook>, NexusError> {
+    let plays = PlayLoader::new(vault_secrets, false).load(path)?;
+    if plays.is_empty() {
+        return Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "Playbook has no plays".to_string(),
+            file: Some(path.to_string_lossy().to_string()),
+            line: None,
+            column: None,
+            suggestion: Some("Add a play with hosts and tasks".to_string()),
+        })));
+    }
+    Ok(plays)
+}
+
+/// Load the plays of a playbook file and the playbooks it imports
+pub(crate) struct PlayLoader<'a> {
+    vault_secrets: Option<&'a VaultSecrets>,
+    /// Files whose `import_playbook` led to the one being loaded, to catch cycles
+    importing: Vec<PathBuf>,
+    /// Every playbook file read, in order
+    pub(crate) files: Vec<PathBuf>,
+    /// With `keep_going`, the plays and imports that failed to load; the rest are
+    /// still loaded
+    pub(crate) errors: Vec<NexusError>,
+    keep_going: bool,
+}
+
+impl<'a> PlayLoader<'a> {
+    pub(crate) fn new(vault_secrets: Option<&'a VaultSecrets>, keep_going: bool) -> Self {
+        PlayLoader {
+            vault_secrets,
+            importing: Vec::new(),
+            files: Vec::new(),
+            errors: Vec::new(),
+            keep_going,
+        }
+    }
+
+    pub(crate) fn load(&mut self, path: &Path) -> Result<Vec<Playbook>, NexusError> {
+        self.files.push(path.to_path_buf());
+        let vault_secrets = self.vault_secrets;
+        let content = std::fs::read_to_string(path).map_err(|e| NexusError::Io {
+            message: format!("Failed to read playbook file: {}", e),
+            path: Some(path.to_path_buf()),
+        })?;
+
+        let content = decrypt_if_vault(content, path, vault_secrets, "Playbook")?;
+        let source_file = path.to_string_lossy().to_string();
+
+        let entries = match serde_yaml::from_str::<YamlValue>(&content) {
+            Ok(YamlValue::Sequence(entries)) => entries,
+            // A single play, or invalid YAML that parsing it reports with its location
+            _ => {
+                return Ok(vec![parse_playbook_with_vault(
+                    &content,
+                    source_file,
+                    vault_secrets,
+                )?])
+            }
+        };
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(start) = self.importing.iter().position(|p| *p == canonical) {
+            let mut cycle: Vec<String> = self.importing[start..]
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            cycle.push(canonical.display().to_string());
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("import_playbook cycle: {}", cycle.join(" -> ")),
+                file: Some(source_file),
+                line: None,
+                column: None,
+                suggestion: Some(
+                    "A playbook can't import itself, directly or indirectly".to_string(),
+                ),
+            })));
+        }
+
+        let entry_lines = PlaybookLines::scan_plays(&content);
+        let entry_lines = if entry_lines.len() == entries.len() {
+            entry_lines
+        } else {
+            vec![Default::default(); entries.len()]
+        };
+
+        self.importing.push(canonical);
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let mut plays = Vec::new();
+        for (entry, (entry_line, lines)) in entries.into_iter().zip(entry_lines) {
+            let location = (entry_line.line > 0).then(|| entry_line.location(&source_file));
+            let loaded = match entry.get("import_playbook") {
+                Some(import) => match import.as_str() {
+                    Some(file) => self.load(&base_dir.join(file)),
+                    None => Err(NexusError::Parse(Box::new(ParseError {
+                        kind: ParseErrorKind::InvalidValue,
+                        message: "import_playbook must be a file name".to_string(),
+                        file: Some(source_file.clone()),
+                        line: None,
+                        column: None,
+                        suggestion: Some("e.g. import_playbook: webservers.yml".to_string()),
+                    }))),
+                },
+                None => self
+                    .convert_play(entry, &source_file, &lines)
+                    .map(|play| vec![play]),
+            };
+
+            match loaded.map_err(|e| e.with_location(location.as_ref())) {
+                Ok(loaded) => plays.extend(loaded),
+                Err(e) if self.keep_going => self.errors.push(e),
+                Err(e) => {
+                    self.importing.pop();
+                    return Err(e);
+                }
+            }
+        }
+
+        self.importing.pop();
+        Ok(plays)
+    }
+
+    /// Convert one entry of a list of plays
+    fn convert_play(
+        &self,
+        entry: YamlValue,
+        source_file: &str,
+        lines: &PlaybookLines,
+    ) -> Result<Playbook, NexusError> {
+        let value = decrypt_inline_vault(entry, self.vault_secrets, source_file)?;
+        let raw: RawPlaybook = serde_yaml::from_value(value).map_err(|e| {
+            NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidYaml,
+                message: format!("Invalid play: {}", e),
+                file: Some(source_file.to_string()),
+                line: None,
+                column: None,
+                suggestion: Some(
+                    "Each entry of a list of plays is a play (hosts, tasks, ...) or an \
+                     import_playbook"
+                        .to_string(),
+                ),
+            }))
+        })?;
+        convert_playbook(raw, source_file.to_string(), lines)
+    }
+}
+
+/// Decrypt file content if it is vault-encrypted, otherwise return it unchanged
+fn decrypt_if_vault(
+    content: String,
+    path: &Path,
+    vault_secrets: Option<&VaultSecrets>,
+    kind: &str,
+) -> Result<String, NexusError> {
+    if !crate::vault::is_vault_string(&content) {
+        return Ok(content);
+    }
 
-    // Check if the file is vault-encrypted
-    let content = if crate::vault::is_vault_string(&content) {
-        let password = vault_password.ok_or_else(|| NexusError::Runtime {
+    let secrets = vault_secrets
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| NexusError::Runtime {
             function: None,
             message: format!(
-                "Playbook file {} is encrypted but no vault password provided",
+                "{} file {} is encrypted but no vault password provided",
+                kind,
                 path.display()
             ),
             suggestion: Some(
                 "Use --vault-password, --vault-password-file, or --ask-vault-pass".to_string(),
             ),
+            location: None,
         })?;
 
-        crate::vault::format::VaultFile::parse(&content)
-            .and_then(|vault| vault.decrypt(password))
-            .map_err(|e| NexusError::Runtime {
-                function: None,
-                message: format!("Failed to decrypt playbook: {}", e),
-                suggestion: Some("Check that the vault password is correct".to_string()),
-            })?
-    } else {
-        content
+    secrets
+        .decrypt_str(&content)
+        .map_err(|e| NexusError::Runtime {
+            function: None,
+            message: format!("Failed to decrypt {}: {}", kind.to_lowercase(), e),
+            suggestion: Some("Check that the vault password is correct".to_string()),
+            location: None,
+        })
+}
+
+/// Replace inline `!vault` scalars with their decrypted text
+fn decrypt_inline_vault(
+    value: YamlValue,
+    vault_secrets: Option<&VaultSecrets>,
+    source: &str,
+) -> Result<YamlValue, NexusError> {
+    match value {
+        YamlValue::Tagged(tagged) if tagged.tag == "vault" => {
+            let YamlValue::String(ciphertext) = tagged.value else {
+                return Err(NexusError::Parse(Box::new(ParseError {
+                    kind: ParseErrorKind::InvalidValue,
+                    message: "!vault must tag an encrypted string".to_string(),
+                    file: Some(source.to_string()),
+                    line: None,
+                    column: None,
+                    suggestion: Some(
+                        "Generate the value with: nexus vault encrypt_string --name <var> '<secret>'"
+                            .to_string(),
+                    ),
+                })));
+            };
+            let secrets =
+                vault_secrets
+                    .filter(|s| !s.is_empty())
+                    .ok_or_else(|| NexusError::Runtime {
+                        function: None,
+                        message: format!(
+                            "{} contains !vault values but no vault password provided",
+                            source
+                        ),
+                        suggestion: Some(
+                            "Use --vault-password, --vault-password-file, or --ask-vault-pass"
+                                .to_string(),
+                        ),
+                        location: None,
+                    })?;
+            secrets
+                .decrypt_str(&ciphertext)
+                .map(YamlValue::String)
+                .map_err(|e| NexusError::Runtime {
+                    function: None,
+                    message: format!("Failed to decrypt !vault value in {}: {}", source, e),
+                    suggestion: Some("Check that the vault password is correct".to_string()),
+                    location: None,
+                })
+        }
+        YamlValue::Tagged(mut tagged) => {
+            tagged.value = decrypt_inline_vault(tagged.value, vault_secrets, source)?;
+            Ok(YamlValue::Tagged(tagged))
+        }
+        YamlValue::Sequence(seq) => seq
+            .into_iter()
+            .map(|v| decrypt_inline_vault(v, vault_secrets, source))
+            .collect::<Result<_, _>>()
+            .map(YamlValue::Sequence),
+        YamlValue::Mapping(map) => map
+            .into_iter()
+            .map(|(k, v)| Ok((k, decrypt_inline_vault(v, vault_secrets, source)?)))
+            .collect::<Result<_, NexusError>>()
+            .map(YamlValue::Mapping),
+        other => Ok(other),
+    }
+}
+
+/// A `!vault` value outside a playbook or vars file would otherwise be used as ciphertext
+fn undecrypted_vault_error() -> NexusError {
+    NexusError::Parse(Box::new(ParseError {
+        kind: ParseErrorKind::InvalidValue,
+        message: "Inline !vault values are only supported in playbooks and vars files".to_string(),
+        file: None,
+        line: None,
+        column: None,
+        suggestion: Some("Move the secret into play vars or a vars_files entry".to_string()),
+    }))
+}
+
+/// Load a `vars_files` entry: a YAML mapping of variables, optionally vault-encrypted
+pub fn load_vars_file(
+    path: &Path,
+    vault_secrets: Option<&VaultSecrets>,
+) -> Result<HashMap<String, Value>, NexusError> {
+    let content = std::fs::read_to_string(path).map_err(|e| NexusError::Io {
+        message: format!("Failed to read vars file: {}", e),
+        path: Some(path.to_path_buf()),
+    })?;
+
+    let content = decrypt_if_vault(content, path, vault_secrets, "Vars")?;
+
+    let raw: Option<HashMap<String, YamlValue>> = serde_yaml::from_str(&content).map_err(|e| {
+        let (line, column) = extract_yaml_error_location(&e);
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidYaml,
+            message: format!("Invalid vars file YAML: {}", e),
+            file: Some(path.to_string_lossy().to_string()),
+            line,
+            column,
+            suggestion: Some(
+                "A vars file must be a mapping of variable names to values".to_string(),
+            ),
+        }))
+    })?;
+
+    let source = path.display().to_string();
+    let raw = raw
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| Ok((k, decrypt_inline_vault(v, vault_secrets, &source)?)))
+        .collect::<Result<_, NexusError>>()?;
+
+    convert_vars(raw)
+}
+
+/// Parse `-e`/`--extra-vars` arguments into variables.
+///
+/// Each argument is one of:
+/// - `key=value` pairs, whitespace-separated (`-e "env=prod region=eu"`)
+/// - `@path/to/vars.yml` to load a vars file (may be vault-encrypted)
+/// - an inline JSON or YAML object (`-e '{"replicas": 3}'`)
+///
+/// When the same key appears more than once, the last one wins.
+pub fn parse_extra_vars(
+    args: &[String],
+    vault_secrets: Option<&VaultSecrets>,
+) -> Result<HashMap<String, Value>, NexusError> {
+    let invalid = |arg: &str, message: String| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message: format!("Invalid extra vars '{}': {}", arg, message),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(
+                "Use -e key=value, -e @vars.yml, or -e '{\"key\": \"value\"}'".to_string(),
+            ),
+        }))
     };
 
-    parse_playbook(&content, path.to_string_lossy().to_string())
+    let mut vars = HashMap::new();
+    for arg in args {
+        let trimmed = arg.trim();
+        if let Some(file) = trimmed.strip_prefix('@') {
+            vars.extend(load_vars_file(Path::new(file), vault_secrets)?);
+        } else if trimmed.starts_with('{') {
+            let raw: YamlValue =
+                serde_yaml::from_str(trimmed).map_err(|e| invalid(arg, e.to_string()))?;
+            let raw: HashMap<String, YamlValue> =
+                serde_yaml::from_value(decrypt_inline_vault(raw, vault_secrets, "--extra-vars")?)
+                    .map_err(|e| invalid(arg, e.to_string()))?;
+            vars.extend(convert_vars(raw)?);
+        } else {
+            for (key, value) in split_extra_var_pairs(trimmed).map_err(|e| invalid(arg, e))? {
+                vars.insert(key, Value::String(value));
+            }
+        }
+    }
+    Ok(vars)
+}
+
+/// Split `key=value` extra vars into pairs. Words are split like a shell does, so
+/// `b='hello world'` keeps its space, and a word without `=` continues the value
+/// before it, so `-e "msg=hello world"` is one pair.
+fn split_extra_var_pairs(arg: &str) -> Result<Vec<(String, String)>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for word in words {
+        match word.split_once('=').filter(|(key, _)| !key.is_empty()) {
+            Some((key, value)) => pairs.push((key.to_string(), value.to_string())),
+            None => match pairs.last_mut() {
+                Some((_, value)) => {
+                    value.push(' ');
+                    value.push_str(&word);
+                }
+                None => return Err(format!("expected key=value, got '{}'", word)),
+            },
+        }
+    }
+    Ok(pairs)
 }
 
 /// Parse a playbook from a string
 pub fn parse_playbook(content: &str, source_file: String) -> Result<Playbook, NexusError> {
-    let raw: RawPlaybook = serde_yaml::from_str(content).map_err(|e| {
+    parse_playbook_with_vault(content, source_file, None)
+}
+
+/// Parse a playbook from a string, decrypting inline `!vault` values with `vault_secrets`
+fn parse_playbook_with_vault(
+    content: &str,
+    source_file: String,
+    vault_secrets: Option<&VaultSecrets>,
+) -> Result<Playbook, NexusError> {
+    let lines = PlaybookLines::scan(content);
+    parse_playbook_with_lines(content, source_file, vault_secrets, &lines)
+}
+
+/// Parse a playbook, taking the line numbers of its tasks from `lines` (which
+/// can come from another file when the playbook was generated from it)
+pub(crate) fn parse_playbook_with_lines(
+    content: &str,
+    source_file: String,
+    vault_secrets: Option<&VaultSecrets>,
+    lines: &PlaybookLines,
+) -> Result<Playbook, NexusError> {
+    let invalid_yaml = |e: serde_yaml::Error| {
         let (line, column) = extract_yaml_error_location(&e);
         NexusError::Parse(Box::new(ParseError {
             kind: ParseErrorKind::InvalidYaml,
@@ -286,9 +772,18 @@ pub fn parse_playbook(content: &str, source_file: String) -> Result<Playbook, Ne
                 "Check YAML syntax - ensure proper indentation and valid YAML".to_string(),
             ),
         }))
-    })?;
+    };
 
-    convert_playbook(raw, source_file)
+    // Only take the slower untyped route (which loses error locations) when needed
+    let raw: RawPlaybook = if content.contains("!vault") {
+        let value: YamlValue = serde_yaml::from_str(content).map_err(invalid_yaml)?;
+        let value = decrypt_inline_vault(value, vault_secrets, &source_file)?;
+        serde_yaml::from_value(value).map_err(invalid_yaml)?
+    } else {
+        serde_yaml::from_str(content).map_err(invalid_yaml)?
+    };
+
+    convert_playbook(raw, source_file, lines)
 }
 
 pub(crate) fn extract_yaml_error_location(e: &serde_yaml::Error) -> (Option<usize>, Option<usize>) {
@@ -312,7 +807,11 @@ fn convert_inline_host(raw: RawInlineHost) -> Result<InlineHost, NexusError> {
     })
 }
 
-fn convert_playbook(raw: RawPlaybook, source_file: String) -> Result<Playbook, NexusError> {
+fn convert_playbook(
+    raw: RawPlaybook,
+    source_file: String,
+    lines: &PlaybookLines,
+) -> Result<Playbook, NexusError> {
     let hosts = match raw.hosts {
         Some(RawHostsValue::Pattern(h)) if h == "all" => HostPattern::All,
         Some(RawHostsValue::Pattern(h)) if h == "localhost" => HostPattern::Localhost,
@@ -333,26 +832,37 @@ fn convert_playbook(raw: RawPlaybook, source_file: String) -> Result<Playbook, N
 
     let vars = raw.vars.map(convert_vars).transpose()?.unwrap_or_default();
 
-    let tasks = raw
-        .tasks
-        .map(|tasks| {
-            tasks
-                .into_iter()
-                .enumerate()
-                .map(|(i, t)| convert_task_or_block(t, &source_file, i))
-                .collect::<Result<Vec<_>, _>>()
+    let vars_prompt = raw
+        .vars_prompt
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| {
+            Ok(VarsPrompt {
+                prompt: p.prompt.unwrap_or_else(|| p.name.clone()),
+                name: p.name,
+                private: p.private.unwrap_or(true),
+                default: p.default.map(yaml_to_value).transpose()?,
+            })
         })
-        .transpose()?
-        .unwrap_or_default();
+        .collect::<Result<Vec<_>, NexusError>>()?;
+
+    let tasks = convert_task_list(raw.tasks, &source_file, &lines.tasks)?;
 
     let handlers = raw
         .handlers
         .map(|handlers| {
+            let handler_lines = matching(&lines.handlers, handlers.len());
             handlers
                 .into_iter()
                 .enumerate()
-                .map(|(i, h)| convert_handler(h, &source_file, i))
-                .collect::<Result<Vec<_>, _>>()
+                .map(|(i, h)| {
+                    let location = handler_lines.get(i).map(|l| l.location(&source_file));
+                    let mut handler = convert_handler(h, &source_file, i)
+                        .map_err(|e| e.with_location(location.as_ref()))?;
+                    handler.location = location;
+                    Ok(handler)
+                })
+                .collect::<Result<Vec<_>, NexusError>>()
         })
         .transpose()?
         .unwrap_or_default();
@@ -374,31 +884,8 @@ fn convert_playbook(raw: RawPlaybook, source_file: String) -> Result<Playbook, N
         .transpose()?
         .unwrap_or_default();
 
-    // Parse pre_tasks
-    let pre_tasks = raw
-        .pre_tasks
-        .map(|tasks| {
-            tasks
-                .into_iter()
-                .enumerate()
-                .map(|(i, t)| convert_task_or_block(t, &source_file, i))
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .transpose()?
-        .unwrap_or_default();
-
-    // Parse post_tasks
-    let post_tasks = raw
-        .post_tasks
-        .map(|tasks| {
-            tasks
-                .into_iter()
-                .enumerate()
-                .map(|(i, t)| convert_task_or_block(t, &source_file, i))
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .transpose()?
-        .unwrap_or_default();
+    let pre_tasks = convert_task_list(raw.pre_tasks, &source_file, &lines.pre_tasks)?;
+    let post_tasks = convert_task_list(raw.post_tasks, &source_file, &lines.post_tasks)?;
 
     // Parse serial configuration
     let serial = raw.serial.map(convert_serial).transpose()?;
@@ -414,8 +901,11 @@ fn convert_playbook(raw: RawPlaybook, source_file: String) -> Result<Playbook, N
 
     Ok(Playbook {
         source_file,
+        name: raw.name,
         hosts,
         vars,
+        vars_files: raw.vars_files.unwrap_or_default(),
+        vars_prompt,
         tasks,
         handlers,
         functions,
@@ -425,54 +915,76 @@ fn convert_playbook(raw: RawPlaybook, source_file: String) -> Result<Playbook, N
         pre_tasks,
         post_tasks,
         gather_facts: raw.gather_facts.unwrap_or(false),
+        gather_subset: match raw.gather_subset {
+            Some(TagsValue::Single(s)) => s.split(',').map(|c| c.trim().to_string()).collect(),
+            Some(TagsValue::Multiple(v)) => v,
+            None => vec![],
+        },
         connection: raw.connection,
         serial,
         throttle: raw.throttle,
         strategy,
+        environment: raw
+            .environment
+            .as_ref()
+            .map(parse_environment)
+            .transpose()?
+            .unwrap_or_default(),
     })
 }
 
 fn convert_serial(raw: RawSerial) -> Result<Serial, NexusError> {
     match raw {
         RawSerial::Count(n) => Ok(Serial::Count(n)),
-        RawSerial::Percentage(s) => {
-            // Parse percentage string like "25%"
-            if let Some(stripped) = s.strip_suffix('%') {
-                let percentage = stripped.parse::<u8>().map_err(|_| {
-                    NexusError::Parse(Box::new(ParseError {
-                        kind: ParseErrorKind::InvalidValue,
-                        message: format!("Invalid percentage value: {}", s),
-                        file: None,
-                        line: None,
-                        column: None,
-                        suggestion: Some(
-                            "Use a number between 0-100 followed by % (e.g., '25%')".to_string(),
-                        ),
-                    }))
-                })?;
-                if percentage > 100 {
-                    return Err(NexusError::Parse(Box::new(ParseError {
-                        kind: ParseErrorKind::InvalidValue,
-                        message: format!("Percentage must be between 0-100, got {}", percentage),
-                        file: None,
-                        line: None,
-                        column: None,
-                        suggestion: None,
-                    })));
+        RawSerial::Percentage(s) => Ok(Serial::Percentage(parse_serial_percentage(&s)?)),
+        RawSerial::List(list) => list
+            .into_iter()
+            .map(|batch| match batch {
+                RawSerialBatch::Count(n) => Ok(SerialBatch::Count(n)),
+                RawSerialBatch::Percentage(s) => {
+                    Ok(SerialBatch::Percentage(parse_serial_percentage(&s)?))
                 }
-                Ok(Serial::Percentage(percentage))
-            } else {
-                Err(NexusError::Parse(Box::new(ParseError {
-                    kind: ParseErrorKind::InvalidValue,
-                    message: format!("Expected percentage string with % suffix, got: {}", s),
-                    file: None,
-                    line: None,
-                    column: None,
-                    suggestion: Some("Use format like '25%'".to_string()),
-                })))
-            }
+            })
+            .collect::<Result<_, _>>()
+            .map(Serial::List),
+    }
+}
+
+/// Parse a percentage string like "25%"
+fn parse_serial_percentage(s: &str) -> Result<u8, NexusError> {
+    if let Some(stripped) = s.strip_suffix('%') {
+        let percentage = stripped.trim().parse::<u8>().map_err(|_| {
+            NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid percentage value: {}", s),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some(
+                    "Use a number between 0-100 followed by % (e.g., '25%')".to_string(),
+                ),
+            }))
+        })?;
+        if percentage > 100 {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Percentage must be between 0-100, got {}", percentage),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: None,
+            })));
         }
-        RawSerial::List(list) => Ok(Serial::List(list)),
+        Ok(percentage)
+    } else {
+        Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message: format!("Expected percentage string with % suffix, got: {}", s),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Use format like '25%'".to_string()),
+        })))
     }
 }
 
@@ -484,6 +996,15 @@ pub(crate) fn convert_vars(
         .collect()
 }
 
+/// `vars:` of a block or task, evaluated when the task runs
+pub(crate) fn convert_expression_vars(
+    vars: HashMap<String, YamlValue>,
+) -> Result<HashMap<String, Expression>, NexusError> {
+    vars.iter()
+        .map(|(k, v)| Ok((k.clone(), yaml_to_expression(v)?)))
+        .collect()
+}
+
 fn yaml_to_value(yaml: YamlValue) -> Result<Value, NexusError> {
     match yaml {
         YamlValue::Null => Ok(Value::Null),
@@ -515,10 +1036,34 @@ fn yaml_to_value(yaml: YamlValue) -> Result<Value, NexusError> {
                 .collect();
             Ok(Value::Dict(items?))
         }
+        YamlValue::Tagged(tagged) if tagged.tag == "vault" => Err(undecrypted_vault_error()),
         YamlValue::Tagged(tagged) => yaml_to_value(tagged.value),
     }
 }
 
+/// Convert a playbook's task list, pointing errors and the tasks themselves at
+/// their lines in the source
+fn convert_task_list(
+    raw: Option<Vec<RawTask>>,
+    source_file: &str,
+    lines: &[ItemLines],
+) -> Result<Vec<TaskOrBlock>, NexusError> {
+    let raw = raw.unwrap_or_default();
+    let lines = matching(lines, raw.len());
+
+    let mut tasks = raw
+        .into_iter()
+        .enumerate()
+        .map(|(i, t)| {
+            convert_task_or_block(t, source_file, i).map_err(|e| {
+                e.with_location(lines.get(i).map(|l| l.location(source_file)).as_ref())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    locate_tasks(&mut tasks, lines, source_file);
+    Ok(tasks)
+}
+
 /// Convert RawTask to either Task or Block
 fn convert_task_or_block(
     raw: RawTask,
@@ -605,11 +1150,18 @@ fn convert_block(
         .transpose()?
         .unwrap_or_default();
 
+    let vars = raw
+        .vars
+        .map(convert_expression_vars)
+        .transpose()?
+        .unwrap_or_default();
+
     Ok(TaskOrBlock::Block(Block {
         name,
         block,
         rescue,
         always,
+        vars,
         when,
         tags,
         location: None,
@@ -627,9 +1179,17 @@ fn convert_task(raw: RawTask, source_file: &str, index: usize) -> Result<Task, N
         .map(|w| parse_condition(&w))
         .transpose()?;
 
-    let fail_when = raw.fail_when.map(|w| parse_condition(&w)).transpose()?;
+    let fail_when = raw
+        .fail_when
+        .map(|c| convert_condition(c, BinaryOperator::Or))
+        .transpose()?
+        .flatten();
 
-    let changed_when = raw.changed_when.map(|w| parse_condition(&w)).transpose()?;
+    let changed_when = raw
+        .changed_when
+        .map(|c| convert_condition(c, BinaryOperator::And))
+        .transpose()?
+        .flatten();
 
     let notify = match raw.notify {
         Some(NotifyValue::Single(s)) => vec![s],
@@ -637,7 +1197,7 @@ fn convert_task(raw: RawTask, source_file: &str, index: usize) -> Result<Task, N
         None => vec![],
     };
 
-    let loop_expr = raw.loop_expr.map(|l| parse_condition(&l)).transpose()?;
+    let (loop_expr, loop_nested) = convert_loop(raw.loop_expr, raw.loop_nested)?;
 
     let loop_var = raw.loop_var.unwrap_or_else(|| "item".to_string());
 
@@ -684,6 +1244,12 @@ fn convert_task(raw: RawTask, source_file: &str, index: usize) -> Result<Task, N
     // Parse delegate_to
     let delegate_to = raw.delegate_to.map(|d| parse_condition(&d)).transpose()?;
 
+    let vars = raw
+        .vars
+        .map(convert_expression_vars)
+        .transpose()?
+        .unwrap_or_default();
+
     Ok(Task {
         name,
         module,
@@ -692,7 +1258,9 @@ fn convert_task(raw: RawTask, source_file: &str, index: usize) -> Result<Task, N
         fail_when,
         changed_when,
         notify,
+        always_notify: raw.always_notify.unwrap_or(false),
         loop_expr,
+        loop_nested,
         loop_var,
         location: None, // TODO: track source locations
         sudo: raw.sudo,
@@ -704,6 +1272,9 @@ fn convert_task(raw: RawTask, source_file: &str, index: usize) -> Result<Task, N
         throttle: raw.throttle,
         delegate_to,
         delegate_facts: raw.delegate_facts.unwrap_or(false),
+        no_log: raw.no_log.unwrap_or(false),
+        check_mode: raw.check_mode,
+        vars,
     })
 }
 
@@ -762,20 +1333,9 @@ fn convert_retry_config(raw: RawRetryConfig, _task_name: &str) -> Result<RetryCo
 
 /// Convert raw async config to AST
 fn convert_async_config(async_timeout: Option<u64>, poll: Option<u64>) -> AsyncConfig {
-    let timeout = async_timeout.unwrap_or(300);
-    let poll_interval = poll.unwrap_or(10);
-
-    // Calculate retries based on timeout and poll interval
-    let retries = if poll_interval > 0 {
-        ((timeout / poll_interval) + 5).max(10) as u32
-    } else {
-        0
-    };
-
     AsyncConfig {
-        async_timeout: timeout,
-        poll: poll_interval,
-        retries,
+        async_timeout: async_timeout.unwrap_or(300),
+        poll: poll.unwrap_or(10),
     }
 }
 
@@ -797,9 +1357,16 @@ fn convert_handler(
 
     let module = parse_module_call(&raw.module, source_file)?;
 
+    let listen = match raw.listen {
+        Some(NotifyValue::Single(s)) => vec![s],
+        Some(NotifyValue::Multiple(v)) => v,
+        None => Vec::new(),
+    };
+
     Ok(Handler {
         name,
         module,
+        listen,
         location: None,
     })
 }
@@ -835,8 +1402,43 @@ fn convert_role_ref(raw: RawRoleRef, _source_file: &str) -> Result<RoleRef, Nexu
     }
 }
 
-pub(crate) fn parse_condition(cond: &str) -> Result<Expression, NexusError> {
-    // Strip ${} if present
+/// Convert `loop` / `loop_nested` into the loop expression and whether it is nested.
+///
+/// `loop_nested` takes either a YAML list of lists (each entry may be an
+/// expression such as `${users}`) or a single expression that evaluates to one.
+pub(crate) fn convert_loop(
+    loop_expr: Option<String>,
+    loop_nested: Option<YamlValue>,
+) -> Result<(Option<Expression>, bool), NexusError> {
+    match (loop_expr, loop_nested) {
+        (Some(_), Some(_)) => Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message: "A task cannot have both 'loop' and 'loop_nested'".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Use loop_nested with a list of lists instead".to_string()),
+        }))),
+        (Some(l), None) => Ok((Some(parse_condition(&l)?), false)),
+        (None, Some(YamlValue::String(s))) => Ok((Some(parse_condition(&s)?), true)),
+        (None, Some(YamlValue::Sequence(lists))) => {
+            // `- ${users}` refers to the list itself, not its string form
+            let lists = lists
+                .iter()
+                .map(|list| match list {
+                    YamlValue::String(s) => parse_condition(s),
+                    other => yaml_to_expression(other),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((Some(Expression::List(lists)), true))
+        }
+        (None, Some(value)) => Ok((Some(yaml_to_expression(&value)?), true)),
+        (None, None) => Ok((None, false)),
+    }
+}
+
+pub(crate) fn parse_condition(cond: &str) -> Result<Expression, NexusError> {
+    // Strip ${} if present
     let expr_str = if cond.starts_with("${") && cond.ends_with('}') {
         &cond[2..cond.len() - 1]
     } else {
@@ -856,9 +1458,13 @@ fn parse_module_call(
         "when",
         "register",
         "fail_when",
+        "failed_when",
         "changed_when",
         "notify",
+        "always_notify",
         "loop",
+        "loop_nested",
+        "with_nested",
         "loop_var",
         "sudo",
         "as",
@@ -873,6 +1479,10 @@ fn parse_module_call(
         "throttle",
         "delegate_to",
         "delegate_facts",
+        "no_log",
+        "check_mode",
+        "environment",
+        "listen",
     ];
 
     // Find the module type
@@ -933,6 +1543,96 @@ fn parse_module_call(
         return parse_shell_module(shell_value, module, source_file);
     }
 
+    if let Some(wait_value) = module.get("wait_for") {
+        return parse_wait_for_module(wait_value, module, source_file);
+    }
+
+    if let Some(url_value) = module.get("get_url") {
+        return parse_get_url_module(url_value, module, source_file);
+    }
+
+    if let Some(systemd_value) = module.get("systemd") {
+        return parse_systemd_module(systemd_value, module, source_file);
+    }
+
+    if let Some(pip_value) = module.get("pip") {
+        return parse_pip_module(pip_value, module, source_file);
+    }
+
+    if let Some(slurp_value) = module.get("slurp") {
+        return parse_slurp_module(slurp_value, module, source_file);
+    }
+
+    if let Some(stat_value) = module.get("stat") {
+        return parse_stat_module(stat_value, module, source_file);
+    }
+
+    if let Some(async_status_value) = module.get("async_status") {
+        return parse_async_status_module(async_status_value, module, source_file);
+    }
+
+    if let Some(fetch_value) = module.get("fetch") {
+        return parse_fetch_module(fetch_value, module, source_file);
+    }
+
+    if let Some(sync_value) = module.get("synchronize") {
+        return parse_synchronize_module(sync_value, module, source_file);
+    }
+
+    if let Some(meta_value) = module.get("meta") {
+        return parse_meta_module(meta_value, module, source_file);
+    }
+
+    if let Some(mount_value) = module.get("mount") {
+        return parse_mount_module(mount_value, module, source_file);
+    }
+
+    if let Some(reboot_value) = module.get("reboot") {
+        return parse_reboot_module(reboot_value, module, source_file);
+    }
+
+    if let Some(authorized_key_value) = module.get("authorized_key") {
+        return parse_authorized_key_module(authorized_key_value, module, source_file);
+    }
+
+    if let Some(blockinfile_value) = module.get("blockinfile") {
+        return parse_blockinfile_module(blockinfile_value, module, source_file);
+    }
+
+    if let Some(hostname_value) = module.get("hostname") {
+        return parse_named_module("hostname", hostname_value, "web01.example.com")
+            .map(|name| ModuleCall::Hostname { name });
+    }
+
+    if let Some(timezone_value) = module.get("timezone") {
+        return parse_named_module("timezone", timezone_value, "Europe/Berlin")
+            .map(|name| ModuleCall::Timezone { name });
+    }
+
+    if let Some(debug_value) = module.get("debug") {
+        return parse_debug_module(debug_value, module, source_file);
+    }
+
+    if let Some(assert_value) = module.get("assert") {
+        return parse_assert_module(assert_value, module, source_file);
+    }
+
+    if let Some(set_fact_value) = module.get("set_fact") {
+        return parse_set_fact_module(set_fact_value, module, source_file);
+    }
+
+    if let Some(group_by_value) = module.get("group_by") {
+        return parse_group_by_module(group_by_value, module, source_file);
+    }
+
+    // Anything else is a custom module if the module path has a file for it;
+    // otherwise it's most likely a typo
+    if let [name] = module_keys.as_slice() {
+        if is_custom_module_name(name) && custom_module_exists(name, source_file) {
+            return parse_custom_module(name, &module[*name]);
+        }
+    }
+
     // Unknown module - provide helpful error
     let unknown_key = module_keys[0];
     let _suggestion = suggest_module(unknown_key);
@@ -951,10 +1651,86 @@ fn parse_module_call(
     })))
 }
 
+/// Modules the parser knows, for suggestions when a module name is unknown
+const BUILTIN_MODULES: [&str; 29] = [
+    "package",
+    "service",
+    "file",
+    "command",
+    "shell",
+    "user",
+    "template",
+    "facts",
+    "run",
+    "wait_for",
+    "get_url",
+    "systemd",
+    "pip",
+    "slurp",
+    "stat",
+    "async_status",
+    "fetch",
+    "synchronize",
+    "meta",
+    "mount",
+    "reboot",
+    "authorized_key",
+    "blockinfile",
+    "hostname",
+    "timezone",
+    "debug",
+    "assert",
+    "set_fact",
+    "group_by",
+];
+
+/// `--module-path` directories, searched for custom modules before the `library/`
+/// directory next to the file being parsed
+static MODULE_PATHS: Lazy<RwLock<Vec<PathBuf>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Set the `--module-path` directories that custom modules are looked up in while
+/// parsing, so a misspelled module fails at parse time rather than on the host
+pub fn set_module_paths(paths: Vec<PathBuf>) {
+    *MODULE_PATHS.write() = paths;
+}
+
+/// Whether a custom module called `name` exists on the module path, or in the
+/// `library/` directory next to `source_file`, the same places the task runs it from
+fn custom_module_exists(name: &str, source_file: &str) -> bool {
+    let mut paths = MODULE_PATHS.read().clone();
+    paths.push(
+        Path::new(source_file)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("library"),
+    );
+    CustomModule::new(paths).find(name).is_some()
+}
+
+/// A custom module's parameters: a mapping as is, a bare value as `_raw_params`
+fn parse_custom_module(name: &str, value: &YamlValue) -> Result<ModuleCall, NexusError> {
+    let args = match value {
+        YamlValue::Mapping(_) => yaml_to_expression(value)?,
+        YamlValue::Null => Expression::Dict(Vec::new()),
+        _ => Expression::Dict(vec![(
+            Expression::String("_raw_params".to_string()),
+            yaml_to_expression(value)?,
+        )]),
+    };
+    Ok(ModuleCall::Custom {
+        name: name.to_string(),
+        args,
+    })
+}
+
+/// Whether `name` can be a custom module's file name: letters, digits and underscores
+fn is_custom_module_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 fn suggest_module(name: &str) -> String {
-    let modules = [
-        "package", "service", "file", "command", "shell", "user", "template", "facts", "run",
-    ];
+    let modules = BUILTIN_MODULES;
 
     // Simple edit distance for suggestions
     for m in &modules {
@@ -1015,10 +1791,45 @@ fn parse_package_module(
     module: &HashMap<String, YamlValue>,
     _source_file: &str,
 ) -> Result<ModuleCall, NexusError> {
-    let name = yaml_to_expression(value)?;
+    // package: <name or list> with sibling params, or a mapping with name
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
 
-    let state = module
-        .get("state")
+    let names_value = match value {
+        YamlValue::Mapping(_) => get_param("name"),
+        _ => Some(value),
+    };
+    let name = match names_value {
+        Some(YamlValue::Sequence(seq)) => seq
+            .iter()
+            .map(yaml_to_expression)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(v) => vec![yaml_to_expression(v)?],
+        None => vec![],
+    };
+
+    let update_cache = get_param("update_cache")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    if name.is_empty() && !update_cache {
+        return Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "package module requires 'name'".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add name: <package> or a list of packages".to_string()),
+        })));
+    }
+
+    let state = get_param("state")
         .and_then(|v| v.as_str())
         .map(|s| match s {
             "installed" | "present" => PackageState::Installed,
@@ -1028,7 +1839,14 @@ fn parse_package_module(
         })
         .unwrap_or(PackageState::Installed);
 
-    Ok(ModuleCall::Package { name, state })
+    Ok(ModuleCall::Package {
+        name,
+        state,
+        update_cache,
+        autoremove: get_param("autoremove")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    })
 }
 
 fn parse_service_module(
@@ -1059,6 +1877,165 @@ fn parse_service_module(
     })
 }
 
+fn parse_systemd_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // systemd: <unit> with sibling params, or a mapping (name is optional for daemon_reload)
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let invalid = |message: String, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let name = match value {
+        YamlValue::Mapping(_) => get_param("name").map(yaml_to_expression).transpose()?,
+        YamlValue::Null => None,
+        _ => Some(yaml_to_expression(value)?),
+    };
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        None => None,
+        Some("running") | Some("started") => Some(ServiceState::Running),
+        Some("stopped") => Some(ServiceState::Stopped),
+        Some("restarted") => Some(ServiceState::Restarted),
+        Some("reloaded") => Some(ServiceState::Reloaded),
+        Some(other) => {
+            return Err(invalid(
+                format!("Invalid systemd state: {}", other),
+                "Use one of: started, stopped, restarted, reloaded",
+            ));
+        }
+    };
+
+    let scope = match get_param("scope").and_then(|v| v.as_str()) {
+        None | Some("system") => SystemdScope::System,
+        Some("user") => SystemdScope::User,
+        Some(other) => {
+            return Err(invalid(
+                format!("Invalid systemd scope: {}", other),
+                "Use one of: system, user",
+            ));
+        }
+    };
+
+    let daemon_reload = get_param("daemon_reload")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    if name.is_none() && !daemon_reload {
+        return Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "systemd module requires 'name' unless daemon_reload is set".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add name: <unit>".to_string()),
+        })));
+    }
+
+    Ok(ModuleCall::Systemd {
+        name,
+        state,
+        enabled: get_param("enabled").and_then(|v| v.as_bool()),
+        daemon_reload,
+        masked: get_param("masked").and_then(|v| v.as_bool()),
+        scope,
+    })
+}
+
+fn parse_pip_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // pip: <name or list> with sibling params, or a mapping with name/requirements
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let names_value = match value {
+        YamlValue::Mapping(_) => get_param("name"),
+        YamlValue::Null => None,
+        _ => Some(value),
+    };
+    let name = match names_value {
+        Some(YamlValue::Sequence(seq)) => seq
+            .iter()
+            .map(yaml_to_expression)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(v) => vec![yaml_to_expression(v)?],
+        None => vec![],
+    };
+
+    let requirements = get_param("requirements")
+        .map(yaml_to_expression)
+        .transpose()?;
+
+    if name.is_empty() && requirements.is_none() {
+        return Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "pip module requires 'name' or 'requirements'".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add name: <package> or requirements: requirements.txt".to_string()),
+        })));
+    }
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        None | Some("present") | Some("installed") => PipState::Present,
+        Some("absent") | Some("removed") => PipState::Absent,
+        Some("latest") => PipState::Latest,
+        Some(other) => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid pip state: {}", other),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some("Use one of: present, absent, latest".to_string()),
+            })));
+        }
+    };
+
+    Ok(ModuleCall::Pip {
+        name,
+        state,
+        version: get_param("version").map(yaml_to_expression).transpose()?,
+        virtualenv: get_param("virtualenv")
+            .map(yaml_to_expression)
+            .transpose()?,
+        virtualenv_command: get_param("virtualenv_command")
+            .map(yaml_to_expression)
+            .transpose()?,
+        requirements,
+        executable: get_param("executable")
+            .map(yaml_to_expression)
+            .transpose()?,
+    })
+}
+
 fn parse_file_module(
     value: &YamlValue,
     module: &HashMap<String, YamlValue>,
@@ -1118,6 +2095,24 @@ fn parse_file_module(
 
     let mode = get_param("mode").map(yaml_to_expression).transpose()?;
 
+    let recurse = get_param("recurse")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if recurse && state != FileState::Directory {
+        return Err(NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message: "file module 'recurse' requires 'state: directory'".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Set state: directory, or drop recurse for a single file".to_string()),
+        })));
+    }
+
+    let creates = get_param("creates").map(yaml_to_expression).transpose()?;
+
+    let removes = get_param("removes").map(yaml_to_expression).transpose()?;
+
     Ok(ModuleCall::File {
         path,
         state,
@@ -1126,6 +2121,9 @@ fn parse_file_module(
         owner,
         group,
         mode,
+        recurse,
+        creates,
+        removes,
     })
 }
 
@@ -1140,10 +2138,26 @@ fn parse_command_module(
 
     let removes = module.get("removes").map(yaml_to_expression).transpose()?;
 
+    let environment = module
+        .get("environment")
+        .map(parse_environment)
+        .transpose()?
+        .unwrap_or_default();
+
+    let stdin = module.get("stdin").map(yaml_to_expression).transpose()?;
+
+    let stdin_add_newline = module
+        .get("stdin_add_newline")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
     Ok(ModuleCall::Command {
         cmd,
         creates,
         removes,
+        environment,
+        stdin,
+        stdin_add_newline,
     })
 }
 
@@ -1169,6 +2183,30 @@ fn parse_user_module(
     let shell = module.get("shell").map(yaml_to_expression).transpose()?;
     let home = module.get("home").map(yaml_to_expression).transpose()?;
     let create_home = module.get("create_home").and_then(|v| v.as_bool());
+    let flag = |key: &str| module.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    let append = flag("append");
+    let system = flag("system");
+    let generate_ssh_key = flag("generate_ssh_key");
+    let password = module.get("password").map(yaml_to_expression).transpose()?;
+    // `password_expire` is accepted as a short form of `password_expire_max`
+    let password_expire_max = module
+        .get("password_expire_max")
+        .or_else(|| module.get("password_expire"))
+        .map(yaml_to_expression)
+        .transpose()?;
+    let password_expire_min = module
+        .get("password_expire_min")
+        .map(yaml_to_expression)
+        .transpose()?;
+    let expires = module.get("expires").map(yaml_to_expression).transpose()?;
+    let ssh_key_type = module
+        .get("ssh_key_type")
+        .map(yaml_to_expression)
+        .transpose()?;
+    let ssh_key_file = module
+        .get("ssh_key_file")
+        .map(yaml_to_expression)
+        .transpose()?;
 
     let groups = module
         .get("groups")
@@ -1192,9 +2230,18 @@ fn parse_user_module(
         uid,
         gid,
         groups,
+        append,
         shell,
         home,
         create_home,
+        system,
+        password,
+        password_expire_max,
+        password_expire_min,
+        expires,
+        generate_ssh_key,
+        ssh_key_type,
+        ssh_key_file,
     })
 }
 
@@ -1223,6 +2270,9 @@ fn parse_template_module(
     let owner = module.get("owner").map(yaml_to_expression).transpose()?;
     let group = module.get("group").map(yaml_to_expression).transpose()?;
     let mode = module.get("mode").map(yaml_to_expression).transpose()?;
+    let validate = module.get("validate").map(yaml_to_expression).transpose()?;
+    let creates = module.get("creates").map(yaml_to_expression).transpose()?;
+    let removes = module.get("removes").map(yaml_to_expression).transpose()?;
 
     Ok(ModuleCall::Template {
         src,
@@ -1230,6 +2280,9 @@ fn parse_template_module(
         owner,
         group,
         mode,
+        validate,
+        creates,
+        removes,
     })
 }
 
@@ -1334,112 +2387,2214 @@ fn parse_shell_module(
 
     let removes = get_param("removes").map(yaml_to_expression).transpose()?;
 
+    let environment = get_param("environment")
+        .map(parse_environment)
+        .transpose()?
+        .unwrap_or_default();
+
+    let stdin = get_param("stdin").map(yaml_to_expression).transpose()?;
+
+    let stdin_add_newline = get_param("stdin_add_newline")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
     Ok(ModuleCall::Shell {
         command,
         chdir,
         creates,
         removes,
+        environment,
+        stdin,
+        stdin_add_newline,
     })
 }
 
-pub(crate) fn yaml_to_expression(value: &YamlValue) -> Result<Expression, NexusError> {
-    match value {
-        YamlValue::String(s) => {
-            if has_interpolation(s) {
-                parse_interpolated_string(s)
-            } else {
-                Ok(Expression::String(s.clone()))
-            }
+/// Parse an `environment:` mapping of variable names to values (which may be expressions)
+fn parse_environment(value: &YamlValue) -> Result<HashMap<String, Expression>, NexusError> {
+    let invalid = |message: String| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(
+                "Use a mapping, e.g. environment: { PATH: \"/opt/bin:/usr/bin\" }".to_string(),
+            ),
+        }))
+    };
+
+    let YamlValue::Mapping(map) = value else {
+        return Err(invalid(
+            "environment must be a mapping of variable names to values".to_string(),
+        ));
+    };
+
+    map.iter()
+        .map(|(key, value)| {
+            let name = key
+                .as_str()
+                .filter(|name| is_environment_name(name))
+                .ok_or_else(|| invalid(format!("Invalid environment variable name: {:?}", key)))?;
+            Ok((name.to_string(), yaml_to_expression(value)?))
+        })
+        .collect()
+}
+
+fn is_environment_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_wait_for_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // wait_for can be specified as:
+    // 1. wait_for: 8080            (port on localhost)
+    // 2. wait_for: /var/run/ready  (path)
+    // 3. wait_for:
+    //      host: db.local
+    //      port: 5432
+    //      state: started
+    //      timeout: 60
+    //      delay: 5
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
         }
-        YamlValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
+        .or_else(|| module.get(key))
+    };
+
+    let (mut port, mut path) = (None, None);
+    match value {
+        YamlValue::Number(_) => port = Some(yaml_to_expression(value)?),
+        YamlValue::String(_) => path = Some(yaml_to_expression(value)?),
+        _ => {}
+    }
+
+    let host = get_param("host").map(yaml_to_expression).transpose()?;
+    if port.is_none() {
+        port = get_param("port").map(yaml_to_expression).transpose()?;
+    }
+    if path.is_none() {
+        path = get_param("path").map(yaml_to_expression).transpose()?;
+    }
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        None | Some("started") => WaitForState::Started,
+        Some("stopped") => WaitForState::Stopped,
+        Some("present") => WaitForState::Present,
+        Some("absent") => WaitForState::Absent,
+        Some(other) => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid wait_for state: {}", other),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some("Use one of: started, stopped, present, absent".to_string()),
+            })));
+        }
+    };
+
+    let seconds = |key: &str, default: u64| -> Result<u64, NexusError> {
+        let Some(v) = get_param(key) else {
+            return Ok(default);
+        };
+        v.as_u64()
+            .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+            .ok_or_else(|| {
+                NexusError::Parse(Box::new(ParseError {
+                    kind: ParseErrorKind::InvalidValue,
+                    message: format!(
+                        "Invalid wait_for {}: {}",
+                        key,
+                        serde_yaml::to_string(v).unwrap_or_default().trim()
+                    ),
+                    file: None,
+                    line: None,
+                    column: None,
+                    suggestion: Some(format!(
+                        "Give {} as a whole number of seconds, e.g. {}: 30",
+                        key, key
+                    )),
+                }))
+            })
+    };
+
+    Ok(ModuleCall::WaitFor {
+        host,
+        port,
+        path,
+        state,
+        timeout: seconds("timeout", 300)?,
+        delay: seconds("delay", 0)?,
+    })
+}
+
+fn parse_mount_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // mount: /data with sibling parameters, or a mapping with path:
+    //   mount:
+    //     path: /data
+    //     src: /dev/sdb1
+    //     fstype: xfs
+    //     opts: noatime
+    //     state: mounted
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let path = match value {
+        YamlValue::String(_) => Some(yaml_to_expression(value)?),
+        _ => get_param("path").map(yaml_to_expression).transpose()?,
+    }
+    .ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "mount module requires a 'path'".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Use 'mount: /data' or 'mount: { path: /data }'".to_string()),
+        }))
+    })?;
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        Some("mounted") => MountState::Mounted,
+        Some("unmounted") => MountState::Unmounted,
+        Some("present") => MountState::Present,
+        Some("absent") => MountState::Absent,
+        Some("remounted") => MountState::Remounted,
+        other => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: if other.is_some() {
+                    ParseErrorKind::InvalidValue
+                } else {
+                    ParseErrorKind::MissingField
+                },
+                message: match other {
+                    Some(state) => format!("Invalid mount state: {}", state),
+                    None => "mount module requires a 'state'".to_string(),
+                },
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some(
+                    "Use one of: mounted, unmounted, present, absent, remounted".to_string(),
+                ),
+            })));
+        }
+    };
+
+    let param = |key: &str| get_param(key).map(yaml_to_expression).transpose();
+
+    Ok(ModuleCall::Mount {
+        path,
+        src: param("src")?,
+        fstype: param("fstype")?,
+        opts: param("opts")?,
+        dump: param("dump")?,
+        passno: param("passno")?,
+        fstab: param("fstab")?,
+        state,
+    })
+}
+
+fn parse_reboot_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // reboot takes no required arguments:
+    // 1. reboot:                   (defaults)
+    // 2. reboot:
+    //      reboot_timeout: 900
+    //      connect_timeout: 10
+    //      post_reboot_delay: 30
+    //      test_command: systemctl is-system-running
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let seconds = |key: &str, default: u64| -> u64 {
+        get_param(key)
+            .and_then(|v| {
+                v.as_u64()
+                    .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+            })
+            .unwrap_or(default)
+    };
+
+    Ok(ModuleCall::Reboot {
+        reboot_timeout: seconds("reboot_timeout", 600),
+        connect_timeout: seconds("connect_timeout", 10),
+        post_reboot_delay: seconds("post_reboot_delay", 0),
+        test_command: get_param("test_command")
+            .map(yaml_to_expression)
+            .transpose()?,
+    })
+}
+
+fn parse_authorized_key_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // authorized_key: with a mapping, or sibling parameters:
+    //   authorized_key:
+    //     user: deploy
+    //     key: "ssh-ed25519 AAAA... deploy@laptop"
+    //     exclusive: true
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let missing = |field: &str, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: format!("authorized_key module requires '{}' field", field),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let user = get_param("user")
+        .map(yaml_to_expression)
+        .transpose()?
+        .ok_or_else(|| missing("user", "Add user: deploy"))?;
+
+    let key = match get_param("key") {
+        Some(YamlValue::Sequence(seq)) => seq
+            .iter()
+            .map(yaml_to_expression)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(v) => vec![yaml_to_expression(v)?],
+        None => vec![],
+    };
+    if key.is_empty() {
+        return Err(missing(
+            "key",
+            "Add key: \"ssh-ed25519 AAAA... user@host\" or a list of keys",
+        ));
+    }
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        None | Some("present") => AuthorizedKeyState::Present,
+        Some("absent") => AuthorizedKeyState::Absent,
+        Some(other) => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid authorized_key state: {}", other),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some("Use one of: present, absent".to_string()),
+            })));
+        }
+    };
+
+    Ok(ModuleCall::AuthorizedKey {
+        user,
+        key,
+        state,
+        path: get_param("path").map(yaml_to_expression).transpose()?,
+        exclusive: get_param("exclusive")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        manage_dir: get_param("manage_dir")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true),
+    })
+}
+
+fn parse_blockinfile_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // blockinfile: /etc/hosts with sibling parameters, or a mapping with path:
+    //   blockinfile:
+    //     path: /etc/hosts
+    //     block: |
+    //       10.0.0.5 db
+    //     insertafter: "^127.0.0.1"
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let invalid = |kind: ParseErrorKind, message: &str, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind,
+            message: message.to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let path = match value {
+        YamlValue::String(_) => Some(yaml_to_expression(value)?),
+        _ => get_param("path")
+            .or_else(|| get_param("dest"))
+            .map(yaml_to_expression)
+            .transpose()?,
+    }
+    .ok_or_else(|| {
+        invalid(
+            ParseErrorKind::MissingField,
+            "blockinfile module requires a 'path'",
+            "Use 'blockinfile: /etc/hosts' or 'blockinfile: { path: /etc/hosts }'",
+        )
+    })?;
+
+    let state = match get_param("state").and_then(|v| v.as_str()) {
+        None | Some("present") => BlockInFileState::Present,
+        Some("absent") => BlockInFileState::Absent,
+        Some(other) => {
+            return Err(invalid(
+                ParseErrorKind::InvalidValue,
+                &format!("Invalid blockinfile state: {}", other),
+                "Use one of: present, absent",
+            ));
+        }
+    };
+
+    let param = |key: &str| get_param(key).map(yaml_to_expression).transpose();
+    let insertafter = param("insertafter")?;
+    let insertbefore = param("insertbefore")?;
+    if insertafter.is_some() && insertbefore.is_some() {
+        return Err(invalid(
+            ParseErrorKind::InvalidValue,
+            "blockinfile takes insertafter or insertbefore, not both",
+            "Remove one of insertafter and insertbefore",
+        ));
+    }
+
+    Ok(ModuleCall::BlockInFile {
+        path,
+        block: param("block")?,
+        marker: param("marker")?,
+        state,
+        insertafter,
+        insertbefore,
+        create: get_param("create")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    })
+}
+
+fn parse_debug_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // debug: "message", or a mapping (or siblings) with msg or var:
+    //   debug:
+    //     var: result.stdout_lines
+    //     verbosity: 2
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let invalid = |message: String, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let msg = match value {
+        YamlValue::Mapping(_) | YamlValue::Null => get_param("msg"),
+        other => Some(other),
+    }
+    .map(yaml_to_expression)
+    .transpose()?;
+
+    // var names a variable or expression, with or without ${...}
+    let var = match get_param("var") {
+        None => None,
+        Some(YamlValue::String(s)) => {
+            let s = s.trim();
+            let inner = s
+                .strip_prefix("${")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .unwrap_or(s);
+            Some(parse_expression(inner)?)
+        }
+        Some(other) => {
+            return Err(invalid(
+                format!("debug var must be a variable name, got {:?}", other),
+                "Use 'var: result.stdout'",
+            ))
+        }
+    };
+
+    if msg.is_some() && var.is_some() {
+        return Err(invalid(
+            "debug takes msg or var, not both".to_string(),
+            "Remove one of msg and var",
+        ));
+    }
+
+    let verbosity = match get_param("verbosity") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+            .and_then(|n| u8::try_from(n).ok())
+            .ok_or_else(|| {
+                invalid(
+                    format!("Invalid debug verbosity: {:?}", v),
+                    "Use a number of -v flags, e.g. 'verbosity: 2'",
+                )
+            })?,
+    };
+
+    Ok(ModuleCall::Debug {
+        msg,
+        var,
+        verbosity,
+    })
+}
+
+fn parse_assert_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // assert: "${x > 0}", or a mapping (or siblings) with a list of conditions:
+    //   assert:
+    //     that:
+    //       - ${port > 0}
+    //       - ${env in ['dev', 'prod']}
+    //     fail_msg: "bad settings"
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let invalid = |kind: ParseErrorKind, message: String, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let conditions = match value {
+        YamlValue::Mapping(_) | YamlValue::Null => get_param("that"),
+        other => Some(other),
+    };
+    let sources: Vec<String> = match conditions {
+        Some(YamlValue::Sequence(items)) => items.iter().map(condition_source).collect(),
+        Some(other) => vec![condition_source(other)],
+        None => Vec::new(),
+    };
+    if sources.is_empty() {
+        return Err(invalid(
+            ParseErrorKind::MissingField,
+            "assert module requires at least one condition in 'that'".to_string(),
+            "Use 'assert: { that: [\"${port > 0}\"] }'",
+        ));
+    }
+
+    let that = sources
+        .into_iter()
+        .map(|source| {
+            let condition = parse_condition(source.trim())?;
+            Ok(Assertion { source, condition })
+        })
+        .collect::<Result<Vec<_>, NexusError>>()?;
+
+    let param = |key: &str| get_param(key).map(yaml_to_expression).transpose();
+
+    Ok(ModuleCall::Assert {
+        that,
+        // Ansible's older `msg` is the failure message too
+        fail_msg: match param("fail_msg")? {
+            Some(msg) => Some(msg),
+            None => param("msg")?,
+        },
+        success_msg: param("success_msg")?,
+        quiet: get_param("quiet")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    })
+}
+
+fn parse_set_fact_module(
+    value: &YamlValue,
+    _module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // set_fact:
+    //   release: ${version}
+    //   ports: [80, 443]
+    //   cacheable: true
+    let invalid = |message: String| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::InvalidValue,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Use 'set_fact: { name: value }'".to_string()),
+        }))
+    };
+
+    let map = match value {
+        YamlValue::Mapping(map) => map,
+        other => {
+            return Err(invalid(format!(
+                "set_fact takes a mapping of names to values, got {:?}",
+                other
+            )))
+        }
+    };
+
+    let mut facts = Vec::new();
+    let mut cacheable = false;
+    for (key, value) in map {
+        let name = key
+            .as_str()
+            .ok_or_else(|| invalid(format!("Invalid fact name: {:?}", key)))?;
+        if name == "cacheable" {
+            cacheable = value.as_bool().unwrap_or(false);
+            continue;
+        }
+        facts.push((name.to_string(), fact_expression(value)?));
+    }
+
+    if facts.is_empty() {
+        return Err(invalid("set_fact requires at least one fact".to_string()));
+    }
+
+    Ok(ModuleCall::SetFact { facts, cacheable })
+}
+
+fn parse_group_by_module(
+    value: &YamlValue,
+    _module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // group_by: "distro_${ansible_distribution}"
+    // group_by:
+    //   key: distro_${ansible_distribution}
+    let key = match value {
+        YamlValue::Mapping(map) => map.get(YamlValue::String("key".to_string())),
+        YamlValue::Null => None,
+        other => Some(other),
+    };
+    let key = key.ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "group_by requires a key".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(
+                "Use 'group_by: { key: \"distro_${ansible_distribution}\" }'".to_string(),
+            ),
+        }))
+    })?;
+
+    Ok(ModuleCall::GroupBy {
+        key: yaml_to_expression(key)?,
+    })
+}
+
+/// The `name` of a module that takes only that: `module: <name>` or a mapping with
+/// `name:` (a sibling `name:` is the task's name)
+fn parse_named_module(
+    module: &str,
+    value: &YamlValue,
+    example: &str,
+) -> Result<Expression, NexusError> {
+    let name = match value {
+        YamlValue::Mapping(map) => map.get(YamlValue::String("name".to_string())),
+        YamlValue::Null => None,
+        other => Some(other),
+    };
+    let name = name.ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: format!("{} module requires a 'name'", module),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(format!(
+                "Use '{0}: {1}' or '{0}: {{ name: {1} }}'",
+                module, example
+            )),
+        }))
+    })?;
+    yaml_to_expression(name)
+}
+
+/// A value that is a single `${...}` keeps the expression's type instead of
+/// becoming a string
+fn fact_expression(value: &YamlValue) -> Result<Expression, NexusError> {
+    match yaml_to_expression(value)? {
+        Expression::InterpolatedString(mut parts)
+            if parts.len() == 1 && matches!(parts[0], StringPart::Expression(_)) =>
+        {
+            match parts.remove(0) {
+                StringPart::Expression(expr) => Ok(expr),
+                StringPart::Literal(_) => unreachable!(),
+            }
+        }
+        other => Ok(other),
+    }
+}
+
+/// Source text of a condition written as a string or a plain YAML scalar
+fn condition_source(value: &YamlValue) -> String {
+    match value {
+        YamlValue::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+fn parse_get_url_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // get_url: <url> with sibling dest/checksum, or a mapping with url/dest
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let missing = |field: &str, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: format!("get_url module requires '{}' field", field),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let url = if let YamlValue::Mapping(_) = value {
+        get_param("url")
+            .map(yaml_to_expression)
+            .transpose()?
+            .ok_or_else(|| missing("url", "Add url: https://example.com/file"))?
+    } else {
+        yaml_to_expression(value)?
+    };
+
+    let dest = get_param("dest")
+        .map(yaml_to_expression)
+        .transpose()?
+        .ok_or_else(|| missing("dest", "Add dest: /path/to/destination"))?;
+
+    let checksum = get_param("checksum").map(yaml_to_expression).transpose()?;
+    let owner = get_param("owner").map(yaml_to_expression).transpose()?;
+    let group = get_param("group").map(yaml_to_expression).transpose()?;
+    let mode = get_param("mode").map(yaml_to_expression).transpose()?;
+
+    Ok(ModuleCall::GetUrl {
+        url,
+        dest,
+        checksum,
+        owner,
+        group,
+        mode,
+    })
+}
+
+fn parse_slurp_module(
+    value: &YamlValue,
+    _module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // slurp: <path>, or a mapping with src (alias path)
+    let src = match value {
+        YamlValue::Mapping(map) => map
+            .get(YamlValue::String("src".to_string()))
+            .or_else(|| map.get(YamlValue::String("path".to_string()))),
+        other => Some(other),
+    };
+
+    let src = src.map(yaml_to_expression).transpose()?.ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "slurp module requires 'src' field".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add src: /path/to/file".to_string()),
+        }))
+    })?;
+
+    Ok(ModuleCall::Slurp { src })
+}
+
+fn parse_stat_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // stat: <path>, or a mapping with path
+    let params = value.as_mapping();
+    let path = match params {
+        Some(map) => map.get(YamlValue::String("path".to_string())),
+        None => Some(value),
+    };
+
+    let path = path.map(yaml_to_expression).transpose()?.ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "stat module requires 'path' field".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add path: /path/to/file".to_string()),
+        }))
+    })?;
+
+    let get_checksum = params
+        .and_then(|map| map.get(YamlValue::String("get_checksum".to_string())))
+        .or_else(|| module.get("get_checksum"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    Ok(ModuleCall::Stat { path, get_checksum })
+}
+
+fn parse_async_status_module(
+    value: &YamlValue,
+    _module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // async_status: <job id>, or a mapping with job_id (alias jid)
+    let job_id = match value {
+        YamlValue::Mapping(map) => map
+            .get(YamlValue::String("job_id".to_string()))
+            .or_else(|| map.get(YamlValue::String("jid".to_string()))),
+        other => Some(other),
+    };
+
+    let job_id = job_id.map(yaml_to_expression).transpose()?.ok_or_else(|| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: "async_status module requires 'job_id' field".to_string(),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some("Add job_id: ${job.ansible_job_id}".to_string()),
+        }))
+    })?;
+
+    Ok(ModuleCall::AsyncStatus { job_id })
+}
+
+fn parse_fetch_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // fetch: <src> with sibling dest/flat, or a mapping with src/dest
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let missing = |field: &str, suggestion: &str| {
+        NexusError::Parse(Box::new(ParseError {
+            kind: ParseErrorKind::MissingField,
+            message: format!("fetch module requires '{}' field", field),
+            file: None,
+            line: None,
+            column: None,
+            suggestion: Some(suggestion.to_string()),
+        }))
+    };
+
+    let src = if let YamlValue::Mapping(_) = value {
+        get_param("src")
+            .map(yaml_to_expression)
+            .transpose()?
+            .ok_or_else(|| missing("src", "Add src: /path/on/remote"))?
+    } else {
+        yaml_to_expression(value)?
+    };
+
+    let dest = get_param("dest")
+        .map(yaml_to_expression)
+        .transpose()?
+        .ok_or_else(|| missing("dest", "Add dest: /local/directory/"))?;
+
+    let flat = get_param("flat").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    Ok(ModuleCall::Fetch { src, dest, flat })
+}
+
+fn parse_synchronize_module(
+    value: &YamlValue,
+    module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    // synchronize: {src, dest, ...}, or its parameters as sibling keys
+    let get_param = |key: &str| -> Option<&YamlValue> {
+        if let YamlValue::Mapping(map) = value {
+            map.get(YamlValue::String(key.to_string()))
+        } else {
+            None
+        }
+        .or_else(|| module.get(key))
+    };
+
+    let required = |field: &str, suggestion: &str| {
+        get_param(field)
+            .map(yaml_to_expression)
+            .transpose()?
+            .ok_or_else(|| {
+                NexusError::Parse(Box::new(ParseError {
+                    kind: ParseErrorKind::MissingField,
+                    message: format!("synchronize module requires '{}' field", field),
+                    file: None,
+                    line: None,
+                    column: None,
+                    suggestion: Some(suggestion.to_string()),
+                }))
+            })
+    };
+    let src = required("src", "Add src: path/to/copy/")?;
+    let dest = required("dest", "Add dest: /path/on/host/")?;
+
+    let mode = match get_param("mode").and_then(|v| v.as_str()) {
+        None | Some("push") => SyncMode::Push,
+        Some("pull") => SyncMode::Pull,
+        Some(other) => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid synchronize mode: {}", other),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some("Use push (to the host) or pull (from it)".to_string()),
+            })));
+        }
+    };
+
+    let rsync_opts = match get_param("rsync_opts") {
+        Some(YamlValue::Sequence(seq)) => seq
+            .iter()
+            .map(yaml_to_expression)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(v) => vec![yaml_to_expression(v)?],
+        None => vec![],
+    };
+
+    let flag =
+        |key: &str, default: bool| get_param(key).and_then(|v| v.as_bool()).unwrap_or(default);
+
+    Ok(ModuleCall::Synchronize {
+        src,
+        dest,
+        mode,
+        delete: flag("delete", false),
+        archive: flag("archive", true),
+        checksum: flag("checksum", false),
+        rsync_opts,
+    })
+}
+
+fn parse_meta_module(
+    value: &YamlValue,
+    _module: &HashMap<String, YamlValue>,
+    _source_file: &str,
+) -> Result<ModuleCall, NexusError> {
+    let action = match value.as_str().map(str::trim) {
+        Some("flush_handlers") => MetaAction::FlushHandlers,
+        Some("clear_facts") => MetaAction::ClearFacts,
+        Some("end_play") => MetaAction::EndPlay,
+        Some("clear_host_errors") => MetaAction::ClearHostErrors,
+        other => {
+            return Err(NexusError::Parse(Box::new(ParseError {
+                kind: ParseErrorKind::InvalidValue,
+                message: format!("Invalid meta action: {}", other.unwrap_or("<non-string>")),
+                file: None,
+                line: None,
+                column: None,
+                suggestion: Some(
+                    "Use one of: flush_handlers, clear_facts, end_play, clear_host_errors"
+                        .to_string(),
+                ),
+            })));
+        }
+    };
+
+    Ok(ModuleCall::Meta { action })
+}
+
+pub(crate) fn yaml_to_expression(value: &YamlValue) -> Result<Expression, NexusError> {
+    match value {
+        YamlValue::String(s) => {
+            if has_interpolation(s) {
+                parse_interpolated_string(s)
+            } else {
+                Ok(Expression::String(s.clone()))
+            }
+        }
+        YamlValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
                 Ok(Expression::Integer(i))
             } else if let Some(f) = n.as_f64() {
                 Ok(Expression::Float(f))
             } else {
                 Ok(Expression::Integer(0))
             }
-        }
-        YamlValue::Bool(b) => Ok(Expression::Boolean(*b)),
-        YamlValue::Null => Ok(Expression::Null),
-        YamlValue::Sequence(seq) => {
-            let items: Result<Vec<_>, _> = seq.iter().map(yaml_to_expression).collect();
-            Ok(Expression::List(items?))
-        }
-        YamlValue::Mapping(map) => {
-            let items: Result<Vec<_>, _> = map
-                .iter()
-                .map(|(k, v)| {
-                    let key = yaml_to_expression(k)?;
-                    let value = yaml_to_expression(v)?;
-                    Ok((key, value))
-                })
-                .collect();
-            Ok(Expression::Dict(items?))
-        }
-        YamlValue::Tagged(tagged) => yaml_to_expression(&tagged.value),
+        }
+        YamlValue::Bool(b) => Ok(Expression::Boolean(*b)),
+        YamlValue::Null => Ok(Expression::Null),
+        YamlValue::Sequence(seq) => {
+            let items: Result<Vec<_>, _> = seq.iter().map(yaml_to_expression).collect();
+            Ok(Expression::List(items?))
+        }
+        YamlValue::Mapping(map) => {
+            let items: Result<Vec<_>, _> = map
+                .iter()
+                .map(|(k, v)| {
+                    let key = yaml_to_expression(k)?;
+                    let value = yaml_to_expression(v)?;
+                    Ok((key, value))
+                })
+                .collect();
+            Ok(Expression::Dict(items?))
+        }
+        YamlValue::Tagged(tagged) if tagged.tag == "vault" => Err(undecrypted_vault_error()),
+        YamlValue::Tagged(tagged) => yaml_to_expression(&tagged.value),
+    }
+}
+
+/// Convert import_tasks - static import resolved at parse time
+fn convert_import_tasks(
+    import_file: String,
+    raw: RawTask,
+    source_file: &str,
+) -> Result<TaskOrBlock, NexusError> {
+    // Parse tags
+    let tags = match raw.tags {
+        Some(TagsValue::Single(s)) => s.split(',').map(|t| t.trim().to_string()).collect(),
+        Some(TagsValue::Multiple(v)) => v,
+        None => vec![],
+    };
+
+    // Convert vars
+    let vars = raw.vars.map(convert_vars).transpose()?.unwrap_or_default();
+
+    // Resolve the file path relative to the playbook directory
+    let playbook_dir = Path::new(source_file).parent().unwrap_or(Path::new("."));
+    let import_path = playbook_dir.join(&import_file);
+
+    // Validate that the file exists
+    if !import_path.exists() {
+        return Err(NexusError::Io {
+            message: format!("Task file not found: {}", import_path.display()),
+            path: Some(import_path.clone()),
+        });
+    }
+
+    // Return the Import node - scheduler will handle loading the tasks
+    Ok(TaskOrBlock::Import(ImportTasks {
+        file: import_path.to_string_lossy().to_string(),
+        vars,
+        tags,
+        location: None,
+    }))
+}
+
+/// Convert include_tasks - dynamic include resolved at runtime
+fn convert_include_tasks(
+    include_file: String,
+    raw: RawTask,
+    _source_file: &str,
+) -> Result<TaskOrBlock, NexusError> {
+    // Parse the file expression (can contain variables)
+    let file_expr = if has_interpolation(&include_file) {
+        parse_interpolated_string(&include_file)?
+    } else {
+        Expression::String(include_file)
+    };
+
+    // Parse when condition
+    let when = raw
+        .when_condition
+        .map(|w| parse_condition(&w))
+        .transpose()?;
+
+    // Parse loop expression
+    let loop_expr = raw.loop_expr.map(|l| parse_condition(&l)).transpose()?;
+    let loop_var = raw.loop_var.unwrap_or_else(|| "item".to_string());
+
+    // Parse tags
+    let tags = match raw.tags {
+        Some(TagsValue::Single(s)) => s.split(',').map(|t| t.trim().to_string()).collect(),
+        Some(TagsValue::Multiple(v)) => v,
+        None => vec![],
+    };
+
+    // Convert vars to expressions
+    let vars = raw
+        .vars
+        .map(|v| {
+            v.into_iter()
+                .map(|(k, val)| Ok((k, yaml_to_expression(&val)?)))
+                .collect::<Result<HashMap<_, _>, NexusError>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(TaskOrBlock::Include(IncludeTasks {
+        file: file_expr,
+        vars,
+        when,
+        loop_expr,
+        loop_var,
+        tags,
+        location: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_playbook() {
+        let yaml = r#"
+hosts: webservers
+
+tasks:
+  - name: Install nginx
+    package: nginx
+    state: installed
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        assert_eq!(playbook.hosts, HostPattern::Group("webservers".to_string()));
+        assert_eq!(playbook.tasks.len(), 1);
+        if let TaskOrBlock::Task(ref task) = playbook.tasks[0] {
+            assert_eq!(task.name, "Install nginx");
+        } else {
+            panic!("Expected Task, got Block");
+        }
+    }
+
+    #[test]
+    fn test_parse_with_variables() {
+        let yaml = r#"
+hosts: all
+
+vars:
+  webserver: nginx
+  port: 80
+
+tasks:
+  - name: Install webserver
+    package: ${vars.webserver}
+    state: installed
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        assert!(playbook.vars.contains_key("webserver"));
+        assert!(playbook.vars.contains_key("port"));
+    }
+
+    #[test]
+    fn test_unknown_module_error() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Bad task
+    packages: nginx
+"#;
+
+        let result = parse_playbook(yaml, "test.nx.yaml".to_string());
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Unknown module"));
+        assert!(matches!(err, NexusError::Parse(ref e) if e.line == Some(5)));
+    }
+
+    #[test]
+    fn test_task_locations() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: First
+    command: echo one
+
+  - name: Guarded
+    block:
+      - name: Inner
+        command: echo two
+handlers:
+  - name: restart
+    command: echo restart
+"#;
+
+        let playbook = parse_playbook(yaml, "site.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref first) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert_eq!(
+            first.location.as_ref().unwrap().to_string(),
+            "site.nx.yaml:5:3"
+        );
+        let TaskOrBlock::Block(ref block) = playbook.tasks[1] else {
+            panic!("Expected Block");
+        };
+        assert_eq!(block.location.as_ref().unwrap().line, 8);
+        assert_eq!(block.block[0].location.as_ref().unwrap().line, 10);
+        assert_eq!(playbook.handlers[0].location.as_ref().unwrap().line, 13);
+    }
+
+    #[test]
+    fn test_parse_reboot_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Reboot with defaults
+    reboot:
+  - name: Reboot slowly
+    reboot:
+      reboot_timeout: 900
+      post_reboot_delay: 30
+      test_command: systemctl is-system-running
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::Reboot {
+                reboot_timeout: 600,
+                connect_timeout: 10,
+                post_reboot_delay: 0,
+                test_command: None,
+            }
+        ));
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::Reboot {
+                reboot_timeout,
+                post_reboot_delay,
+                test_command,
+                ..
+            } => {
+                assert_eq!(*reboot_timeout, 900);
+                assert_eq!(*post_reboot_delay, 30);
+                assert!(test_command.is_some());
+            }
+            other => panic!("Expected Reboot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_debug_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Show a message
+    debug: "Deployed ${version}"
+  - name: Show a variable at -vv
+    debug:
+      var: result.stdout_lines
+      verbosity: 2
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::Debug {
+                msg: Some(_),
+                var: None,
+                verbosity: 0,
+            }
+        ));
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::Debug {
+                msg,
+                var,
+                verbosity,
+            } => {
+                assert!(msg.is_none());
+                assert!(
+                    matches!(var, Some(Expression::Variable(path)) if path == &["result", "stdout_lines"])
+                );
+                assert_eq!(*verbosity, 2);
+            }
+            other => panic!("Expected Debug, got {:?}", other),
+        }
+
+        let both = "hosts: all\ntasks:\n  - debug:\n      msg: hi\n      var: x\n";
+        assert!(parse_playbook(both, "test.nx.yaml".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_set_fact_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Pick a release
+    set_fact:
+      release: ${version}
+      url: "https://example.com/${version}"
+      cacheable: true
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::SetFact { facts, cacheable } => {
+                let names: Vec<_> = facts.iter().map(|(name, _)| name.as_str()).collect();
+                assert_eq!(names, vec!["release", "url"]);
+                // A lone ${...} keeps the value's type
+                assert!(matches!(facts[0].1, Expression::Variable(_)));
+                assert!(matches!(facts[1].1, Expression::InterpolatedString(_)));
+                assert!(*cacheable);
+            }
+            other => panic!("Expected SetFact, got {:?}", other),
+        }
+
+        let empty = r#"
+hosts: all
+
+tasks:
+  - name: Nothing to set
+    set_fact:
+      cacheable: true
+"#;
+        assert!(parse_playbook(empty, "test.nx.yaml".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_assert_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Check settings
+    assert:
+      that:
+        - ${port > 0}
+        - env == 'prod'
+      fail_msg: "bad settings"
+      quiet: true
+  - name: Single condition
+    assert: ${replicas >= 3}
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::Assert {
+                that,
+                fail_msg,
+                success_msg,
+                quiet,
+            } => {
+                let sources: Vec<_> = that.iter().map(|a| a.source.as_str()).collect();
+                assert_eq!(sources, vec!["${port > 0}", "env == 'prod'"]);
+                assert!(fail_msg.is_some());
+                assert!(success_msg.is_none());
+                assert!(*quiet);
+            }
+            other => panic!("Expected Assert, got {:?}", other),
+        }
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(&task.module, ModuleCall::Assert { that, .. } if that.len() == 1));
+
+        let empty = "hosts: all\ntasks:\n  - assert:\n      fail_msg: nope\n";
+        assert!(parse_playbook(empty, "test.nx.yaml".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_wait_for_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Wait for postgres
+    wait_for:
+      host: db.local
+      port: 5432
+      timeout: 30
+  - name: Wait for lock removal
+    wait_for: /var/run/app.lock
+    state: absent
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::WaitFor {
+                host,
+                port,
+                path,
+                state,
+                timeout,
+                delay,
+            } => {
+                assert!(host.is_some());
+                assert!(matches!(port, Some(Expression::Integer(5432))));
+                assert!(path.is_none());
+                assert_eq!(*state, WaitForState::Started);
+                assert_eq!(*timeout, 30);
+                assert_eq!(*delay, 0);
+            }
+            other => panic!("Expected WaitFor, got {:?}", other),
+        }
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::WaitFor {
+                path: Some(_),
+                state: WaitForState::Absent,
+                timeout: 300,
+                ..
+            }
+        ));
+
+        let err = parse_playbook(
+            "hosts: all\ntasks:\n  - wait_for:\n      port: 8080\n      delay: soon\n",
+            "test.nx.yaml".to_string(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Invalid wait_for delay: soon"));
+    }
+
+    #[test]
+    fn test_parse_get_url_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Fetch release
+    get_url: https://example.com/app.tar.gz
+    dest: /opt/app.tar.gz
+    checksum: "sha256:abc123"
+    mode: "0644"
+  - name: Missing dest
+    get_url:
+      url: https://example.com/app.tar.gz
+"#;
+
+        let result = parse_playbook(yaml, "test.nx.yaml".to_string());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("requires 'dest'"));
+
+        let yaml = yaml.split("  - name: Missing dest").next().unwrap();
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::GetUrl {
+                checksum: Some(_),
+                mode: Some(_),
+                owner: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_gather_subset() {
+        let yaml =
+            "hosts: all\ngather_facts: true\ngather_subset: ['!hardware', network]\ntasks: []\n";
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        assert_eq!(playbook.gather_subset, vec!["!hardware", "network"]);
+
+        let yaml = "hosts: all\ngather_subset: min, network\ntasks: []\n";
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        assert_eq!(playbook.gather_subset, vec!["min", "network"]);
+    }
+
+    #[test]
+    fn test_parse_file_recurse() {
+        let yaml = r#"
+hosts: all
+tasks:
+  - name: Own the web root
+    file: /var/www
+    state: directory
+    owner: www-data
+    recurse: true
+"#;
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        let TaskOrBlock::Task(task) = &playbook.tasks[0] else {
+            panic!("expected a task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::File { recurse: true, .. }
+        ));
+
+        let yaml = "hosts: all\ntasks:\n  - file: /etc/motd\n    mode: '0644'\n    recurse: true\n";
+        let err = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap_err();
+        assert!(err.to_string().contains("state: directory"));
+    }
+
+    #[test]
+    fn test_parse_stat_module() {
+        let yaml = r#"
+hosts: all
+tasks:
+  - stat: /etc/app.conf
+    register: conf
+  - stat:
+      path: /etc/app.conf
+      get_checksum: true
+"#;
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        let checksums: Vec<bool> = playbook
+            .tasks
+            .iter()
+            .map(|task| match task {
+                TaskOrBlock::Task(task) => match task.module {
+                    ModuleCall::Stat { get_checksum, .. } => get_checksum,
+                    _ => panic!("expected a stat task"),
+                },
+                _ => panic!("expected a task"),
+            })
+            .collect();
+        assert_eq!(checksums, vec![false, true]);
+    }
+
+    #[test]
+    fn test_parse_hostname_and_timezone_modules() {
+        let yaml = r#"
+hosts: all
+tasks:
+  - name: Set the hostname
+    hostname: web01.example.com
+  - timezone:
+      name: Europe/Berlin
+"#;
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        let modules: Vec<&ModuleCall> = playbook
+            .tasks
+            .iter()
+            .map(|task| match task {
+                TaskOrBlock::Task(task) => &task.module,
+                _ => panic!("expected a task"),
+            })
+            .collect();
+        assert!(matches!(
+            modules[0],
+            ModuleCall::Hostname { name: Expression::String(name) } if name == "web01.example.com"
+        ));
+        assert!(matches!(
+            modules[1],
+            ModuleCall::Timezone { name: Expression::String(name) } if name == "Europe/Berlin"
+        ));
+
+        let yaml = "hosts: all\ntasks:\n  - timezone:\n";
+        let err = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("timezone module requires a 'name'"));
+    }
+
+    #[test]
+    fn test_parse_user_account_options() {
+        let yaml = r#"
+hosts: all
+tasks:
+  - user: deploy
+    groups: [docker]
+    append: true
+    system: true
+    password: "$6$salt$hash"
+    password_expire: 90
+    expires: "2026-12-31"
+    generate_ssh_key: true
+    ssh_key_type: ed25519
+"#;
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("expected a task");
+        };
+        let ModuleCall::User {
+            append,
+            system,
+            ref password,
+            ref password_expire_max,
+            ref expires,
+            generate_ssh_key,
+            ref ssh_key_type,
+            ..
+        } = task.module
+        else {
+            panic!("expected a user task");
+        };
+        assert!(append && system && generate_ssh_key);
+        assert!(password.is_some() && expires.is_some() && ssh_key_type.is_some());
+        assert!(matches!(password_expire_max, Some(Expression::Integer(90))));
+    }
+
+    #[test]
+    fn test_parse_custom_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        std::fs::create_dir(&library).unwrap();
+        std::fs::write(library.join("rotate_keys.py"), "").unwrap();
+        std::fs::write(library.join("healthcheck"), "").unwrap();
+        let source_file = dir.path().join("test.nx.yml").to_string_lossy().to_string();
+
+        let yaml = r#"
+hosts: all
+tasks:
+  - name: Rotate keys
+    rotate_keys:
+      service: api
+      keep: 3
+    register: rotated
+  - healthcheck: https://localhost/health
+"#;
+        let playbook = parse_playbook(yaml, source_file.clone()).unwrap();
+        let modules: Vec<&ModuleCall> = playbook
+            .tasks
+            .iter()
+            .map(|task| match task {
+                TaskOrBlock::Task(task) => &task.module,
+                _ => panic!("expected a task"),
+            })
+            .collect();
+
+        let ModuleCall::Custom { name, args } = modules[0] else {
+            panic!("expected a custom module");
+        };
+        assert_eq!(name, "rotate_keys");
+        assert!(matches!(args, Expression::Dict(items) if items.len() == 2));
+        assert_eq!(modules[0].module_name(), "rotate_keys");
+
+        let ModuleCall::Custom { args, .. } = modules[1] else {
+            panic!("expected a custom module");
+        };
+        assert!(matches!(
+            args,
+            Expression::Dict(items) if matches!(&items[0].0, Expression::String(key) if key == "_raw_params")
+        ));
+
+        // Without a module file it's a misspelled module, not a custom one
+        let err = parse_playbook("hosts: all\ntasks:\n  - comand: ls\n", source_file).unwrap_err();
+        assert!(err.to_string().contains("Unknown module: comand"));
+        assert!(matches!(err, NexusError::Parse(ref e) if e.line == Some(3)));
+    }
+
+    #[test]
+    fn test_parse_serial_ramp() {
+        let yaml = r#"
+hosts: all
+serial: [1, 5, "50%"]
+tasks: []
+"#;
+        let playbook = parse_playbook(yaml, "test.nx.yml".to_string()).unwrap();
+        assert_eq!(
+            playbook.serial,
+            Some(Serial::List(vec![
+                SerialBatch::Count(1),
+                SerialBatch::Count(5),
+                SerialBatch::Percentage(50),
+            ]))
+        );
+
+        let yaml = "hosts: all\nserial: [1, \"150%\"]\ntasks: []\n";
+        assert!(parse_playbook(yaml, "test.nx.yml".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_slurp_and_fetch_modules() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Read motd
+    slurp: /etc/motd
+    register: motd
+  - name: Back up hosts
+    fetch:
+      src: /etc/hosts
+      dest: backups/
+      flat: true
+  - name: Missing dest
+    fetch: /etc/hosts
+"#;
+
+        let err = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap_err();
+        assert!(err.to_string().contains("requires 'dest'"));
+
+        let yaml = yaml.split("  - name: Missing dest").next().unwrap();
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref slurp) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(slurp.module, ModuleCall::Slurp { .. }));
+        let TaskOrBlock::Task(ref fetch) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(fetch.module, ModuleCall::Fetch { flat: true, .. }));
+    }
+
+    #[test]
+    fn test_parse_synchronize_module() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Deploy site
+    synchronize:
+      src: dist/
+      dest: /var/www/app/
+      delete: true
+      rsync_opts: ["--exclude=.git"]
+  - name: Collect logs
+    synchronize:
+      src: /var/log/app/
+      dest: logs/
+      mode: pull
+      archive: false
+  - name: Bad mode
+    synchronize:
+      src: a
+      dest: b
+      mode: sideways
+"#;
+
+        let err = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap_err();
+        assert!(err.to_string().contains("Invalid synchronize mode"));
+
+        let yaml = yaml.split("  - name: Bad mode").next().unwrap();
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref push) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            push.module,
+            ModuleCall::Synchronize {
+                mode: SyncMode::Push,
+                delete: true,
+                archive: true,
+                checksum: false,
+                ref rsync_opts,
+                ..
+            } if rsync_opts.len() == 1
+        ));
+        let TaskOrBlock::Task(ref pull) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            pull.module,
+            ModuleCall::Synchronize {
+                mode: SyncMode::Pull,
+                archive: false,
+                ..
+            }
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_parse_notify_forms() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Single
+    command: "true"
+    notify: restart nginx
+  - name: List
+    command: "true"
+    notify:
+      - restart nginx
+      - reload haproxy
+  - name: Always
+    command: "true"
+    changed_when: false
+    always_notify: true
+    notify: restart nginx
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let tasks: Vec<&Task> = playbook
+            .tasks
+            .iter()
+            .map(|t| match t {
+                TaskOrBlock::Task(task) => task.as_ref(),
+                _ => panic!("Expected Task"),
+            })
+            .collect();
+
+        assert_eq!(tasks[0].notify, vec!["restart nginx"]);
+        assert!(!tasks[0].always_notify);
+        assert_eq!(tasks[1].notify, vec!["restart nginx", "reload haproxy"]);
+        assert!(tasks[2].always_notify);
+        assert!(matches!(
+            tasks[2].changed_when,
+            Some(Expression::Boolean(false))
+        ));
+    }
 
     #[test]
-    fn test_parse_simple_playbook() {
+    fn test_parse_condition_lists() {
         let yaml = r#"
-hosts: webservers
+hosts: all
 
 tasks:
-  - name: Install nginx
-    package: nginx
-    state: installed
+  - name: Lists
+    command: "true"
+    failed_when: ["a", "b"]
+    changed_when: ["a", "b"]
+  - name: Empty lists
+    command: "true"
+    failed_when: []
+    changed_when: []
 "#;
 
         let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
-        assert_eq!(playbook.hosts, HostPattern::Group("webservers".to_string()));
-        assert_eq!(playbook.tasks.len(), 1);
-        if let TaskOrBlock::Task(ref task) = playbook.tasks[0] {
-            assert_eq!(task.name, "Install nginx");
-        } else {
-            panic!("Expected Task, got Block");
+        let tasks: Vec<&Task> = playbook
+            .tasks
+            .iter()
+            .map(|t| match t {
+                TaskOrBlock::Task(task) => task.as_ref(),
+                _ => panic!("Expected Task"),
+            })
+            .collect();
+
+        assert!(matches!(
+            tasks[0].fail_when,
+            Some(Expression::BinaryOp {
+                op: BinaryOperator::Or,
+                ..
+            })
+        ));
+        assert!(matches!(
+            tasks[0].changed_when,
+            Some(Expression::BinaryOp {
+                op: BinaryOperator::And,
+                ..
+            })
+        ));
+        assert!(tasks[1].fail_when.is_none());
+        assert!(tasks[1].changed_when.is_none());
+    }
+
+    #[test]
+    fn test_parse_loop_nested() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Literal lists
+    command: "echo ${item[0]} ${item[1]}"
+    loop_nested:
+      - [alice, bob]
+      - ${groups}
+  - name: Ansible spelling
+    command: "true"
+    with_nested: ${matrix}
+  - name: Plain loop
+    command: "true"
+    loop: ${users}
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let tasks: Vec<&Task> = playbook
+            .tasks
+            .iter()
+            .map(|t| match t {
+                TaskOrBlock::Task(task) => task.as_ref(),
+                _ => panic!("Expected Task"),
+            })
+            .collect();
+
+        assert!(tasks[0].loop_nested);
+        assert!(matches!(tasks[0].loop_expr, Some(Expression::List(ref l)) if l.len() == 2));
+        assert!(tasks[1].loop_nested);
+        assert!(!tasks[2].loop_nested);
+
+        let both = r#"
+hosts: all
+tasks:
+  - command: "true"
+    loop: ${a}
+    loop_nested: [[1], [2]]
+"#;
+        assert!(parse_playbook(both, "test.nx.yaml".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_package_list() {
+        let yaml = r#"
+hosts: all
+
+tasks:
+  - name: Install tools
+    package: [nginx=1.24.*, git, curl]
+    update_cache: true
+  - name: Clean up
+    package:
+      name: telnet
+      state: absent
+      autoremove: true
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::Package {
+                name,
+                state,
+                update_cache,
+                autoremove,
+            } => {
+                assert_eq!(name.len(), 3);
+                assert!(matches!(&name[0], Expression::String(s) if s == "nginx=1.24.*"));
+                assert_eq!(*state, PackageState::Installed);
+                assert!(*update_cache);
+                assert!(!*autoremove);
+            }
+            other => panic!("Expected Package, got {:?}", other),
         }
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            &task.module,
+            ModuleCall::Package {
+                state: PackageState::Absent,
+                autoremove: true,
+                ..
+            }
+        ));
     }
 
     #[test]
-    fn test_parse_with_variables() {
+    fn test_parse_systemd_module() {
         let yaml = r#"
 hosts: all
 
-vars:
-  webserver: nginx
-  port: 80
+tasks:
+  - name: Reload units
+    systemd:
+      daemon_reload: true
+  - name: Run agent
+    systemd: agent.service
+    state: started
+    enabled: true
+    masked: false
+    scope: user
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::Systemd {
+                name: None,
+                state: None,
+                daemon_reload: true,
+                ..
+            }
+        ));
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            task.module,
+            ModuleCall::Systemd {
+                name: Some(_),
+                state: Some(ServiceState::Running),
+                enabled: Some(true),
+                daemon_reload: false,
+                masked: Some(false),
+                scope: SystemdScope::User,
+            }
+        ));
+
+        let err = parse_playbook(
+            "hosts: all\ntasks:\n  - systemd:\n      state: started\n",
+            "test.nx.yaml".to_string(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("requires 'name'"));
+    }
+
+    #[test]
+    fn test_parse_pip_module() {
+        let yaml = r#"
+hosts: all
 
 tasks:
-  - name: Install webserver
-    package: ${vars.webserver}
-    state: installed
+  - name: Install app deps
+    pip: [requests, "flask>=2"]
+    virtualenv: /opt/app/venv
+    state: latest
+  - name: Install from requirements
+    pip:
+      requirements: /opt/app/requirements.txt
+      executable: pip3
 "#;
 
         let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
-        assert!(playbook.vars.contains_key("webserver"));
-        assert!(playbook.vars.contains_key("port"));
+        let TaskOrBlock::Task(ref task) = playbook.tasks[0] else {
+            panic!("Expected Task");
+        };
+        match &task.module {
+            ModuleCall::Pip {
+                name,
+                state,
+                virtualenv,
+                ..
+            } => {
+                assert_eq!(name.len(), 2);
+                assert_eq!(*state, PipState::Latest);
+                assert!(virtualenv.is_some());
+            }
+            other => panic!("Expected Pip, got {:?}", other),
+        }
+
+        let TaskOrBlock::Task(ref task) = playbook.tasks[1] else {
+            panic!("Expected Task");
+        };
+        assert!(matches!(
+            &task.module,
+            ModuleCall::Pip {
+                name,
+                requirements: Some(_),
+                executable: Some(_),
+                ..
+            } if name.is_empty()
+        ));
     }
 
     #[test]
-    fn test_unknown_module_error() {
+    fn test_parse_meta_module() {
         let yaml = r#"
 hosts: all
 
 tasks:
-  - name: Bad task
-    packages: nginx
+  - meta: flush_handlers
+  - name: Stop here
+    meta: end_play
+  - meta: clear_host_errors
 "#;
 
-        let result = parse_playbook(yaml, "test.nx.yaml".to_string());
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Unknown module"));
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        let actions: Vec<MetaAction> = playbook
+            .tasks
+            .iter()
+            .map(|item| match item {
+                TaskOrBlock::Task(task) => match task.module {
+                    ModuleCall::Meta { action } => action,
+                    ref other => panic!("Expected Meta, got {:?}", other),
+                },
+                _ => panic!("Expected Task"),
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                MetaAction::FlushHandlers,
+                MetaAction::EndPlay,
+                MetaAction::ClearHostErrors
+            ]
+        );
+
+        let err = parse_playbook(
+            "hosts: all\ntasks:\n  - meta: reset_connection\n",
+            "test.nx.yaml".to_string(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid meta action: reset_connection"));
+    }
+
+    #[test]
+    fn test_parse_vars_files_and_prompt() {
+        let yaml = r#"
+hosts: all
+
+vars_files:
+  - vars/common.yml
+  - vars/secrets.yml
+
+vars_prompt:
+  - name: release
+    prompt: Release to deploy
+    private: false
+    default: latest
+  - name: db_password
+
+tasks: []
+"#;
+
+        let playbook = parse_playbook(yaml, "test.nx.yaml".to_string()).unwrap();
+        assert_eq!(
+            playbook.vars_files,
+            vec!["vars/common.yml", "vars/secrets.yml"]
+        );
+        assert_eq!(playbook.vars_prompt.len(), 2);
+        assert_eq!(playbook.vars_prompt[0].prompt, "Release to deploy");
+        assert!(!playbook.vars_prompt[0].private);
+        assert_eq!(
+            playbook.vars_prompt[0].default,
+            Some(Value::String("latest".to_string()))
+        );
+        // Prompt text defaults to the name, input is hidden by default
+        assert_eq!(playbook.vars_prompt[1].prompt, "db_password");
+        assert!(playbook.vars_prompt[1].private);
+    }
+
+    #[test]
+    fn test_parse_extra_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("extra.yml");
+        std::fs::write(&file, "replicas: 2\nregion: eu\n").unwrap();
+
+        let args = vec![
+            "env=staging version=1.2".to_string(),
+            format!("@{}", file.display()),
+            r#"{"replicas": 3, "features": ["a", "b"]}"#.to_string(),
+            "env=prod".to_string(),
+        ];
+        let vars = parse_extra_vars(&args, None).unwrap();
+
+        assert_eq!(vars.get("env"), Some(&Value::String("prod".to_string())));
+        assert_eq!(vars.get("version"), Some(&Value::String("1.2".to_string())));
+        assert_eq!(vars.get("region"), Some(&Value::String("eu".to_string())));
+        assert_eq!(vars.get("replicas"), Some(&Value::Int(3)));
+        assert!(matches!(vars.get("features"), Some(Value::List(items)) if items.len() == 2));
+
+        assert!(parse_extra_vars(&["novalue".to_string()], None).is_err());
+        assert!(parse_extra_vars(&["{not yaml".to_string()], None).is_err());
+    }
+
+    #[test]
+    fn test_parse_extra_vars_with_spaces() {
+        let args = vec![
+            "msg=hello world".to_string(),
+            r#"greeting='good morning' name="Ada \"A\" Lovelace" n=1"#.to_string(),
+        ];
+        let vars = parse_extra_vars(&args, None).unwrap();
+        let get = |key: &str| vars.get(key).cloned();
+        assert_eq!(get("msg"), Some(Value::String("hello world".to_string())));
+        assert_eq!(
+            get("greeting"),
+            Some(Value::String("good morning".to_string()))
+        );
+        assert_eq!(
+            get("name"),
+            Some(Value::String("Ada \"A\" Lovelace".to_string()))
+        );
+        assert_eq!(get("n"), Some(Value::String("1".to_string())));
+
+        assert!(parse_extra_vars(&["msg='unterminated".to_string()], None).is_err());
     }
 
     #[test]
@@ -1727,93 +4882,93 @@ tasks:
         assert_eq!(playbook.hosts, HostPattern::Localhost);
         assert_eq!(playbook.tasks.len(), 1);
     }
-}
-
-/// Convert import_tasks - static import resolved at parse time
-fn convert_import_tasks(
-    import_file: String,
-    raw: RawTask,
-    source_file: &str,
-) -> Result<TaskOrBlock, NexusError> {
-    // Parse tags
-    let tags = match raw.tags {
-        Some(TagsValue::Single(s)) => s.split(',').map(|t| t.trim().to_string()).collect(),
-        Some(TagsValue::Multiple(v)) => v,
-        None => vec![],
-    };
 
-    // Convert vars
-    let vars = raw.vars.map(convert_vars).transpose()?.unwrap_or_default();
+    #[test]
+    fn test_parse_inline_vault_vars() {
+        let secrets = VaultSecrets::single("pw");
+        let secret =
+            crate::vault::encrypt_inline("s3cret", &secrets, None, Some("db_password")).unwrap();
+        let vars: String = secret.lines().map(|l| format!("  {}\n", l)).collect();
+        let yaml = format!(
+            "hosts: localhost\nvars:\n{}  db_user: app\ntasks:\n  - name: Noop\n    command: \"true\"\n",
+            vars
+        );
 
-    // Resolve the file path relative to the playbook directory
-    let playbook_dir = Path::new(source_file).parent().unwrap_or(Path::new("."));
-    let import_path = playbook_dir.join(&import_file);
+        let playbook =
+            parse_playbook_with_vault(&yaml, "test.nx.yml".to_string(), Some(&secrets)).unwrap();
+        assert_eq!(
+            playbook.vars.get("db_password"),
+            Some(&Value::String("s3cret".to_string()))
+        );
+        assert_eq!(
+            playbook.vars.get("db_user"),
+            Some(&Value::String("app".to_string()))
+        );
 
-    // Validate that the file exists
-    if !import_path.exists() {
-        return Err(NexusError::Io {
-            message: format!("Task file not found: {}", import_path.display()),
-            path: Some(import_path.clone()),
-        });
+        let err = parse_playbook(&yaml, "test.nx.yml".to_string()).unwrap_err();
+        assert!(err.to_string().contains("no vault password provided"));
+        assert!(parse_playbook_with_vault(
+            &yaml,
+            "test.nx.yml".to_string(),
+            Some(&VaultSecrets::single("wrong"))
+        )
+        .is_err());
     }
 
-    // Return the Import node - scheduler will handle loading the tasks
-    Ok(TaskOrBlock::Import(ImportTasks {
-        file: import_path.to_string_lossy().to_string(),
-        vars,
-        tags,
-        location: None,
-    }))
-}
-
-/// Convert include_tasks - dynamic include resolved at runtime
-fn convert_include_tasks(
-    include_file: String,
-    raw: RawTask,
-    _source_file: &str,
-) -> Result<TaskOrBlock, NexusError> {
-    // Parse the file expression (can contain variables)
-    let file_expr = if has_interpolation(&include_file) {
-        parse_interpolated_string(&include_file)?
-    } else {
-        Expression::String(include_file)
-    };
-
-    // Parse when condition
-    let when = raw
-        .when_condition
-        .map(|w| parse_condition(&w))
-        .transpose()?;
-
-    // Parse loop expression
-    let loop_expr = raw.loop_expr.map(|l| parse_condition(&l)).transpose()?;
-    let loop_var = raw.loop_var.unwrap_or_else(|| "item".to_string());
-
-    // Parse tags
-    let tags = match raw.tags {
-        Some(TagsValue::Single(s)) => s.split(',').map(|t| t.trim().to_string()).collect(),
-        Some(TagsValue::Multiple(v)) => v,
-        None => vec![],
-    };
-
-    // Convert vars to expressions
-    let vars = raw
-        .vars
-        .map(|v| {
-            v.into_iter()
-                .map(|(k, val)| Ok((k, yaml_to_expression(&val)?)))
-                .collect::<Result<HashMap<_, _>, NexusError>>()
-        })
-        .transpose()?
-        .unwrap_or_default();
+    #[test]
+    fn test_parse_multiple_plays_and_import_playbook() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("db.nx.yml"),
+            "- name: Databases\n  hosts: db\n  tasks:\n    - name: Migrate\n      command: echo migrate\n",
+        )
+        .unwrap();
+        let site = dir.path().join("site.nx.yml");
+        std::fs::write(
+            &site,
+            r#"- name: Web servers
+  hosts: web
+  tasks:
+    - name: Deploy
+      command: echo deploy
+
+- import_playbook: db.nx.yml
+
+- hosts: all
+  tasks:
+    - name: Check
+      command: echo check
+"#,
+        )
+        .unwrap();
+
+        let plays = parse_playbook_file(&site).unwrap();
+        assert_eq!(plays.len(), 3);
+        assert_eq!(plays[0].name.as_deref(), Some("Web servers"));
+        assert_eq!(plays[0].hosts, HostPattern::Group("web".to_string()));
+        assert_eq!(plays[1].name.as_deref(), Some("Databases"));
+        assert!(plays[1].source_file.ends_with("db.nx.yml"));
+        assert_eq!(plays[2].name, None);
+
+        // Task locations are per play
+        let TaskOrBlock::Task(ref check) = plays[2].tasks[0] else {
+            panic!("Expected Task");
+        };
+        assert_eq!(check.location.as_ref().unwrap().line, 11);
+    }
 
-    Ok(TaskOrBlock::Include(IncludeTasks {
-        file: file_expr,
-        vars,
-        when,
-        loop_expr,
-        loop_var,
-        tags,
-        location: None,
-    }))
+    #[test]
+    fn test_import_playbook_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.nx.yml");
+        std::fs::write(&a, "- import_playbook: b.nx.yml\n").unwrap();
+        std::fs::write(dir.path().join("b.nx.yml"), "- import_playbook: a.nx.yml\n").unwrap();
+
+        let err = parse_playbook_file(&a).unwrap_err();
+        assert!(err.to_string().contains("import_playbook cycle"));
+
+        let empty = dir.path().join("empty.nx.yml");
+        std::fs::write(&empty, "[]\n").unwrap();
+        assert!(parse_playbook_file(&empty).is_err());
+    }
 }
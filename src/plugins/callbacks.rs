@@ -5,7 +5,7 @@ use std::fs::{File, OpenOptions};
 use std::io::Write as IoWrite;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use parking_lot::Mutex;
@@ -141,19 +141,37 @@ impl Default for CallbackManager {
 
 // ========== Built-in Plugins ==========
 
-/// JSON log callback - writes events to a JSON file
+/// Seconds since `name` started on `host` in `started`, for events that don't carry
+/// a duration
+fn elapsed_since_start(
+    started: &Mutex<HashMap<(String, String), Instant>>,
+    host: &str,
+    name: &str,
+) -> Option<f64> {
+    started
+        .lock()
+        .remove(&(host.to_string(), name.to_string()))
+        .map(|started| started.elapsed().as_secs_f64())
+}
+
+/// JSON log callback - appends one JSON object per event to a file (JSON lines)
 pub struct JsonLogCallback {
     file: Arc<Mutex<File>>,
+    started: Mutex<HashMap<(String, String), Instant>>,
 }
 
 impl JsonLogCallback {
     /// Create a new JSON log callback that writes to the specified file
     pub fn new(path: impl Into<PathBuf>) -> std::io::Result<Self> {
         let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
         let file = OpenOptions::new().create(true).append(true).open(path)?;
 
         Ok(JsonLogCallback {
             file: Arc::new(Mutex::new(file)),
+            started: Mutex::new(HashMap::new()),
         })
     }
 
@@ -193,6 +211,7 @@ impl CallbackPlugin for JsonLogCallback {
                         "changed": stats.changed,
                         "failed": stats.failed,
                         "skipped": stats.skipped,
+                        "unreachable": stats.unreachable,
                     }),
                 )
             })
@@ -203,10 +222,23 @@ impl CallbackPlugin for JsonLogCallback {
             "timestamp": chrono::Utc::now().to_rfc3339(),
             "duration_secs": recap.total_duration.as_secs_f64(),
             "hosts": hosts,
+            "has_failures": recap.has_failures(),
+        }));
+    }
+
+    async fn on_play_start(&self, play: &str, hosts: &[String]) {
+        self.write_event(json!({
+            "event": "play_start",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "play": play,
+            "hosts": hosts,
         }));
     }
 
     async fn on_task_start(&self, host: &str, task: &str) {
+        self.started
+            .lock()
+            .insert((host.to_string(), task.to_string()), Instant::now());
         self.write_event(json!({
             "event": "task_start",
             "timestamp": chrono::Utc::now().to_rfc3339(),
@@ -222,6 +254,9 @@ impl CallbackPlugin for JsonLogCallback {
         result: &TaskOutput,
         duration: Duration,
     ) {
+        self.started
+            .lock()
+            .remove(&(host.to_string(), task.to_string()));
         self.write_event(json!({
             "event": "task_complete",
             "timestamp": chrono::Utc::now().to_rfc3339(),
@@ -235,15 +270,192 @@ impl CallbackPlugin for JsonLogCallback {
         }));
     }
 
+    async fn on_task_skipped(&self, host: &str, task: &str, reason: &str) {
+        self.write_event(json!({
+            "event": "task_skipped",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "host": host,
+            "task": task,
+            "changed": false,
+            "failed": false,
+            "duration_secs": elapsed_since_start(&self.started, host, task),
+            "reason": reason,
+        }));
+    }
+
     async fn on_task_failed(&self, host: &str, task: &str, error: &str) {
         self.write_event(json!({
             "event": "task_failed",
             "timestamp": chrono::Utc::now().to_rfc3339(),
             "host": host,
             "task": task,
+            "changed": false,
+            "failed": true,
+            "duration_secs": elapsed_since_start(&self.started, host, task),
             "error": error,
         }));
     }
+
+    async fn on_handler_start(&self, host: &str, handler: &str) {
+        self.started
+            .lock()
+            .insert((host.to_string(), handler.to_string()), Instant::now());
+        self.write_event(json!({
+            "event": "handler_start",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "host": host,
+            "handler": handler,
+        }));
+    }
+
+    async fn on_handler_complete(&self, host: &str, handler: &str, result: &TaskOutput) {
+        self.write_event(json!({
+            "event": "handler_complete",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "host": host,
+            "handler": handler,
+            "changed": result.changed,
+            "failed": result.failed,
+            "duration_secs": elapsed_since_start(&self.started, host, handler),
+            "stdout": result.stdout,
+            "stderr": result.stderr,
+        }));
+    }
+}
+
+/// Tree callback - writes every task result of a host to `<dir>/<hostname>` as JSON,
+/// like Ansible's `--tree`
+pub struct TreeCallback {
+    dir: PathBuf,
+    results: Mutex<HashMap<String, Vec<serde_json::Value>>>,
+    started: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl TreeCallback {
+    /// Create a tree callback writing to `dir`, which is created if missing
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(TreeCallback {
+            dir,
+            results: Mutex::new(HashMap::new()),
+            started: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Add a result to `host`'s file. The whole file is rewritten, so it stays
+    /// valid JSON if the run stops part way.
+    fn record(&self, host: &str, result: serde_json::Value) {
+        let mut results = self.results.lock();
+        let host_results = results.entry(host.to_string()).or_default();
+        host_results.push(result);
+
+        let document = json!({
+            "host": host,
+            "results": host_results,
+        });
+        let path = self.dir.join(host);
+        let written = serde_json::to_string_pretty(&document)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&path, json));
+        if let Err(e) = written {
+            eprintln!(
+                "Warning: tree callback: could not write {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl CallbackPlugin for TreeCallback {
+    fn name(&self) -> &str {
+        "tree"
+    }
+
+    async fn on_task_start(&self, host: &str, task: &str) {
+        self.started
+            .lock()
+            .insert((host.to_string(), task.to_string()), Instant::now());
+    }
+
+    async fn on_task_complete(
+        &self,
+        host: &str,
+        task: &str,
+        result: &TaskOutput,
+        duration: Duration,
+    ) {
+        self.started
+            .lock()
+            .remove(&(host.to_string(), task.to_string()));
+        self.record(
+            host,
+            json!({
+                "task": task,
+                "changed": result.changed,
+                "failed": result.failed,
+                "skipped": false,
+                "stdout": result.stdout,
+                "stderr": result.stderr,
+                "msg": result.message,
+                "duration_secs": duration.as_secs_f64(),
+            }),
+        );
+    }
+
+    async fn on_task_skipped(&self, host: &str, task: &str, reason: &str) {
+        self.record(
+            host,
+            json!({
+                "task": task,
+                "changed": false,
+                "failed": false,
+                "skipped": true,
+                "msg": reason,
+                "duration_secs": elapsed_since_start(&self.started, host, task),
+            }),
+        );
+    }
+
+    async fn on_task_failed(&self, host: &str, task: &str, error: &str) {
+        self.record(
+            host,
+            json!({
+                "task": task,
+                "changed": false,
+                "failed": true,
+                "skipped": false,
+                "msg": error,
+                "duration_secs": elapsed_since_start(&self.started, host, task),
+            }),
+        );
+    }
+
+    async fn on_handler_start(&self, host: &str, handler: &str) {
+        self.started
+            .lock()
+            .insert((host.to_string(), handler.to_string()), Instant::now());
+    }
+
+    async fn on_handler_complete(&self, host: &str, handler: &str, result: &TaskOutput) {
+        self.record(
+            host,
+            json!({
+                "task": handler,
+                "handler": true,
+                "changed": result.changed,
+                "failed": result.failed,
+                "skipped": result.skipped,
+                "stdout": result.stdout,
+                "stderr": result.stderr,
+                "msg": result.message,
+                "duration_secs": elapsed_since_start(&self.started, host, handler),
+            }),
+        );
+    }
 }
 
 /// Timer callback - tracks task execution times and shows statistics
@@ -292,7 +504,7 @@ impl TimerCallback {
         }
 
         // Sort by total time (descending)
-        stats.task_stats.sort_by(|a, b| b.total.cmp(&a.total));
+        stats.task_stats.sort_by_key(|s| std::cmp::Reverse(s.total));
 
         stats
     }
@@ -378,6 +590,169 @@ impl CallbackPlugin for TimerCallback {
     }
 }
 
+/// Number of tasks profile_tasks shows when no count is given
+const DEFAULT_PROFILE_TASKS: usize = 20;
+
+/// Profile callback - lists the slowest task runs per host, like Ansible's profile_tasks
+pub struct ProfileTasksCallback {
+    top: usize,
+    started: Mutex<HashMap<(String, String), Instant>>,
+    timings: Mutex<Vec<TaskTiming>>,
+}
+
+/// Duration of one task on one host
+#[derive(Debug, Clone)]
+pub struct TaskTiming {
+    pub host: String,
+    pub task: String,
+    pub duration: Duration,
+    pub failed: bool,
+}
+
+impl ProfileTasksCallback {
+    /// Create a profile callback that shows the `top` slowest tasks
+    pub fn new(top: usize) -> Self {
+        ProfileTasksCallback {
+            top,
+            started: Mutex::new(HashMap::new()),
+            timings: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, host: &str, task: &str, duration: Duration, failed: bool) {
+        self.timings.lock().push(TaskTiming {
+            host: host.to_string(),
+            task: task.to_string(),
+            duration,
+            failed,
+        });
+    }
+
+    /// Render the summary for a playbook that took `wall_time`
+    pub fn summary(&self, wall_time: Duration) -> String {
+        let mut timings = self.timings.lock().clone();
+        timings.sort_by_key(|t| std::cmp::Reverse(t.duration));
+        let task_time: Duration = timings.iter().map(|t| t.duration).sum();
+
+        let mut out = String::new();
+        out.push_str(&format!("\n{}\n", "=".repeat(60)));
+        out.push_str(&format!(
+            "Task Profile (slowest {} of {})\n",
+            self.top.min(timings.len()),
+            timings.len()
+        ));
+        out.push_str(&format!("{}\n", "=".repeat(60)));
+
+        for (i, timing) in timings.iter().take(self.top).enumerate() {
+            out.push_str(&format!(
+                "{:>2}. {:<15} {:<30} {:>8.2}s{}\n",
+                i + 1,
+                truncate(&timing.host, 15),
+                truncate(&timing.task, 30),
+                timing.duration.as_secs_f64(),
+                if timing.failed { " (failed)" } else { "" }
+            ));
+        }
+
+        out.push_str(&format!(
+            "\nPlaybook wall time: {:>8.2}s\n",
+            wall_time.as_secs_f64()
+        ));
+        out.push_str(&format!(
+            "Summed task time:   {:>8.2}s",
+            task_time.as_secs_f64()
+        ));
+        // Summed time above wall time is work that ran in parallel across hosts
+        if !wall_time.is_zero() && !task_time.is_zero() {
+            out.push_str(&format!(
+                " ({:.1}x parallelism)",
+                task_time.as_secs_f64() / wall_time.as_secs_f64()
+            ));
+        }
+        out.push_str(&format!("\n{}", "=".repeat(60)));
+        out
+    }
+}
+
+impl Default for ProfileTasksCallback {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROFILE_TASKS)
+    }
+}
+
+#[async_trait]
+impl CallbackPlugin for ProfileTasksCallback {
+    fn name(&self) -> &str {
+        "profile_tasks"
+    }
+
+    async fn on_task_start(&self, host: &str, task: &str) {
+        self.started
+            .lock()
+            .insert((host.to_string(), task.to_string()), Instant::now());
+    }
+
+    async fn on_task_complete(
+        &self,
+        host: &str,
+        task: &str,
+        _result: &TaskOutput,
+        duration: Duration,
+    ) {
+        self.started
+            .lock()
+            .remove(&(host.to_string(), task.to_string()));
+        self.record(host, task, duration, false);
+    }
+
+    async fn on_task_failed(&self, host: &str, task: &str, _error: &str) {
+        // Failures don't report a duration, so time them from on_task_start
+        let started = self
+            .started
+            .lock()
+            .remove(&(host.to_string(), task.to_string()));
+        if let Some(started) = started {
+            self.record(host, task, started.elapsed(), true);
+        }
+    }
+
+    async fn on_playbook_complete(&self, recap: &PlayRecap) {
+        println!("{}", self.summary(recap.total_duration));
+    }
+}
+
+/// How long a notification callback waits on its endpoint before giving up
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP client shared by the notification callbacks
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .user_agent(format!("nexus/{}", crate::VERSION))
+        .build()
+        .unwrap_or_default()
+}
+
+/// POST a JSON payload. A notification must never fail the playbook, so errors
+/// and non-success responses are only reported as warnings.
+async fn post_json(client: &reqwest::Client, plugin: &str, url: &str, payload: &serde_json::Value) {
+    match client.post(url).json(payload).send().await {
+        Ok(response) if !response.status().is_success() => {
+            eprintln!(
+                "Warning: {} callback: {} returned {}",
+                plugin,
+                url,
+                response.status()
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!(
+            "Warning: {} callback: failed to post to {}: {}",
+            plugin, url, e
+        ),
+    }
+}
+
 /// Webhook callback - POSTs events to a URL
 pub struct WebhookCallback {
     url: String,
@@ -389,12 +764,12 @@ impl WebhookCallback {
     pub fn new(url: impl Into<String>) -> Self {
         WebhookCallback {
             url: url.into(),
-            client: reqwest::Client::new(),
+            client: http_client(),
         }
     }
 
     async fn post_event(&self, event: serde_json::Value) {
-        let _ = self.client.post(&self.url).json(&event).send().await;
+        post_json(&self.client, "webhook", &self.url, &event).await;
     }
 }
 
@@ -426,6 +801,7 @@ impl CallbackPlugin for WebhookCallback {
                         "changed": stats.changed,
                         "failed": stats.failed,
                         "skipped": stats.skipped,
+                        "unreachable": stats.unreachable,
                     }),
                 )
             })
@@ -457,42 +833,27 @@ impl CallbackPlugin for WebhookCallback {
 pub struct SlackCallback {
     webhook_url: String,
     client: reqwest::Client,
+    notify_failures: bool,
 }
 
 impl SlackCallback {
-    /// Create a new Slack callback
+    /// Create a new Slack callback that posts the playbook summary
     pub fn new(webhook_url: impl Into<String>) -> Self {
         SlackCallback {
             webhook_url: webhook_url.into(),
-            client: reqwest::Client::new(),
+            client: http_client(),
+            notify_failures: false,
         }
     }
 
-    async fn send_message(&self, message: &str, color: &str) {
-        let payload = json!({
-            "attachments": [{
-                "color": color,
-                "text": message,
-                "mrkdwn_in": ["text"]
-            }]
-        });
-
-        let _ = self
-            .client
-            .post(&self.webhook_url)
-            .json(&payload)
-            .send()
-            .await;
-    }
-}
-
-#[async_trait]
-impl CallbackPlugin for SlackCallback {
-    fn name(&self) -> &str {
-        "slack"
+    /// Also post a message as soon as any task fails
+    pub fn with_failure_notifications(mut self, notify: bool) -> Self {
+        self.notify_failures = notify;
+        self
     }
 
-    async fn on_playbook_complete(&self, recap: &PlayRecap) {
+    /// Build the summary message and its attachment color
+    fn summary(recap: &PlayRecap) -> (&'static str, String) {
         let total_ok: usize = recap.hosts.values().map(|s| s.ok).sum();
         let total_changed: usize = recap.hosts.values().map(|s| s.changed).sum();
         let total_failed: usize = recap.hosts.values().map(|s| s.failed).sum();
@@ -506,7 +867,7 @@ impl CallbackPlugin for SlackCallback {
             ("good", "SUCCESS")
         };
 
-        let message = format!(
+        let mut message = format!(
             "*Playbook execution {}*\n\
             Duration: {:.2}s\n\
             Hosts: {}\n\
@@ -520,10 +881,47 @@ impl CallbackPlugin for SlackCallback {
             total_skipped
         );
 
+        let mut hosts: Vec<_> = recap.hosts.iter().collect();
+        hosts.sort_by(|a, b| a.0.cmp(b.0));
+        for (host, stats) in hosts {
+            message.push_str(&format!(
+                "\n`{}` ok={} changed={} failed={} skipped={}",
+                host, stats.ok, stats.changed, stats.failed, stats.skipped
+            ));
+        }
+
+        (color, message)
+    }
+
+    async fn send_message(&self, message: &str, color: &str) {
+        let payload = json!({
+            "attachments": [{
+                "color": color,
+                "text": message,
+                "mrkdwn_in": ["text"]
+            }]
+        });
+
+        post_json(&self.client, "slack", &self.webhook_url, &payload).await;
+    }
+}
+
+#[async_trait]
+impl CallbackPlugin for SlackCallback {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn on_playbook_complete(&self, recap: &PlayRecap) {
+        let (color, message) = Self::summary(recap);
         self.send_message(&message, color).await;
     }
 
     async fn on_task_failed(&self, host: &str, task: &str, error: &str) {
+        if !self.notify_failures {
+            return;
+        }
+
         let message = format!(
             "*Task Failed*\n\
             Host: `{}`\n\
@@ -582,8 +980,32 @@ pub fn create_callback_plugin(spec: &str) -> Result<Box<dyn CallbackPlugin>, Str
                 .map_err(|e| format!("Failed to create json_log callback: {}", e))
         }
 
+        "tree" => {
+            let dir = args.ok_or_else(|| {
+                "tree callback requires a directory (e.g., tree:/var/log/nexus)".to_string()
+            })?;
+
+            TreeCallback::new(dir)
+                .map(|p| Box::new(p) as Box<dyn CallbackPlugin>)
+                .map_err(|e| format!("Failed to create tree callback: {}", e))
+        }
+
         "timer" => Ok(Box::new(TimerCallback::new())),
 
+        "profile_tasks" => {
+            let top = match args {
+                Some(n) => n.parse().map_err(|_| {
+                    format!(
+                        "profile_tasks expects a number of tasks to show (e.g., profile_tasks:20), got '{}'",
+                        n
+                    )
+                })?,
+                None => DEFAULT_PROFILE_TASKS,
+            };
+
+            Ok(Box::new(ProfileTasksCallback::new(top)))
+        }
+
         "webhook" => {
             let url = args.ok_or_else(|| {
                 "webhook callback requires a URL (e.g., webhook:https://example.com/events)"
@@ -594,11 +1016,18 @@ pub fn create_callback_plugin(spec: &str) -> Result<Box<dyn CallbackPlugin>, Str
         }
 
         "slack" => {
-            let webhook_url = args.ok_or_else(|| {
+            let args = args.ok_or_else(|| {
                 "slack callback requires a webhook URL (e.g., slack:https://hooks.slack.com/...)".to_string()
             })?;
-
-            Ok(Box::new(SlackCallback::new(webhook_url)))
+            // slack:failures:URL also posts every task failure as it happens
+            let (webhook_url, notify_failures) = match args.strip_prefix("failures:") {
+                Some(url) => (url, true),
+                None => (args, false),
+            };
+
+            Ok(Box::new(
+                SlackCallback::new(webhook_url).with_failure_notifications(notify_failures),
+            ))
         }
 
         _ => Err(format!("Unknown callback plugin: {}", name)),
@@ -633,10 +1062,167 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_profile_tasks_summary() {
+        let profile = ProfileTasksCallback::new(2);
+        let output = TaskOutput::success();
+        profile
+            .on_task_complete("web1", "Install", &output, Duration::from_secs(3))
+            .await;
+        profile
+            .on_task_complete("web2", "Install", &output, Duration::from_secs(5))
+            .await;
+        profile
+            .on_task_complete("web1", "Configure", &output, Duration::from_secs(1))
+            .await;
+        profile.on_task_start("web2", "Start").await;
+        profile.on_task_failed("web2", "Start", "boom").await;
+
+        let summary = profile.summary(Duration::from_secs(6));
+        assert!(summary.contains("slowest 2 of 4"));
+        let web2 = summary.find(" 1. web2").unwrap();
+        let web1 = summary.find(" 2. web1").unwrap();
+        assert!(web2 < web1);
+        assert!(!summary.contains("Configure"));
+        assert!(summary.contains("Playbook wall time:     6.00s"));
+        assert!(summary.contains("Summed task time:       9.00s (1.5x parallelism)"));
+
+        assert!(create_callback_plugin("profile_tasks:20").is_ok());
+        assert!(create_callback_plugin("profile_tasks:many").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_log_writes_one_event_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/nexus.jsonl");
+        let log = create_callback_plugin(&format!("json_log:{}", path.display())).unwrap();
+
+        log.on_playbook_start("site.nx.yml", &["web1".to_string()])
+            .await;
+        log.on_task_start("web1", "Install").await;
+        log.on_task_complete(
+            "web1",
+            "Install",
+            &TaskOutput::changed(),
+            Duration::from_millis(250),
+        )
+        .await;
+        log.on_task_start("web1", "Start").await;
+        log.on_task_failed("web1", "Start", "exit code 1").await;
+        log.on_task_skipped("web1", "Debug", "condition not met")
+            .await;
+        log.on_handler_start("web1", "restart nginx").await;
+        log.on_handler_complete("web1", "restart nginx", &TaskOutput::changed())
+            .await;
+        log.on_playbook_complete(&PlayRecap::new()).await;
+
+        // Flushed per event, so the file is complete before the callback is dropped
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let names: Vec<&str> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "playbook_start",
+                "task_start",
+                "task_complete",
+                "task_start",
+                "task_failed",
+                "task_skipped",
+                "handler_start",
+                "handler_complete",
+                "playbook_complete"
+            ]
+        );
+        assert_eq!(events[2]["changed"], json!(true));
+        assert_eq!(events[2]["duration_secs"], json!(0.25));
+        assert_eq!(events[4]["failed"], json!(true));
+        assert!(events[4]["duration_secs"].is_f64());
+        assert!(events[5]["duration_secs"].is_null());
+        assert!(events[7]["duration_secs"].is_f64());
+        assert!(events.iter().all(|e| e["timestamp"].is_string()));
+    }
+
+    #[test]
+    fn test_slack_summary_lists_hosts() {
+        let mut recap = PlayRecap::new();
+        recap.record(&crate::output::TaskResult::changed("web2", "Install"));
+        recap.record(&crate::output::TaskResult::ok("web1", "Install"));
+        recap.record(&crate::output::TaskResult::failed(
+            "web1",
+            "Start",
+            "exit code 1",
+        ));
+
+        let (color, message) = SlackCallback::summary(&recap);
+        assert_eq!(color, "danger");
+        assert!(message.starts_with("*Playbook execution FAILED*"));
+        assert!(message.contains("OK: 1 | Changed: 1 | Failed: 1 | Skipped: 0"));
+        let web1 = message
+            .find("`web1` ok=1 changed=0 failed=1 skipped=0")
+            .unwrap();
+        let web2 = message
+            .find("`web2` ok=0 changed=1 failed=0 skipped=0")
+            .unwrap();
+        assert!(web1 < web2);
+    }
+
+    #[tokio::test]
+    async fn test_notification_errors_do_not_fail() {
+        // Nothing listens on port 1; posting must only warn
+        let slack = create_callback_plugin("slack:failures:http://127.0.0.1:1/hook").unwrap();
+        slack.on_task_failed("web1", "Start", "exit code 1").await;
+        slack.on_playbook_complete(&PlayRecap::new()).await;
+
+        let webhook = create_callback_plugin("webhook:http://127.0.0.1:1/events").unwrap();
+        webhook.on_playbook_complete(&PlayRecap::new()).await;
+    }
+
     #[test]
     fn test_truncate() {
         assert_eq!(truncate("hello", 10), "hello");
         assert_eq!(truncate("hello world", 8), "hello...");
         assert_eq!(truncate("test", 4), "test");
     }
+
+    #[tokio::test]
+    async fn test_tree_writes_a_file_per_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree_dir = dir.path().join("tree");
+        let tree = create_callback_plugin(&format!("tree:{}", tree_dir.display())).unwrap();
+        assert!(tree_dir.is_dir());
+
+        let mut output = TaskOutput::changed();
+        output.stdout = "installed".to_string();
+        tree.on_task_start("web1", "Install").await;
+        tree.on_task_complete("web1", "Install", &output, Duration::from_millis(500))
+            .await;
+        tree.on_task_start("web1", "Start").await;
+        tree.on_task_failed("web1", "Start", "exit code 1").await;
+        tree.on_task_skipped("web2", "Install", "condition not met")
+            .await;
+
+        let read = |host: &str| -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(tree_dir.join(host)).unwrap()).unwrap()
+        };
+        let web1 = read("web1");
+        assert_eq!(web1["host"], json!("web1"));
+        let results = web1["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["task"], json!("Install"));
+        assert_eq!(results[0]["changed"], json!(true));
+        assert_eq!(results[0]["stdout"], json!("installed"));
+        assert_eq!(results[0]["duration_secs"], json!(0.5));
+        assert_eq!(results[1]["failed"], json!(true));
+        assert_eq!(results[1]["msg"], json!("exit code 1"));
+        assert_eq!(read("web2")["results"][0]["skipped"], json!(true));
+
+        assert!(create_callback_plugin("tree").is_err());
+    }
 }
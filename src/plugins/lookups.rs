@@ -1,13 +1,14 @@
 // Lookup plugins for fetching data from various sources during playbook execution
 
 use std::fs;
-use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
 
 use rand::distributions::{Alphanumeric, DistString};
 use rand::Rng;
 
 use crate::executor::ExecutionContext;
+use crate::modules::{render_template_file, TemplateEngine};
 use crate::output::errors::NexusError;
 use crate::parser::ast::Value;
 
@@ -30,6 +31,7 @@ pub fn lookup(
             suggestion: Some(
                 "Available lookups: file, env, pipe, password, template, first_found".to_string(),
             ),
+            location: None,
         }),
     }
 }
@@ -42,17 +44,19 @@ fn lookup_file(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(file)".to_string()),
             message: "file lookup requires a file path argument".to_string(),
             suggestion: Some("Example: lookup('file', '/path/to/file.txt')".to_string()),
+            location: None,
         });
     }
 
-    let path = args[0].to_string();
+    let path = expand_home(&args[0].to_string());
 
     fs::read_to_string(&path)
         .map(|content| Value::String(content.trim_end().to_string()))
         .map_err(|e| NexusError::Runtime {
             function: Some("lookup(file)".to_string()),
-            message: format!("Failed to read file '{}': {}", path, e),
+            message: format!("Failed to read file '{}': {}", path.display(), e),
             suggestion: Some("Check that the file exists and is readable".to_string()),
+            location: None,
         })
 }
 
@@ -64,6 +68,7 @@ fn lookup_env(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(env)".to_string()),
             message: "env lookup requires an environment variable name".to_string(),
             suggestion: Some("Example: lookup('env', 'HOME')".to_string()),
+            location: None,
         });
     }
 
@@ -75,6 +80,7 @@ fn lookup_env(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(env)".to_string()),
             message: format!("Environment variable '{}' not found", var_name),
             suggestion: Some("Check that the environment variable is set".to_string()),
+            location: None,
         })
 }
 
@@ -86,6 +92,7 @@ fn lookup_pipe(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(pipe)".to_string()),
             message: "pipe lookup requires a command string".to_string(),
             suggestion: Some("Example: lookup('pipe', 'date +%Y-%m-%d')".to_string()),
+            location: None,
         });
     }
 
@@ -99,6 +106,7 @@ fn lookup_pipe(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(pipe)".to_string()),
             message: format!("Failed to execute command '{}': {}", command, e),
             suggestion: Some("Check that the command is valid and executable".to_string()),
+            location: None,
         })?;
 
     if !output.status.success() {
@@ -107,6 +115,7 @@ fn lookup_pipe(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(pipe)".to_string()),
             message: format!("Command '{}' failed: {}", command, stderr),
             suggestion: None,
+            location: None,
         });
     }
 
@@ -125,6 +134,7 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
             suggestion: Some(
                 "Example: lookup('password', '/tmp/passwords/db_pass length=20')".to_string(),
             ),
+            location: None,
         });
     }
 
@@ -136,10 +146,12 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(password)".to_string()),
             message: "password lookup requires a file path".to_string(),
             suggestion: None,
+            location: None,
         });
     }
 
-    let file_path = parts[0];
+    let file_path = expand_home(parts[0]);
+    let file_path = file_path.as_path();
     let mut length = 16; // default length
     let mut use_special = false;
 
@@ -153,13 +165,18 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
     }
 
     // Check if password file exists
-    if Path::new(file_path).exists() {
+    if file_path.exists() {
         return fs::read_to_string(file_path)
             .map(|content| Value::String(content.trim().to_string()))
             .map_err(|e| NexusError::Runtime {
                 function: Some("lookup(password)".to_string()),
-                message: format!("Failed to read password file '{}': {}", file_path, e),
+                message: format!(
+                    "Failed to read password file '{}': {}",
+                    file_path.display(),
+                    e
+                ),
                 suggestion: None,
+                location: None,
             });
     }
 
@@ -171,19 +188,25 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
     };
 
     // Create parent directory if it doesn't exist
-    if let Some(parent) = Path::new(file_path).parent() {
+    if let Some(parent) = file_path.parent() {
         fs::create_dir_all(parent).map_err(|e| NexusError::Runtime {
             function: Some("lookup(password)".to_string()),
             message: format!("Failed to create password directory: {}", e),
             suggestion: None,
+            location: None,
         })?;
     }
 
     // Write password to file
     fs::write(file_path, &password).map_err(|e| NexusError::Runtime {
         function: Some("lookup(password)".to_string()),
-        message: format!("Failed to write password file '{}': {}", file_path, e),
+        message: format!(
+            "Failed to write password file '{}': {}",
+            file_path.display(),
+            e
+        ),
         suggestion: None,
+        location: None,
     })?;
 
     // Set file permissions to 0600 (owner read/write only)
@@ -195,6 +218,7 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
                 function: Some("lookup(password)".to_string()),
                 message: format!("Failed to get file metadata: {}", e),
                 suggestion: None,
+                location: None,
             })?
             .permissions();
         perms.set_mode(0o600);
@@ -202,6 +226,7 @@ fn lookup_password(args: &[Value]) -> Result<Value, NexusError> {
             function: Some("lookup(password)".to_string()),
             message: format!("Failed to set file permissions: {}", e),
             suggestion: None,
+            location: None,
         })?;
     }
 
@@ -222,47 +247,46 @@ fn generate_password_with_special(length: usize) -> String {
         .collect()
 }
 
-/// Render a template string
-/// Usage: lookup('template', 'Hello {{ name }}!')
+/// Render a template file on the control node, or a template string, with the
+/// task's variables
+/// Usage: lookup('template', 'templates/upstream.conf.j2')
+///        lookup('template', 'Hello {{ name }}!')
 fn lookup_template(args: &[Value], ctx: &ExecutionContext) -> Result<Value, NexusError> {
     if args.is_empty() {
         return Err(NexusError::Runtime {
             function: Some("lookup(template)".to_string()),
-            message: "template lookup requires a template string".to_string(),
-            suggestion: Some("Example: lookup('template', 'Hello {{ name }}!')".to_string()),
+            message: "template lookup requires a template file or string".to_string(),
+            suggestion: Some(
+                "Example: lookup('template', 'templates/upstream.conf.j2')".to_string(),
+            ),
+            location: None,
         });
     }
 
-    let template = args[0].to_string();
+    let source = args[0].to_string();
+    if source.contains("{{") || source.contains("{%") {
+        return TemplateEngine::new()
+            .render(&source, ctx)
+            .map(Value::String);
+    }
 
-    // Simple template rendering - replace {{ var }} with variable values
-    let mut result = template.clone();
-    let re =
-        regex::Regex::new(r"\{\{\s*([a-zA-Z_][a-zA-Z0-9_]*(?:\.[a-zA-Z_][a-zA-Z0-9_]*)*)\s*\}\}")
-            .map_err(|e| NexusError::Runtime {
+    let path = expand_home(&source);
+    if !path.is_file() {
+        return Err(NexusError::Runtime {
             function: Some("lookup(template)".to_string()),
-            message: format!("Regex error: {}", e),
-            suggestion: None,
-        })?;
-
-    for cap in re.captures_iter(&template.clone()) {
-        let full_match = &cap[0];
-        let var_name = &cap[1];
-
-        // Parse nested variable path
-        let path: Vec<String> = var_name.split('.').map(|s| s.to_string()).collect();
-        let value = ctx
-            .get_nested_var(&path)
-            .unwrap_or_else(|| Value::String(String::new()));
-
-        result = result.replace(full_match, &value.to_string());
+            message: format!("Template file not found: {}", path.display()),
+            suggestion: Some(
+                "Relative paths are relative to the directory nexus runs in".to_string(),
+            ),
+            location: None,
+        });
     }
-
-    Ok(Value::String(result))
+    render_template_file(&path, ctx).map(Value::String)
 }
 
-/// Return the first file that exists from a list
+/// Return the first file that exists from a list, or from several arguments
 /// Usage: lookup('first_found', ['config.local.yml', 'config.yml', 'defaults.yml'])
+///        lookup('first_found', 'config.local.yml', 'config.yml')
 fn lookup_first_found(args: &[Value]) -> Result<Value, NexusError> {
     if args.is_empty() {
         return Err(NexusError::Runtime {
@@ -271,24 +295,30 @@ fn lookup_first_found(args: &[Value]) -> Result<Value, NexusError> {
             suggestion: Some(
                 "Example: lookup('first_found', ['config.local.yml', 'config.yml'])".to_string(),
             ),
+            location: None,
         });
     }
 
-    let paths = match &args[0] {
-        Value::List(list) => list.iter().map(|v| v.to_string()).collect::<Vec<String>>(),
-        Value::String(s) => vec![s.clone()],
-        _ => {
-            return Err(NexusError::Runtime {
-                function: Some("lookup(first_found)".to_string()),
-                message: "first_found lookup requires a list or string argument".to_string(),
-                suggestion: None,
-            })
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg {
+            Value::List(list) => paths.extend(list.iter().map(|v| v.to_string())),
+            Value::String(s) => paths.push(s.clone()),
+            _ => {
+                return Err(NexusError::Runtime {
+                    function: Some("lookup(first_found)".to_string()),
+                    message: "first_found lookup requires a list or string argument".to_string(),
+                    suggestion: None,
+                    location: None,
+                })
+            }
         }
-    };
+    }
 
     for path in paths {
-        if Path::new(&path).exists() {
-            return Ok(Value::String(path));
+        let path = expand_home(&path);
+        if path.exists() {
+            return Ok(Value::String(path.to_string_lossy().into_owned()));
         }
     }
 
@@ -296,14 +326,26 @@ fn lookup_first_found(args: &[Value]) -> Result<Value, NexusError> {
         function: Some("lookup(first_found)".to_string()),
         message: "No files found from the provided list".to_string(),
         suggestion: Some("Check that at least one file in the list exists".to_string()),
+        location: None,
     })
 }
 
+/// A control node path with a leading `~` replaced by the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::inventory::Host;
     use std::collections::HashMap;
+    use std::path::Path;
     use std::sync::Arc;
 
     fn create_test_context() -> ExecutionContext {
@@ -327,6 +369,18 @@ mod tests {
         let args = vec![Value::String("Hello {{ name }}!".to_string())];
         let result = lookup_template(&args, &ctx).unwrap();
         assert_eq!(result, Value::String("Hello World!".to_string()));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("greeting.j2"), "{% include 'name.j2' %}!").unwrap();
+        fs::write(dir.path().join("name.j2"), "Hello {{ name | upper }}").unwrap();
+        let args = vec![Value::String(
+            dir.path().join("greeting.j2").to_string_lossy().to_string(),
+        )];
+        let result = lookup_template(&args, &ctx).unwrap();
+        assert_eq!(result, Value::String("Hello WORLD!".to_string()));
+
+        let args = vec![Value::String("missing.j2".to_string())];
+        assert!(lookup_template(&args, &ctx).is_err());
     }
 
     #[test]
@@ -349,6 +403,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lookup_first_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let defaults = dir.path().join("defaults.yml");
+        fs::write(&defaults, "port: 80\n").unwrap();
+        let missing = dir.path().join("local.yml").to_string_lossy().to_string();
+        let defaults = defaults.to_string_lossy().to_string();
+
+        let list = vec![Value::List(vec![
+            Value::String(missing.clone()),
+            Value::String(defaults.clone()),
+        ])];
+        assert_eq!(
+            lookup_first_found(&list).unwrap(),
+            Value::String(defaults.clone())
+        );
+
+        let separate = vec![
+            Value::String(missing.clone()),
+            Value::String(defaults.clone()),
+        ];
+        assert_eq!(
+            lookup_first_found(&separate).unwrap(),
+            Value::String(defaults)
+        );
+
+        assert!(lookup_first_found(&[Value::String(missing)]).is_err());
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            expand_home("~/.ssh/id_ed25519.pub"),
+            Path::new(&home).join(".ssh/id_ed25519.pub")
+        );
+        assert_eq!(expand_home("~other/key"), PathBuf::from("~other/key"));
+        assert_eq!(expand_home("/etc/motd"), PathBuf::from("/etc/motd"));
+    }
+
     #[test]
     fn test_main_lookup_dispatch() {
         std::env::set_var("TEST_LOOKUP_VAR", "dispatch_test");
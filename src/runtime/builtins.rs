@@ -2,9 +2,15 @@
 
 use std::collections::HashMap;
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use openssl::hash::MessageDigest;
+
+use super::crypt::{self, CryptScheme};
 use crate::executor::ExecutionContext;
+use crate::modules::TemplateEngine;
 use crate::output::errors::NexusError;
-use crate::parser::ast::{Expression, Value};
+use crate::parser::ast::{BinaryOperator, Expression, Value};
 
 /// Call a built-in function
 pub fn call_builtin(
@@ -33,10 +39,20 @@ pub fn call_builtin(
         "any" => builtin_any(args),
         "all" => builtin_all(args),
         "print" => builtin_print(args),
+        "regex_replace" => builtin_regex_replace(args, kwargs),
+        "regex_search" => builtin_regex_search(args, kwargs),
+        "regex_findall" => builtin_regex_findall(args, kwargs),
+        "b64encode" => builtin_b64encode(args),
+        "b64decode" => builtin_b64decode(args),
+        "hash" => builtin_hash(args),
+        "password_hash" => builtin_password_hash(args, kwargs),
+        "dict2items" => builtin_dict2items(args, kwargs),
+        "items2dict" => builtin_items2dict(args, kwargs),
         _ => Err(NexusError::Runtime {
             function: Some(name.to_string()),
             message: format!("Unknown function: {}", name),
             suggestion: Some("Check function name and available builtins".to_string()),
+            location: None,
         }),
     }
 }
@@ -60,6 +76,7 @@ fn builtin_lookup(args: Vec<Value>, ctx: &ExecutionContext) -> Result<Value, Nex
             function: Some("lookup".to_string()),
             message: "lookup requires at least one argument (lookup type)".to_string(),
             suggestion: Some("Example: lookup('env', 'HOME')".to_string()),
+            location: None,
         });
     }
 
@@ -70,6 +87,7 @@ fn builtin_lookup(args: Vec<Value>, ctx: &ExecutionContext) -> Result<Value, Nex
                 function: Some("lookup".to_string()),
                 message: "First argument to lookup must be a string (lookup type)".to_string(),
                 suggestion: None,
+                location: None,
             })
         }
     };
@@ -82,8 +100,15 @@ pub fn call_method(
     obj: &Value,
     method: &str,
     args: Vec<Value>,
-    _kwargs: HashMap<String, Value>,
+    kwargs: HashMap<String, Value>,
 ) -> Result<Value, NexusError> {
+    // The Ansible converter emits filters as methods, e.g. `users.dict2items()`
+    if matches!(method, "dict2items" | "items2dict") {
+        let mut call_args = vec![obj.clone()];
+        call_args.extend(args);
+        return call_builtin(method, call_args, kwargs);
+    }
+
     match obj {
         Value::String(s) => call_string_method(s, method, args),
         Value::List(l) => call_list_method(l, method, args),
@@ -92,38 +117,160 @@ pub fn call_method(
             function: Some(method.to_string()),
             message: format!("Cannot call method '{}' on {:?}", method, obj),
             suggestion: None,
+            location: None,
         }),
     }
 }
 
+/// An argument passed to a filter: a plain value, or a lambda such as
+/// `x => x.active` for higher-order filters like `map` and `select`
+#[derive(Debug, Clone)]
+pub enum FilterArg<'a> {
+    Value(Value),
+    Lambda {
+        params: &'a [String],
+        body: &'a Expression,
+    },
+}
+
+impl FilterArg<'_> {
+    fn as_value(&self) -> Option<&Value> {
+        match self {
+            FilterArg::Value(v) => Some(v),
+            FilterArg::Lambda { .. } => None,
+        }
+    }
+}
+
+/// A lambda bound to the context it was written in.
+///
+/// Parameters are set in a private copy of the context, so the body sees every
+/// variable of the enclosing scope and binding them does not leak out.
+struct Closure<'a> {
+    params: &'a [String],
+    body: &'a Expression,
+    scope: ExecutionContext,
+}
+
+impl<'a> Closure<'a> {
+    fn new(params: &'a [String], body: &'a Expression, ctx: &ExecutionContext) -> Self {
+        Closure {
+            params,
+            body,
+            scope: ctx.clone_for_task(),
+        }
+    }
+
+    fn call(&mut self, args: &[Value]) -> Result<Value, NexusError> {
+        for (param, value) in self.params.iter().zip(args) {
+            if param == "item" {
+                self.scope.loop_item = Some(value.clone());
+            }
+            self.scope.set_var(param.clone(), value.clone());
+        }
+        super::evaluate_expression(self.body, &self.scope)
+    }
+}
+
 /// Apply a filter to a value
 pub fn apply_filter(
     input: &Value,
     filter_name: &str,
-    predicate: Option<&Expression>,
-    _ctx: &ExecutionContext,
+    args: &[FilterArg],
+    kwargs: &HashMap<String, Value>,
+    ctx: &ExecutionContext,
 ) -> Result<Value, NexusError> {
     match filter_name {
-        "filter" => {
+        "filter" | "select" | "reject" => {
             let list = match input {
                 Value::List(l) => l,
                 _ => return Err(filter_type_error(filter_name, "list", input)),
             };
-
-            // Without a predicate, filter out falsy values
-            if predicate.is_none() {
-                let filtered: Vec<Value> = list.iter().filter(|v| v.is_truthy()).cloned().collect();
-                return Ok(Value::List(filtered));
+            let keep = filter_name != "reject";
+            let mut result = Vec::new();
+            let mut matcher = Matcher::new(filter_name, args, ctx)?;
+            for item in list {
+                if matcher.matches(Some(item))? == keep {
+                    result.push(item.clone());
+                }
             }
+            Ok(Value::List(result))
+        }
 
-            // With predicate, we'd need lambda evaluation
-            // For now, just return the list
-            Ok(input.clone())
+        "selectattr" | "rejectattr" => {
+            let list = match input {
+                Value::List(l) => l,
+                _ => return Err(filter_type_error(filter_name, "list", input)),
+            };
+            let attr = match args.first().and_then(FilterArg::as_value) {
+                Some(Value::String(a)) => a,
+                _ => {
+                    return Err(NexusError::Runtime {
+                        function: Some(filter_name.to_string()),
+                        message: format!("{} requires an attribute name", filter_name),
+                        suggestion: Some(format!(
+                            "Example: {}('state', 'equalto', 'running')",
+                            filter_name
+                        )),
+                        location: None,
+                    })
+                }
+            };
+            let keep = filter_name == "selectattr";
+            let mut result = Vec::new();
+            let mut matcher = Matcher::new(filter_name, &args[1..], ctx)?;
+            for item in list {
+                let value = lookup_attribute(item, attr);
+                if matcher.matches(value.as_ref())? == keep {
+                    result.push(item.clone());
+                }
+            }
+            Ok(Value::List(result))
         }
 
         "map" => {
-            // Similar to filter, needs lambda support
-            Ok(input.clone())
+            let list = match input {
+                Value::List(l) => l,
+                _ => return Err(filter_type_error(filter_name, "list", input)),
+            };
+
+            if let Some(attribute) = kwargs.get("attribute") {
+                let attr = attribute.to_string();
+                let default = kwargs.get("default").cloned().unwrap_or(Value::Null);
+                return Ok(Value::List(
+                    list.iter()
+                        .map(|item| lookup_attribute(item, &attr).unwrap_or(default.clone()))
+                        .collect(),
+                ));
+            }
+
+            match args.first() {
+                Some(FilterArg::Lambda { params, body }) => {
+                    let mut closure = Closure::new(params, body, ctx);
+                    let mapped: Result<Vec<Value>, NexusError> = list
+                        .iter()
+                        .map(|item| closure.call(std::slice::from_ref(item)))
+                        .collect();
+                    Ok(Value::List(mapped?))
+                }
+                // map('upper'), map('regex_replace', 'a', 'b'): apply a filter to each item
+                Some(FilterArg::Value(Value::String(name))) => {
+                    let mapped: Result<Vec<Value>, NexusError> = list
+                        .iter()
+                        .map(|item| apply_filter(item, name, &args[1..], kwargs, ctx))
+                        .collect();
+                    Ok(Value::List(mapped?))
+                }
+                _ => Err(NexusError::Runtime {
+                    function: Some("map".to_string()),
+                    message: "map requires a lambda, a filter name or attribute=".to_string(),
+                    suggestion: Some(
+                        "Examples: map(x => x.name), map('upper'), map(attribute='name')"
+                            .to_string(),
+                    ),
+                    location: None,
+                }),
+            }
         }
 
         "first" => match input {
@@ -131,6 +278,7 @@ pub fn apply_filter(
                 function: None,
                 message: "Cannot get first element of empty list".to_string(),
                 suggestion: None,
+                location: None,
             }),
             _ => Err(filter_type_error(filter_name, "list", input)),
         },
@@ -140,6 +288,7 @@ pub fn apply_filter(
                 function: None,
                 message: "Cannot get last element of empty list".to_string(),
                 suggestion: None,
+                location: None,
             }),
             _ => Err(filter_type_error(filter_name, "list", input)),
         },
@@ -161,15 +310,10 @@ pub fn apply_filter(
 
         "join" => match input {
             Value::List(l) => {
-                let sep = predicate
-                    .and_then(|p| {
-                        if let Expression::String(s) = p {
-                            Some(s.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or_else(|| ",".to_string());
+                let sep = match args.first().and_then(FilterArg::as_value) {
+                    Some(Value::String(s)) => s.clone(),
+                    _ => ",".to_string(),
+                };
 
                 let joined: String = l.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(&sep);
                 Ok(Value::String(joined))
@@ -179,15 +323,10 @@ pub fn apply_filter(
 
         "split" => match input {
             Value::String(s) => {
-                let sep = predicate
-                    .and_then(|p| {
-                        if let Expression::String(sp) = p {
-                            Some(sp.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or_else(|| " ".to_string());
+                let sep = match args.first().and_then(FilterArg::as_value) {
+                    Some(Value::String(sp)) => sp.clone(),
+                    _ => " ".to_string(),
+                };
 
                 let parts: Vec<Value> = s.split(&sep).map(|p| Value::String(p.to_string())).collect();
                 Ok(Value::List(parts))
@@ -210,16 +349,24 @@ pub fn apply_filter(
             _ => Err(filter_type_error(filter_name, "string", input)),
         },
 
-        "replace" => {
-            // Would need two args from predicate
-            Ok(input.clone())
-        }
+        "replace" => match (input, filter_values(filter_name, args)?.as_slice()) {
+            (Value::String(s), [Value::String(from), Value::String(to), ..]) => {
+                Ok(Value::String(s.replace(from.as_str(), to)))
+            }
+            (Value::String(_), _) => Err(NexusError::Runtime {
+                function: Some("replace".to_string()),
+                message: "replace filter requires 2 arguments".to_string(),
+                suggestion: Some("Use: value | replace('old', 'new')".to_string()),
+                location: None,
+            }),
+            _ => Err(filter_type_error(filter_name, "string", input)),
+        },
 
         "default" => {
             if input.is_truthy() {
                 Ok(input.clone())
-            } else if let Some(Expression::String(default)) = predicate {
-                Ok(Value::String(default.clone()))
+            } else if let Some(default) = args.first().and_then(FilterArg::as_value) {
+                Ok(default.clone())
             } else {
                 Ok(Value::String(String::new()))
             }
@@ -234,6 +381,7 @@ pub fn apply_filter(
                     function: None,
                     message: format!("Cannot convert '{}' to int", s),
                     suggestion: None,
+                    location: None,
                 }),
             Value::Float(f) => Ok(Value::Int(*f as i64)),
             Value::Int(i) => Ok(Value::Int(*i)),
@@ -249,6 +397,7 @@ pub fn apply_filter(
                     function: None,
                     message: format!("Cannot convert '{}' to float", s),
                     suggestion: None,
+                    location: None,
                 }),
             Value::Int(i) => Ok(Value::Float(*i as f64)),
             Value::Float(f) => Ok(Value::Float(*f)),
@@ -289,10 +438,189 @@ pub fn apply_filter(
             _ => Err(filter_type_error(filter_name, "dict", input)),
         },
 
+        // Render a string as a template with the task's variables
+        "from_template" => match input {
+            Value::String(s) => TemplateEngine::new().render(s, ctx).map(Value::String),
+            _ => Err(filter_type_error(filter_name, "string", input)),
+        },
+
+        // Functions that double as filters: `x | f(a)` is `f(x, a)`
+        "regex_replace" | "regex_search" | "regex_findall" | "b64encode" | "b64decode"
+        | "hash" | "password_hash" | "dict2items" | "items2dict" => {
+            let mut call_args = vec![input.clone()];
+            call_args.extend(filter_values(filter_name, args)?);
+            call_builtin(filter_name, call_args, kwargs.clone())
+        }
+
         _ => Err(NexusError::Runtime {
             function: None,
             message: format!("Unknown filter: {}", filter_name),
-            suggestion: Some("Available filters: filter, map, first, last, unique, join, split, upper, lower, trim, default, int, float, length, keys, values, items".to_string()),
+            suggestion: Some("Available filters: filter, select, reject, selectattr, rejectattr, map, first, last, unique, join, split, upper, lower, trim, replace, default, int, float, length, keys, values, items, from_template, regex_replace, regex_search, regex_findall, b64encode, b64decode, hash, password_hash, dict2items, items2dict".to_string()),
+            location: None,
+        }),
+    }
+}
+
+/// Filter arguments as plain values, rejecting lambdas
+fn filter_values(filter_name: &str, args: &[FilterArg]) -> Result<Vec<Value>, NexusError> {
+    args.iter()
+        .map(|arg| {
+            arg.as_value().cloned().ok_or_else(|| NexusError::Runtime {
+                function: Some(filter_name.to_string()),
+                message: format!("Filter '{}' does not accept a lambda", filter_name),
+                suggestion: None,
+                location: None,
+            })
+        })
+        .collect()
+}
+
+/// Look up a dotted attribute path (`a.b.0`) in a value
+fn lookup_attribute(value: &Value, path: &str) -> Option<Value> {
+    let mut current = value.clone();
+    for part in path.split('.') {
+        current = match current {
+            Value::Dict(ref map) => map.get(part).cloned()?,
+            Value::List(ref list) => list.get(part.parse::<usize>().ok()?).cloned()?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// The predicate of select/reject/selectattr/rejectattr: a lambda, a named
+/// Jinja2 test with its arguments, or plain truthiness
+enum Matcher<'a> {
+    Truthy,
+    Lambda(Box<Closure<'a>>),
+    Test { name: String, args: Vec<Value> },
+}
+
+impl<'a> Matcher<'a> {
+    fn new(
+        filter_name: &str,
+        args: &'a [FilterArg<'a>],
+        ctx: &ExecutionContext,
+    ) -> Result<Self, NexusError> {
+        match args.first() {
+            None => Ok(Matcher::Truthy),
+            Some(FilterArg::Lambda { params, body }) => {
+                Ok(Matcher::Lambda(Box::new(Closure::new(params, body, ctx))))
+            }
+            Some(FilterArg::Value(Value::String(name))) => Ok(Matcher::Test {
+                name: name.clone(),
+                args: filter_values(filter_name, &args[1..])?,
+            }),
+            Some(FilterArg::Value(other)) => Err(NexusError::Runtime {
+                function: Some(filter_name.to_string()),
+                message: format!("Expected a test name or lambda, got {:?}", other),
+                suggestion: Some("Examples: select('defined'), select(x => x > 1)".to_string()),
+                location: None,
+            }),
+        }
+    }
+
+    /// Whether `value` passes; `None` means the value (or attribute) is undefined
+    fn matches(&mut self, value: Option<&Value>) -> Result<bool, NexusError> {
+        match self {
+            Matcher::Truthy => Ok(value.is_some_and(Value::is_truthy)),
+            Matcher::Lambda(closure) => {
+                let arg = value.cloned().unwrap_or(Value::Null);
+                Ok(closure.call(std::slice::from_ref(&arg))?.is_truthy())
+            }
+            Matcher::Test { name, args } => run_test(name, value, args),
+        }
+    }
+}
+
+/// Evaluate a Jinja2-style test such as `equalto`, `match` or `defined`
+fn run_test(name: &str, value: Option<&Value>, args: &[Value]) -> Result<bool, NexusError> {
+    let missing_arg = || NexusError::Runtime {
+        function: Some(name.to_string()),
+        message: format!("Test '{}' requires an argument", name),
+        suggestion: None,
+        location: None,
+    };
+    let compare = |op: BinaryOperator| -> Result<bool, NexusError> {
+        let arg = args.first().ok_or_else(missing_arg)?;
+        match value {
+            Some(v) => Ok(super::evaluate_binary_op(v, &op, arg)?.is_truthy()),
+            None => Ok(false),
+        }
+    };
+
+    match name {
+        "defined" => Ok(value.is_some()),
+        "undefined" => Ok(value.is_none()),
+        "none" => Ok(matches!(value, None | Some(Value::Null))),
+        "truthy" => Ok(value.is_some_and(Value::is_truthy)),
+        "falsy" => Ok(!value.is_some_and(Value::is_truthy)),
+        "true" => Ok(matches!(value, Some(Value::Bool(true)))),
+        "false" => Ok(matches!(value, Some(Value::Bool(false)))),
+        "equalto" | "eq" | "==" | "sameas" => compare(BinaryOperator::Eq),
+        "ne" | "!=" => compare(BinaryOperator::Ne),
+        "gt" | "greaterthan" | ">" => compare(BinaryOperator::Gt),
+        "ge" | ">=" => compare(BinaryOperator::Ge),
+        "lt" | "lessthan" | "<" => compare(BinaryOperator::Lt),
+        "le" | "<=" => compare(BinaryOperator::Le),
+        "in" => compare(BinaryOperator::In),
+        "contains" => {
+            let arg = args.first().ok_or_else(missing_arg)?;
+            match value {
+                Some(v) => Ok(super::evaluate_binary_op(arg, &BinaryOperator::In, v)?.is_truthy()),
+                None => Ok(false),
+            }
+        }
+        "match" | "search" | "regex" => {
+            let pattern = match args.first() {
+                Some(Value::String(p)) => p,
+                _ => return Err(missing_arg()),
+            };
+            // `match` is anchored at the start like Python's re.match
+            let pattern = if name == "match" {
+                format!("^(?:{})", pattern)
+            } else {
+                pattern.clone()
+            };
+            let re = compile_regex(name, &pattern, "")?;
+            Ok(match value {
+                Some(Value::String(s)) => re.is_match(s),
+                Some(Value::Null) | None => false,
+                Some(other) => re.is_match(&other.to_string()),
+            })
+        }
+        "string" => Ok(matches!(value, Some(Value::String(_)))),
+        "number" => Ok(matches!(value, Some(Value::Int(_) | Value::Float(_)))),
+        "integer" => Ok(matches!(value, Some(Value::Int(_)))),
+        "float" => Ok(matches!(value, Some(Value::Float(_)))),
+        "boolean" => Ok(matches!(value, Some(Value::Bool(_)))),
+        "mapping" => Ok(matches!(value, Some(Value::Dict(_)))),
+        "sequence" | "iterable" => Ok(matches!(
+            value,
+            Some(Value::List(_) | Value::String(_) | Value::Dict(_))
+        )),
+        "even" | "odd" | "divisibleby" => {
+            let n = match value {
+                Some(Value::Int(n)) => *n,
+                _ => return Ok(false),
+            };
+            Ok(match name {
+                "even" => n % 2 == 0,
+                "odd" => n % 2 != 0,
+                _ => {
+                    let d = get_int(args.first().ok_or_else(missing_arg)?)?;
+                    d != 0 && n % d == 0
+                }
+            })
+        }
+        _ => Err(NexusError::Runtime {
+            function: Some(name.to_string()),
+            message: format!("Unknown test: {}", name),
+            suggestion: Some(
+                "Available tests: defined, undefined, none, truthy, falsy, equalto, ne, gt, ge, lt, le, in, contains, match, search, string, number, mapping, sequence, even, odd, divisibleby"
+                    .to_string(),
+            ),
+            location: None,
         }),
     }
 }
@@ -327,6 +655,7 @@ fn builtin_int(args: Vec<Value>) -> Result<Value, NexusError> {
                     function: Some("int".to_string()),
                     message: format!("Cannot convert '{}' to int", s),
                     suggestion: None,
+                    location: None,
                 })
         }
         Value::Bool(b) => Ok(Value::Int(if *b { 1 } else { 0 })),
@@ -347,6 +676,7 @@ fn builtin_float(args: Vec<Value>) -> Result<Value, NexusError> {
                     function: Some("float".to_string()),
                     message: format!("Cannot convert '{}' to float", s),
                     suggestion: None,
+                    location: None,
                 })
         }
         _ => Err(arg_type_error("float", 0, "number/string", &args[0])),
@@ -404,6 +734,7 @@ fn builtin_range(args: Vec<Value>) -> Result<Value, NexusError> {
                 function: Some("range".to_string()),
                 message: "range takes 1-3 arguments".to_string(),
                 suggestion: None,
+                location: None,
             })
         }
     };
@@ -413,6 +744,7 @@ fn builtin_range(args: Vec<Value>) -> Result<Value, NexusError> {
             function: Some("range".to_string()),
             message: "range step cannot be zero".to_string(),
             suggestion: None,
+            location: None,
         });
     }
 
@@ -441,6 +773,7 @@ fn builtin_min(args: Vec<Value>) -> Result<Value, NexusError> {
             function: Some("min".to_string()),
             message: "min requires at least one argument".to_string(),
             suggestion: None,
+            location: None,
         });
     }
 
@@ -468,6 +801,7 @@ fn builtin_max(args: Vec<Value>) -> Result<Value, NexusError> {
             function: Some("max".to_string()),
             message: "max requires at least one argument".to_string(),
             suggestion: None,
+            location: None,
         });
     }
 
@@ -509,6 +843,7 @@ fn builtin_sum(args: Vec<Value>) -> Result<Value, NexusError> {
                             function: Some("sum".to_string()),
                             message: "sum requires a list of numbers".to_string(),
                             suggestion: None,
+                            location: None,
                         })
                     }
                 }
@@ -596,6 +931,7 @@ fn builtin_zip(args: Vec<Value>) -> Result<Value, NexusError> {
             function: Some("zip".to_string()),
             message: "zip requires at least 2 arguments".to_string(),
             suggestion: None,
+            location: None,
         });
     }
 
@@ -607,6 +943,7 @@ fn builtin_zip(args: Vec<Value>) -> Result<Value, NexusError> {
                 function: Some("zip".to_string()),
                 message: "zip requires list arguments".to_string(),
                 suggestion: None,
+                location: None,
             }),
         })
         .collect();
@@ -643,6 +980,440 @@ fn builtin_print(args: Vec<Value>) -> Result<Value, NexusError> {
     Ok(Value::Null)
 }
 
+// Regex functions
+//
+// These follow the Jinja2/Ansible filters of the same name, so
+// `value | regex_replace('a', 'b')` in a template is `regex_replace(value, 'a', 'b')`.
+// A trailing flags string ("i", "m", "s", "x") or the `ignorecase` / `multiline`
+// keyword arguments adjust matching.
+
+fn builtin_regex_replace(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("regex_replace", &args, 2)?;
+    let input = text_value(&args[0]);
+    let pattern = string_arg("regex_replace", &args, 1)?;
+    let replacement = match args.get(2) {
+        Some(_) => string_arg("regex_replace", &args, 2)?,
+        None => "",
+    };
+    let flags = regex_flags("regex_replace", args.get(3), &kwargs)?;
+
+    let re = compile_regex("regex_replace", pattern, &flags)?;
+    let replacement = python_replacement(replacement);
+    Ok(Value::String(
+        re.replace_all(&input, replacement.as_str()).into_owned(),
+    ))
+}
+
+fn builtin_regex_search(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("regex_search", &args, 2)?;
+    let input = text_value(&args[0]);
+    let pattern = string_arg("regex_search", &args, 1)?;
+
+    // Extra arguments are either group references ('\1', '\g<name>') or flags
+    let mut groups = Vec::new();
+    let mut flag_arg = None;
+    for (idx, arg) in args.iter().enumerate().skip(2) {
+        match arg {
+            Value::String(s) if s.starts_with('\\') => groups.push(group_reference(s)),
+            _ if flag_arg.is_none() => flag_arg = Some(arg),
+            _ => return Err(arg_type_error("regex_search", idx, "group reference", arg)),
+        }
+    }
+    let flags = regex_flags("regex_search", flag_arg, &kwargs)?;
+    let re = compile_regex("regex_search", pattern, &flags)?;
+
+    let Some(caps) = re.captures(&input) else {
+        return Ok(Value::Null);
+    };
+
+    if groups.is_empty() {
+        return Ok(Value::String(caps[0].to_string()));
+    }
+
+    Ok(Value::List(
+        groups
+            .iter()
+            .map(|group| {
+                let m = match group.parse::<usize>() {
+                    Ok(idx) => caps.get(idx),
+                    Err(_) => caps.name(group),
+                };
+                m.map(|m| Value::String(m.as_str().to_string()))
+                    .unwrap_or(Value::Null)
+            })
+            .collect(),
+    ))
+}
+
+fn builtin_regex_findall(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("regex_findall", &args, 2)?;
+    let input = text_value(&args[0]);
+    let pattern = string_arg("regex_findall", &args, 1)?;
+    let flags = regex_flags("regex_findall", args.get(2), &kwargs)?;
+    let re = compile_regex("regex_findall", pattern, &flags)?;
+
+    // Like Python's re.findall: whole matches without groups, the group with
+    // one group, and a list of groups with several
+    let group_value = |m: Option<regex::Match>| {
+        Value::String(m.map(|m| m.as_str().to_string()).unwrap_or_default())
+    };
+    let matches = re
+        .captures_iter(&input)
+        .map(|caps| match caps.len() {
+            1 => group_value(caps.get(0)),
+            2 => group_value(caps.get(1)),
+            n => Value::List((1..n).map(|i| group_value(caps.get(i))).collect()),
+        })
+        .collect();
+    Ok(Value::List(matches))
+}
+
+/// The text a string function operates on
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn string_arg<'a>(func: &str, args: &'a [Value], idx: usize) -> Result<&'a str, NexusError> {
+    match &args[idx] {
+        Value::String(s) => Ok(s),
+        other => Err(arg_type_error(func, idx, "string", other)),
+    }
+}
+
+/// Collect inline flags from a flags string and the ignorecase/multiline kwargs
+fn regex_flags(
+    func: &str,
+    flags: Option<&Value>,
+    kwargs: &HashMap<String, Value>,
+) -> Result<String, NexusError> {
+    let mut result = String::new();
+    match flags {
+        None | Some(Value::Null) => {}
+        Some(Value::String(s)) => {
+            for c in s.chars() {
+                match c.to_ascii_lowercase() {
+                    c @ ('i' | 'm' | 's' | 'x') => result.push(c),
+                    _ => {
+                        return Err(NexusError::Runtime {
+                            function: Some(func.to_string()),
+                            message: format!("Unknown regex flag '{}'", c),
+                            suggestion: Some("Supported flags: i, m, s, x".to_string()),
+                            location: None,
+                        })
+                    }
+                }
+            }
+        }
+        Some(other) => return Err(arg_type_error(func, 2, "flags string", other)),
+    }
+
+    if kwargs.get("ignorecase").is_some_and(|v| v.is_truthy()) {
+        result.push('i');
+    }
+    if kwargs.get("multiline").is_some_and(|v| v.is_truthy()) {
+        result.push('m');
+    }
+    Ok(result)
+}
+
+fn compile_regex(func: &str, pattern: &str, flags: &str) -> Result<regex::Regex, NexusError> {
+    let pattern = if flags.is_empty() {
+        pattern.to_string()
+    } else {
+        format!("(?{}){}", flags, pattern)
+    };
+    regex::Regex::new(&pattern).map_err(|e| NexusError::Runtime {
+        function: Some(func.to_string()),
+        message: format!("Invalid regex '{}': {}", pattern, e),
+        suggestion: None,
+        location: None,
+    })
+}
+
+/// Translate a Python-style replacement (`\1`, `\g<name>`) into the `${1}` form
+/// used by the regex crate, escaping literal `$` along the way
+fn python_replacement(replacement: &str) -> String {
+    let mut result = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => result.push_str("$$"),
+            '\\' => match chars.peek().copied() {
+                Some(d) if d.is_ascii_digit() => {
+                    let mut group = String::new();
+                    while let Some(d) = chars.peek().copied().filter(char::is_ascii_digit) {
+                        group.push(d);
+                        chars.next();
+                        if group.len() == 2 {
+                            break;
+                        }
+                    }
+                    result.push_str(&format!("${{{}}}", group));
+                }
+                Some('g') => {
+                    chars.next();
+                    let mut group = String::new();
+                    if chars.peek() == Some(&'<') {
+                        chars.next();
+                        for d in chars.by_ref() {
+                            if d == '>' {
+                                break;
+                            }
+                            group.push(d);
+                        }
+                        result.push_str(&format!("${{{}}}", group));
+                    } else {
+                        result.push_str("\\g");
+                    }
+                }
+                Some('\\') => {
+                    chars.next();
+                    result.push('\\');
+                }
+                Some('n') => {
+                    chars.next();
+                    result.push('\n');
+                }
+                Some('t') => {
+                    chars.next();
+                    result.push('\t');
+                }
+                _ => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Group name or number from a `\1` / `\g<name>` reference
+fn group_reference(reference: &str) -> String {
+    let reference = reference.trim_start_matches('\\');
+    reference
+        .strip_prefix("g<")
+        .and_then(|r| r.strip_suffix('>'))
+        .unwrap_or(reference)
+        .to_string()
+}
+
+// Encoding and hashing functions
+
+fn builtin_b64encode(args: Vec<Value>) -> Result<Value, NexusError> {
+    require_args("b64encode", &args, 1)?;
+    Ok(Value::String(
+        BASE64.encode(text_value(&args[0]).as_bytes()),
+    ))
+}
+
+fn builtin_b64decode(args: Vec<Value>) -> Result<Value, NexusError> {
+    require_args("b64decode", &args, 1)?;
+    let encoded = string_arg("b64decode", &args, 0)?;
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| NexusError::Runtime {
+            function: Some("b64decode".to_string()),
+            message: format!("Invalid base64 input: {}", e),
+            suggestion: None,
+            location: None,
+        })?;
+    String::from_utf8(bytes)
+        .map(Value::String)
+        .map_err(|_| NexusError::Runtime {
+            function: Some("b64decode".to_string()),
+            message: "Decoded base64 data is not valid UTF-8".to_string(),
+            suggestion: None,
+            location: None,
+        })
+}
+
+/// Hex digest of a string, `sha1` by default as in Ansible
+fn builtin_hash(args: Vec<Value>) -> Result<Value, NexusError> {
+    require_args("hash", &args, 1)?;
+    let data = text_value(&args[0]);
+    let algorithm = match args.get(1) {
+        Some(_) => string_arg("hash", &args, 1)?.to_lowercase(),
+        None => "sha1".to_string(),
+    };
+
+    let digest = match algorithm.as_str() {
+        "md5" => MessageDigest::md5(),
+        "sha1" => MessageDigest::sha1(),
+        "sha224" => MessageDigest::sha224(),
+        "sha256" => MessageDigest::sha256(),
+        "sha384" => MessageDigest::sha384(),
+        "sha512" => MessageDigest::sha512(),
+        _ => {
+            return Err(NexusError::Runtime {
+                function: Some("hash".to_string()),
+                message: format!("Unsupported hash algorithm: {}", algorithm),
+                suggestion: Some(
+                    "Supported algorithms: md5, sha1, sha224, sha256, sha384, sha512".to_string(),
+                ),
+                location: None,
+            })
+        }
+    };
+
+    let bytes = openssl::hash::hash(digest, data.as_bytes()).map_err(|e| NexusError::Runtime {
+        function: Some("hash".to_string()),
+        message: format!("Failed to compute {} digest: {}", algorithm, e),
+        suggestion: None,
+        location: None,
+    })?;
+    Ok(Value::String(
+        bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    ))
+}
+
+/// crypt(3)-compatible password hash for /etc/shadow, e.g. `$6$salt$...`
+fn builtin_password_hash(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("password_hash", &args, 1)?;
+    let password = text_value(&args[0]);
+    let scheme_name = match args.get(1) {
+        Some(_) => string_arg("password_hash", &args, 1)?,
+        None => "sha512",
+    };
+    let scheme = CryptScheme::from_name(scheme_name).ok_or_else(|| NexusError::Runtime {
+        function: Some("password_hash".to_string()),
+        message: format!("Unsupported password hash scheme: {}", scheme_name),
+        suggestion: Some("Use 'sha512' or 'sha256'".to_string()),
+        location: None,
+    })?;
+
+    let salt = match args.get(2).or_else(|| kwargs.get("salt")) {
+        None | Some(Value::Null) => crypt::random_salt(),
+        Some(Value::String(s)) => {
+            if let Some(c) = s.chars().find(|c| matches!(c, '$' | ':' | '\n')) {
+                return Err(NexusError::Runtime {
+                    function: Some("password_hash".to_string()),
+                    message: format!("Invalid character {:?} in salt", c),
+                    suggestion: None,
+                    location: None,
+                });
+            }
+            s.clone()
+        }
+        Some(other) => return Err(arg_type_error("password_hash", 2, "string", other)),
+    };
+
+    let rounds = match args.get(3).or_else(|| kwargs.get("rounds")) {
+        None | Some(Value::Null) => crypt::DEFAULT_ROUNDS,
+        Some(v) => u32::try_from(get_int(v)?)
+            .map_err(|_| arg_type_error("password_hash", 3, "positive integer", v))?,
+    };
+
+    Ok(Value::String(crypt::sha_crypt(
+        scheme, &password, &salt, rounds,
+    )))
+}
+
+// Dict conversion functions
+
+/// The `key_name`/`value_name` kwargs of dict2items and items2dict
+fn item_field_names(
+    func: &str,
+    kwargs: &HashMap<String, Value>,
+) -> Result<(String, String), NexusError> {
+    let field = |name: &str, default: &str| match kwargs.get(name) {
+        None => Ok(default.to_string()),
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(other) => Err(NexusError::Runtime {
+            function: Some(func.to_string()),
+            message: format!("{} must be a string, got {:?}", name, other),
+            suggestion: None,
+            location: None,
+        }),
+    };
+    Ok((field("key_name", "key")?, field("value_name", "value")?))
+}
+
+/// `{a: 1}` to `[{key: a, value: 1}]`, sorted by key so loops run in a stable order
+fn builtin_dict2items(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("dict2items", &args, 1)?;
+    let dict = match &args[0] {
+        Value::Dict(d) => d,
+        other => return Err(arg_type_error("dict2items", 0, "dict", other)),
+    };
+    let (key_name, value_name) = item_field_names("dict2items", &kwargs)?;
+
+    let mut keys: Vec<&String> = dict.keys().collect();
+    keys.sort();
+    Ok(Value::List(
+        keys.into_iter()
+            .map(|k| {
+                let mut item = HashMap::new();
+                item.insert(key_name.clone(), Value::String(k.clone()));
+                item.insert(value_name.clone(), dict[k].clone());
+                Value::Dict(item)
+            })
+            .collect(),
+    ))
+}
+
+/// The inverse of dict2items: `[{key: a, value: 1}]` to `{a: 1}`
+fn builtin_items2dict(
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, NexusError> {
+    require_args("items2dict", &args, 1)?;
+    let list = match &args[0] {
+        Value::List(l) => l,
+        other => return Err(arg_type_error("items2dict", 0, "list", other)),
+    };
+    let (key_name, value_name) = item_field_names("items2dict", &kwargs)?;
+
+    let mut dict = HashMap::new();
+    for item in list {
+        let fields = match item {
+            Value::Dict(d) => d,
+            other => {
+                return Err(NexusError::Runtime {
+                    function: Some("items2dict".to_string()),
+                    message: format!("items2dict expects a list of dicts, got {:?}", other),
+                    suggestion: None,
+                    location: None,
+                })
+            }
+        };
+        let (Some(key), Some(value)) = (fields.get(&key_name), fields.get(&value_name)) else {
+            return Err(NexusError::Runtime {
+                function: Some("items2dict".to_string()),
+                message: format!(
+                    "Item is missing '{}' or '{}': {:?}",
+                    key_name, value_name, fields
+                ),
+                suggestion: Some(
+                    "Set key_name/value_name to the fields used in the list".to_string(),
+                ),
+                location: None,
+            });
+        };
+        dict.insert(text_value(key), value.clone());
+    }
+    Ok(Value::Dict(dict))
+}
+
 // String methods
 
 fn call_string_method(s: &str, method: &str, args: Vec<Value>) -> Result<Value, NexusError> {
@@ -670,6 +1441,7 @@ fn call_string_method(s: &str, method: &str, args: Vec<Value>) -> Result<Value,
                 function: Some("join".to_string()),
                 message: "join requires a list argument".to_string(),
                 suggestion: None,
+                location: None,
             }),
         },
         "replace" => {
@@ -678,6 +1450,7 @@ fn call_string_method(s: &str, method: &str, args: Vec<Value>) -> Result<Value,
                     function: Some("replace".to_string()),
                     message: "replace requires 2 arguments".to_string(),
                     suggestion: None,
+                    location: None,
                 });
             }
             let old = args[0].to_string();
@@ -707,6 +1480,7 @@ fn call_string_method(s: &str, method: &str, args: Vec<Value>) -> Result<Value,
             function: Some(method.to_string()),
             message: format!("Unknown string method: {}", method),
             suggestion: None,
+            location: None,
         }),
     }
 }
@@ -734,6 +1508,7 @@ fn call_list_method(l: &[Value], method: &str, args: Vec<Value>) -> Result<Value
                 function: Some("index".to_string()),
                 message: "index requires an argument".to_string(),
                 suggestion: None,
+                location: None,
             })?;
             for (i, v) in l.iter().enumerate() {
                 if v.to_string() == item.to_string() {
@@ -747,6 +1522,7 @@ fn call_list_method(l: &[Value], method: &str, args: Vec<Value>) -> Result<Value
                 function: Some("count".to_string()),
                 message: "count requires an argument".to_string(),
                 suggestion: None,
+                location: None,
             })?;
             let count = l
                 .iter()
@@ -767,6 +1543,7 @@ fn call_list_method(l: &[Value], method: &str, args: Vec<Value>) -> Result<Value
             function: Some(method.to_string()),
             message: format!("Unknown list method: {}", method),
             suggestion: None,
+            location: None,
         }),
     }
 }
@@ -807,6 +1584,7 @@ fn call_dict_method(
             function: Some(method.to_string()),
             message: format!("Unknown dict method: {}", method),
             suggestion: None,
+            location: None,
         }),
     }
 }
@@ -824,6 +1602,7 @@ fn require_args(func: &str, args: &[Value], expected: usize) -> Result<(), Nexus
                 args.len()
             ),
             suggestion: None,
+            location: None,
         })
     } else {
         Ok(())
@@ -838,6 +1617,7 @@ fn arg_type_error(func: &str, arg_idx: usize, expected: &str, got: &Value) -> Ne
             arg_idx, func, expected, got
         ),
         suggestion: None,
+        location: None,
     }
 }
 
@@ -846,6 +1626,7 @@ fn filter_type_error(filter: &str, expected: &str, got: &Value) -> NexusError {
         function: None,
         message: format!("Filter '{}' requires {}, got {:?}", filter, expected, got),
         suggestion: None,
+        location: None,
     }
 }
 
@@ -856,6 +1637,7 @@ fn get_int(v: &Value) -> Result<i64, NexusError> {
             function: None,
             message: format!("Expected int, got {:?}", v),
             suggestion: None,
+            location: None,
         }),
     }
 }
@@ -868,3 +1650,332 @@ fn compare_for_sort(a: &Value, b: &Value) -> std::cmp::Ordering {
         _ => std::cmp::Ordering::Equal,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &str) -> Value {
+        Value::String(v.to_string())
+    }
+
+    #[test]
+    fn test_regex_replace_backreferences() {
+        let result = call_builtin(
+            "regex_replace",
+            vec![s("host-01.example.com"), s(r"^(\w+)-(\d+)"), s(r"\2-\1")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, s("01-host.example.com"));
+
+        let result = call_builtin(
+            "regex_replace",
+            vec![
+                s("key=value"),
+                s(r"(?P<k>\w+)=(?P<v>\w+)"),
+                s(r"\g<v>: $\g<k>"),
+            ],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, s("value: $key"));
+    }
+
+    #[test]
+    fn test_regex_replace_ignorecase() {
+        let mut kwargs = HashMap::new();
+        kwargs.insert("ignorecase".to_string(), Value::Bool(true));
+        let result = call_builtin(
+            "regex_replace",
+            vec![s("Foo foo"), s("foo"), s("bar")],
+            kwargs,
+        )
+        .unwrap();
+        assert_eq!(result, s("bar bar"));
+
+        let result = call_builtin(
+            "regex_replace",
+            vec![s("Foo foo"), s("foo"), s("bar"), s("i")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, s("bar bar"));
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let result = call_builtin(
+            "regex_search",
+            vec![s("nginx version: 1.24.0"), s(r"\d+\.\d+")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, s("1.24"));
+
+        let result =
+            call_builtin("regex_search", vec![s("nginx"), s(r"\d+")], HashMap::new()).unwrap();
+        assert_eq!(result, Value::Null);
+
+        let result = call_builtin(
+            "regex_search",
+            vec![s("user=admin"), s(r"(\w+)=(\w+)"), s(r"\2")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, Value::List(vec![s("admin")]));
+    }
+
+    #[test]
+    fn test_regex_findall() {
+        let result = call_builtin(
+            "regex_findall",
+            vec![s("a1 B2 c3"), s(r"[a-z]\d")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, Value::List(vec![s("a1"), s("c3")]));
+
+        let result = call_builtin(
+            "regex_findall",
+            vec![s("a1 B2 c3"), s(r"([a-z])\d"), s("i")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(result, Value::List(vec![s("a"), s("B"), s("c")]));
+
+        let result = call_builtin(
+            "regex_findall",
+            vec![s("a=1,b=2"), s(r"(\w)=(\d)")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            Value::List(vec![
+                Value::List(vec![s("a"), s("1")]),
+                Value::List(vec![s("b"), s("2")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_regex_invalid_pattern() {
+        let err = call_builtin("regex_search", vec![s("x"), s("(")], HashMap::new()).unwrap_err();
+        assert!(err.to_string().contains("Invalid regex"));
+    }
+
+    #[test]
+    fn test_b64_round_trip() {
+        let encoded = call_builtin("b64encode", vec![s("user:pa55")], HashMap::new()).unwrap();
+        assert_eq!(encoded, s("dXNlcjpwYTU1"));
+        let decoded = call_builtin("b64decode", vec![encoded], HashMap::new()).unwrap();
+        assert_eq!(decoded, s("user:pa55"));
+        assert!(call_builtin("b64decode", vec![s("not base64!")], HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let sha1 = call_builtin("hash", vec![s("abc")], HashMap::new()).unwrap();
+        assert_eq!(sha1, s("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        let md5 = call_builtin("hash", vec![s("abc"), s("md5")], HashMap::new()).unwrap();
+        assert_eq!(md5, s("900150983cd24fb0d6963f7d28e17f72"));
+        assert!(call_builtin("hash", vec![s("abc"), s("crc32")], HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let hashed = call_builtin(
+            "password_hash",
+            vec![s("Hello world!"), s("sha512"), s("saltstring")],
+            HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            hashed,
+            s("$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1")
+        );
+
+        // A random salt is generated when none is given
+        let Value::String(random) =
+            call_builtin("password_hash", vec![s("secret")], HashMap::new()).unwrap()
+        else {
+            panic!("expected a string");
+        };
+        assert!(random.starts_with("$6$"));
+        assert_eq!(random.split('$').count(), 4);
+
+        assert!(call_builtin(
+            "password_hash",
+            vec![s("secret"), s("sha512"), s("bad$salt")],
+            HashMap::new()
+        )
+        .is_err());
+    }
+
+    fn users_ctx() -> ExecutionContext {
+        let user = |name: &str, active: bool, uid: i64| {
+            let mut map = HashMap::new();
+            map.insert("name".to_string(), s(name));
+            map.insert("active".to_string(), Value::Bool(active));
+            map.insert("uid".to_string(), Value::Int(uid));
+            Value::Dict(map)
+        };
+        let mut vars = HashMap::new();
+        vars.insert(
+            "users".to_string(),
+            Value::List(vec![
+                user("alice", true, 1000),
+                user("bob", false, 1001),
+                user("carol", true, 1002),
+            ]),
+        );
+        vars.insert("min_uid".to_string(), Value::Int(1001));
+        ExecutionContext::new(
+            std::sync::Arc::new(crate::inventory::Host::new("localhost")),
+            vars,
+        )
+    }
+
+    fn eval(expr: &str, ctx: &ExecutionContext) -> Value {
+        let expr = crate::parser::expressions::parse_expression(expr).unwrap();
+        super::super::evaluate_expression(&expr, ctx).unwrap()
+    }
+
+    fn names(list: &[&str]) -> Value {
+        Value::List(list.iter().map(|n| s(n)).collect())
+    }
+
+    #[test]
+    fn test_selectattr_and_map_attribute() {
+        let ctx = users_ctx();
+        assert_eq!(
+            eval("users | selectattr('active') | map(attribute='name')", &ctx),
+            names(&["alice", "carol"])
+        );
+        assert_eq!(
+            eval(
+                "users | selectattr('name', 'equalto', 'bob') | map(attribute='uid')",
+                &ctx
+            ),
+            Value::List(vec![Value::Int(1001)])
+        );
+        assert_eq!(
+            eval(
+                "users | rejectattr('uid', 'lt', 1001) | map(attribute='name')",
+                &ctx
+            ),
+            names(&["bob", "carol"])
+        );
+        assert_eq!(
+            eval(
+                "users | map(attribute='shell', default='/bin/sh') | first",
+                &ctx
+            ),
+            s("/bin/sh")
+        );
+    }
+
+    #[test]
+    fn test_lambdas_capture_context() {
+        let ctx = users_ctx();
+        assert_eq!(
+            eval(
+                "users | select(u => u.uid >= min_uid) | map(u => u.name | upper)",
+                &ctx
+            ),
+            names(&["BOB", "CAROL"])
+        );
+        assert_eq!(
+            eval("users | reject(u => u.active) | map(u => u.name)", &ctx),
+            names(&["bob"])
+        );
+        assert_eq!(
+            eval("users | filter(x => x.active) | length", &ctx),
+            Value::Int(2)
+        );
+        // Binding the parameter does not leak into the enclosing scope
+        assert!(ctx.get_var("u").is_none());
+    }
+
+    #[test]
+    fn test_select_with_tests_and_map_filter_name() {
+        let ctx = users_ctx();
+        assert_eq!(
+            eval(
+                "users | map(attribute='name') | select('match', '[ab]') | map('upper')",
+                &ctx
+            ),
+            names(&["ALICE", "BOB"])
+        );
+        assert_eq!(
+            eval(
+                "users | map(attribute='name') | map('regex_replace', '^(.)', '\\\\1:')",
+                &ctx
+            ),
+            names(&["a:lice", "b:ob", "c:arol"])
+        );
+        assert_eq!(
+            eval("['a1', 'b', 'c22'] | select('search', '\\d+$')", &ctx),
+            names(&["a1", "c22"])
+        );
+        assert_eq!(
+            eval("[1, 2, 3, 4] | reject('even')", &ctx),
+            Value::List(vec![Value::Int(1), Value::Int(3)])
+        );
+    }
+
+    #[test]
+    fn test_from_template_filter() {
+        let ctx = users_ctx();
+        assert_eq!(
+            eval(
+                "'{% for u in users %}{{ u.name }} {% endfor %}(min {{ min_uid }})' | from_template",
+                &ctx
+            ),
+            Value::String("alice bob carol (min 1001)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dict2items_and_items2dict() {
+        let mut vars = HashMap::new();
+        vars.insert("b".to_string(), Value::Int(2));
+        vars.insert("a".to_string(), Value::Int(1));
+        let dict = Value::Dict(vars);
+
+        let items = call_builtin("dict2items", vec![dict.clone()], HashMap::new()).unwrap();
+        let Value::List(ref list) = items else {
+            panic!("expected a list, got {:?}", items);
+        };
+        let keys: Vec<Value> = list
+            .iter()
+            .map(|i| lookup_attribute(i, "key").unwrap())
+            .collect();
+        assert_eq!(keys, vec![s("a"), s("b")]);
+        assert_eq!(
+            call_builtin("items2dict", vec![items], HashMap::new()).unwrap(),
+            dict
+        );
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("key_name".to_string(), s("name"));
+        kwargs.insert("value_name".to_string(), s("uid"));
+        let renamed = call_builtin("dict2items", vec![dict.clone()], kwargs.clone()).unwrap();
+        assert_eq!(
+            call_builtin("items2dict", vec![renamed], kwargs).unwrap(),
+            dict
+        );
+
+        assert!(call_builtin(
+            "items2dict",
+            vec![Value::List(vec![Value::Dict(HashMap::new())])],
+            HashMap::new()
+        )
+        .is_err());
+        assert_eq!(
+            eval("{'x': 1}.dict2items() | map(attribute='key')", &users_ctx()),
+            names(&["x"])
+        );
+    }
+}
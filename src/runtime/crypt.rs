@@ -0,0 +1,231 @@
+// SHA-crypt password hashing ($5$ / $6$), compatible with glibc crypt(3)
+//
+// Implements the algorithm from https://www.akkadia.org/drepper/SHA-crypt.txt so
+// hashes can be placed directly in /etc/shadow.
+
+use rand::Rng;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Rounds used when none are requested; omitted from the output like crypt(3)
+pub const DEFAULT_ROUNDS: u32 = 5000;
+const MIN_ROUNDS: u32 = 1000;
+const MAX_ROUNDS: u32 = 999_999_999;
+const MAX_SALT_LEN: usize = 16;
+
+const CRYPT_ALPHABET: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Byte order used when encoding a SHA-512 digest
+const SHA512_ORDER: [(usize, usize, usize); 21] = [
+    (0, 21, 42),
+    (22, 43, 1),
+    (44, 2, 23),
+    (3, 24, 45),
+    (25, 46, 4),
+    (47, 5, 26),
+    (6, 27, 48),
+    (28, 49, 7),
+    (50, 8, 29),
+    (9, 30, 51),
+    (31, 52, 10),
+    (53, 11, 32),
+    (12, 33, 54),
+    (34, 55, 13),
+    (56, 14, 35),
+    (15, 36, 57),
+    (37, 58, 16),
+    (59, 17, 38),
+    (18, 39, 60),
+    (40, 61, 19),
+    (62, 20, 41),
+];
+
+/// Byte order used when encoding a SHA-256 digest
+const SHA256_ORDER: [(usize, usize, usize); 10] = [
+    (0, 10, 20),
+    (21, 1, 11),
+    (12, 22, 2),
+    (3, 13, 23),
+    (24, 4, 14),
+    (15, 25, 5),
+    (6, 16, 26),
+    (27, 7, 17),
+    (18, 28, 8),
+    (9, 19, 29),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptScheme {
+    Sha256,
+    Sha512,
+}
+
+impl CryptScheme {
+    /// Parse a scheme name as accepted by Ansible's `password_hash`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha512" | "sha512_crypt" => Some(CryptScheme::Sha512),
+            "sha256" | "sha256_crypt" => Some(CryptScheme::Sha256),
+            _ => None,
+        }
+    }
+
+    fn magic(self) -> &'static str {
+        match self {
+            CryptScheme::Sha256 => "$5$",
+            CryptScheme::Sha512 => "$6$",
+        }
+    }
+}
+
+/// Hash `password` with the given scheme, salt and rounds
+pub fn sha_crypt(scheme: CryptScheme, password: &str, salt: &str, rounds: u32) -> String {
+    let salt = match salt.char_indices().nth(MAX_SALT_LEN) {
+        Some((end, _)) => &salt[..end],
+        None => salt,
+    };
+    let rounds = rounds.clamp(MIN_ROUNDS, MAX_ROUNDS);
+
+    let digest = match scheme {
+        CryptScheme::Sha256 => {
+            sha_crypt_digest::<Sha256>(password.as_bytes(), salt.as_bytes(), rounds)
+        }
+        CryptScheme::Sha512 => {
+            sha_crypt_digest::<Sha512>(password.as_bytes(), salt.as_bytes(), rounds)
+        }
+    };
+
+    let mut out = String::from(scheme.magic());
+    if rounds != DEFAULT_ROUNDS {
+        out.push_str(&format!("rounds={}$", rounds));
+    }
+    out.push_str(salt);
+    out.push('$');
+
+    match scheme {
+        CryptScheme::Sha256 => {
+            for (a, b, c) in SHA256_ORDER {
+                encode_24bit(&mut out, digest[a], digest[b], digest[c], 4);
+            }
+            encode_24bit(&mut out, 0, digest[31], digest[30], 3);
+        }
+        CryptScheme::Sha512 => {
+            for (a, b, c) in SHA512_ORDER {
+                encode_24bit(&mut out, digest[a], digest[b], digest[c], 4);
+            }
+            encode_24bit(&mut out, 0, 0, digest[63], 2);
+        }
+    }
+
+    out
+}
+
+/// A random salt drawn from the crypt alphabet
+pub fn random_salt() -> String {
+    let mut rng = rand::thread_rng();
+    (0..MAX_SALT_LEN)
+        .map(|_| CRYPT_ALPHABET[rng.gen_range(0..CRYPT_ALPHABET.len())] as char)
+        .collect()
+}
+
+fn sha_crypt_digest<D: Digest>(password: &[u8], salt: &[u8], rounds: u32) -> Vec<u8> {
+    let digest_b = D::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+
+    let mut ctx_a = D::new().chain_update(password).chain_update(salt);
+    ctx_a.update(repeat_to(&digest_b, password.len()));
+    let mut len = password.len();
+    while len > 0 {
+        if len & 1 == 1 {
+            ctx_a.update(&digest_b);
+        } else {
+            ctx_a.update(password);
+        }
+        len >>= 1;
+    }
+    let digest_a = ctx_a.finalize();
+
+    let mut ctx_dp = D::new();
+    for _ in 0..password.len() {
+        ctx_dp.update(password);
+    }
+    let p = repeat_to(&ctx_dp.finalize(), password.len());
+
+    let mut ctx_ds = D::new();
+    for _ in 0..16 + digest_a[0] as usize {
+        ctx_ds.update(salt);
+    }
+    let s = repeat_to(&ctx_ds.finalize(), salt.len());
+
+    let mut c = digest_a.to_vec();
+    for i in 0..rounds {
+        let mut ctx = D::new();
+        if i % 2 == 1 {
+            ctx.update(&p);
+        } else {
+            ctx.update(&c);
+        }
+        if i % 3 != 0 {
+            ctx.update(&s);
+        }
+        if i % 7 != 0 {
+            ctx.update(&p);
+        }
+        if i % 2 == 1 {
+            ctx.update(&c);
+        } else {
+            ctx.update(&p);
+        }
+        c = ctx.finalize().to_vec();
+    }
+
+    c
+}
+
+/// `bytes` repeated (and truncated) to exactly `len` bytes
+fn repeat_to(bytes: &[u8], len: usize) -> Vec<u8> {
+    bytes.iter().copied().cycle().take(len).collect()
+}
+
+fn encode_24bit(out: &mut String, b2: u8, b1: u8, b0: u8, n: usize) {
+    let mut w = ((b2 as u32) << 16) | ((b1 as u32) << 8) | b0 as u32;
+    for _ in 0..n {
+        out.push(CRYPT_ALPHABET[(w & 0x3f) as usize] as char);
+        w >>= 6;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha_crypt_reference_vectors() {
+        assert_eq!(
+            sha_crypt(CryptScheme::Sha512, "Hello world!", "saltstring", DEFAULT_ROUNDS),
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1"
+        );
+        assert_eq!(
+            sha_crypt(
+                CryptScheme::Sha256,
+                "Hello world!",
+                "saltstring",
+                DEFAULT_ROUNDS
+            ),
+            "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"
+        );
+        assert_eq!(
+            sha_crypt(CryptScheme::Sha512, "Hello world!", "saltstringsaltstring", 10000),
+            "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v."
+        );
+    }
+
+    #[test]
+    fn test_random_salt() {
+        let salt = random_salt();
+        assert_eq!(salt.len(), MAX_SALT_LEN);
+        assert!(salt.bytes().all(|b| CRYPT_ALPHABET.contains(&b)));
+    }
+}
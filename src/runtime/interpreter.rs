@@ -53,6 +53,7 @@ impl Interpreter {
                     "Check function name and ensure it's defined in the functions block"
                         .to_string(),
                 ),
+                location: None,
             })?;
 
         // Create local scope with parameters
@@ -69,6 +70,7 @@ impl Interpreter {
                     function: Some(name.to_string()),
                     message: format!("Missing required argument: {}", param.name),
                     suggestion: None,
+                    location: None,
                 });
             };
             local_vars.insert(param.name.clone(), value);
@@ -151,6 +153,7 @@ impl Interpreter {
                             function: None,
                             message: "Cannot iterate over non-iterable value".to_string(),
                             suggestion: None,
+                            location: None,
                         })
                     }
                 };
@@ -270,6 +273,7 @@ mod tests {
     use crate::inventory::Host;
     use std::sync::Arc;
 
+    #[allow(dead_code)]
     fn create_test_context() -> ExecutionContext {
         let host = Host::new("test-host");
         ExecutionContext::new(Arc::new(host), HashMap::new())
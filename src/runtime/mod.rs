@@ -1,6 +1,7 @@
 // Runtime module - expression evaluation and function execution
 
 mod builtins;
+mod crypt;
 mod interpreter;
 mod types;
 
@@ -27,6 +28,7 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
             function: None,
             message: format!("Variable not found: {}", path.join(".")),
             suggestion: Some("Check variable name and ensure it's defined".to_string()),
+            location: None,
         }),
 
         Expression::InterpolatedString(parts) => {
@@ -101,6 +103,7 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
                             list.len()
                         ),
                         suggestion: None,
+                        location: None,
                     })
                 }
                 (Value::Dict(map), Value::String(key)) => {
@@ -108,6 +111,7 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
                         function: None,
                         message: format!("Key '{}' not found in dict", key),
                         suggestion: None,
+                        location: None,
                     })
                 }
                 (Value::String(s), Value::Int(i)) => {
@@ -123,12 +127,14 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
                             function: None,
                             message: format!("Index {} out of bounds for string", i),
                             suggestion: None,
+                            location: None,
                         })
                 }
                 _ => Err(NexusError::Runtime {
                     function: None,
                     message: format!("Cannot index {:?} with {:?}", obj_val, idx_val),
                     suggestion: None,
+                    location: None,
                 }),
             }
         }
@@ -141,11 +147,13 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
                     function: None,
                     message: format!("Attribute '{}' not found", attr),
                     suggestion: None,
+                    location: None,
                 }),
                 _ => Err(NexusError::Runtime {
                     function: None,
                     message: format!("Cannot access attribute '{}' on {:?}", attr, obj_val),
                     suggestion: None,
+                    location: None,
                 }),
             }
         }
@@ -173,19 +181,39 @@ pub fn evaluate_expression(expr: &Expression, ctx: &ExecutionContext) -> Result<
         Expression::Filter {
             input,
             filter_name,
-            predicate,
+            args,
+            kwargs,
         } => {
             let input_val = evaluate_expression(input, ctx)?;
-            apply_filter(&input_val, filter_name, predicate.as_deref(), ctx)
+            // Lambdas are passed through unevaluated and called per item by the filter
+            let evaluated_args: Result<Vec<_>, _> = args
+                .iter()
+                .map(|a| match a {
+                    Expression::Lambda { params, body } => Ok(FilterArg::Lambda { params, body }),
+                    other => evaluate_expression(other, ctx).map(FilterArg::Value),
+                })
+                .collect();
+            let evaluated_kwargs: Result<HashMap<_, _>, _> = kwargs
+                .iter()
+                .map(|(k, v)| evaluate_expression(v, ctx).map(|val| (k.clone(), val)))
+                .collect();
+
+            apply_filter(
+                &input_val,
+                filter_name,
+                &evaluated_args?,
+                &evaluated_kwargs?,
+                ctx,
+            )
         }
 
-        Expression::Lambda { params: _, body: _ } => {
-            // Lambdas are evaluated when called, not when defined
-            // For now, we'll return null as we don't have closures
+        Expression::Lambda { .. } => {
+            // Lambdas are called by the filter they are passed to, never evaluated directly
             Err(NexusError::Runtime {
                 function: None,
-                message: "Lambda expressions not fully supported yet".to_string(),
-                suggestion: None,
+                message: "A lambda can only be used as a filter argument".to_string(),
+                suggestion: Some("Example: ${items | map(x => x.name)}".to_string()),
+                location: None,
             })
         }
 
@@ -248,6 +276,7 @@ fn evaluate_binary_op(
                         function: None,
                         message: "Division by zero".to_string(),
                         suggestion: None,
+                        location: None,
                     })
                 } else {
                     Ok(Value::Int(a / b))
@@ -311,6 +340,7 @@ fn evaluate_unary_op(op: &UnaryOperator, val: &Value) -> Result<Value, NexusErro
                 function: None,
                 message: format!("Cannot negate {:?}", val),
                 suggestion: None,
+                location: None,
             }),
         },
     }
@@ -360,6 +390,7 @@ where
                 function: None,
                 message: format!("Cannot compare {:?} and {:?}", left, right),
                 suggestion: None,
+                location: None,
             })
         }
     };
@@ -377,6 +408,7 @@ fn type_error(op: &str, left: &Value, right: &Value) -> NexusError {
             type_name(right)
         ),
         suggestion: None,
+        location: None,
     }
 }
 
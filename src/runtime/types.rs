@@ -112,6 +112,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_type_of() {
         assert_eq!(type_of(&Value::Null), "null");
         assert_eq!(type_of(&Value::Bool(true)), "bool");
@@ -2,6 +2,9 @@
 //
 // Format: $NEXUS_VAULT;1.0;AES256
 //         <base64-encoded-encrypted-content>
+//
+// Content encrypted with a vault ID uses version 1.1, which adds the ID as a label:
+//         $NEXUS_VAULT;1.1;AES256;<vault-id>
 
 use super::{VaultCipher, VaultError};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
@@ -17,6 +20,8 @@ pub const VAULT_CIPHER: &str = "AES256";
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VaultFormat {
     V1_0,
+    /// Adds a vault ID label to the header
+    V1_1,
 }
 
 impl std::str::FromStr for VaultFormat {
@@ -25,6 +30,7 @@ impl std::str::FromStr for VaultFormat {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "1.0" => Ok(VaultFormat::V1_0),
+            "1.1" => Ok(VaultFormat::V1_1),
             _ => Err(()),
         }
     }
@@ -34,6 +40,7 @@ impl VaultFormat {
     pub fn as_str(&self) -> &'static str {
         match self {
             VaultFormat::V1_0 => "1.0",
+            VaultFormat::V1_1 => "1.1",
         }
     }
 }
@@ -42,6 +49,8 @@ impl VaultFormat {
 #[derive(Debug)]
 pub struct VaultFile {
     pub format: VaultFormat,
+    /// Vault ID the content was encrypted with (1.1 format only)
+    pub label: Option<String>,
     pub salt: Vec<u8>,
     pub nonce: Vec<u8>,
     pub ciphertext: Vec<u8>,
@@ -69,12 +78,20 @@ impl VaultFile {
 
         Ok(VaultFile {
             format: VaultFormat::V1_0,
+            label: None,
             salt,
             nonce,
             ciphertext,
         })
     }
 
+    /// Record the vault ID used for encryption in the header
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.format = VaultFormat::V1_1;
+        self.label = Some(label.into());
+        self
+    }
+
     /// Decrypt the vault file
     pub fn decrypt(&self, password: &str) -> Result<String, VaultError> {
         let cipher = VaultCipher::with_salt(password, &self.salt)?;
@@ -90,10 +107,25 @@ impl VaultFile {
         Self::parse(&content)
     }
 
-    /// Write vault file to disk
+    /// Write vault file to disk.
+    /// The content goes to a temporary file next to `path` that is then renamed over it,
+    /// so an interrupted write never leaves a truncated vault file.
     pub fn write_to_file(&self, path: &Path) -> Result<(), VaultError> {
-        let content = self.format_as_string();
-        std::fs::write(path, content)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let tmp = path.with_file_name(format!(".{}.nexus-tmp", name));
+
+        std::fs::write(&tmp, self.format_as_string())?;
+        // Keep the original file's permissions (vault files are often 0600)
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&tmp, metadata.permissions())?;
+        }
+        if let Err(e) = std::fs::rename(&tmp, path) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e.into());
+        }
         Ok(())
     }
 
@@ -105,11 +137,11 @@ impl VaultFile {
             return Err(VaultError::InvalidFormat("Empty vault file".to_string()));
         }
 
-        // Parse header: $NEXUS_VAULT;1.0;AES256
-        let header = lines[0];
+        // Parse header: $NEXUS_VAULT;1.0;AES256 or $NEXUS_VAULT;1.1;AES256;<vault-id>
+        let header = lines[0].trim();
         let parts: Vec<&str> = header.split(';').collect();
 
-        if parts.len() != 3 {
+        if parts.len() != 3 && parts.len() != 4 {
             return Err(VaultError::InvalidFormat(format!(
                 "Invalid header format: {}",
                 header
@@ -134,6 +166,17 @@ impl VaultFile {
             )));
         }
 
+        let label = match (format, parts.get(3)) {
+            (VaultFormat::V1_0, None) => None,
+            (VaultFormat::V1_1, Some(label)) if !label.is_empty() => Some(label.to_string()),
+            _ => {
+                return Err(VaultError::InvalidFormat(format!(
+                    "Invalid header format: {}",
+                    header
+                )))
+            }
+        };
+
         // Get the base64-encoded data (all remaining lines joined)
         let encoded_data = lines[1..].join("");
 
@@ -162,6 +205,7 @@ impl VaultFile {
 
         Ok(VaultFile {
             format,
+            label,
             salt,
             nonce,
             ciphertext,
@@ -180,12 +224,12 @@ impl VaultFile {
         let encoded = BASE64.encode(&combined);
 
         // Split into lines of 80 characters for readability
-        let mut lines = vec![format!(
-            "{};{};{}",
-            VAULT_HEADER,
-            self.format.as_str(),
-            VAULT_CIPHER
-        )];
+        let mut header = format!("{};{};{}", VAULT_HEADER, self.format.as_str(), VAULT_CIPHER);
+        if let Some(label) = &self.label {
+            header.push(';');
+            header.push_str(label);
+        }
+        let mut lines = vec![header];
 
         for chunk in encoded.as_bytes().chunks(80) {
             lines.push(String::from_utf8_lossy(chunk).to_string());
@@ -254,6 +298,24 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
+    #[test]
+    fn test_vault_id_label_round_trip() {
+        let vault = VaultFile::encrypt("secret", "pw")
+            .unwrap()
+            .with_label("prod");
+        let formatted = vault.format_as_string();
+        assert!(formatted.starts_with("$NEXUS_VAULT;1.1;AES256;prod\n"));
+
+        let parsed = VaultFile::parse(&formatted).unwrap();
+        assert_eq!(parsed.format, VaultFormat::V1_1);
+        assert_eq!(parsed.label.as_deref(), Some("prod"));
+        assert_eq!(parsed.decrypt("pw").unwrap(), "secret");
+
+        // 1.1 requires a label and 1.0 must not have one
+        assert!(VaultFile::parse("$NEXUS_VAULT;1.1;AES256\nAAAA").is_err());
+        assert!(VaultFile::parse("$NEXUS_VAULT;1.0;AES256;prod\nAAAA").is_err());
+    }
+
     #[test]
     fn test_wrong_password_fails() {
         let password = "correct";
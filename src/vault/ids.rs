@@ -0,0 +1,187 @@
+// Vault IDs: several labeled passwords, e.g. one for dev and one for prod secrets
+//
+// Content encrypted with a vault ID records the ID in its header, so decryption
+// tries the matching password first and falls back to the others.
+
+use super::{VaultError, VaultFile};
+use zeroize::Zeroizing;
+
+/// Vault ID used for passwords given without one (`--vault-password`, `--ask-vault-pass`)
+pub const DEFAULT_VAULT_ID: &str = "default";
+
+/// Vault passwords keyed by vault ID, in the order they were given
+#[derive(Clone, Default)]
+pub struct VaultSecrets {
+    entries: Vec<(String, Zeroizing<String>)>,
+}
+
+impl VaultSecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A single password under the default vault ID
+    pub fn single(password: impl Into<String>) -> Self {
+        let mut secrets = Self::new();
+        secrets.add(DEFAULT_VAULT_ID, password);
+        secrets
+    }
+
+    /// Add a password for a vault ID, replacing an earlier one with the same ID
+    pub fn add(&mut self, id: impl Into<String>, password: impl Into<String>) {
+        let id = id.into();
+        let password = Zeroizing::new(password.into());
+        match self
+            .entries
+            .iter_mut()
+            .find(|(existing, _)| *existing == id)
+        {
+            Some(entry) => entry.1 = password,
+            None => self.entries.push((id, password)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(id, _)| id.as_str())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(existing, _)| existing == id)
+            .map(|(_, password)| password.as_str())
+    }
+
+    /// Pick the password to encrypt with: the requested vault ID, or the only one given.
+    /// Returns the label to record in the header (none for the default ID).
+    pub fn encryption_key(&self, id: Option<&str>) -> Result<(Option<&str>, &str), VaultError> {
+        let (id, password) = match id {
+            Some(id) => self
+                .entries
+                .iter()
+                .find(|(existing, _)| existing == id)
+                .ok_or_else(|| {
+                    VaultError::VaultIdError(format!("no password given for vault ID '{}'", id))
+                })?,
+            None => match self.entries.as_slice() {
+                [entry] => entry,
+                [] => return Err(VaultError::InvalidPassword),
+                _ => {
+                    return Err(VaultError::VaultIdError(
+                        "several vault IDs given; choose one with --encrypt-vault-id".to_string(),
+                    ))
+                }
+            },
+        };
+        let label = (id != DEFAULT_VAULT_ID).then_some(id.as_str());
+        Ok((label, password.as_str()))
+    }
+
+    /// Encrypt with the password chosen by `encryption_key`, labeling the result
+    pub fn encrypt(&self, plaintext: &str, id: Option<&str>) -> Result<VaultFile, VaultError> {
+        let (label, password) = self.encryption_key(id)?;
+        let vault_file = VaultFile::encrypt(plaintext, password)?;
+        Ok(match label {
+            Some(label) => vault_file.with_label(label),
+            None => vault_file,
+        })
+    }
+
+    /// Decrypt with the password matching the content's vault ID first, then the others
+    pub fn decrypt(&self, vault_file: &VaultFile) -> Result<String, VaultError> {
+        if self.is_empty() {
+            return Err(VaultError::InvalidPassword);
+        }
+
+        let label = vault_file.label.as_deref();
+        let ordered = self
+            .entries
+            .iter()
+            .filter(|(id, _)| Some(id.as_str()) == label)
+            .chain(
+                self.entries
+                    .iter()
+                    .filter(|(id, _)| Some(id.as_str()) != label),
+            );
+
+        let mut last_error = None;
+        for (_, password) in ordered {
+            match vault_file.decrypt(password) {
+                Ok(plaintext) => return Ok(plaintext),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match (label, last_error) {
+            (Some(label), _) if self.get(label).is_none() => Err(VaultError::VaultIdError(
+                format!("encrypted with vault ID '{}', which was not given", label),
+            )),
+            (_, Some(e)) => Err(e),
+            (_, None) => Err(VaultError::InvalidPassword),
+        }
+    }
+
+    /// Parse and decrypt vault-formatted content
+    pub fn decrypt_str(&self, content: &str) -> Result<String, VaultError> {
+        self.decrypt(&VaultFile::parse(content)?)
+    }
+}
+
+// Never print the passwords themselves
+impl std::fmt::Debug for VaultSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultSecrets")
+            .field("ids", &self.ids().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Split a `--vault-id` value into its ID and password source (`prompt` or a file).
+/// A value without `@` is a source for the default vault ID.
+pub fn parse_vault_id(spec: &str) -> (&str, &str) {
+    match spec.split_once('@') {
+        Some((id, source)) if !id.is_empty() => (id, source),
+        Some((_, source)) => (DEFAULT_VAULT_ID, source),
+        None => (DEFAULT_VAULT_ID, spec),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_ids_pick_matching_password() {
+        let mut secrets = VaultSecrets::new();
+        secrets.add("dev", "dev-pass");
+        secrets.add("prod", "prod-pass");
+
+        assert!(secrets.encrypt("x", None).is_err());
+        let prod = secrets.encrypt("prod secret", Some("prod")).unwrap();
+        assert_eq!(prod.label.as_deref(), Some("prod"));
+        assert_eq!(secrets.decrypt(&prod).unwrap(), "prod secret");
+
+        // Unlabeled content falls back to trying every password
+        let unlabeled = VaultFile::encrypt("dev secret", "dev-pass").unwrap();
+        assert_eq!(secrets.decrypt(&unlabeled).unwrap(), "dev secret");
+
+        let only_dev = {
+            let mut s = VaultSecrets::new();
+            s.add("dev", "dev-pass");
+            s
+        };
+        let err = only_dev.decrypt(&prod).unwrap_err();
+        assert!(err.to_string().contains("vault ID 'prod'"));
+
+        assert_eq!(
+            parse_vault_id("prod@prod-pass.txt"),
+            ("prod", "prod-pass.txt")
+        );
+        assert_eq!(parse_vault_id("dev@prompt"), ("dev", "prompt"));
+        assert_eq!(parse_vault_id("pass.txt"), (DEFAULT_VAULT_ID, "pass.txt"));
+    }
+}
@@ -13,13 +13,15 @@ use argon2::{
 };
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::RngCore;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use zeroize::Zeroizing;
 
 pub mod format;
+pub mod ids;
 
 pub use format::{VaultFile, VaultFormat};
+pub use ids::{parse_vault_id, VaultSecrets, DEFAULT_VAULT_ID};
 
 /// Vault error types
 #[derive(Debug, Error)]
@@ -41,6 +43,9 @@ pub enum VaultError {
 
     #[error("Key derivation failed: {0}")]
     KeyDerivationError(String),
+
+    #[error("Vault ID error: {0}")]
+    VaultIdError(String),
 }
 
 /// Encryption context holds the key and cipher
@@ -167,26 +172,247 @@ pub fn decrypt_string(password: &str, encrypted: &str) -> Result<String, VaultEr
         .map_err(|e| VaultError::DecryptionError(format!("Invalid UTF-8: {}", e)))
 }
 
-/// Encrypt a file
-pub fn encrypt_file(path: &Path, password: &str) -> Result<(), VaultError> {
+/// Encrypt a file with the password for vault ID `id` (see `VaultSecrets::encryption_key`)
+pub fn encrypt_file(
+    path: &Path,
+    secrets: &VaultSecrets,
+    id: Option<&str>,
+) -> Result<(), VaultError> {
     let content = std::fs::read_to_string(path)?;
-    let vault_file = VaultFile::encrypt(&content, password)?;
+    let vault_file = secrets.encrypt(&content, id)?;
     vault_file.write_to_file(path)?;
     Ok(())
 }
 
 /// Decrypt a file
-pub fn decrypt_file(path: &Path, password: &str) -> Result<(), VaultError> {
+pub fn decrypt_file(path: &Path, secrets: &VaultSecrets) -> Result<(), VaultError> {
     let vault_file = VaultFile::read_from_file(path)?;
-    let content = vault_file.decrypt(password)?;
+    let content = secrets.decrypt(&vault_file)?;
     std::fs::write(path, content)?;
     Ok(())
 }
 
 /// View decrypted content without modifying the file
-pub fn view_file(path: &Path, password: &str) -> Result<String, VaultError> {
+pub fn view_file(path: &Path, secrets: &VaultSecrets) -> Result<String, VaultError> {
     let vault_file = VaultFile::read_from_file(path)?;
-    vault_file.decrypt(password)
+    secrets.decrypt(&vault_file)
+}
+
+/// Encrypt a single value as an inline `!vault` YAML scalar.
+/// With a `name` the result is a complete `name: !vault |` entry for a vars file.
+pub fn encrypt_inline(
+    plaintext: &str,
+    secrets: &VaultSecrets,
+    id: Option<&str>,
+    name: Option<&str>,
+) -> Result<String, VaultError> {
+    let vault_file = secrets.encrypt(plaintext, id)?;
+
+    let mut out = match name {
+        Some(name) => format!("{}: !vault |\n", name),
+        None => "!vault |\n".to_string(),
+    };
+    for line in vault_file.format_as_string().lines() {
+        out.push_str("  ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Re-encrypt vault files with a new password, keeping each file's vault ID.
+/// Every file is decrypted before any is written, so a wrong password or a file that
+/// isn't vault-encrypted leaves all of them unchanged.
+pub fn rekey_files(
+    paths: &[PathBuf],
+    old_secrets: &VaultSecrets,
+    new_password: &str,
+) -> Result<(), VaultError> {
+    let mut rekeyed = Vec::with_capacity(paths.len());
+    for path in paths {
+        let content = std::fs::read_to_string(path)?;
+        if !VaultFile::is_vault_format(&content) {
+            return Err(VaultError::InvalidFormat(format!(
+                "{} is not vault-encrypted",
+                path.display()
+            )));
+        }
+        let vault_file = VaultFile::parse(&content)?;
+        let plaintext = Zeroizing::new(
+            old_secrets
+                .decrypt(&vault_file)
+                .map_err(|e| VaultError::DecryptionError(format!("{}: {}", path.display(), e)))?,
+        );
+        let mut new_file = VaultFile::encrypt(&plaintext, new_password)?;
+        if let Some(label) = vault_file.label {
+            new_file = new_file.with_label(label);
+        }
+        rekeyed.push((path, new_file));
+    }
+
+    for (path, vault_file) in rekeyed {
+        vault_file.write_to_file(path)?;
+    }
+    Ok(())
+}
+
+/// Expand paths given on the command line into vault files.
+/// Directories are searched recursively and a `*`/`?` pattern in the file name is matched
+/// against its directory; both only pick up vault-encrypted files. Plain paths are kept
+/// as given so callers can report files that aren't encrypted.
+pub fn find_vault_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, VaultError> {
+    let mut found = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if path.is_dir() {
+            collect_tree_files(path, &mut |p| is_vault_file(p), &mut found)?;
+        } else if name.contains('*') || name.contains('?') {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let pattern = name_pattern(&name)?;
+
+            let mut matches: Vec<PathBuf> = std::fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.is_file()
+                        && p.file_name()
+                            .is_some_and(|n| pattern.is_match(&n.to_string_lossy()))
+                        && is_vault_file(p)
+                })
+                .collect();
+            matches.sort();
+            found.extend(matches);
+        } else {
+            found.push(path.clone());
+        }
+    }
+
+    found.dedup();
+    Ok(found)
+}
+
+/// Files encrypted or decrypted by `encrypt_tree`/`decrypt_tree`, and those skipped
+/// because they already were
+#[derive(Debug, Default)]
+pub struct TreeSummary {
+    pub processed: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Encrypt every file under `dir` in place with the password for vault ID `id`.
+/// Only YAML files are picked up unless `pattern` (`*`/`?` matched against file names)
+/// is given; files that are already vault-encrypted are skipped.
+pub fn encrypt_tree(
+    dir: &Path,
+    secrets: &VaultSecrets,
+    id: Option<&str>,
+    pattern: Option<&str>,
+) -> Result<TreeSummary, VaultError> {
+    let mut summary = TreeSummary::default();
+    for path in find_tree_files(dir, pattern)? {
+        if is_vault_file(&path) {
+            summary.skipped.push(path);
+        } else {
+            encrypt_file(&path, secrets, id)?;
+            summary.processed.push(path);
+        }
+    }
+    Ok(summary)
+}
+
+/// Decrypt every vault file under `dir` in place, picking files like `encrypt_tree`
+/// and skipping those that aren't encrypted. Every file is decrypted before any is
+/// written, so a wrong password leaves all of them unchanged.
+pub fn decrypt_tree(
+    dir: &Path,
+    secrets: &VaultSecrets,
+    pattern: Option<&str>,
+) -> Result<TreeSummary, VaultError> {
+    let mut summary = TreeSummary::default();
+    let mut decrypted = Vec::new();
+    for path in find_tree_files(dir, pattern)? {
+        if !is_vault_file(&path) {
+            summary.skipped.push(path);
+            continue;
+        }
+        let vault_file = VaultFile::read_from_file(&path)?;
+        let plaintext = Zeroizing::new(
+            secrets
+                .decrypt(&vault_file)
+                .map_err(|e| VaultError::DecryptionError(format!("{}: {}", path.display(), e)))?,
+        );
+        decrypted.push((path, plaintext));
+    }
+
+    for (path, plaintext) in decrypted {
+        std::fs::write(&path, plaintext.as_bytes())?;
+        summary.processed.push(path);
+    }
+    Ok(summary)
+}
+
+/// Files under `dir`, recursively and sorted, whose name matches `pattern` or, without
+/// one, that have a `.yml`/`.yaml` extension
+fn find_tree_files(dir: &Path, pattern: Option<&str>) -> Result<Vec<PathBuf>, VaultError> {
+    if !dir.is_dir() {
+        return Err(VaultError::InvalidFormat(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+    let pattern = pattern.map(name_pattern).transpose()?;
+    let mut found = Vec::new();
+    collect_tree_files(
+        dir,
+        &mut |path| match pattern {
+            Some(ref pattern) => path
+                .file_name()
+                .is_some_and(|n| pattern.is_match(&n.to_string_lossy())),
+            None => path
+                .extension()
+                .is_some_and(|ext| ext == "yml" || ext == "yaml"),
+        },
+        &mut found,
+    )?;
+    Ok(found)
+}
+
+fn collect_tree_files(
+    dir: &Path,
+    wanted: &mut dyn FnMut(&Path) -> bool,
+    found: &mut Vec<PathBuf>,
+) -> Result<(), VaultError> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_tree_files(&path, wanted, found)?;
+        } else if path.is_file() && wanted(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// A regex matching whole file names against a `*`/`?` pattern
+fn name_pattern(pattern: &str) -> Result<regex::Regex, VaultError> {
+    let regex = format!(
+        "^{}$",
+        regex::escape(pattern)
+            .replace("\\*", ".*")
+            .replace("\\?", ".")
+    );
+    regex::Regex::new(&regex)
+        .map_err(|e| VaultError::InvalidFormat(format!("Invalid pattern {}: {}", pattern, e)))
 }
 
 /// Check if a file is vault-encrypted
@@ -268,6 +494,80 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_rekey_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let group_vars = dir.path().join("group_vars");
+        std::fs::create_dir(&group_vars).unwrap();
+        let secrets = group_vars.join("secrets.yml");
+        let nested = group_vars.join("web").join("vault.yml");
+        std::fs::create_dir(nested.parent().unwrap()).unwrap();
+        for (path, content) in [(&secrets, "db_password: s3cret"), (&nested, "api_key: abc")] {
+            std::fs::write(path, content).unwrap();
+            encrypt_file(path, &VaultSecrets::single("old"), None).unwrap();
+        }
+        std::fs::write(group_vars.join("plain.yml"), "port: 80").unwrap();
+
+        // Directories and patterns only pick up encrypted files
+        let files = find_vault_files(std::slice::from_ref(&group_vars)).unwrap();
+        assert_eq!(files, vec![secrets.clone(), nested.clone()]);
+        assert_eq!(
+            find_vault_files(&[group_vars.join("*.yml")]).unwrap(),
+            vec![secrets.clone()]
+        );
+
+        // A wrong password or a plain file leaves everything untouched
+        let before = std::fs::read_to_string(&secrets).unwrap();
+        let (old, new) = (VaultSecrets::single("old"), VaultSecrets::single("new"));
+        assert!(rekey_files(&files, &VaultSecrets::single("wrong"), "new").is_err());
+        let with_plain = vec![secrets.clone(), group_vars.join("plain.yml")];
+        assert!(rekey_files(&with_plain, &old, "new").is_err());
+        assert_eq!(std::fs::read_to_string(&secrets).unwrap(), before);
+
+        rekey_files(&files, &old, "new").unwrap();
+        assert_eq!(view_file(&secrets, &new).unwrap(), "db_password: s3cret");
+        assert_eq!(view_file(&nested, &new).unwrap(), "api_key: abc");
+        assert!(view_file(&secrets, &old).is_err());
+        assert!(!group_vars.join(".secrets.yml.nexus-tmp").exists());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let vars = dir.path().join("group_vars");
+        std::fs::create_dir_all(vars.join("web")).unwrap();
+        let db = vars.join("db.yml");
+        let web = vars.join("web").join("vault.yaml");
+        let readme = vars.join("README.md");
+        let env = vars.join("web").join("app.env");
+        std::fs::write(&db, "db_password: s3cret").unwrap();
+        std::fs::write(&web, "api_key: abc").unwrap();
+        std::fs::write(&readme, "notes").unwrap();
+        std::fs::write(&env, "TOKEN=xyz").unwrap();
+        encrypt_file(&web, &VaultSecrets::single("pw"), None).unwrap();
+
+        let secrets = VaultSecrets::single("pw");
+        let summary = encrypt_tree(&vars, &secrets, None, None).unwrap();
+        assert_eq!(summary.processed, vec![db.clone()]);
+        assert_eq!(summary.skipped, vec![web.clone()]);
+        assert!(is_vault_file(&db));
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "notes");
+        assert!(!is_vault_file(&env));
+
+        let summary = encrypt_tree(&vars, &secrets, None, Some("*.env")).unwrap();
+        assert_eq!(summary.processed, vec![env.clone()]);
+
+        // A wrong password leaves every file encrypted
+        assert!(decrypt_tree(&vars, &VaultSecrets::single("wrong"), None).is_err());
+        assert!(is_vault_file(&db) && is_vault_file(&web));
+
+        let summary = decrypt_tree(&vars, &secrets, None).unwrap();
+        assert_eq!(summary.processed, vec![db.clone(), web.clone()]);
+        assert_eq!(std::fs::read_to_string(&db).unwrap(), "db_password: s3cret");
+        assert!(is_vault_file(&env));
+        assert!(encrypt_tree(&db, &secrets, None, None).is_err());
+    }
+
     #[test]
     fn test_vault_cipher() {
         let password = "test123";